@@ -0,0 +1,73 @@
+//! Throughput of `StreamAnalyzer::analyze_file` on a Wikipedia-style dump
+//!
+//! Compare buffered reads with memory mapping by saving a baseline:
+//!
+//! ```text
+//! cargo bench -p ferret --bench analyze_file -- --save-baseline bufread
+//! cargo bench -p ferret --bench analyze_file --features mmap -- --baseline bufread
+//! ```
+//!
+//! The `in_memory` benchmark (`analyze_bytes` on a loaded document) is the
+//! upper bound the mapped path approaches.
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use ferret::analyzer::stream::StreamAnalyzer;
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+const PAGES: usize = 50_000;
+
+/// Write a MediaWiki XML export with `PAGES` pages
+fn write_dump(path: &Path) -> std::io::Result<()> {
+    let mut out = BufWriter::new(fs::File::create(path)?);
+    writeln!(
+        out,
+        r#"<mediawiki xmlns="http://www.mediawiki.org/xml/export-0.10/" version="0.10" xml:lang="en">"#
+    )?;
+    writeln!(
+        out,
+        "<siteinfo><sitename>Wikipedia</sitename><dbname>enwiki</dbname></siteinfo>"
+    )?;
+    for id in 0..PAGES {
+        write!(
+            out,
+            r#"<page><title>Article {id}</title><ns>0</ns><id>{id}</id><revision><id>{rev}</id><parentid>{parent}</parentid><timestamp>2024-01-01T00:00:00Z</timestamp><contributor><username>Editor{user}</username><id>{user}</id></contributor><model>wikitext</model><format>text/x-wiki</format><text bytes="420" xml:space="preserve">"#,
+            id = id,
+            rev = id * 7,
+            parent = id * 7 - id.min(1),
+            user = id % 97,
+        )?;
+        for _ in 0..6 {
+            out.write_all(b"'''Lorem ipsum''' dolor sit amet, [[consectetur]] adipiscing elit, sed do eiusmod tempor &amp; incididunt. ")?;
+        }
+        writeln!(out, "</text><sha1>abc{}</sha1></revision></page>", id)?;
+    }
+    writeln!(out, "</mediawiki>")?;
+    out.flush()
+}
+
+fn analyze_file(c: &mut Criterion) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("enwiki-pages.xml");
+    write_dump(&path).unwrap();
+    let size = fs::metadata(&path).unwrap().len();
+
+    let analyzer = StreamAnalyzer::new(10);
+    let mut group = c.benchmark_group("wiki_dump");
+    group.throughput(Throughput::Bytes(size));
+    group.sample_size(10);
+
+    group.bench_function("analyze_file", |b| {
+        b.iter(|| analyzer.analyze_file(&path).unwrap())
+    });
+
+    let content = fs::read(&path).unwrap();
+    group.bench_function("in_memory", |b| {
+        b.iter(|| analyzer.analyze_bytes(&content).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, analyze_file);
+criterion_main!(benches);
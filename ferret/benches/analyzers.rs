@@ -0,0 +1,83 @@
+//! StatsAnalyzer (tl DOM) vs StreamAnalyzer (quick-xml events)
+//!
+//! Run with `cargo bench -p ferret --bench analyzers`; results are
+//! summarised in `benches/README.md`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use ferret::analyzer::stream::StreamAnalyzer;
+use ferret::analyzer::{AnalysisResult, Analyzer, StatsAnalyzer};
+use ferret::parser::FerretParser;
+use ferret::walker::DomWalker;
+use std::fs;
+
+const FIXTURES: &[&str] = &[
+    "attributes.html",
+    "deeply_nested.html",
+    "realistic_sample.html",
+    "realistic_sample.xml",
+    "unicode.html",
+];
+
+/// Size of the generated document
+const GENERATED_BYTES: usize = 50 * 1024 * 1024;
+
+fn analyze_dom(content: &str) -> AnalysisResult {
+    let vdom = FerretParser::parse(content).unwrap();
+    let mut analyzer = StatsAnalyzer::new(10);
+    for (_handle, node, depth) in DomWalker::new(vdom.children().to_vec(), vdom.parser()) {
+        analyzer.visit(node, depth);
+    }
+    analyzer.result()
+}
+
+/// A product listing page repeated until it reaches `GENERATED_BYTES`
+fn generate_document() -> String {
+    let mut html = String::with_capacity(GENERATED_BYTES + 1024);
+    html.push_str(r#"<!DOCTYPE html><html lang="en"><head><meta charset="utf-8"><title>Catalog</title><link rel="stylesheet" href="/site.css"></head><body class="catalog"><main id="content">"#);
+    let mut id = 0;
+    while html.len() < GENERATED_BYTES {
+        html.push_str(&format!(
+            r#"<article class="product card" data-id="{id}" data-category="c{category}"><header><h2 class="title"><a href="/p/{id}" rel="bookmark">Product {id}</a></h2></header><img src="/img/{id}.jpg" alt="Product {id}" width="120" height="90"><p class="description">A short description with <em>emphasis</em> and a <a href="/tags/{category}" class="tag">tag</a>.</p><ul class="specs"><li class="spec">Weight: {id}g</li><li class="spec">Colour: red</li></ul><footer><span class="price" data-currency="EUR">{price}</span><button type="button" class="btn btn-primary">Add</button></footer></article>"#,
+            id = id,
+            category = id % 25,
+            price = id % 1000,
+        ));
+        id += 1;
+    }
+    html.push_str("</main></body></html>");
+    html
+}
+
+fn fixtures(c: &mut Criterion) {
+    let mut group = c.benchmark_group("fixtures");
+    for name in FIXTURES {
+        let content = fs::read_to_string(format!("tests/fixtures/{}", name)).unwrap();
+        group.throughput(Throughput::Bytes(content.len() as u64));
+
+        group.bench_with_input(BenchmarkId::new("stats", name), &content, |b, content| {
+            b.iter(|| analyze_dom(content))
+        });
+        let analyzer = StreamAnalyzer::new(10);
+        group.bench_with_input(BenchmarkId::new("stream", name), &content, |b, content| {
+            b.iter(|| analyzer.analyze_string(content).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn generated(c: &mut Criterion) {
+    let content = generate_document();
+    let mut group = c.benchmark_group("generated_50mb");
+    group.throughput(Throughput::Bytes(content.len() as u64));
+    group.sample_size(10);
+
+    group.bench_function("stats", |b| b.iter(|| analyze_dom(&content)));
+    let analyzer = StreamAnalyzer::new(10);
+    group.bench_function("stream", |b| {
+        b.iter(|| analyzer.analyze_string(&content).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, fixtures, generated);
+criterion_main!(benches);
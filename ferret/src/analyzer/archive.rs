@@ -0,0 +1,387 @@
+use crate::analyzer::batch::is_document;
+use crate::analyzer::stream::StreamAnalyzer;
+use crate::analyzer::{AnalysisResult, AnalysisResultSet};
+use anyhow::{Context, Result};
+use flate2::read::{MultiGzDecoder, ZlibDecoder};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Cursor, Read};
+use std::path::Path;
+
+/// Archive formats understood by `StreamAnalyzer::analyze_archive`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Zip,
+    Tar,
+    TarGz,
+    /// WARC, optionally gzip-compressed per record (`.warc.gz`)
+    Warc,
+}
+
+impl ArchiveFormat {
+    /// Detect the format from the file name
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?.to_ascii_lowercase();
+        if name.ends_with(".zip") {
+            Some(Self::Zip)
+        } else if name.ends_with(".tar") {
+            Some(Self::Tar)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(Self::TarGz)
+        } else if name.ends_with(".warc") || name.ends_with(".warc.gz") {
+            Some(Self::Warc)
+        } else {
+            None
+        }
+    }
+}
+
+impl StreamAnalyzer {
+    /// Analyze the HTML/XML documents inside a zip, tar, tar.gz or WARC archive
+    ///
+    /// Members are streamed straight from the archive without extracting
+    /// them to disk. In zip and tar archives, members are selected by
+    /// extension like in `analyze_dir`; in WARC files, `response` and
+    /// `resource` records with an HTML or XML content type are analyzed and
+    /// reported under their target URI.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use ferret::analyzer::stream::StreamAnalyzer;
+    /// use std::path::Path;
+    /// let analyzer = StreamAnalyzer::new(10);
+    /// let set = analyzer.analyze_archive(Path::new("crawl.warc.gz"))?;
+    /// for entry in &set.entries {
+    ///     println!("{}: {:?}", entry.source, entry.error);
+    /// }
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn analyze_archive(&self, path: &Path) -> Result<AnalysisResultSet> {
+        let format = ArchiveFormat::from_path(path)
+            .with_context(|| format!("Unsupported archive: {}", path.display()))?;
+        let file = File::open(path)?;
+
+        let results = match format {
+            ArchiveFormat::Zip => self.analyze_zip(file)?,
+            ArchiveFormat::Tar => self.analyze_tar(file)?,
+            ArchiveFormat::TarGz => self.analyze_tar(MultiGzDecoder::new(BufReader::new(file)))?,
+            ArchiveFormat::Warc => {
+                let mut reader = BufReader::new(file);
+                if reader.fill_buf()?.starts_with(&[0x1f, 0x8b]) {
+                    self.analyze_warc(BufReader::new(MultiGzDecoder::new(reader)))?
+                } else {
+                    self.analyze_warc(reader)?
+                }
+            }
+        };
+        Ok(AnalysisResultSet::from_results(
+            results,
+            self.top_values_limit,
+        ))
+    }
+
+    fn analyze_zip(&self, file: File) -> Result<Vec<(String, Result<AnalysisResult>)>> {
+        let mut archive = zip::ZipArchive::new(BufReader::new(file))?;
+        let mut results = Vec::new();
+        for index in 0..archive.len() {
+            let member = archive.by_index(index)?;
+            let name = member.name().to_string();
+            if member.is_file() && is_document(Path::new(&name)) {
+                let result = self.analyze_member(&name, member);
+                results.push((name, result));
+            }
+        }
+        Ok(results)
+    }
+
+    fn analyze_tar<R: Read>(&self, reader: R) -> Result<Vec<(String, Result<AnalysisResult>)>> {
+        let mut archive = tar::Archive::new(reader);
+        let mut results = Vec::new();
+        for entry in archive.entries()? {
+            let entry = entry?;
+            let name = entry.path()?.to_string_lossy().into_owned();
+            if entry.header().entry_type().is_file() && is_document(Path::new(&name)) {
+                let result = self.analyze_member(&name, entry);
+                results.push((name, result));
+            }
+        }
+        Ok(results)
+    }
+
+    fn analyze_member<R: Read>(&self, name: &str, reader: R) -> Result<AnalysisResult> {
+        if name.ends_with(".gz") {
+            self.analyze_reader(BufReader::new(MultiGzDecoder::new(reader)))
+        } else {
+            self.analyze_reader(BufReader::new(reader))
+        }
+    }
+
+    fn analyze_warc<R: BufRead>(
+        &self,
+        mut reader: R,
+    ) -> Result<Vec<(String, Result<AnalysisResult>)>> {
+        let mut results = Vec::new();
+        while let Some(headers) = read_warc_headers(&mut reader)? {
+            let length: u64 = headers
+                .get("content-length")
+                .context("WARC record without Content-Length")?
+                .parse()
+                .context("Invalid WARC Content-Length")?;
+            let source = headers
+                .get("warc-target-uri")
+                .or_else(|| headers.get("warc-record-id"))
+                .cloned()
+                .unwrap_or_default();
+            let content_type = headers.get("content-type").map(String::as_str);
+
+            let mut block = (&mut reader).take(length);
+            let result = match (headers.get("warc-type").map(String::as_str), content_type) {
+                (Some("response"), Some(ct)) if ct.starts_with("application/http") => self
+                    .read_block(&mut block, length)
+                    .and_then(|data| self.analyze_http_response(&data))
+                    .transpose(),
+                (Some("resource"), Some(ct)) if is_markup(ct) => Some(
+                    self.read_block(&mut block, length)
+                        .and_then(|data| self.analyze_reader(Cursor::new(data))),
+                ),
+                _ => None,
+            };
+
+            // Skip whatever is left of the block, e.g. after a limit violation
+            std::io::copy(&mut block, &mut std::io::sink())?;
+            if block.limit() > 0 {
+                // A limit violation is more telling than the missing bytes
+                return Err(match result {
+                    Some(Err(err)) => err,
+                    _ => anyhow::anyhow!("Truncated WARC record: {}", source),
+                });
+            }
+            if let Some(result) = result {
+                results.push((source, result));
+            }
+        }
+        Ok(results)
+    }
+
+    fn read_block<R: Read>(&self, block: &mut R, length: u64) -> Result<Vec<u8>> {
+        self.limits
+            .check_input(usize::try_from(length).unwrap_or(usize::MAX))?;
+        // Content-Length is untrusted: grow the buffer as the data comes in
+        let mut data = Vec::with_capacity(length.min(64 * 1024) as usize);
+        let max = self
+            .limits
+            .max_input_bytes
+            .map_or(u64::MAX, |limit| limit as u64 + 1);
+        block.by_ref().take(max).read_to_end(&mut data)?;
+        self.limits.check_input(data.len())?;
+        Ok(data)
+    }
+
+    /// Analyze the body of a raw HTTP response stored in a WARC record
+    ///
+    /// Returns `None` for responses that aren't HTML or XML.
+    fn analyze_http_response(&self, response: &[u8]) -> Result<Option<AnalysisResult>> {
+        let (head, body) = split_http_head(response).context("Malformed HTTP response")?;
+        let head = String::from_utf8_lossy(head);
+        let headers: HashMap<String, String> = head
+            .lines()
+            .skip(1)
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+            .collect();
+
+        if !headers.get("content-type").is_some_and(|ct| is_markup(ct)) {
+            return Ok(None);
+        }
+
+        let body = match headers.get("transfer-encoding") {
+            Some(encoding) if encoding.eq_ignore_ascii_case("chunked") => decode_chunked(body)?,
+            _ => body.to_vec(),
+        };
+        let encoding = headers
+            .get("content-encoding")
+            .map(|encoding| encoding.to_ascii_lowercase());
+        let result = match encoding.as_deref() {
+            None | Some("identity") => self.analyze_reader(Cursor::new(body)),
+            Some("gzip" | "x-gzip") => {
+                self.analyze_reader(BufReader::new(MultiGzDecoder::new(body.as_slice())))
+            }
+            Some("deflate") => {
+                self.analyze_reader(BufReader::new(ZlibDecoder::new(body.as_slice())))
+            }
+            Some(other) => anyhow::bail!("Unsupported Content-Encoding: {}", other),
+        };
+        result.map(Some)
+    }
+}
+
+fn is_markup(content_type: &str) -> bool {
+    let content_type = content_type.to_ascii_lowercase();
+    content_type.contains("html") || content_type.contains("xml")
+}
+
+/// Read the header block of the next WARC record
+///
+/// Returns `None` at the end of the input. Header names are lowercased.
+fn read_warc_headers<R: BufRead>(reader: &mut R) -> Result<Option<HashMap<String, String>>> {
+    let mut line = String::new();
+    // Records are separated by blank lines
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        if !line.trim().is_empty() {
+            break;
+        }
+    }
+    if !line.starts_with("WARC/") {
+        anyhow::bail!("Invalid WARC record header: {}", line.trim());
+    }
+
+    let mut headers = HashMap::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+        }
+    }
+    Ok(Some(headers))
+}
+
+/// Split an HTTP message into its head and body
+fn split_http_head(message: &[u8]) -> Option<(&[u8], &[u8])> {
+    [&b"\r\n\r\n"[..], &b"\n\n"[..]]
+        .iter()
+        .filter_map(|separator| {
+            message
+                .windows(separator.len())
+                .position(|window| window == *separator)
+                .map(|index| (index, separator.len()))
+        })
+        .min()
+        .map(|(index, len)| (&message[..index], &message[index + len..]))
+}
+
+/// Decode a body sent with `Transfer-Encoding: chunked`
+fn decode_chunked(mut data: &[u8]) -> Result<Vec<u8>> {
+    let mut body = Vec::new();
+    loop {
+        let line_end = data
+            .iter()
+            .position(|&b| b == b'\n')
+            .context("Truncated chunked body")?;
+        let size_line = String::from_utf8_lossy(&data[..line_end]);
+        // Chunk extensions follow a `;`
+        let size_hex = size_line.split(';').next().unwrap_or("").trim();
+        let size = usize::from_str_radix(size_hex, 16)
+            .with_context(|| format!("Invalid chunk size: {}", size_hex))?;
+        data = &data[line_end + 1..];
+
+        if size == 0 {
+            return Ok(body);
+        }
+        let chunk = data.get(..size).context("Truncated chunked body")?;
+        body.extend_from_slice(chunk);
+        data = &data[size..];
+        data = data
+            .strip_prefix(b"\r\n")
+            .or_else(|| data.strip_prefix(b"\n"))
+            .unwrap_or(data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::FerretError;
+    use crate::limits::Limits;
+
+    fn warc_record(headers: &str, block: &[u8]) -> Vec<u8> {
+        let mut record = format!(
+            "WARC/1.0\r\n{}Content-Length: {}\r\n\r\n",
+            headers,
+            block.len()
+        )
+        .into_bytes();
+        record.extend_from_slice(block);
+        record.extend_from_slice(b"\r\n\r\n");
+        record
+    }
+
+    #[test]
+    fn test_decode_chunked() {
+        let body = decode_chunked(b"4\r\n<div\r\n7;ext=1\r\n></div>\r\n0\r\n\r\n").unwrap();
+        assert_eq!(body, b"<div></div>");
+        assert!(decode_chunked(b"a\r\nshort").is_err());
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(
+            ArchiveFormat::from_path(Path::new("a/crawl.warc.gz")),
+            Some(ArchiveFormat::Warc)
+        );
+        assert_eq!(
+            ArchiveFormat::from_path(Path::new("dump.TGZ")),
+            Some(ArchiveFormat::TarGz)
+        );
+        assert_eq!(ArchiveFormat::from_path(Path::new("page.html")), None);
+    }
+
+    #[test]
+    fn test_warc_records() {
+        let mut warc = warc_record(
+            "WARC-Type: warcinfo\r\nContent-Type: application/warc-fields\r\n",
+            b"software: test\r\n",
+        );
+        warc.extend(warc_record(
+            "WARC-Type: response\r\nWARC-Target-URI: https://example.com/\r\nContent-Type: application/http; msgtype=response\r\n",
+            b"HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nTransfer-Encoding: chunked\r\n\r\nc\r\n<p>hello</p>\r\n0\r\n\r\n",
+        ));
+        warc.extend(warc_record(
+            "WARC-Type: response\r\nWARC-Target-URI: https://example.com/logo.png\r\nContent-Type: application/http; msgtype=response\r\n",
+            b"HTTP/1.1 200 OK\r\nContent-Type: image/png\r\n\r\n\x89PNG",
+        ));
+        warc.extend(warc_record(
+            "WARC-Type: resource\r\nWARC-Target-URI: file:///feed.xml\r\nContent-Type: application/xml\r\n",
+            b"<feed><entry/></feed>",
+        ));
+
+        let analyzer = StreamAnalyzer::new(10);
+        let results = analyzer.analyze_warc(Cursor::new(warc)).unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].0, "https://example.com/");
+        assert_eq!(results[0].1.as_ref().unwrap().tags["p"].count, 1);
+        assert_eq!(results[1].0, "file:///feed.xml");
+        assert!(results[1].1.as_ref().unwrap().tags.contains_key("entry"));
+    }
+
+    #[test]
+    fn test_warc_truncated() {
+        let mut warc = warc_record(
+            "WARC-Type: resource\r\nContent-Type: text/html\r\n",
+            b"<p></p>",
+        );
+        warc.truncate(warc.len() - 8);
+        let analyzer = StreamAnalyzer::new(10);
+        assert!(analyzer.analyze_warc(Cursor::new(warc)).is_err());
+    }
+
+    #[test]
+    fn test_warc_huge_content_length() {
+        let warc = b"WARC/1.0\r\nWARC-Type: resource\r\nContent-Type: text/html\r\nContent-Length: 18446744073709551615\r\n\r\n<p></p>\r\n\r\n";
+        let analyzer = StreamAnalyzer::new(10).with_limits(Limits::untrusted());
+        let err = analyzer.analyze_warc(Cursor::new(warc)).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<FerretError>(),
+            Some(FerretError::InputTooLarge { .. })
+        ));
+        // Without limits, the record is only reported as truncated
+        let analyzer = StreamAnalyzer::new(10);
+        assert!(analyzer.analyze_warc(Cursor::new(warc)).is_err());
+    }
+}
@@ -0,0 +1,226 @@
+use crate::analyzer::stream::StreamAnalyzer;
+use crate::analyzer::{AnalysisResult, AnalysisResultSet};
+use crate::progress::{Progress, ProgressEvent};
+use anyhow::{Context, Result};
+use futures::StreamExt;
+use rayon::prelude::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// File extensions picked up by `analyze_dir`, optionally followed by `.gz`
+pub const DOCUMENT_EXTENSIONS: &[&str] = &["html", "htm", "xhtml", "xml"];
+
+impl StreamAnalyzer {
+    /// Analyze every HTML/XML document in a directory
+    ///
+    /// Files are selected by extension (see [`DOCUMENT_EXTENSIONS`]);
+    /// gzip-compressed variants such as `page.html.gz` are included. Hidden
+    /// files and directories are skipped. Use `analyze_glob` for other
+    /// selections.
+    ///
+    /// # Arguments
+    /// * `path` - Directory to scan
+    /// * `recursive` - Also scan subdirectories
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use ferret::analyzer::stream::StreamAnalyzer;
+    /// use std::path::Path;
+    /// let analyzer = StreamAnalyzer::new(10);
+    /// let set = analyzer.analyze_dir(Path::new("dumps"), true)?;
+    /// println!("{} files, {} tags", set.entries.len(), set.aggregate.tags.len());
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn analyze_dir(&self, path: &Path, recursive: bool) -> Result<AnalysisResultSet> {
+        let mut files = Vec::new();
+        collect_documents(path, recursive, &mut files)?;
+        files.sort();
+        Ok(self.analyze_files(files))
+    }
+
+    /// Analyze every file matching a glob pattern such as `dumps/**/*.html`
+    ///
+    /// As in a shell, wildcards don't match hidden files and directories.
+    pub fn analyze_glob(&self, pattern: &str) -> Result<AnalysisResultSet> {
+        Ok(self.analyze_files(glob_files(pattern)?))
+    }
+
+    /// Analyze files in parallel
+    ///
+    /// A file that fails to analyze is reported in its entry and left out
+    /// of the aggregate; it does not abort the batch.
+    pub fn analyze_files(&self, paths: impl IntoIterator<Item = PathBuf>) -> AnalysisResultSet {
+        let paths: Vec<PathBuf> = paths.into_iter().collect();
+        let progress = BatchProgress::new(self.progress.as_ref(), paths.len());
+        let results: Vec<_> = paths
+            .par_iter()
+            .map(|path| {
+                let source = path.display().to_string();
+                let result = self.analyze_file(path);
+                progress.complete(&source, &result);
+                (source, result)
+            })
+            .collect();
+        AnalysisResultSet::from_results(results, self.top_values_limit)
+    }
+
+    /// Fetch and analyze many URLs, at most `concurrency` at a time
+    ///
+    /// Entries are returned in input order. A failed URL is reported in its
+    /// entry and does not abort the batch. To space out requests to the
+    /// same host, configure a [`HostThrottle`](crate::fetch::HostThrottle)
+    /// in the fetch options.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use ferret::analyzer::stream::StreamAnalyzer;
+    /// # async fn run() -> anyhow::Result<()> {
+    /// use ferret::fetch::{FetchOptions, HostThrottle};
+    /// use std::time::Duration;
+    ///
+    /// let analyzer = StreamAnalyzer::new(10).with_fetch_options(
+    ///     FetchOptions::default().throttle(HostThrottle::new(Duration::from_millis(500))),
+    /// );
+    /// let urls = ["https://example.com/a", "https://example.com/b"];
+    /// let set = analyzer.analyze_urls(urls, 8).await;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn analyze_urls<I>(&self, urls: I, concurrency: usize) -> AnalysisResultSet
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        let urls: Vec<String> = urls.into_iter().map(Into::into).collect();
+        let progress = BatchProgress::new(self.progress.as_ref(), urls.len());
+        let progress = &progress;
+        let results: Vec<_> = futures::stream::iter(urls)
+            .map(|url| async move {
+                let result = self.analyze_url(&url).await;
+                progress.complete(&url, &result);
+                (url, result)
+            })
+            .buffered(concurrency.max(1))
+            .collect()
+            .await;
+        AnalysisResultSet::from_results(results, self.top_values_limit)
+    }
+}
+
+/// Reports a `SourceCompleted` event for every finished source of a batch
+struct BatchProgress<'a> {
+    progress: Option<&'a Progress>,
+    completed: AtomicUsize,
+    total: usize,
+}
+
+impl<'a> BatchProgress<'a> {
+    fn new(progress: Option<&'a Progress>, total: usize) -> Self {
+        Self {
+            progress,
+            completed: AtomicUsize::new(0),
+            total,
+        }
+    }
+
+    fn complete(&self, source: &str, result: &Result<AnalysisResult>) {
+        let completed = self.completed.fetch_add(1, Ordering::Relaxed) + 1;
+        if let Some(progress) = self.progress {
+            progress.report(ProgressEvent::SourceCompleted {
+                source: source.to_string(),
+                completed,
+                total: self.total,
+                error: result.as_ref().err().map(|err| format!("{:#}", err)),
+                tags: result
+                    .as_ref()
+                    .map(AnalysisResult::tag_counts)
+                    .unwrap_or_default(),
+            });
+        }
+    }
+}
+
+/// Files matching a glob pattern such as `dumps/**/*.html`, in path order
+///
+/// As in a shell, wildcards don't match hidden files and directories.
+pub fn glob_files(pattern: &str) -> Result<Vec<PathBuf>> {
+    let options = glob::MatchOptions {
+        require_literal_leading_dot: true,
+        ..Default::default()
+    };
+    let paths = glob::glob_with(pattern, options)
+        .with_context(|| format!("Invalid pattern: {}", pattern))?;
+
+    let mut files = Vec::new();
+    for entry in paths {
+        let path = entry?;
+        if path.is_file() {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+/// Whether `input` contains glob wildcards
+pub fn is_glob(input: &str) -> bool {
+    input.contains(['*', '?', '['])
+}
+
+fn collect_documents(dir: &Path, recursive: bool, files: &mut Vec<PathBuf>) -> Result<()> {
+    let entries = fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))?;
+    for entry in entries {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            if recursive {
+                collect_documents(&path, recursive, files)?;
+            }
+        } else if is_document(&path) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn is_document(path: &Path) -> bool {
+    let path = match path.extension() {
+        Some(ext) if ext == "gz" => path.with_extension(""),
+        _ => path.to_path_buf(),
+    };
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            DOCUMENT_EXTENSIONS
+                .iter()
+                .any(|known| ext.eq_ignore_ascii_case(known))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_glob() {
+        assert!(is_glob("dumps/**/*.html"));
+        assert!(is_glob("page-?.html"));
+        assert!(is_glob("page-[0-9].html"));
+        assert!(!is_glob("dumps/page.html"));
+    }
+
+    #[test]
+    fn test_is_document() {
+        assert!(is_document(Path::new("a/page.html")));
+        assert!(is_document(Path::new("a/PAGE.HTM")));
+        assert!(is_document(Path::new("sitemap.xml.gz")));
+        assert!(!is_document(Path::new("notes.txt")));
+        assert!(!is_document(Path::new("archive.gz")));
+        assert!(!is_document(Path::new("README")));
+    }
+}
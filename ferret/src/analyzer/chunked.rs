@@ -0,0 +1,288 @@
+use crate::analyzer::stream::{StreamAnalyzer, StreamState, GZIP_MAGIC};
+use crate::analyzer::{AnalysisResult, MAX_PARSE_ISSUES};
+use crate::progress::ProgressEvent;
+use anyhow::Result;
+use rayon::prelude::*;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Files are split into chunks of at least this size
+const MIN_CHUNK_BYTES: u64 = 4 * 1024 * 1024;
+
+/// Chunks per worker thread, so a slow chunk doesn't leave threads idle
+const CHUNKS_PER_THREAD: usize = 4;
+
+/// Bytes read at a time while looking for a chunk boundary
+const SEARCH_BLOCK_BYTES: usize = 64 * 1024;
+
+impl StreamAnalyzer {
+    /// Analyze a large, flat document on all cores
+    ///
+    /// Meant for dumps that are one long list of `record` elements, such as
+    /// `<page>` in a Wikipedia export or `<url>` in a sitemap. The file is
+    /// split just before `<record` start tags into chunks that are analyzed
+    /// on the rayon thread pool, and the partial results are merged.
+    ///
+    /// The chunks after the first start with the elements that were open
+    /// where the first chunk ended, so depths and end-tag matching are the
+    /// same as for `analyze_file` as long as every `<record` tag sits at the
+    /// same level. The record name must not appear as literal text inside
+    /// comments or CDATA sections. Attribute values are merged like
+    /// [`AnalysisResult::merge`] does, so with a low top values limit the
+    /// tracked values can differ from a sequential run. Likewise, markup so
+    /// broken that it leaves elements open only affects the rest of its own
+    /// chunk.
+    ///
+    /// Gzip-compressed and small files are analyzed sequentially. No
+    /// `Parsed` progress events are reported while the chunks run.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use ferret::analyzer::stream::StreamAnalyzer;
+    /// use std::path::Path;
+    /// let analyzer = StreamAnalyzer::new(10);
+    /// let result = analyzer.analyze_file_chunked(Path::new("enwiki-pages.xml"), "page")?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn analyze_file_chunked(&self, path: &Path, record: &str) -> Result<AnalysisResult> {
+        let size = std::fs::metadata(path)?.len();
+        let chunks = (rayon::current_num_threads() * CHUNKS_PER_THREAD)
+            .min((size / MIN_CHUNK_BYTES) as usize);
+        self.analyze_file_in_chunks(path, record, chunks)
+    }
+
+    pub(crate) fn analyze_file_in_chunks(
+        &self,
+        path: &Path,
+        record: &str,
+        chunks: usize,
+    ) -> Result<AnalysisResult> {
+        let mut file = File::open(path)?;
+        let size = file.metadata()?.len();
+        let mut magic = [0; 2];
+        let is_gzip = path.extension().is_some_and(|ext| ext == "gz")
+            || (file.read(&mut magic)? == magic.len() && magic == GZIP_MAGIC);
+        if is_gzip || chunks < 2 {
+            return self.analyze_file(path);
+        }
+        self.limits.check_input(size as usize)?;
+
+        let boundaries = find_boundaries(&mut file, size, record.as_bytes(), chunks)?;
+        let Some(&first_end) = boundaries.first() else {
+            return self.analyze_file(path);
+        };
+
+        let mut first = StreamState::new(self).without_progress();
+        file.seek(SeekFrom::Start(0))?;
+        first.read_events(BufReader::new(file.take(first_end)), 0)?;
+
+        let mut ranges: Vec<(u64, u64)> = boundaries.windows(2).map(|w| (w[0], w[1])).collect();
+        ranges.push((*boundaries.last().unwrap_or(&first_end), size));
+        let last = ranges.len() - 1;
+        let states: Vec<StreamState> = ranges
+            .iter()
+            .map(|_| first.continuation(self).without_progress())
+            .collect();
+
+        let rest: Vec<AnalysisResult> = ranges
+            .into_par_iter()
+            .zip(states)
+            .enumerate()
+            .map(|(index, ((start, end), mut state))| {
+                let mut file = File::open(path)?;
+                file.seek(SeekFrom::Start(start))?;
+                state.read_events(BufReader::new(file.take(end - start)), start as usize)?;
+                if index == last {
+                    state.finish()
+                } else {
+                    Ok(state.finish_partial())
+                }
+            })
+            .collect::<Result<_>>()?;
+
+        let mut result = first.finish_partial();
+        let mut parse_errors = std::mem::take(&mut result.parse_errors);
+        for part in &rest {
+            result.merge(part, self.top_values_limit);
+            parse_errors.extend(part.parse_errors.iter().cloned());
+        }
+        result.files_analyzed = 1;
+
+        let elements = result.tags.values().map(|tag| tag.count).sum();
+        self.limits.check_nodes(elements)?;
+        self.limits.check_parse_errors(parse_errors.len())?;
+        parse_errors.truncate(MAX_PARSE_ISSUES);
+        result.parse_errors = parse_errors;
+
+        if let Some(progress) = &self.progress {
+            progress.report(ProgressEvent::Parsed {
+                bytes: size as usize,
+                elements,
+            });
+        }
+        Ok(result)
+    }
+}
+
+/// Offsets of `<record` start tags at or after evenly spaced positions
+///
+/// The offsets are strictly increasing; fewer than `chunks - 1` are
+/// returned when the file runs out of records.
+fn find_boundaries(file: &mut File, size: u64, record: &[u8], chunks: usize) -> Result<Vec<u64>> {
+    let mut boundaries: Vec<u64> = Vec::new();
+    for index in 1..chunks {
+        let target = size * index as u64 / chunks as u64;
+        let from = boundaries
+            .last()
+            .map_or(target, |last| target.max(last + 1));
+        match find_start_tag(file, from, record)? {
+            Some(offset) => boundaries.push(offset),
+            None => break,
+        }
+    }
+    Ok(boundaries)
+}
+
+/// Offset of the first `<record` start tag at or after `from`
+fn find_start_tag(file: &mut File, from: u64, record: &[u8]) -> Result<Option<u64>> {
+    file.seek(SeekFrom::Start(from))?;
+    let mut reader = BufReader::with_capacity(SEARCH_BLOCK_BYTES, file);
+    // `<`, the name and the byte that ends it
+    let tag_len = record.len() + 2;
+    let mut window: Vec<u8> = Vec::new();
+    let mut window_start = from;
+
+    loop {
+        let block = reader.fill_buf()?;
+        if block.is_empty() {
+            return Ok(None);
+        }
+        window.extend_from_slice(block);
+        let consumed = block.len();
+        reader.consume(consumed);
+
+        let found = window.windows(tag_len).position(|tag| {
+            tag[0] == b'<'
+                && &tag[1..tag_len - 1] == record
+                && matches!(tag[tag_len - 1], b' ' | b'\t' | b'\r' | b'\n' | b'>' | b'/')
+        });
+        if let Some(index) = found {
+            return Ok(Some(window_start + index as u64));
+        }
+
+        // Keep the tail, which may hold the start of a tag split across blocks
+        let drop = window.len().saturating_sub(tag_len - 1);
+        window.drain(..drop);
+        window_start += drop as u64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::limits::Limits;
+    use crate::sniff::ParseMode;
+    use std::io::Write;
+
+    fn write_dump(dir: &Path) -> std::path::PathBuf {
+        let path = dir.join("dump.xml");
+        let mut file = std::fs::File::create(&path).unwrap();
+        write!(
+            file,
+            r#"<?xml version="1.0"?><mediawiki><siteinfo><link>x</link></siteinfo>"#
+        )
+        .unwrap();
+        for id in 0..500 {
+            write!(
+                file,
+                r#"<page id="{}" ns="{}"><title>P{}</title><pages>not a record</pages><revision><text>t</text></revision></page>"#,
+                id,
+                id % 3,
+                id
+            )
+            .unwrap();
+            if id == 250 {
+                // A mismatched end tag in the middle of the file
+                write!(file, "<page><title>x</stray></title></page>").unwrap();
+            }
+        }
+        write!(file, "</mediawiki>").unwrap();
+        path
+    }
+
+    #[test]
+    fn test_matches_sequential_analysis() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_dump(dir.path());
+
+        for mode in [ParseMode::Auto, ParseMode::Xml] {
+            let analyzer = StreamAnalyzer::new(10).with_mode(mode).with_structure(true);
+            let expected = analyzer.analyze_file(&path).unwrap();
+            assert_eq!(
+                expected.parse_errors.len(),
+                usize::from(mode == ParseMode::Xml)
+            );
+
+            for chunks in [2, 7, 64] {
+                let result = analyzer
+                    .analyze_file_in_chunks(&path, "page", chunks)
+                    .unwrap();
+                assert_eq!(result.files_analyzed, 1);
+                assert_eq!(result.max_depth, expected.max_depth, "{} chunks", chunks);
+                assert_eq!(
+                    result.parse_errors, expected.parse_errors,
+                    "{} chunks",
+                    chunks
+                );
+                assert_eq!(result.children, expected.children, "{} chunks", chunks);
+                assert_eq!(result.depth_counts, expected.depth_counts);
+                assert_eq!(result.tags.len(), expected.tags.len());
+                for (name, stats) in &expected.tags {
+                    let actual = &result.tags[name];
+                    assert_eq!(actual.count, stats.count, "{} in {} chunks", name, chunks);
+                    for (attr, attr_stats) in &stats.attributes {
+                        assert_eq!(actual.attributes[attr].count, attr_stats.count);
+                    }
+                }
+                assert_eq!(
+                    result.tags["page"].attributes["ns"].value_counts,
+                    expected.tags["page"].attributes["ns"].value_counts
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_boundaries() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_dump(dir.path());
+        let mut file = File::open(&path).unwrap();
+        let size = file.metadata().unwrap().len();
+
+        let boundaries = find_boundaries(&mut file, size, b"page", 10).unwrap();
+        assert_eq!(boundaries.len(), 9);
+        let content = std::fs::read(&path).unwrap();
+        for offset in boundaries {
+            let tag = &content[offset as usize..offset as usize + 6];
+            assert!(tag == b"<page " || tag == b"<page>", "{:?}", tag);
+        }
+
+        // No records at all
+        assert!(find_boundaries(&mut file, size, b"entry", 4)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_limits_apply_to_whole_document() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_dump(dir.path());
+
+        let analyzer = StreamAnalyzer::new(10).with_limits(Limits {
+            max_nodes: Some(2000),
+            ..Limits::default()
+        });
+        assert!(analyzer.analyze_file_in_chunks(&path, "page", 8).is_err());
+    }
+}
@@ -0,0 +1,461 @@
+use crate::analyzer::section::{Element, SectionAnalyzer};
+use crate::analyzer::AnalysisResult;
+use crate::fetch::FetchOptions;
+use anyhow::Result;
+use reqwest::{Client, Url};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+
+/// At-rules whose blocks hold style rules; the blocks of all others, such
+/// as `@font-face` and `@keyframes`, hold no selectors
+const GROUPING_RULES: &[&str] = &[
+    "media",
+    "supports",
+    "layer",
+    "container",
+    "document",
+    "scope",
+    "starting-style",
+];
+
+/// Class and id names, e.g. used in HTML or selected in CSS
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CssNames {
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub classes: BTreeSet<String>,
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub ids: BTreeSet<String>,
+}
+
+impl CssNames {
+    /// Classes and ids of the selectors in the stylesheet `css`
+    ///
+    /// Selectors inside `@media`, `@supports` and other grouping rules and
+    /// nested rules count; escapes are decoded, so `.md\:flex` selects
+    /// the class `md:flex`.
+    ///
+    /// # Example
+    /// ```
+    /// use ferret::analyzer::css::CssNames;
+    ///
+    /// let names = CssNames::parse(
+    ///     "nav > .menu a:not(.active), #top { color: #333 }
+    ///      @media (min-width: 40.5em) { .md\\:flex { display: flex } }",
+    /// );
+    /// assert_eq!(names.classes.iter().collect::<Vec<_>>(), ["active", "md:flex", "menu"]);
+    /// assert_eq!(names.ids.iter().collect::<Vec<_>>(), ["top"]);
+    /// ```
+    pub fn parse(css: &str) -> Self {
+        let mut names = CssNames::default();
+        names.add_css(css);
+        names
+    }
+
+    /// Add the classes and ids selected in `css`, see [`parse`](Self::parse)
+    pub fn add_css(&mut self, css: &str) {
+        let mut prelude = String::new();
+        // Open blocks, and whether each holds rules rather than only
+        // declarations
+        let mut blocks: Vec<bool> = Vec::new();
+        let mut chars = css.chars().peekable();
+        while let Some(c) = chars.next() {
+            let selecting = blocks.last().copied().unwrap_or(true);
+            match c {
+                '/' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    let mut previous = '\0';
+                    for c in chars.by_ref() {
+                        if previous == '*' && c == '/' {
+                            break;
+                        }
+                        previous = c;
+                    }
+                }
+                '"' | '\'' => {
+                    prelude.push(c);
+                    while let Some(next) = chars.next() {
+                        prelude.push(next);
+                        if next == '\\' {
+                            prelude.extend(chars.next());
+                        } else if next == c {
+                            break;
+                        }
+                    }
+                }
+                '\\' => {
+                    prelude.push(c);
+                    prelude.extend(chars.next());
+                }
+                '{' => {
+                    let rule = prelude.trim();
+                    let holds_rules = match rule.strip_prefix('@') {
+                        Some(at_rule) => {
+                            let name = at_rule
+                                .split(|c: char| c.is_whitespace() || c == '(')
+                                .next()
+                                .unwrap_or_default();
+                            selecting
+                                && GROUPING_RULES.contains(&name.to_ascii_lowercase().as_str())
+                        }
+                        None => {
+                            if selecting {
+                                self.add_selector(rule);
+                            }
+                            // Nested rules may follow the declarations
+                            selecting
+                        }
+                    };
+                    blocks.push(holds_rules);
+                    prelude.clear();
+                }
+                '}' => {
+                    blocks.pop();
+                    prelude.clear();
+                }
+                ';' => prelude.clear(),
+                c => prelude.push(c),
+            }
+        }
+    }
+
+    fn add_selector(&mut self, selector: &str) {
+        let mut chars = selector.chars().peekable();
+        let mut brackets = 0usize;
+        while let Some(c) = chars.next() {
+            match c {
+                '[' => brackets += 1,
+                ']' => brackets = brackets.saturating_sub(1),
+                '\\' => {
+                    chars.next();
+                }
+                '"' | '\'' => {
+                    while let Some(next) = chars.next() {
+                        if next == '\\' {
+                            chars.next();
+                        } else if next == c {
+                            break;
+                        }
+                    }
+                }
+                '.' | '#' if brackets == 0 => {
+                    let name = identifier(&mut chars);
+                    if name.is_empty() {
+                        continue;
+                    }
+                    if c == '.' {
+                        self.classes.insert(name);
+                    } else {
+                        self.ids.insert(name);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Read a CSS identifier, decoding escapes
+fn identifier(chars: &mut std::iter::Peekable<std::str::Chars>) -> String {
+    let mut name = String::new();
+    // `.5` is a number, not a class
+    if chars.peek().is_some_and(char::is_ascii_digit) {
+        return name;
+    }
+    while let Some(&c) = chars.peek() {
+        if c == '\\' {
+            chars.next();
+            let mut hex = String::new();
+            while hex.len() < 6 && chars.peek().is_some_and(char::is_ascii_hexdigit) {
+                hex.extend(chars.next());
+            }
+            if hex.is_empty() {
+                name.extend(chars.next());
+            } else {
+                if chars.peek().is_some_and(|c| c.is_whitespace()) {
+                    chars.next();
+                }
+                let code = u32::from_str_radix(&hex, 16).unwrap_or_default();
+                name.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+            }
+        } else if c.is_alphanumeric() || c == '-' || c == '_' || !c.is_ascii() {
+            name.push(c);
+            chars.next();
+        } else {
+            break;
+        }
+    }
+    name
+}
+
+/// Classes and ids used in the HTML against those its CSS selects
+///
+/// The CSS is the page's `<style>` blocks plus stylesheets added with
+/// [`add_stylesheet`](Self::add_stylesheet), e.g. from local files or
+/// [`StylesheetFetcher`]. Undefined names are used in the HTML but
+/// selected nowhere, which for ids often means they are only link targets
+/// or script hooks; unused names are selected but never used, dead CSS
+/// unless scripts add them.
+///
+/// # Example
+/// ```
+/// # use ferret::analyzer::stream::StreamAnalyzer;
+/// use ferret::analyzer::section::Section;
+///
+/// let analyzer = StreamAnalyzer::new(10).with_sections([Section::Css]);
+/// let result = analyzer.analyze_string(
+///     r#"<style>.card { padding: 1em }</style>
+///        <div class="card shadow" id="main"></div>"#,
+/// )?;
+/// let mut css = result.css.unwrap();
+/// assert_eq!(css.undefined_classes, ["shadow"]);
+/// css.add_stylesheet(".shadow { box-shadow: 0 0 2px } .hidden { display: none }");
+/// assert!(css.undefined_classes.is_empty());
+/// assert_eq!(css.unused_classes, ["hidden"]);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CssReport {
+    /// URLs of `<link rel="stylesheet">`, resolved against the page when
+    /// it was fetched
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stylesheets: Vec<String>,
+    /// Classes and ids in the HTML
+    pub used: CssNames,
+    /// Classes and ids selected by the CSS
+    pub defined: CssNames,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub undefined_classes: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unused_classes: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub undefined_ids: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unused_ids: Vec<String>,
+}
+
+impl CssReport {
+    /// Add the selectors of the stylesheet `css` and compare again
+    pub fn add_stylesheet(&mut self, css: &str) {
+        self.defined.add_css(css);
+        self.compare();
+    }
+
+    /// Fill the undefined and unused names from `used` and `defined`
+    fn compare(&mut self) {
+        let difference = |a: &BTreeSet<String>, b: &BTreeSet<String>| -> Vec<String> {
+            a.difference(b).cloned().collect()
+        };
+        let (used, defined) = (&self.used, &self.defined);
+        self.undefined_classes = difference(&used.classes, &defined.classes);
+        self.unused_classes = difference(&defined.classes, &used.classes);
+        self.undefined_ids = difference(&used.ids, &defined.ids);
+        self.unused_ids = difference(&defined.ids, &used.ids);
+    }
+
+    /// Combine with the report of another page of the same site: a class
+    /// is unused only if no page uses it
+    pub fn merge(&mut self, other: &CssReport) {
+        for stylesheet in &other.stylesheets {
+            if !self.stylesheets.contains(stylesheet) {
+                self.stylesheets.push(stylesheet.clone());
+            }
+        }
+        for (names, other) in [
+            (&mut self.used, &other.used),
+            (&mut self.defined, &other.defined),
+        ] {
+            names.classes.extend(other.classes.iter().cloned());
+            names.ids.extend(other.ids.iter().cloned());
+        }
+        self.compare();
+    }
+}
+
+/// Downloads linked stylesheets into [`CssReport`]s, each URL once
+pub struct StylesheetFetcher {
+    fetch: FetchOptions,
+    client: Client,
+    /// Stylesheets by URL; `None` for those that failed to load
+    fetched: HashMap<String, Option<String>>,
+}
+
+impl StylesheetFetcher {
+    pub fn new(fetch: FetchOptions) -> Result<Self> {
+        Ok(Self {
+            client: fetch.build_client()?,
+            fetch,
+            fetched: HashMap::new(),
+        })
+    }
+
+    /// Add the stylesheets linked from the page of `report`
+    ///
+    /// Stylesheets that fail to load are logged and left out.
+    pub async fn add_linked(&mut self, report: &mut CssReport) {
+        for url in report.stylesheets.clone() {
+            if !self.fetched.contains_key(&url) {
+                let css = match self.get(&url).await {
+                    Ok(css) => Some(css),
+                    Err(err) => {
+                        tracing::warn!("stylesheet {}: {:#}", url, err);
+                        None
+                    }
+                };
+                self.fetched.insert(url.clone(), css);
+            }
+            if let Some(css) = &self.fetched[&url] {
+                report.defined.add_css(css);
+            }
+        }
+        report.compare();
+    }
+
+    async fn get(&self, url: &str) -> Result<String> {
+        let response = self.fetch.send(&self.client, url).await?.response;
+        if !response.status().is_success() {
+            anyhow::bail!("HTTP error: {}", response.status());
+        }
+        Ok(response.text().await?)
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct CssAnalyzer {
+    report: CssReport,
+    /// Inside `<style>`
+    style: Option<String>,
+}
+
+impl SectionAnalyzer for CssAnalyzer {
+    fn element(&mut self, element: &Element) {
+        let used = &mut self.report.used;
+        if let Some(class) = element.attribute("class") {
+            used.classes
+                .extend(class.split_ascii_whitespace().map(str::to_string));
+        }
+        if let Some(id) = element.attribute("id").map(str::trim) {
+            if !id.is_empty() {
+                used.ids.insert(id.to_string());
+            }
+        }
+        match element.name {
+            "link" if element.has_rel("stylesheet") => {
+                if let Some(href) = element.attribute("href").map(str::trim) {
+                    self.report.stylesheets.push(href.to_string());
+                }
+            }
+            "style" => self.style = Some(String::new()),
+            _ => {}
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        if let Some(style) = &mut self.style {
+            style.push_str(text);
+        }
+    }
+
+    fn end(&mut self, name: &str) {
+        if name == "style" {
+            if let Some(style) = self.style.take() {
+                self.report.defined.add_css(&style);
+            }
+        }
+    }
+
+    fn finish(self: Box<Self>, page: Option<&Url>, result: &mut AnalysisResult) {
+        let mut report = self.report;
+        if let Some(page) = page {
+            for stylesheet in &mut report.stylesheets {
+                if let Ok(url) = page.join(stylesheet) {
+                    *stylesheet = url.to_string();
+                }
+            }
+        }
+        report.stylesheets.dedup();
+        report.compare();
+        result.css = Some(report);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::section::Section;
+    use crate::analyzer::stream::StreamAnalyzer;
+
+    fn names(names: &BTreeSet<String>) -> Vec<&str> {
+        names.iter().map(String::as_str).collect()
+    }
+
+    #[test]
+    fn test_parse_css() {
+        let css = CssNames::parse(
+            r#"/* .commented { } */
+            @charset "utf-8";
+            @import url("x.css");
+            @font-face { font-family: "A.B"; src: url(a.woff2) }
+            @keyframes spin { from { opacity: 0.5 } 50% { opacity: 1 } }
+            a[href$=".pdf"], input[class~="x"] { color: #fff }
+            ul.nav li > a.is-active::after, #logo:hover { content: ".fake" }
+            @media screen and (max-width: 600.5px) {
+              @supports (display: grid) { .grid\:cols-2, .\31 0col { display: grid } }
+            }
+            .card { padding: 1em; &.wide { width: 100% } .title { margin: 0 } }
+            .ünïcode, .a.b{}"#,
+        );
+        assert_eq!(
+            names(&css.classes),
+            [
+                "10col",
+                "a",
+                "b",
+                "card",
+                "grid:cols-2",
+                "is-active",
+                "nav",
+                "title",
+                "wide",
+                "ünïcode"
+            ]
+        );
+        assert_eq!(names(&css.ids), ["logo"]);
+    }
+
+    #[test]
+    fn test_css() {
+        let analyzer = StreamAnalyzer::new(10).with_sections([Section::Css]);
+        let result = analyzer
+            .analyze_string(
+                r#"<html><head>
+                <link rel="stylesheet" href="/site.css">
+                <link rel="preload" href="/font.woff2">
+                <style>.hero, .unused, #main { color: red }</style>
+                </head><body id="main">
+                <section class="hero  dark" id=" top "><p class="hero">Hi</p></section>
+                </body></html>"#,
+            )
+            .unwrap();
+        let mut css = result.css.unwrap();
+        assert_eq!(css.stylesheets, ["/site.css"]);
+        assert_eq!(names(&css.used.classes), ["dark", "hero"]);
+        assert_eq!(names(&css.used.ids), ["main", "top"]);
+        assert_eq!(css.undefined_classes, ["dark"]);
+        assert_eq!(css.unused_classes, ["unused"]);
+        assert_eq!(css.undefined_ids, ["top"]);
+        assert!(css.unused_ids.is_empty());
+
+        css.add_stylesheet(".dark { color: black } #top { }");
+        assert!(css.undefined_classes.is_empty());
+        assert!(css.undefined_ids.is_empty());
+
+        let other = analyzer
+            .analyze_string(r#"<p class="unused other">x</p>"#)
+            .unwrap()
+            .css
+            .unwrap();
+        css.merge(&other);
+        assert!(css.unused_classes.is_empty());
+        assert_eq!(css.undefined_classes, ["other"]);
+    }
+}
@@ -0,0 +1,272 @@
+use crate::analyzer::section::{Element, SectionAnalyzer};
+use crate::analyzer::AnalysisResult;
+use crate::language::{self, primary_subtag};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+
+/// Words of visible text kept for detecting its language
+const MAX_DETECTION_WORDS: usize = 2000;
+
+/// Elements whose text isn't shown
+const HIDDEN_TEXT: &[&str] = &["script", "style", "noscript", "template"];
+
+/// `<link rel="alternate" hreflang href>`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Alternate {
+    pub hreflang: String,
+    pub href: String,
+}
+
+/// An element with a `lang` attribute
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LangNode {
+    pub element: String,
+    pub lang: String,
+    /// Nesting level, 1 for the top-level element
+    pub depth: usize,
+}
+
+/// Declared and detected languages and `hreflang` alternates of a page
+///
+/// The language of the visible text is detected from its common words, see
+/// [`language::detect`]; a mismatch is counted when it differs from the
+/// primary subtag of `<html lang>`.
+///
+/// Detection is a stopword heuristic, not a statistical detector like
+/// whatlang, and gives no confidence score. It only knows English, German,
+/// French, Spanish, Italian, Dutch, Portuguese and Swedish. Text in other
+/// languages, shorter than [`language::MIN_DETECTION_WORDS`] words or with
+/// too few common words is left undetected and never counted as a
+/// mismatch. Text in an unsupported language that shares short words with
+/// a supported one, such as Polish `i` and `a`, may be detected as that
+/// language and counted as a mismatch.
+///
+/// Merged results count mismatches and
+/// pages without `lang` across all pages, list each alternate once and keep
+/// the languages and `lang` tree of the first page.
+///
+/// # Example
+/// ```
+/// # use ferret::analyzer::stream::StreamAnalyzer;
+/// use ferret::analyzer::section::Section;
+///
+/// let analyzer = StreamAnalyzer::new(10).with_sections([Section::I18n]);
+/// let result = analyzer.analyze_string(
+///     r#"<html lang="en"><head>
+///        <link rel="alternate" hreflang="de" href="https://example.com/de/">
+///        </head><body><p>Die Seite ist auf Deutsch, und der Text steht in der
+///        Mitte, aber die Sprache im Kopf ist falsch.</p></body></html>"#,
+/// )?;
+/// let i18n = result.i18n.unwrap();
+/// assert_eq!(i18n.lang.as_deref(), Some("en"));
+/// assert_eq!(i18n.detected.as_deref(), Some("de"));
+/// assert_eq!(i18n.mismatches, 1);
+/// assert_eq!(i18n.alternates[0].hreflang, "de");
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct I18nReport {
+    /// `lang` of the `<html>` element
+    pub lang: Option<String>,
+    /// ISO 639-1 code of the language of the visible text, if it is one of
+    /// the eight supported languages and could be told
+    pub detected: Option<String>,
+    /// Pages whose text is in another language than they declare
+    pub mismatches: usize,
+    /// Pages without `<html lang>`
+    pub missing_lang: usize,
+    /// Elements with a `lang` attribute, in document order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub lang_tree: Vec<LangNode>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub alternates: Vec<Alternate>,
+    /// `hreflang` values that are neither language tags nor `x-default`,
+    /// e.g. `en_US`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub invalid_hreflang: Vec<String>,
+}
+
+impl I18nReport {
+    pub fn merge(&mut self, other: &I18nReport) {
+        if self.lang.is_none() {
+            self.lang.clone_from(&other.lang);
+        }
+        if self.detected.is_none() {
+            self.detected.clone_from(&other.detected);
+        }
+        self.mismatches += other.mismatches;
+        self.missing_lang += other.missing_lang;
+        if self.lang_tree.is_empty() {
+            self.lang_tree.clone_from(&other.lang_tree);
+        }
+        for alternate in &other.alternates {
+            if !self.alternates.contains(alternate) {
+                self.alternates.push(alternate.clone());
+            }
+        }
+        for hreflang in &other.invalid_hreflang {
+            if !self.invalid_hreflang.contains(hreflang) {
+                self.invalid_hreflang.push(hreflang.clone());
+            }
+        }
+    }
+}
+
+/// Whether `hreflang` is `x-default` or shaped like a BCP 47 language tag:
+/// a 2-3 letter language, then subtags of 2-8 letters or digits
+///
+/// # Example
+/// ```
+/// use ferret::analyzer::i18n::is_valid_hreflang;
+/// assert!(is_valid_hreflang("de-AT"));
+/// assert!(is_valid_hreflang("zh-Hant-TW"));
+/// assert!(!is_valid_hreflang("en_US"));
+/// ```
+pub fn is_valid_hreflang(hreflang: &str) -> bool {
+    if hreflang.eq_ignore_ascii_case("x-default") {
+        return true;
+    }
+    let mut subtags = hreflang.split('-');
+    let language = subtags.next().unwrap_or_default();
+    (2..=3).contains(&language.len())
+        && language.bytes().all(|byte| byte.is_ascii_alphabetic())
+        && subtags.all(|subtag| {
+            (2..=8).contains(&subtag.len())
+                && subtag.bytes().all(|byte| byte.is_ascii_alphanumeric())
+        })
+}
+
+#[derive(Default)]
+pub(crate) struct I18nAnalyzer {
+    report: I18nReport,
+    /// Inside an element from [`HIDDEN_TEXT`]
+    hidden: Option<String>,
+    words: Vec<String>,
+}
+
+impl SectionAnalyzer for I18nAnalyzer {
+    fn element(&mut self, element: &Element) {
+        if let Some(lang) = element.attribute("lang").map(str::trim) {
+            if element.name == "html" && self.report.lang.is_none() {
+                self.report.lang = Some(lang.to_string());
+            }
+            self.report.lang_tree.push(LangNode {
+                element: element.name.to_string(),
+                lang: lang.to_string(),
+                depth: element.depth,
+            });
+        }
+        if element.name == "link" && element.has_rel("alternate") {
+            if let (Some(hreflang), Some(href)) =
+                (element.attribute("hreflang"), element.attribute("href"))
+            {
+                let hreflang = hreflang.trim();
+                if !is_valid_hreflang(hreflang) {
+                    self.report.invalid_hreflang.push(hreflang.to_string());
+                }
+                let alternate = Alternate {
+                    hreflang: hreflang.to_string(),
+                    href: href.trim().to_string(),
+                };
+                if !self.report.alternates.contains(&alternate) {
+                    self.report.alternates.push(alternate);
+                }
+            }
+        }
+        if HIDDEN_TEXT.contains(&element.name) {
+            self.hidden = Some(element.name.to_string());
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        if self.hidden.is_some() || self.words.len() >= MAX_DETECTION_WORDS {
+            return;
+        }
+        let room = MAX_DETECTION_WORDS - self.words.len();
+        self.words.extend(language::words(text).take(room));
+    }
+
+    fn end(&mut self, name: &str) {
+        if self.hidden.as_deref() == Some(name) {
+            self.hidden = None;
+        }
+    }
+
+    fn finish(self: Box<Self>, _page: Option<&Url>, result: &mut AnalysisResult) {
+        let mut report = self.report;
+        report.detected = language::detect(&self.words).map(str::to_string);
+        match (&report.lang, &report.detected) {
+            (None, _) => report.missing_lang = 1,
+            (Some(lang), Some(detected))
+                if !primary_subtag(lang).eq_ignore_ascii_case(detected) =>
+            {
+                report.mismatches = 1;
+            }
+            _ => {}
+        }
+        result.i18n = Some(report);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::section::Section;
+    use crate::analyzer::stream::StreamAnalyzer;
+
+    #[test]
+    fn test_i18n() {
+        let analyzer = StreamAnalyzer::new(10).with_sections([Section::I18n]);
+        let result = analyzer
+            .analyze_string(
+                r#"<html lang="fr-CA"><head>
+                <link rel="alternate" hreflang="fr-CA" href="/fr/">
+                <link rel="alternate" hreflang="en_US" href="/en/">
+                <link rel="alternate" hreflang="x-default" href="/">
+                <link rel="alternate" type="application/rss+xml" href="/feed">
+                <script>var the = "the the the the the the the the the the";</script>
+                </head><body>
+                <p>Le site est en français et il est fait pour les lecteurs du Québec,
+                   avec des articles sur la ville et la région.</p>
+                <blockquote lang="en"><p>Quoted</p></blockquote>
+                </body></html>"#,
+            )
+            .unwrap();
+        let i18n = result.i18n.unwrap();
+        assert_eq!(i18n.lang.as_deref(), Some("fr-CA"));
+        assert_eq!(i18n.detected.as_deref(), Some("fr"));
+        assert_eq!((i18n.mismatches, i18n.missing_lang), (0, 0));
+        assert_eq!(
+            i18n.lang_tree,
+            [
+                LangNode {
+                    element: "html".to_string(),
+                    lang: "fr-CA".to_string(),
+                    depth: 1,
+                },
+                LangNode {
+                    element: "blockquote".to_string(),
+                    lang: "en".to_string(),
+                    depth: 3,
+                },
+            ]
+        );
+        assert_eq!(
+            i18n.alternates
+                .iter()
+                .map(|alternate| alternate.hreflang.as_str())
+                .collect::<Vec<_>>(),
+            ["fr-CA", "en_US", "x-default"]
+        );
+        assert_eq!(i18n.invalid_hreflang, ["en_US"]);
+
+        let bare = analyzer.analyze_string("<p>Hi</p>").unwrap().i18n.unwrap();
+        assert_eq!((bare.missing_lang, bare.detected.as_deref()), (1, None));
+
+        let mut merged = i18n.clone();
+        merged.merge(&bare);
+        assert_eq!(merged.missing_lang, 1);
+        assert_eq!(merged.lang.as_deref(), Some("fr-CA"));
+        assert_eq!(merged.alternates.len(), 3);
+    }
+}
@@ -0,0 +1,237 @@
+use crate::analyzer::section::{Element, SectionAnalyzer};
+use crate::analyzer::AnalysisResult;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+
+/// What an icon link is for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IconKind {
+    /// `rel="icon"` or `rel="shortcut icon"`
+    Favicon,
+    /// `rel="apple-touch-icon"` or `rel="apple-touch-icon-precomposed"`
+    AppleTouch,
+    /// `rel="mask-icon"`, Safari's pinned tab icon
+    Mask,
+}
+
+impl IconKind {
+    fn of(element: &Element) -> Option<IconKind> {
+        if element.has_rel("icon") {
+            Some(IconKind::Favicon)
+        } else if element.has_rel("apple-touch-icon")
+            || element.has_rel("apple-touch-icon-precomposed")
+        {
+            Some(IconKind::AppleTouch)
+        } else if element.has_rel("mask-icon") {
+            Some(IconKind::Mask)
+        } else {
+            None
+        }
+    }
+}
+
+/// An icon `<link>`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Icon {
+    pub kind: IconKind,
+    pub href: String,
+    /// `sizes`, e.g. `32x32` or `any`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sizes: Option<String>,
+    /// `type`, e.g. `image/png`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime: Option<String>,
+}
+
+/// Favicons, apple-touch icons, the web app manifest and theme colors of a
+/// page
+///
+/// `missing` names what the page lacks of `favicon`, `apple-touch-icon`,
+/// `manifest` and `theme-color`. Browsers still request `/favicon.ico`
+/// without a favicon link, which isn't checked. Merged results list the
+/// icons, manifest and theme colors of the first page declaring them and
+/// everything missing on any page.
+///
+/// # Example
+/// ```
+/// # use ferret::analyzer::stream::StreamAnalyzer;
+/// use ferret::analyzer::section::Section;
+///
+/// let analyzer = StreamAnalyzer::new(10).with_sections([Section::Icons]);
+/// let result = analyzer.analyze_string(
+///     r#"<head><link rel="icon" href="/favicon.svg" type="image/svg+xml">
+///        <link rel="manifest" href="/site.webmanifest"></head>"#,
+/// )?;
+/// let icons = result.icons.unwrap();
+/// assert_eq!(icons.favicons().count(), 1);
+/// assert_eq!(icons.manifest.as_deref(), Some("/site.webmanifest"));
+/// assert_eq!(icons.missing, ["apple-touch-icon", "theme-color"]);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IconReport {
+    /// Icon links in document order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub icons: Vec<Icon>,
+    /// `href` of `<link rel="manifest">`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub manifest: Option<String>,
+    /// `content` of each `<meta name="theme-color">`; pages may set one per
+    /// `media` query, e.g. for dark mode
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub theme_colors: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub missing: Vec<String>,
+}
+
+impl IconReport {
+    pub fn favicons(&self) -> impl Iterator<Item = &Icon> {
+        self.of_kind(IconKind::Favicon)
+    }
+
+    pub fn apple_touch_icons(&self) -> impl Iterator<Item = &Icon> {
+        self.of_kind(IconKind::AppleTouch)
+    }
+
+    fn of_kind(&self, kind: IconKind) -> impl Iterator<Item = &Icon> {
+        self.icons.iter().filter(move |icon| icon.kind == kind)
+    }
+
+    pub fn merge(&mut self, other: &IconReport) {
+        if self.icons.is_empty() {
+            self.icons.clone_from(&other.icons);
+        }
+        if self.manifest.is_none() {
+            self.manifest.clone_from(&other.manifest);
+        }
+        if self.theme_colors.is_empty() {
+            self.theme_colors.clone_from(&other.theme_colors);
+        }
+        for name in &other.missing {
+            if !self.missing.contains(name) {
+                self.missing.push(name.clone());
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct IconAnalyzer {
+    report: IconReport,
+}
+
+impl SectionAnalyzer for IconAnalyzer {
+    fn element(&mut self, element: &Element) {
+        let report = &mut self.report;
+        match element.name {
+            "link" => {
+                let Some(href) = element.attribute("href").map(str::trim) else {
+                    return;
+                };
+                if element.has_rel("manifest") {
+                    if report.manifest.is_none() {
+                        report.manifest = Some(href.to_string());
+                    }
+                } else if let Some(kind) = IconKind::of(element) {
+                    let value = |name| {
+                        element
+                            .attribute(name)
+                            .map(str::trim)
+                            .filter(|value| !value.is_empty())
+                            .map(str::to_string)
+                    };
+                    report.icons.push(Icon {
+                        kind,
+                        href: href.to_string(),
+                        sizes: value("sizes"),
+                        mime: value("type"),
+                    });
+                }
+            }
+            "meta" => {
+                let theme_color = element
+                    .attribute("name")
+                    .is_some_and(|name| name.trim().eq_ignore_ascii_case("theme-color"));
+                if let Some(color) = element.attribute("content").filter(|_| theme_color) {
+                    report.theme_colors.push(color.trim().to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn finish(self: Box<Self>, _page: Option<&Url>, result: &mut AnalysisResult) {
+        let mut report = self.report;
+        let missing = [
+            ("favicon", report.favicons().next().is_some()),
+            (
+                "apple-touch-icon",
+                report.apple_touch_icons().next().is_some(),
+            ),
+            ("manifest", report.manifest.is_some()),
+            ("theme-color", !report.theme_colors.is_empty()),
+        ]
+        .into_iter()
+        .filter(|&(_, present)| !present)
+        .map(|(name, _)| name.to_string())
+        .collect();
+        report.missing = missing;
+        result.icons = Some(report);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::section::Section;
+    use crate::analyzer::stream::StreamAnalyzer;
+
+    #[test]
+    fn test_icons() {
+        let analyzer = StreamAnalyzer::new(10).with_sections([Section::Icons]);
+        let result = analyzer
+            .analyze_string(
+                r##"<html><head>
+                <link rel="shortcut icon" href="/favicon.ico">
+                <link rel="icon" href="/icon-32.png" sizes="32x32" type="image/png">
+                <link rel="ICON" href="/icon.svg" sizes="any" type="image/svg+xml">
+                <link rel="apple-touch-icon" href="/apple-touch-icon.png" sizes="180x180">
+                <link rel="mask-icon" href="/mask.svg" color="#000">
+                <link rel="icon">
+                <link rel="manifest" href="/manifest.json">
+                <meta name="theme-color" content="#ffffff" media="(prefers-color-scheme: light)">
+                <meta name="Theme-Color" content=" #000000 " media="(prefers-color-scheme: dark)">
+                <meta name="description" content="Not a color">
+                </head></html>"##,
+            )
+            .unwrap();
+        let icons = result.icons.unwrap();
+        assert_eq!(icons.favicons().count(), 3);
+        assert_eq!(
+            icons.icons[1],
+            Icon {
+                kind: IconKind::Favicon,
+                href: "/icon-32.png".to_string(),
+                sizes: Some("32x32".to_string()),
+                mime: Some("image/png".to_string()),
+            }
+        );
+        assert_eq!(icons.apple_touch_icons().count(), 1);
+        assert_eq!(icons.icons[4].kind, IconKind::Mask);
+        assert_eq!(icons.manifest.as_deref(), Some("/manifest.json"));
+        assert_eq!(icons.theme_colors, ["#ffffff", "#000000"]);
+        assert!(icons.missing.is_empty());
+
+        let bare = analyzer.analyze_string("<p>Hi</p>").unwrap().icons.unwrap();
+        assert_eq!(
+            bare.missing,
+            ["favicon", "apple-touch-icon", "manifest", "theme-color"]
+        );
+
+        let mut merged = icons.clone();
+        merged.merge(&bare);
+        assert_eq!(merged.icons, icons.icons);
+        assert_eq!(merged.missing.len(), 4);
+    }
+}
@@ -0,0 +1,310 @@
+use crate::analyzer::section::{Element, SectionAnalyzer};
+use crate::analyzer::AnalysisResult;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+
+/// Descriptor of a `srcset` candidate
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Descriptor {
+    /// `480w`, the image's width in pixels
+    Width(u32),
+    /// `2x`, or no descriptor for `1x`
+    Density(f64),
+    /// `100h` or anything else browsers ignore the candidate for
+    Other,
+}
+
+/// Candidates of a `srcset` attribute as `(url, descriptor)` pairs
+///
+/// Follows the HTML parsing rules closely enough for URLs containing
+/// commas, such as data URLs.
+///
+/// # Example
+/// ```
+/// use ferret::analyzer::images::{parse_srcset, Descriptor};
+/// assert_eq!(
+///     parse_srcset("a.jpg 480w, b.jpg 960w"),
+///     [("a.jpg", Descriptor::Width(480)), ("b.jpg", Descriptor::Width(960))]
+/// );
+/// assert_eq!(parse_srcset("a.jpg, b.jpg 2x")[0], ("a.jpg", Descriptor::Density(1.0)));
+/// ```
+pub fn parse_srcset(srcset: &str) -> Vec<(&str, Descriptor)> {
+    let mut candidates = Vec::new();
+    let mut rest = srcset;
+    loop {
+        rest = rest.trim_start_matches(|c: char| c == ',' || c.is_ascii_whitespace());
+        if rest.is_empty() {
+            break;
+        }
+        let end = rest
+            .find(|c: char| c.is_ascii_whitespace())
+            .unwrap_or(rest.len());
+        let url = &rest[..end];
+        rest = &rest[end..];
+        // A trailing comma ends a candidate without descriptors
+        if let Some(url) = url.strip_suffix(',') {
+            candidates.push((url.trim_end_matches(','), Descriptor::Density(1.0)));
+            continue;
+        }
+        let mut depth = 0usize;
+        let end = rest
+            .char_indices()
+            .find(|&(_, c)| {
+                match c {
+                    '(' => depth += 1,
+                    ')' => depth = depth.saturating_sub(1),
+                    ',' if depth == 0 => return true,
+                    _ => {}
+                }
+                false
+            })
+            .map_or(rest.len(), |(index, _)| index);
+        let descriptors = rest[..end].trim();
+        rest = &rest[end..];
+        candidates.push((url, parse_descriptor(descriptors)));
+    }
+    candidates
+}
+
+fn parse_descriptor(descriptor: &str) -> Descriptor {
+    if descriptor.is_empty() {
+        return Descriptor::Density(1.0);
+    }
+    if let Some(width) = descriptor.strip_suffix('w') {
+        if let Ok(width) = width.parse() {
+            return Descriptor::Width(width);
+        }
+    }
+    if let Some(density) = descriptor.strip_suffix('x') {
+        if let Ok(density) = density.parse() {
+            return Descriptor::Density(density);
+        }
+    }
+    Descriptor::Other
+}
+
+/// How images adapt to screen sizes and whether their space is reserved
+///
+/// Counts `<img>` elements, `srcset` candidates on images and on the
+/// `<source>` elements of `<picture>`, and the images lacking either. An
+/// image without `width` and `height` attributes makes the page shift once
+/// it loads (Cumulative Layout Shift). Vector images and data URLs need no
+/// responsive variants and are not counted as missing them.
+///
+/// # Example
+/// ```
+/// # use ferret::analyzer::stream::StreamAnalyzer;
+/// use ferret::analyzer::section::Section;
+///
+/// let analyzer = StreamAnalyzer::new(10).with_sections([Section::Images]);
+/// let result = analyzer.analyze_string(
+///     r#"<img src="a.jpg" srcset="a.jpg 1x, a@2x.jpg 2x" width="40" height="40">
+///        <picture><source srcset="b.webp 640w" sizes="50vw"><img src="b.jpg"></picture>
+///        <img src="c.jpg">"#,
+/// )?;
+/// let images = result.images.unwrap();
+/// assert_eq!(images.images, 3);
+/// assert_eq!(images.responsive, 2);
+/// assert_eq!(images.candidates, 3);
+/// assert_eq!(images.missing_dimensions, 2);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImageReport {
+    /// `<img>` elements
+    pub images: usize,
+    /// Images with a `srcset` or inside a `<picture>` with `<source>`
+    /// variants
+    pub responsive: usize,
+    /// Images inside a `<picture>`
+    pub in_picture: usize,
+    /// `<source>` elements inside a `<picture>`
+    pub sources: usize,
+    /// `srcset` candidates of images and sources
+    pub candidates: usize,
+    /// Candidates with a width descriptor, e.g. `480w`
+    pub width_descriptors: usize,
+    /// Candidates with a density descriptor, e.g. `2x`, or none
+    pub density_descriptors: usize,
+    /// `srcset`s with width descriptors but no `sizes`, which browsers
+    /// treat as `100vw`
+    pub missing_sizes: usize,
+    /// Raster images without `srcset` or `<picture>` variants
+    pub missing_variants: usize,
+    /// Images without both `width` and `height` attributes
+    pub missing_dimensions: usize,
+}
+
+impl ImageReport {
+    pub fn merge(&mut self, other: &ImageReport) {
+        self.images += other.images;
+        self.responsive += other.responsive;
+        self.in_picture += other.in_picture;
+        self.sources += other.sources;
+        self.candidates += other.candidates;
+        self.width_descriptors += other.width_descriptors;
+        self.density_descriptors += other.density_descriptors;
+        self.missing_sizes += other.missing_sizes;
+        self.missing_variants += other.missing_variants;
+        self.missing_dimensions += other.missing_dimensions;
+    }
+
+    /// Count the candidates of `srcset`
+    fn add_srcset(&mut self, srcset: &str, sizes: bool) {
+        let mut widths = false;
+        for (_, descriptor) in parse_srcset(srcset) {
+            self.candidates += 1;
+            match descriptor {
+                Descriptor::Width(_) => {
+                    self.width_descriptors += 1;
+                    widths = true;
+                }
+                Descriptor::Density(_) => self.density_descriptors += 1,
+                Descriptor::Other => {}
+            }
+        }
+        if widths && !sizes {
+            self.missing_sizes += 1;
+        }
+    }
+}
+
+/// Whether `src` needs no responsive variants
+fn is_scalable(src: &str) -> bool {
+    let src = src.trim();
+    if src.starts_with("data:") {
+        return true;
+    }
+    let path = src.split(['?', '#']).next().unwrap_or_default();
+    path.to_ascii_lowercase().ends_with(".svg")
+}
+
+/// Section analyzers only see start tags, so an `<img>` is taken to belong
+/// to the last `<picture>` opened before it, whose last child it is in
+/// valid markup
+#[derive(Default)]
+pub(crate) struct ImageAnalyzer {
+    report: ImageReport,
+    /// Inside a `<picture>` whose `<img>` hasn't been seen
+    picture: bool,
+    /// `<source>` variants of the current picture
+    picture_sources: usize,
+}
+
+impl SectionAnalyzer for ImageAnalyzer {
+    fn element(&mut self, element: &Element) {
+        match element.name {
+            "picture" => {
+                self.picture = true;
+                self.picture_sources = 0;
+            }
+            "source" if self.picture => {
+                self.report.sources += 1;
+                if let Some(srcset) = element.attribute("srcset") {
+                    self.picture_sources += 1;
+                    let sizes = element.attribute("sizes").is_some();
+                    self.report.add_srcset(srcset, sizes);
+                }
+            }
+            "img" => {
+                let report = &mut self.report;
+                report.images += 1;
+                let srcset = element
+                    .attribute("srcset")
+                    .filter(|srcset| !srcset.trim().is_empty());
+                if let Some(srcset) = srcset {
+                    report.add_srcset(srcset, element.attribute("sizes").is_some());
+                }
+                let picture = std::mem::take(&mut self.picture);
+                if picture {
+                    report.in_picture += 1;
+                }
+                if srcset.is_some() || (picture && self.picture_sources > 0) {
+                    report.responsive += 1;
+                } else if !element.attribute("src").is_some_and(is_scalable) {
+                    report.missing_variants += 1;
+                }
+                if element.attribute("width").is_none() || element.attribute("height").is_none() {
+                    report.missing_dimensions += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn finish(self: Box<Self>, _page: Option<&Url>, result: &mut AnalysisResult) {
+        result.images = Some(self.report);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::section::Section;
+    use crate::analyzer::stream::StreamAnalyzer;
+
+    #[test]
+    fn test_parse_srcset() {
+        assert_eq!(
+            parse_srcset(" a.jpg  1.5x ,b.jpg 100h,c.jpg,, d.jpg 2x"),
+            [
+                ("a.jpg", Descriptor::Density(1.5)),
+                ("b.jpg", Descriptor::Other),
+                ("c.jpg", Descriptor::Density(1.0)),
+                ("d.jpg", Descriptor::Density(2.0)),
+            ]
+        );
+        assert_eq!(
+            parse_srcset("data:image/png;base64,AAAA 1x, b.jpg 2x"),
+            [
+                ("data:image/png;base64,AAAA", Descriptor::Density(1.0)),
+                ("b.jpg", Descriptor::Density(2.0)),
+            ]
+        );
+        assert!(parse_srcset(" , ").is_empty());
+    }
+
+    #[test]
+    fn test_images() {
+        let analyzer = StreamAnalyzer::new(10).with_sections([Section::Images]);
+        let result = analyzer
+            .analyze_string(
+                r#"<body>
+                <img src="hero.jpg" srcset="hero-480.jpg 480w, hero-960.jpg 960w"
+                     sizes="(max-width: 600px) 480px, 960px" width="960" height="400">
+                <img src="wide.jpg" srcset="wide-480.jpg 480w, wide-960.jpg 960w">
+                <picture>
+                  <source type="image/avif" srcset="p.avif">
+                  <source type="image/webp" srcset="p.webp 1x, p@2x.webp 2x">
+                  <img src="p.jpg" alt="" width="10">
+                </picture>
+                <picture><img src="lonely.jpg"></picture>
+                <img src="logo.svg"><img src="data:image/gif;base64,R0lG">
+                <video><source src="v.mp4"></video>
+                <img src="plain.png" width="1" height="1">
+                </body>"#,
+            )
+            .unwrap();
+        let images = result.images.unwrap();
+        assert_eq!(
+            images,
+            ImageReport {
+                images: 7,
+                responsive: 3,
+                in_picture: 2,
+                sources: 2,
+                candidates: 7,
+                width_descriptors: 4,
+                density_descriptors: 3,
+                missing_sizes: 1,
+                missing_variants: 2,
+                missing_dimensions: 5,
+            }
+        );
+
+        let mut merged = images;
+        merged.merge(&images);
+        assert_eq!(merged.images, 14);
+        assert_eq!(merged.missing_dimensions, 10);
+    }
+}
@@ -0,0 +1,223 @@
+use crate::analyzer::stream::{StreamAnalyzer, StreamState};
+use crate::analyzer::AnalysisResult;
+use crate::sniff::ParseMode;
+use anyhow::Result;
+use quick_xml::events::Event;
+use quick_xml::reader::Reader;
+use reqwest::header::HeaderMap;
+use reqwest::Url;
+
+/// Push-based analyzer for documents that arrive in chunks
+///
+/// Network code can hand each chunk to `feed()` as soon as it is received and
+/// call `finish()` once the body is complete. Only the unparsed tail of the
+/// input (at most one incomplete tag or text run) is buffered between calls,
+/// so memory use stays constant regardless of document size.
+///
+/// # Example
+/// ```
+/// use ferret::analyzer::incremental::IncrementalAnalyzer;
+///
+/// let mut analyzer = IncrementalAnalyzer::new(10);
+/// analyzer.feed(b"<div class=\"a\"><p>Hel")?;
+/// analyzer.feed(b"lo</p></di")?;
+/// analyzer.feed(b"v>")?;
+/// let result = analyzer.finish()?;
+/// assert_eq!(result.tags["p"].count, 1);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub struct IncrementalAnalyzer {
+    state: StreamState,
+    pending: Vec<u8>,
+    consumed: usize,
+}
+
+impl IncrementalAnalyzer {
+    /// Create an incremental analyzer with default configuration
+    ///
+    /// Use [`StreamAnalyzer::incremental`] to inherit limits and other settings.
+    ///
+    /// # Arguments
+    /// * `top_values_limit` - Maximum number of unique values to track per attribute
+    pub fn new(top_values_limit: usize) -> Self {
+        StreamAnalyzer::new(top_values_limit).incremental()
+    }
+
+    pub(crate) fn from_state(state: StreamState) -> Self {
+        Self {
+            state,
+            pending: Vec::new(),
+            consumed: 0,
+        }
+    }
+
+    /// Switch parse mode before the first chunk is fed
+    pub(crate) fn set_mode(&mut self, mode: ParseMode) {
+        self.state.set_mode(mode);
+    }
+
+    /// Record the URL the document was fetched from, against which
+    /// sections resolve relative links
+    pub(crate) fn set_page(&mut self, page: Url) {
+        self.state.set_page(page);
+    }
+
+    /// Pass the headers of the response the document came with to the
+    /// sections
+    pub(crate) fn set_headers(&mut self, headers: &HeaderMap) {
+        self.state.set_headers(headers);
+    }
+
+    /// Total number of bytes fed so far
+    pub fn bytes_fed(&self) -> usize {
+        self.consumed + self.pending.len()
+    }
+
+    /// Feed the next chunk of the document
+    ///
+    /// Chunks may split the input at any byte, including inside tags or
+    /// multi-byte characters.
+    pub fn feed(&mut self, chunk: &[u8]) -> Result<()> {
+        self.pending.extend_from_slice(chunk);
+        self.state.check_position(self.bytes_fed())?;
+        self.process(false)
+    }
+
+    /// Process any buffered input and return the final result
+    pub fn finish(mut self) -> Result<AnalysisResult> {
+        self.process(true)?;
+        self.state.finish()
+    }
+
+    /// Parse as many complete events as possible from the pending buffer
+    ///
+    /// Unless `is_final` is set, text runs and markup truncated at the end of
+    /// the buffer are kept for the next call, since their remainder has not
+    /// arrived yet.
+    fn process(&mut self, is_final: bool) -> Result<()> {
+        let mut reader = Reader::from_reader(self.pending.as_slice());
+        reader.trim_text(true);
+        reader.check_end_names(false); // Be permissive with HTML
+
+        let mut committed = 0;
+        loop {
+            let event = reader.read_event();
+            let position = reader.buffer_position();
+
+            match event {
+                Ok(Event::Eof) => break,
+                Ok(Event::Text(_)) if !is_final => {
+                    // The text may continue in the next chunk; it is re-read
+                    // unless a following event commits past it.
+                }
+                Ok(_) if !is_final && self.pending[position - 1] != b'>' => {
+                    // quick-xml reports markup truncated by the end of the
+                    // buffer (e.g. `</di`) as a complete event
+                    break;
+                }
+                Ok(event) => {
+                    self.state.handle_event(&event, self.consumed + position)?;
+                    committed = position;
+                }
+                Err(quick_xml::Error::UnexpectedEof(_)) if !is_final => {
+                    // Markup cut off by the end of the chunk; wait for more data
+                    break;
+                }
+                Err(err) => {
+                    // Skip errors to be resilient with malformed HTML/XML
+                    if position == committed {
+                        break;
+                    }
+                    self.state.record_error(self.consumed + position, &err)?;
+                    committed = position;
+                }
+            }
+        }
+
+        if is_final {
+            committed = self.pending.len();
+        }
+        self.pending.drain(..committed);
+        self.consumed += committed;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::FerretError;
+    use crate::limits::Limits;
+
+    const HTML: &str = r#"<html><head><title>Test</title><!-- a > comment --></head>
+        <body class="main"><div id="content" data-x='1>2'>Hello <b>world</b></div>
+        <img src="a.png" /><p>Unclosed<div>Another</div></body></html>"#;
+
+    #[test]
+    fn test_matches_stream_analyzer_for_any_split() {
+        let expected = StreamAnalyzer::new(10).analyze_string(HTML).unwrap();
+        let bytes = HTML.as_bytes();
+
+        for split in 0..bytes.len() {
+            let mut analyzer = IncrementalAnalyzer::new(10);
+            analyzer.feed(&bytes[..split]).unwrap();
+            analyzer.feed(&bytes[split..]).unwrap();
+            let result = analyzer.finish().unwrap();
+
+            assert_eq!(result.max_depth, expected.max_depth, "split at {}", split);
+            assert_eq!(result.tags.len(), expected.tags.len(), "split at {}", split);
+            for (name, stats) in &expected.tags {
+                let actual = &result.tags[name];
+                assert_eq!(actual.count, stats.count, "split at {}", split);
+                assert_eq!(actual.attributes.len(), stats.attributes.len());
+            }
+        }
+    }
+
+    #[test]
+    fn test_byte_at_a_time() {
+        let mut analyzer = IncrementalAnalyzer::new(10);
+        for byte in HTML.as_bytes() {
+            analyzer.feed(std::slice::from_ref(byte)).unwrap();
+        }
+        let result = analyzer.finish().unwrap();
+
+        let div_stats = &result.tags["div"];
+        assert_eq!(div_stats.count, 2);
+        assert_eq!(
+            div_stats.attributes["data-x"].value_counts.get("1>2"),
+            Some(&1)
+        );
+    }
+
+    #[test]
+    fn test_pending_buffer_stays_small() {
+        let mut analyzer = IncrementalAnalyzer::new(10);
+        analyzer.feed(b"<root>").unwrap();
+        for _ in 0..1000 {
+            analyzer.feed(b"<item id=\"1\">value</item>").unwrap();
+            assert!(analyzer.pending.len() < 64);
+        }
+        analyzer.feed(b"</root>").unwrap();
+
+        let result = analyzer.finish().unwrap();
+        assert_eq!(result.tags["item"].count, 1000);
+    }
+
+    #[test]
+    fn test_limits_apply_to_fed_bytes() {
+        let mut analyzer = StreamAnalyzer::new(10)
+            .with_limits(Limits {
+                max_input_bytes: Some(8),
+                ..Limits::default()
+            })
+            .incremental();
+
+        analyzer.feed(b"<div>").unwrap();
+        let err = analyzer.feed(b"</div>").unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<FerretError>(),
+            Some(FerretError::InputTooLarge { limit: 8 })
+        ));
+    }
+}
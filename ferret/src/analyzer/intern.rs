@@ -0,0 +1,211 @@
+use crate::analyzer::{AttributeStats, TagStats};
+use std::collections::HashMap;
+
+/// Handle for an interned name
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct Symbol(u32);
+
+/// Deduplicates tag and attribute names
+///
+/// Documents repeat a few dozen names millions of times. Interning them
+/// allocates each name once per analysis; lookups of known names borrow
+/// the input and allocate nothing.
+#[derive(Debug, Default)]
+pub(crate) struct Interner {
+    symbols: HashMap<Box<str>, Symbol>,
+    names: Vec<Box<str>>,
+}
+
+impl Interner {
+    pub(crate) fn intern(&mut self, name: &str) -> Symbol {
+        if let Some(&symbol) = self.symbols.get(name) {
+            return symbol;
+        }
+        let symbol = Symbol(self.names.len() as u32);
+        self.names.push(name.into());
+        self.symbols.insert(name.into(), symbol);
+        symbol
+    }
+
+    pub(crate) fn resolve(&self, symbol: Symbol) -> &str {
+        &self.names[symbol.0 as usize]
+    }
+}
+
+/// Tag and attribute counts keyed by interned names
+///
+/// The analyzers count into this and only build the string-keyed maps of
+/// `AnalysisResult` once, when the result is requested.
+#[derive(Debug, Default)]
+pub(crate) struct TagCounter {
+    pub(crate) names: Interner,
+    /// Indexed by symbol; entries for attribute names keep a count of 0
+    tags: Vec<TagCount>,
+    /// Parent/child pairs, only counted when structure is tracked
+    children: HashMap<(Symbol, Symbol), usize>,
+    top_values_limit: usize,
+}
+
+#[derive(Debug, Default)]
+struct TagCount {
+    count: usize,
+    /// Elements have a handful of distinct attributes, so a linear scan
+    /// beats hashing
+    attributes: Vec<(Symbol, AttributeCount)>,
+}
+
+#[derive(Debug, Default)]
+struct AttributeCount {
+    count: usize,
+    value_counts: HashMap<String, usize>,
+}
+
+impl TagCounter {
+    pub(crate) fn new(top_values_limit: usize) -> Self {
+        Self {
+            top_values_limit,
+            ..Default::default()
+        }
+    }
+
+    /// Count one occurrence of the tag `name`
+    pub(crate) fn add_tag(&mut self, name: &str) -> Symbol {
+        let symbol = self.names.intern(name);
+        self.tag_mut(symbol).count += 1;
+        symbol
+    }
+
+    /// Count one occurrence of an attribute on a tag returned by `add_tag`
+    pub(crate) fn add_attribute(&mut self, tag: Symbol, name: &str, value: &str) {
+        let name = self.names.intern(name);
+        let top_values_limit = self.top_values_limit;
+        let attributes = &mut self.tag_mut(tag).attributes;
+        let index = match attributes.iter().position(|(attr, _)| *attr == name) {
+            Some(index) => index,
+            None => {
+                attributes.push((name, AttributeCount::default()));
+                attributes.len() - 1
+            }
+        };
+        let attr_stats = &mut attributes[index].1;
+        attr_stats.count += 1;
+
+        // Track top N values
+        // Optimization: Don't track new values if we've hit the limit,
+        // but continue counting existing values. Only new values allocate.
+        if let Some(count) = attr_stats.value_counts.get_mut(value) {
+            *count += 1;
+        } else if attr_stats.value_counts.len() < top_values_limit {
+            attr_stats.value_counts.insert(value.to_string(), 1);
+        }
+    }
+
+    /// Count one occurrence of `child` directly inside `parent`
+    pub(crate) fn add_child(&mut self, parent: Symbol, child: Symbol) {
+        *self.children.entry((parent, child)).or_insert(0) += 1;
+    }
+
+    fn tag_mut(&mut self, tag: Symbol) -> &mut TagCount {
+        let index = tag.0 as usize;
+        if index >= self.tags.len() {
+            self.tags.resize_with(index + 1, TagCount::default);
+        }
+        &mut self.tags[index]
+    }
+
+    /// Statistics in the form stored in `AnalysisResult::tags`
+    pub(crate) fn to_tags(&self) -> HashMap<String, TagStats> {
+        self.tags
+            .iter()
+            .enumerate()
+            .filter(|(_, tag_count)| tag_count.count > 0)
+            .map(|(index, tag_count)| {
+                let name = self.names.resolve(Symbol(index as u32)).to_string();
+                let attributes = tag_count
+                    .attributes
+                    .iter()
+                    .map(|(attr, attr_count)| {
+                        let name = self.names.resolve(*attr).to_string();
+                        let stats = AttributeStats {
+                            name: name.clone(),
+                            count: attr_count.count,
+                            value_counts: attr_count.value_counts.clone(),
+                            ..Default::default()
+                        };
+                        (name, stats)
+                    })
+                    .collect();
+                let stats = TagStats {
+                    name: name.clone(),
+                    count: tag_count.count,
+                    attributes,
+                    ..Default::default()
+                };
+                (name, stats)
+            })
+            .collect()
+    }
+
+    /// Parent/child counts in the form stored in `AnalysisResult::children`
+    pub(crate) fn to_children(&self) -> HashMap<String, HashMap<String, usize>> {
+        let mut children: HashMap<String, HashMap<String, usize>> = HashMap::new();
+        for (&(parent, child), &count) in &self.children {
+            children
+                .entry(self.names.resolve(parent).to_string())
+                .or_default()
+                .insert(self.names.resolve(child).to_string(), count);
+        }
+        children
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interner() {
+        let mut names = Interner::default();
+        let div = names.intern("div");
+        assert_eq!(names.intern("span"), names.intern("span"));
+        assert_eq!(names.intern("div"), div);
+        assert_ne!(names.intern("DIV"), div);
+        assert_eq!(names.resolve(div), "div");
+    }
+
+    #[test]
+    fn test_tag_counter() {
+        let mut counter = TagCounter::new(1);
+        for value in ["a", "b", "a"] {
+            let div = counter.add_tag("div");
+            counter.add_attribute(div, "class", value);
+        }
+        counter.add_tag("p");
+
+        let tags = counter.to_tags();
+        assert_eq!(tags["div"].name, "div");
+        assert_eq!(tags["div"].count, 3);
+        assert_eq!(tags["p"].count, 1);
+        assert!(tags["p"].attributes.is_empty());
+
+        let class = &tags["div"].attributes["class"];
+        assert_eq!(class.name, "class");
+        assert_eq!(class.count, 3);
+        assert_eq!(class.value_counts.len(), 1);
+        assert_eq!(class.value_counts.get("a"), Some(&2));
+    }
+
+    #[test]
+    fn test_children() {
+        let mut counter = TagCounter::new(1);
+        let ul = counter.add_tag("ul");
+        for _ in 0..3 {
+            let li = counter.add_tag("li");
+            counter.add_child(ul, li);
+        }
+
+        let children = counter.to_children();
+        assert_eq!(children.len(), 1);
+        assert_eq!(children["ul"]["li"], 3);
+    }
+}
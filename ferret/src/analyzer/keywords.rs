@@ -0,0 +1,288 @@
+use crate::analyzer::section::{Element, SectionAnalyzer};
+use crate::analyzer::AnalysisResult;
+use crate::language::{self, primary_subtag};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Keywords and two-word phrases kept per page
+pub const TOP_KEYWORDS: usize = 20;
+
+/// Words of visible text kept per page
+const MAX_WORDS: usize = 50_000;
+
+/// Elements whose text isn't shown
+const HIDDEN_TEXT: &[&str] = &["script", "style", "noscript", "template"];
+
+/// Elements that don't end a phrase, so `<b>open</b> source` makes
+/// `open source`
+const INLINE: &[&str] = &[
+    "a", "abbr", "b", "bdi", "bdo", "cite", "code", "data", "dfn", "em", "i", "kbd", "mark", "q",
+    "s", "samp", "small", "span", "strong", "sub", "sup", "time", "u", "var",
+];
+
+/// A word or phrase and how often it occurs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Keyword {
+    pub term: String,
+    pub count: usize,
+    /// Share of all words of visible text, in percent
+    pub density: f64,
+}
+
+/// Most frequent words and two-word phrases of the visible text
+///
+/// Text in `<script>`, `<style>`, `<noscript>` and `<template>` isn't
+/// counted. Stopwords of the page's language, from `<html lang>` or else
+/// detected, are left out of the keywords and phrases, as are numbers and
+/// single letters; pages in other languages keep their stopwords. Phrases
+/// don't span block elements. Merged results add up the kept keywords of
+/// each page, so terms just short of a page's top list are missed.
+///
+/// # Example
+/// ```
+/// # use ferret::analyzer::stream::StreamAnalyzer;
+/// use ferret::analyzer::section::Section;
+///
+/// let analyzer = StreamAnalyzer::new(10).with_sections([Section::Keywords]);
+/// let result = analyzer.analyze_string(
+///     "<html lang=en><h1>Rust web scraping</h1>
+///      <p>Web scraping with Rust is fast, and the tools for web scraping are good.</p></html>",
+/// )?;
+/// let keywords = result.keywords.unwrap();
+/// assert_eq!(keywords.words, 17);
+/// // Ties are in alphabetical order
+/// assert_eq!(keywords.top_words[0].term, "scraping");
+/// assert_eq!(keywords.top_words[1].term, "web");
+/// assert_eq!(keywords.top_phrases[0].term, "web scraping");
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct KeywordReport {
+    /// Words of visible text, stopwords included
+    pub words: usize,
+    /// ISO 639-1 code of the language whose stopwords were left out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    /// Up to [`TOP_KEYWORDS`] words, most frequent first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub top_words: Vec<Keyword>,
+    /// Up to [`TOP_KEYWORDS`] two-word phrases, most frequent first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub top_phrases: Vec<Keyword>,
+}
+
+impl KeywordReport {
+    pub fn merge(&mut self, other: &KeywordReport) {
+        self.words += other.words;
+        if self.language.is_none() {
+            self.language.clone_from(&other.language);
+        }
+        for (top, other_top) in [
+            (&mut self.top_words, &other.top_words),
+            (&mut self.top_phrases, &other.top_phrases),
+        ] {
+            let mut counts: HashMap<String, usize> = HashMap::new();
+            for keyword in top.iter().chain(other_top) {
+                *counts.entry(keyword.term.clone()).or_default() += keyword.count;
+            }
+            *top = ranked(counts, self.words);
+        }
+    }
+}
+
+/// The [`TOP_KEYWORDS`] most frequent of `counts`, ties in alphabetical
+/// order
+fn ranked(counts: HashMap<String, usize>, words: usize) -> Vec<Keyword> {
+    let mut keywords: Vec<Keyword> = counts
+        .into_iter()
+        .map(|(term, count)| Keyword {
+            term,
+            count,
+            density: count as f64 * 100.0 / words.max(1) as f64,
+        })
+        .collect();
+    keywords.sort_unstable_by(|a, b| b.count.cmp(&a.count).then_with(|| a.term.cmp(&b.term)));
+    keywords.truncate(TOP_KEYWORDS);
+    keywords
+}
+
+/// Whether `word` can be a keyword regardless of language
+fn is_term(word: &str) -> bool {
+    word.chars().nth(1).is_some() && !word.chars().all(|c| c.is_numeric())
+}
+
+/// Words are kept until the end of the document, when the page's
+/// language and so its stopwords are known
+#[derive(Default)]
+pub(crate) struct KeywordAnalyzer {
+    lang: Option<String>,
+    /// Inside an element from [`HIDDEN_TEXT`]
+    hidden: Option<String>,
+    /// Words of visible text and whether each starts a phrase
+    words: Vec<(String, bool)>,
+    /// A block element was opened or closed since the last word
+    boundary: bool,
+}
+
+impl SectionAnalyzer for KeywordAnalyzer {
+    fn element(&mut self, element: &Element) {
+        if element.name == "html" && self.lang.is_none() {
+            self.lang = element.attribute("lang").map(str::to_string);
+        }
+        if HIDDEN_TEXT.contains(&element.name) {
+            self.hidden = Some(element.name.to_string());
+        }
+        if !INLINE.contains(&element.name) {
+            self.boundary = true;
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        if self.hidden.is_some() {
+            return;
+        }
+        for word in language::words(text) {
+            if self.words.len() >= MAX_WORDS {
+                return;
+            }
+            let boundary = std::mem::take(&mut self.boundary);
+            self.words.push((word, boundary));
+        }
+    }
+
+    fn end(&mut self, name: &str) {
+        if self.hidden.as_deref() == Some(name) {
+            self.hidden = None;
+        }
+        if !INLINE.contains(&name) {
+            self.boundary = true;
+        }
+    }
+
+    fn finish(self: Box<Self>, _page: Option<&Url>, result: &mut AnalysisResult) {
+        let declared = self
+            .lang
+            .as_deref()
+            .map(primary_subtag)
+            .and_then(|lang| language::stopwords(lang).map(|_| lang.to_ascii_lowercase()));
+        let language = declared.or_else(|| {
+            language::detect(self.words.iter().map(|(word, _)| word)).map(str::to_string)
+        });
+        let stopwords = language
+            .as_deref()
+            .and_then(language::stopwords)
+            .unwrap_or_default();
+        let is_keyword = |word: &str| is_term(word) && !stopwords.contains(&word);
+
+        let mut words: HashMap<String, usize> = HashMap::new();
+        let mut phrases: HashMap<String, usize> = HashMap::new();
+        let mut previous: Option<&str> = None;
+        for (word, boundary) in &self.words {
+            if *boundary {
+                previous = None;
+            }
+            if !is_keyword(word) {
+                previous = None;
+                continue;
+            }
+            *words.entry(word.clone()).or_default() += 1;
+            if let Some(previous) = previous {
+                *phrases.entry(format!("{} {}", previous, word)).or_default() += 1;
+            }
+            previous = Some(word);
+        }
+        let total = self.words.len();
+        result.keywords = Some(KeywordReport {
+            words: total,
+            language,
+            top_words: ranked(words, total),
+            // A phrase found once is no keyword
+            top_phrases: ranked(
+                phrases
+                    .into_iter()
+                    .filter(|&(_, count)| count > 1)
+                    .collect(),
+                total,
+            ),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::section::Section;
+    use crate::analyzer::stream::StreamAnalyzer;
+
+    fn terms(keywords: &[Keyword]) -> Vec<(&str, usize)> {
+        keywords
+            .iter()
+            .map(|keyword| (keyword.term.as_str(), keyword.count))
+            .collect()
+    }
+
+    #[test]
+    fn test_keywords() {
+        let analyzer = StreamAnalyzer::new(10).with_sections([Section::Keywords]);
+        let result = analyzer
+            .analyze_string(
+                r#"<html><head><title>Open source tools</title>
+                <style>.open { source: 1 }</style></head><body>
+                <script>var open = "source";</script>
+                <h1>Open source</h1>
+                <p>We build <b>open</b> source tools for the web, and the web is open.</p>
+                <ul><li>Open</li><li>Source 2024</li></ul>
+                </body></html>"#,
+            )
+            .unwrap();
+        let keywords = result.keywords.unwrap();
+        assert_eq!(keywords.words, 21);
+        assert_eq!(keywords.language.as_deref(), Some("en"));
+        assert_eq!(
+            terms(&keywords.top_words),
+            [
+                ("open", 5),
+                ("source", 4),
+                ("tools", 2),
+                ("web", 2),
+                ("build", 1),
+            ]
+        );
+        assert!((keywords.top_words[0].density - 500.0 / 21.0).abs() < 1e-9);
+        assert_eq!(
+            terms(&keywords.top_phrases),
+            [("open source", 3), ("source tools", 2)]
+        );
+
+        let mut merged = keywords.clone();
+        merged.merge(&keywords);
+        assert_eq!(merged.words, 42);
+        assert_eq!(merged.top_words[0].count, 10);
+        assert_eq!(merged.top_words[0].density, keywords.top_words[0].density);
+    }
+
+    #[test]
+    fn test_keywords_language() {
+        let analyzer = StreamAnalyzer::new(10).with_sections([Section::Keywords]);
+        let german = analyzer
+            .analyze_string(
+                "<p>Die Katze und der Hund sind im Garten, und die Katze ist schneller als \
+                 der Hund.</p>",
+            )
+            .unwrap()
+            .keywords
+            .unwrap();
+        assert_eq!(german.language.as_deref(), Some("de"));
+        assert_eq!(terms(&german.top_words[..2]), [("hund", 2), ("katze", 2)]);
+
+        // Unknown languages keep their stopwords
+        let unknown = analyzer
+            .analyze_string(r#"<html lang="fi"><p>ja ja ja kissa</p></html>"#)
+            .unwrap()
+            .keywords
+            .unwrap();
+        assert_eq!(unknown.language, None);
+        assert_eq!(terms(&unknown.top_words), [("ja", 3), ("kissa", 1)]);
+    }
+}
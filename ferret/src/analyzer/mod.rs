@@ -1,8 +1,36 @@
+use crate::fetch::RedirectHop;
+use crate::monitor::{structure_features, Fingerprint};
+use crate::similarity::{cluster_templates, TemplateCluster};
+use anyhow::Result;
+use css::CssReport;
+use i18n::I18nReport;
+use icons::IconReport;
+use images::ImageReport;
+use intern::TagCounter;
+use keywords::KeywordReport;
+use perf::PerfReport;
+use security::SecurityReport;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::io::Read;
+use third_party::ThirdPartyReport;
 use tl::Node;
 
+pub mod archive;
+pub mod batch;
+pub mod chunked;
+pub mod css;
+pub mod i18n;
+pub mod icons;
+pub mod images;
+pub mod incremental;
+pub(crate) mod intern;
+pub mod keywords;
+pub mod perf;
+pub mod section;
+pub mod security;
 pub mod stream;
+pub mod third_party;
 
 pub trait Analyzer {
     fn visit(&mut self, node: &Node, depth: usize) -> bool;
@@ -14,6 +42,464 @@ pub struct AnalysisResult {
     pub tags: HashMap<String, TagStats>,
     pub files_analyzed: usize,
     pub max_depth: usize,
+    /// Number of elements at each nesting level; index 0 counts the
+    /// top-level elements
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub depth_counts: Vec<usize>,
+    /// Redirects followed before reaching the analyzed document (URL analysis only)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub redirects: Vec<RedirectHop>,
+    /// Markup errors skipped while parsing (stream mode), at most
+    /// `MAX_PARSE_ISSUES` per document
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub parse_errors: Vec<ParseIssue>,
+    /// How often each tag appears directly inside another, keyed by parent
+    /// and then child tag name; only filled when structure tracking is
+    /// enabled (see `StreamAnalyzer::with_structure`)
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub children: HashMap<String, HashMap<String, usize>>,
+    /// Outcome of checking the links on the analyzed pages; only filled by
+    /// crawls with link checking (see `Crawler::with_link_check`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub links: Option<LinkCounts>,
+    /// Resources loaded from other sites; only filled when the
+    /// `third-party` section is enabled (see `StreamAnalyzer::with_sections`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub third_party: Option<ThirdPartyReport>,
+    /// Subresource Integrity findings and response headers; only filled
+    /// when the `security` section is enabled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub security: Option<SecurityReport>,
+    /// Responsive image use; only filled when the `images` section is
+    /// enabled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub images: Option<ImageReport>,
+    /// Resource hints and render-blocking resources; only filled when the
+    /// `perf` section is enabled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub perf: Option<PerfReport>,
+    /// Languages and `hreflang` alternates; only filled when the `i18n`
+    /// section is enabled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub i18n: Option<I18nReport>,
+    /// Favicons, web app manifest and theme colors; only filled when the
+    /// `icons` section is enabled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icons: Option<IconReport>,
+    /// Most frequent words and phrases; only filled when the `keywords`
+    /// section is enabled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keywords: Option<KeywordReport>,
+    /// Classes and ids used in the HTML against those selected by its
+    /// CSS; only filled when the `css` section is enabled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub css: Option<CssReport>,
+}
+
+/// Link targets checked and how many of them failed or redirected
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LinkCounts {
+    pub checked: usize,
+    /// Answered with a 4xx or 5xx status, or not at all
+    pub broken: usize,
+    pub redirected: usize,
+    /// No response at all, e.g. an unknown host; also counted as broken
+    pub unreachable: usize,
+}
+
+/// Parse errors kept per document; later ones are only counted towards
+/// `Limits::max_parse_errors`
+pub const MAX_PARSE_ISSUES: usize = 100;
+
+/// A markup error the stream parser recovered from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParseIssue {
+    /// Byte offset in the (decompressed) input where the error was detected
+    pub position: usize,
+    pub message: String,
+}
+
+impl AnalysisResult {
+    /// Structural fingerprint, leaving out what occurs fewer than
+    /// [`DEFAULT_MIN_COUNT`](crate::monitor::DEFAULT_MIN_COUNT) times; see
+    /// [`Fingerprint`]
+    pub fn fingerprint(&self) -> Fingerprint {
+        self.fingerprint_with(crate::monitor::DEFAULT_MIN_COUNT)
+    }
+
+    /// Structural fingerprint of the features occurring at least
+    /// `min_count` times
+    pub fn fingerprint_with(&self, min_count: usize) -> Fingerprint {
+        Fingerprint::of(&structure_features(self, min_count))
+    }
+
+    /// Read a result written by `MsgpackExporter`
+    pub fn from_msgpack(bytes: &[u8]) -> Result<Self> {
+        Ok(rmp_serde::from_slice(bytes)?)
+    }
+
+    /// Read a result written by `MsgpackExporter` from a file or stream
+    pub fn from_msgpack_reader(reader: impl Read) -> Result<Self> {
+        Ok(rmp_serde::from_read(reader)?)
+    }
+
+    /// Read a result written by `CborExporter`
+    pub fn from_cbor(bytes: &[u8]) -> Result<Self> {
+        Self::from_cbor_reader(bytes)
+    }
+
+    /// Read a result written by `CborExporter` from a file or stream
+    pub fn from_cbor_reader(reader: impl Read) -> Result<Self> {
+        Ok(ciborium::from_reader(reader)?)
+    }
+
+    /// Rebuild a result from a comma-separated `CsvExporter` export
+    ///
+    /// Columns are matched by their headers, so exports with a BOM or a
+    /// subset of columns in any order load too, as long as `Tag` is one of
+    /// them. CSV only holds tag, attribute and value counts: depths,
+    /// redirects and parse errors stay empty and `files_analyzed` is 1.
+    /// Values are only restored together with their `Value Count`.
+    pub fn from_csv(reader: impl Read) -> Result<Self> {
+        Self::from_csv_with_delimiter(reader, b',')
+    }
+
+    /// Like [`from_csv`](Self::from_csv) for exports written with another
+    /// `CsvOptions::delimiter`
+    pub fn from_csv_with_delimiter(reader: impl Read, delimiter: u8) -> Result<Self> {
+        use crate::exporter::CsvColumn;
+
+        let mut rdr = csv::ReaderBuilder::new()
+            .delimiter(delimiter)
+            .from_reader(reader);
+        // Position of each column in the file, by `CsvColumn` discriminant
+        let mut positions = [None; 6];
+        for (position, header) in rdr.headers()?.iter().enumerate() {
+            let column: CsvColumn = header.parse()?;
+            positions[column as usize] = Some(position);
+        }
+        if positions[CsvColumn::Tag as usize].is_none() {
+            anyhow::bail!("CSV has no {} column", CsvColumn::Tag.header());
+        }
+
+        let mut result = AnalysisResult {
+            files_analyzed: 1,
+            ..Default::default()
+        };
+        for record in rdr.records() {
+            let record = record?;
+            let line = record.position().map_or(0, |position| position.line());
+            let field = |column: CsvColumn| {
+                positions[column as usize]
+                    .and_then(|position| record.get(position))
+                    .filter(|field| !field.is_empty())
+            };
+            let count = |column: CsvColumn| -> Result<Option<usize>> {
+                field(column)
+                    .map(|field| {
+                        field.parse().map_err(|_| {
+                            anyhow::anyhow!(
+                                "invalid {} {:?} on line {}",
+                                column.header(),
+                                field,
+                                line
+                            )
+                        })
+                    })
+                    .transpose()
+            };
+
+            let Some(tag_name) = field(CsvColumn::Tag) else {
+                anyhow::bail!("missing {} on line {}", CsvColumn::Tag.header(), line);
+            };
+            let tag = result
+                .tags
+                .entry(tag_name.to_string())
+                .or_insert_with(|| TagStats {
+                    name: tag_name.to_string(),
+                    ..Default::default()
+                });
+            if let Some(count) = count(CsvColumn::Count)? {
+                tag.count = count;
+            }
+
+            let Some(attr_name) = field(CsvColumn::Attribute) else {
+                continue;
+            };
+            let attr = tag
+                .attributes
+                .entry(attr_name.to_string())
+                .or_insert_with(|| AttributeStats {
+                    name: attr_name.to_string(),
+                    ..Default::default()
+                });
+            if let Some(count) = count(CsvColumn::AttributeCount)? {
+                attr.count = count;
+            }
+            // An empty value with a count is the empty string, e.g. `alt=""`
+            if let Some(value_count) = count(CsvColumn::ValueCount)? {
+                let value = field(CsvColumn::Value).unwrap_or_default();
+                attr.value_counts.insert(value.to_string(), value_count);
+            }
+        }
+        Ok(result)
+    }
+
+    /// Number of elements, summed over all tags
+    pub fn total_elements(&self) -> usize {
+        self.tags.values().map(|tag| tag.count).sum()
+    }
+
+    /// Number of elements of each tag
+    pub fn tag_counts(&self) -> BTreeMap<String, usize> {
+        self.tags
+            .iter()
+            .map(|(name, tag)| (name.clone(), tag.count))
+            .collect()
+    }
+
+    /// Number of distinct tag and attribute name pairs; `a@href` and
+    /// `link@href` count separately
+    pub fn distinct_attributes(&self) -> usize {
+        self.tags.values().map(|tag| tag.attributes.len()).sum()
+    }
+
+    /// Whether [`add_percentages`](Self::add_percentages) has filled in the
+    /// percentage fields
+    pub fn has_percentages(&self) -> bool {
+        self.tags.values().any(|tag| tag.percent.is_some())
+    }
+
+    /// Fill in `TagStats::percent` (tag count as a share of all elements)
+    /// and `AttributeStats::value_percents` (value count as a share of the
+    /// attribute's occurrences), so they are part of the serialized result
+    pub fn add_percentages(&mut self) {
+        let total = self.total_elements();
+        for tag in self.tags.values_mut() {
+            tag.percent = Some(percentage(tag.count, total));
+            for attr in tag.attributes.values_mut() {
+                attr.value_percents = attr
+                    .value_counts
+                    .iter()
+                    .map(|(value, &count)| (value.clone(), percentage(count, attr.count)))
+                    .collect();
+            }
+        }
+    }
+
+    /// Add the statistics of `other` to this result
+    ///
+    /// Tag, attribute, parent/child and depth counts are summed and
+    /// `max_depth` is the larger of both. Values are tracked like the
+    /// analyzers do: counts for known values are always combined, new
+    /// values only while fewer than `top_values_limit` are tracked for the
+    /// attribute. Percentages are recomputed if either side had them.
+    pub fn merge(&mut self, other: &AnalysisResult, top_values_limit: usize) {
+        let percentages = self.has_percentages() || other.has_percentages();
+        self.files_analyzed += other.files_analyzed;
+        self.max_depth = self.max_depth.max(other.max_depth);
+
+        if self.depth_counts.len() < other.depth_counts.len() {
+            self.depth_counts.resize(other.depth_counts.len(), 0);
+        }
+        for (count, other_count) in self.depth_counts.iter_mut().zip(&other.depth_counts) {
+            *count += other_count;
+        }
+
+        for (parent, other_children) in &other.children {
+            let children = self.children.entry(parent.clone()).or_default();
+            for (child, count) in other_children {
+                *children.entry(child.clone()).or_insert(0) += count;
+            }
+        }
+
+        for (tag_name, other_tag) in &other.tags {
+            let tag_stats = self
+                .tags
+                .entry(tag_name.clone())
+                .or_insert_with(|| TagStats {
+                    name: tag_name.clone(),
+                    ..Default::default()
+                });
+            tag_stats.count += other_tag.count;
+
+            for (attr_name, other_attr) in &other_tag.attributes {
+                let attr_stats = tag_stats
+                    .attributes
+                    .entry(attr_name.clone())
+                    .or_insert_with(|| AttributeStats {
+                        name: attr_name.clone(),
+                        ..Default::default()
+                    });
+                attr_stats.count += other_attr.count;
+
+                // Most frequent values first, so they win when the limit is reached
+                let mut values: Vec<_> = other_attr.value_counts.iter().collect();
+                values.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+                for (value, count) in values {
+                    if attr_stats.value_counts.len() < top_values_limit
+                        || attr_stats.value_counts.contains_key(value)
+                    {
+                        *attr_stats.value_counts.entry(value.clone()).or_insert(0) += count;
+                    }
+                }
+            }
+        }
+
+        if let Some(other_links) = other.links {
+            let links = self.links.get_or_insert_with(LinkCounts::default);
+            links.checked += other_links.checked;
+            links.broken += other_links.broken;
+            links.redirected += other_links.redirected;
+            links.unreachable += other_links.unreachable;
+        }
+
+        if let Some(other_third_party) = &other.third_party {
+            self.third_party
+                .get_or_insert_with(ThirdPartyReport::default)
+                .merge(other_third_party);
+        }
+        if let Some(other_security) = &other.security {
+            self.security
+                .get_or_insert_with(SecurityReport::default)
+                .merge(other_security);
+        }
+        if let Some(other_images) = &other.images {
+            self.images
+                .get_or_insert_with(ImageReport::default)
+                .merge(other_images);
+        }
+        if let Some(other_perf) = &other.perf {
+            self.perf
+                .get_or_insert_with(PerfReport::default)
+                .merge(other_perf);
+        }
+        if let Some(other_i18n) = &other.i18n {
+            self.i18n
+                .get_or_insert_with(I18nReport::default)
+                .merge(other_i18n);
+        }
+        if let Some(other_icons) = &other.icons {
+            self.icons
+                .get_or_insert_with(IconReport::default)
+                .merge(other_icons);
+        }
+        if let Some(other_keywords) = &other.keywords {
+            self.keywords
+                .get_or_insert_with(KeywordReport::default)
+                .merge(other_keywords);
+        }
+        if let Some(other_css) = &other.css {
+            self.css
+                .get_or_insert_with(CssReport::default)
+                .merge(other_css);
+        }
+
+        if percentages {
+            self.add_percentages();
+        }
+    }
+}
+
+/// Results for a batch of inputs, e.g. every file in a directory
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct AnalysisResultSet {
+    /// One entry per input, in input order
+    pub entries: Vec<SourceResult>,
+    /// All successful results merged together
+    pub aggregate: AnalysisResult,
+    /// Groups of entries sharing a template, once
+    /// [`cluster_templates`](Self::cluster_templates) ran
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub clusters: Vec<TemplateCluster>,
+}
+
+/// Outcome of analyzing a single input of a batch
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SourceResult {
+    /// File path, URL or archive member name
+    pub source: String,
+    pub result: Option<AnalysisResult>,
+    /// Why the input could not be analyzed
+    pub error: Option<String>,
+    /// Id of the entry's [`TemplateCluster`], once
+    /// [`AnalysisResultSet::cluster_templates`] ran
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cluster: Option<usize>,
+}
+
+impl SourceResult {
+    pub fn new(source: String, result: anyhow::Result<AnalysisResult>) -> Self {
+        match result {
+            Ok(result) => Self {
+                source,
+                result: Some(result),
+                error: None,
+                cluster: None,
+            },
+            Err(err) => Self {
+                source,
+                result: None,
+                error: Some(format!("{:#}", err)),
+                cluster: None,
+            },
+        }
+    }
+}
+
+impl AnalysisResultSet {
+    /// Collect per-source outcomes and merge the successful ones
+    pub fn from_results(
+        results: impl IntoIterator<Item = (String, anyhow::Result<AnalysisResult>)>,
+        top_values_limit: usize,
+    ) -> Self {
+        Self::from_entries(
+            results
+                .into_iter()
+                .map(|(source, result)| SourceResult::new(source, result)),
+            top_values_limit,
+        )
+    }
+
+    /// Collect entries, e.g. of several batches, and merge the successful ones
+    pub fn from_entries(
+        entries: impl IntoIterator<Item = SourceResult>,
+        top_values_limit: usize,
+    ) -> Self {
+        let mut set = Self::default();
+        for entry in entries {
+            if let Some(result) = &entry.result {
+                set.aggregate.merge(result, top_values_limit);
+            }
+            set.entries.push(entry);
+        }
+        set
+    }
+
+    /// Entries that failed to analyze
+    pub fn errors(&self) -> impl Iterator<Item = &SourceResult> {
+        self.entries.iter().filter(|entry| entry.error.is_some())
+    }
+
+    /// Group entries with near-identical structure, e.g. thousands of
+    /// product pages sharing one template, setting each entry's `cluster`
+    /// and filling `clusters`
+    ///
+    /// Entries are grouped when their [`SimHash`](crate::similarity::SimHash)es
+    /// differ in at most `max_distance` bits, directly or through other
+    /// entries; see [`DEFAULT_MAX_DISTANCE`](crate::similarity::DEFAULT_MAX_DISTANCE).
+    pub fn cluster_templates(&mut self, max_distance: u32) {
+        let (ids, clusters) = cluster_templates(
+            self.entries
+                .iter()
+                .map(|entry| (entry.source.as_str(), entry.result.as_ref())),
+            max_distance,
+        );
+        for (entry, id) in self.entries.iter_mut().zip(ids) {
+            entry.cluster = id;
+        }
+        self.clusters = clusters;
+    }
 }
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
@@ -21,6 +507,9 @@ pub struct TagStats {
     pub name: String,
     pub count: usize,
     pub attributes: HashMap<String, AttributeStats>,
+    /// Share of all elements in percent, see [`AnalysisResult::add_percentages`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub percent: Option<f64>,
 }
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
@@ -28,22 +517,42 @@ pub struct AttributeStats {
     pub name: String,
     pub count: usize,
     pub value_counts: HashMap<String, usize>,
+    /// Share of the attribute's occurrences per value in percent, see
+    /// [`AnalysisResult::add_percentages`]
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub value_percents: HashMap<String, f64>,
+}
+
+/// `part` as a percentage of `total`; zero when `total` is zero
+pub fn percentage(part: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        part as f64 * 100.0 / total as f64
+    }
+}
+
+/// Count one element at `depth` (1 for top-level elements)
+pub(crate) fn count_depth(depth_counts: &mut Vec<usize>, depth: usize) {
+    if depth_counts.len() < depth {
+        depth_counts.resize(depth, 0);
+    }
+    depth_counts[depth - 1] += 1;
 }
 
 pub struct StatsAnalyzer {
     result: AnalysisResult,
-    top_values_limit: usize,
+    counter: TagCounter,
 }
 
 impl StatsAnalyzer {
     pub fn new(top_values_limit: usize) -> Self {
         Self {
             result: AnalysisResult {
-                tags: HashMap::new(),
                 files_analyzed: 1, // Single file scope
-                max_depth: 0,
+                ..Default::default()
             },
-            top_values_limit,
+            counter: TagCounter::new(top_values_limit),
         }
     }
 }
@@ -55,45 +564,11 @@ impl Analyzer for StatsAnalyzer {
         }
 
         if let Some(tag) = node.as_tag() {
-            let tag_name = tag.name().as_utf8_str().to_string();
-
-            let tag_stats = self
-                .result
-                .tags
-                .entry(tag_name.clone())
-                .or_insert_with(|| TagStats {
-                    name: tag_name,
-                    count: 0,
-                    attributes: HashMap::new(),
-                });
-
-            tag_stats.count += 1;
-
+            count_depth(&mut self.result.depth_counts, depth + 1);
+            let tag_name = self.counter.add_tag(&tag.name().as_utf8_str());
             for (key, val_opt) in tag.attributes().iter() {
-                let attr_name = key.as_ref().to_string();
-                let val_str = val_opt
-                    .as_ref()
-                    .map(|v| v.as_ref().to_string())
-                    .unwrap_or_default();
-
-                let attr_stats = tag_stats
-                    .attributes
-                    .entry(attr_name.clone())
-                    .or_insert_with(|| AttributeStats {
-                        name: attr_name,
-                        count: 0,
-                        value_counts: HashMap::new(),
-                    });
-
-                attr_stats.count += 1;
-
-                // Track top N values
-                // Optimization: Don't track if over very large limit, but for now simple map
-                if attr_stats.value_counts.len() < self.top_values_limit
-                    || attr_stats.value_counts.contains_key(&val_str)
-                {
-                    *attr_stats.value_counts.entry(val_str).or_insert(0) += 1;
-                }
+                let value = val_opt.as_deref().unwrap_or_default();
+                self.counter.add_attribute(tag_name, &key, value);
             }
         }
 
@@ -101,7 +576,10 @@ impl Analyzer for StatsAnalyzer {
     }
 
     fn result(&self) -> AnalysisResult {
-        self.result.clone()
+        AnalysisResult {
+            tags: self.counter.to_tags(),
+            ..self.result.clone()
+        }
     }
 }
 
@@ -126,6 +604,7 @@ mod tests {
 
         assert_eq!(result.tags.get("div").map(|t| t.count), Some(1));
         assert_eq!(result.tags.get("p").map(|t| t.count), Some(2));
+        assert_eq!(result.depth_counts, [1, 2]);
 
         let div_stats = result.tags.get("div").unwrap();
         assert_eq!(div_stats.attributes.get("class").map(|a| a.count), Some(1));
@@ -139,4 +618,55 @@ mod tests {
             Some(&1)
         );
     }
+
+    #[test]
+    fn test_merge() {
+        let analyzer = stream::StreamAnalyzer::new(2);
+        let mut merged = analyzer
+            .analyze_string(r#"<div class="a"><p class="x">1</p></div>"#)
+            .unwrap();
+        let other = analyzer
+            .analyze_string(r#"<root><div class="c"/><div class="b"/><div class="b"/></root>"#)
+            .unwrap();
+        merged.merge(&other, 2);
+
+        assert_eq!(merged.files_analyzed, 2);
+        assert_eq!(merged.max_depth, 2);
+        assert_eq!(merged.depth_counts, [2, 4]);
+        assert_eq!(merged.tags["div"].count, 4);
+        assert_eq!(merged.tags["root"].count, 1);
+
+        let class = &merged.tags["div"].attributes["class"];
+        assert_eq!(class.count, 4);
+        // Only two values are tracked; the more frequent `b` wins over `c`
+        assert_eq!(class.value_counts.len(), 2);
+        assert_eq!(class.value_counts.get("b"), Some(&2));
+    }
+
+    #[test]
+    fn test_percentages() {
+        let analyzer = stream::StreamAnalyzer::new(5);
+        let mut result = analyzer
+            .analyze_string(r#"<ul><li class="a"/><li class="a"/><li class="b"/></ul>"#)
+            .unwrap();
+        assert!(!result.has_percentages());
+        let json = serde_json::to_string(&result).unwrap();
+        assert!(!json.contains("percent"));
+
+        result.add_percentages();
+        assert_eq!(result.tags["ul"].percent, Some(25.0));
+        assert_eq!(result.tags["li"].percent, Some(75.0));
+        let class = &result.tags["li"].attributes["class"];
+        assert!((class.value_percents["a"] - 200.0 / 3.0).abs() < 1e-9);
+
+        let json = serde_json::to_string(&result).unwrap();
+        let restored: AnalysisResult = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.tags["ul"].percent, Some(25.0));
+
+        // Merging keeps the percentages in line with the new counts
+        let other = analyzer.analyze_string("<ul><li/></ul>").unwrap();
+        result.merge(&other, 5);
+        assert_eq!(result.tags["ul"].percent, Some(100.0 / 3.0));
+        assert_eq!(percentage(1, 0), 0.0);
+    }
 }
@@ -0,0 +1,245 @@
+use crate::analyzer::section::{Element, SectionAnalyzer};
+use crate::analyzer::AnalysisResult;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+
+/// Resource hints, render-blocking resources and inline code of a page
+///
+/// Blocking scripts are external classic scripts without `async` or
+/// `defer`; module scripts are deferred by default. Blocking stylesheets
+/// are those without a `media` query, or with `all` or `screen`. Inline
+/// byte counts cover `<script>` and `<style>` contents with surrounding
+/// whitespace trimmed; `<script>` blocks of data types such as
+/// `application/ld+json` are not code and not counted.
+///
+/// # Example
+/// ```
+/// # use ferret::analyzer::stream::StreamAnalyzer;
+/// use ferret::analyzer::section::Section;
+///
+/// let analyzer = StreamAnalyzer::new(10).with_sections([Section::Perf]);
+/// let result = analyzer.analyze_string(
+///     r#"<head><link rel="preconnect" href="https://cdn.test">
+///        <link rel="stylesheet" href="site.css">
+///        <script src="app.js"></script><script src="stats.js" defer></script>
+///        <style>p{margin:0}</style></head>"#,
+/// )?;
+/// let perf = result.perf.unwrap();
+/// assert_eq!(perf.preconnect, 1);
+/// assert_eq!(perf.blocking_scripts, 1);
+/// assert_eq!(perf.blocking_stylesheets, 1);
+/// assert_eq!(perf.inline_style_bytes, 11);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PerfReport {
+    /// `<link rel="preload">`
+    pub preload: usize,
+    /// `<link rel="modulepreload">`
+    pub modulepreload: usize,
+    /// `<link rel="prefetch">`
+    pub prefetch: usize,
+    /// `<link rel="preconnect">`
+    pub preconnect: usize,
+    /// `<link rel="dns-prefetch">`
+    pub dns_prefetch: usize,
+    /// External scripts
+    pub scripts: usize,
+    pub async_scripts: usize,
+    pub defer_scripts: usize,
+    pub module_scripts: usize,
+    /// External classic scripts without `async` or `defer`
+    pub blocking_scripts: usize,
+    pub stylesheets: usize,
+    /// Stylesheets applying to every screen
+    pub blocking_stylesheets: usize,
+    pub inline_scripts: usize,
+    pub inline_script_bytes: usize,
+    pub inline_styles: usize,
+    pub inline_style_bytes: usize,
+}
+
+impl PerfReport {
+    pub fn merge(&mut self, other: &PerfReport) {
+        self.preload += other.preload;
+        self.modulepreload += other.modulepreload;
+        self.prefetch += other.prefetch;
+        self.preconnect += other.preconnect;
+        self.dns_prefetch += other.dns_prefetch;
+        self.scripts += other.scripts;
+        self.async_scripts += other.async_scripts;
+        self.defer_scripts += other.defer_scripts;
+        self.module_scripts += other.module_scripts;
+        self.blocking_scripts += other.blocking_scripts;
+        self.stylesheets += other.stylesheets;
+        self.blocking_stylesheets += other.blocking_stylesheets;
+        self.inline_scripts += other.inline_scripts;
+        self.inline_script_bytes += other.inline_script_bytes;
+        self.inline_styles += other.inline_styles;
+        self.inline_style_bytes += other.inline_style_bytes;
+    }
+}
+
+/// Whether a `<script type>` is run as JavaScript
+fn is_javascript(kind: Option<&str>) -> bool {
+    match kind.map(str::trim) {
+        None | Some("") => true,
+        Some(kind) => {
+            kind.eq_ignore_ascii_case("module")
+                || kind.eq_ignore_ascii_case("text/javascript")
+                || kind.eq_ignore_ascii_case("application/javascript")
+        }
+    }
+}
+
+/// Element whose text is being counted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Inline {
+    Script,
+    Style,
+}
+
+#[derive(Default)]
+pub(crate) struct PerfAnalyzer {
+    report: PerfReport,
+    inline: Option<Inline>,
+}
+
+impl SectionAnalyzer for PerfAnalyzer {
+    fn element(&mut self, element: &Element) {
+        let report = &mut self.report;
+        match element.name {
+            "link" => {
+                for (rel, count) in [
+                    ("preload", &mut report.preload),
+                    ("modulepreload", &mut report.modulepreload),
+                    ("prefetch", &mut report.prefetch),
+                    ("preconnect", &mut report.preconnect),
+                    ("dns-prefetch", &mut report.dns_prefetch),
+                ] {
+                    if element.has_rel(rel) {
+                        *count += 1;
+                    }
+                }
+                if element.has_rel("stylesheet") && !element.has_rel("alternate") {
+                    report.stylesheets += 1;
+                    let media = element.attribute("media").map(str::trim);
+                    if media.is_none_or(|media| {
+                        media.is_empty()
+                            || media.eq_ignore_ascii_case("all")
+                            || media.eq_ignore_ascii_case("screen")
+                    }) {
+                        report.blocking_stylesheets += 1;
+                    }
+                }
+            }
+            "script" => {
+                let kind = element.attribute("type");
+                if !is_javascript(kind) {
+                    return;
+                }
+                if element.attribute("src").is_none() {
+                    report.inline_scripts += 1;
+                    self.inline = Some(Inline::Script);
+                    return;
+                }
+                report.scripts += 1;
+                let module = kind.is_some_and(|kind| kind.trim().eq_ignore_ascii_case("module"));
+                let is_async = element.attribute("async").is_some();
+                let defer = element.attribute("defer").is_some();
+                if module {
+                    report.module_scripts += 1;
+                }
+                if is_async {
+                    report.async_scripts += 1;
+                } else if defer {
+                    report.defer_scripts += 1;
+                }
+                if !module && !is_async && !defer {
+                    report.blocking_scripts += 1;
+                }
+            }
+            "style" => {
+                report.inline_styles += 1;
+                self.inline = Some(Inline::Style);
+            }
+            _ => {}
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        match self.inline {
+            Some(Inline::Script) => self.report.inline_script_bytes += text.len(),
+            Some(Inline::Style) => self.report.inline_style_bytes += text.len(),
+            None => {}
+        }
+    }
+
+    fn end(&mut self, name: &str) {
+        if matches!(name, "script" | "style") {
+            self.inline = None;
+        }
+    }
+
+    fn finish(self: Box<Self>, _page: Option<&Url>, result: &mut AnalysisResult) {
+        result.perf = Some(self.report);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::section::Section;
+    use crate::analyzer::stream::StreamAnalyzer;
+
+    #[test]
+    fn test_perf() {
+        let analyzer = StreamAnalyzer::new(10).with_sections([Section::Perf]);
+        let result = analyzer
+            .analyze_string(
+                r#"<html><head>
+                <link rel="preload" href="font.woff2" as="font" crossorigin>
+                <link rel="modulepreload" href="app.mjs">
+                <link rel="dns-prefetch preconnect" href="https://cdn.test">
+                <link rel="prefetch" href="/next">
+                <link rel="stylesheet" href="site.css">
+                <link rel="stylesheet" href="print.css" media="print">
+                <link rel="alternate stylesheet" href="dark.css">
+                <script src="blocking.js"></script>
+                <script async src="a.js"></script>
+                <script defer src="d.js"></script>
+                <script type="module" src="app.mjs"></script>
+                <script>var x = 1;</script>
+                <script type="application/ld+json">{"@type": "Thing"}</script>
+                <style>body { color: red }</style>
+                </head><body><p>Not counted</p></body></html>"#,
+            )
+            .unwrap();
+        let perf = result.perf.unwrap();
+        assert_eq!(
+            perf,
+            PerfReport {
+                preload: 1,
+                modulepreload: 1,
+                prefetch: 1,
+                preconnect: 1,
+                dns_prefetch: 1,
+                scripts: 4,
+                async_scripts: 1,
+                defer_scripts: 1,
+                module_scripts: 1,
+                blocking_scripts: 1,
+                stylesheets: 2,
+                blocking_stylesheets: 1,
+                inline_scripts: 1,
+                inline_script_bytes: 10,
+                inline_styles: 1,
+                inline_style_bytes: 19,
+            }
+        );
+
+        let mut merged = perf;
+        merged.merge(&perf);
+        assert_eq!(merged.inline_style_bytes, 38);
+    }
+}
@@ -0,0 +1,211 @@
+//! Optional analyses of specific elements, each reported in its own section
+//! of the [`AnalysisResult`]
+//!
+//! Sections are enabled per analyzer with
+//! [`StreamAnalyzer::with_sections`](crate::analyzer::stream::StreamAnalyzer::with_sections)
+//! and see every element as it is parsed, next to the tag statistics.
+
+use crate::analyzer::css::CssAnalyzer;
+use crate::analyzer::i18n::I18nAnalyzer;
+use crate::analyzer::icons::IconAnalyzer;
+use crate::analyzer::images::ImageAnalyzer;
+use crate::analyzer::keywords::KeywordAnalyzer;
+use crate::analyzer::perf::PerfAnalyzer;
+use crate::analyzer::security::SecurityAnalyzer;
+use crate::analyzer::third_party::ThirdPartyAnalyzer;
+use crate::analyzer::AnalysisResult;
+use anyhow::{bail, Result};
+use reqwest::header::HeaderMap;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// An optional analysis and the result field it fills
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Section {
+    /// Scripts, stylesheets, iframes and images loaded from other sites,
+    /// into `third_party`
+    ThirdParty,
+    /// Cross-origin scripts and stylesheets without Subresource Integrity,
+    /// and security-relevant response headers, into `security`
+    Security,
+    /// `srcset`, `sizes` and `<picture>` use and images without
+    /// dimensions, into `images`
+    Images,
+    /// Resource hints, render-blocking scripts and stylesheets and inline
+    /// code size, into `perf`
+    Perf,
+    /// Declared and detected languages and `hreflang` alternates, into
+    /// `i18n`
+    I18n,
+    /// Favicons, apple-touch icons, the web app manifest and theme colors,
+    /// into `icons`
+    Icons,
+    /// Most frequent words and two-word phrases of the visible text, into
+    /// `keywords`
+    Keywords,
+    /// Classes and ids used in the HTML but not selected by its CSS and
+    /// the other way round, into `css`
+    Css,
+}
+
+impl Section {
+    pub const ALL: [Section; 8] = [
+        Section::ThirdParty,
+        Section::Security,
+        Section::Images,
+        Section::Perf,
+        Section::I18n,
+        Section::Icons,
+        Section::Keywords,
+        Section::Css,
+    ];
+
+    /// Name used in config files, query parameters and on the command line
+    pub fn name(self) -> &'static str {
+        match self {
+            Section::ThirdParty => "third-party",
+            Section::Security => "security",
+            Section::Images => "images",
+            Section::Perf => "perf",
+            Section::I18n => "i18n",
+            Section::Icons => "icons",
+            Section::Keywords => "keywords",
+            Section::Css => "css",
+        }
+    }
+
+    pub(crate) fn analyzer(self) -> Box<dyn SectionAnalyzer> {
+        match self {
+            Section::ThirdParty => Box::<ThirdPartyAnalyzer>::default(),
+            Section::Security => Box::<SecurityAnalyzer>::default(),
+            Section::Images => Box::<ImageAnalyzer>::default(),
+            Section::Perf => Box::<PerfAnalyzer>::default(),
+            Section::I18n => Box::<I18nAnalyzer>::default(),
+            Section::Icons => Box::<IconAnalyzer>::default(),
+            Section::Keywords => Box::<KeywordAnalyzer>::default(),
+            Section::Css => Box::<CssAnalyzer>::default(),
+        }
+    }
+
+    /// Whether `result` was analyzed with this section
+    pub(crate) fn is_in(self, result: &AnalysisResult) -> bool {
+        match self {
+            Section::ThirdParty => result.third_party.is_some(),
+            Section::Security => result.security.is_some(),
+            Section::Images => result.images.is_some(),
+            Section::Perf => result.perf.is_some(),
+            Section::I18n => result.i18n.is_some(),
+            Section::Icons => result.icons.is_some(),
+            Section::Keywords => result.keywords.is_some(),
+            Section::Css => result.css.is_some(),
+        }
+    }
+
+    pub(crate) fn remove_from(self, result: &mut AnalysisResult) {
+        match self {
+            Section::ThirdParty => result.third_party = None,
+            Section::Security => result.security = None,
+            Section::Images => result.images = None,
+            Section::Perf => result.perf = None,
+            Section::I18n => result.i18n = None,
+            Section::Icons => result.icons = None,
+            Section::Keywords => result.keywords = None,
+            Section::Css => result.css = None,
+        }
+    }
+}
+
+impl fmt::Display for Section {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Section {
+    type Err = anyhow::Error;
+
+    fn from_str(name: &str) -> Result<Self> {
+        let name = name.trim();
+        match Section::ALL
+            .into_iter()
+            .find(|section| section.name().eq_ignore_ascii_case(name))
+        {
+            Some(section) => Ok(section),
+            None => bail!(
+                "Unknown section {:?}; expected one of {}",
+                name,
+                Section::ALL.map(Section::name).join(", ")
+            ),
+        }
+    }
+}
+
+/// A start or self-closing tag as seen by section analyzers
+#[derive(Debug, Clone, Copy)]
+pub struct Element<'a> {
+    /// Lowercased in HTML mode
+    pub name: &'a str,
+    /// Attributes in document order, with entities decoded
+    pub attributes: &'a [(String, String)],
+    /// Nesting level, 1 for top-level elements
+    pub depth: usize,
+}
+
+impl<'a> Element<'a> {
+    /// Value of the attribute `name`, matched case-insensitively
+    pub fn attribute(&self, name: &str) -> Option<&'a str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Whether the space-separated `rel` attribute contains `token`
+    pub fn has_rel(&self, token: &str) -> bool {
+        self.attribute("rel").is_some_and(|rel| {
+            rel.split_ascii_whitespace()
+                .any(|rel| rel.eq_ignore_ascii_case(token))
+        })
+    }
+}
+
+/// Collects one section while a document is parsed
+pub(crate) trait SectionAnalyzer: Send {
+    fn element(&mut self, element: &Element);
+
+    /// Text content, with entities decoded and surrounding whitespace
+    /// trimmed
+    fn text(&mut self, _text: &str) {}
+
+    /// An end tag, lowercased in HTML mode
+    fn end(&mut self, _name: &str) {}
+
+    /// Headers of the response the document came with; not called for
+    /// local input
+    fn headers(&mut self, _headers: &HeaderMap) {}
+
+    /// Add the section to `result`; `page` is the URL the document was
+    /// fetched from, if any
+    fn finish(self: Box<Self>, page: Option<&Url>, result: &mut AnalysisResult);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_section_names() {
+        for section in Section::ALL {
+            assert_eq!(section.name().parse::<Section>().unwrap(), section);
+        }
+        assert_eq!(
+            " Third-Party".parse::<Section>().unwrap(),
+            Section::ThirdParty
+        );
+        assert_eq!("SECURITY".parse::<Section>().unwrap(), Section::Security);
+        assert!("seo".parse::<Section>().is_err());
+    }
+}
@@ -0,0 +1,359 @@
+use crate::analyzer::section::{Element, SectionAnalyzer};
+use crate::analyzer::AnalysisResult;
+use reqwest::header::HeaderMap;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Response headers recorded by the `security` section
+pub const SECURITY_HEADERS: &[&str] = &[
+    "content-security-policy",
+    "content-security-policy-report-only",
+    "strict-transport-security",
+    "x-content-type-options",
+    "x-frame-options",
+    "referrer-policy",
+    "permissions-policy",
+    "cross-origin-opener-policy",
+    "cross-origin-embedder-policy",
+    "cross-origin-resource-policy",
+];
+
+/// What is wrong with an element
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FindingKind {
+    /// A cross-origin script or stylesheet without an `integrity` hash;
+    /// whoever controls the other origin controls what the page runs
+    MissingIntegrity,
+    /// An `integrity` hash on a cross-origin resource without a
+    /// `crossorigin` attribute; browsers refuse to check it and block the
+    /// resource
+    MissingCrossorigin,
+}
+
+impl fmt::Display for FindingKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FindingKind::MissingIntegrity => "missing integrity",
+            FindingKind::MissingCrossorigin => "integrity without crossorigin",
+        })
+    }
+}
+
+/// An element loading a resource unsafely
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct SecurityFinding {
+    pub kind: FindingKind,
+    /// `script` or `link`
+    pub element: String,
+    /// The resource as written in the document
+    pub url: String,
+}
+
+impl fmt::Display for SecurityFinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<{}> {}: {}", self.element, self.url, self.kind)
+    }
+}
+
+/// Subresource Integrity findings and security-relevant response headers
+///
+/// Scripts, stylesheets and preloads of either count as cross-origin when
+/// their origin differs from the page's. Without a page URL, as for local
+/// files, the origin is taken from `<base href>` or
+/// `<link rel="canonical">`; failing that, every absolute URL counts as
+/// cross-origin. Headers are only known for URL analyses.
+///
+/// Merged results list each finding and missing header once.
+///
+/// # Example
+/// ```
+/// # use ferret::analyzer::stream::StreamAnalyzer;
+/// use ferret::analyzer::section::Section;
+/// use ferret::analyzer::security::FindingKind;
+///
+/// let analyzer = StreamAnalyzer::new(10).with_sections([Section::Security]);
+/// let result = analyzer.analyze_string(
+///     r#"<script src="/app.js"></script>
+///        <script src="https://cdn.test/lib.js"></script>
+///        <script src="https://cdn.test/ok.js" integrity="sha384-abc" crossorigin></script>"#,
+/// )?;
+/// let report = result.security.unwrap();
+/// assert_eq!(report.findings.len(), 1);
+/// assert_eq!(report.findings[0].kind, FindingKind::MissingIntegrity);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SecurityReport {
+    pub findings: Vec<SecurityFinding>,
+    /// Values of the [`SECURITY_HEADERS`] the response had, by lowercase
+    /// name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+    /// Recommended headers the response lacked, see
+    /// [`SecurityReport::missing_headers`]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub missing_headers: Vec<String>,
+}
+
+impl SecurityReport {
+    /// Recommended headers absent from `headers`, the response of `page`
+    ///
+    /// These are a Content Security Policy, `X-Content-Type-Options`,
+    /// `Referrer-Policy`, protection against framing by either
+    /// `X-Frame-Options` or the CSP's `frame-ancestors`, and
+    /// `Strict-Transport-Security` on https pages.
+    pub fn missing_headers(headers: &BTreeMap<String, String>, page: Option<&Url>) -> Vec<String> {
+        let csp = headers.get("content-security-policy");
+        let framing = headers.contains_key("x-frame-options")
+            || csp.is_some_and(|csp| {
+                csp.split(';')
+                    .any(|directive| directive.trim_start().starts_with("frame-ancestors"))
+            });
+        let https = page.is_some_and(|page| page.scheme() == "https");
+        [
+            ("content-security-policy", csp.is_some()),
+            (
+                "strict-transport-security",
+                !https || headers.contains_key("strict-transport-security"),
+            ),
+            (
+                "x-content-type-options",
+                headers.contains_key("x-content-type-options"),
+            ),
+            ("x-frame-options", framing),
+            ("referrer-policy", headers.contains_key("referrer-policy")),
+        ]
+        .into_iter()
+        .filter(|(_, present)| !present)
+        .map(|(name, _)| name.to_string())
+        .collect()
+    }
+
+    pub fn merge(&mut self, other: &SecurityReport) {
+        for finding in &other.findings {
+            if !self.findings.contains(finding) {
+                self.findings.push(finding.clone());
+            }
+        }
+        for (name, value) in &other.headers {
+            self.headers
+                .entry(name.clone())
+                .or_insert_with(|| value.clone());
+        }
+        for name in &other.missing_headers {
+            if !self.missing_headers.contains(name) {
+                self.missing_headers.push(name.clone());
+            }
+        }
+    }
+}
+
+/// A script or stylesheet reference and its SRI attributes
+struct Resource {
+    element: &'static str,
+    url: String,
+    integrity: bool,
+    crossorigin: bool,
+}
+
+#[derive(Default)]
+pub(crate) struct SecurityAnalyzer {
+    resources: Vec<Resource>,
+    base: Option<String>,
+    canonical: Option<String>,
+    /// `None` unless the document came with response headers
+    headers: Option<BTreeMap<String, String>>,
+}
+
+impl SectionAnalyzer for SecurityAnalyzer {
+    fn element(&mut self, element: &Element) {
+        let (name, url) = match element.name {
+            "script" => ("script", element.attribute("src")),
+            "link" if element.has_rel("canonical") => {
+                self.canonical = element.attribute("href").map(str::to_string);
+                return;
+            }
+            "link"
+                if element.has_rel("stylesheet")
+                    || element.has_rel("modulepreload")
+                    || (element.has_rel("preload")
+                        && element
+                            .attribute("as")
+                            .is_some_and(|kind| matches!(kind, "script" | "style"))) =>
+            {
+                ("link", element.attribute("href"))
+            }
+            "base" => {
+                self.base = element.attribute("href").map(str::to_string);
+                return;
+            }
+            _ => return,
+        };
+        let Some(url) = url.map(str::trim).filter(|url| !url.is_empty()) else {
+            return;
+        };
+        self.resources.push(Resource {
+            element: name,
+            url: url.to_string(),
+            integrity: element
+                .attribute("integrity")
+                .is_some_and(|integrity| !integrity.trim().is_empty()),
+            crossorigin: element.attribute("crossorigin").is_some(),
+        });
+    }
+
+    fn headers(&mut self, headers: &HeaderMap) {
+        let recorded = SECURITY_HEADERS
+            .iter()
+            .filter_map(|&name| {
+                let values: Vec<&str> = headers
+                    .get_all(name)
+                    .iter()
+                    .filter_map(|value| value.to_str().ok())
+                    .collect();
+                (!values.is_empty()).then(|| (name.to_string(), values.join(", ")))
+            })
+            .collect();
+        self.headers = Some(recorded);
+    }
+
+    fn finish(self: Box<Self>, page: Option<&Url>, result: &mut AnalysisResult) {
+        let hint = || {
+            let hint = self.base.as_deref().or(self.canonical.as_deref())?;
+            Url::parse(hint).ok()
+        };
+        let base = page.cloned().or_else(hint);
+
+        let mut report = SecurityReport::default();
+        for resource in &self.resources {
+            let resolved = match &base {
+                Some(base) => base.join(&resource.url),
+                None if resource.url.starts_with("//") => {
+                    Url::parse(&format!("https:{}", resource.url))
+                }
+                None => Url::parse(&resource.url),
+            };
+            let cross_origin = match (&resolved, &base) {
+                // Relative to an unknown page
+                (Err(_), _) => false,
+                (Ok(resolved), _) if !matches!(resolved.scheme(), "http" | "https") => false,
+                (Ok(resolved), Some(base)) => resolved.origin() != base.origin(),
+                (Ok(_), None) => true,
+            };
+            if !cross_origin {
+                continue;
+            }
+            let kind = match (resource.integrity, resource.crossorigin) {
+                (false, _) => FindingKind::MissingIntegrity,
+                (true, false) => FindingKind::MissingCrossorigin,
+                (true, true) => continue,
+            };
+            report.findings.push(SecurityFinding {
+                kind,
+                element: resource.element.to_string(),
+                url: resource.url.clone(),
+            });
+        }
+        if let Some(headers) = self.headers {
+            report.missing_headers = SecurityReport::missing_headers(&headers, page);
+            report.headers = headers;
+        }
+        result.security = Some(report);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::section::Section;
+    use crate::analyzer::stream::StreamAnalyzer;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn test_security_findings() {
+        let analyzer = StreamAnalyzer::new(10).with_sections([Section::Security]);
+        let html = r#"<html><head>
+            <script src="https://www.example.com/same.js"></script>
+            <script src="https://static.example.com/other.js"></script>
+            <link rel="stylesheet" href="https://cdn.test/a.css" integrity="sha384-x">
+            <link rel="preload" as="script" href="https://cdn.test/b.js"
+                  integrity="sha384-y" crossorigin="anonymous">
+            <link rel="preload" as="image" href="https://cdn.test/c.png">
+            <link rel="icon" href="https://cdn.test/favicon.ico">
+            <script>inline()</script>
+            </head></html>"#;
+        let mut state = analyzer.incremental();
+        state.set_page(Url::parse("https://www.example.com/").unwrap());
+        state.feed(html.as_bytes()).unwrap();
+        let report = state.finish().unwrap().security.unwrap();
+        assert_eq!(
+            report.findings,
+            [
+                SecurityFinding {
+                    kind: FindingKind::MissingIntegrity,
+                    element: "script".to_string(),
+                    url: "https://static.example.com/other.js".to_string(),
+                },
+                SecurityFinding {
+                    kind: FindingKind::MissingCrossorigin,
+                    element: "link".to_string(),
+                    url: "https://cdn.test/a.css".to_string(),
+                },
+            ]
+        );
+        assert_eq!(
+            report.findings[1].to_string(),
+            "<link> https://cdn.test/a.css: integrity without crossorigin"
+        );
+        // No response, so nothing is known about headers
+        assert!(report.headers.is_empty());
+        assert!(report.missing_headers.is_empty());
+
+        let mut merged = report.clone();
+        merged.merge(&report);
+        assert_eq!(merged, report);
+    }
+
+    #[test]
+    fn test_security_headers() {
+        let analyzer = StreamAnalyzer::new(10).with_sections([Section::Security]);
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "content-security-policy",
+            HeaderValue::from_static("default-src 'self'; frame-ancestors 'none'"),
+        );
+        headers.insert(
+            "x-content-type-options",
+            HeaderValue::from_static("nosniff"),
+        );
+        headers.insert("server", HeaderValue::from_static("nginx"));
+
+        let mut state = analyzer.incremental();
+        state.set_page(Url::parse("https://example.com/").unwrap());
+        state.set_headers(&headers);
+        state.feed(b"<p>hi</p>").unwrap();
+        let report = state.finish().unwrap().security.unwrap();
+        assert_eq!(
+            report.headers.keys().collect::<Vec<_>>(),
+            ["content-security-policy", "x-content-type-options"]
+        );
+        assert_eq!(
+            report.missing_headers,
+            ["strict-transport-security", "referrer-policy"]
+        );
+
+        let http = Url::parse("http://example.com/").unwrap();
+        assert_eq!(
+            SecurityReport::missing_headers(&BTreeMap::new(), Some(&http)),
+            [
+                "content-security-policy",
+                "x-content-type-options",
+                "x-frame-options",
+                "referrer-policy"
+            ]
+        );
+    }
+}
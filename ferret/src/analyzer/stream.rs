@@ -1,12 +1,38 @@
-use crate::analyzer::{AnalysisResult, AttributeStats, TagStats};
+use crate::analyzer::incremental::IncrementalAnalyzer;
+use crate::analyzer::intern::{Symbol, TagCounter};
+use crate::analyzer::section::{Element, Section, SectionAnalyzer};
+use crate::analyzer::{count_depth, AnalysisResult, ParseIssue, MAX_PARSE_ISSUES};
+use crate::cache::{CacheWriter, HttpCache};
+use crate::error::FerretError;
+use crate::fetch::{FetchOptions, Fetched};
+use crate::limits::Limits;
+use crate::progress::{CancellationToken, Progress, ProgressEvent, REPORT_INTERVAL_BYTES};
+#[cfg(feature = "rendered")]
+use crate::render::{BrowserOptions, Renderer};
+use crate::sniff::{self, ParseMode};
 use anyhow::Result;
-use quick_xml::events::Event;
+use flate2::read::MultiGzDecoder;
+use futures::StreamExt;
+use quick_xml::events::{BytesStart, Event};
 use quick_xml::reader::Reader;
-use std::collections::HashMap;
+use reqwest::header::{HeaderMap, CONTENT_TYPE};
+use reqwest::{StatusCode, Url};
 use std::fs::File;
-use std::io::{BufReader, Cursor};
+use std::io::{BufRead, BufReader, Write};
 use std::path::Path;
 
+/// Leading bytes of every gzip stream
+pub(crate) const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Read buffer for stdin; larger than the default to cut down on syscalls
+/// when large documents are piped in
+const STDIN_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Files at least this large are memory-mapped when the `mmap` feature is
+/// enabled; for smaller ones setting up the map costs more than it saves
+#[cfg(feature = "mmap")]
+const MMAP_THRESHOLD: usize = 1024 * 1024;
+
 /// Stream-based analyzer for large files and URLs
 ///
 /// Unlike StatsAnalyzer which loads the entire document into memory,
@@ -18,6 +44,21 @@ use std::path::Path;
 pub struct StreamAnalyzer {
     pub top_values_limit: usize,
     pub proxy_url: Option<String>,
+    pub limits: Limits,
+    pub fetch: FetchOptions,
+    pub cache: Option<HttpCache>,
+    pub progress: Option<Progress>,
+    /// Aborts running analyses with `FerretError::Cancelled` once cancelled
+    pub cancel: Option<CancellationToken>,
+    /// HTML or XML handling; `Auto` lets URL fetches detect it
+    pub mode: ParseMode,
+    /// Count parent/child tag pairs into `AnalysisResult::children`
+    pub structure: bool,
+    /// Optional analyses added to each result
+    pub sections: Vec<Section>,
+    /// Render URLs in a headless browser instead of fetching them
+    #[cfg(feature = "rendered")]
+    pub render: Option<BrowserOptions>,
 }
 
 impl StreamAnalyzer {
@@ -29,6 +70,16 @@ impl StreamAnalyzer {
         Self {
             top_values_limit,
             proxy_url: None,
+            limits: Limits::default(),
+            fetch: FetchOptions::default(),
+            cache: None,
+            progress: None,
+            cancel: None,
+            mode: ParseMode::Auto,
+            structure: false,
+            sections: Vec::new(),
+            #[cfg(feature = "rendered")]
+            render: None,
         }
     }
 
@@ -38,49 +89,276 @@ impl StreamAnalyzer {
     /// * `top_values_limit` - Maximum number of unique values to track per attribute
     /// * `proxy_url` - Base URL of the CORS proxy server (e.g., "http://localhost:8080/")
     ///
+    /// For a regular HTTP or SOCKS5 forward proxy use
+    /// [`FetchOptions::proxy`](crate::fetch::FetchOptions::proxy) instead.
+    ///
     /// # Example
-    /// ```
+    /// ```no_run
+    /// # use ferret::analyzer::stream::StreamAnalyzer;
+    /// # async fn run() -> anyhow::Result<()> {
     /// let analyzer = StreamAnalyzer::with_proxy(10, "http://localhost:8080/".to_string());
     /// let result = analyzer.analyze_url("https://example.com/data.xml").await?;
+    /// # Ok(())
+    /// # }
     /// ```
     pub fn with_proxy(top_values_limit: usize, proxy_url: String) -> Self {
         Self {
-            top_values_limit,
             proxy_url: Some(proxy_url),
+            ..Self::new(top_values_limit)
+        }
+    }
+
+    /// Abort analysis with a `FerretError` once any of the given limits is exceeded
+    ///
+    /// # Example
+    /// ```
+    /// # use ferret::analyzer::stream::StreamAnalyzer;
+    /// use ferret::limits::Limits;
+    /// let analyzer = StreamAnalyzer::new(10).with_limits(Limits::untrusted());
+    /// assert!(analyzer.analyze_string("<div></div>").is_ok());
+    /// ```
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Use custom headers, User-Agent, auth and cookies for URL fetches
+    ///
+    /// # Example
+    /// ```
+    /// # use ferret::analyzer::stream::StreamAnalyzer;
+    /// use ferret::fetch::FetchOptions;
+    /// let analyzer = StreamAnalyzer::new(10)
+    ///     .with_fetch_options(FetchOptions::default().user_agent("Mozilla/5.0"));
+    /// ```
+    pub fn with_fetch_options(mut self, fetch: FetchOptions) -> Self {
+        self.fetch = fetch;
+        self
+    }
+
+    /// Cache fetched documents on disk and revalidate them on later runs
+    ///
+    /// See [`HttpCache`] for details.
+    pub fn with_cache(mut self, cache: HttpCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Report bytes read, elements processed and completed batch sources
+    ///
+    /// See [`Progress`] for details.
+    pub fn with_progress(mut self, progress: Progress) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    /// Abort analysis cleanly when `token` is cancelled
+    ///
+    /// Running analyses stop at the next element or network read and
+    /// return `FerretError::Cancelled`; batches report the remaining
+    /// sources as cancelled.
+    ///
+    /// # Example
+    /// ```
+    /// # use ferret::analyzer::stream::StreamAnalyzer;
+    /// use ferret::progress::CancellationToken;
+    /// let token = CancellationToken::new();
+    /// let analyzer = StreamAnalyzer::new(10).with_cancellation(token.clone());
+    /// token.cancel();
+    /// assert!(analyzer.analyze_string("<div></div>").is_err());
+    /// ```
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    /// Force HTML or strict XML handling instead of detecting it
+    ///
+    /// # Example
+    /// ```
+    /// # use ferret::analyzer::stream::StreamAnalyzer;
+    /// use ferret::sniff::ParseMode;
+    /// let analyzer = StreamAnalyzer::new(10).with_mode(ParseMode::Xml);
+    /// let result = analyzer.analyze_string("<feed><entry></feed>")?;
+    /// assert_eq!(result.parse_errors.len(), 1);
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn with_mode(mut self, mode: ParseMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Also count which tags appear directly inside which, into
+    /// `AnalysisResult::children`
+    ///
+    /// In HTML mode the names are lowercased, and elements closed
+    /// implicitly (such as a `<p>` followed by another `<p>`) are siblings.
+    ///
+    /// # Example
+    /// ```
+    /// # use ferret::analyzer::stream::StreamAnalyzer;
+    /// let analyzer = StreamAnalyzer::new(10).with_structure(true);
+    /// let result = analyzer.analyze_string("<ul><li>a<li>b</ul>")?;
+    /// assert_eq!(result.children["ul"]["li"], 2);
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn with_structure(mut self, structure: bool) -> Self {
+        self.structure = structure;
+        self
+    }
+
+    /// Also run the given optional analyses, each filling its own field
+    /// of the result; see [`Section`]
+    ///
+    /// # Example
+    /// ```
+    /// # use ferret::analyzer::stream::StreamAnalyzer;
+    /// use ferret::analyzer::section::Section;
+    /// let analyzer = StreamAnalyzer::new(10).with_sections([Section::ThirdParty]);
+    /// let result = analyzer.analyze_string(r#"<script src="https://cdn.test/x.js"></script>"#)?;
+    /// assert_eq!(result.third_party.unwrap().origins.len(), 1);
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn with_sections(mut self, sections: impl IntoIterator<Item = Section>) -> Self {
+        for section in sections {
+            if !self.sections.contains(&section) {
+                self.sections.push(section);
+            }
         }
+        self
+    }
+
+    /// Analyze URLs as a headless browser renders them, after their
+    /// scripts ran; see [`Renderer`](crate::render::Renderer)
+    ///
+    /// Rendered pages are always parsed as HTML. The host policy,
+    /// robots.txt and throttle of the fetch options are applied to the URL
+    /// before the browser loads it, but not to the redirects it follows or
+    /// the resources the page loads; the other fetch options and the cache
+    /// don't apply.
+    #[cfg(feature = "rendered")]
+    pub fn with_render(mut self, options: BrowserOptions) -> Self {
+        self.render = Some(options);
+        self
     }
 
     /// Analyze a local file
     ///
+    /// Gzip-compressed files (detected by the `.gz` extension or the gzip
+    /// magic bytes) are decompressed transparently while streaming.
+    ///
+    /// With the `mmap` feature, uncompressed files of 1 MiB or more are
+    /// memory-mapped and parsed in place, avoiding a copy of every event
+    /// into an intermediate buffer. This is noticeably faster for
+    /// multi-gigabyte dumps. The file must not be truncated by another
+    /// process while it is being analyzed.
+    ///
     /// # Arguments
     /// * `path` - Path to the XML/HTML file
     ///
     /// # Example
-    /// ```
+    /// ```no_run
+    /// # use ferret::analyzer::stream::StreamAnalyzer;
     /// use std::path::Path;
     /// let analyzer = StreamAnalyzer::new(10);
     /// let result = analyzer.analyze_file(Path::new("data.xml"))?;
+    /// # Ok::<(), anyhow::Error>(())
     /// ```
     pub fn analyze_file(&self, path: &Path) -> Result<AnalysisResult> {
         let file = File::open(path)?;
-        let reader = BufReader::new(file);
+        let size = file.metadata()?.len() as usize;
+        let mut reader = BufReader::new(file);
+
+        let is_gzip = path.extension().is_some_and(|ext| ext == "gz")
+            || reader.fill_buf()?.starts_with(&GZIP_MAGIC);
+        if is_gzip {
+            // The size limit applies to the decompressed stream
+            return self.analyze_reader(BufReader::new(MultiGzDecoder::new(reader)));
+        }
+
+        self.limits.check_input(size)?;
+        #[cfg(feature = "mmap")]
+        if size >= MMAP_THRESHOLD {
+            return self.analyze_mmap(reader.get_ref());
+        }
+        self.analyze_reader(reader)
+    }
+
+    #[cfg(feature = "mmap")]
+    fn analyze_mmap(&self, file: &File) -> Result<AnalysisResult> {
+        // SAFETY: the map is read-only and dropped before returning. As with
+        // any mapping, another process truncating the file meanwhile would
+        // make reads fault; `analyze_file` documents this.
+        let map = unsafe { memmap2::Mmap::map(file)? };
+        #[cfg(unix)]
+        map.advise(memmap2::Advice::Sequential)?;
+        self.analyze_bytes(&map)
+    }
+
+    /// Analyze a document piped to standard input
+    ///
+    /// Stdin is locked for the duration of the analysis. Gzip-compressed
+    /// input is detected by its magic bytes and decompressed while streaming.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use ferret::analyzer::stream::StreamAnalyzer;
+    /// // curl -s https://example.com | my-tool
+    /// let result = StreamAnalyzer::new(10).analyze_stdin()?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn analyze_stdin(&self) -> Result<AnalysisResult> {
+        let stdin = std::io::stdin();
+        let mut reader = BufReader::with_capacity(STDIN_BUFFER_SIZE, stdin.lock());
+        if reader.fill_buf()?.starts_with(&GZIP_MAGIC) {
+            return self.analyze_reader(BufReader::new(MultiGzDecoder::new(reader)));
+        }
         self.analyze_reader(reader)
     }
 
     /// Analyze content from a URL
     ///
+    /// The response body is parsed chunk by chunk as it arrives, so memory use
+    /// does not grow with the size of the document. Bodies sent with a gzip,
+    /// brotli or deflate `Content-Encoding` are decoded by the HTTP client;
+    /// raw gzip files (e.g. `sitemap.xml.gz`) are detected by their magic bytes.
+    ///
     /// If a proxy URL is configured via `with_proxy()`, requests will be
     /// routed through the proxy to bypass CORS restrictions.
     ///
+    /// With a cache configured via `with_cache()`, a previously stored
+    /// document is revalidated with a conditional request and its analysis
+    /// reused when the server answers `304 Not Modified`.
+    ///
     /// # Arguments
     /// * `url` - Full URL to the XML/HTML resource
     ///
     /// # Example
-    /// ```
+    /// ```no_run
+    /// # use ferret::analyzer::stream::StreamAnalyzer;
+    /// # async fn run() -> anyhow::Result<()> {
     /// let analyzer = StreamAnalyzer::new(10);
     /// let result = analyzer.analyze_url("https://example.com/data.xml").await?;
+    /// # Ok(())
+    /// # }
     /// ```
     pub async fn analyze_url(&self, url: &str) -> Result<AnalysisResult> {
+        #[cfg(feature = "rendered")]
+        if let Some(options) = &self.render {
+            let client = self.fetch.build_client()?;
+            self.cancellable(self.fetch.admit(&client, &Url::parse(url)?))
+                .await?;
+            let renderer = Renderer::new(options.clone())?;
+            let html = self.cancellable(renderer.render(url)).await?;
+            let mut analyzer = self.incremental();
+            analyzer.set_mode(ParseMode::Html);
+            if let Ok(page) = Url::parse(url) {
+                analyzer.set_page(page);
+            }
+            analyzer.feed(html.as_bytes())?;
+            return analyzer.finish();
+        }
+
         let target_url = if let Some(proxy) = &self.proxy_url {
             // Route through proxy by appending the target URL
             // Proxy format: http://proxy.example.com/{target_url}
@@ -94,15 +372,106 @@ impl StreamAnalyzer {
             url.to_string()
         };
 
-        let response = reqwest::get(&target_url).await?;
+        let cached = self.cache.as_ref().and_then(|cache| cache.get(url));
+        let revalidate = cached
+            .as_ref()
+            .map(|entry| entry.revalidate(self.fetch.clone()));
+        let fetch = revalidate.as_ref().unwrap_or(&self.fetch);
+
+        let client = fetch.build_client()?;
+        let Fetched {
+            response,
+            redirects,
+        } = self.cancellable(fetch.send(&client, &target_url)).await?;
+        // Relative URLs in the document resolve against the final URL, not
+        // the proxy's
+        let page = match &self.proxy_url {
+            Some(_) => Url::parse(url).ok(),
+            None => Some(response.url().clone()),
+        };
+
+        if let (Some(cache), Some(entry), StatusCode::NOT_MODIFIED) =
+            (&self.cache, cached, response.status())
+        {
+            let settings_match = entry.top_values_limit == self.top_values_limit
+                && (!self.structure || !entry.result.children.is_empty())
+                && self
+                    .sections
+                    .iter()
+                    .all(|section| section.is_in(&entry.result));
+            let mut result = if settings_match {
+                entry.result
+            } else {
+                // Stored with different settings; re-analyze the cached body
+                self.analyze_cached(&cache.body_path(url), page)?
+            };
+            if !self.structure {
+                result.children.clear();
+            }
+            for section in Section::ALL {
+                if !self.sections.contains(&section) {
+                    section.remove_from(&mut result);
+                }
+            }
+            result.redirects = redirects;
+            return Ok(result);
+        }
 
         if !response.status().is_success() {
             anyhow::bail!("HTTP error: {}", response.status());
         }
 
-        let content = response.text().await?;
-        let reader = Cursor::new(content.into_bytes());
-        self.analyze_reader(reader)
+        if let Some(length) = response.content_length() {
+            self.limits.check_input(length as usize)?;
+        }
+
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        let mut analyzer = self.incremental();
+        if let Some(page) = page {
+            analyzer.set_page(page);
+        }
+        analyzer.set_headers(response.headers());
+        let mut sink = BodySink {
+            analyzer,
+            store: match &self.cache {
+                Some(cache) => cache.writer(url, response.headers())?,
+                None => None,
+            },
+            content_type,
+            mode: self.mode,
+            head: Some(Vec::new()),
+        };
+        let mut gunzip: Option<flate2::write::MultiGzDecoder<Vec<u8>>> = None;
+        let mut body = response.bytes_stream();
+        let mut first_chunk = true;
+
+        while let Some(chunk) = self.cancellable(self.fetch.read(body.next())).await? {
+            let chunk = chunk?;
+            if first_chunk && chunk.starts_with(&GZIP_MAGIC) {
+                gunzip = Some(flate2::write::MultiGzDecoder::new(Vec::new()));
+            }
+            first_chunk = false;
+
+            match gunzip.as_mut() {
+                Some(decoder) => {
+                    decoder.write_all(&chunk)?;
+                    sink.write(decoder.get_ref())?;
+                    decoder.get_mut().clear();
+                }
+                None => sink.write(&chunk)?,
+            }
+        }
+
+        if let Some(decoder) = gunzip {
+            sink.write(&decoder.finish()?)?;
+        }
+        let mut result = sink.finish(self.top_values_limit)?;
+        result.redirects = redirects;
+        Ok(result)
     }
 
     /// Analyze content from a string
@@ -114,108 +483,601 @@ impl StreamAnalyzer {
     ///
     /// # Example
     /// ```
+    /// # use ferret::analyzer::stream::StreamAnalyzer;
     /// let analyzer = StreamAnalyzer::new(10);
     /// let html = r#"<html><body><div id="test">Hello</div></body></html>"#;
     /// let result = analyzer.analyze_string(html)?;
+    /// # Ok::<(), anyhow::Error>(())
     /// ```
     pub fn analyze_string(&self, content: &str) -> Result<AnalysisResult> {
-        let reader = Cursor::new(content.as_bytes());
-        self.analyze_reader(reader)
+        self.analyze_bytes(content.as_bytes())
+    }
+
+    /// Analyze a document that is already in memory
+    ///
+    /// Events borrow directly from `content` instead of being copied into a
+    /// read buffer, so this is the fastest way to analyze a loaded document.
+    pub fn analyze_bytes(&self, content: &[u8]) -> Result<AnalysisResult> {
+        let mut reader = Reader::from_reader(content);
+        reader.trim_text(true);
+        reader.check_end_names(false); // Be permissive with HTML
+
+        let mut state = StreamState::new(self);
+        loop {
+            let event = reader.read_event();
+            state.check_position(reader.buffer_position())?;
+
+            match event {
+                Ok(Event::Eof) => break,
+                Ok(event) => state.handle_event(&event, reader.buffer_position())?,
+                Err(err) => {
+                    // Skip errors to be resilient with malformed HTML/XML
+                    state.record_error(reader.buffer_position(), &err)?;
+                }
+            }
+        }
+
+        state.finish()
+    }
+
+    /// Await `future` unless the cancellation token fires first
+    pub(crate) async fn cancellable<T>(
+        &self,
+        future: impl std::future::Future<Output = Result<T>>,
+    ) -> Result<T> {
+        match &self.cancel {
+            Some(token) => tokio::select! {
+                result = future => result,
+                _ = token.cancelled() => Err(FerretError::Cancelled.into()),
+            },
+            None => future.await,
+        }
+    }
+
+    /// Analyze a cached body of the document at `page`
+    ///
+    /// Like `analyze_file`, but sections see the page's URL.
+    fn analyze_cached(&self, path: &Path, page: Option<Url>) -> Result<AnalysisResult> {
+        let mut analyzer = self.incremental();
+        if let Some(page) = page {
+            analyzer.set_page(page);
+        }
+        let mut reader = BufReader::new(File::open(path)?);
+        loop {
+            let chunk = reader.fill_buf()?;
+            if chunk.is_empty() {
+                break;
+            }
+            let len = chunk.len();
+            analyzer.feed(chunk)?;
+            reader.consume(len);
+        }
+        analyzer.finish()
+    }
+
+    /// Create a push-based analyzer sharing this analyzer's configuration
+    ///
+    /// See [`IncrementalAnalyzer`] for feeding chunks as they arrive.
+    pub fn incremental(&self) -> IncrementalAnalyzer {
+        IncrementalAnalyzer::from_state(StreamState::new(self))
     }
 
     /// Core analysis logic that works with any BufRead implementation
     ///
     /// This is the internal method that all other public methods delegate to.
     /// It performs streaming XML/HTML parsing using quick-xml.
-    fn analyze_reader<R: std::io::BufRead>(&self, reader: R) -> Result<AnalysisResult> {
+    pub(crate) fn analyze_reader<R: std::io::BufRead>(&self, reader: R) -> Result<AnalysisResult> {
+        let mut state = StreamState::new(self);
+        state.read_events(reader, 0)?;
+        state.finish()
+    }
+}
+
+/// Destination of a fetched body: the analyzer and, optionally, the cache
+///
+/// The first `sniff::SNIFF_LEN` bytes are held back until the parse mode
+/// has been detected from them and the `Content-Type` header.
+struct BodySink {
+    analyzer: IncrementalAnalyzer,
+    store: Option<CacheWriter>,
+    content_type: Option<String>,
+    /// Mode forced by the caller, or `Auto` to detect it
+    mode: ParseMode,
+    /// Bytes buffered for sniffing; `None` once the mode is known
+    head: Option<Vec<u8>>,
+}
+
+impl BodySink {
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        if let Some(store) = self.store.as_mut() {
+            store.write(data)?;
+        }
+        match self.head.as_mut() {
+            Some(head) => {
+                head.extend_from_slice(data);
+                if head.len() >= sniff::SNIFF_LEN {
+                    self.flush_head()?;
+                }
+                Ok(())
+            }
+            None => self.analyzer.feed(data),
+        }
+    }
+
+    fn flush_head(&mut self) -> Result<()> {
+        if let Some(head) = self.head.take() {
+            let detected = sniff::detect(self.content_type.as_deref(), &head)?;
+            if self.mode == ParseMode::Auto {
+                self.mode = detected;
+            }
+            self.analyzer.set_mode(self.mode);
+            self.analyzer.feed(&head)?;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self, top_values_limit: usize) -> Result<AnalysisResult> {
+        self.flush_head()?;
+        let result = self.analyzer.finish()?;
+        if let Some(store) = self.store {
+            store.commit(top_values_limit, &result)?;
+        }
+        Ok(result)
+    }
+}
+
+/// HTML elements that never have content or an end tag
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+
+/// Elements whose start tag implicitly closes an open `<p>`
+const CLOSES_P: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "details",
+    "div",
+    "dl",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "main",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "ul",
+];
+
+/// Whether starting an `opening` element implicitly closes an open `current` one
+///
+/// A simplified form of the HTML parsing rules: only the innermost open
+/// element is considered.
+fn closes_implicitly(opening: &str, current: &str) -> bool {
+    match current {
+        "p" => CLOSES_P.contains(&opening),
+        "li" => opening == "li",
+        "dt" | "dd" => matches!(opening, "dt" | "dd"),
+        "td" | "th" => matches!(opening, "td" | "th" | "tr" | "tbody" | "thead" | "tfoot"),
+        "tr" => matches!(opening, "tr" | "tbody" | "thead" | "tfoot"),
+        "tbody" | "thead" | "tfoot" => matches!(opening, "tbody" | "thead" | "tfoot"),
+        "option" => matches!(opening, "option" | "optgroup"),
+        "optgroup" => opening == "optgroup",
+        _ => false,
+    }
+}
+
+/// Running statistics for a single streamed document
+///
+/// Shared by the pull-based `StreamAnalyzer` methods and the push-based
+/// `IncrementalAnalyzer` so both produce identical results.
+pub(crate) struct StreamState {
+    limits: Limits,
+    result: AnalysisResult,
+    counter: TagCounter,
+    /// Names of the currently open elements, lowercased in HTML mode
+    ///
+    /// Matching end tags against this stack (instead of counting them)
+    /// keeps depth correct for unclosed, stray and void tags.
+    open_elements: Vec<Symbol>,
+    /// Reused for lowercasing element names
+    name_buf: String,
+    mode: ParseMode,
+    /// Apply HTML void-element and implicit-close rules; in `Auto` mode
+    /// turned off once an XML declaration is seen
+    html_rules: bool,
+    structure: bool,
+    elements: usize,
+    parse_errors: usize,
+    progress: Option<Progress>,
+    cancel: Option<CancellationToken>,
+    /// Position of the last `Parsed` progress event
+    reported: usize,
+    position: usize,
+    sections: Vec<Box<dyn SectionAnalyzer>>,
+    /// URL the document was fetched from, for sections resolving links
+    page: Option<Url>,
+    /// Reused for the attributes passed to sections
+    attributes: Vec<(String, String)>,
+}
+
+impl StreamState {
+    pub(crate) fn new(analyzer: &StreamAnalyzer) -> Self {
+        let mut state = Self {
+            limits: analyzer.limits,
+            result: AnalysisResult {
+                files_analyzed: 1,
+                ..Default::default()
+            },
+            counter: TagCounter::new(analyzer.top_values_limit),
+            open_elements: Vec::new(),
+            name_buf: String::new(),
+            mode: ParseMode::Auto,
+            html_rules: true,
+            structure: analyzer.structure,
+            elements: 0,
+            parse_errors: 0,
+            progress: analyzer.progress.clone(),
+            cancel: analyzer.cancel.clone(),
+            reported: 0,
+            position: 0,
+            sections: analyzer
+                .sections
+                .iter()
+                .map(|section| section.analyzer())
+                .collect(),
+            page: None,
+            attributes: Vec::new(),
+        };
+        state.set_mode(analyzer.mode);
+        state
+    }
+
+    /// A fresh state for input that continues where this one stopped
+    ///
+    /// The continuation starts with the same open elements and parse rules
+    /// but empty statistics, so documents can be analyzed in pieces and the
+    /// results merged.
+    pub(crate) fn continuation(&self, analyzer: &StreamAnalyzer) -> Self {
+        let mut state = Self::new(analyzer);
+        state.mode = self.mode;
+        state.html_rules = self.html_rules;
+        state.page.clone_from(&self.page);
+        state.open_elements = self
+            .open_elements
+            .iter()
+            .map(|&open| {
+                let name = self.counter.names.resolve(open);
+                state.counter.names.intern(name)
+            })
+            .collect();
+        state
+    }
+
+    /// Stop reporting `Parsed` progress events
+    pub(crate) fn without_progress(mut self) -> Self {
+        self.progress = None;
+        self
+    }
+
+    /// Handle every event from `reader`, whose first byte is at absolute
+    /// byte offset `offset` of the document
+    pub(crate) fn read_events<R: BufRead>(&mut self, reader: R, offset: usize) -> Result<()> {
         let mut reader = Reader::from_reader(reader);
         reader.trim_text(true);
         reader.check_end_names(false); // Be permissive with HTML
 
         let mut buf = Vec::new();
-        let mut result = AnalysisResult::default();
-        result.files_analyzed = 1;
+        loop {
+            let event = reader.read_event_into(&mut buf);
+            let position = offset + reader.buffer_position();
+            self.check_position(position)?;
+
+            match event {
+                Ok(Event::Eof) => break,
+                Ok(event) => self.handle_event(&event, position)?,
+                // The reader would fail the same way again, e.g. on a corrupt
+                // gzip stream
+                Err(quick_xml::Error::Io(err)) => {
+                    return Err(std::io::Error::new(err.kind(), err.to_string()).into())
+                }
+                Err(err) => {
+                    // Skip errors to be resilient with malformed HTML/XML
+                    self.record_error(position, &err)?;
+                }
+            }
+            buf.clear();
+        }
+        Ok(())
+    }
 
-        // Depth tracking is approximate in streaming mode without strict XML
-        let mut depth = 0;
+    /// Record the URL the document was fetched from
+    pub(crate) fn set_page(&mut self, page: Url) {
+        self.page = Some(page);
+    }
 
-        loop {
-            match reader.read_event_into(&mut buf) {
-                Ok(Event::Start(e)) => {
-                    depth += 1;
-                    if depth > result.max_depth {
-                        result.max_depth = depth;
+    /// Pass the headers of the response the document came with to the
+    /// sections
+    pub(crate) fn set_headers(&mut self, headers: &HeaderMap) {
+        for section in &mut self.sections {
+            section.headers(headers);
+        }
+    }
+
+    /// Switch parse mode; only meaningful before any input was handled
+    pub(crate) fn set_mode(&mut self, mode: ParseMode) {
+        self.mode = mode;
+        self.html_rules = mode != ParseMode::Xml;
+    }
+
+    /// Record the absolute byte offset reached
+    ///
+    /// Enforces the input size limit, checks for cancellation and reports
+    /// progress every `REPORT_INTERVAL_BYTES`.
+    pub(crate) fn check_position(&mut self, position: usize) -> Result<()> {
+        self.limits.check_input(position)?;
+        if self
+            .cancel
+            .as_ref()
+            .is_some_and(|token| token.is_cancelled())
+        {
+            return Err(FerretError::Cancelled.into());
+        }
+
+        self.position = position;
+        if position - self.reported >= REPORT_INTERVAL_BYTES {
+            self.report();
+        }
+        Ok(())
+    }
+
+    fn report(&mut self) {
+        if let Some(progress) = &self.progress {
+            progress.report(ProgressEvent::Parsed {
+                bytes: self.position,
+                elements: self.elements,
+            });
+        }
+        self.reported = self.position;
+    }
+
+    /// Update statistics for one event ending at byte offset `position`
+    pub(crate) fn handle_event(&mut self, event: &Event, position: usize) -> Result<()> {
+        match event {
+            Event::Start(e) => {
+                self.elements += 1;
+                self.limits.check_nodes(self.elements)?;
+                let tag = self.process_element(e);
+
+                let name = self.element_name(tag);
+                if self.html_rules {
+                    let names = &self.counter.names;
+                    if VOID_ELEMENTS.contains(&names.resolve(name)) {
+                        // `<br>` without a closing slash
+                        count_depth(&mut self.result.depth_counts, self.open_elements.len() + 1);
+                        self.record_child(name);
+                        self.visit_sections(e, self.open_elements.len() + 1);
+                        return Ok(());
+                    }
+                    while let Some(&current) = self.open_elements.last() {
+                        if !closes_implicitly(names.resolve(name), names.resolve(current)) {
+                            break;
+                        }
+                        self.open_elements.pop();
                     }
+                }
 
-                    self.process_element(&e, &mut result);
+                self.record_child(name);
+                self.open_elements.push(name);
+                let depth = self.open_elements.len();
+                count_depth(&mut self.result.depth_counts, depth);
+                if depth > self.result.max_depth {
+                    self.result.max_depth = depth;
+                }
+                self.limits.check_depth(depth)?;
+                self.visit_sections(e, depth);
+            }
+            Event::Empty(e) => {
+                // Self-closing tags like <img /> or <br />
+                self.elements += 1;
+                self.limits.check_nodes(self.elements)?;
+                let tag = self.process_element(e);
+                self.visit_sections(e, self.open_elements.len() + 1);
+                count_depth(&mut self.result.depth_counts, self.open_elements.len() + 1);
+                if self.structure {
+                    let name = self.element_name(tag);
+                    self.record_child(name);
+                }
+            }
+            Event::End(e) => {
+                // Close the matching element and any left open inside it;
+                // stray end tags are ignored
+                let tag = self
+                    .counter
+                    .names
+                    .intern(&String::from_utf8_lossy(e.name().as_ref()));
+                let name = self.element_name(tag);
+                if self.mode == ParseMode::Xml && self.open_elements.last() != Some(&name) {
+                    let names = &self.counter.names;
+                    let error = quick_xml::Error::EndEventMismatch {
+                        expected: self
+                            .open_elements
+                            .last()
+                            .map(|&open| names.resolve(open).to_string())
+                            .unwrap_or_default(),
+                        found: names.resolve(name).to_string(),
+                    };
+                    self.record_error(position, &error)?;
                 }
-                Ok(Event::Empty(e)) => {
-                    // Self-closing tags like <img /> or <br />
-                    self.process_element(&e, &mut result);
+                if let Some(index) = self.open_elements.iter().rposition(|open| *open == name) {
+                    self.open_elements.truncate(index);
                 }
-                Ok(Event::End(_)) => {
-                    if depth > 0 {
-                        depth -= 1;
+                if !self.sections.is_empty() {
+                    let name = self.counter.names.resolve(name);
+                    for section in &mut self.sections {
+                        section.end(name);
                     }
                 }
-                Ok(Event::Eof) => break,
-                Err(_) => {
-                    // Ignore errors to be resilient with malformed HTML/XML
+            }
+            Event::Text(e) if !self.sections.is_empty() => {
+                let text = match e.unescape() {
+                    Ok(text) => text,
+                    // Raw text like `a && b` in scripts
+                    Err(_) => String::from_utf8_lossy(e),
+                };
+                for section in &mut self.sections {
+                    section.text(&text);
                 }
-                _ => (),
             }
-            buf.clear();
+            Event::CData(e) if !self.sections.is_empty() => {
+                let text = String::from_utf8_lossy(e);
+                for section in &mut self.sections {
+                    section.text(&text);
+                }
+            }
+            Event::Decl(_) if self.mode == ParseMode::Auto => self.html_rules = false,
+            _ => (),
         }
+        Ok(())
+    }
 
-        Ok(result)
+    /// Name used to match `tag` against open elements: lowercased in HTML mode
+    fn element_name(&mut self, tag: Symbol) -> Symbol {
+        let name = self.counter.names.resolve(tag);
+        if !self.html_rules || !name.bytes().any(|byte| byte.is_ascii_uppercase()) {
+            return tag;
+        }
+        self.name_buf.clear();
+        self.name_buf.push_str(name);
+        self.name_buf.make_ascii_lowercase();
+        self.counter.names.intern(&self.name_buf)
     }
 
-    /// Process a single XML/HTML element (tag and its attributes)
+    /// Count `child` inside the innermost open element, if structure is tracked
+    fn record_child(&mut self, child: Symbol) {
+        if let (true, Some(&parent)) = (self.structure, self.open_elements.last()) {
+            self.counter.add_child(parent, child);
+        }
+    }
+
+    /// Record a recoverable parse error at `position`
     ///
-    /// This method updates the result statistics for a given tag.
-    fn process_element<'a>(
-        &self,
-        e: &quick_xml::events::BytesStart<'a>,
-        result: &mut AnalysisResult,
-    ) {
-        // Process Tag
-        let tag_name = String::from_utf8_lossy(e.name().as_ref()).to_string();
-        let tag_stats = result
-            .tags
-            .entry(tag_name.clone())
-            .or_insert_with(|| TagStats {
-                name: tag_name,
-                count: 0,
-                attributes: HashMap::new(),
+    /// Fails once `Limits::max_parse_errors` is exceeded.
+    pub(crate) fn record_error(
+        &mut self,
+        position: usize,
+        error: &impl std::fmt::Display,
+    ) -> Result<()> {
+        self.parse_errors += 1;
+        if self.result.parse_errors.len() < MAX_PARSE_ISSUES {
+            self.result.parse_errors.push(ParseIssue {
+                position,
+                message: error.to_string(),
             });
-        tag_stats.count += 1;
-
-        // Process Attributes
-        for attr in e.attributes() {
-            if let Ok(attr) = attr {
-                let attr_name = String::from_utf8_lossy(attr.key.as_ref()).to_string();
-                let attr_val = String::from_utf8_lossy(&attr.value).to_string();
-
-                let attr_stats = tag_stats
-                    .attributes
-                    .entry(attr_name.clone())
-                    .or_insert_with(|| AttributeStats {
-                        name: attr_name,
-                        count: 0,
-                        value_counts: HashMap::new(),
-                    });
-                attr_stats.count += 1;
-
-                // Track top N values
-                // Optimization: Don't track new values if we've hit the limit,
-                // but continue counting existing values
-                if attr_stats.value_counts.len() < self.top_values_limit
-                    || attr_stats.value_counts.contains_key(&attr_val)
-                {
-                    *attr_stats.value_counts.entry(attr_val).or_insert(0) += 1;
-                }
+        }
+        self.limits.check_parse_errors(self.parse_errors)?;
+        Ok(())
+    }
+
+    /// Statistics for a piece of a document that continues elsewhere
+    ///
+    /// Unlike `finish`, elements left open are not reported.
+    pub(crate) fn finish_partial(mut self) -> AnalysisResult {
+        self.finish_sections();
+        self.result.tags = self.counter.to_tags();
+        self.result.children = self.counter.to_children();
+        self.result
+    }
+
+    /// Final statistics; in XML mode elements left open are parse errors
+    pub(crate) fn finish(mut self) -> Result<AnalysisResult> {
+        if self.mode == ParseMode::Xml {
+            for &name in std::mem::take(&mut self.open_elements).iter().rev() {
+                let message = format!("Unclosed element <{}>", self.counter.names.resolve(name));
+                self.record_error(self.position, &message)?;
             }
         }
+        self.report();
+        self.finish_sections();
+        self.result.tags = self.counter.to_tags();
+        self.result.children = self.counter.to_children();
+        Ok(self.result)
+    }
+
+    fn finish_sections(&mut self) {
+        for section in std::mem::take(&mut self.sections) {
+            section.finish(self.page.as_ref(), &mut self.result);
+        }
+    }
+
+    /// Pass a start or self-closing tag at nesting level `depth` to the
+    /// enabled sections
+    fn visit_sections(&mut self, e: &BytesStart, depth: usize) {
+        if self.sections.is_empty() {
+            return;
+        }
+        let mut name = String::from_utf8_lossy(e.name().as_ref()).into_owned();
+        if self.html_rules {
+            name.make_ascii_lowercase();
+        }
+        self.attributes.clear();
+        // Valueless attributes like `crossorigin` are common in HTML
+        let attributes = if self.html_rules {
+            e.html_attributes()
+        } else {
+            e.attributes()
+        };
+        for attr in attributes.flatten() {
+            let value = match attr.unescape_value() {
+                Ok(value) => value.into_owned(),
+                Err(_) => String::from_utf8_lossy(&attr.value).into_owned(),
+            };
+            self.attributes.push((
+                String::from_utf8_lossy(attr.key.as_ref()).into_owned(),
+                value,
+            ));
+        }
+        let element = Element {
+            name: &name,
+            attributes: &self.attributes,
+            depth,
+        };
+        for section in &mut self.sections {
+            section.element(&element);
+        }
+    }
+
+    /// Process a single XML/HTML element (tag and its attributes)
+    ///
+    /// This method updates the result statistics for a given tag and
+    /// returns its interned name.
+    fn process_element(&mut self, e: &BytesStart) -> Symbol {
+        let tag = self
+            .counter
+            .add_tag(&String::from_utf8_lossy(e.name().as_ref()));
+        for attr in e.attributes().flatten() {
+            self.counter.add_attribute(
+                tag,
+                &String::from_utf8_lossy(attr.key.as_ref()),
+                &String::from_utf8_lossy(&attr.value),
+            );
+        }
+        tag
     }
 }
 
@@ -264,6 +1126,18 @@ mod tests {
         assert_eq!(result.max_depth, 4); // div -> div -> div -> span
     }
 
+    #[test]
+    fn test_depth_counts() {
+        let analyzer = StreamAnalyzer::new(10);
+        let html = r#"<div><p>a<br>b</p><p>c<img src="x" /></p></div><footer></footer>"#;
+        let result = analyzer.analyze_string(html).unwrap();
+
+        // Void elements count at their level but don't add to `max_depth`
+        assert_eq!(result.depth_counts, [2, 2, 2]);
+        assert_eq!(result.max_depth, 2);
+        assert!(analyzer.analyze_string("").unwrap().depth_counts.is_empty());
+    }
+
     #[test]
     fn test_attribute_value_limit() {
         let analyzer = StreamAnalyzer::new(2); // Only track 2 unique values
@@ -295,10 +1169,189 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_read_error() {
+        let analyzer = StreamAnalyzer::new(10);
+        let corrupt: &[u8] = b"\x1f\x8b\x08\x00garbage";
+        let result = analyzer.analyze_reader(BufReader::new(MultiGzDecoder::new(corrupt)));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_limits() {
+        use crate::error::FerretError;
+
+        let html = r#"<div><div><div><span>Deep</span></div></div></div>"#;
+
+        let analyzer = StreamAnalyzer::new(10).with_limits(Limits {
+            max_depth: Some(3),
+            ..Limits::default()
+        });
+        let err = analyzer.analyze_string(html).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<FerretError>(),
+            Some(FerretError::TooDeep { limit: 3 })
+        ));
+
+        let analyzer = StreamAnalyzer::new(10).with_limits(Limits {
+            max_nodes: Some(2),
+            ..Limits::default()
+        });
+        let err = analyzer.analyze_string(html).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<FerretError>(),
+            Some(FerretError::TooManyNodes { limit: 2 })
+        ));
+
+        let analyzer = StreamAnalyzer::new(10).with_limits(Limits {
+            max_input_bytes: Some(16),
+            ..Limits::default()
+        });
+        let err = analyzer.analyze_string(html).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<FerretError>(),
+            Some(FerretError::InputTooLarge { limit: 16 })
+        ));
+
+        let analyzer = StreamAnalyzer::new(10).with_limits(Limits::untrusted());
+        assert_eq!(analyzer.analyze_string(html).unwrap().max_depth, 4);
+    }
+
     #[tokio::test]
     async fn test_url_analysis_placeholder() {
         // This would require a mock HTTP server for proper testing
         // In a real test suite, you'd use something like `mockito` or `wiremock`
         // to create a mock HTTP server that returns test HTML
     }
+
+    #[test]
+    fn test_parse_errors() {
+        use crate::error::FerretError;
+
+        let analyzer = StreamAnalyzer::new(10);
+        let result = analyzer.analyze_string("<div><p>ok</p></div>").unwrap();
+        assert!(result.parse_errors.is_empty());
+
+        let html = "<root><p>text</p><!-- unclosed";
+        let result = analyzer.analyze_string(html).unwrap();
+        assert_eq!(result.parse_errors.len(), 1);
+        assert_eq!(result.parse_errors[0].position, 18);
+        assert!(result.parse_errors[0].message.contains("Comment"));
+        assert_eq!(result.tags["p"].count, 1);
+
+        let mut incremental = analyzer.incremental();
+        incremental.feed(&html.as_bytes()[..20]).unwrap();
+        incremental.feed(&html.as_bytes()[20..]).unwrap();
+        assert_eq!(
+            incremental.finish().unwrap().parse_errors,
+            result.parse_errors
+        );
+
+        // Strict mode
+        let analyzer = StreamAnalyzer::new(10).with_limits(Limits {
+            max_parse_errors: Some(0),
+            ..Limits::default()
+        });
+        let err = analyzer.analyze_string(html).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<FerretError>(),
+            Some(FerretError::TooManyParseErrors { limit: 0 })
+        ));
+    }
+
+    #[test]
+    fn test_depth_on_tag_soup() {
+        let analyzer = StreamAnalyzer::new(10);
+        let depth = |html: &str| analyzer.analyze_string(html).unwrap().max_depth;
+
+        // Void elements without a closing slash
+        assert_eq!(depth("<div><br><img src=x><input><span>t</span></div>"), 2);
+        assert_eq!(depth("<HEAD><META charset=utf-8><LINK rel=x></HEAD>"), 1);
+        // Stray and mismatched end tags
+        assert_eq!(depth("<div></span></p></div><div></div>"), 1);
+        assert_eq!(depth("<div><span><b>t</div><div>u</div>"), 3);
+        // Implicitly closed elements
+        assert_eq!(depth("<body><p>a<p>b<p>c</body>"), 2);
+        assert_eq!(depth("<ul><li>a<li>b<li>c</ul>"), 2);
+        assert_eq!(depth("<table><tr><td>a<td>b<tr><td>c</table>"), 3);
+        assert_eq!(depth("<p>text<div>block</div>"), 1);
+
+        // XML documents are taken literally
+        assert_eq!(
+            depth(r#"<?xml version="1.0"?><rss><item><link>u</link><p><p></p></p></item></rss>"#),
+            4
+        );
+    }
+
+    #[test]
+    fn test_structure() {
+        let html = r#"<DIV><p>a<p>b<BR><img src=x /></DIV><ul><li>c</li></ul>"#;
+        assert!(StreamAnalyzer::new(10)
+            .analyze_string(html)
+            .unwrap()
+            .children
+            .is_empty());
+
+        let result = StreamAnalyzer::new(10)
+            .with_structure(true)
+            .analyze_string(html)
+            .unwrap();
+        let children = &result.children;
+        assert_eq!(children.len(), 3);
+        assert_eq!(children["div"].get("p"), Some(&2));
+        assert_eq!(children["p"].get("br"), Some(&1));
+        assert_eq!(children["p"].get("img"), Some(&1));
+        assert_eq!(children["ul"].get("li"), Some(&1));
+        assert!(!children.contains_key("li"));
+    }
+
+    #[test]
+    fn test_depth_matches_dom_analyzer() {
+        use crate::analyzer::{Analyzer, StatsAnalyzer};
+        use crate::parser::FerretParser;
+        use crate::walker::DomWalker;
+
+        // Element-only documents: the DOM depth of the deepest text node
+        // equals the number of open elements around it
+        for html in [
+            "<div><br><img src=x><span>t</span></div>",
+            "<div><input><input><input><b>x</b></div>",
+            "<b><i>x</b></i><p>y</p>",
+            "<html><body><div><hr><p>t</p></div></body></html>",
+        ] {
+            let vdom = FerretParser::parse(html).unwrap();
+            let mut dom = StatsAnalyzer::new(10);
+            for (_handle, node, depth) in DomWalker::new(vdom.children().to_vec(), vdom.parser()) {
+                dom.visit(node, depth);
+            }
+            let stream = StreamAnalyzer::new(10).analyze_string(html).unwrap();
+            assert_eq!(stream.max_depth, dom.result().max_depth, "{}", html);
+            // tl nests misnested tags differently, which moves elements
+            // between levels
+            if !html.starts_with("<b><i>") {
+                assert_eq!(stream.depth_counts, dom.result().depth_counts, "{}", html);
+            }
+        }
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn test_mmap_matches_buffered() {
+        let mut xml = String::from("<dump>");
+        while xml.len() < MMAP_THRESHOLD {
+            xml.push_str(r#"<page id="1"><title>T</title><text>Body &amp; more</text></page>"#);
+        }
+        xml.push_str("<page><broken</page></dump>");
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("dump.xml");
+        std::fs::write(&path, &xml).unwrap();
+
+        let analyzer = StreamAnalyzer::new(10);
+        let mapped = analyzer.analyze_file(&path).unwrap();
+        let buffered = analyzer.analyze_reader(xml.as_bytes()).unwrap();
+        assert_eq!(mapped.tags["page"].count, buffered.tags["page"].count);
+        assert_eq!(mapped.max_depth, buffered.max_depth);
+        assert_eq!(mapped.parse_errors, buffered.parse_errors);
+    }
 }
@@ -0,0 +1,327 @@
+use crate::analyzer::section::{Element, SectionAnalyzer};
+use crate::analyzer::AnalysisResult;
+use crate::fetch::matches_host;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+
+/// Hosts of well-known analytics, advertising and session-recording
+/// services, with the service's name; subdomains match too
+pub const KNOWN_TRACKERS: &[(&str, &str)] = &[
+    ("google-analytics.com", "Google Analytics"),
+    ("googletagmanager.com", "Google Tag Manager"),
+    ("doubleclick.net", "Google Ads"),
+    ("googlesyndication.com", "Google Ads"),
+    ("googleadservices.com", "Google Ads"),
+    ("connect.facebook.net", "Meta Pixel"),
+    ("analytics.tiktok.com", "TikTok Pixel"),
+    ("snap.licdn.com", "LinkedIn Insight Tag"),
+    ("static.ads-twitter.com", "X Ads"),
+    ("ct.pinterest.com", "Pinterest Tag"),
+    ("bat.bing.com", "Microsoft Advertising"),
+    ("clarity.ms", "Microsoft Clarity"),
+    ("hotjar.com", "Hotjar"),
+    ("fullstory.com", "FullStory"),
+    ("mixpanel.com", "Mixpanel"),
+    ("segment.com", "Segment"),
+    ("segment.io", "Segment"),
+    ("amplitude.com", "Amplitude"),
+    ("hs-analytics.net", "HubSpot"),
+    ("mc.yandex.ru", "Yandex Metrica"),
+    ("scorecardresearch.com", "Comscore"),
+    ("quantserve.com", "Quantcast"),
+    ("criteo.com", "Criteo"),
+    ("criteo.net", "Criteo"),
+    ("taboola.com", "Taboola"),
+    ("outbrain.com", "Outbrain"),
+    ("adnxs.com", "Xandr"),
+    ("amazon-adsystem.com", "Amazon Ads"),
+];
+
+/// Name of the known tracker serving from `host`, if any
+pub fn tracker(host: &str) -> Option<&'static str> {
+    KNOWN_TRACKERS
+        .iter()
+        .find(|(domain, _)| matches_host(host, domain))
+        .map(|(_, name)| *name)
+}
+
+/// Site `host` belongs to: its last two labels, or three under a two-letter
+/// country domain with a short second level like `co.uk`
+///
+/// A heuristic in place of the public suffix list: `cdn.example.com` and
+/// `www.example.com` are the same site, `example.co.uk` and `other.co.uk`
+/// are not.
+pub fn site(host: &str) -> &str {
+    let host = host.trim_end_matches('.');
+    if host.parse::<std::net::IpAddr>().is_ok() || host.starts_with('[') {
+        return host;
+    }
+    let labels: Vec<&str> = host.rsplitn(4, '.').collect();
+    let keep = match labels.as_slice() {
+        [tld, second, _, ..] if tld.len() == 2 && second.len() <= 3 => 3,
+        _ => 2,
+    };
+    if labels.len() <= keep {
+        return host;
+    }
+    let suffix: usize = labels[..keep].iter().map(|label| label.len() + 1).sum();
+    &host[host.len() + 1 - suffix..]
+}
+
+/// External resources of one origin host
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OriginResources {
+    pub scripts: usize,
+    pub stylesheets: usize,
+    pub iframes: usize,
+    pub images: usize,
+    /// Name of the tracking service the host belongs to, see
+    /// [`KNOWN_TRACKERS`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tracker: Option<String>,
+}
+
+impl OriginResources {
+    pub fn total(&self) -> usize {
+        self.scripts + self.stylesheets + self.iframes + self.images
+    }
+
+    fn add(&mut self, kind: ResourceKind) {
+        match kind {
+            ResourceKind::Script => self.scripts += 1,
+            ResourceKind::Stylesheet => self.stylesheets += 1,
+            ResourceKind::Iframe => self.iframes += 1,
+            ResourceKind::Image => self.images += 1,
+        }
+    }
+
+    fn merge(&mut self, other: &OriginResources) {
+        self.scripts += other.scripts;
+        self.stylesheets += other.stylesheets;
+        self.iframes += other.iframes;
+        self.images += other.images;
+        if self.tracker.is_none() {
+            self.tracker.clone_from(&other.tracker);
+        }
+    }
+}
+
+/// Scripts, stylesheets, iframes and images by where they are loaded from
+///
+/// Resources count as first-party when they are relative or come from the
+/// page's own [`site`]. Without a page URL, as for local files, the site is
+/// taken from `<base href>` or `<link rel="canonical">`; failing that, every
+/// absolute URL counts as third-party.
+///
+/// # Example
+/// ```
+/// # use ferret::analyzer::stream::StreamAnalyzer;
+/// use ferret::analyzer::section::Section;
+///
+/// let analyzer = StreamAnalyzer::new(10).with_sections([Section::ThirdParty]);
+/// let result = analyzer.analyze_string(
+///     r#"<link rel="canonical" href="https://shop.example/">
+///        <script src="/app.js"></script>
+///        <script src="https://www.googletagmanager.com/gtm.js"></script>
+///        <img src="https://cdn.images.test/a.png">"#,
+/// )?;
+/// let report = result.third_party.unwrap();
+/// assert_eq!(report.first_party, 1);
+/// assert_eq!(report.third_parties(), 2);
+/// assert_eq!(report.trackers().count(), 1);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ThirdPartyReport {
+    /// Resources loaded from the page's own site
+    pub first_party: usize,
+    /// Resources loaded from other sites, by host
+    pub origins: BTreeMap<String, OriginResources>,
+}
+
+impl ThirdPartyReport {
+    /// Distinct other sites resources are loaded from
+    pub fn third_parties(&self) -> usize {
+        self.origins
+            .keys()
+            .map(|host| site(host))
+            .collect::<HashSet<_>>()
+            .len()
+    }
+
+    /// Hosts of known trackers with their resources
+    pub fn trackers(&self) -> impl Iterator<Item = (&str, &OriginResources)> {
+        self.origins
+            .iter()
+            .filter(|(_, resources)| resources.tracker.is_some())
+            .map(|(host, resources)| (host.as_str(), resources))
+    }
+
+    pub fn merge(&mut self, other: &ThirdPartyReport) {
+        self.first_party += other.first_party;
+        for (host, resources) in &other.origins {
+            self.origins
+                .entry(host.clone())
+                .or_default()
+                .merge(resources);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ResourceKind {
+    Script,
+    Stylesheet,
+    Iframe,
+    Image,
+}
+
+/// Collects resource URLs; they are classified once the page's site is
+/// known
+#[derive(Default)]
+pub(crate) struct ThirdPartyAnalyzer {
+    resources: Vec<(ResourceKind, String)>,
+    /// `<base href>`, else `<link rel="canonical">`
+    base: Option<String>,
+    canonical: Option<String>,
+}
+
+impl SectionAnalyzer for ThirdPartyAnalyzer {
+    fn element(&mut self, element: &Element) {
+        let resource = match element.name {
+            "script" => element
+                .attribute("src")
+                .map(|src| (ResourceKind::Script, src)),
+            "iframe" => element
+                .attribute("src")
+                .map(|src| (ResourceKind::Iframe, src)),
+            "img" => element
+                .attribute("src")
+                .map(|src| (ResourceKind::Image, src)),
+            "link" if element.has_rel("stylesheet") => element
+                .attribute("href")
+                .map(|href| (ResourceKind::Stylesheet, href)),
+            "link" if element.has_rel("canonical") => {
+                self.canonical = element.attribute("href").map(str::to_string);
+                None
+            }
+            "base" => {
+                self.base = element.attribute("href").map(str::to_string);
+                None
+            }
+            _ => None,
+        };
+        if let Some((kind, url)) = resource {
+            let url = url.trim();
+            if !url.is_empty() {
+                self.resources.push((kind, url.to_string()));
+            }
+        }
+    }
+
+    fn finish(self: Box<Self>, page: Option<&Url>, result: &mut AnalysisResult) {
+        let hint = || {
+            let hint = self.base.as_deref().or(self.canonical.as_deref())?;
+            Url::parse(hint).ok()
+        };
+        let base = page.cloned().or_else(hint);
+        let own_site = base
+            .as_ref()
+            .and_then(|base| base.host_str())
+            .map(|host| site(host).to_ascii_lowercase());
+
+        let mut report = ThirdPartyReport::default();
+        for (kind, url) in &self.resources {
+            let resolved = match &base {
+                Some(base) => base.join(url),
+                // Protocol-relative URLs still name their host
+                None if url.starts_with("//") => Url::parse(&format!("https:{}", url)),
+                None => Url::parse(url),
+            };
+            let Ok(resolved) = resolved else {
+                // Relative to an unknown page
+                report.first_party += 1;
+                continue;
+            };
+            if !matches!(resolved.scheme(), "http" | "https") {
+                // data:, blob: and the like load nothing from elsewhere
+                continue;
+            }
+            let Some(host) = resolved.host_str() else {
+                continue;
+            };
+            if own_site.as_deref() == Some(site(host)) {
+                report.first_party += 1;
+                continue;
+            }
+            let resources = report.origins.entry(host.to_string()).or_default();
+            resources.add(*kind);
+            if resources.tracker.is_none() {
+                resources.tracker = tracker(host).map(str::to_string);
+            }
+        }
+        result.third_party = Some(report);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::section::Section;
+    use crate::analyzer::stream::StreamAnalyzer;
+
+    #[test]
+    fn test_site() {
+        assert_eq!(site("www.example.com"), "example.com");
+        assert_eq!(site("example.com"), "example.com");
+        assert_eq!(site("a.b.example.co.uk"), "example.co.uk");
+        assert_eq!(site("example.co.uk"), "example.co.uk");
+        assert_eq!(site("cdn.example.de"), "example.de");
+        assert_eq!(site("localhost"), "localhost");
+        assert_eq!(site("127.0.0.1"), "127.0.0.1");
+    }
+
+    #[test]
+    fn test_third_party() {
+        let analyzer = StreamAnalyzer::new(10).with_sections([Section::ThirdParty]);
+        let html = r#"<html><head>
+            <link rel="stylesheet" href="https://static.example.com/site.css">
+            <link rel="preload stylesheet" href="https://fonts.googleapis.com/css">
+            <script async src="https://www.google-analytics.com/analytics.js"></script>
+            <script src="//cdn.jsdelivr.net/npm/x.js"></script>
+            <script>inline()</script>
+            </head><body>
+            <img src="/logo.png"><img src="data:image/png;base64,AA">
+            <img src="https://www.google-analytics.com/collect?v=1">
+            <iframe src="https://www.youtube.com/embed/x"></iframe>
+            </body></html>"#;
+        let result = analyzer.analyze_string(html).unwrap();
+        let report = result.third_party.as_ref().unwrap();
+        // No page URL: only relative URLs are first-party
+        assert_eq!(report.first_party, 1);
+        assert_eq!(report.origins.len(), 5);
+
+        let mut state = analyzer.incremental();
+        state.set_page(Url::parse("https://www.example.com/").unwrap());
+        state.feed(html.as_bytes()).unwrap();
+        let report = state.finish().unwrap().third_party.unwrap();
+        assert_eq!(report.first_party, 2);
+        assert_eq!(report.third_parties(), 4);
+        let analytics = &report.origins["www.google-analytics.com"];
+        assert_eq!((analytics.scripts, analytics.images), (1, 1));
+        assert_eq!(analytics.tracker.as_deref(), Some("Google Analytics"));
+        assert_eq!(report.origins["fonts.googleapis.com"].stylesheets, 1);
+        assert_eq!(report.origins["cdn.jsdelivr.net"].scripts, 1);
+        assert_eq!(report.origins["www.youtube.com"].iframes, 1);
+        assert_eq!(
+            report.trackers().map(|(host, _)| host).collect::<Vec<_>>(),
+            ["www.google-analytics.com"]
+        );
+
+        let mut merged = report.clone();
+        merged.merge(&report);
+        assert_eq!(merged.first_party, 4);
+        assert_eq!(merged.origins["www.google-analytics.com"].total(), 4);
+    }
+}
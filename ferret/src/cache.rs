@@ -0,0 +1,279 @@
+use crate::analyzer::AnalysisResult;
+use crate::fetch::FetchOptions;
+use anyhow::Result;
+use reqwest::header::{HeaderMap, HeaderName, ETAG, LAST_MODIFIED};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::hash::Hasher;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use tempfile::NamedTempFile;
+
+/// On-disk cache of fetched documents and their analysis, keyed by URL
+///
+/// Responses carrying an `ETag` or `Last-Modified` header are stored together
+/// with their validators. Later fetches of the same URL send a conditional
+/// request (`If-None-Match` / `If-Modified-Since`) and reuse the cached
+/// analysis when the server answers `304 Not Modified`.
+///
+/// # Example
+/// ```no_run
+/// # use ferret::analyzer::stream::StreamAnalyzer;
+/// # async fn run() -> anyhow::Result<()> {
+/// use ferret::cache::HttpCache;
+/// let analyzer = StreamAnalyzer::new(10).with_cache(HttpCache::new(".ferret-cache"));
+/// let result = analyzer.analyze_url("https://example.com/sitemap.xml").await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct HttpCache {
+    dir: PathBuf,
+}
+
+/// A cached response: validators plus the analysis of its body
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheEntry {
+    pub url: String,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    /// `top_values_limit` the stored result was computed with
+    pub top_values_limit: usize,
+    pub result: AnalysisResult,
+}
+
+impl CacheEntry {
+    /// Add the conditional request headers for this entry to `options`
+    pub fn revalidate(&self, mut options: FetchOptions) -> FetchOptions {
+        if let Some(etag) = &self.etag {
+            options = options.header("If-None-Match", etag.as_str());
+        }
+        if let Some(last_modified) = &self.last_modified {
+            options = options.header("If-Modified-Since", last_modified.as_str());
+        }
+        options
+    }
+}
+
+impl HttpCache {
+    /// Use `dir` for cache files; it is created on the first write
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Look up the entry for `url`
+    ///
+    /// Missing or unreadable entries are treated as a cache miss.
+    pub fn get(&self, url: &str) -> Option<CacheEntry> {
+        let file = File::open(self.entry_path(url)).ok()?;
+        let entry: CacheEntry = serde_json::from_reader(std::io::BufReader::new(file)).ok()?;
+        (entry.url == url && self.body_path(url).exists()).then_some(entry)
+    }
+
+    /// Path of the stored response body for `url`
+    pub fn body_path(&self, url: &str) -> PathBuf {
+        self.dir.join(format!("{}.body", cache_key(url)))
+    }
+
+    /// Delete the entry for `url`, if any
+    pub fn remove(&self, url: &str) -> Result<()> {
+        for path in [self.entry_path(url), self.body_path(url)] {
+            match fs::remove_file(path) {
+                Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err.into()),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Start storing a response for `url` if it carries validators
+    ///
+    /// Returns `None` for responses that could never be revalidated.
+    pub(crate) fn writer(&self, url: &str, headers: &HeaderMap) -> Result<Option<CacheWriter>> {
+        let header = |name: HeaderName| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+        let (etag, last_modified) = (header(ETAG), header(LAST_MODIFIED));
+        if etag.is_none() && last_modified.is_none() {
+            return Ok(None);
+        }
+
+        fs::create_dir_all(&self.dir)?;
+        // Unique, so concurrent downloads of a URL don't write to one file
+        let body = NamedTempFile::new_in(&self.dir)?;
+        Ok(Some(CacheWriter {
+            cache: self.clone(),
+            body: BufWriter::new(body),
+            url: url.to_string(),
+            etag,
+            last_modified,
+        }))
+    }
+
+    fn entry_path(&self, url: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", cache_key(url)))
+    }
+}
+
+/// Writes a response body to the cache while it is being analyzed
+///
+/// Nothing becomes visible to `HttpCache::get` until `commit()` succeeds, so
+/// an interrupted download never leaves a partial entry behind; its
+/// temporary file is deleted when the writer is dropped.
+pub(crate) struct CacheWriter {
+    cache: HttpCache,
+    body: BufWriter<NamedTempFile>,
+    url: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+impl CacheWriter {
+    pub(crate) fn write(&mut self, data: &[u8]) -> Result<()> {
+        self.body.write_all(data)?;
+        Ok(())
+    }
+
+    pub(crate) fn commit(self, top_values_limit: usize, result: &AnalysisResult) -> Result<()> {
+        let body = self.body.into_inner().map_err(|err| err.into_error())?;
+        body.persist(self.cache.body_path(&self.url))?;
+
+        let entry = CacheEntry {
+            url: self.url,
+            etag: self.etag,
+            last_modified: self.last_modified,
+            top_values_limit,
+            result: AnalysisResult {
+                redirects: Vec::new(),
+                ..result.clone()
+            },
+        };
+        let mut out = BufWriter::new(NamedTempFile::new_in(&self.cache.dir)?);
+        serde_json::to_writer(&mut out, &entry)?;
+        let out = out.into_inner().map_err(|err| err.into_error())?;
+        out.persist(self.cache.entry_path(&entry.url))?;
+        Ok(())
+    }
+}
+
+/// Stable file name for a URL (64-bit FNV-1a)
+///
+/// `DefaultHasher` is not guaranteed to be stable across Rust releases, which
+/// would silently invalidate the cache after a toolchain upgrade.
+pub(crate) fn cache_key(url: &str) -> String {
+    let mut hasher = Fnv1a::default();
+    hasher.write(url.as_bytes());
+    format!("{:016x}", hasher.finish())
+}
+
+/// 64-bit FNV-1a, for hashes that are persisted and must not change
+/// between builds
+pub(crate) struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+}
+
+impl Hasher for Fnv1a {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(0x100000001b3);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn test_store_and_get() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = HttpCache::new(dir.path().join("cache"));
+        let url = "https://example.com/a.xml";
+        assert!(cache.get(url).is_none());
+
+        let mut headers = HeaderMap::new();
+        assert!(cache.writer(url, &headers).unwrap().is_none());
+
+        headers.insert(ETAG, HeaderValue::from_static("\"v1\""));
+        let mut writer = cache.writer(url, &headers).unwrap().unwrap();
+        writer.write(b"<a></a>").unwrap();
+        assert!(cache.get(url).is_none());
+
+        let result = AnalysisResult {
+            max_depth: 1,
+            ..Default::default()
+        };
+        writer.commit(10, &result).unwrap();
+
+        let entry = cache.get(url).unwrap();
+        assert_eq!(entry.etag.as_deref(), Some("\"v1\""));
+        assert_eq!(entry.last_modified, None);
+        assert_eq!(entry.result.max_depth, 1);
+        assert_eq!(fs::read(cache.body_path(url)).unwrap(), b"<a></a>");
+        assert!(cache.get("https://example.com/b.xml").is_none());
+
+        cache.remove(url).unwrap();
+        assert!(cache.get(url).is_none());
+    }
+
+    #[test]
+    fn test_concurrent_writers() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = HttpCache::new(dir.path());
+        let url = "https://example.com/";
+        let mut headers = HeaderMap::new();
+        headers.insert(ETAG, HeaderValue::from_static("\"v1\""));
+
+        let mut first = cache.writer(url, &headers).unwrap().unwrap();
+        let mut second = cache.writer(url, &headers).unwrap().unwrap();
+        let mut dropped = cache.writer(url, &headers).unwrap().unwrap();
+        first.write(b"<p>first</p>").unwrap();
+        second.write(b"<p>second</p>").unwrap();
+        dropped.write(b"<p>dropped</p>").unwrap();
+        first.commit(10, &AnalysisResult::default()).unwrap();
+        drop(dropped);
+        second.commit(10, &AnalysisResult::default()).unwrap();
+
+        assert_eq!(fs::read(cache.body_path(url)).unwrap(), b"<p>second</p>");
+        // The entry and its body, without temporary files left over
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn test_revalidate_headers() {
+        let entry = CacheEntry {
+            url: "https://example.com/".to_string(),
+            etag: Some("\"v1\"".to_string()),
+            last_modified: Some("Wed, 21 Oct 2015 07:28:00 GMT".to_string()),
+            top_values_limit: 10,
+            result: AnalysisResult::default(),
+        };
+        let options = entry.revalidate(FetchOptions::default());
+        assert_eq!(
+            options.headers,
+            vec![
+                ("If-None-Match".to_string(), "\"v1\"".to_string()),
+                (
+                    "If-Modified-Since".to_string(),
+                    "Wed, 21 Oct 2015 07:28:00 GMT".to_string()
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,665 @@
+//! Layered settings shared by the `ferret` CLI and the API server
+//!
+//! Built-in defaults are overridden by a `ferret.toml` file, then by
+//! `FERRET_*` environment variables; command-line flags and query
+//! parameters override the result.
+//!
+//! ```toml
+//! [analyzer]
+//! top_values = 20
+//! limits = { max_input_bytes = 10485760, max_depth = 512 }
+//!
+//! [fetch]
+//! user_agent = "docs-audit/1.0"
+//! timeout_secs = 30
+//! retries = 2
+//!
+//! [export]
+//! format = "json"
+//!
+//! [server]
+//! port = 3000
+//! allowed_hosts = ["example.com"]
+//! batch_concurrency = 4
+//!
+//! [sink]
+//! url = "nats://localhost:4222"
+//! subject = "ferret.pages"
+//! ```
+
+use crate::analyzer::section::Section;
+use crate::analyzer::stream::StreamAnalyzer;
+use crate::fetch::{
+    matches_host, FetchOptions, HostPolicy, HostThrottle, ProxyConfig, RetryPolicy,
+    DEFAULT_MAX_REDIRECTS,
+};
+use crate::limits::Limits;
+use crate::rules::Rule;
+use crate::sink::Sink;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// File read by [`Config::load`] from the working directory when no path
+/// is given
+pub const CONFIG_FILE: &str = "ferret.toml";
+
+/// Environment variable with the path of the config file
+pub const CONFIG_ENV: &str = "FERRET_CONFIG";
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub analyzer: AnalyzerConfig,
+    pub fetch: FetchConfig,
+    pub export: ExportConfig,
+    pub server: ServerConfig,
+    pub sink: SinkConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AnalyzerConfig {
+    /// Distinct values tracked per attribute (`FERRET_TOP_VALUES`)
+    pub top_values: usize,
+    /// Count parent/child tag pairs
+    pub structure: bool,
+    /// Optional analyses, e.g. `["third-party"]`
+    pub sections: Vec<Section>,
+    pub limits: Limits,
+}
+
+impl Default for AnalyzerConfig {
+    fn default() -> Self {
+        Self {
+            top_values: 10,
+            structure: false,
+            sections: Vec::new(),
+            limits: Limits::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FetchConfig {
+    /// `FERRET_USER_AGENT`
+    pub user_agent: Option<String>,
+    /// Overall limit per request (`FERRET_TIMEOUT`)
+    pub timeout_secs: Option<u64>,
+    pub connect_timeout_secs: Option<u64>,
+    pub read_timeout_secs: Option<u64>,
+    /// Retries of failed requests (`FERRET_RETRIES`)
+    pub retries: u32,
+    pub max_redirects: usize,
+    /// Delay between requests to the same host
+    pub throttle_ms: Option<u64>,
+    /// Forward proxy URL, e.g. `socks5h://proxy:1080` (`FERRET_PROXY`)
+    pub proxy: Option<String>,
+    /// Idle connections kept open per host
+    pub pool_max_idle_per_host: Option<usize>,
+    /// How long an idle connection is kept open
+    pub pool_idle_timeout_secs: Option<u64>,
+}
+
+impl Default for FetchConfig {
+    fn default() -> Self {
+        Self {
+            user_agent: None,
+            timeout_secs: None,
+            connect_timeout_secs: None,
+            read_timeout_secs: None,
+            retries: 0,
+            max_redirects: DEFAULT_MAX_REDIRECTS,
+            throttle_ms: None,
+            proxy: None,
+            pool_max_idle_per_host: None,
+            pool_idle_timeout_secs: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExportConfig {
+    /// Exporter used when a request names none, see
+    /// [`registry`](crate::exporter::registry) (`FERRET_EXPORT_FORMAT`)
+    pub format: String,
+}
+
+impl Default for ExportConfig {
+    fn default() -> Self {
+        Self {
+            format: "csv".to_string(),
+        }
+    }
+}
+
+/// Where crawls publish their pages as they complete, see [`crate::sink`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SinkConfig {
+    /// NATS server, e.g. `nats://localhost:4222`; none publishes nothing
+    /// (`FERRET_SINK_URL`)
+    pub url: Option<String>,
+    /// `FERRET_SINK_SUBJECT`
+    pub subject: String,
+}
+
+impl Default for SinkConfig {
+    fn default() -> Self {
+        Self {
+            url: None,
+            subject: "ferret.pages".to_string(),
+        }
+    }
+}
+
+impl SinkConfig {
+    /// A connection to the configured server, if there is one
+    pub async fn connect(&self) -> Result<Option<Sink>> {
+        match &self.url {
+            Some(url) => Ok(Some(Sink::connect(url, &self.subject).await?)),
+            None => Ok(None),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// `PORT` or `FERRET_PORT`
+    pub port: u16,
+    /// Hosts the server may fetch, including their subdomains; empty allows
+    /// any host (`FERRET_ALLOWED_HOSTS`, comma-separated)
+    pub allowed_hosts: Vec<String>,
+    /// Hosts the server may not fetch, including their subdomains
+    /// (`FERRET_DENIED_HOSTS`, comma-separated)
+    pub denied_hosts: Vec<String>,
+    /// Fetch loopback, private and link-local addresses, which are refused
+    /// by default so clients can't reach the server's own network
+    pub allow_private: bool,
+    /// URLs of a batch request fetched at the same time
+    pub batch_concurrency: usize,
+    /// Most URLs accepted in one batch request
+    pub max_batch_urls: usize,
+    /// Background jobs running at the same time; others wait in the queue
+    pub job_concurrency: usize,
+    /// Jobs kept in memory, queued, running and finished; the oldest
+    /// finished jobs are dropped to make room for new ones
+    pub max_jobs: usize,
+    /// Most pages a job fetches, as batch URLs or crawled pages
+    pub max_job_pages: usize,
+    /// Requests per minute from one client IP address, which may come in
+    /// a burst; `0` disables the limit (`FERRET_RATE_LIMIT`)
+    pub rate_limit: u32,
+    /// Longest time fetching a page may take, redirects, retries and body
+    /// included; each request is also bounded by `fetch.timeout_secs` if
+    /// it's shorter. `0` leaves it to `fetch.timeout_secs`
+    pub fetch_timeout_secs: u64,
+    /// How long `GET /api/report` reuses the analysis of a URL fetched with
+    /// the same options; `0` disables the cache
+    pub cache_ttl_secs: u64,
+    /// Analyses kept by that cache; the oldest are dropped to make room
+    pub cache_entries: usize,
+    /// JSON Lines file keeping every analysis of a fetched page, read back
+    /// on start; without one the history is lost on restart
+    /// (`FERRET_HISTORY_PATH`)
+    pub history_path: Option<PathBuf>,
+    /// Analyses kept in memory for the history and trend endpoints; `0`
+    /// records none
+    pub max_history_runs: usize,
+    /// Pages analyzed again and again from the start, besides those
+    /// registered with `POST /api/admin/schedules`
+    pub schedules: Vec<ScheduleConfig>,
+    /// Shortest interval between two analyses of a scheduled page
+    pub min_schedule_secs: u64,
+    /// Most schedules, configured and registered
+    pub max_schedules: usize,
+    /// URLs notified when a scheduled page changes
+    pub webhooks: Vec<WebhookConfig>,
+    /// Tags that must be added, removed or change count for a scheduled
+    /// analysis to notify the webhooks
+    pub webhook_min_changes: usize,
+    /// Address clients reach the server at, e.g. `https://ferret.example.com`,
+    /// for the links of notifications; they are relative without it
+    pub public_url: Option<String>,
+    /// Rules checked against each scheduled analysis; the webhooks are
+    /// told when a page starts violating one, e.g.
+    /// `alert_rules = ['count(img[alt=""]) > 0']`. Pages of crawl jobs are
+    /// published to the sink with those they violate.
+    pub alert_rules: Vec<Rule>,
+    /// Take the client address from the last `X-Forwarded-For` entry, the
+    /// one added by the reverse proxy the server is only reachable through;
+    /// those before it come from the client
+    pub trust_forwarded_for: bool,
+    /// Most verbose level logged, overall or per module, e.g. `info` or
+    /// `warn,scapi=debug` (`FERRET_LOG`)
+    pub log_filter: String,
+    /// `FERRET_LOG_FORMAT`
+    pub log_format: LogFormat,
+    /// How long requests and running jobs may take to finish once the
+    /// server is told to stop, by SIGTERM or SIGINT
+    pub shutdown_timeout_secs: u64,
+    /// File the jobs unfinished at shutdown are saved to, and queued again
+    /// from on start; without one they are lost (`FERRET_JOBS_PATH`)
+    pub jobs_path: Option<PathBuf>,
+    /// Built web UI served at `/`, relative to the working directory; it
+    /// is served only if it exists (`FERRET_UI_DIR`, empty for none)
+    pub ui_dir: Option<PathBuf>,
+    /// Request headers passed on to the pages fetched for the request,
+    /// e.g. `["authorization", "cookie"]` to analyze pages behind a login;
+    /// empty forwards none (`FERRET_FORWARD_HEADERS`, comma-separated)
+    pub forward_headers: Vec<String>,
+    /// Bearer token of the `/api/admin` routes, which are disabled without
+    /// one (`FERRET_ADMIN_TOKEN`)
+    pub admin_token: Option<String>,
+}
+
+/// A page the server analyzes on an interval, e.g.
+/// `schedules = [{ url = "https://example.com/", interval_secs = 3600 }]`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScheduleConfig {
+    pub url: String,
+    pub interval_secs: u64,
+}
+
+/// Where change notifications are sent, e.g.
+/// `webhooks = [{ url = "https://hooks.example.com/ferret", secret = "..." }]`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// Key of the HMAC-SHA256 signature sent in `X-Ferret-Signature`
+    #[serde(default)]
+    pub secret: Option<String>,
+    #[serde(default)]
+    pub format: WebhookFormat,
+}
+
+/// Body of the notifications sent to a webhook
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookFormat {
+    /// The event itself
+    #[default]
+    Json,
+    /// A Slack message with blocks, for an incoming webhook
+    Slack,
+    /// A Discord message with an embed
+    Discord,
+}
+
+/// How the server writes its log lines
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human readable
+    #[default]
+    Text,
+    /// A JSON object per line, with the fields of the event and its spans
+    Json,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            port: 8080,
+            allowed_hosts: Vec::new(),
+            denied_hosts: Vec::new(),
+            allow_private: false,
+            batch_concurrency: 8,
+            max_batch_urls: 100,
+            job_concurrency: 2,
+            max_jobs: 100,
+            max_job_pages: 1000,
+            rate_limit: 120,
+            fetch_timeout_secs: 30,
+            cache_ttl_secs: 300,
+            cache_entries: 1000,
+            history_path: None,
+            max_history_runs: 10_000,
+            schedules: Vec::new(),
+            min_schedule_secs: 60,
+            max_schedules: 100,
+            webhooks: Vec::new(),
+            webhook_min_changes: 1,
+            public_url: None,
+            alert_rules: Vec::new(),
+            trust_forwarded_for: false,
+            log_filter: "info".to_string(),
+            log_format: LogFormat::Text,
+            shutdown_timeout_secs: 30,
+            jobs_path: None,
+            ui_dir: Some(PathBuf::from("frontend/dist")),
+            forward_headers: Vec::new(),
+            admin_token: None,
+        }
+    }
+}
+
+impl Config {
+    /// Defaults, overridden by the config file and the environment
+    ///
+    /// The file is `path`, else the file named by `FERRET_CONFIG`, else
+    /// `ferret.toml` if it exists. An explicitly named file must exist.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let path = path
+            .map(Path::to_path_buf)
+            .or_else(|| std::env::var_os(CONFIG_ENV).map(PathBuf::from));
+        let mut config = match path {
+            Some(path) => Self::from_file(&path)?,
+            None if Path::new(CONFIG_FILE).is_file() => Self::from_file(Path::new(CONFIG_FILE))?,
+            None => Self::default(),
+        };
+        config.apply_env(|name| std::env::var(name).ok())?;
+        Ok(config)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::from_toml(&text).with_context(|| format!("Invalid config {}", path.display()))
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self> {
+        Ok(toml::from_str(text)?)
+    }
+
+    /// Override settings with the environment variables named in the field
+    /// docs, looked up through `var`
+    pub fn apply_env(&mut self, var: impl Fn(&str) -> Option<String>) -> Result<()> {
+        let parse = |name: &str| -> Result<Option<u64>> {
+            var(name)
+                .map(|value| {
+                    value
+                        .trim()
+                        .parse()
+                        .with_context(|| format!("Invalid {}: {:?}", name, value))
+                })
+                .transpose()
+        };
+
+        if let Some(top_values) = parse("FERRET_TOP_VALUES")? {
+            self.analyzer.top_values = top_values as usize;
+        }
+        if let Some(user_agent) = var("FERRET_USER_AGENT") {
+            self.fetch.user_agent = Some(user_agent);
+        }
+        if let Some(timeout) = parse("FERRET_TIMEOUT")? {
+            self.fetch.timeout_secs = Some(timeout);
+        }
+        if let Some(retries) = parse("FERRET_RETRIES")? {
+            self.fetch.retries = retries
+                .try_into()
+                .context("Invalid FERRET_RETRIES: too large")?;
+        }
+        if let Some(proxy) = var("FERRET_PROXY") {
+            self.fetch.proxy = Some(proxy);
+        }
+        if let Some(format) = var("FERRET_EXPORT_FORMAT") {
+            self.export.format = format;
+        }
+        let port = match parse("FERRET_PORT")? {
+            Some(port) => Some(port),
+            None => parse("PORT")?,
+        };
+        if let Some(port) = port {
+            self.server.port = port
+                .try_into()
+                .with_context(|| format!("Invalid port: {}", port))?;
+        }
+        if let Some(path) = var("FERRET_HISTORY_PATH") {
+            self.server.history_path = Some(PathBuf::from(path));
+        }
+        if let Some(path) = var("FERRET_JOBS_PATH") {
+            self.server.jobs_path = Some(PathBuf::from(path));
+        }
+        if let Some(path) = var("FERRET_UI_DIR") {
+            self.server.ui_dir =
+                Some(PathBuf::from(path)).filter(|path| !path.as_os_str().is_empty());
+        }
+        if let Some(url) = var("FERRET_SINK_URL") {
+            self.sink.url = Some(url).filter(|url| !url.is_empty());
+        }
+        if let Some(subject) = var("FERRET_SINK_SUBJECT") {
+            self.sink.subject = subject;
+        }
+        if let Some(token) = var("FERRET_ADMIN_TOKEN") {
+            self.server.admin_token = Some(token).filter(|token| !token.is_empty());
+        }
+        if let Some(rate_limit) = parse("FERRET_RATE_LIMIT")? {
+            self.server.rate_limit = rate_limit
+                .try_into()
+                .context("Invalid FERRET_RATE_LIMIT: too large")?;
+        }
+        if let Some(filter) = var("FERRET_LOG") {
+            self.server.log_filter = filter;
+        }
+        if let Some(format) = var("FERRET_LOG_FORMAT") {
+            self.server.log_format = match format.trim() {
+                "text" => LogFormat::Text,
+                "json" => LogFormat::Json,
+                _ => anyhow::bail!("Invalid FERRET_LOG_FORMAT: {:?}", format),
+            };
+        }
+        let list = |name: &str| {
+            var(name).map(|list| {
+                list.split(',')
+                    .map(str::trim)
+                    .filter(|item| !item.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+        };
+        if let Some(hosts) = list("FERRET_ALLOWED_HOSTS") {
+            self.server.allowed_hosts = hosts;
+        }
+        if let Some(hosts) = list("FERRET_DENIED_HOSTS") {
+            self.server.denied_hosts = hosts;
+        }
+        if let Some(headers) = list("FERRET_FORWARD_HEADERS") {
+            self.server.forward_headers = headers;
+        }
+        Ok(())
+    }
+
+    /// A stream analyzer with these analyzer and fetch settings
+    pub fn stream_analyzer(&self) -> StreamAnalyzer {
+        StreamAnalyzer::new(self.analyzer.top_values)
+            .with_limits(self.analyzer.limits)
+            .with_structure(self.analyzer.structure)
+            .with_sections(self.analyzer.sections.iter().copied())
+            .with_fetch_options(self.fetch.options())
+    }
+}
+
+impl FetchConfig {
+    pub fn options(&self) -> FetchOptions {
+        let mut options = FetchOptions::default()
+            .retry(RetryPolicy::retries(self.retries))
+            .max_redirects(self.max_redirects);
+        if let Some(user_agent) = &self.user_agent {
+            options = options.user_agent(user_agent);
+        }
+        if let Some(timeout) = self.timeout_secs {
+            options = options.timeout(Duration::from_secs(timeout));
+        }
+        if let Some(timeout) = self.connect_timeout_secs {
+            options = options.connect_timeout(Duration::from_secs(timeout));
+        }
+        if let Some(timeout) = self.read_timeout_secs {
+            options = options.read_timeout(Duration::from_secs(timeout));
+        }
+        if let Some(delay) = self.throttle_ms {
+            options = options.throttle(HostThrottle::new(Duration::from_millis(delay)));
+        }
+        if let Some(proxy) = &self.proxy {
+            options = options.proxy(ProxyConfig::new(proxy));
+        }
+        if let Some(max) = self.pool_max_idle_per_host {
+            options = options.pool_max_idle_per_host(max);
+        }
+        if let Some(timeout) = self.pool_idle_timeout_secs {
+            options = options.pool_idle_timeout(Duration::from_secs(timeout));
+        }
+        options
+    }
+}
+
+impl ServerConfig {
+    /// Whether `url` points to an allowed host or one of its subdomains,
+    /// and not to a denied one
+    ///
+    /// Addresses are only checked when fetching, by the
+    /// [`host_policy`](Self::host_policy).
+    pub fn allows(&self, url: &str) -> bool {
+        if self.allowed_hosts.is_empty() && self.denied_hosts.is_empty() {
+            return true;
+        }
+        let Ok(url) = reqwest::Url::parse(url) else {
+            return false;
+        };
+        let Some(host) = url.host_str() else {
+            return false;
+        };
+        !self
+            .denied_hosts
+            .iter()
+            .any(|denied| matches_host(host, denied))
+            && (self.allowed_hosts.is_empty()
+                || self
+                    .allowed_hosts
+                    .iter()
+                    .any(|allowed| matches_host(host, allowed)))
+    }
+
+    /// The hosts and addresses the server may fetch, for every request
+    /// including redirects
+    pub fn host_policy(&self) -> HostPolicy {
+        HostPolicy {
+            allowed: self.allowed_hosts.clone(),
+            denied: self.denied_hosts.clone(),
+            allow_private: self.allow_private,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_from_toml() {
+        let config = Config::from_toml(
+            r#"
+            [analyzer]
+            top_values = 3
+            sections = ["third-party"]
+            limits = { max_depth = 64 }
+
+            [fetch]
+            user_agent = "audit"
+            retries = 2
+
+            [server]
+            allowed_hosts = ["example.com"]
+            batch_concurrency = 2
+            schedules = [{ url = "https://example.com/", interval_secs = 600 }]
+            webhooks = [{ url = "https://hooks.slack.com/services/x", format = "slack" }]
+            alert_rules = ["max_depth > 40"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.analyzer.top_values, 3);
+        assert_eq!(config.analyzer.sections, [Section::ThirdParty]);
+        assert_eq!(config.analyzer.limits.max_depth, Some(64));
+        assert_eq!(config.fetch.retries, 2);
+        assert_eq!(config.fetch.max_redirects, DEFAULT_MAX_REDIRECTS);
+        assert_eq!(config.export, ExportConfig::default());
+        assert_eq!(config.server.port, 8080);
+        assert_eq!(config.server.batch_concurrency, 2);
+        assert_eq!(config.server.max_batch_urls, 100);
+        assert_eq!(config.server.max_job_pages, 1000);
+        assert_eq!(config.server.schedules[0].interval_secs, 600);
+        assert_eq!(config.server.webhooks[0].format, WebhookFormat::Slack);
+        assert_eq!(config.server.alert_rules, [Rule::max_depth(40)]);
+
+        let options = config.fetch.options();
+        assert_eq!(options.user_agent.as_deref(), Some("audit"));
+        assert_eq!(options.retry.max_retries, 2);
+
+        assert_eq!(Config::from_toml("").unwrap(), Config::default());
+        assert!(Config::from_toml("[fetch]\nuser-agent = \"x\"").is_err());
+        assert!(Config::from_toml("[server]\nalert_rules = [\"depth > 1\"]").is_err());
+    }
+
+    #[test]
+    fn test_apply_env() {
+        let env: HashMap<_, _> = [
+            ("FERRET_TOP_VALUES", "4"),
+            ("FERRET_PROXY", "socks5h://proxy:1080"),
+            ("PORT", "3000"),
+            ("FERRET_ALLOWED_HOSTS", "example.com, example.org,"),
+            ("FERRET_RATE_LIMIT", "0"),
+            ("FERRET_DENIED_HOSTS", "internal.example.com"),
+            ("FERRET_HISTORY_PATH", "runs.jsonl"),
+            ("FERRET_LOG_FORMAT", "json"),
+            ("FERRET_UI_DIR", ""),
+            ("FERRET_FORWARD_HEADERS", "Authorization,x-api-key"),
+            ("FERRET_ADMIN_TOKEN", "secret"),
+            ("FERRET_SINK_URL", "nats://localhost:4222"),
+        ]
+        .into_iter()
+        .collect();
+        let mut config = Config::default();
+        config
+            .apply_env(|name| env.get(name).map(|value| value.to_string()))
+            .unwrap();
+        assert_eq!(config.analyzer.top_values, 4);
+        assert_eq!(config.fetch.proxy.as_deref(), Some("socks5h://proxy:1080"));
+        assert_eq!(config.server.port, 3000);
+        assert_eq!(config.server.allowed_hosts, ["example.com", "example.org"]);
+        assert_eq!(config.server.rate_limit, 0);
+        assert_eq!(config.server.denied_hosts, ["internal.example.com"]);
+        assert_eq!(
+            config.server.history_path.as_deref(),
+            Some(Path::new("runs.jsonl"))
+        );
+        assert_eq!(config.server.log_format, LogFormat::Json);
+        assert_eq!(config.server.ui_dir, None);
+        assert_eq!(
+            config.server.forward_headers,
+            ["Authorization", "x-api-key"]
+        );
+        assert_eq!(config.server.admin_token.as_deref(), Some("secret"));
+        assert_eq!(config.sink.url.as_deref(), Some("nats://localhost:4222"));
+        assert_eq!(config.sink.subject, "ferret.pages");
+
+        let err = config
+            .apply_env(|name| (name == "FERRET_PORT").then(|| "70000".to_string()))
+            .unwrap_err();
+        assert!(err.to_string().contains("port"), "{}", err);
+    }
+
+    #[test]
+    fn test_allows() {
+        let mut server = ServerConfig::default();
+        assert!(server.allows("https://anything.test/"));
+
+        server.allowed_hosts = vec!["Example.com".to_string()];
+        assert!(server.allows("https://example.com/a"));
+        assert!(server.allows("http://www.EXAMPLE.com:8080/"));
+        assert!(!server.allows("https://badexample.com/"));
+        assert!(!server.allows("https://example.com.evil.test/"));
+        assert!(!server.allows("not a url"));
+
+        server.denied_hosts = vec!["internal.example.com".to_string()];
+        assert!(server.allows("https://www.example.com/"));
+        assert!(!server.allows("https://api.internal.example.com/"));
+        assert!(!server.host_policy().allow_private);
+    }
+}
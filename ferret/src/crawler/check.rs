@@ -0,0 +1,271 @@
+use crate::analyzer::LinkCounts;
+use crate::crawler::Link;
+use crate::fetch::{FetchOptions, Fetched, RedirectHop};
+use anyhow::Result;
+use futures::StreamExt;
+use reqwest::{Client, Method};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Outcome of requesting a link target
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LinkStatus {
+    /// Status of the final response after redirects; `None` if no response
+    /// arrived
+    pub status: Option<u16>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub redirects: Vec<RedirectHop>,
+    /// Why no response arrived, e.g. an unknown host or a timeout
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl LinkStatus {
+    /// Answered with a 4xx or 5xx status, or not at all
+    pub fn is_broken(&self) -> bool {
+        self.status.is_none_or(|status| status >= 400)
+    }
+
+    pub fn is_redirected(&self) -> bool {
+        !self.redirects.is_empty()
+    }
+
+    pub fn is_unreachable(&self) -> bool {
+        self.status.is_none()
+    }
+}
+
+/// A link found on a page and what requesting it gave
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CheckedLink {
+    pub url: String,
+    pub text: String,
+    #[serde(flatten)]
+    pub status: LinkStatus,
+}
+
+/// The checked links of one page, in document order
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PageLinks {
+    pub source: String,
+    pub links: Vec<CheckedLink>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LinkReport {
+    pub pages: Vec<PageLinks>,
+}
+
+impl LinkReport {
+    /// Broken links with the page they were found on
+    pub fn broken(&self) -> impl Iterator<Item = (&str, &CheckedLink)> {
+        self.links().filter(|(_, link)| link.status.is_broken())
+    }
+
+    /// Redirecting links with the page they were found on
+    pub fn redirected(&self) -> impl Iterator<Item = (&str, &CheckedLink)> {
+        self.links().filter(|(_, link)| link.status.is_redirected())
+    }
+
+    /// Counts of distinct link targets, however many pages link to them
+    pub fn counts(&self) -> LinkCounts {
+        let mut seen = HashSet::new();
+        let mut counts = LinkCounts::default();
+        for (_, link) in self.links() {
+            if !seen.insert(&link.url) {
+                continue;
+            }
+            counts.checked += 1;
+            counts.broken += usize::from(link.status.is_broken());
+            counts.redirected += usize::from(link.status.is_redirected());
+            counts.unreachable += usize::from(link.status.is_unreachable());
+        }
+        counts
+    }
+
+    fn links(&self) -> impl Iterator<Item = (&str, &CheckedLink)> {
+        self.pages.iter().flat_map(|page| {
+            page.links
+                .iter()
+                .map(move |link| (page.source.as_str(), link))
+        })
+    }
+}
+
+/// Requests the targets of links to find broken ones and redirects
+///
+/// Each distinct URL is requested once with `HEAD`, and again with `GET`
+/// when the server answers `HEAD` with an error, as some don't support it.
+/// The fetch options' throttle, retries and redirect limit apply.
+///
+/// # Example
+/// ```no_run
+/// # async fn run() -> anyhow::Result<()> {
+/// use ferret::crawler::{extract_links, LinkChecker};
+/// use ferret::fetch::FetchOptions;
+///
+/// let base = "https://example.com/".parse()?;
+/// let links = extract_links(r#"<a href="/missing">Gone</a>"#, &base);
+/// let report = LinkChecker::new(FetchOptions::default())
+///     .check([(base.to_string(), links)])
+///     .await?;
+/// for (source, link) in report.broken() {
+///     println!("{}: {} {:?}", source, link.url, link.status.status);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct LinkChecker {
+    fetch: FetchOptions,
+    concurrency: usize,
+}
+
+impl LinkChecker {
+    /// A checker sending 8 requests at a time
+    pub fn new(fetch: FetchOptions) -> Self {
+        Self {
+            fetch,
+            concurrency: 8,
+        }
+    }
+
+    /// Requests sent at the same time
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Check the links of each `(source page, links)` pair
+    ///
+    /// Fails only if no HTTP client can be built from the fetch options.
+    pub async fn check<I>(&self, pages: I) -> Result<LinkReport>
+    where
+        I: IntoIterator<Item = (String, Vec<Link>)>,
+    {
+        self.check_known(pages, HashMap::new()).await
+    }
+
+    /// Like [`check`](Self::check), without requesting the URLs in `known`
+    pub(crate) async fn check_known<I>(
+        &self,
+        pages: I,
+        mut known: HashMap<String, LinkStatus>,
+    ) -> Result<LinkReport>
+    where
+        I: IntoIterator<Item = (String, Vec<Link>)>,
+    {
+        let pages: Vec<_> = pages.into_iter().collect();
+        let mut pending = Vec::new();
+        let mut queued = HashSet::new();
+        for link in pages.iter().flat_map(|(_, links)| links) {
+            if !known.contains_key(&link.url) && queued.insert(&link.url) {
+                pending.push(link.url.clone());
+            }
+        }
+
+        let client = self.fetch.build_client()?;
+        let checked: Vec<_> = futures::stream::iter(pending)
+            .map(|url| async {
+                let status = self.check_url(&client, &url).await;
+                (url, status)
+            })
+            .buffer_unordered(self.concurrency)
+            .collect()
+            .await;
+        known.extend(checked);
+
+        let pages = pages
+            .into_iter()
+            .map(|(source, links)| PageLinks {
+                source,
+                links: links
+                    .into_iter()
+                    .map(|link| CheckedLink {
+                        status: known[&link.url].clone(),
+                        url: link.url,
+                        text: link.text,
+                    })
+                    .collect(),
+            })
+            .collect();
+        Ok(LinkReport { pages })
+    }
+
+    async fn check_url(&self, client: &Client, url: &str) -> LinkStatus {
+        let head = self.request(client, Method::HEAD, url).await;
+        if !head.is_broken() || head.is_unreachable() {
+            return head;
+        }
+        self.request(client, Method::GET, url).await
+    }
+
+    async fn request(&self, client: &Client, method: Method, url: &str) -> LinkStatus {
+        match self.fetch.send_with_method(client, method, url).await {
+            Ok(Fetched {
+                response,
+                redirects,
+            }) => LinkStatus {
+                status: Some(response.status().as_u16()),
+                redirects,
+                error: None,
+            },
+            Err(err) => LinkStatus {
+                status: None,
+                redirects: Vec::new(),
+                error: Some(format!("{:#}", err)),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts() {
+        let link = |url: &str, status: Option<u16>, redirects: usize| CheckedLink {
+            url: url.to_string(),
+            text: String::new(),
+            status: LinkStatus {
+                status,
+                redirects: vec![
+                    RedirectHop {
+                        url: url.to_string(),
+                        status: 301,
+                        location: format!("{}/", url),
+                    };
+                    redirects
+                ],
+                error: status.is_none().then(|| "unreachable".to_string()),
+            },
+        };
+        let report = LinkReport {
+            pages: vec![
+                PageLinks {
+                    source: "https://example.com/".to_string(),
+                    links: vec![
+                        link("https://example.com/a", Some(200), 1),
+                        link("https://example.com/gone", Some(404), 0),
+                        link("https://down.test", None, 0),
+                    ],
+                },
+                PageLinks {
+                    source: "https://example.com/a".to_string(),
+                    links: vec![link("https://example.com/gone", Some(404), 0)],
+                },
+            ],
+        };
+        assert_eq!(
+            report.counts(),
+            LinkCounts {
+                checked: 3,
+                broken: 2,
+                redirected: 1,
+                unreachable: 1,
+            }
+        );
+        assert_eq!(report.broken().count(), 3);
+        assert_eq!(report.redirected().count(), 1);
+    }
+}
@@ -0,0 +1,799 @@
+//! Breadth-first website crawling
+//!
+//! Starting from seed URLs, pages are fetched level by level, analyzed, and
+//! their `<a href>` links followed while they stay in scope, up to a maximum
+//! link depth and page count.
+
+use crate::analyzer::stream::StreamAnalyzer;
+use crate::analyzer::{AnalysisResult, AnalysisResultSet, SourceResult};
+use crate::fetch::{matches_host, FetchOptions, Fetched, HostThrottle};
+use crate::limits::Limits;
+use crate::parser::FerretParser;
+use crate::progress::ProgressEvent;
+use crate::similarity::{cluster_templates, TemplateCluster};
+use crate::sink::Sink;
+use crate::sniff::{self, ParseMode};
+use anyhow::{Context, Result};
+use futures::StreamExt;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{Client, Response, Url};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+mod check;
+mod normalize;
+mod sitemap;
+
+pub use check::{CheckedLink, LinkChecker, LinkReport, LinkStatus, PageLinks};
+pub use normalize::{UrlNormalizer, TRACKING_PARAMS};
+pub use sitemap::{
+    parse_sitemap, SitemapCoverage, SitemapEntries, SitemapSource, Sitemaps, MAX_SITEMAPS,
+};
+
+/// Delay between requests to the same host unless the analyzer's fetch
+/// options already set a throttle
+pub const DEFAULT_DELAY: Duration = Duration::from_millis(250);
+
+/// Hosts whose pages are crawled
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Scope {
+    /// The hosts of the seed URLs
+    #[default]
+    SeedHosts,
+    /// These hosts and their subdomains
+    Hosts(Vec<String>),
+    /// Every host; only sensible with a small page limit
+    Any,
+}
+
+/// Crawls websites with a [`StreamAnalyzer`]'s fetch options and limits
+///
+/// # Example
+/// ```no_run
+/// # async fn run() -> anyhow::Result<()> {
+/// use ferret::analyzer::stream::StreamAnalyzer;
+/// use ferret::crawler::Crawler;
+///
+/// let crawler = Crawler::new(StreamAnalyzer::new(10))
+///     .with_max_depth(2)
+///     .with_max_pages(50);
+/// let crawl = crawler.crawl(["https://example.com/"]).await?;
+/// println!("{} pages, {} tags", crawl.pages.len(), crawl.aggregate.tags.len());
+/// # Ok(())
+/// # }
+/// ```
+pub struct Crawler {
+    analyzer: StreamAnalyzer,
+    scope: Scope,
+    max_depth: usize,
+    max_pages: usize,
+    concurrency: usize,
+    link_check: bool,
+    normalizer: UrlNormalizer,
+    sitemap: Option<SitemapSource>,
+    sink: Option<Sink>,
+}
+
+impl Crawler {
+    /// A crawler following links two levels deep, for at most 100 pages,
+    /// fetching 4 pages at a time and waiting [`DEFAULT_DELAY`] between
+    /// requests to the same host
+    pub fn new(mut analyzer: StreamAnalyzer) -> Self {
+        if analyzer.fetch.throttle.is_none() {
+            analyzer.fetch.throttle = Some(HostThrottle::new(DEFAULT_DELAY));
+        }
+        Self {
+            analyzer,
+            scope: Scope::default(),
+            max_depth: 2,
+            max_pages: 100,
+            concurrency: 4,
+            link_check: false,
+            normalizer: UrlNormalizer::default(),
+            sitemap: None,
+            sink: None,
+        }
+    }
+
+    pub fn with_scope(mut self, scope: Scope) -> Self {
+        self.scope = scope;
+        self
+    }
+
+    /// Link hops followed from the seeds; `0` only fetches the seeds
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Pages fetched in total, including failed ones
+    pub fn with_max_pages(mut self, max_pages: usize) -> Self {
+        self.max_pages = max_pages;
+        self
+    }
+
+    /// Pages fetched at the same time
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// After crawling, request every link found on the crawled pages,
+    /// including out-of-scope ones, to find broken links and redirects;
+    /// see [`LinkChecker`]
+    pub fn with_link_check(mut self, link_check: bool) -> Self {
+        self.link_check = link_check;
+        self
+    }
+
+    /// How seeds and links are normalized before they are compared;
+    /// defaults to [`UrlNormalizer::default`]
+    pub fn with_normalizer(mut self, normalizer: UrlNormalizer) -> Self {
+        self.normalizer = normalizer;
+        self
+    }
+
+    /// After crawling, compare the pages found with the URLs listed in the
+    /// site's sitemaps; see [`SitemapCoverage`]
+    pub fn with_sitemap(mut self, source: SitemapSource) -> Self {
+        self.sitemap = Some(source);
+        self
+    }
+
+    /// Publish each page to `sink` as soon as it is analyzed
+    pub fn with_sink(mut self, sink: Sink) -> Self {
+        self.sink = Some(sink);
+        self
+    }
+
+    /// Wait `delay` between requests to the same host
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.analyzer.fetch.throttle = Some(HostThrottle::new(delay));
+        self
+    }
+
+    /// Crawl breadth first from `seeds`
+    ///
+    /// Fails only for invalid seed URLs; pages that can't be fetched or
+    /// analyzed are reported in their entry.
+    pub async fn crawl<I>(&self, seeds: I) -> Result<CrawlResult>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        let mut frontier = Vec::new();
+        for seed in seeds {
+            let seed = seed.as_ref();
+            let url = Url::parse(seed).with_context(|| format!("Invalid URL {}", seed))?;
+            frontier.push(self.normalizer.normalize(url));
+        }
+        let seeds = frontier.clone();
+        let seed_hosts: Vec<String> = frontier
+            .iter()
+            .filter_map(|url| url.host_str().map(str::to_string))
+            .collect();
+
+        let client = self.analyzer.fetch.build_client()?;
+        let mut seen: HashSet<String> = frontier.iter().map(|url| url.to_string()).collect();
+        // Canonical URL of each analyzed page, and the page claiming it
+        let mut claimed: HashMap<String, String> = HashMap::new();
+        let mut pages: Vec<CrawledPage> = Vec::new();
+        let mut depth = 0;
+        while !frontier.is_empty() && pages.len() < self.max_pages {
+            frontier.truncate(self.max_pages - pages.len());
+            let known = seen.len();
+            let crawled: Vec<_> = futures::stream::iter(frontier)
+                .map(|url| self.crawl_page(&client, url, depth))
+                .buffered(self.concurrency)
+                .collect()
+                .await;
+
+            let mut next = Vec::new();
+            for (mut page, final_url) in crawled {
+                if let Some(final_url) = final_url {
+                    seen.insert(final_url);
+                }
+                if page.result.is_some() {
+                    let canonical = page.canonical.clone().unwrap_or_else(|| page.url.clone());
+                    match claimed.get(&canonical) {
+                        Some(original) => {
+                            page.duplicate_of = Some(original.clone());
+                            page.result = None;
+                        }
+                        None => {
+                            claimed.insert(canonical.clone(), page.url.clone());
+                            seen.insert(canonical);
+                        }
+                    }
+                }
+                // The links of a duplicate are those of its original
+                if depth < self.max_depth && page.duplicate_of.is_none() {
+                    for link in &page.links {
+                        let Ok(url) = Url::parse(&link.url) else {
+                            continue;
+                        };
+                        if self.in_scope(&url, &seed_hosts) && seen.insert(link.url.clone()) {
+                            next.push(url);
+                        }
+                    }
+                }
+                pages.push(page);
+                if let (Some(sink), Some(page)) = (&self.sink, pages.last()) {
+                    sink.publish(page);
+                }
+                if let (Some(progress), Some(page)) = (&self.analyzer.progress, pages.last()) {
+                    progress.report(ProgressEvent::SourceCompleted {
+                        source: page.url.clone(),
+                        completed: pages.len(),
+                        total: known.min(self.max_pages),
+                        error: page.error.clone(),
+                        tags: page
+                            .result
+                            .as_ref()
+                            .map(AnalysisResult::tag_counts)
+                            .unwrap_or_default(),
+                    });
+                }
+            }
+            frontier = next;
+            depth += 1;
+        }
+
+        let mut aggregate = AnalysisResult::default();
+        for result in pages.iter().filter_map(|page| page.result.as_ref()) {
+            aggregate.merge(result, self.analyzer.top_values_limit);
+        }
+        let mut link_check = None;
+        if self.link_check {
+            let report = self.check_links(&pages).await?;
+            aggregate.links = Some(report.counts());
+            link_check = Some(report);
+        }
+        let mut crawl = CrawlResult {
+            pages,
+            aggregate,
+            link_check,
+            sitemap: None,
+            clusters: Vec::new(),
+        };
+        if let Some(source) = &self.sitemap {
+            let urls = match source {
+                SitemapSource::Discover => {
+                    Sitemaps::discover(&self.analyzer.fetch, &client, &seeds).await
+                }
+                SitemapSource::Urls(urls) => urls.clone(),
+            };
+            let sitemaps =
+                Sitemaps::fetch(&self.analyzer.fetch, &client, &self.analyzer.limits, urls).await;
+            crawl.sitemap = Some(SitemapCoverage::new(&crawl, &sitemaps, &self.normalizer));
+        }
+        Ok(crawl)
+    }
+
+    /// Check the links of `pages`, reusing the status of crawled pages
+    async fn check_links(&self, pages: &[CrawledPage]) -> Result<LinkReport> {
+        let known = pages
+            .iter()
+            .map(|page| {
+                let status = LinkStatus {
+                    status: page.status,
+                    redirects: page
+                        .result
+                        .as_ref()
+                        .map(|result| result.redirects.clone())
+                        .unwrap_or_default(),
+                    error: page.status.is_none().then(|| page.error.clone()).flatten(),
+                };
+                (page.url.clone(), status)
+            })
+            .collect();
+        let linking = pages
+            .iter()
+            .filter(|page| !page.links.is_empty())
+            .map(|page| (page.url.clone(), page.links.clone()));
+        LinkChecker::new(self.analyzer.fetch.clone())
+            .with_concurrency(self.concurrency)
+            .check_known(linking, known)
+            .await
+    }
+
+    fn in_scope(&self, url: &Url, seed_hosts: &[String]) -> bool {
+        let Some(host) = url.host_str() else {
+            return false;
+        };
+        match &self.scope {
+            Scope::SeedHosts => seed_hosts
+                .iter()
+                .any(|seed| seed.eq_ignore_ascii_case(host)),
+            Scope::Hosts(hosts) => hosts.iter().any(|allowed| matches_host(host, allowed)),
+            Scope::Any => true,
+        }
+    }
+
+    /// The crawled page and, after redirects, the URL it was served from
+    async fn crawl_page(
+        &self,
+        client: &Client,
+        url: Url,
+        depth: usize,
+    ) -> (CrawledPage, Option<String>) {
+        let mut page = CrawledPage {
+            url: url.to_string(),
+            depth,
+            status: None,
+            result: None,
+            error: None,
+            links: Vec::new(),
+            canonical: None,
+            duplicate_of: None,
+            cluster: None,
+        };
+        match self.fetch_page(client, &mut page).await {
+            Ok(final_url) => (page, Some(final_url.to_string())),
+            Err(err) => {
+                page.error = Some(format!("{:#}", err));
+                (page, None)
+            }
+        }
+    }
+
+    /// Fill in the status, analysis and links of `page`, returning the URL
+    /// it was served from
+    async fn fetch_page(&self, client: &Client, page: &mut CrawledPage) -> Result<Url> {
+        let fetch = &self.analyzer.fetch;
+        let limits = &self.analyzer.limits;
+        let Fetched {
+            response,
+            redirects,
+        } = fetch.send(client, &page.url).await?;
+        page.status = Some(response.status().as_u16());
+        if !response.status().is_success() {
+            anyhow::bail!("HTTP error: {}", response.status());
+        }
+        let final_url = response.url().clone();
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        let mut analyzer = self.analyzer.incremental();
+        analyzer.set_page(final_url.clone());
+        analyzer.set_headers(response.headers());
+        let body = read_body(fetch, limits, response).await?;
+
+        let mode = sniff::detect(content_type.as_deref(), &body)?;
+        analyzer.set_mode(mode);
+        analyzer.feed(&body)?;
+        let mut result = analyzer.finish()?;
+        result.redirects = redirects;
+
+        if mode != ParseMode::Xml {
+            let (links, canonical) = scan_links(
+                &String::from_utf8_lossy(&body),
+                &final_url,
+                &self.normalizer,
+            );
+            page.links = links;
+            page.canonical = canonical
+                .map(String::from)
+                .filter(|canonical| *canonical != page.url);
+        }
+        page.result = Some(result);
+        Ok(self.normalizer.normalize(final_url))
+    }
+}
+
+/// The body of `response`, failing once it exceeds `limits`
+async fn read_body(
+    fetch: &FetchOptions,
+    limits: &Limits,
+    mut response: Response,
+) -> Result<Vec<u8>> {
+    if let Some(length) = response.content_length() {
+        limits.check_input(length as usize)?;
+    }
+    let mut body = Vec::new();
+    while let Some(chunk) = fetch.read(response.chunk()).await?? {
+        body.extend_from_slice(&chunk);
+        limits.check_input(body.len())?;
+    }
+    Ok(body)
+}
+
+/// Targets of the `<a href>` and `<area href>` elements in `html`
+///
+/// Links are resolved against `base`, stripped of their fragment and
+/// returned in document order without duplicates, keeping the text of the
+/// first link to each target; only http(s) links are kept.
+pub fn extract_links(html: &str, base: &Url) -> Vec<Link> {
+    scan_links(html, base, &UrlNormalizer::minimal()).0
+}
+
+/// The `<link rel="canonical">` URL of `html`, resolved against `base`
+pub fn canonical_url(html: &str, base: &Url) -> Option<Url> {
+    scan_links(html, base, &UrlNormalizer::minimal()).1
+}
+
+/// Links and canonical URL of `html`, normalized with `normalizer`
+fn scan_links(html: &str, base: &Url, normalizer: &UrlNormalizer) -> (Vec<Link>, Option<Url>) {
+    let Ok(vdom) = FerretParser::parse(html) else {
+        return (Vec::new(), None);
+    };
+    let attribute = |tag: &tl::HTMLTag, name: &str| {
+        tag.attributes()
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .and_then(|(_, value)| value)
+            .map(|value| value.into_owned())
+    };
+    let mut seen = HashSet::new();
+    let mut links = Vec::new();
+    let mut canonical = None;
+    for node in vdom.nodes() {
+        let Some(tag) = node.as_tag() else { continue };
+        let name = tag.name().as_utf8_str();
+        let is_area = name.eq_ignore_ascii_case("area");
+        let is_link = name.eq_ignore_ascii_case("link");
+        if !is_area && !is_link && !name.eq_ignore_ascii_case("a") {
+            continue;
+        }
+        let Some(href) = attribute(tag, "href") else {
+            continue;
+        };
+        let Some(url) = normalizer.resolve(base, &href.replace("&amp;", "&")) else {
+            continue;
+        };
+        if is_link {
+            let rel = attribute(tag, "rel").unwrap_or_default();
+            if canonical.is_none()
+                && rel
+                    .split_whitespace()
+                    .any(|rel| rel.eq_ignore_ascii_case("canonical"))
+            {
+                canonical = Some(url);
+            }
+            continue;
+        }
+        if seen.insert(url.to_string()) {
+            let text = if is_area {
+                attribute(tag, "alt").unwrap_or_default()
+            } else {
+                node.inner_text(vdom.parser()).into_owned()
+            };
+            links.push(Link {
+                url: url.into(),
+                text: text.split_whitespace().collect::<Vec<_>>().join(" "),
+            });
+        }
+    }
+    (links, canonical)
+}
+
+/// Pages of a crawl and their merged analysis
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CrawlResult {
+    /// Pages in crawl order: breadth first, seeds first
+    pub pages: Vec<CrawledPage>,
+    /// All successfully analyzed pages merged together
+    pub aggregate: AnalysisResult,
+    /// Status of every link, with [`Crawler::with_link_check`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link_check: Option<LinkReport>,
+    /// Comparison with the site's sitemaps, with [`Crawler::with_sitemap`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sitemap: Option<SitemapCoverage>,
+    /// Groups of pages sharing a template, once
+    /// [`cluster_templates`](Self::cluster_templates) ran
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub clusters: Vec<TemplateCluster>,
+}
+
+impl CrawlResult {
+    /// Pages that failed to fetch or analyze
+    pub fn errors(&self) -> impl Iterator<Item = &CrawledPage> {
+        self.pages.iter().filter(|page| page.error.is_some())
+    }
+
+    /// Crawled pages as nodes, followed by the uncrawled link targets, with
+    /// an edge for each link
+    ///
+    /// Links to the URL a crawled page redirected to point to that page.
+    pub fn link_graph(&self) -> LinkGraph {
+        let mut graph = LinkGraph::default();
+        let mut index: HashMap<&str, usize> = HashMap::new();
+        for page in &self.pages {
+            index.insert(&page.url, graph.nodes.len());
+            graph.nodes.push(GraphNode {
+                url: page.url.clone(),
+                depth: Some(page.depth),
+                error: page.error.clone(),
+            });
+        }
+        for (node, page) in self.pages.iter().enumerate() {
+            let redirected = page
+                .result
+                .as_ref()
+                .and_then(|result| result.redirects.last());
+            if let Some(hop) = redirected {
+                index.entry(&hop.location).or_insert(node);
+            }
+        }
+        for (source, page) in self.pages.iter().enumerate() {
+            for link in &page.links {
+                let target = *index.entry(&link.url).or_insert_with(|| {
+                    graph.nodes.push(GraphNode {
+                        url: link.url.clone(),
+                        depth: None,
+                        error: None,
+                    });
+                    graph.nodes.len() - 1
+                });
+                graph.edges.push(GraphEdge {
+                    source,
+                    target,
+                    text: link.text.clone(),
+                });
+            }
+        }
+        graph
+    }
+
+    /// Group analyzed pages with near-identical structure, setting each
+    /// page's `cluster` and filling `clusters`; see
+    /// [`AnalysisResultSet::cluster_templates`]
+    pub fn cluster_templates(&mut self, max_distance: u32) {
+        let (ids, clusters) = cluster_templates(
+            self.pages
+                .iter()
+                .map(|page| (page.url.as_str(), page.result.as_ref())),
+            max_distance,
+        );
+        for (page, id) in self.pages.iter_mut().zip(ids) {
+            page.cluster = id;
+        }
+        self.clusters = clusters;
+    }
+
+    /// The pages as a batch result, e.g. for exporters and reporters
+    pub fn to_result_set(&self) -> AnalysisResultSet {
+        AnalysisResultSet {
+            entries: self
+                .pages
+                .iter()
+                .map(|page| SourceResult {
+                    source: page.url.clone(),
+                    result: page.result.clone(),
+                    error: page.error.clone(),
+                    cluster: page.cluster,
+                })
+                .collect(),
+            aggregate: self.aggregate.clone(),
+            clusters: self.clusters.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrawledPage {
+    /// URL as linked; see `result.redirects` for where it led
+    pub url: String,
+    /// Link hops from the nearest seed; `0` for seeds
+    pub depth: usize,
+    /// Status of the final response; `None` if none arrived
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    pub result: Option<AnalysisResult>,
+    /// Why the page could not be fetched or analyzed
+    pub error: Option<String>,
+    /// Links on the page, see [`extract_links`]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub links: Vec<Link>,
+    /// The page's `<link rel="canonical">` URL, if it isn't `url`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub canonical: Option<String>,
+    /// Earlier page with the same canonical URL; the analysis of this one is
+    /// dropped and its links aren't followed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duplicate_of: Option<String>,
+    /// Id of the page's [`TemplateCluster`], once
+    /// [`CrawlResult::cluster_templates`] ran
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cluster: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Link {
+    /// Absolute target without fragment
+    pub url: String,
+    /// Anchor text with whitespace collapsed, or `alt` of an `<area>`
+    pub text: String,
+}
+
+/// Pages and the links between them, see [`CrawlResult::link_graph`]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LinkGraph {
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GraphNode {
+    pub url: String,
+    /// Depth the page was crawled at; `None` for link targets that weren't
+    /// crawled because they were out of scope or beyond the limits
+    pub depth: Option<usize>,
+    pub error: Option<String>,
+}
+
+impl GraphNode {
+    pub fn crawled(&self) -> bool {
+        self.depth.is_some()
+    }
+}
+
+/// A link from one page to another
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GraphEdge {
+    /// Index of the linking page in `nodes`
+    pub source: usize,
+    /// Index of the linked page in `nodes`
+    pub target: usize,
+    pub text: String,
+}
+
+impl LinkGraph {
+    /// Only the crawled pages and the links between them
+    pub fn crawled_only(self) -> Self {
+        let mut index = vec![None; self.nodes.len()];
+        let mut nodes = Vec::new();
+        for (old, node) in self.nodes.into_iter().enumerate() {
+            if node.crawled() {
+                index[old] = Some(nodes.len());
+                nodes.push(node);
+            }
+        }
+        let edges = self
+            .edges
+            .into_iter()
+            .filter_map(|edge| {
+                Some(GraphEdge {
+                    source: index[edge.source]?,
+                    target: index[edge.target]?,
+                    text: edge.text,
+                })
+            })
+            .collect();
+        Self { nodes, edges }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_links() {
+        let base = Url::parse("https://example.com/docs/index.html").unwrap();
+        let html = r##"
+            <a href="intro.html#top">Intro</a>
+            <A HREF="/about?a=1&amp;b=2">About</A>
+            <a href="intro.html">Intro again</a>
+            <a href="#section">Same page</a>
+            <a href="mailto:team@example.com">Mail</a>
+            <a name="anchor">No href</a>
+            <map><area href="https://other.test/map" alt="Map"></map>
+        "##;
+        let links: Vec<(String, String)> = extract_links(html, &base)
+            .into_iter()
+            .map(|link| (link.url, link.text))
+            .collect();
+        let expected = [
+            ("https://example.com/docs/intro.html", "Intro"),
+            ("https://example.com/about?a=1&b=2", "About"),
+            ("https://example.com/docs/index.html", "Same page"),
+            ("https://other.test/map", "Map"),
+        ];
+        assert_eq!(
+            links,
+            expected.map(|(url, text)| (url.to_string(), text.to_string()))
+        );
+    }
+
+    #[test]
+    fn test_canonical_url() {
+        let base = Url::parse("https://example.com/a?page=2").unwrap();
+        let html = r#"<head><link rel="stylesheet" href="/s.css">
+            <link REL="Canonical" href="/a#main"><link rel="canonical" href="/b"></head>"#;
+        assert_eq!(
+            canonical_url(html, &base).unwrap().as_str(),
+            "https://example.com/a"
+        );
+        assert!(canonical_url("<a href=\"/b\">b</a>", &base).is_none());
+    }
+
+    #[test]
+    fn test_link_graph() {
+        let link = |url: &str, text: &str| Link {
+            url: url.to_string(),
+            text: text.to_string(),
+        };
+        let page = |url: &str, depth, links| CrawledPage {
+            url: url.to_string(),
+            depth,
+            status: Some(200),
+            result: Some(AnalysisResult::default()),
+            error: None,
+            links,
+            canonical: None,
+            duplicate_of: None,
+            cluster: None,
+        };
+        let mut old = page(
+            "https://example.com/old",
+            1,
+            vec![link("https://example.com/", "Home")],
+        );
+        old.result.as_mut().unwrap().redirects = vec![crate::fetch::RedirectHop {
+            url: old.url.clone(),
+            status: 301,
+            location: "https://example.com/new".to_string(),
+        }];
+        let crawl = CrawlResult {
+            pages: vec![
+                page(
+                    "https://example.com/",
+                    0,
+                    vec![
+                        link("https://example.com/old", "Old"),
+                        link("https://example.com/new", "New"),
+                        link("https://other.test/", "Out"),
+                    ],
+                ),
+                old,
+            ],
+            aggregate: AnalysisResult::default(),
+            link_check: None,
+            sitemap: None,
+            clusters: Vec::new(),
+        };
+
+        let graph = crawl.link_graph();
+        let urls: Vec<_> = graph.nodes.iter().map(|node| node.url.as_str()).collect();
+        assert_eq!(
+            urls,
+            [
+                "https://example.com/",
+                "https://example.com/old",
+                "https://other.test/"
+            ]
+        );
+        assert!(!graph.nodes[2].crawled());
+        let edges: Vec<_> = graph
+            .edges
+            .iter()
+            .map(|edge| (edge.source, edge.target, edge.text.as_str()))
+            .collect();
+        assert_eq!(
+            edges,
+            [(0, 1, "Old"), (0, 1, "New"), (0, 2, "Out"), (1, 0, "Home")]
+        );
+
+        let graph = graph.crawled_only();
+        assert_eq!(graph.nodes.len(), 2);
+        assert_eq!(graph.edges.len(), 3);
+    }
+
+    #[test]
+    fn test_scope() {
+        let crawler = Crawler::new(StreamAnalyzer::new(10));
+        let seeds = vec!["example.com".to_string()];
+        let url = |url: &str| Url::parse(url).unwrap();
+        assert!(crawler.in_scope(&url("https://EXAMPLE.com/a"), &seeds));
+        assert!(!crawler.in_scope(&url("https://www.example.com/a"), &seeds));
+
+        let crawler = crawler.with_scope(Scope::Hosts(vec!["example.com".to_string()]));
+        assert!(crawler.in_scope(&url("https://www.example.com/a"), &seeds));
+        assert!(!crawler.in_scope(&url("https://example.org/"), &seeds));
+    }
+}
@@ -0,0 +1,176 @@
+use reqwest::Url;
+
+/// Query parameters dropped by [`UrlNormalizer::default`]: campaign and
+/// click trackers that don't change the page
+pub const TRACKING_PARAMS: [&str; 5] = ["utm_*", "fbclid", "gclid", "msclkid", "mc_eid"];
+
+/// Rewrites URLs so that addresses of the same page compare equal
+///
+/// Fragments are always removed; hosts are lowercased and default ports
+/// dropped when URLs are parsed. On top of that, matching query parameters
+/// are stripped and the remaining ones sorted by name.
+///
+/// # Example
+/// ```
+/// use ferret::crawler::UrlNormalizer;
+///
+/// let url = "https://Example.com:443/a?utm_source=x&b=2&a=1#top".parse()?;
+/// assert_eq!(
+///     UrlNormalizer::default().normalize(url).as_str(),
+///     "https://example.com/a?a=1&b=2"
+/// );
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UrlNormalizer {
+    /// Names of query parameters to remove; a trailing `*` matches any
+    /// suffix, e.g. `utm_*`
+    pub strip_params: Vec<String>,
+    /// Sort the remaining query parameters by name, keeping the order of
+    /// repeated ones
+    pub sort_params: bool,
+}
+
+impl Default for UrlNormalizer {
+    /// Strips [`TRACKING_PARAMS`] and sorts the rest
+    fn default() -> Self {
+        Self {
+            strip_params: TRACKING_PARAMS.map(String::from).to_vec(),
+            sort_params: true,
+        }
+    }
+}
+
+impl UrlNormalizer {
+    /// Only removes fragments, leaving queries alone
+    pub fn minimal() -> Self {
+        Self {
+            strip_params: Vec::new(),
+            sort_params: false,
+        }
+    }
+
+    pub fn with_strip_params<I, S>(mut self, params: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.strip_params = params.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn with_sort_params(mut self, sort_params: bool) -> Self {
+        self.sort_params = sort_params;
+        self
+    }
+
+    pub fn normalize(&self, mut url: Url) -> Url {
+        url.set_fragment(None);
+        if url.query().is_none() {
+            return url;
+        }
+        if self.strip_params.is_empty() && !self.sort_params {
+            if url.query() == Some("") {
+                url.set_query(None);
+            }
+            return url;
+        }
+
+        let mut params: Vec<(String, String)> = url
+            .query_pairs()
+            .filter(|(name, _)| !self.strips(name))
+            .map(|(name, value)| (name.into_owned(), value.into_owned()))
+            .collect();
+        if self.sort_params {
+            params.sort_by(|a, b| a.0.cmp(&b.0));
+        }
+        if params.is_empty() {
+            url.set_query(None);
+        } else {
+            url.query_pairs_mut().clear().extend_pairs(params);
+        }
+        url
+    }
+
+    /// Resolve `href` against `base` and normalize it; `None` unless it is
+    /// a valid http(s) URL
+    pub fn resolve(&self, base: &Url, href: &str) -> Option<Url> {
+        let url = base.join(href.trim()).ok()?;
+        matches!(url.scheme(), "http" | "https").then(|| self.normalize(url))
+    }
+
+    fn strips(&self, name: &str) -> bool {
+        self.strip_params
+            .iter()
+            .any(|pattern| match pattern.strip_suffix('*') {
+                Some(prefix) => name.starts_with(prefix),
+                None => name == pattern,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalize(normalizer: &UrlNormalizer, url: &str) -> String {
+        normalizer.normalize(url.parse().unwrap()).into()
+    }
+
+    #[test]
+    fn test_normalize() {
+        let normalizer = UrlNormalizer::default();
+        assert_eq!(
+            normalize(&normalizer, "HTTP://WWW.Example.COM:80/Path?x=1#f"),
+            "http://www.example.com/Path?x=1"
+        );
+        assert_eq!(
+            normalize(
+                &normalizer,
+                "https://example.com/?utm_source=a&utm_medium=b"
+            ),
+            "https://example.com/"
+        );
+        assert_eq!(
+            normalize(&normalizer, "https://example.com/?b=2&a=1&b=1&gclid=x"),
+            "https://example.com/?a=1&b=2&b=1"
+        );
+        assert_eq!(
+            normalize(&normalizer, "https://example.com/?q=a+b%26c"),
+            "https://example.com/?q=a+b%26c"
+        );
+
+        let plain = UrlNormalizer::minimal();
+        assert_eq!(
+            normalize(&plain, "https://example.com/?utm_source=a&b=1#f"),
+            "https://example.com/?utm_source=a&b=1"
+        );
+        assert_eq!(
+            normalize(&plain, "https://example.com/?"),
+            "https://example.com/"
+        );
+
+        let custom = UrlNormalizer::minimal().with_strip_params(["session", "ref_*"]);
+        assert_eq!(
+            normalize(
+                &custom,
+                "https://example.com/?session=1&ref_id=2&referrer=3"
+            ),
+            "https://example.com/?referrer=3"
+        );
+    }
+
+    #[test]
+    fn test_resolve() {
+        let normalizer = UrlNormalizer::default();
+        let base: Url = "https://example.com/docs/".parse().unwrap();
+        assert_eq!(
+            normalizer
+                .resolve(&base, " ../a?utm_id=1#x ")
+                .unwrap()
+                .as_str(),
+            "https://example.com/a"
+        );
+        assert!(normalizer.resolve(&base, "javascript:void(0)").is_none());
+    }
+}
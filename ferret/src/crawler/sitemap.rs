@@ -0,0 +1,345 @@
+use crate::crawler::{read_body, CrawlResult, UrlNormalizer};
+use crate::fetch::{FetchOptions, Fetched};
+use crate::limits::Limits;
+use crate::robots::RobotsTxt;
+use anyhow::{bail, Result};
+use flate2::read::MultiGzDecoder;
+use quick_xml::events::Event;
+use quick_xml::reader::Reader;
+use reqwest::{Client, Url};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::io::Read;
+
+/// Sitemaps read at most, counting those listed in sitemap indexes
+pub const MAX_SITEMAPS: usize = 50;
+
+/// Where a crawl finds the sitemaps it is compared with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SitemapSource {
+    /// The `Sitemap:` lines of each seed host's robots.txt, else its
+    /// `/sitemap.xml`
+    Discover,
+    Urls(Vec<String>),
+}
+
+/// Entries of one sitemap file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SitemapEntries {
+    /// `<url><loc>` page URLs
+    pub urls: Vec<String>,
+    /// `<sitemap><loc>` URLs of a sitemap index
+    pub sitemaps: Vec<String>,
+}
+
+/// Read a sitemap or sitemap index, gzipped or not
+///
+/// # Example
+/// ```
+/// use ferret::crawler::parse_sitemap;
+///
+/// let xml = br#"<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+///     <url><loc>https://example.com/?a=1&amp;b=2</loc></url>
+/// </urlset>"#;
+/// assert_eq!(parse_sitemap(xml)?.urls, ["https://example.com/?a=1&b=2"]);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn parse_sitemap(xml: &[u8]) -> Result<SitemapEntries> {
+    let mut decompressed = Vec::new();
+    let xml = if xml.starts_with(&[0x1f, 0x8b]) {
+        MultiGzDecoder::new(xml).read_to_end(&mut decompressed)?;
+        &decompressed[..]
+    } else {
+        xml
+    };
+
+    let mut reader = Reader::from_reader(xml);
+    let mut entries = SitemapEntries::default();
+    let mut buf = Vec::new();
+    // Local names of the open elements
+    let mut open: Vec<Vec<u8>> = Vec::new();
+    // Text of a `<loc>` directly inside `<url>` or `<sitemap>`
+    let mut loc: Option<String> = None;
+    loop {
+        match reader.read_event_into(&mut buf)? {
+            Event::Start(start) => {
+                let name = start.local_name().as_ref().to_vec();
+                let parent = open.last().map(Vec::as_slice);
+                if name == b"loc" && matches!(parent, Some(b"url" | b"sitemap")) {
+                    loc = Some(String::new());
+                }
+                open.push(name);
+            }
+            Event::Text(text) => {
+                if let Some(loc) = &mut loc {
+                    loc.push_str(&text.unescape()?);
+                }
+            }
+            Event::CData(data) => {
+                if let Some(loc) = &mut loc {
+                    loc.push_str(&String::from_utf8_lossy(&data));
+                }
+            }
+            Event::End(_) => {
+                let closed = open.pop();
+                if let (Some(b"loc"), Some(url), Some(parent)) =
+                    (closed.as_deref(), loc.take(), open.last())
+                {
+                    let url = url.trim().to_string();
+                    match parent.as_slice() {
+                        _ if url.is_empty() => {}
+                        b"url" => entries.urls.push(url),
+                        _ => entries.sitemaps.push(url),
+                    }
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+    Ok(entries)
+}
+
+/// Page URLs of a set of sitemaps
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Sitemaps {
+    /// Sitemaps read, including those listed in indexes
+    pub read: Vec<String>,
+    /// Page URLs in the order listed
+    pub urls: Vec<String>,
+    /// Sitemaps that could not be read, with the reason
+    pub errors: Vec<String>,
+}
+
+impl Sitemaps {
+    /// Fetch the sitemaps at `urls` and, up to [`MAX_SITEMAPS`], those
+    /// listed in sitemap indexes
+    pub async fn fetch(
+        fetch: &FetchOptions,
+        client: &Client,
+        limits: &Limits,
+        urls: Vec<String>,
+    ) -> Self {
+        let mut sitemaps = Self::default();
+        let mut queue = urls;
+        let mut seen = HashSet::new();
+        let mut next = 0;
+        while next < queue.len() && sitemaps.read.len() < MAX_SITEMAPS {
+            let url = queue[next].clone();
+            next += 1;
+            if !seen.insert(url.clone()) {
+                continue;
+            }
+            match fetch_sitemap(fetch, client, limits, &url).await {
+                Ok(entries) => {
+                    sitemaps.urls.extend(entries.urls);
+                    queue.extend(entries.sitemaps);
+                    sitemaps.read.push(url);
+                }
+                Err(err) => sitemaps.errors.push(format!("{}: {:#}", url, err)),
+            }
+        }
+        sitemaps
+    }
+
+    /// Sitemaps named by the robots.txt of each seed's origin, or the
+    /// origin's `/sitemap.xml` if there are none
+    pub async fn discover(fetch: &FetchOptions, client: &Client, seeds: &[Url]) -> Vec<String> {
+        let mut origins = Vec::new();
+        for seed in seeds {
+            let origin = seed.origin().ascii_serialization();
+            if !origins.contains(&origin) {
+                origins.push(origin);
+            }
+        }
+        let mut sitemaps = Vec::new();
+        for origin in origins {
+            let robots = format!("{}/robots.txt", origin);
+            let listed = match fetch.send(client, &robots).await {
+                Ok(Fetched { response, .. }) if response.status().is_success() => response
+                    .text()
+                    .await
+                    .map(|text| RobotsTxt::parse(&text).sitemaps().to_vec())
+                    .unwrap_or_default(),
+                _ => Vec::new(),
+            };
+            if listed.is_empty() {
+                sitemaps.push(format!("{}/sitemap.xml", origin));
+            }
+            sitemaps.extend(listed);
+        }
+        sitemaps
+    }
+}
+
+async fn fetch_sitemap(
+    fetch: &FetchOptions,
+    client: &Client,
+    limits: &Limits,
+    url: &str,
+) -> Result<SitemapEntries> {
+    let Fetched { response, .. } = fetch.send(client, url).await?;
+    if !response.status().is_success() {
+        bail!("HTTP error: {}", response.status());
+    }
+    let body = read_body(fetch, limits, response).await?;
+    parse_sitemap(&body)
+}
+
+/// How a crawl and the site's sitemaps cover each other
+///
+/// Only the crawled pages' links are known, so with a depth or page limit
+/// some listed URLs may be reported as unlinked although deeper pages link
+/// to them.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SitemapCoverage {
+    /// Sitemaps read, including those listed in indexes
+    pub sitemaps: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+    /// Distinct page URLs listed
+    pub listed: usize,
+    /// Listed URLs that were neither seeds nor linked from a crawled page
+    pub not_linked: Vec<String>,
+    /// Crawled pages the sitemaps don't list under their URL, the URL they
+    /// redirected to or their canonical URL
+    pub not_listed: Vec<String>,
+}
+
+impl SitemapCoverage {
+    /// Compare `crawl` with `sitemaps`, normalizing URLs with `normalizer`
+    pub fn new(crawl: &CrawlResult, sitemaps: &Sitemaps, normalizer: &UrlNormalizer) -> Self {
+        let normalize = |url: &str| {
+            Url::parse(url)
+                .map(|url| normalizer.normalize(url).to_string())
+                .unwrap_or_else(|_| url.to_string())
+        };
+        let mut listed = Vec::new();
+        let mut listed_set = HashSet::new();
+        for url in &sitemaps.urls {
+            let url = normalize(url);
+            if listed_set.insert(url.clone()) {
+                listed.push(url);
+            }
+        }
+
+        let mut discovered = HashSet::new();
+        let mut not_listed = Vec::new();
+        for page in &crawl.pages {
+            let redirected = page
+                .result
+                .as_ref()
+                .and_then(|result| result.redirects.last())
+                .map(|hop| normalize(&hop.location));
+            let names: Vec<String> = [Some(page.url.clone()), redirected, page.canonical.clone()]
+                .into_iter()
+                .flatten()
+                .collect();
+            let reached = page
+                .status
+                .is_some_and(|status| (200..300).contains(&status));
+            if reached
+                && page.duplicate_of.is_none()
+                && !names.iter().any(|name| listed_set.contains(name))
+            {
+                not_listed.push(page.url.clone());
+            }
+            discovered.extend(names);
+            discovered.extend(page.links.iter().map(|link| link.url.clone()));
+        }
+
+        Self {
+            sitemaps: sitemaps.read.clone(),
+            errors: sitemaps.errors.clone(),
+            listed: listed.len(),
+            not_linked: listed
+                .into_iter()
+                .filter(|url| !discovered.contains(url))
+                .collect(),
+            not_listed,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::AnalysisResult;
+    use crate::crawler::{CrawledPage, Link};
+    use flate2::write::GzEncoder;
+    use std::io::Write;
+
+    #[test]
+    fn test_parse_sitemap() {
+        let index = br#"<?xml version="1.0"?>
+            <sitemapindex xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+              <sitemap><loc> https://example.com/a.xml </loc><lastmod>2024-01-01</lastmod></sitemap>
+              <sitemap><loc><![CDATA[https://example.com/b.xml.gz]]></loc></sitemap>
+            </sitemapindex>"#;
+        let entries = parse_sitemap(index).unwrap();
+        assert!(entries.urls.is_empty());
+        assert_eq!(
+            entries.sitemaps,
+            ["https://example.com/a.xml", "https://example.com/b.xml.gz"]
+        );
+
+        let urlset = br#"<urlset><url><loc>https://example.com/</loc>
+            <image:image><image:loc>https://example.com/i.png</image:loc></image:image></url>
+            <url><loc></loc></url></urlset>"#;
+        let mut gzipped = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gzipped.write_all(urlset).unwrap();
+        let entries = parse_sitemap(&gzipped.finish().unwrap()).unwrap();
+        assert_eq!(entries.urls, ["https://example.com/"]);
+
+        assert!(parse_sitemap(b"<urlset><url></urlset>").is_err());
+    }
+
+    #[test]
+    fn test_coverage() {
+        let page = |url: &str, status, links: &[&str]| CrawledPage {
+            url: url.to_string(),
+            depth: 0,
+            status: Some(status),
+            result: Some(AnalysisResult::default()),
+            error: None,
+            links: links
+                .iter()
+                .map(|url| Link {
+                    url: url.to_string(),
+                    text: String::new(),
+                })
+                .collect(),
+            canonical: None,
+            duplicate_of: None,
+            cluster: None,
+        };
+        let crawl = CrawlResult {
+            pages: vec![
+                page(
+                    "https://example.com/",
+                    200,
+                    &["https://example.com/a", "https://example.com/gone"],
+                ),
+                page("https://example.com/a", 200, &[]),
+                page("https://example.com/gone", 404, &[]),
+            ],
+            ..Default::default()
+        };
+        let sitemaps = Sitemaps {
+            read: vec!["https://example.com/sitemap.xml".to_string()],
+            urls: [
+                "https://example.com/",
+                "https://example.com/?utm_source=x",
+                "https://example.com/orphan",
+            ]
+            .map(String::from)
+            .to_vec(),
+            errors: Vec::new(),
+        };
+        let coverage = SitemapCoverage::new(&crawl, &sitemaps, &UrlNormalizer::default());
+        assert_eq!(coverage.listed, 2);
+        assert_eq!(coverage.not_linked, ["https://example.com/orphan"]);
+        assert_eq!(coverage.not_listed, ["https://example.com/a"]);
+    }
+}
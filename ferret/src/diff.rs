@@ -0,0 +1,202 @@
+use crate::analyzer::{AnalysisResult, AttributeStats, TagStats};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+
+/// How a tag or attribute differs between two results
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Change {
+    /// Only in the new result
+    Added,
+    /// Only in the old result
+    Removed,
+    /// In both, with different counts (for tags: or changed attributes)
+    Changed,
+    Unchanged,
+}
+
+impl Change {
+    fn between(old: Option<usize>, new: Option<usize>) -> Self {
+        match (old, new) {
+            (None, Some(_)) => Change::Added,
+            (Some(_), None) => Change::Removed,
+            (Some(old), Some(new)) if old != new => Change::Changed,
+            _ => Change::Unchanged,
+        }
+    }
+}
+
+/// Structural differences between two analysis results, e.g. two versions
+/// of a page or two runs over a site
+///
+/// # Example
+/// ```
+/// # use ferret::analyzer::stream::StreamAnalyzer;
+/// use ferret::diff::{AnalysisDiff, Change};
+///
+/// let analyzer = StreamAnalyzer::new(10);
+/// let old = analyzer.analyze_string(r#"<div><p>a</p></div>"#)?;
+/// let new = analyzer.analyze_string(r#"<div class="x"><p>a</p><p>b</p></div>"#)?;
+///
+/// let diff = AnalysisDiff::new(&old, &new);
+/// let p = diff.tags.iter().find(|tag| tag.name == "p").unwrap();
+/// assert_eq!((p.change, p.delta()), (Change::Changed, 1));
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnalysisDiff {
+    /// Every tag of either result, sorted by name
+    pub tags: Vec<TagDiff>,
+    pub old_max_depth: usize,
+    pub new_max_depth: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TagDiff {
+    pub name: String,
+    pub change: Change,
+    /// 0 when the tag is added
+    pub old_count: usize,
+    /// 0 when the tag is removed
+    pub new_count: usize,
+    /// Every attribute of the tag in either result, sorted by name
+    pub attributes: Vec<AttributeDiff>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AttributeDiff {
+    pub name: String,
+    pub change: Change,
+    pub old_count: usize,
+    pub new_count: usize,
+}
+
+impl AnalysisDiff {
+    pub fn new(old: &AnalysisResult, new: &AnalysisResult) -> Self {
+        let names: BTreeSet<&String> = old.tags.keys().chain(new.tags.keys()).collect();
+        let tags = names
+            .into_iter()
+            .map(|name| TagDiff::new(name, old.tags.get(name), new.tags.get(name)))
+            .collect();
+        Self {
+            tags,
+            old_max_depth: old.max_depth,
+            new_max_depth: new.max_depth,
+        }
+    }
+
+    /// Whether both results have the same tags and attributes with the
+    /// same counts
+    pub fn is_empty(&self) -> bool {
+        self.changes().next().is_none() && self.old_max_depth == self.new_max_depth
+    }
+
+    /// Tags that were added, removed or changed
+    pub fn changes(&self) -> impl Iterator<Item = &TagDiff> {
+        self.tags
+            .iter()
+            .filter(|tag| tag.change != Change::Unchanged)
+    }
+}
+
+impl TagDiff {
+    fn new(name: &str, old: Option<&TagStats>, new: Option<&TagStats>) -> Self {
+        let empty = HashMap::new();
+        let old_attributes = old.map_or(&empty, |tag| &tag.attributes);
+        let new_attributes = new.map_or(&empty, |tag| &tag.attributes);
+        let attributes: Vec<AttributeDiff> = old_attributes
+            .keys()
+            .chain(new_attributes.keys())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .map(|attr| {
+                AttributeDiff::new(attr, old_attributes.get(attr), new_attributes.get(attr))
+            })
+            .collect();
+
+        let mut change = Change::between(old.map(|tag| tag.count), new.map(|tag| tag.count));
+        if change == Change::Unchanged
+            && attributes
+                .iter()
+                .any(|attr| attr.change != Change::Unchanged)
+        {
+            change = Change::Changed;
+        }
+
+        Self {
+            name: name.to_string(),
+            change,
+            old_count: old.map_or(0, |tag| tag.count),
+            new_count: new.map_or(0, |tag| tag.count),
+            attributes,
+        }
+    }
+
+    /// `new_count - old_count`
+    pub fn delta(&self) -> i64 {
+        self.new_count as i64 - self.old_count as i64
+    }
+}
+
+impl AttributeDiff {
+    fn new(name: &str, old: Option<&AttributeStats>, new: Option<&AttributeStats>) -> Self {
+        Self {
+            name: name.to_string(),
+            change: Change::between(old.map(|attr| attr.count), new.map(|attr| attr.count)),
+            old_count: old.map_or(0, |attr| attr.count),
+            new_count: new.map_or(0, |attr| attr.count),
+        }
+    }
+
+    /// `new_count - old_count`
+    pub fn delta(&self) -> i64 {
+        self.new_count as i64 - self.old_count as i64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::stream::StreamAnalyzer;
+
+    #[test]
+    fn test_diff() {
+        let analyzer = StreamAnalyzer::new(10);
+        let old = analyzer
+            .analyze_string(r#"<div id="a"><p>1</p><span>x</span><img src="a"/></div>"#)
+            .unwrap();
+        let new = analyzer
+            .analyze_string(r#"<div class="b"><p>1</p><p>2</p><img src="b"/><em>y</em></div>"#)
+            .unwrap();
+        let diff = AnalysisDiff::new(&old, &new);
+
+        let names: Vec<_> = diff.tags.iter().map(|tag| tag.name.as_str()).collect();
+        assert_eq!(names, ["div", "em", "img", "p", "span"]);
+        let tag = |name: &str| diff.tags.iter().find(|tag| tag.name == name).unwrap();
+
+        assert_eq!(tag("em").change, Change::Added);
+        assert_eq!(tag("span").change, Change::Removed);
+        assert_eq!(tag("span").delta(), -1);
+        assert_eq!(tag("p").change, Change::Changed);
+        // Same counts; values don't matter
+        assert_eq!(tag("img").change, Change::Unchanged);
+
+        // Same count, different attributes
+        let div = tag("div");
+        assert_eq!(div.change, Change::Changed);
+        assert_eq!(div.delta(), 0);
+        let changes: Vec<_> = div
+            .attributes
+            .iter()
+            .map(|attr| (attr.name.as_str(), attr.change, attr.delta()))
+            .collect();
+        assert_eq!(
+            changes,
+            [("class", Change::Added, 1), ("id", Change::Removed, -1)]
+        );
+
+        assert_eq!(diff.changes().count(), 4);
+        assert!(!diff.is_empty());
+        assert!(AnalysisDiff::new(&old, &old).is_empty());
+    }
+}
@@ -0,0 +1,40 @@
+use std::time::Duration;
+use thiserror::Error;
+
+/// Typed failures raised by the analyzers.
+///
+/// Public methods still return `anyhow::Result`; callers that need to react to
+/// a specific failure (e.g. map a limit violation to an HTTP status) can use
+/// `err.downcast_ref::<FerretError>()`.
+#[derive(Debug, Error)]
+pub enum FerretError {
+    #[error("input exceeds the limit of {limit} bytes")]
+    InputTooLarge { limit: usize },
+
+    #[error("document exceeds the limit of {limit} nodes")]
+    TooManyNodes { limit: usize },
+
+    #[error("document exceeds the maximum depth of {limit}")]
+    TooDeep { limit: usize },
+
+    #[error("document has more than {limit} parse errors")]
+    TooManyParseErrors { limit: usize },
+
+    #[error("no response data received for {0:?}")]
+    ReadTimeout(Duration),
+
+    #[error("stopped after {0} redirects")]
+    TooManyRedirects(usize),
+
+    #[error("{0} is disallowed by robots.txt")]
+    RobotsDisallowed(String),
+
+    #[error("fetching {url} is not allowed: {reason}")]
+    HostNotAllowed { url: String, reason: String },
+
+    #[error("analysis was cancelled")]
+    Cancelled,
+
+    #[error("unsupported content: {0}")]
+    UnsupportedContent(String),
+}
@@ -0,0 +1,396 @@
+//! Static SVG charts shared by the HTML and SVG exporters
+//!
+//! Charts are plain SVG strings without scripts, so they render in
+//! browsers, wikis and mail clients alike. Every mark carries a `<title>`
+//! for hover tooltips.
+
+use crate::html::escape;
+use std::fmt::Write;
+
+/// Width of every chart in pixels
+pub(crate) const CHART_WIDTH: f64 = 720.0;
+
+/// Colors of bars and treemap groups, cycled
+pub(crate) const PALETTE: &[&str] = &[
+    "#4e79a7", "#f28e2b", "#e15759", "#76b7b2", "#59a14f", "#edc948", "#b07aa1", "#ff9da7",
+    "#9c755f", "#bab0ac",
+];
+
+const FONT: &str = "font-family=\"Helvetica, Arial, sans-serif\" font-size=\"12\"";
+
+/// A rendered chart
+pub(crate) struct Chart {
+    /// Standalone `<svg>` element
+    pub(crate) svg: String,
+    pub(crate) height: f64,
+}
+
+/// Horizontal bar chart with one labelled bar per `(label, value)`
+pub(crate) fn bar_chart(title: &str, bars: &[(String, usize)]) -> Chart {
+    const ROW: f64 = 22.0;
+    const LABEL_WIDTH: f64 = 160.0;
+    const VALUE_WIDTH: f64 = 70.0;
+    const TOP: f64 = 30.0;
+
+    let height = TOP + ROW * bars.len().max(1) as f64 + 10.0;
+    let max = bars
+        .iter()
+        .map(|(_, value)| *value)
+        .max()
+        .unwrap_or(0)
+        .max(1) as f64;
+    let bar_area = CHART_WIDTH - LABEL_WIDTH - VALUE_WIDTH;
+
+    let mut svg = open_svg(title, height);
+    for (index, (label, value)) in bars.iter().enumerate() {
+        let y = TOP + ROW * index as f64;
+        let width = (*value as f64 / max * bar_area).max(1.0);
+        let _ = write!(
+            svg,
+            "<g><title>{label}: {value}</title>\
+             <text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"end\">{}</text>\
+             <rect x=\"{LABEL_WIDTH:.1}\" y=\"{:.1}\" width=\"{width:.1}\" height=\"{:.1}\" fill=\"{}\"/>\
+             <text x=\"{:.1}\" y=\"{:.1}\">{value}</text></g>",
+            LABEL_WIDTH - 8.0,
+            y + 15.0,
+            escape(&shorten(label, 24)),
+            y + 3.0,
+            ROW - 6.0,
+            PALETTE[0],
+            LABEL_WIDTH + width + 6.0,
+            y + 15.0,
+            label = escape(label),
+        );
+    }
+    svg.push_str("</svg>");
+    Chart { svg, height }
+}
+
+/// Vertical histogram; bar `i` is labelled `i + 1`
+pub(crate) fn histogram(title: &str, x_label: &str, counts: &[usize]) -> Chart {
+    const HEIGHT: f64 = 260.0;
+    const LEFT: f64 = 60.0;
+    const TOP: f64 = 30.0;
+    const BOTTOM: f64 = 40.0;
+
+    let max = counts.iter().copied().max().unwrap_or(0).max(1);
+    let plot_width = CHART_WIDTH - LEFT - 10.0;
+    let plot_height = HEIGHT - TOP - BOTTOM;
+    let slot = plot_width / counts.len().max(1) as f64;
+    // Label at most ~25 bars along the axis
+    let label_every = counts.len().div_ceil(25).max(1);
+
+    let mut svg = open_svg(title, HEIGHT);
+    let base = TOP + plot_height;
+    let _ = write!(
+        svg,
+        "<line x1=\"{LEFT}\" y1=\"{base}\" x2=\"{:.1}\" y2=\"{base}\" stroke=\"#666\"/>\
+         <text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"end\">{max}</text>\
+         <text x=\"{:.1}\" y=\"{base}\" text-anchor=\"end\">0</text>\
+         <text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\">{}</text>",
+        LEFT + plot_width,
+        LEFT - 6.0,
+        TOP + 10.0,
+        LEFT - 6.0,
+        LEFT + plot_width / 2.0,
+        HEIGHT - 6.0,
+        escape(x_label),
+    );
+    for (index, &count) in counts.iter().enumerate() {
+        let x = LEFT + slot * index as f64;
+        let height = count as f64 / max as f64 * plot_height;
+        let _ = write!(
+            svg,
+            "<g><title>{}: {count}</title>\
+             <rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{height:.1}\" fill=\"{}\"/>",
+            index + 1,
+            x + slot * 0.1,
+            base - height,
+            (slot * 0.8).max(1.0),
+            PALETTE[0],
+        );
+        if index % label_every == 0 {
+            let _ = write!(
+                svg,
+                "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\">{}</text>",
+                x + slot / 2.0,
+                base + 16.0,
+                index + 1
+            );
+        }
+        svg.push_str("</g>");
+    }
+    svg.push_str("</svg>");
+    Chart {
+        svg,
+        height: HEIGHT,
+    }
+}
+
+/// A cell of a treemap: `label` sized by `value`, colored by `group`
+pub(crate) struct TreemapItem {
+    pub(crate) label: String,
+    pub(crate) group: String,
+    pub(crate) value: usize,
+}
+
+/// Squarified treemap of `items`; cells of the same group share a color
+pub(crate) fn treemap(title: &str, items: &[TreemapItem]) -> Chart {
+    const HEIGHT: f64 = 420.0;
+    const TOP: f64 = 30.0;
+
+    let mut items: Vec<&TreemapItem> = items.iter().filter(|item| item.value > 0).collect();
+    items.sort_by(|a, b| b.value.cmp(&a.value).then_with(|| a.label.cmp(&b.label)));
+
+    let mut groups: Vec<&str> = Vec::new();
+    for item in &items {
+        if !groups.contains(&item.group.as_str()) {
+            groups.push(&item.group);
+        }
+    }
+
+    let area = Rect {
+        x: 0.0,
+        y: TOP,
+        width: CHART_WIDTH,
+        height: HEIGHT - TOP,
+    };
+    let values: Vec<f64> = items.iter().map(|item| item.value as f64).collect();
+    let cells = squarify(&values, area);
+
+    let mut svg = open_svg(title, HEIGHT);
+    for (item, cell) in items.iter().zip(cells) {
+        let group = groups.iter().position(|group| *group == item.group);
+        let color = PALETTE[group.unwrap_or(0) % PALETTE.len()];
+        let _ = write!(
+            svg,
+            "<g><title>{}: {}</title>\
+             <rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"{color}\" stroke=\"#fff\"/>",
+            escape(&item.label),
+            item.value,
+            cell.x,
+            cell.y,
+            cell.width,
+            cell.height,
+        );
+        // Only label cells with room for a few characters
+        if cell.width >= 40.0 && cell.height >= 18.0 {
+            let chars = ((cell.width - 8.0) / 7.0) as usize;
+            let _ = write!(
+                svg,
+                "<text x=\"{:.1}\" y=\"{:.1}\" fill=\"#fff\">{}</text>",
+                cell.x + 4.0,
+                cell.y + 14.0,
+                escape(&shorten(&item.label, chars)),
+            );
+        }
+        svg.push_str("</g>");
+    }
+    svg.push_str("</svg>");
+    Chart {
+        svg,
+        height: HEIGHT,
+    }
+}
+
+/// One SVG document with `charts` stacked top to bottom
+pub(crate) fn stack(charts: &[Chart]) -> String {
+    const GAP: f64 = 24.0;
+
+    let height = charts.iter().map(|chart| chart.height + GAP).sum::<f64>() - GAP;
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{CHART_WIDTH}\" height=\"{height}\" \
+         viewBox=\"0 0 {CHART_WIDTH} {height}\">\
+         <rect width=\"100%\" height=\"100%\" fill=\"#fff\"/>"
+    );
+    let mut y = 0.0;
+    for chart in charts {
+        svg.push_str(&chart.svg.replacen("<svg ", &format!("<svg y=\"{y}\" "), 1));
+        y += chart.height + GAP;
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+fn open_svg(title: &str, height: f64) -> String {
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{CHART_WIDTH}\" height=\"{height}\" \
+         viewBox=\"0 0 {CHART_WIDTH} {height}\" {FONT} role=\"img\">\
+         <title>{title}</title>\
+         <text x=\"0\" y=\"18\" font-size=\"15\" font-weight=\"bold\">{title}</text>",
+        title = escape(title)
+    )
+}
+
+/// `text` cut to `max_chars` characters, ending in `…` when shortened
+pub(crate) fn shorten(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let mut short: String = text.chars().take(max_chars.saturating_sub(1)).collect();
+    short.push('…');
+    short
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Rect {
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+}
+
+/// Lay out `values` (sorted, largest first) in `area` with cells as close
+/// to square as possible (Bruls, Huizing and van Wijk's algorithm)
+fn squarify(values: &[f64], mut area: Rect) -> Vec<Rect> {
+    let total: f64 = values.iter().sum();
+    if total <= 0.0 {
+        return Vec::new();
+    }
+    let scale = area.width * area.height / total;
+    let areas: Vec<f64> = values.iter().map(|value| value * scale).collect();
+
+    let mut cells = Vec::with_capacity(areas.len());
+    let mut start = 0;
+    while start < areas.len() {
+        let side = area.width.min(area.height);
+        let mut end = start + 1;
+        while end < areas.len()
+            && worst(&areas[start..=end], side) <= worst(&areas[start..end], side)
+        {
+            end += 1;
+        }
+        area = layout_row(&areas[start..end], area, &mut cells);
+        start = end;
+    }
+    cells
+}
+
+/// Largest aspect ratio of a row of `areas` along a side of length `side`
+fn worst(areas: &[f64], side: f64) -> f64 {
+    let sum: f64 = areas.iter().sum();
+    let side = side * side;
+    areas
+        .iter()
+        .map(|&area| (side * area / (sum * sum)).max(sum * sum / (side * area)))
+        .fold(0.0, f64::max)
+}
+
+/// Place a row along the shorter side of `area`; returns what is left
+fn layout_row(areas: &[f64], area: Rect, cells: &mut Vec<Rect>) -> Rect {
+    let sum: f64 = areas.iter().sum();
+    if area.width >= area.height {
+        // Column on the left
+        let width = sum / area.height;
+        let mut y = area.y;
+        for &cell in areas {
+            let height = cell / width;
+            cells.push(Rect {
+                x: area.x,
+                y,
+                width,
+                height,
+            });
+            y += height;
+        }
+        Rect {
+            x: area.x + width,
+            width: area.width - width,
+            ..area
+        }
+    } else {
+        // Row at the top
+        let height = sum / area.width;
+        let mut x = area.x;
+        for &cell in areas {
+            let width = cell / height;
+            cells.push(Rect {
+                x,
+                y: area.y,
+                width,
+                height,
+            });
+            x += width;
+        }
+        Rect {
+            y: area.y + height,
+            height: area.height - height,
+            ..area
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_squarify_fills_area() {
+        let area = Rect {
+            x: 0.0,
+            y: 0.0,
+            width: 600.0,
+            height: 400.0,
+        };
+        let values = [6.0, 6.0, 4.0, 3.0, 2.0, 2.0, 1.0];
+        let cells = squarify(&values, area);
+        assert_eq!(cells.len(), values.len());
+
+        let total: f64 = values.iter().sum();
+        for (cell, value) in cells.iter().zip(values) {
+            let expected = value / total * area.width * area.height;
+            assert!((cell.width * cell.height - expected).abs() < 1e-6);
+            assert!(cell.x >= -1e-9 && cell.x + cell.width <= area.width + 1e-6);
+            assert!(cell.y >= -1e-9 && cell.y + cell.height <= area.height + 1e-6);
+        }
+        assert!(squarify(&[], area).is_empty());
+    }
+
+    #[test]
+    fn test_charts_escape_labels() {
+        let bars = [("<script>".to_string(), 3), ("p".to_string(), 1)];
+        let svg = bar_chart("Tags & counts", &bars).svg;
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        assert!(svg.ends_with("</svg>"));
+        assert!(svg.contains("Tags &amp; counts"));
+        assert!(svg.contains("&lt;script&gt;: 3"));
+        assert!(!svg.contains("<script>"));
+
+        let svg = histogram("Depth", "Depth", &[1, 5, 2]).svg;
+        assert_eq!(svg.matches("<rect").count(), 3);
+
+        let items = [
+            TreemapItem {
+                label: "a@href".to_string(),
+                group: "a".to_string(),
+                value: 10,
+            },
+            TreemapItem {
+                label: "a@id".to_string(),
+                group: "a".to_string(),
+                value: 0,
+            },
+        ];
+        let svg = treemap("Attributes", &items).svg;
+        assert_eq!(svg.matches("<rect").count(), 1);
+        assert!(svg.contains("a@href: 10"));
+    }
+
+    #[test]
+    fn test_stack() {
+        let charts = [
+            bar_chart("Tags", &[("p".to_string(), 2)]),
+            histogram("Depth", "Depth", &[1]),
+        ];
+        let height = charts[0].height + 24.0 + charts[1].height;
+        let svg = stack(&charts);
+
+        assert!(svg.starts_with(&format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{CHART_WIDTH}\" height=\"{height}\""
+        )));
+        assert!(svg.contains("<svg y=\"0\" xmlns="));
+        assert!(svg.contains(&format!("<svg y=\"{}\" xmlns=", charts[0].height + 24.0)));
+        assert_eq!(svg.matches("<svg").count(), 3);
+        assert_eq!(svg.matches("</svg>").count(), 3);
+    }
+}
@@ -0,0 +1,110 @@
+use anyhow::Result;
+use flate2::write::GzEncoder;
+use std::io::Write;
+use std::path::Path;
+
+/// Compression applied to exported output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    /// gzip at level 0 (none) to 9 (smallest); 6 is a good default
+    Gzip(u32),
+    /// Zstandard at level 1 to 22; 3 is a good default. Compresses better
+    /// and faster than gzip. Requires the `zstd` feature.
+    #[cfg(feature = "zstd")]
+    Zstd(i32),
+}
+
+impl Compression {
+    /// Extension appended to compressed files, without the dot
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Compression::Gzip(_) => "gz",
+            #[cfg(feature = "zstd")]
+            Compression::Zstd(_) => "zst",
+        }
+    }
+
+    /// Compression implied by the extension of `path` (`.gz` or `.zst`),
+    /// at the default level
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "gz" => Some(Compression::Gzip(6)),
+            #[cfg(feature = "zstd")]
+            "zst" => Some(Compression::Zstd(3)),
+            _ => None,
+        }
+    }
+}
+
+/// Settings for `Exporter::export_with` and `Exporter::export_to_writer_with`
+///
+/// # Example
+/// ```no_run
+/// # use ferret::analyzer::stream::StreamAnalyzer;
+/// use ferret::exporter::{Compression, ExportOptions, Exporter, JsonExporter};
+/// use std::path::Path;
+///
+/// let result = StreamAnalyzer::new(10).analyze_file(Path::new("dump.xml"))?;
+/// let options = ExportOptions {
+///     compression: Some(Compression::Gzip(9)),
+/// };
+/// JsonExporter.export_with(&result, Path::new("dump.json.gz"), &options)?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExportOptions {
+    pub compression: Option<Compression>,
+}
+
+impl ExportOptions {
+    /// Options for writing to `path`: compressed if its extension says so
+    pub fn for_path(path: &Path) -> Self {
+        Self {
+            compression: Compression::from_path(path),
+        }
+    }
+}
+
+/// Run `write` on `writer`, compressed with `compression`
+pub(crate) fn write_compressed(
+    compression: Option<Compression>,
+    writer: &mut dyn Write,
+    write: impl FnOnce(&mut dyn Write) -> Result<()>,
+) -> Result<()> {
+    match compression {
+        None => write(writer),
+        Some(Compression::Gzip(level)) => {
+            let mut encoder = GzEncoder::new(writer, flate2::Compression::new(level));
+            write(&mut encoder)?;
+            encoder.finish()?;
+            Ok(())
+        }
+        #[cfg(feature = "zstd")]
+        Some(Compression::Zstd(level)) => {
+            let mut encoder = zstd::Encoder::new(writer, level)?;
+            write(&mut encoder)?;
+            encoder.finish()?;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_path() {
+        assert_eq!(
+            Compression::from_path(Path::new("out/result.json.gz")),
+            Some(Compression::Gzip(6))
+        );
+        assert_eq!(Compression::from_path(Path::new("result.json")), None);
+        assert_eq!(Compression::from_path(Path::new("gz")), None);
+        #[cfg(feature = "zstd")]
+        assert_eq!(
+            Compression::from_path(Path::new("result.csv.zst")),
+            Some(Compression::Zstd(3))
+        );
+    }
+}
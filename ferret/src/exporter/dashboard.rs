@@ -0,0 +1,134 @@
+use crate::analyzer::AnalysisResult;
+use crate::exporter::chart::{self, TreemapItem};
+use crate::exporter::{write_tag_tree, Exporter};
+use anyhow::Result;
+use askama::Template;
+use std::io::Write;
+
+/// Attributes shown in the treemap; smaller ones would be unreadable
+const MAX_TREEMAP_CELLS: usize = 60;
+
+#[derive(Template)]
+#[template(path = "dashboard.html")]
+struct DashboardTemplate<'a> {
+    title: &'a str,
+    files_analyzed: usize,
+    elements: usize,
+    tags: usize,
+    attributes: usize,
+    max_depth: usize,
+    parse_errors: usize,
+    tag_chart: String,
+    depth_chart: String,
+    attribute_treemap: String,
+    tree: String,
+}
+
+/// Self-contained HTML report for sharing
+///
+/// Shows summary figures, a bar chart of the most frequent tags, the
+/// number of elements per depth, a treemap of attribute usage and the
+/// collapsible tag tree of [`HtmlTreeExporter`](crate::exporter::HtmlTreeExporter).
+/// Charts are inline SVG, so the file has no scripts and loads nothing
+/// from the network.
+pub struct DashboardExporter {
+    /// Page heading and title
+    pub title: String,
+    /// Tags shown in the bar chart, most frequent first
+    pub top_tags: usize,
+}
+
+impl Default for DashboardExporter {
+    fn default() -> Self {
+        Self {
+            title: "Ferret analysis".to_string(),
+            top_tags: 25,
+        }
+    }
+}
+
+impl DashboardExporter {
+    pub fn with_title(mut self, title: impl Into<String>) -> Self {
+        self.title = title.into();
+        self
+    }
+
+    pub fn with_top_tags(mut self, top_tags: usize) -> Self {
+        self.top_tags = top_tags;
+        self
+    }
+}
+
+impl Exporter for DashboardExporter {
+    fn export_to_writer(&self, result: &AnalysisResult, writer: &mut dyn Write) -> Result<()> {
+        let mut tags: Vec<_> = result.tags.values().collect();
+        tags.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.name.cmp(&b.name)));
+        let bars: Vec<(String, usize)> = tags
+            .iter()
+            .take(self.top_tags)
+            .map(|tag| (tag.name.clone(), tag.count))
+            .collect();
+
+        let mut cells: Vec<TreemapItem> = tags
+            .iter()
+            .flat_map(|tag| {
+                tag.attributes.values().map(|attr| TreemapItem {
+                    label: format!("{}@{}", tag.name, attr.name),
+                    group: tag.name.clone(),
+                    value: attr.count,
+                })
+            })
+            .collect();
+        cells.sort_by(|a, b| b.value.cmp(&a.value).then_with(|| a.label.cmp(&b.label)));
+        cells.truncate(MAX_TREEMAP_CELLS);
+
+        let mut tree = Vec::new();
+        write_tag_tree(result, false, &mut tree)?;
+
+        let template = DashboardTemplate {
+            title: &self.title,
+            files_analyzed: result.files_analyzed,
+            elements: result.total_elements(),
+            tags: tags.len(),
+            attributes: result.distinct_attributes(),
+            max_depth: result.max_depth,
+            parse_errors: result.parse_errors.len(),
+            tag_chart: chart::bar_chart(&format!("Top {} tags", bars.len()), &bars).svg,
+            depth_chart: chart::histogram("Elements per depth", "Depth", &result.depth_counts).svg,
+            attribute_treemap: chart::treemap("Attribute usage", &cells).svg,
+            tree: String::from_utf8(tree)?,
+        };
+        template.write_into(writer)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::stream::StreamAnalyzer;
+
+    #[test]
+    fn test_dashboard() {
+        let result = StreamAnalyzer::new(10)
+            .analyze_string(
+                r#"<ul id="menu"><li class="a">1</li><li class="b">2</li><li>3</li></ul><p>x</p>"#,
+            )
+            .unwrap();
+        let mut html = Vec::new();
+        DashboardExporter::default()
+            .with_title("Menu <audit>")
+            .export_to_writer(&result, &mut html)
+            .unwrap();
+        let html = String::from_utf8(html).unwrap();
+
+        assert!(html.contains("<h1>Menu &lt;audit&gt;</h1>"));
+        assert_eq!(html.matches("<svg").count(), 3);
+        assert!(html.contains("<title>li: 3</title>"));
+        assert!(html.contains("<title>li@class: 2</title>"));
+        assert!(html.contains("<span class='tag'>li</span>"));
+        // Nothing is loaded from elsewhere
+        assert!(!html.contains("<script"));
+        assert!(!html.contains("src="));
+    }
+}
@@ -0,0 +1,302 @@
+use crate::diff::{AnalysisDiff, Change};
+use crate::exporter::compression::write_compressed;
+use crate::exporter::ExportOptions;
+use crate::html::escape;
+use anyhow::Result;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DiffFormat {
+    /// Standalone page with a table of changes, colored by kind
+    #[default]
+    Html,
+    /// One row per changed tag or attribute
+    Csv,
+}
+
+/// Renders an [`AnalysisDiff`]: added, removed and changed tags and
+/// attributes with their old and new counts
+///
+/// Unlike the other exporters this one takes two results, so it doesn't
+/// implement [`Exporter`](crate::exporter::Exporter); it has its own
+/// `export` and `export_to_writer`.
+///
+/// # Example
+/// ```no_run
+/// # use ferret::analyzer::stream::StreamAnalyzer;
+/// use ferret::diff::AnalysisDiff;
+/// use ferret::exporter::{DiffExporter, DiffFormat};
+/// use std::path::Path;
+///
+/// let analyzer = StreamAnalyzer::new(10);
+/// let old = analyzer.analyze_file(Path::new("before.html"))?;
+/// let new = analyzer.analyze_file(Path::new("after.html"))?;
+/// DiffExporter::new(DiffFormat::Html)
+///     .with_labels("before", "after")
+///     .export(&AnalysisDiff::new(&old, &new), Path::new("diff.html"))?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub struct DiffExporter {
+    pub format: DiffFormat,
+    /// Names of the compared results, e.g. two URLs or run dates
+    pub old_label: String,
+    pub new_label: String,
+    /// Also list tags and attributes whose counts didn't change
+    pub include_unchanged: bool,
+}
+
+impl DiffExporter {
+    pub fn new(format: DiffFormat) -> Self {
+        Self {
+            format,
+            old_label: "old".to_string(),
+            new_label: "new".to_string(),
+            include_unchanged: false,
+        }
+    }
+
+    pub fn with_labels(mut self, old: impl Into<String>, new: impl Into<String>) -> Self {
+        self.old_label = old.into();
+        self.new_label = new.into();
+        self
+    }
+
+    pub fn with_unchanged(mut self, include_unchanged: bool) -> Self {
+        self.include_unchanged = include_unchanged;
+        self
+    }
+
+    /// Write the diff to a file at `path`, compressed if its extension is
+    /// `.gz` (or `.zst`, with the `zstd` feature)
+    pub fn export(&self, diff: &AnalysisDiff, path: &Path) -> Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        write_compressed(
+            ExportOptions::for_path(path).compression,
+            &mut file,
+            |writer| self.export_to_writer(diff, writer),
+        )?;
+        file.flush()?;
+        Ok(())
+    }
+
+    pub fn export_to_writer(&self, diff: &AnalysisDiff, writer: &mut dyn Write) -> Result<()> {
+        match self.format {
+            DiffFormat::Html => self.write_html(diff, writer),
+            DiffFormat::Csv => self.write_csv(diff, writer),
+        }
+    }
+
+    /// Rows to show: tag, attribute (empty for the tag itself), change and
+    /// counts
+    fn rows<'a>(
+        &self,
+        diff: &'a AnalysisDiff,
+    ) -> impl Iterator<Item = (&'a str, &'a str, Change, usize, usize)> {
+        let include_unchanged = self.include_unchanged;
+        diff.tags.iter().flat_map(move |tag| {
+            let tag_row = (
+                tag.name.as_str(),
+                "",
+                tag.change,
+                tag.old_count,
+                tag.new_count,
+            );
+            let attributes = tag.attributes.iter().map(move |attr| {
+                (
+                    tag.name.as_str(),
+                    attr.name.as_str(),
+                    attr.change,
+                    attr.old_count,
+                    attr.new_count,
+                )
+            });
+            std::iter::once(tag_row)
+                .chain(attributes)
+                .filter(move |row| include_unchanged || row.2 != Change::Unchanged)
+        })
+    }
+
+    fn write_csv(&self, diff: &AnalysisDiff, writer: &mut dyn Write) -> Result<()> {
+        let mut wtr = csv::Writer::from_writer(writer);
+        wtr.write_record([
+            "Change",
+            "Tag",
+            "Attribute",
+            "Old Count",
+            "New Count",
+            "Delta",
+        ])?;
+        for (tag, attribute, change, old_count, new_count) in self.rows(diff) {
+            wtr.write_record([
+                change_name(change),
+                tag,
+                attribute,
+                &old_count.to_string(),
+                &new_count.to_string(),
+                &format_delta(old_count, new_count),
+            ])?;
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+
+    fn write_html(&self, diff: &AnalysisDiff, file: &mut dyn Write) -> Result<()> {
+        let old_label = escape(&self.old_label);
+        let new_label = escape(&self.new_label);
+        writeln!(file, "<!DOCTYPE html><html><head><meta charset=\"UTF-8\">")?;
+        writeln!(
+            file,
+            "<title>Diff: {} vs {}</title><style>",
+            old_label, new_label
+        )?;
+        writeln!(file, "body {{ font-family: sans-serif; }}")?;
+        writeln!(file, "table {{ border-collapse: collapse; }}")?;
+        writeln!(
+            file,
+            "th, td {{ padding: 4px 12px; border-bottom: 1px solid #ddd; text-align: left; }}"
+        )?;
+        writeln!(file, "td.num {{ text-align: right; }}")?;
+        writeln!(
+            file,
+            ".attr td:nth-child(2) {{ padding-left: 28px; color: #e67e22; }}"
+        )?;
+        writeln!(file, ".added {{ background: #e6ffec; }}")?;
+        writeln!(file, ".removed {{ background: #ffebe9; }}")?;
+        writeln!(file, ".changed {{ background: #fff8c5; }}")?;
+        writeln!(file, ".up {{ color: #1a7f37; font-weight: bold; }}")?;
+        writeln!(file, ".down {{ color: #cf222e; font-weight: bold; }}")?;
+        writeln!(file, "</style></head><body>")?;
+        writeln!(file, "<h1>{} &rarr; {}</h1>", old_label, new_label)?;
+
+        let count = |change| diff.tags.iter().filter(|tag| tag.change == change).count();
+        writeln!(
+            file,
+            "<p>Tags: {} added, {} removed, {} changed. Max depth: {} &rarr; {}.</p>",
+            count(Change::Added),
+            count(Change::Removed),
+            count(Change::Changed),
+            diff.old_max_depth,
+            diff.new_max_depth
+        )?;
+
+        if diff.is_empty() {
+            writeln!(file, "<p>No structural changes.</p></body></html>")?;
+            return Ok(());
+        }
+
+        writeln!(
+            file,
+            "<table><thead><tr><th>Change</th><th>Tag / attribute</th>"
+        )?;
+        writeln!(
+            file,
+            "<th>{}</th><th>{}</th><th>Delta</th></tr></thead><tbody>",
+            old_label, new_label
+        )?;
+        for (tag, attribute, change, old_count, new_count) in self.rows(diff) {
+            let (row_class, name) = if attribute.is_empty() {
+                ("tag", format!("&lt;{}&gt;", escape(tag)))
+            } else {
+                ("attr", format!("@{}", escape(attribute)))
+            };
+            let delta_class = match new_count.cmp(&old_count) {
+                std::cmp::Ordering::Greater => "up",
+                std::cmp::Ordering::Less => "down",
+                std::cmp::Ordering::Equal => "",
+            };
+            writeln!(
+                file,
+                "<tr class='{} {}'><td>{}</td><td>{}</td><td class='num'>{}</td><td class='num'>{}</td><td class='num {}'>{}</td></tr>",
+                row_class,
+                change_name(change),
+                change_name(change),
+                name,
+                old_count,
+                new_count,
+                delta_class,
+                format_delta(old_count, new_count)
+            )?;
+        }
+        writeln!(file, "</tbody></table></body></html>")?;
+        Ok(())
+    }
+}
+
+fn change_name(change: Change) -> &'static str {
+    match change {
+        Change::Added => "added",
+        Change::Removed => "removed",
+        Change::Changed => "changed",
+        Change::Unchanged => "unchanged",
+    }
+}
+
+/// Signed difference, e.g. `+3`, `-1` or `0`
+fn format_delta(old: usize, new: usize) -> String {
+    let delta = new as i64 - old as i64;
+    if delta > 0 {
+        format!("+{}", delta)
+    } else {
+        delta.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::stream::StreamAnalyzer;
+
+    fn diff() -> AnalysisDiff {
+        let analyzer = StreamAnalyzer::new(10);
+        let old = analyzer
+            .analyze_string(r#"<div id="a"><p>1</p><span>x</span></div>"#)
+            .unwrap();
+        let new = analyzer
+            .analyze_string(r#"<div id="a"><p>1</p><p>2</p><em>y</em></div>"#)
+            .unwrap();
+        AnalysisDiff::new(&old, &new)
+    }
+
+    fn export(exporter: &DiffExporter, diff: &AnalysisDiff) -> String {
+        let mut out = Vec::new();
+        exporter.export_to_writer(diff, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_csv() {
+        let csv = export(&DiffExporter::new(DiffFormat::Csv), &diff());
+        assert_eq!(
+            csv,
+            "Change,Tag,Attribute,Old Count,New Count,Delta\n\
+             added,em,,0,1,+1\n\
+             changed,p,,1,2,+1\n\
+             removed,span,,1,0,-1\n"
+        );
+
+        let csv = export(
+            &DiffExporter::new(DiffFormat::Csv).with_unchanged(true),
+            &diff(),
+        );
+        assert!(csv.contains("unchanged,div,,1,1,0\nunchanged,div,id,1,1,0\n"));
+    }
+
+    #[test]
+    fn test_html() {
+        let html = export(
+            &DiffExporter::new(DiffFormat::Html).with_labels("<v1>", "v2"),
+            &diff(),
+        );
+        assert!(html.contains("<h1>&lt;v1&gt; &rarr; v2</h1>"));
+        assert!(html.contains("<p>Tags: 1 added, 1 removed, 1 changed."));
+        assert!(html.contains("<tr class='tag removed'><td>removed</td><td>&lt;span&gt;</td>"));
+        assert!(html.contains("<td class='num up'>+1</td>"));
+        assert!(!html.contains("&lt;div&gt;"));
+
+        let unchanged = AnalysisDiff::new(&Default::default(), &Default::default());
+        let html = export(&DiffExporter::new(DiffFormat::Html), &unchanged);
+        assert!(html.contains("No structural changes."));
+    }
+}
@@ -0,0 +1,183 @@
+use crate::analyzer::AnalysisResult;
+use crate::exporter::chart::shorten;
+use crate::exporter::Exporter;
+use anyhow::Result;
+use std::io::Write;
+
+/// Values longer than this are shortened in node labels
+const MAX_LABEL_CHARS: usize = 40;
+
+/// Graphviz graph of tags, their attributes and the most frequent values
+///
+/// Tags are boxes linked to their attributes, which are linked to their
+/// top values; labels carry the counts. When the result has parent/child
+/// counts (see `StreamAnalyzer::with_structure`), bold edges connect
+/// parent tags to their children. Render it with `dot -Tsvg graph.dot`.
+///
+/// # Example
+/// ```
+/// # use ferret::analyzer::stream::StreamAnalyzer;
+/// use ferret::exporter::{DotExporter, Exporter};
+///
+/// let analyzer = StreamAnalyzer::new(10).with_structure(true);
+/// let result = analyzer.analyze_string(r#"<ul><li class="a">x</li></ul>"#)?;
+/// let mut dot = Vec::new();
+/// DotExporter::default().export_to_writer(&result, &mut dot)?;
+/// assert!(String::from_utf8(dot)?.contains(r#""ul" -> "li""#));
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub struct DotExporter {
+    /// Values shown per attribute, most frequent first
+    pub max_values: usize,
+}
+
+impl Default for DotExporter {
+    fn default() -> Self {
+        Self { max_values: 5 }
+    }
+}
+
+impl DotExporter {
+    pub fn with_max_values(mut self, max_values: usize) -> Self {
+        self.max_values = max_values;
+        self
+    }
+}
+
+impl Exporter for DotExporter {
+    fn export_to_writer(&self, result: &AnalysisResult, writer: &mut dyn Write) -> Result<()> {
+        writeln!(writer, "digraph ferret {{")?;
+        writeln!(writer, "    rankdir=LR;")?;
+        writeln!(writer, "    node [fontname=\"Helvetica\"];")?;
+        writeln!(writer, "    edge [color=\"gray40\"];")?;
+
+        let mut tags: Vec<_> = result.tags.values().collect();
+        tags.sort_by(|a, b| a.name.cmp(&b.name));
+
+        for tag in &tags {
+            // Tag and attribute names can't contain `@` or `=`, so node
+            // ids don't collide
+            writeln!(
+                writer,
+                "    {} [shape=box, style=filled, fillcolor=\"lightblue\", label={}];",
+                quote(&tag.name),
+                quote(&format!("<{}>\n{}", tag.name, tag.count))
+            )?;
+
+            let mut attributes: Vec<_> = tag.attributes.values().collect();
+            attributes.sort_by(|a, b| a.name.cmp(&b.name));
+            for attr in attributes {
+                let attr_id = format!("{}@{}", tag.name, attr.name);
+                writeln!(
+                    writer,
+                    "    {} [shape=ellipse, label={}];",
+                    quote(&attr_id),
+                    quote(&format!("{}\n{}", attr.name, attr.count))
+                )?;
+                writeln!(writer, "    {} -> {};", quote(&tag.name), quote(&attr_id))?;
+
+                let mut values: Vec<_> = attr.value_counts.iter().collect();
+                values.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+                for (value, count) in values.into_iter().take(self.max_values) {
+                    let value_id = format!("{}={}", attr_id, value);
+                    writeln!(
+                        writer,
+                        "    {} [shape=note, fontsize=10, label={}];",
+                        quote(&value_id),
+                        quote(&format!("\"{}\"", shorten(value, MAX_LABEL_CHARS)))
+                    )?;
+                    writeln!(
+                        writer,
+                        "    {} -> {} [label=\"{}\"];",
+                        quote(&attr_id),
+                        quote(&value_id),
+                        count
+                    )?;
+                }
+            }
+        }
+
+        let mut edges: Vec<_> = result
+            .children
+            .iter()
+            .flat_map(|(parent, children)| {
+                children
+                    .iter()
+                    .map(move |(child, count)| (parent, child, count))
+            })
+            .collect();
+        edges.sort();
+        for (parent, child, count) in edges {
+            writeln!(
+                writer,
+                "    {} -> {} [style=bold, color=\"black\", label=\"{}\"];",
+                quote(parent),
+                quote(child),
+                count
+            )?;
+        }
+
+        writeln!(writer, "}}")?;
+        Ok(())
+    }
+}
+
+/// DOT string literal for `text`
+pub(super) fn quote(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => {}
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::stream::StreamAnalyzer;
+
+    fn export(exporter: &DotExporter, result: &AnalysisResult) -> String {
+        let mut dot = Vec::new();
+        exporter.export_to_writer(result, &mut dot).unwrap();
+        String::from_utf8(dot).unwrap()
+    }
+
+    #[test]
+    fn test_graph() {
+        let html = r#"<div id="main"><a href="/x">1</a><a href="/y">2</a><a href="/x">3</a></div>"#;
+        let result = StreamAnalyzer::new(10).analyze_string(html).unwrap();
+        let dot = export(&DotExporter::default().with_max_values(1), &result);
+
+        assert!(dot.starts_with("digraph ferret {\n"));
+        assert!(dot.ends_with("}\n"));
+        assert!(dot
+            .contains(r#""a" [shape=box, style=filled, fillcolor="lightblue", label="<a>\n3"];"#));
+        assert!(dot.contains(r#""a" -> "a@href";"#));
+        assert!(dot.contains(r#""a@href" -> "a@href=/x" [label="2"];"#));
+        // Only the most frequent value
+        assert!(!dot.contains("a@href=/y"));
+        // No structure tracked
+        assert!(!dot.contains("style=bold"));
+
+        let result = StreamAnalyzer::new(10)
+            .with_structure(true)
+            .analyze_string(html)
+            .unwrap();
+        let dot = export(&DotExporter::default(), &result);
+        assert!(dot.contains(r#""div" -> "a" [style=bold, color="black", label="3"];"#));
+    }
+
+    #[test]
+    fn test_quoting() {
+        assert_eq!(quote(r#"say "hi"\n"#), r#""say \"hi\"\\n""#);
+        assert_eq!(quote("a\r\nb"), r#""a\nb""#);
+    }
+}
@@ -0,0 +1,209 @@
+use crate::analyzer::{AnalysisResult, AnalysisResultSet, TagStats};
+use crate::cache::cache_key;
+use crate::exporter::Exporter;
+use anyhow::Result;
+use serde_json::json;
+use std::io::Write;
+
+/// How the `_id` of each tag document is chosen
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum DocId {
+    /// Let Elasticsearch generate ids; every import adds new documents
+    #[default]
+    Auto,
+    /// `<hash of the source>-<tag>`, so re-importing a source replaces its
+    /// documents
+    SourceTag,
+    /// Custom id with `{source}`, `{source_hash}` and `{tag}` placeholders,
+    /// e.g. `2024-06-01:{source_hash}:{tag}`
+    Template(String),
+}
+
+/// Newline-delimited actions for the Elasticsearch (or OpenSearch) `_bulk`
+/// API, one document per tag
+///
+/// ```text
+/// {"index":{"_index":"ferret"}}
+/// {"source":"https://example.com/","tag":"a","count":12,"attributes":[{"name":"href","count":12,"values":[{"value":"/","count":3}]}]}
+/// ```
+///
+/// Attributes and values are arrays of objects rather than maps keyed by
+/// name, so arbitrary markup doesn't add fields to the index mapping. Map
+/// `attributes` as `nested` to query attributes and counts together.
+///
+/// # Example
+/// ```no_run
+/// # use ferret::analyzer::stream::StreamAnalyzer;
+/// use ferret::exporter::{DocId, ElasticsearchBulkExporter, Exporter};
+/// use std::path::Path;
+///
+/// let result = StreamAnalyzer::new(10).analyze_file(Path::new("page.html"))?;
+/// ElasticsearchBulkExporter::new("pages")
+///     .source("page.html")
+///     .doc_id(DocId::SourceTag)
+///     .export(&result, Path::new("bulk.ndjson"))?;
+/// // curl -H 'Content-Type: application/x-ndjson' --data-binary @bulk.ndjson localhost:9200/_bulk
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub struct ElasticsearchBulkExporter {
+    pub index: String,
+    pub doc_id: DocId,
+    /// `source` field for single results; batches use each entry's source
+    pub source: Option<String>,
+}
+
+impl ElasticsearchBulkExporter {
+    pub fn new(index: impl Into<String>) -> Self {
+        Self {
+            index: index.into(),
+            doc_id: DocId::Auto,
+            source: None,
+        }
+    }
+
+    pub fn doc_id(mut self, doc_id: DocId) -> Self {
+        self.doc_id = doc_id;
+        self
+    }
+
+    /// Record `source` (a path or URL) for results exported with `export`
+    pub fn source(mut self, source: impl Into<String>) -> Self {
+        self.source = Some(source.into());
+        self
+    }
+
+    /// Actions for every successful entry of `set`
+    pub fn export_set_to_writer(
+        &self,
+        set: &AnalysisResultSet,
+        writer: &mut dyn Write,
+    ) -> Result<()> {
+        for entry in &set.entries {
+            if let Some(result) = &entry.result {
+                self.write_actions(&entry.source, result, writer)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_actions(
+        &self,
+        source: &str,
+        result: &AnalysisResult,
+        writer: &mut dyn Write,
+    ) -> Result<()> {
+        let mut tags: Vec<_> = result.tags.values().collect();
+        tags.sort_by(|a, b| a.name.cmp(&b.name));
+        for tag in tags {
+            let mut action = json!({ "_index": self.index });
+            if let Some(id) = self.id(source, &tag.name) {
+                action["_id"] = id.into();
+            }
+            serde_json::to_writer(&mut *writer, &json!({ "index": action }))?;
+            writeln!(writer)?;
+            serde_json::to_writer(&mut *writer, &document(source, tag))?;
+            writeln!(writer)?;
+        }
+        Ok(())
+    }
+
+    fn id(&self, source: &str, tag: &str) -> Option<String> {
+        match &self.doc_id {
+            DocId::Auto => None,
+            DocId::SourceTag => Some(format!("{}-{}", cache_key(source), tag)),
+            DocId::Template(template) => Some(
+                template
+                    .replace("{source_hash}", &cache_key(source))
+                    .replace("{source}", source)
+                    .replace("{tag}", tag),
+            ),
+        }
+    }
+}
+
+impl Exporter for ElasticsearchBulkExporter {
+    fn export_to_writer(&self, result: &AnalysisResult, writer: &mut dyn Write) -> Result<()> {
+        self.write_actions(self.source.as_deref().unwrap_or_default(), result, writer)
+    }
+}
+
+pub(super) fn document(source: &str, tag: &TagStats) -> serde_json::Value {
+    let mut attributes: Vec<_> = tag.attributes.values().collect();
+    attributes.sort_by(|a, b| a.name.cmp(&b.name));
+    let attributes: Vec<_> = attributes
+        .into_iter()
+        .map(|attr| {
+            let mut values: Vec<_> = attr.value_counts.iter().collect();
+            values.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+            let values: Vec<_> = values
+                .into_iter()
+                .map(|(value, count)| json!({ "value": value, "count": count }))
+                .collect();
+            json!({ "name": attr.name, "count": attr.count, "values": values })
+        })
+        .collect();
+    json!({
+        "source": source,
+        "tag": tag.name,
+        "count": tag.count,
+        "attributes": attributes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::stream::StreamAnalyzer;
+    use serde_json::Value;
+
+    fn export(exporter: &ElasticsearchBulkExporter) -> Vec<Value> {
+        let result = StreamAnalyzer::new(10)
+            .analyze_string(r#"<div><a href="/x">1</a><a href="/x">2</a><a href="/y">3</a></div>"#)
+            .unwrap();
+        let mut ndjson = Vec::new();
+        exporter.export_to_writer(&result, &mut ndjson).unwrap();
+        let ndjson = String::from_utf8(ndjson).unwrap();
+        assert!(ndjson.ends_with('\n'));
+        ndjson
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_bulk_actions() {
+        let lines = export(&ElasticsearchBulkExporter::new("pages").source("index.html"));
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], json!({ "index": { "_index": "pages" } }));
+        assert_eq!(
+            lines[1],
+            json!({
+                "source": "index.html",
+                "tag": "a",
+                "count": 3,
+                "attributes": [{
+                    "name": "href",
+                    "count": 3,
+                    "values": [{ "value": "/x", "count": 2 }, { "value": "/y", "count": 1 }],
+                }],
+            })
+        );
+        assert_eq!(lines[3]["tag"], "div");
+        assert_eq!(lines[3]["attributes"], json!([]));
+    }
+
+    #[test]
+    fn test_doc_ids() {
+        let exporter = ElasticsearchBulkExporter::new("pages")
+            .source("index.html")
+            .doc_id(DocId::SourceTag);
+        let lines = export(&exporter);
+        let id = format!("{}-a", cache_key("index.html"));
+        assert_eq!(lines[0]["index"]["_id"], id.as_str());
+        // Stable across runs
+        assert_eq!(export(&exporter)[0], lines[0]);
+
+        let exporter = exporter.doc_id(DocId::Template("run1:{source}:{tag}".to_string()));
+        assert_eq!(export(&exporter)[2]["index"]["_id"], "run1:index.html:div");
+    }
+}
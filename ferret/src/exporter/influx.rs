@@ -0,0 +1,189 @@
+use crate::analyzer::{AnalysisResult, AnalysisResultSet};
+use crate::exporter::Exporter;
+use anyhow::Result;
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Results as InfluxDB line protocol, e.g. for Telegraf's `file` or
+/// `http_listener_v2` inputs or the `/api/v2/write` endpoint
+///
+/// Three measurements are written, with names as tags and counts as
+/// integer fields:
+///
+/// ```text
+/// ferret_tag,source=https://example.com/,tag=div count=123i 1718000000000000000
+/// ferret_attribute,source=https://example.com/,tag=div,attr=class count=80i,distinct_values=10i 1718000000000000000
+/// ferret_document,source=https://example.com/ files=1i,max_depth=12i,parse_errors=0i 1718000000000000000
+/// ```
+///
+/// Timestamps are in nanoseconds, the default write precision.
+pub struct InfluxExporter {
+    /// `source` tag for single results; batches use each entry's source
+    pub source: Option<String>,
+    /// Time of the analysis; the time of export when unset
+    pub timestamp: Option<SystemTime>,
+}
+
+impl InfluxExporter {
+    pub fn new() -> Self {
+        Self {
+            source: None,
+            timestamp: None,
+        }
+    }
+
+    /// Tag the points of single results with `source` (a path or URL)
+    pub fn source(mut self, source: impl Into<String>) -> Self {
+        self.source = Some(source.into());
+        self
+    }
+
+    /// Timestamp the points with `time` instead of the time of export
+    pub fn timestamp(mut self, time: SystemTime) -> Self {
+        self.timestamp = Some(time);
+        self
+    }
+
+    /// Points of every successful entry of `set`, tagged by source
+    pub fn export_set_to_writer(
+        &self,
+        set: &AnalysisResultSet,
+        writer: &mut dyn Write,
+    ) -> Result<()> {
+        let timestamp = self.nanos()?;
+        for entry in &set.entries {
+            if let Some(result) = &entry.result {
+                write_points(Some(&entry.source), result, timestamp, writer)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn nanos(&self) -> Result<u128> {
+        let time = self.timestamp.unwrap_or_else(SystemTime::now);
+        Ok(time.duration_since(UNIX_EPOCH)?.as_nanos())
+    }
+}
+
+impl Default for InfluxExporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Exporter for InfluxExporter {
+    fn export_to_writer(&self, result: &AnalysisResult, writer: &mut dyn Write) -> Result<()> {
+        write_points(self.source.as_deref(), result, self.nanos()?, writer)
+    }
+}
+
+fn write_points(
+    source: Option<&str>,
+    result: &AnalysisResult,
+    timestamp: u128,
+    writer: &mut dyn Write,
+) -> Result<()> {
+    // Empty tag values are invalid in line protocol, so leave the tag out
+    let source = match source {
+        Some(source) if !source.is_empty() => format!(",source={}", escape_tag(source)),
+        _ => String::new(),
+    };
+
+    let mut tags: Vec<_> = result.tags.values().collect();
+    tags.sort_by(|a, b| a.name.cmp(&b.name));
+    for tag in tags {
+        let tag_name = escape_tag(&tag.name);
+        writeln!(
+            writer,
+            "ferret_tag{},tag={} count={}i {}",
+            source, tag_name, tag.count, timestamp
+        )?;
+
+        let mut attributes: Vec<_> = tag.attributes.values().collect();
+        attributes.sort_by(|a, b| a.name.cmp(&b.name));
+        for attr in attributes {
+            writeln!(
+                writer,
+                "ferret_attribute{},tag={},attr={} count={}i,distinct_values={}i {}",
+                source,
+                tag_name,
+                escape_tag(&attr.name),
+                attr.count,
+                attr.value_counts.len(),
+                timestamp
+            )?;
+        }
+    }
+
+    writeln!(
+        writer,
+        "ferret_document{} files={}i,max_depth={}i,parse_errors={}i {}",
+        source,
+        result.files_analyzed,
+        result.max_depth,
+        result.parse_errors.len(),
+        timestamp
+    )?;
+    Ok(())
+}
+
+/// Escape a tag value: commas, equals signs and spaces; line breaks, which
+/// can't be escaped, become spaces
+fn escape_tag(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            ',' | '=' | ' ' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' | '\r' => escaped.push_str("\\ "),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::stream::StreamAnalyzer;
+    use std::time::Duration;
+
+    #[test]
+    fn test_line_protocol() {
+        let result = StreamAnalyzer::new(10)
+            .analyze_string(r#"<div class="a"><p>1</p><p class="b">2</p></div>"#)
+            .unwrap();
+        let time = UNIX_EPOCH + Duration::from_secs(1_718_000_000);
+        let mut lines = Vec::new();
+        InfluxExporter::new()
+            .source("my page,v=2")
+            .timestamp(time)
+            .export_to_writer(&result, &mut lines)
+            .unwrap();
+        let lines = String::from_utf8(lines).unwrap();
+
+        assert_eq!(
+            lines.lines().collect::<Vec<_>>(),
+            [
+                r"ferret_tag,source=my\ page\,v\=2,tag=div count=1i 1718000000000000000",
+                r"ferret_attribute,source=my\ page\,v\=2,tag=div,attr=class count=1i,distinct_values=1i 1718000000000000000",
+                r"ferret_tag,source=my\ page\,v\=2,tag=p count=2i 1718000000000000000",
+                r"ferret_attribute,source=my\ page\,v\=2,tag=p,attr=class count=1i,distinct_values=1i 1718000000000000000",
+                r"ferret_document,source=my\ page\,v\=2 files=1i,max_depth=2i,parse_errors=0i 1718000000000000000",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_without_source() {
+        let result = StreamAnalyzer::new(10).analyze_string("<br/>").unwrap();
+        let mut lines = Vec::new();
+        InfluxExporter::new()
+            .export_to_writer(&result, &mut lines)
+            .unwrap();
+        let lines = String::from_utf8(lines).unwrap();
+        assert!(lines.starts_with("ferret_tag,tag=br count=1i "));
+    }
+}
@@ -0,0 +1,266 @@
+use crate::crawler::LinkGraph;
+use crate::exporter::compression::write_compressed;
+use crate::exporter::dot::quote;
+use crate::exporter::ExportOptions;
+use crate::html::escape;
+use anyhow::Result;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GraphFormat {
+    /// Graphviz; render with `dot -Tsvg links.dot` or `sfdp` for large sites
+    #[default]
+    Dot,
+    /// GEXF 1.3, e.g. for Gephi
+    Gexf,
+    /// The serialized [`LinkGraph`]: nodes and edges with node indices
+    Json,
+}
+
+impl GraphFormat {
+    /// Format implied by the extension of `path` (`.dot`, `.gv`, `.gexf` or
+    /// `.json`), looking past a `.gz` or `.zst` compression suffix
+    pub fn for_path(path: &Path) -> Option<Self> {
+        let path = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("gz" | "zst") => path.file_stem().map(Path::new)?,
+            _ => path,
+        };
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "dot" | "gv" => Some(GraphFormat::Dot),
+            "gexf" => Some(GraphFormat::Gexf),
+            "json" => Some(GraphFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+/// Writes the [`LinkGraph`] of a crawl: one node per page, one edge per
+/// link, labelled with its anchor text
+///
+/// Pages that weren't crawled are drawn dashed in DOT and have no depth in
+/// GEXF. Like [`DiffExporter`](crate::exporter::DiffExporter) it doesn't
+/// implement [`Exporter`](crate::exporter::Exporter), which takes an
+/// analysis result.
+///
+/// # Example
+/// ```no_run
+/// # async fn run() -> anyhow::Result<()> {
+/// use ferret::analyzer::stream::StreamAnalyzer;
+/// use ferret::crawler::Crawler;
+/// use ferret::exporter::{GraphFormat, LinkGraphExporter};
+/// use std::path::Path;
+///
+/// let crawl = Crawler::new(StreamAnalyzer::new(10))
+///     .crawl(["https://example.com/"])
+///     .await?;
+/// LinkGraphExporter::new(GraphFormat::Gexf)
+///     .export(&crawl.link_graph().crawled_only(), Path::new("links.gexf"))?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct LinkGraphExporter {
+    pub format: GraphFormat,
+}
+
+impl LinkGraphExporter {
+    pub fn new(format: GraphFormat) -> Self {
+        Self { format }
+    }
+
+    /// Write the graph to a file at `path`, compressed if its extension is
+    /// `.gz` (or `.zst`, with the `zstd` feature)
+    pub fn export(&self, graph: &LinkGraph, path: &Path) -> Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        write_compressed(
+            ExportOptions::for_path(path).compression,
+            &mut file,
+            |writer| self.export_to_writer(graph, writer),
+        )?;
+        file.flush()?;
+        Ok(())
+    }
+
+    pub fn export_to_writer(&self, graph: &LinkGraph, writer: &mut dyn Write) -> Result<()> {
+        match self.format {
+            GraphFormat::Dot => write_dot(graph, writer),
+            GraphFormat::Gexf => write_gexf(graph, writer),
+            GraphFormat::Json => {
+                serde_json::to_writer_pretty(&mut *writer, graph)?;
+                writeln!(writer)?;
+                Ok(())
+            }
+        }
+    }
+}
+
+fn write_dot(graph: &LinkGraph, writer: &mut dyn Write) -> Result<()> {
+    writeln!(writer, "digraph links {{")?;
+    writeln!(writer, "    node [shape=box, fontname=\"Helvetica\"];")?;
+    writeln!(writer, "    edge [color=\"gray40\", fontsize=10];")?;
+    for (id, node) in graph.nodes.iter().enumerate() {
+        let style = match (node.crawled(), &node.error) {
+            (false, _) => ", style=dashed, fontcolor=\"gray40\"",
+            (true, Some(_)) => ", color=\"red\"",
+            (true, None) => "",
+        };
+        writeln!(writer, "    n{} [label={}{}];", id, quote(&node.url), style)?;
+    }
+    for edge in &graph.edges {
+        writeln!(
+            writer,
+            "    n{} -> n{} [label={}];",
+            edge.source,
+            edge.target,
+            quote(&edge.text)
+        )?;
+    }
+    writeln!(writer, "}}")?;
+    Ok(())
+}
+
+fn write_gexf(graph: &LinkGraph, writer: &mut dyn Write) -> Result<()> {
+    writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        writer,
+        r#"<gexf xmlns="http://gexf.net/1.3" version="1.3">"#
+    )?;
+    writeln!(writer, r#"  <graph defaultedgetype="directed">"#)?;
+    writeln!(writer, r#"    <attributes class="node">"#)?;
+    writeln!(
+        writer,
+        r#"      <attribute id="depth" title="depth" type="integer"/>"#
+    )?;
+    writeln!(
+        writer,
+        r#"      <attribute id="crawled" title="crawled" type="boolean"/>"#
+    )?;
+    writeln!(
+        writer,
+        r#"      <attribute id="error" title="error" type="string"/>"#
+    )?;
+    writeln!(writer, "    </attributes>")?;
+    writeln!(writer, "    <nodes>")?;
+    for (id, node) in graph.nodes.iter().enumerate() {
+        writeln!(
+            writer,
+            r#"      <node id="{}" label="{}"><attvalues>"#,
+            id,
+            escape(&node.url)
+        )?;
+        if let Some(depth) = node.depth {
+            writeln!(
+                writer,
+                r#"        <attvalue for="depth" value="{}"/>"#,
+                depth
+            )?;
+        }
+        writeln!(
+            writer,
+            r#"        <attvalue for="crawled" value="{}"/>"#,
+            node.crawled()
+        )?;
+        if let Some(error) = &node.error {
+            writeln!(
+                writer,
+                r#"        <attvalue for="error" value="{}"/>"#,
+                escape(error)
+            )?;
+        }
+        writeln!(writer, "      </attvalues></node>")?;
+    }
+    writeln!(writer, "    </nodes>")?;
+    writeln!(writer, "    <edges>")?;
+    for (id, edge) in graph.edges.iter().enumerate() {
+        writeln!(
+            writer,
+            r#"      <edge id="{}" source="{}" target="{}" label="{}"/>"#,
+            id,
+            edge.source,
+            edge.target,
+            escape(&edge.text)
+        )?;
+    }
+    writeln!(writer, "    </edges>")?;
+    writeln!(writer, "  </graph>")?;
+    writeln!(writer, "</gexf>")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crawler::{GraphEdge, GraphNode};
+
+    fn graph() -> LinkGraph {
+        let node = |url: &str, depth| GraphNode {
+            url: url.to_string(),
+            depth,
+            error: None,
+        };
+        LinkGraph {
+            nodes: vec![
+                node("https://example.com/", Some(0)),
+                node("https://example.com/a", Some(1)),
+                node("https://other.test/", None),
+            ],
+            edges: vec![
+                GraphEdge {
+                    source: 0,
+                    target: 1,
+                    text: r#"Say "A" & <b>"#.to_string(),
+                },
+                GraphEdge {
+                    source: 0,
+                    target: 2,
+                    text: "Out".to_string(),
+                },
+            ],
+        }
+    }
+
+    fn export(format: GraphFormat, graph: &LinkGraph) -> String {
+        let mut out = Vec::new();
+        LinkGraphExporter::new(format)
+            .export_to_writer(graph, &mut out)
+            .unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_dot() {
+        let dot = export(GraphFormat::Dot, &graph());
+        assert!(dot.starts_with("digraph links {\n"));
+        assert!(dot.contains(r#"n0 [label="https://example.com/"];"#));
+        assert!(dot.contains(r#"n2 [label="https://other.test/", style=dashed"#));
+        assert!(dot.contains(r#"n0 -> n1 [label="Say \"A\" & <b>"];"#));
+    }
+
+    #[test]
+    fn test_gexf() {
+        let gexf = export(GraphFormat::Gexf, &graph());
+        assert!(gexf.contains(r#"<node id="1" label="https://example.com/a"><attvalues>"#));
+        assert!(gexf.contains(r#"<attvalue for="depth" value="1"/>"#));
+        assert!(gexf.contains(
+            r#"<edge id="0" source="0" target="1" label="Say &quot;A&quot; &amp; &lt;b&gt;"/>"#
+        ));
+        assert!(gexf.trim_end().ends_with("</gexf>"));
+    }
+
+    #[test]
+    fn test_json() {
+        let json = export(GraphFormat::Json, &graph());
+        let parsed: LinkGraph = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, graph());
+    }
+
+    #[test]
+    fn test_for_path() {
+        let format = |path: &str| GraphFormat::for_path(Path::new(path));
+        assert_eq!(format("links.gv"), Some(GraphFormat::Dot));
+        assert_eq!(format("links.GEXF"), Some(GraphFormat::Gexf));
+        assert_eq!(format("links.json.gz"), Some(GraphFormat::Json));
+        assert_eq!(format("links.csv"), None);
+    }
+}
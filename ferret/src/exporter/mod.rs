@@ -1,68 +1,307 @@
 use crate::analyzer::AnalysisResult;
+use crate::html::escape;
+use crate::reporter::{MarkdownDisplay, RenderOptions, Reporter};
 use anyhow::Result;
 use askama::Template;
 use std::fs::File;
-use std::io::Write;
+use std::io::{BufWriter, Write};
 use std::path::Path;
 
+mod chart;
+mod compression;
+mod dashboard;
+mod diff;
+mod dot;
+mod elasticsearch;
+mod influx;
+mod link_graph;
+mod ndjson;
+#[cfg(feature = "parquet")]
+mod parquet;
+mod prometheus;
+mod registry;
+mod svg;
+mod tag_cloud;
+#[cfg(feature = "xlsx")]
+mod xlsx;
+
+#[cfg(feature = "parquet")]
+pub use self::parquet::ParquetExporter;
+pub use compression::{Compression, ExportOptions};
+pub use dashboard::DashboardExporter;
+pub use diff::{DiffExporter, DiffFormat};
+pub use dot::DotExporter;
+pub use elasticsearch::{DocId, ElasticsearchBulkExporter};
+pub use influx::InfluxExporter;
+pub use link_graph::{GraphFormat, LinkGraphExporter};
+pub use ndjson::NdjsonExporter;
+pub use prometheus::PrometheusExporter;
+pub use registry::{registry, BoxedExporter, ExporterRegistry, Format};
+pub use svg::SvgExporter;
+pub use tag_cloud::{TagCloudExporter, TagCloudLayout};
+#[cfg(feature = "xlsx")]
+pub use xlsx::XlsxExporter;
+
 pub trait Exporter {
-    fn export(&self, result: &AnalysisResult, path: &Path) -> Result<()>;
+    /// Write the report to `writer`, e.g. an HTTP response body or stdout
+    fn export_to_writer(&self, result: &AnalysisResult, writer: &mut dyn Write) -> Result<()>;
+
+    /// Write the report to a file at `path`, replacing it if it exists
+    ///
+    /// Paths ending in `.gz` (or `.zst`, with the `zstd` feature) are
+    /// compressed accordingly.
+    fn export(&self, result: &AnalysisResult, path: &Path) -> Result<()> {
+        self.export_with(result, path, &ExportOptions::for_path(path))
+    }
+
+    /// Like `export_to_writer`, but compressed as set in `options`
+    fn export_to_writer_with(
+        &self,
+        result: &AnalysisResult,
+        writer: &mut dyn Write,
+        options: &ExportOptions,
+    ) -> Result<()> {
+        compression::write_compressed(options.compression, writer, |writer| {
+            self.export_to_writer(result, writer)
+        })
+    }
+
+    /// Like `export`, but compressed as set in `options` whatever the
+    /// extension of `path`
+    fn export_with(
+        &self,
+        result: &AnalysisResult,
+        path: &Path,
+        options: &ExportOptions,
+    ) -> Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        self.export_to_writer_with(result, &mut file, options)?;
+        file.flush()?;
+        Ok(())
+    }
 }
 
 pub struct JsonExporter;
 
 impl Exporter for JsonExporter {
-    fn export(&self, result: &AnalysisResult, path: &Path) -> Result<()> {
-        let file = File::create(path)?;
-        serde_json::to_writer_pretty(file, result)?;
+    fn export_to_writer(&self, result: &AnalysisResult, writer: &mut dyn Write) -> Result<()> {
+        serde_json::to_writer_pretty(writer, result)?;
         Ok(())
     }
 }
 
-pub struct CsvExporter;
+/// Markdown file rendered by [`MarkdownDisplay`](crate::reporter::MarkdownDisplay)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MarkdownExporter {
+    pub display: MarkdownDisplay,
+}
+
+impl Exporter for MarkdownExporter {
+    fn export_to_writer(&self, result: &AnalysisResult, writer: &mut dyn Write) -> Result<()> {
+        let options = RenderOptions::default().with_color(false);
+        writer.write_all(self.display.render(result, &options).as_bytes())?;
+        Ok(())
+    }
+}
+
+/// Compact binary MessagePack, read back with `AnalysisResult::from_msgpack`
+///
+/// Fields are written by name, so files stay readable when fields are
+/// added to `AnalysisResult`. About a third of the size of pretty JSON.
+pub struct MsgpackExporter;
+
+impl Exporter for MsgpackExporter {
+    fn export_to_writer(&self, result: &AnalysisResult, writer: &mut dyn Write) -> Result<()> {
+        rmp_serde::encode::write_named(writer, result)?;
+        Ok(())
+    }
+}
+
+/// Compact binary CBOR (RFC 8949), read back with `AnalysisResult::from_cbor`
+pub struct CborExporter;
+
+impl Exporter for CborExporter {
+    fn export_to_writer(&self, result: &AnalysisResult, writer: &mut dyn Write) -> Result<()> {
+        ciborium::into_writer(result, writer)?;
+        Ok(())
+    }
+}
+
+/// A column of `CsvExporter` output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CsvColumn {
+    Tag,
+    Count,
+    Attribute,
+    AttributeCount,
+    Value,
+    ValueCount,
+}
+
+impl CsvColumn {
+    /// All columns in the default order
+    pub const ALL: [CsvColumn; 6] = [
+        CsvColumn::Tag,
+        CsvColumn::Count,
+        CsvColumn::Attribute,
+        CsvColumn::AttributeCount,
+        CsvColumn::Value,
+        CsvColumn::ValueCount,
+    ];
+
+    pub fn header(&self) -> &'static str {
+        match self {
+            CsvColumn::Tag => "Tag",
+            CsvColumn::Count => "Count",
+            CsvColumn::Attribute => "Attribute",
+            CsvColumn::AttributeCount => "Attribute Count",
+            CsvColumn::Value => "Value",
+            CsvColumn::ValueCount => "Value Count",
+        }
+    }
+}
+
+impl std::str::FromStr for CsvColumn {
+    type Err = anyhow::Error;
+
+    /// Parse a header or its snake_case form, e.g. `attribute_count`
+    fn from_str(name: &str) -> Result<Self> {
+        let name = name.trim();
+        CsvColumn::ALL
+            .into_iter()
+            .find(|column| {
+                let header = column.header();
+                header.eq_ignore_ascii_case(name)
+                    || header.replace(' ', "_").eq_ignore_ascii_case(name)
+            })
+            .ok_or_else(|| anyhow::anyhow!("Unknown CSV column: {}", name))
+    }
+}
+
+/// When `CsvExporter` quotes fields
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CsvQuoting {
+    /// Only fields containing the delimiter, quotes or line breaks
+    #[default]
+    Necessary,
+    Always,
+    /// Every field that isn't a number
+    NonNumeric,
+    /// Never; fields with special characters make invalid CSV
+    Never,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvOptions {
+    /// Field separator, e.g. `b';'` for Excel in locales with a decimal comma
+    pub delimiter: u8,
+    /// Start with a UTF-8 byte order mark so Excel detects the encoding
+    pub bom: bool,
+    pub quoting: CsvQuoting,
+    /// Columns to write, in order
+    pub columns: Vec<CsvColumn>,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            bom: false,
+            quoting: CsvQuoting::default(),
+            columns: CsvColumn::ALL.to_vec(),
+        }
+    }
+}
+
+/// One row per attribute value, or per attribute or tag without any
+///
+/// Rows are sorted by tag and attribute name, values by count.
+///
+/// # Example
+/// ```
+/// use ferret::exporter::{CsvColumn, CsvExporter, CsvOptions};
+///
+/// // Semicolon-separated tag counts for Excel
+/// let exporter = CsvExporter::new(CsvOptions {
+///     delimiter: b';',
+///     bom: true,
+///     columns: vec![CsvColumn::Tag, CsvColumn::Count],
+///     ..CsvOptions::default()
+/// });
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CsvExporter {
+    pub options: CsvOptions,
+}
+
+impl CsvExporter {
+    pub fn new(options: CsvOptions) -> Self {
+        Self { options }
+    }
+}
 
 impl Exporter for CsvExporter {
-    fn export(&self, result: &AnalysisResult, path: &Path) -> Result<()> {
-        let mut wtr = csv::Writer::from_path(path)?;
+    fn export_to_writer(&self, result: &AnalysisResult, writer: &mut dyn Write) -> Result<()> {
+        let options = &self.options;
+        if options.bom {
+            writer.write_all("\u{feff}".as_bytes())?;
+        }
+        let quoting = match options.quoting {
+            CsvQuoting::Necessary => csv::QuoteStyle::Necessary,
+            CsvQuoting::Always => csv::QuoteStyle::Always,
+            CsvQuoting::NonNumeric => csv::QuoteStyle::NonNumeric,
+            CsvQuoting::Never => csv::QuoteStyle::Never,
+        };
+        let mut wtr = csv::WriterBuilder::new()
+            .delimiter(options.delimiter)
+            .quote_style(quoting)
+            .from_writer(writer);
 
         // Write headers
-        wtr.write_record(&[
-            "Tag",
-            "Count",
-            "Attribute",
-            "Attribute Count",
-            "Value",
-            "Value Count",
-        ])?;
-
-        // Flatten the nested structure for CSV
-        for (tag_name, tag_stats) in &result.tags {
+        wtr.write_record(options.columns.iter().map(CsvColumn::header))?;
+
+        // Flatten the nested structure for CSV; rows hold every column,
+        // in the order the variants are declared
+        let mut write_row = |row: [&str; 6]| {
+            wtr.write_record(options.columns.iter().map(|&column| row[column as usize]))
+        };
+        let mut tags: Vec<_> = result.tags.values().collect();
+        tags.sort_by(|a, b| a.name.cmp(&b.name));
+        for tag_stats in tags {
+            let tag_count = tag_stats.count.to_string();
             // Write tag level info even if no attributes
             if tag_stats.attributes.is_empty() {
-                wtr.write_record(&[tag_name, &tag_stats.count.to_string(), "", "", "", ""])?;
-            } else {
-                for (attr_name, attr_stats) in &tag_stats.attributes {
-                    if attr_stats.value_counts.is_empty() {
-                        wtr.write_record(&[
-                            tag_name,
-                            &tag_stats.count.to_string(),
-                            attr_name,
-                            &attr_stats.count.to_string(),
-                            "",
-                            "",
-                        ])?;
-                    } else {
-                        for (val, val_count) in &attr_stats.value_counts {
-                            wtr.write_record(&[
-                                tag_name,
-                                &tag_stats.count.to_string(),
-                                attr_name,
-                                &attr_stats.count.to_string(),
-                                val,
-                                &val_count.to_string(),
-                            ])?;
-                        }
-                    }
+                write_row([&tag_stats.name, &tag_count, "", "", "", ""])?;
+                continue;
+            }
+
+            let mut attributes: Vec<_> = tag_stats.attributes.values().collect();
+            attributes.sort_by(|a, b| a.name.cmp(&b.name));
+            for attr_stats in attributes {
+                let attr_count = attr_stats.count.to_string();
+                if attr_stats.value_counts.is_empty() {
+                    write_row([
+                        &tag_stats.name,
+                        &tag_count,
+                        &attr_stats.name,
+                        &attr_count,
+                        "",
+                        "",
+                    ])?;
+                    continue;
+                }
+
+                let mut values: Vec<_> = attr_stats.value_counts.iter().collect();
+                values.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+                for (val, val_count) in values {
+                    write_row([
+                        &tag_stats.name,
+                        &tag_count,
+                        &attr_stats.name,
+                        &attr_count,
+                        val,
+                        &val_count.to_string(),
+                    ])?;
                 }
             }
         }
@@ -72,57 +311,88 @@ impl Exporter for CsvExporter {
     }
 }
 
-pub struct HtmlTreeExporter;
+/// Standalone HTML page with a collapsible tree of tags, attributes and
+/// their most frequent values
+#[derive(Debug, Clone, Default)]
+pub struct HtmlTreeExporter {
+    /// Show attribute values as `<code>` spans, which keeps markup and
+    /// whitespace in values readable
+    pub code_values: bool,
+}
+
+impl HtmlTreeExporter {
+    pub fn with_code_values(mut self, code_values: bool) -> Self {
+        self.code_values = code_values;
+        self
+    }
+}
 
 impl Exporter for HtmlTreeExporter {
-    fn export(&self, result: &AnalysisResult, path: &Path) -> Result<()> {
-        let mut file = File::create(path)?;
+    fn export_to_writer(&self, result: &AnalysisResult, file: &mut dyn Write) -> Result<()> {
         writeln!(file, "<!DOCTYPE html><html><head><style>")?;
         writeln!(file, "body {{ font-family: sans-serif; }}")?;
         writeln!(file, "ul {{ list-style-type: none; }}")?;
         writeln!(file, ".tag {{ color: #2c3e50; font-weight: bold; }}")?;
         writeln!(file, ".attr {{ color: #e67e22; }}")?;
         writeln!(file, ".val {{ color: #27ae60; }}")?;
+        writeln!(
+            file,
+            "code.val {{ background: #f4f4f4; padding: 0 0.2em; white-space: pre-wrap; }}"
+        )?;
         writeln!(file, ".count {{ color: #7f8c8d; font-size: 0.9em; }}")?;
         writeln!(file, "</style></head><body>")?;
         writeln!(file, "<h1>Analysis Report</h1>")?;
         writeln!(file, "<p>Files analyzed: {}</p>", result.files_analyzed)?;
-        writeln!(file, "<ul>")?;
+        write_tag_tree(result, self.code_values, file)?;
+        writeln!(file, "</body></html>")?;
+        Ok(())
+    }
+}
+
+/// Collapsible `<ul>` tree of tags, attributes and their top values
+fn write_tag_tree(result: &AnalysisResult, code_values: bool, file: &mut dyn Write) -> Result<()> {
+    let value_element = if code_values { "code" } else { "span" };
+    writeln!(file, "<ul>")?;
 
-        let mut sorted_tags: Vec<_> = result.tags.values().collect();
-        sorted_tags.sort_by(|a, b| b.count.cmp(&a.count));
+    let mut sorted_tags: Vec<_> = result.tags.values().collect();
+    sorted_tags.sort_by_key(|t| std::cmp::Reverse(t.count));
 
-        for tag in sorted_tags {
-            writeln!(file, "<li><details><summary><span class='tag'>{}</span> <span class='count'>({})</span></summary>", tag.name, tag.count)?;
+    for tag in sorted_tags {
+        writeln!(file, "<li><details><summary><span class='tag'>{}</span> <span class='count'>({})</span></summary>", escape(&tag.name), tag.count)?;
 
-            if !tag.attributes.is_empty() {
-                writeln!(file, "<ul>")?;
-                let mut sorted_attrs: Vec<_> = tag.attributes.values().collect();
-                sorted_attrs.sort_by(|a, b| b.count.cmp(&a.count));
+        if !tag.attributes.is_empty() {
+            writeln!(file, "<ul>")?;
+            let mut sorted_attrs: Vec<_> = tag.attributes.values().collect();
+            sorted_attrs.sort_by_key(|a| std::cmp::Reverse(a.count));
 
-                for attr in sorted_attrs {
-                    writeln!(file, "<li><details><summary><span class='attr'>@{}</span> <span class='count'>({})</span></summary>", attr.name, attr.count)?;
+            for attr in sorted_attrs {
+                writeln!(file, "<li><details><summary><span class='attr'>@{}</span> <span class='count'>({})</span></summary>", escape(&attr.name), attr.count)?;
 
-                    if !attr.value_counts.is_empty() {
-                        writeln!(file, "<ul>")?;
-                        let mut sorted_vals: Vec<_> = attr.value_counts.iter().collect();
-                        sorted_vals.sort_by(|a, b| b.1.cmp(a.1));
+                if !attr.value_counts.is_empty() {
+                    writeln!(file, "<ul>")?;
+                    let mut sorted_vals: Vec<_> = attr.value_counts.iter().collect();
+                    sorted_vals.sort_by(|a, b| b.1.cmp(a.1));
 
-                        for (val, count) in sorted_vals.iter().take(10) {
-                            writeln!(file, "<li><span class='val'>{}</span> <span class='count'>({})</span></li>", val, count)?;
-                        }
-                        writeln!(file, "</ul>")?;
+                    for (val, count) in sorted_vals.iter().take(10) {
+                        writeln!(
+                            file,
+                            "<li><{element} class='val'>{}</{element}> <span class='count'>({})</span></li>",
+                            escape(val),
+                            count,
+                            element = value_element
+                        )?;
                     }
-                    writeln!(file, "</details></li>")?;
+                    writeln!(file, "</ul>")?;
                 }
-                writeln!(file, "</ul>")?;
+                writeln!(file, "</details></li>")?;
             }
-            writeln!(file, "</details></li>")?;
+            writeln!(file, "</ul>")?;
         }
-
-        writeln!(file, "</ul></body></html>")?;
-        Ok(())
+        writeln!(file, "</details></li>")?;
     }
+
+    writeln!(file, "</ul>")?;
+    Ok(())
 }
 
 #[derive(Template)]
@@ -135,20 +405,292 @@ pub struct GraphVisualizerTemplate<'a> {
 mod filters {
     use crate::analyzer::AnalysisResult;
 
+    /// JSON for the inline `<script>`, escaped so values cannot close it
     #[allow(dead_code)]
     pub fn json(data: &AnalysisResult) -> askama::Result<String> {
-        serde_json::to_string(data).map_err(|e| askama::Error::Custom(Box::new(e)))
+        crate::html::script_json(data).map_err(|e| askama::Error::Custom(Box::new(e)))
     }
 }
 
 pub struct GraphVisualizerExporter;
 
 impl Exporter for GraphVisualizerExporter {
-    fn export(&self, result: &AnalysisResult, path: &Path) -> Result<()> {
+    fn export_to_writer(&self, result: &AnalysisResult, writer: &mut dyn Write) -> Result<()> {
         let template = GraphVisualizerTemplate { data: result };
-        let content = template.render()?;
-        let mut file = File::create(path)?;
-        file.write_all(content.as_bytes())?;
+        template.write_into(writer)?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::stream::StreamAnalyzer;
+    use std::time::SystemTime;
+
+    #[test]
+    fn test_export_to_writer_matches_file() {
+        let result = StreamAnalyzer::new(10)
+            .analyze_string(r#"<div class="a"><p>x</p></div>"#)
+            .unwrap();
+        let dir = tempfile::tempdir().unwrap();
+
+        let exporters: [(&dyn Exporter, &str); 14] = [
+            (&JsonExporter, "report.json"),
+            (&CsvExporter::default(), "report.csv"),
+            (&HtmlTreeExporter::default(), "report.html"),
+            (&GraphVisualizerExporter, "graph.html"),
+            (&DotExporter::default(), "graph.dot"),
+            (&DashboardExporter::default(), "dashboard.html"),
+            (&SvgExporter::default(), "charts.svg"),
+            (&PrometheusExporter::new(), "metrics.txt"),
+            (
+                &InfluxExporter::new().timestamp(SystemTime::UNIX_EPOCH),
+                "points.lp",
+            ),
+            (&ElasticsearchBulkExporter::new("ferret"), "bulk.ndjson"),
+            (&NdjsonExporter::new(), "tags.ndjson"),
+            (&MsgpackExporter, "result.msgpack"),
+            (&CborExporter, "result.cbor"),
+            (&MarkdownExporter::default(), "report.md"),
+        ];
+        for (exporter, name) in exporters {
+            let mut buffer = Vec::new();
+            exporter.export_to_writer(&result, &mut buffer).unwrap();
+            assert!(!buffer.is_empty(), "{}", name);
+
+            let path = dir.path().join(name);
+            exporter.export(&result, &path).unwrap();
+            assert_eq!(std::fs::read(&path).unwrap(), buffer, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_compressed_export() {
+        use flate2::read::GzDecoder;
+        use std::io::Read;
+
+        let result = StreamAnalyzer::new(10)
+            .analyze_string(&"<div class=\"a\"><p>x</p></div>".repeat(100))
+            .unwrap();
+        let mut plain = Vec::new();
+        JsonExporter.export_to_writer(&result, &mut plain).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("result.json.gz");
+        JsonExporter.export(&result, &path).unwrap();
+        let compressed = std::fs::read(&path).unwrap();
+        assert!(compressed.len() < plain.len());
+        let mut decompressed = Vec::new();
+        GzDecoder::new(compressed.as_slice())
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, plain);
+
+        // Explicit options win over the extension
+        let path = dir.path().join("result.json");
+        let options = ExportOptions {
+            compression: Some(Compression::Gzip(1)),
+        };
+        CsvExporter::default()
+            .export_with(&result, &path, &options)
+            .unwrap();
+        assert_eq!(&std::fs::read(&path).unwrap()[..2], &[0x1f, 0x8b]);
+        JsonExporter
+            .export_with(&result, &path, &ExportOptions::default())
+            .unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), plain);
+
+        #[cfg(feature = "zstd")]
+        {
+            let path = dir.path().join("result.json.zst");
+            JsonExporter.export(&result, &path).unwrap();
+            let decompressed = zstd::decode_all(std::fs::File::open(&path).unwrap()).unwrap();
+            assert_eq!(decompressed, plain);
+        }
+    }
+
+    #[test]
+    fn test_binary_round_trip() {
+        let analyzer = StreamAnalyzer::new(10)
+            .with_mode(crate::sniff::ParseMode::Xml)
+            .with_structure(true);
+        let mut result = analyzer
+            .analyze_string(include_str!("../../tests/fixtures/realistic_sample.xml"))
+            .unwrap();
+        result.parse_errors.push(crate::analyzer::ParseIssue {
+            position: 7,
+            message: "Unclosed element <é>".to_string(),
+        });
+        let json = serde_json::to_value(&result).unwrap();
+        let pretty = serde_json::to_vec_pretty(&result).unwrap();
+
+        let mut msgpack = Vec::new();
+        MsgpackExporter
+            .export_to_writer(&result, &mut msgpack)
+            .unwrap();
+        let decoded = AnalysisResult::from_msgpack(&msgpack).unwrap();
+        assert_eq!(serde_json::to_value(&decoded).unwrap(), json);
+        let decoded = AnalysisResult::from_msgpack_reader(msgpack.as_slice()).unwrap();
+        assert_eq!(serde_json::to_value(&decoded).unwrap(), json);
+        assert!(msgpack.len() * 2 < pretty.len());
+
+        let mut cbor = Vec::new();
+        CborExporter.export_to_writer(&result, &mut cbor).unwrap();
+        let decoded = AnalysisResult::from_cbor(&cbor).unwrap();
+        assert_eq!(serde_json::to_value(&decoded).unwrap(), json);
+        assert!(cbor.len() * 2 < pretty.len());
+
+        assert!(AnalysisResult::from_msgpack(b"not msgpack").is_err());
+        assert!(AnalysisResult::from_cbor(&[0xff]).is_err());
+    }
+
+    #[test]
+    fn test_csv_options() {
+        let result = StreamAnalyzer::new(10)
+            .analyze_string(r#"<a href="/x;y">1</a><a href="/z">2</a><a href="/x;y">3</a><br/>"#)
+            .unwrap();
+        let export = |options: CsvOptions| {
+            let mut csv = Vec::new();
+            CsvExporter::new(options)
+                .export_to_writer(&result, &mut csv)
+                .unwrap();
+            String::from_utf8(csv).unwrap()
+        };
+
+        assert_eq!(
+            export(CsvOptions::default()),
+            "Tag,Count,Attribute,Attribute Count,Value,Value Count\n\
+             a,3,href,3,/x;y,2\n\
+             a,3,href,3,/z,1\n\
+             br,1,,,,\n"
+        );
+        assert_eq!(
+            export(CsvOptions {
+                delimiter: b';',
+                bom: true,
+                columns: vec![CsvColumn::Value, CsvColumn::Tag],
+                ..CsvOptions::default()
+            }),
+            "\u{feff}Value;Tag\n\"/x;y\";a\n/z;a\n;br\n"
+        );
+        assert_eq!(
+            export(CsvOptions {
+                quoting: CsvQuoting::NonNumeric,
+                columns: vec![CsvColumn::Tag, CsvColumn::ValueCount],
+                ..CsvOptions::default()
+            }),
+            "\"Tag\",\"Value Count\"\n\"a\",2\n\"a\",1\n\"br\",\"\"\n"
+        );
+    }
+
+    #[test]
+    fn test_csv_round_trip() {
+        let result = StreamAnalyzer::new(10)
+            .analyze_string(include_str!("../../tests/fixtures/realistic_sample.html"))
+            .unwrap();
+        let export = |result: &AnalysisResult, options: CsvOptions| {
+            let mut csv = Vec::new();
+            CsvExporter::new(options)
+                .export_to_writer(result, &mut csv)
+                .unwrap();
+            csv
+        };
+
+        let csv = export(&result, CsvOptions::default());
+        let decoded = AnalysisResult::from_csv(csv.as_slice()).unwrap();
+        assert_eq!(export(&decoded, CsvOptions::default()), csv);
+        assert_eq!(decoded.tags.len(), result.tags.len());
+        for (name, tag) in &result.tags {
+            assert_eq!(decoded.tags[name].count, tag.count, "{}", name);
+        }
+
+        let options = CsvOptions {
+            delimiter: b';',
+            bom: true,
+            quoting: CsvQuoting::Always,
+            columns: vec![
+                CsvColumn::ValueCount,
+                CsvColumn::Value,
+                CsvColumn::Attribute,
+                CsvColumn::Tag,
+                CsvColumn::AttributeCount,
+                CsvColumn::Count,
+            ],
+        };
+        let decoded = AnalysisResult::from_csv_with_delimiter(
+            export(&result, options.clone()).as_slice(),
+            b';',
+        )
+        .unwrap();
+        assert_eq!(export(&decoded, CsvOptions::default()), csv);
+
+        // Empty values are kept apart from attributes without tracked values
+        let result = StreamAnalyzer::new(10)
+            .analyze_string(r#"<img alt=""><img alt="x">"#)
+            .unwrap();
+        let decoded =
+            AnalysisResult::from_csv(export(&result, CsvOptions::default()).as_slice()).unwrap();
+        assert_eq!(
+            decoded.tags["img"].attributes["alt"].value_counts,
+            result.tags["img"].attributes["alt"].value_counts
+        );
+        let result = StreamAnalyzer::new(0)
+            .analyze_string(r#"<img alt="x">"#)
+            .unwrap();
+        let decoded =
+            AnalysisResult::from_csv(export(&result, CsvOptions::default()).as_slice()).unwrap();
+        assert_eq!(decoded.tags["img"].attributes["alt"].count, 1);
+        assert!(decoded.tags["img"].attributes["alt"]
+            .value_counts
+            .is_empty());
+    }
+
+    #[test]
+    fn test_csv_import_errors() {
+        let error = |csv: &str| {
+            AnalysisResult::from_csv(csv.as_bytes())
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(error("Count\n3\n"), "CSV has no Tag column");
+        assert_eq!(error("Tag,Count\na,x\n"), "invalid Count \"x\" on line 2");
+        assert_eq!(error("Tag,Count\n,3\n"), "missing Tag on line 2");
+        assert!(error("Tag,Colour\n").contains("Colour"));
+    }
+
+    #[test]
+    fn test_csv_column_names() {
+        assert_eq!(
+            "attribute_count".parse::<CsvColumn>().unwrap(),
+            CsvColumn::AttributeCount
+        );
+        assert_eq!(
+            " Value Count".parse::<CsvColumn>().unwrap(),
+            CsvColumn::ValueCount
+        );
+        assert!("depth".parse::<CsvColumn>().is_err());
+    }
+
+    #[test]
+    fn test_html_tree_escapes_values() {
+        let result = StreamAnalyzer::new(10)
+            .analyze_string(r#"<a title="<b>&amp;">x</a>"#)
+            .unwrap();
+        let mut html = Vec::new();
+        HtmlTreeExporter::default()
+            .export_to_writer(&result, &mut html)
+            .unwrap();
+        let html = String::from_utf8(html).unwrap();
+        assert!(html.contains("<span class='val'>&lt;b&gt;&amp;amp;</span>"));
+        assert!(!html.contains("<b>"));
+
+        let mut html = Vec::new();
+        HtmlTreeExporter::default()
+            .with_code_values(true)
+            .export_to_writer(&result, &mut html)
+            .unwrap();
+        let html = String::from_utf8(html).unwrap();
+        assert!(html.contains("<code class='val'>&lt;b&gt;&amp;amp;</code>"));
+    }
+}
@@ -0,0 +1,100 @@
+use crate::analyzer::{AnalysisResult, AnalysisResultSet};
+use crate::exporter::elasticsearch::document;
+use crate::exporter::Exporter;
+use anyhow::Result;
+use std::io::Write;
+
+/// Newline-delimited JSON, one object per tag, for `jq`, log shippers and
+/// other tools reading a record per line
+///
+/// ```text
+/// {"source":"https://example.com/","tag":"a","count":12,"attributes":[{"name":"href","count":12,"values":[{"value":"/","count":3}]}]}
+/// ```
+///
+/// The records are the documents of [`ElasticsearchBulkExporter`](super::ElasticsearchBulkExporter)
+/// without the action lines of the `_bulk` API.
+pub struct NdjsonExporter {
+    /// `source` field for single results; batches use each entry's source
+    pub source: Option<String>,
+}
+
+impl NdjsonExporter {
+    pub fn new() -> Self {
+        Self { source: None }
+    }
+
+    /// Record `source` (a path or URL) for results exported with `export`
+    pub fn source(mut self, source: impl Into<String>) -> Self {
+        self.source = Some(source.into());
+        self
+    }
+
+    /// Records of every successful entry of `set`
+    pub fn export_set_to_writer(
+        &self,
+        set: &AnalysisResultSet,
+        writer: &mut dyn Write,
+    ) -> Result<()> {
+        for entry in &set.entries {
+            if let Some(result) = &entry.result {
+                write_records(&entry.source, result, writer)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for NdjsonExporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Exporter for NdjsonExporter {
+    fn export_to_writer(&self, result: &AnalysisResult, writer: &mut dyn Write) -> Result<()> {
+        write_records(self.source.as_deref().unwrap_or_default(), result, writer)
+    }
+}
+
+fn write_records(source: &str, result: &AnalysisResult, writer: &mut dyn Write) -> Result<()> {
+    let mut tags: Vec<_> = result.tags.values().collect();
+    tags.sort_by(|a, b| a.name.cmp(&b.name));
+    for tag in tags {
+        serde_json::to_writer(&mut *writer, &document(source, tag))?;
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::stream::StreamAnalyzer;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn test_records() {
+        let result = StreamAnalyzer::new(10)
+            .analyze_string(r#"<div><a href="/x">1</a><a href="/y">2</a></div>"#)
+            .unwrap();
+        let mut ndjson = Vec::new();
+        NdjsonExporter::new()
+            .source("index.html")
+            .export_to_writer(&result, &mut ndjson)
+            .unwrap();
+        let ndjson = String::from_utf8(ndjson).unwrap();
+        assert!(ndjson.ends_with('\n'));
+        let lines: Vec<Value> = ndjson
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["source"], "index.html");
+        assert_eq!(lines[0]["tag"], "a");
+        assert_eq!(
+            lines[0]["attributes"][0]["values"][1],
+            json!({ "value": "/y", "count": 1 })
+        );
+        assert_eq!(lines[1]["tag"], "div");
+    }
+}
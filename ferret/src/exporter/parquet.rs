@@ -0,0 +1,239 @@
+use crate::analyzer::{AnalysisResult, AnalysisResultSet};
+use crate::exporter::Exporter;
+use anyhow::Result;
+use arrow_array::{ArrayRef, RecordBatch, StringArray, UInt64Array};
+use arrow_schema::{DataType, Field, Schema};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Flat, columnar table of counts for analytics tools such as DuckDB or Spark
+///
+/// Every row is one count: `(run_id, source, tag, attr, value, count)`.
+/// Tag rows have a null `attr` and `value`, attribute rows a null `value`.
+/// For example, `SELECT tag, sum(count) FROM 'run.parquet' WHERE attr IS
+/// NULL GROUP BY tag` totals the tags of every source. Columns are Snappy
+/// compressed.
+///
+/// Requires the `parquet` feature.
+///
+/// # Example
+/// ```no_run
+/// # use ferret::analyzer::stream::StreamAnalyzer;
+/// use ferret::exporter::ParquetExporter;
+/// use std::path::Path;
+///
+/// let set = StreamAnalyzer::new(10).analyze_dir(Path::new("dumps"), true)?;
+/// ParquetExporter::new("2024-06-01").export_set(&set, Path::new("run.parquet"))?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub struct ParquetExporter {
+    /// Identifies the run, so tables of several runs can be queried together
+    pub run_id: String,
+    /// Source recorded for single results; batches use each entry's source
+    pub source: Option<String>,
+}
+
+impl ParquetExporter {
+    pub fn new(run_id: impl Into<String>) -> Self {
+        Self {
+            run_id: run_id.into(),
+            source: None,
+        }
+    }
+
+    /// Record `source` (a path or URL) for results exported with `export`
+    pub fn source(mut self, source: impl Into<String>) -> Self {
+        self.source = Some(source.into());
+        self
+    }
+
+    /// Write one table with the rows of every successful entry of `set`
+    pub fn export_set(&self, set: &AnalysisResultSet, path: &Path) -> Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        self.export_set_to_writer(set, &mut file)?;
+        file.flush()?;
+        Ok(())
+    }
+
+    pub fn export_set_to_writer(
+        &self,
+        set: &AnalysisResultSet,
+        writer: &mut dyn Write,
+    ) -> Result<()> {
+        let mut rows = Rows::default();
+        for entry in &set.entries {
+            if let Some(result) = &entry.result {
+                rows.add(Some(&entry.source), result);
+            }
+        }
+        self.write(rows, writer)
+    }
+
+    fn write(&self, rows: Rows, writer: &mut dyn Write) -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("run_id", DataType::Utf8, false),
+            Field::new("source", DataType::Utf8, true),
+            Field::new("tag", DataType::Utf8, false),
+            Field::new("attr", DataType::Utf8, true),
+            Field::new("value", DataType::Utf8, true),
+            Field::new("count", DataType::UInt64, false),
+        ]));
+        let run_ids = vec![self.run_id.as_str(); rows.count.len()];
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(StringArray::from(run_ids)),
+            Arc::new(StringArray::from(rows.source)),
+            Arc::new(StringArray::from(rows.tag)),
+            Arc::new(StringArray::from(rows.attr)),
+            Arc::new(StringArray::from(rows.value)),
+            Arc::new(UInt64Array::from(rows.count)),
+        ];
+        let batch = RecordBatch::try_new(schema.clone(), columns)?;
+
+        // ArrowWriter needs a `Send` writer, so the file is built in memory
+        let properties = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build();
+        let mut buffer = Vec::new();
+        let mut arrow_writer = ArrowWriter::try_new(&mut buffer, schema, Some(properties))?;
+        arrow_writer.write(&batch)?;
+        arrow_writer.close()?;
+
+        writer.write_all(&buffer)?;
+        Ok(())
+    }
+}
+
+impl Exporter for ParquetExporter {
+    fn export_to_writer(&self, result: &AnalysisResult, writer: &mut dyn Write) -> Result<()> {
+        let mut rows = Rows::default();
+        rows.add(self.source.as_deref(), result);
+        self.write(rows, writer)
+    }
+}
+
+/// Column values, borrowed from the results being exported
+#[derive(Default)]
+struct Rows<'a> {
+    source: Vec<Option<&'a str>>,
+    tag: Vec<&'a str>,
+    attr: Vec<Option<&'a str>>,
+    value: Vec<Option<&'a str>>,
+    count: Vec<u64>,
+}
+
+impl<'a> Rows<'a> {
+    /// Add the rows of one result, sorted by tag, attribute and value
+    fn add(&mut self, source: Option<&'a str>, result: &'a AnalysisResult) {
+        let mut tags: Vec<_> = result.tags.values().collect();
+        tags.sort_by(|a, b| a.name.cmp(&b.name));
+
+        for tag in tags {
+            self.push(source, &tag.name, None, None, tag.count);
+
+            let mut attributes: Vec<_> = tag.attributes.values().collect();
+            attributes.sort_by(|a, b| a.name.cmp(&b.name));
+            for attr in attributes {
+                self.push(source, &tag.name, Some(&attr.name), None, attr.count);
+
+                let mut values: Vec<_> = attr.value_counts.iter().collect();
+                values.sort();
+                for (value, count) in values {
+                    self.push(source, &tag.name, Some(&attr.name), Some(value), *count);
+                }
+            }
+        }
+    }
+
+    fn push(
+        &mut self,
+        source: Option<&'a str>,
+        tag: &'a str,
+        attr: Option<&'a str>,
+        value: Option<&'a str>,
+        count: usize,
+    ) {
+        self.source.push(source);
+        self.tag.push(tag);
+        self.attr.push(attr);
+        self.value.push(value);
+        self.count.push(count as u64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::stream::StreamAnalyzer;
+    use arrow_array::Array;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    fn read(path: &Path) -> RecordBatch {
+        let file = File::open(path).unwrap();
+        let mut reader = ParquetRecordBatchReaderBuilder::try_new(file)
+            .unwrap()
+            .build()
+            .unwrap();
+        reader.next().unwrap().unwrap()
+    }
+
+    fn strings(batch: &RecordBatch, column: &str) -> Vec<Option<String>> {
+        let array = batch
+            .column_by_name(column)
+            .unwrap()
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        (0..array.len())
+            .map(|i| (!array.is_null(i)).then(|| array.value(i).to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_rows() {
+        let result = StreamAnalyzer::new(10)
+            .analyze_string(r#"<ul><li class="a">1</li><li class="a">2</li><li>3</li></ul>"#)
+            .unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("run.parquet");
+        ParquetExporter::new("run-1")
+            .source("list.html")
+            .export(&result, &path)
+            .unwrap();
+
+        let batch = read(&path);
+        assert_eq!(batch.num_rows(), 4);
+        assert_eq!(
+            strings(&batch, "tag"),
+            ["li", "li", "li", "ul"].map(|s| Some(s.to_string()))
+        );
+        assert_eq!(
+            strings(&batch, "attr"),
+            [
+                None,
+                Some("class".to_string()),
+                Some("class".to_string()),
+                None
+            ]
+        );
+        assert_eq!(
+            strings(&batch, "value"),
+            [None, None, Some("a".to_string()), None]
+        );
+        assert_eq!(
+            strings(&batch, "source"),
+            vec![Some("list.html".to_string()); 4]
+        );
+        let counts = batch
+            .column_by_name("count")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<UInt64Array>()
+            .unwrap();
+        assert_eq!(counts.values().to_vec(), [3, 2, 2, 1]);
+    }
+}
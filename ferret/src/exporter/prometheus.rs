@@ -0,0 +1,210 @@
+use crate::analyzer::{AnalysisResult, AnalysisResultSet};
+use crate::exporter::Exporter;
+use anyhow::Result;
+use std::io::Write;
+
+/// Results as metrics in the Prometheus text exposition format
+///
+/// Every tag and attribute count becomes a gauge sample labelled with the
+/// source, so scheduled analyses can be scraped into Prometheus (or pushed
+/// to a Pushgateway) and graphed in Grafana:
+///
+/// ```text
+/// # HELP ferret_tag_count Elements with this tag
+/// # TYPE ferret_tag_count gauge
+/// ferret_tag_count{source="https://example.com/",tag="div"} 123
+/// ```
+///
+/// Attribute values are left out; their cardinality is unbounded.
+pub struct PrometheusExporter {
+    /// `source` label for single results; batches use each entry's source
+    pub source: Option<String>,
+}
+
+/// Metric families in output order: name, help text and value
+type Family = (&'static str, &'static str, fn(&AnalysisResult) -> usize);
+
+/// Per-source metrics besides the tag and attribute counts
+const SOURCE_METRICS: &[Family] = &[
+    ("ferret_files_analyzed", "Documents analyzed", |result| {
+        result.files_analyzed
+    }),
+    ("ferret_max_depth", "Deepest element nesting", |result| {
+        result.max_depth
+    }),
+    (
+        "ferret_parse_errors",
+        "Markup errors skipped while parsing",
+        |result| result.parse_errors.len(),
+    ),
+];
+
+impl PrometheusExporter {
+    pub fn new() -> Self {
+        Self { source: None }
+    }
+
+    /// Label the metrics of single results with `source` (a path or URL)
+    pub fn source(mut self, source: impl Into<String>) -> Self {
+        self.source = Some(source.into());
+        self
+    }
+
+    /// Metrics of every successful entry of `set`, labelled by source
+    pub fn export_set_to_writer(
+        &self,
+        set: &AnalysisResultSet,
+        writer: &mut dyn Write,
+    ) -> Result<()> {
+        let results: Vec<_> = set
+            .entries
+            .iter()
+            .filter_map(|entry| Some((Some(entry.source.as_str()), entry.result.as_ref()?)))
+            .collect();
+        write_metrics(&results, writer)
+    }
+}
+
+impl Default for PrometheusExporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Exporter for PrometheusExporter {
+    fn export_to_writer(&self, result: &AnalysisResult, writer: &mut dyn Write) -> Result<()> {
+        write_metrics(&[(self.source.as_deref(), result)], writer)
+    }
+}
+
+/// Write each metric family once, with a sample per source
+fn write_metrics(
+    results: &[(Option<&str>, &AnalysisResult)],
+    writer: &mut dyn Write,
+) -> Result<()> {
+    write_family_header(writer, "ferret_tag_count", "Elements with this tag")?;
+    for (source, result) in results {
+        let mut tags: Vec<_> = result.tags.values().collect();
+        tags.sort_by(|a, b| a.name.cmp(&b.name));
+        for tag in tags {
+            write_sample(
+                writer,
+                "ferret_tag_count",
+                *source,
+                &[("tag", &tag.name)],
+                tag.count,
+            )?;
+        }
+    }
+
+    write_family_header(
+        writer,
+        "ferret_attribute_count",
+        "Elements with this tag that have the attribute",
+    )?;
+    for (source, result) in results {
+        let mut tags: Vec<_> = result.tags.values().collect();
+        tags.sort_by(|a, b| a.name.cmp(&b.name));
+        for tag in tags {
+            let mut attributes: Vec<_> = tag.attributes.values().collect();
+            attributes.sort_by(|a, b| a.name.cmp(&b.name));
+            for attr in attributes {
+                write_sample(
+                    writer,
+                    "ferret_attribute_count",
+                    *source,
+                    &[("tag", &tag.name), ("attr", &attr.name)],
+                    attr.count,
+                )?;
+            }
+        }
+    }
+
+    for (name, help, value) in SOURCE_METRICS {
+        write_family_header(writer, name, help)?;
+        for (source, result) in results {
+            write_sample(writer, name, *source, &[], value(result))?;
+        }
+    }
+    Ok(())
+}
+
+fn write_family_header(writer: &mut dyn Write, name: &str, help: &str) -> Result<()> {
+    writeln!(writer, "# HELP {} {}", name, help)?;
+    writeln!(writer, "# TYPE {} gauge", name)?;
+    Ok(())
+}
+
+fn write_sample(
+    writer: &mut dyn Write,
+    name: &str,
+    source: Option<&str>,
+    labels: &[(&str, &str)],
+    value: usize,
+) -> Result<()> {
+    let labels: Vec<String> = source
+        .map(|source| ("source", source))
+        .into_iter()
+        .chain(labels.iter().copied())
+        .map(|(label, value)| format!("{}=\"{}\"", label, escape_label(value)))
+        .collect();
+    if labels.is_empty() {
+        writeln!(writer, "{} {}", name, value)?;
+    } else {
+        writeln!(writer, "{}{{{}}} {}", name, labels.join(","), value)?;
+    }
+    Ok(())
+}
+
+/// Escape a label value: backslash, double quote and line feed
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::stream::StreamAnalyzer;
+
+    #[test]
+    fn test_metrics() {
+        let result = StreamAnalyzer::new(10)
+            .analyze_string(r#"<div class="a"><p>1</p><p class="b">2</p></div>"#)
+            .unwrap();
+        let mut text = Vec::new();
+        PrometheusExporter::new()
+            .source("https://example.com/\"q\"")
+            .export_to_writer(&result, &mut text)
+            .unwrap();
+        let text = String::from_utf8(text).unwrap();
+
+        let source = r#"source="https://example.com/\"q\"""#;
+        assert!(text.starts_with(
+            "# HELP ferret_tag_count Elements with this tag\n# TYPE ferret_tag_count gauge\n"
+        ));
+        assert!(text.contains(&format!("ferret_tag_count{{{},tag=\"p\"}} 2\n", source)));
+        assert!(text.contains(&format!(
+            "ferret_attribute_count{{{},tag=\"div\",attr=\"class\"}} 1\n",
+            source
+        )));
+        assert!(text.contains(&format!("ferret_max_depth{{{}}} 2\n", source)));
+        assert_eq!(text.matches("# TYPE").count(), 5);
+
+        // Without a source, samples of the global metrics have no labels
+        let mut text = Vec::new();
+        PrometheusExporter::new()
+            .export_to_writer(&result, &mut text)
+            .unwrap();
+        assert!(String::from_utf8(text)
+            .unwrap()
+            .contains("\nferret_files_analyzed 1\n"));
+    }
+
+    #[test]
+    fn test_escape_label() {
+        assert_eq!(escape_label("a\\b\"c\nd"), r#"a\\b\"c\nd"#);
+    }
+}
@@ -0,0 +1,348 @@
+use crate::exporter::{
+    CborExporter, CsvExporter, DashboardExporter, DotExporter, ElasticsearchBulkExporter, Exporter,
+    GraphVisualizerExporter, HtmlTreeExporter, InfluxExporter, JsonExporter, MarkdownExporter,
+    MsgpackExporter, NdjsonExporter, PrometheusExporter, SvgExporter, TagCloudExporter,
+    TagCloudLayout,
+};
+use std::path::Path;
+
+/// Exporter that can be sent to other threads, e.g. in a server
+pub type BoxedExporter = Box<dyn Exporter + Send + Sync>;
+
+/// An export format known to an [`ExporterRegistry`]
+#[derive(Clone, Copy)]
+pub struct Format {
+    /// Name used to select the format, e.g. `csv`
+    pub name: &'static str,
+    /// File extensions without the dot; the first is the preferred one
+    pub extensions: &'static [&'static str],
+    /// Media type for HTTP responses
+    pub content_type: &'static str,
+    /// Build the exporter; `source` names the analyzed input (a path or
+    /// URL) for formats that record or display it
+    pub create: fn(source: Option<&str>) -> BoxedExporter,
+}
+
+impl std::fmt::Debug for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Format")
+            .field("name", &self.name)
+            .field("extensions", &self.extensions)
+            .field("content_type", &self.content_type)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Export formats by name and file extension
+///
+/// # Example
+/// ```no_run
+/// # use ferret::analyzer::stream::StreamAnalyzer;
+/// use ferret::exporter::registry;
+/// use std::path::Path;
+///
+/// let result = StreamAnalyzer::new(10).analyze_file(Path::new("page.html"))?;
+/// let output = Path::new("report.csv.gz");
+/// let format = registry().for_path(output).expect("unknown extension");
+/// (format.create)(Some("page.html")).export(&result, output)?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct ExporterRegistry {
+    formats: Vec<Format>,
+}
+
+/// Registry of the built-in formats; `xlsx` and `parquet` are included
+/// when their features are enabled
+pub fn registry() -> ExporterRegistry {
+    let formats = vec![
+        Format {
+            name: "json",
+            extensions: &["json"],
+            content_type: "application/json",
+            create: |_| Box::new(JsonExporter),
+        },
+        Format {
+            name: "csv",
+            extensions: &["csv"],
+            content_type: "text/csv",
+            create: |_| Box::new(CsvExporter::default()),
+        },
+        Format {
+            name: "md",
+            extensions: &["md", "markdown"],
+            content_type: "text/markdown",
+            create: |_| Box::new(MarkdownExporter::default()),
+        },
+        Format {
+            name: "html",
+            extensions: &["html", "htm"],
+            content_type: "text/html",
+            create: |_| Box::new(HtmlTreeExporter::default()),
+        },
+        Format {
+            name: "dashboard",
+            extensions: &[],
+            content_type: "text/html",
+            create: |source| {
+                let exporter = DashboardExporter::default();
+                Box::new(match source {
+                    Some(source) => exporter.with_title(format!("Analysis of {}", source)),
+                    None => exporter,
+                })
+            },
+        },
+        Format {
+            name: "cloud",
+            extensions: &[],
+            content_type: "text/html",
+            create: |source| {
+                let exporter = TagCloudExporter::new(TagCloudLayout::Cloud);
+                Box::new(match source {
+                    Some(source) => exporter.with_title(format!("Tag frequencies of {}", source)),
+                    None => exporter,
+                })
+            },
+        },
+        Format {
+            name: "treemap",
+            extensions: &[],
+            content_type: "text/html",
+            create: |source| {
+                let exporter = TagCloudExporter::new(TagCloudLayout::Treemap);
+                Box::new(match source {
+                    Some(source) => exporter.with_title(format!("Tag frequencies of {}", source)),
+                    None => exporter,
+                })
+            },
+        },
+        Format {
+            name: "graph",
+            extensions: &[],
+            content_type: "text/html",
+            create: |_| Box::new(GraphVisualizerExporter),
+        },
+        Format {
+            name: "svg",
+            extensions: &["svg"],
+            content_type: "image/svg+xml",
+            create: |_| Box::new(SvgExporter::default()),
+        },
+        Format {
+            name: "dot",
+            extensions: &["dot", "gv"],
+            content_type: "text/vnd.graphviz",
+            create: |_| Box::new(DotExporter::default()),
+        },
+        Format {
+            name: "prometheus",
+            extensions: &["prom"],
+            content_type: "text/plain; version=0.0.4",
+            create: |source| {
+                let exporter = PrometheusExporter::new();
+                Box::new(match source {
+                    Some(source) => exporter.source(source),
+                    None => exporter,
+                })
+            },
+        },
+        Format {
+            name: "influx",
+            extensions: &["lp"],
+            content_type: "text/plain",
+            create: |source| {
+                let exporter = InfluxExporter::new();
+                Box::new(match source {
+                    Some(source) => exporter.source(source),
+                    None => exporter,
+                })
+            },
+        },
+        // Before `elasticsearch`, whose actions aren't one record per line
+        Format {
+            name: "ndjson",
+            extensions: &["ndjson", "jsonl"],
+            content_type: "application/x-ndjson",
+            create: |source| {
+                let exporter = NdjsonExporter::new();
+                Box::new(match source {
+                    Some(source) => exporter.source(source),
+                    None => exporter,
+                })
+            },
+        },
+        Format {
+            name: "elasticsearch",
+            extensions: &["ndjson"],
+            content_type: "application/x-ndjson",
+            create: |source| {
+                let exporter = ElasticsearchBulkExporter::new("ferret");
+                Box::new(match source {
+                    Some(source) => exporter.source(source),
+                    None => exporter,
+                })
+            },
+        },
+        Format {
+            name: "msgpack",
+            extensions: &["msgpack", "mpk"],
+            content_type: "application/msgpack",
+            create: |_| Box::new(MsgpackExporter),
+        },
+        Format {
+            name: "cbor",
+            extensions: &["cbor"],
+            content_type: "application/cbor",
+            create: |_| Box::new(CborExporter),
+        },
+        #[cfg(feature = "xlsx")]
+        Format {
+            name: "xlsx",
+            extensions: &["xlsx"],
+            content_type: "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+            create: |_| Box::new(crate::exporter::XlsxExporter),
+        },
+        #[cfg(feature = "parquet")]
+        Format {
+            name: "parquet",
+            extensions: &["parquet"],
+            content_type: "application/vnd.apache.parquet",
+            create: |source| {
+                let exporter = crate::exporter::ParquetExporter::new("");
+                Box::new(match source {
+                    Some(source) => exporter.source(source),
+                    None => exporter,
+                })
+            },
+        },
+    ];
+    ExporterRegistry { formats }
+}
+
+impl ExporterRegistry {
+    /// Format called `name` (case-insensitive)
+    pub fn get(&self, name: &str) -> Option<Format> {
+        self.formats
+            .iter()
+            .find(|format| format.name.eq_ignore_ascii_case(name))
+            .copied()
+    }
+
+    /// Format implied by the extension of `path`, looking past a `.gz` or
+    /// `.zst` compression suffix (`report.csv.gz` is CSV)
+    pub fn for_path(&self, path: &Path) -> Option<Format> {
+        let path = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("gz" | "zst") => path.file_stem().map(Path::new)?,
+            _ => path,
+        };
+        self.for_extension(path.extension()?.to_str()?)
+    }
+
+    /// Format with the file extension `extension`, without the dot
+    /// (case-insensitive)
+    pub fn for_extension(&self, extension: &str) -> Option<Format> {
+        self.formats
+            .iter()
+            .find(|format| {
+                format
+                    .extensions
+                    .iter()
+                    .any(|known| known.eq_ignore_ascii_case(extension))
+            })
+            .copied()
+    }
+
+    /// First format served as `media_type`, ignoring parameters on either
+    /// side (`text/plain` matches `text/plain; version=0.0.4`)
+    pub fn for_media_type(&self, media_type: &str) -> Option<Format> {
+        let essence = |media_type: &str| {
+            let essence = media_type.split(';').next().unwrap_or_default();
+            essence.trim().to_ascii_lowercase()
+        };
+        let media_type = essence(media_type);
+        self.formats
+            .iter()
+            .find(|format| essence(format.content_type) == media_type)
+            .copied()
+    }
+
+    /// Add a format, replacing a registered one with the same name
+    pub fn register(&mut self, format: Format) {
+        self.formats
+            .retain(|known| !known.name.eq_ignore_ascii_case(format.name));
+        self.formats.push(format);
+    }
+
+    /// Names of all formats, in registration order
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.formats.iter().map(|format| format.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::stream::StreamAnalyzer;
+
+    #[test]
+    fn test_lookup() {
+        let registry = registry();
+        assert_eq!(registry.get("CSV").unwrap().content_type, "text/csv");
+        assert!(registry.get("markdown").is_none());
+
+        let name = |path: &str| registry.for_path(Path::new(path)).map(|format| format.name);
+        assert_eq!(name("out/report.json"), Some("json"));
+        assert_eq!(name("report.HTM"), Some("html"));
+        assert_eq!(name("README.md"), Some("md"));
+        assert_eq!(name("report.csv.gz"), Some("csv"));
+        assert_eq!(name("graph.gv.zst"), Some("dot"));
+        assert_eq!(name("tags.ndjson"), Some("ndjson"));
+        assert_eq!(name("tags.jsonl"), Some("ndjson"));
+        assert_eq!(name("report.gz"), None);
+        assert_eq!(name("report.txt"), None);
+        assert_eq!(name("report"), None);
+
+        assert_eq!(registry.for_extension("MARKDOWN").unwrap().name, "md");
+        let media = |media_type| {
+            registry
+                .for_media_type(media_type)
+                .map(|format| format.name)
+        };
+        assert_eq!(media("Text/CSV; charset=utf-8"), Some("csv"));
+        assert_eq!(media("text/html"), Some("html"));
+        assert_eq!(media("text/plain"), Some("prometheus"));
+        assert_eq!(media("application/x-ndjson"), Some("ndjson"));
+        assert_eq!(media("text/*"), None);
+    }
+
+    #[test]
+    fn test_every_format_exports() {
+        let result = StreamAnalyzer::new(10)
+            .analyze_string(r#"<div class="a"><p>x</p></div>"#)
+            .unwrap();
+        let registry = registry();
+        for name in registry.names() {
+            let format = registry.get(name).unwrap();
+            let mut out = Vec::new();
+            (format.create)(Some("https://example.com/"))
+                .export_to_writer(&result, &mut out)
+                .unwrap();
+            assert!(!out.is_empty(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_register() {
+        let mut registry = registry();
+        let count = registry.names().count();
+        registry.register(Format {
+            name: "json",
+            extensions: &["jsn"],
+            content_type: "application/json",
+            create: |_| Box::new(JsonExporter),
+        });
+        assert_eq!(registry.names().count(), count);
+        assert_eq!(registry.for_path(Path::new("a.jsn")).unwrap().name, "json");
+        assert!(registry.for_path(Path::new("a.json")).is_none());
+    }
+}
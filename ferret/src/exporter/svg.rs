@@ -0,0 +1,86 @@
+use crate::analyzer::AnalysisResult;
+use crate::exporter::chart;
+use crate::exporter::Exporter;
+use anyhow::Result;
+use std::io::Write;
+
+/// Tag counts and the depth histogram as one static SVG image
+///
+/// The image has no scripts or external references, so it survives wikis
+/// and mail clients that strip them. Hovering a bar shows its count where
+/// `<title>` tooltips are supported.
+pub struct SvgExporter {
+    /// Tags shown in the bar chart, most frequent first
+    pub top_tags: usize,
+}
+
+impl Default for SvgExporter {
+    fn default() -> Self {
+        Self { top_tags: 25 }
+    }
+}
+
+impl SvgExporter {
+    pub fn with_top_tags(mut self, top_tags: usize) -> Self {
+        self.top_tags = top_tags;
+        self
+    }
+}
+
+impl Exporter for SvgExporter {
+    fn export_to_writer(&self, result: &AnalysisResult, writer: &mut dyn Write) -> Result<()> {
+        let mut tags: Vec<_> = result.tags.values().collect();
+        tags.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.name.cmp(&b.name)));
+        let bars: Vec<(String, usize)> = tags
+            .iter()
+            .take(self.top_tags)
+            .map(|tag| (tag.name.clone(), tag.count))
+            .collect();
+
+        let charts = [
+            chart::bar_chart(&format!("Top {} tags", bars.len()), &bars),
+            chart::histogram("Elements per depth", "Depth", &result.depth_counts),
+        ];
+        writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(writer, "{}", chart::stack(&charts))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::stream::StreamAnalyzer;
+
+    #[test]
+    fn test_svg() {
+        let result = StreamAnalyzer::new(10)
+            .analyze_string("<ul><li>1</li><li>2</li></ul><p>x</p><br/>")
+            .unwrap();
+        let mut svg = Vec::new();
+        SvgExporter::default()
+            .with_top_tags(2)
+            .export_to_writer(&result, &mut svg)
+            .unwrap();
+        let svg = String::from_utf8(svg).unwrap();
+
+        assert!(svg.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<svg "));
+        assert!(svg.contains("<title>Top 2 tags</title>"));
+        assert!(svg.contains("<title>li: 2</title>"));
+        // `br` and `p` tie; the name breaks it
+        assert!(svg.contains("<title>br: 1</title>"));
+        assert!(!svg.contains("<title>p: 1</title>"));
+        // Depth 1 has ul, p and br
+        assert!(svg.contains("<title>1: 3</title>"));
+        assert!(!svg.contains("<script"));
+
+        // The output is well-formed XML
+        let mut reader = quick_xml::Reader::from_str(&svg);
+        loop {
+            match reader.read_event().unwrap() {
+                quick_xml::events::Event::Eof => break,
+                _ => continue,
+            }
+        }
+    }
+}
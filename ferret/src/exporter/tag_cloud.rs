@@ -0,0 +1,182 @@
+use crate::analyzer::AnalysisResult;
+use crate::exporter::chart::{self, TreemapItem, PALETTE};
+use crate::exporter::Exporter;
+use anyhow::Result;
+use askama::Template;
+use std::io::Write;
+
+/// Font sizes of the rarest and most frequent tag in the cloud, in `em`
+const MIN_SIZE: f64 = 0.8;
+const MAX_SIZE: f64 = 3.2;
+
+/// How [`TagCloudExporter`] lays out tag frequencies
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TagCloudLayout {
+    /// Tag names in alphabetical order, sized by frequency
+    #[default]
+    Cloud,
+    /// Rectangles with areas proportional to frequency
+    Treemap,
+}
+
+struct Word {
+    name: String,
+    count: usize,
+    size: String,
+    color: &'static str,
+}
+
+#[derive(Template)]
+#[template(path = "tag_cloud.html")]
+struct TagCloudTemplate<'a> {
+    title: &'a str,
+    words: Vec<Word>,
+    treemap: Option<String>,
+}
+
+/// Standalone HTML page showing which tags dominate a page at a glance
+///
+/// Font sizes in the cloud grow with the logarithm of the count, so a
+/// handful of very frequent tags does not shrink the rest to nothing.
+/// Like [`DashboardExporter`](crate::exporter::DashboardExporter) the page
+/// has no scripts and loads nothing from the network.
+pub struct TagCloudExporter {
+    pub layout: TagCloudLayout,
+    /// Page heading and title
+    pub title: String,
+    /// Most frequent tags shown; the rest are left out
+    pub max_tags: usize,
+}
+
+impl Default for TagCloudExporter {
+    fn default() -> Self {
+        Self {
+            layout: TagCloudLayout::default(),
+            title: "Tag frequencies".to_string(),
+            max_tags: 100,
+        }
+    }
+}
+
+impl TagCloudExporter {
+    pub fn new(layout: TagCloudLayout) -> Self {
+        Self {
+            layout,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_title(mut self, title: impl Into<String>) -> Self {
+        self.title = title.into();
+        self
+    }
+
+    pub fn with_max_tags(mut self, max_tags: usize) -> Self {
+        self.max_tags = max_tags;
+        self
+    }
+}
+
+impl Exporter for TagCloudExporter {
+    fn export_to_writer(&self, result: &AnalysisResult, writer: &mut dyn Write) -> Result<()> {
+        let mut tags: Vec<_> = result.tags.values().filter(|tag| tag.count > 0).collect();
+        tags.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.name.cmp(&b.name)));
+        tags.truncate(self.max_tags);
+
+        let (words, treemap) = match self.layout {
+            TagCloudLayout::Cloud => {
+                let max = tags.first().map_or(1, |tag| tag.count) as f64;
+                let min = tags.last().map_or(1, |tag| tag.count) as f64;
+                let mut words: Vec<Word> = tags
+                    .iter()
+                    .enumerate()
+                    .map(|(rank, tag)| Word {
+                        name: tag.name.clone(),
+                        count: tag.count,
+                        size: format!("{:.2}", font_size(tag.count as f64, min, max)),
+                        color: PALETTE[rank % PALETTE.len()],
+                    })
+                    .collect();
+                words.sort_by(|a, b| a.name.cmp(&b.name));
+                (words, None)
+            }
+            TagCloudLayout::Treemap => {
+                let cells: Vec<TreemapItem> = tags
+                    .iter()
+                    .map(|tag| TreemapItem {
+                        label: tag.name.clone(),
+                        group: tag.name.clone(),
+                        value: tag.count,
+                    })
+                    .collect();
+                let svg = chart::treemap(&format!("Top {} tags", cells.len()), &cells).svg;
+                (Vec::new(), Some(svg))
+            }
+        };
+
+        let template = TagCloudTemplate {
+            title: &self.title,
+            words,
+            treemap,
+        };
+        template.write_into(writer)?;
+        Ok(())
+    }
+}
+
+/// Font size for `count` on a log scale between `min` and `max`
+fn font_size(count: f64, min: f64, max: f64) -> f64 {
+    if max <= min {
+        return (MIN_SIZE + MAX_SIZE) / 2.0;
+    }
+    let t = (count.ln() - min.ln()) / (max.ln() - min.ln());
+    MIN_SIZE + t * (MAX_SIZE - MIN_SIZE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::stream::StreamAnalyzer;
+
+    fn render(exporter: TagCloudExporter) -> String {
+        let result = StreamAnalyzer::new(10)
+            .analyze_string("<ul><li>1</li><li>2</li><li>3</li><li>4</li></ul><p>x</p>")
+            .unwrap();
+        let mut html = Vec::new();
+        exporter.export_to_writer(&result, &mut html).unwrap();
+        String::from_utf8(html).unwrap()
+    }
+
+    #[test]
+    fn test_cloud() {
+        let html = render(TagCloudExporter::default().with_title("Tags <of> page"));
+        assert!(html.contains("<h1>Tags &lt;of&gt; page</h1>"));
+        // Alphabetical, largest font for the most frequent tag
+        let li = html.find(">li<").unwrap();
+        let p = html.find(">p<").unwrap();
+        let ul = html.find(">ul<").unwrap();
+        assert!(li < p && p < ul);
+        assert!(html.contains(&format!("font-size: {:.2}em", MAX_SIZE)));
+        assert!(html.contains(&format!("font-size: {:.2}em", MIN_SIZE)));
+        assert!(!html.contains("<svg"));
+        assert!(!html.contains("<script"));
+    }
+
+    #[test]
+    fn test_treemap() {
+        let html = render(TagCloudExporter::new(TagCloudLayout::Treemap).with_max_tags(2));
+        assert_eq!(html.matches("<svg").count(), 1);
+        assert!(html.contains("<title>li: 4</title>"));
+        // `p` and `ul` tie for second place; names break the tie
+        assert!(html.contains("<title>p: 1</title>"));
+        assert!(!html.contains("<title>ul: 1</title>"));
+    }
+
+    #[test]
+    fn test_font_size() {
+        assert_eq!(font_size(1.0, 1.0, 100.0), MIN_SIZE);
+        assert_eq!(font_size(100.0, 1.0, 100.0), MAX_SIZE);
+        assert!((font_size(10.0, 1.0, 100.0) - (MIN_SIZE + MAX_SIZE) / 2.0).abs() < 1e-9);
+        assert_eq!(font_size(5.0, 5.0, 5.0), (MIN_SIZE + MAX_SIZE) / 2.0);
+    }
+}
@@ -0,0 +1,193 @@
+use crate::analyzer::AnalysisResult;
+use crate::exporter::Exporter;
+use anyhow::Result;
+use rust_xlsxwriter::{Format, Workbook, Worksheet};
+use std::io::Write;
+
+/// Longest string a worksheet cell can hold
+const MAX_CELL_CHARS: usize = 32_767;
+
+/// Excel workbook with one sheet each for tags, attributes and values
+///
+/// Unlike the flattened CSV, every sheet has one row per item of its level,
+/// so counts can be summed and pivoted directly. Header rows are frozen and
+/// filterable, counts use thousands separators and shares are percentages.
+/// Rows are sorted by descending count.
+///
+/// Requires the `xlsx` feature.
+pub struct XlsxExporter;
+
+impl Exporter for XlsxExporter {
+    fn export_to_writer(&self, result: &AnalysisResult, writer: &mut dyn Write) -> Result<()> {
+        let formats = Formats::new();
+        let mut workbook = Workbook::new();
+
+        let mut tags: Vec<_> = result.tags.values().collect();
+        tags.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.name.cmp(&b.name)));
+        let total: usize = tags.iter().map(|tag| tag.count).sum();
+
+        let sheet = workbook.add_worksheet().set_name("Tags")?;
+        write_header(sheet, &formats, &["Tag", "Count", "Share", "Attributes"])?;
+        for (index, tag) in tags.iter().enumerate() {
+            let row = index as u32 + 1;
+            sheet.write_string(row, 0, cell(&tag.name))?;
+            sheet.write_number_with_format(row, 1, tag.count as f64, &formats.count)?;
+            sheet.write_number_with_format(row, 2, share(tag.count, total), &formats.share)?;
+            sheet.write_number_with_format(row, 3, tag.attributes.len() as f64, &formats.count)?;
+        }
+        finish_sheet(sheet, tags.len(), &[20.0, 12.0, 10.0, 12.0])?;
+
+        let mut attributes: Vec<_> = tags
+            .iter()
+            .flat_map(|tag| tag.attributes.values().map(move |attr| (*tag, attr)))
+            .collect();
+        attributes.sort_by(|a, b| {
+            b.1.count
+                .cmp(&a.1.count)
+                .then_with(|| (&a.0.name, &a.1.name).cmp(&(&b.0.name, &b.1.name)))
+        });
+
+        let sheet = workbook.add_worksheet().set_name("Attributes")?;
+        write_header(
+            sheet,
+            &formats,
+            &["Tag", "Attribute", "Count", "Coverage", "Distinct values"],
+        )?;
+        for (index, (tag, attr)) in attributes.iter().enumerate() {
+            let row = index as u32 + 1;
+            sheet.write_string(row, 0, cell(&tag.name))?;
+            sheet.write_string(row, 1, cell(&attr.name))?;
+            sheet.write_number_with_format(row, 2, attr.count as f64, &formats.count)?;
+            // Share of the tag's elements that carry the attribute
+            sheet.write_number_with_format(row, 3, share(attr.count, tag.count), &formats.share)?;
+            sheet.write_number_with_format(
+                row,
+                4,
+                attr.value_counts.len() as f64,
+                &formats.count,
+            )?;
+        }
+        finish_sheet(sheet, attributes.len(), &[20.0, 20.0, 12.0, 10.0, 16.0])?;
+
+        let mut values: Vec<_> = attributes
+            .iter()
+            .flat_map(|&(tag, attr)| {
+                attr.value_counts
+                    .iter()
+                    .map(move |(value, count)| (tag, attr, value, *count))
+            })
+            .collect();
+        values.sort_by(|a, b| {
+            b.3.cmp(&a.3)
+                .then_with(|| (&a.0.name, &a.1.name, a.2).cmp(&(&b.0.name, &b.1.name, b.2)))
+        });
+
+        let sheet = workbook.add_worksheet().set_name("Values")?;
+        write_header(
+            sheet,
+            &formats,
+            &["Tag", "Attribute", "Value", "Count", "Share"],
+        )?;
+        for (index, (tag, attr, value, count)) in values.iter().enumerate() {
+            let row = index as u32 + 1;
+            sheet.write_string(row, 0, cell(&tag.name))?;
+            sheet.write_string(row, 1, cell(&attr.name))?;
+            sheet.write_string(row, 2, cell(value))?;
+            sheet.write_number_with_format(row, 3, *count as f64, &formats.count)?;
+            // Share of the attribute's occurrences with this value
+            sheet.write_number_with_format(row, 4, share(*count, attr.count), &formats.share)?;
+        }
+        finish_sheet(sheet, values.len(), &[20.0, 20.0, 40.0, 12.0, 10.0])?;
+
+        writer.write_all(&workbook.save_to_buffer()?)?;
+        Ok(())
+    }
+}
+
+struct Formats {
+    header: Format,
+    count: Format,
+    share: Format,
+}
+
+impl Formats {
+    fn new() -> Self {
+        Self {
+            header: Format::new().set_bold(),
+            count: Format::new().set_num_format("#,##0"),
+            share: Format::new().set_num_format("0.0%"),
+        }
+    }
+}
+
+fn write_header(sheet: &mut Worksheet, formats: &Formats, titles: &[&str]) -> Result<()> {
+    for (col, title) in titles.iter().enumerate() {
+        sheet.write_string_with_format(0, col as u16, *title, &formats.header)?;
+    }
+    Ok(())
+}
+
+/// Freeze the header row, add filters and set column widths
+fn finish_sheet(sheet: &mut Worksheet, rows: usize, widths: &[f64]) -> Result<()> {
+    sheet.set_freeze_panes(1, 0)?;
+    sheet.autofilter(0, 0, rows as u32, widths.len() as u16 - 1)?;
+    for (col, width) in widths.iter().enumerate() {
+        sheet.set_column_width(col as u16, *width)?;
+    }
+    Ok(())
+}
+
+fn share(part: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        part as f64 / total as f64
+    }
+}
+
+/// `text` cut to what a cell can hold
+fn cell(text: &str) -> &str {
+    match text.char_indices().nth(MAX_CELL_CHARS) {
+        Some((end, _)) => &text[..end],
+        None => text,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::stream::StreamAnalyzer;
+
+    #[test]
+    fn test_workbook() {
+        let result = StreamAnalyzer::new(10)
+            .analyze_string(r#"<ul class="menu"><li class="item">a</li><li>b</li></ul>"#)
+            .unwrap();
+        let mut buffer = Vec::new();
+        XlsxExporter.export_to_writer(&result, &mut buffer).unwrap();
+
+        // An xlsx file is a zip archive with one part per sheet
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(buffer)).unwrap();
+        for sheet in 1..=3 {
+            assert!(archive
+                .by_name(&format!("xl/worksheets/sheet{}.xml", sheet))
+                .is_ok());
+        }
+        let mut workbook = String::new();
+        std::io::Read::read_to_string(
+            &mut archive.by_name("xl/workbook.xml").unwrap(),
+            &mut workbook,
+        )
+        .unwrap();
+        for name in ["Tags", "Attributes", "Values"] {
+            assert!(workbook.contains(&format!("name=\"{}\"", name)), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_cell_length() {
+        let long = "é".repeat(MAX_CELL_CHARS + 10);
+        assert_eq!(cell(&long).chars().count(), MAX_CELL_CHARS);
+        assert_eq!(cell("short"), "short");
+    }
+}
@@ -0,0 +1,281 @@
+//! Structured data extracted from parsed pages
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::io::Write;
+use tl::{HTMLTag, NodeHandle, Parser, VDom};
+
+/// Largest `colspan` browsers honor
+const MAX_COLSPAN: usize = 1000;
+/// Largest `rowspan` browsers honor
+const MAX_ROWSPAN: usize = 65534;
+
+/// A `<table>` as a grid of cell texts
+///
+/// Cells spanning several columns or rows have their text repeated in
+/// every position they cover, so each row has one value per column.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Table {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub caption: Option<String>,
+    /// Column names from the `<thead>` rows or, without one, the leading
+    /// rows of only `<th>` cells; several header rows are joined with
+    /// spaces, e.g. `Sales Q1`. Empty when the table has no header.
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    /// Number of columns
+    pub fn width(&self) -> usize {
+        self.rows
+            .iter()
+            .map(Vec::len)
+            .chain([self.headers.len()])
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Write the table as CSV, with the headers as first record if there
+    /// are any
+    pub fn write_csv<W: Write>(&self, writer: W) -> Result<()> {
+        let mut wtr = csv::Writer::from_writer(writer);
+        if !self.headers.is_empty() {
+            wtr.write_record(&self.headers)?;
+        }
+        for row in &self.rows {
+            wtr.write_record(row)?;
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+
+    /// The table as CSV text
+    ///
+    /// # Example
+    /// ```
+    /// use ferret::parser::FerretParser;
+    ///
+    /// let vdom = FerretParser::parse(
+    ///     "<table><tr><th>Name</th><th>Age</th></tr><tr><td>Ada</td><td>36</td></tr></table>",
+    /// )?;
+    /// let tables = ferret::extract::tables(&vdom);
+    /// assert_eq!(tables[0].to_csv()?, "Name,Age\nAda,36\n");
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn to_csv(&self) -> Result<String> {
+        let mut csv = Vec::new();
+        self.write_csv(&mut csv)?;
+        Ok(String::from_utf8(csv)?)
+    }
+}
+
+/// Every `<table>` of `vdom` in document order
+///
+/// Nested tables are extracted on their own and appear as text in the
+/// cell containing them. Rows are taken from the table itself and its
+/// `<thead>`, `<tbody>` and `<tfoot>` sections, in document order.
+pub fn tables(vdom: &VDom) -> Vec<Table> {
+    let parser = vdom.parser();
+    vdom.nodes()
+        .iter()
+        .filter_map(|node| node.as_tag())
+        .filter(|tag| is(tag, "table"))
+        .map(|tag| table(tag, parser))
+        .collect()
+}
+
+/// Whether `tag` is the element `name`
+fn is(tag: &HTMLTag, name: &str) -> bool {
+    tag.name().as_utf8_str().eq_ignore_ascii_case(name)
+}
+
+fn child_tags<'p, 'a>(tag: &HTMLTag<'a>, parser: &'p Parser<'a>) -> Vec<&'p HTMLTag<'a>> {
+    tag.children()
+        .top()
+        .iter()
+        .filter_map(|handle: &NodeHandle| handle.get(parser)?.as_tag())
+        .collect()
+}
+
+fn attribute<'t>(tag: &'t HTMLTag, name: &str) -> Option<Cow<'t, str>> {
+    tag.attributes()
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .and_then(|(_, value)| value)
+}
+
+/// A span attribute, which browsers read as 1 when missing or invalid
+fn span(tag: &HTMLTag, name: &str, max: usize) -> usize {
+    attribute(tag, name)
+        .and_then(|value| value.trim().parse::<usize>().ok())
+        .filter(|&span| span > 0)
+        .unwrap_or(1)
+        .min(max)
+}
+
+/// Text of `tag` with entities decoded and whitespace collapsed
+fn text(tag: &HTMLTag, parser: &Parser) -> String {
+    let raw = tag.inner_text(parser);
+    let raw = raw.replace("&nbsp;", " ");
+    let decoded = quick_xml::escape::unescape(&raw).map_or(Cow::Borrowed(raw.as_str()), |text| {
+        Cow::Owned(text.into_owned())
+    });
+    decoded.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn table(tag: &HTMLTag, parser: &Parser) -> Table {
+    // Rows with whether each is a header row
+    let mut rows: Vec<(&HTMLTag, bool)> = Vec::new();
+    let mut caption = None;
+    for child in child_tags(tag, parser) {
+        if is(child, "tr") {
+            rows.push((child, false));
+        } else if is(child, "thead") || is(child, "tbody") || is(child, "tfoot") {
+            let head = is(child, "thead");
+            rows.extend(
+                child_tags(child, parser)
+                    .into_iter()
+                    .filter(|row| is(row, "tr"))
+                    .map(|row| (row, head)),
+            );
+        } else if is(child, "caption") && caption.is_none() {
+            caption = Some(text(child, parser)).filter(|caption| !caption.is_empty());
+        }
+    }
+
+    // Without a <thead>, leading rows of only <th> cells are the header
+    if !rows.iter().any(|&(_, head)| head) {
+        for (row, head) in &mut rows {
+            let mut cells = child_tags(row, parser)
+                .into_iter()
+                .filter(|cell| is(cell, "td") || is(cell, "th"))
+                .peekable();
+            if cells.peek().is_none() || !cells.all(|cell| is(cell, "th")) {
+                break;
+            }
+            *head = true;
+        }
+    }
+
+    let mut grid: Vec<Vec<Option<String>>> = vec![Vec::new(); rows.len()];
+    for (index, (row, _)) in rows.iter().enumerate() {
+        let mut column = 0;
+        for cell in child_tags(row, parser)
+            .into_iter()
+            .filter(|cell| is(cell, "td") || is(cell, "th"))
+        {
+            while grid[index].get(column).is_some_and(Option::is_some) {
+                column += 1;
+            }
+            let colspan = span(cell, "colspan", MAX_COLSPAN);
+            let rowspan = span(cell, "rowspan", MAX_ROWSPAN).min(rows.len() - index);
+            let value = text(cell, parser);
+            for spanned in &mut grid[index..index + rowspan] {
+                if spanned.len() < column + colspan {
+                    spanned.resize(column + colspan, None);
+                }
+                for slot in &mut spanned[column..column + colspan] {
+                    *slot = Some(value.clone());
+                }
+            }
+            column += colspan;
+        }
+    }
+
+    let width = grid.iter().map(Vec::len).max().unwrap_or(0);
+    let mut headers: Vec<String> = Vec::new();
+    let mut body = Vec::new();
+    for ((_, head), cells) in rows.iter().zip(grid) {
+        let mut cells: Vec<String> = cells.into_iter().map(Option::unwrap_or_default).collect();
+        cells.resize(width, String::new());
+        if !head {
+            body.push(cells);
+            continue;
+        }
+        headers.resize(width, String::new());
+        for (header, cell) in headers.iter_mut().zip(cells) {
+            // A cell spanning header rows names its column once
+            if cell.is_empty() || *header == cell || header.ends_with(&format!(" {}", cell)) {
+                continue;
+            }
+            if !header.is_empty() {
+                header.push(' ');
+            }
+            header.push_str(&cell);
+        }
+    }
+
+    Table {
+        id: attribute(tag, "id").map(|id| id.into_owned()),
+        caption,
+        headers,
+        rows: body,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::FerretParser;
+
+    #[test]
+    fn test_tables() {
+        let vdom = FerretParser::parse(
+            r#"<TABLE id="sales">
+              <caption> Quarterly   sales </caption>
+              <thead>
+                <tr><th rowspan="2">Region</th><th colspan="2">Sales</th></tr>
+                <tr><th>Q1</th><th>Q2</th></tr>
+              </thead>
+              <tbody>
+                <tr><td rowspan="2">North</td><td>1</td><td>2</td></tr>
+                <tr><td colspan="2">n/a</td></tr>
+                <tr><td>South &amp; West</td><td>3&nbsp;000</td></tr>
+              </tbody>
+            </TABLE>"#,
+        )
+        .unwrap();
+        let tables = tables(&vdom);
+        assert_eq!(
+            tables,
+            [Table {
+                id: Some("sales".to_string()),
+                caption: Some("Quarterly sales".to_string()),
+                headers: vec!["Region".into(), "Sales Q1".into(), "Sales Q2".into()],
+                rows: vec![
+                    vec!["North".into(), "1".into(), "2".into()],
+                    vec!["North".into(), "n/a".into(), "n/a".into()],
+                    vec!["South & West".into(), "3 000".into(), String::new()],
+                ],
+            }]
+        );
+        assert_eq!(tables[0].width(), 3);
+        assert_eq!(
+            tables[0].to_csv().unwrap(),
+            "Region,Sales Q1,Sales Q2\nNorth,1,2\nNorth,n/a,n/a\nSouth & West,3 000,\n"
+        );
+    }
+
+    #[test]
+    fn test_tables_without_header() {
+        let vdom = FerretParser::parse(
+            "<table><tr><th>Key</th><td>Value</td></tr>
+             <tr><td>Inner</td><td><table><tr><td>x</td></tr></table></td></tr></table>",
+        )
+        .unwrap();
+        let tables = tables(&vdom);
+        assert_eq!(tables.len(), 2);
+        assert!(tables[0].headers.is_empty());
+        assert_eq!(
+            tables[0].rows,
+            [["Key", "Value"], ["Inner", "x"]].map(|row| row.map(String::from).to_vec())
+        );
+        assert_eq!(tables[1].rows, [["x".to_string()]]);
+        assert_eq!(tables[1].to_csv().unwrap(), "x\n");
+        assert!(super::tables(&FerretParser::parse("<p>No tables</p>").unwrap()).is_empty());
+    }
+}
@@ -0,0 +1,1000 @@
+use crate::error::FerretError;
+use crate::robots::RobotsPolicy;
+use anyhow::{Context, Result};
+use hyper::client::connect::dns::Name;
+use reqwest::cookie::CookieStore;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use reqwest::header::{
+    HeaderName, HeaderValue, AUTHORIZATION, COOKIE, LOCATION, RETRY_AFTER, USER_AGENT,
+};
+use reqwest::redirect::Policy;
+use reqwest::{Client, Method, RequestBuilder, Response, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// User-Agent sent when none is configured
+pub const DEFAULT_USER_AGENT: &str = concat!("ferret/", env!("CARGO_PKG_VERSION"));
+
+/// Redirects followed unless configured otherwise
+pub const DEFAULT_MAX_REDIRECTS: usize = 10;
+
+/// A redirect response encountered while fetching a URL
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RedirectHop {
+    /// URL that answered with a redirect
+    pub url: String,
+    pub status: u16,
+    /// Absolute URL the redirect pointed to
+    pub location: String,
+}
+
+/// A response together with the redirects that led to it
+#[derive(Debug)]
+pub struct Fetched {
+    pub response: Response,
+    pub redirects: Vec<RedirectHop>,
+}
+
+/// Credentials attached to every request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Auth {
+    Basic {
+        username: String,
+        password: Option<String>,
+    },
+    Bearer(String),
+}
+
+/// Cookie store shared between requests
+///
+/// Cookies set by responses (including redirects) are stored and sent back
+/// on later requests to matching URLs, so a session survives across
+/// `analyze_url` calls. Clones share the same underlying store.
+///
+/// # Example
+/// ```
+/// use ferret::fetch::{CookieJar, FetchOptions};
+///
+/// let jar = CookieJar::new();
+/// jar.add("https://example.com/", "session=abc; Path=/")?;
+/// assert_eq!(jar.cookies("https://example.com/page").as_deref(), Some("session=abc"));
+///
+/// let options = FetchOptions::default().cookie_jar(jar.clone());
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CookieJar(Arc<reqwest::cookie::Jar>);
+
+impl CookieJar {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pre-seed a cookie given in `Set-Cookie` syntax, scoped to `url`
+    pub fn add(&self, url: &str, cookie: &str) -> Result<()> {
+        self.0.add_cookie_str(cookie, &Url::parse(url)?);
+        Ok(())
+    }
+
+    /// The `Cookie` header that would be sent to `url`
+    pub fn cookies(&self, url: &str) -> Option<String> {
+        let url = Url::parse(url).ok()?;
+        let header = self.0.cookies(&url)?;
+        header.to_str().ok().map(str::to_string)
+    }
+}
+
+/// Minimum delay between requests to the same host
+///
+/// Requests are queued per host, so concurrent fetches from many analyzers
+/// sharing one throttle still hit each host at most once per `delay`.
+/// Clones share the same schedule.
+///
+/// # Example
+/// ```
+/// use ferret::fetch::{FetchOptions, HostThrottle};
+/// use std::time::Duration;
+///
+/// let options = FetchOptions::default().throttle(HostThrottle::new(Duration::from_secs(1)));
+/// ```
+#[derive(Debug, Clone)]
+pub struct HostThrottle {
+    delay: Duration,
+    next_request: Arc<Mutex<HashMap<String, Instant>>>,
+}
+
+impl HostThrottle {
+    pub fn new(delay: Duration) -> Self {
+        Self {
+            delay,
+            next_request: Arc::default(),
+        }
+    }
+
+    pub fn delay(&self) -> Duration {
+        self.delay
+    }
+
+    /// Wait until a request to the host of `url` is allowed
+    pub async fn wait(&self, url: &Url) {
+        let host = url.host_str().unwrap_or_default().to_string();
+        let slot = {
+            let mut next_request = self.next_request.lock().unwrap();
+            let now = Instant::now();
+            let slot = next_request.get(&host).map_or(now, |next| (*next).max(now));
+            next_request.insert(host, slot + self.delay);
+            slot
+        };
+        tokio::time::sleep_until(slot).await;
+    }
+}
+
+/// Forward proxy that all requests are sent through
+///
+/// Supports `http://`, `https://`, `socks5://` and `socks5h://` proxy URLs
+/// (`socks5h` resolves hostnames on the proxy). Credentials can be embedded
+/// in the URL or set with `basic_auth()`.
+///
+/// # Example
+/// ```
+/// use ferret::fetch::{FetchOptions, ProxyConfig};
+///
+/// let options = FetchOptions::default()
+///     .proxy(ProxyConfig::new("socks5h://127.0.0.1:1080").basic_auth("user", "secret"));
+/// assert!(options.build_client().is_ok());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProxyConfig {
+    pub url: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+impl ProxyConfig {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            username: None,
+            password: None,
+        }
+    }
+
+    pub fn basic_auth(mut self, username: impl Into<String>, password: impl Into<String>) -> Self {
+        self.username = Some(username.into());
+        self.password = Some(password.into());
+        self
+    }
+
+    fn to_proxy(&self) -> Result<reqwest::Proxy> {
+        let mut url =
+            Url::parse(&self.url).with_context(|| format!("Invalid proxy URL: {}", self.url))?;
+        let Some(username) = &self.username else {
+            return Ok(reqwest::Proxy::all(url)?);
+        };
+        let password = self.password.as_deref().unwrap_or("");
+
+        if url.scheme().starts_with("socks") {
+            // SOCKS credentials are only read from the proxy URL
+            url.set_username(username)
+                .and_then(|_| url.set_password(Some(password)))
+                .map_err(|_| anyhow::anyhow!("Invalid proxy URL: {}", self.url))?;
+            Ok(reqwest::Proxy::all(url)?)
+        } else {
+            Ok(reqwest::Proxy::all(url)?.basic_auth(username, password))
+        }
+    }
+}
+
+/// Hosts that requests may go to, checked before every request including
+/// redirects
+///
+/// By default any host is allowed as long as none of its addresses is
+/// loopback, private, link-local or otherwise not publicly routable, so a
+/// server fetching URLs for its clients can't be made to reach its own
+/// network, e.g. `http://169.254.169.254/`. Host names are resolved for the
+/// check; clients built by [`FetchOptions::build_client`] with a policy
+/// refuse such addresses again when connecting, so a host can't resolve to
+/// a public address for the check and a private one for the request (DNS
+/// rebinding). Behind a proxy, which resolves the host itself, only the
+/// check applies.
+///
+/// # Example
+/// ```
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// use ferret::fetch::HostPolicy;
+///
+/// let policy = HostPolicy {
+///     denied: vec!["internal.example.com".to_string()],
+///     ..HostPolicy::default()
+/// };
+/// assert!(policy.check(&"http://127.0.0.1:8080/".parse().unwrap()).await.is_err());
+/// assert!(policy.check(&"https://api.internal.example.com/".parse().unwrap()).await.is_err());
+/// # }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HostPolicy {
+    /// Hosts allowed, with their subdomains; empty allows any host
+    pub allowed: Vec<String>,
+    /// Hosts refused, with their subdomains, even if allowed
+    pub denied: Vec<String>,
+    /// Allow addresses that aren't publicly routable
+    pub allow_private: bool,
+}
+
+impl HostPolicy {
+    /// Fail with [`FerretError::HostNotAllowed`] if `url` may not be
+    /// fetched
+    pub async fn check(&self, url: &Url) -> Result<()> {
+        let refuse = |reason: String| -> anyhow::Error {
+            FerretError::HostNotAllowed {
+                url: url.to_string(),
+                reason,
+            }
+            .into()
+        };
+        let Some(host) = url.host_str() else {
+            return Err(refuse("no host".to_string()));
+        };
+        if self.denied.iter().any(|denied| matches_host(host, denied)) {
+            return Err(refuse(format!("{} is denied", host)));
+        }
+        if !self.allowed.is_empty()
+            && !self
+                .allowed
+                .iter()
+                .any(|allowed| matches_host(host, allowed))
+        {
+            return Err(refuse(format!("{} is not in the allowed hosts", host)));
+        }
+        if self.allow_private {
+            return Ok(());
+        }
+
+        // IPv6 hosts are in brackets
+        let literal = host.trim_start_matches('[').trim_end_matches(']');
+        let addresses: Vec<IpAddr> = match literal.parse() {
+            Ok(ip) => vec![ip],
+            Err(_) => tokio::net::lookup_host((host, url.port_or_known_default().unwrap_or(80)))
+                .await
+                .with_context(|| format!("Failed to resolve {}", host))?
+                .map(|addr| addr.ip())
+                .collect(),
+        };
+        match addresses.into_iter().find(|ip| !is_public(*ip)) {
+            Some(ip) => Err(refuse(format!("{} is not a public address", ip))),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Resolver of the clients built for a [`HostPolicy`] not allowing private
+/// addresses, failing for hosts with any address that isn't public
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            let addresses: Vec<SocketAddr> =
+                tokio::net::lookup_host((host.as_str(), 0)).await?.collect();
+            if let Some(addr) = addresses.iter().find(|addr| !is_public(addr.ip())) {
+                let reason = format!("{} resolves to {}, not a public address", host, addr.ip());
+                return Err(reason.into());
+            }
+            Ok(Box::new(addresses.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Whether `ip` is publicly routable
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                || a == 0
+                // Shared address space of carrier-grade NAT
+                || (a == 100 && (64..128).contains(&b))
+                // Benchmarking
+                || (a == 198 && (b & 0xfe) == 18)
+                // Reserved, formerly class E
+                || a >= 240)
+        }
+        IpAddr::V6(ip) => {
+            if let Some(ip) = ip.to_ipv4_mapped() {
+                return is_public(ip.into());
+            }
+            let first = ip.segments()[0];
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                // Unique local
+                || (first & 0xfe00) == 0xfc00
+                // Link-local
+                || (first & 0xffc0) == 0xfe80
+                // Site-local, deprecated
+                || (first & 0xffc0) == 0xfec0
+                // 6to4 and NAT64, which reach IPv4 addresses
+                || first == 0x2002
+                || ip.segments()[..6] == [0x64, 0xff9b, 0, 0, 0, 0])
+        }
+    }
+}
+
+/// How failed requests are retried
+///
+/// Connection failures, timeouts, `429 Too Many Requests` and 5xx responses
+/// are retried with exponential backoff and jitter. A `Retry-After` header
+/// (in seconds) takes precedence over the computed delay, capped at
+/// `max_backoff`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt; `0` disables retrying
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled for every further attempt
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 0,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn retries(max_retries: u32) -> Self {
+        Self {
+            max_retries,
+            ..Self::default()
+        }
+    }
+
+    pub fn is_retryable(status: StatusCode) -> bool {
+        status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+    }
+
+    /// Delay before retry number `attempt` (starting at 0)
+    ///
+    /// Uses "equal jitter": a random delay between half and all of the
+    /// exponential backoff, so concurrent clients don't retry in lockstep.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exponential = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_backoff);
+        let half = exponential / 2;
+        half + half.mul_f64(fastrand::f64())
+    }
+}
+
+/// HTTP settings used when fetching documents for analysis
+///
+/// # Example
+/// ```
+/// use ferret::fetch::FetchOptions;
+///
+/// let options = FetchOptions::default()
+///     .user_agent("Mozilla/5.0 (compatible; ferret)")
+///     .header("Accept-Language", "en")
+///     .bearer_auth("token")
+///     .cookie("consent", "yes");
+/// ```
+#[derive(Debug, Clone)]
+pub struct FetchOptions {
+    /// User-Agent header; `DEFAULT_USER_AGENT` when unset
+    pub user_agent: Option<String>,
+    /// Additional request headers, sent in order
+    pub headers: Vec<(String, String)>,
+    pub auth: Option<Auth>,
+    /// Cookies sent as a single `Cookie` header
+    pub cookies: Vec<(String, String)>,
+    /// Session cookie store; when unset, cookies set by responses are ignored
+    pub cookie_jar: Option<CookieJar>,
+    pub connect_timeout: Option<Duration>,
+    /// Maximum time to wait for the next chunk of the response body
+    pub read_timeout: Option<Duration>,
+    /// Overall limit for a single request, including reading the body
+    pub timeout: Option<Duration>,
+    pub retry: RetryPolicy,
+    /// Redirects to follow; `0` returns the redirect response itself
+    pub max_redirects: usize,
+    /// Check robots.txt before every request and honour its crawl delay
+    pub robots: Option<RobotsPolicy>,
+    /// Space out requests to the same host
+    pub throttle: Option<HostThrottle>,
+    /// Forward proxy; when unset the `HTTP_PROXY`/`HTTPS_PROXY` environment
+    /// variables are honoured
+    pub proxy: Option<ProxyConfig>,
+    /// Hosts requests may go to; any when unset
+    pub host_policy: Option<HostPolicy>,
+    /// Idle connections kept open per host; reqwest's default when unset
+    pub pool_max_idle_per_host: Option<usize>,
+    /// How long an idle connection is kept open
+    pub pool_idle_timeout: Option<Duration>,
+    /// Client returned by [`build_client`](Self::build_client) instead of
+    /// a new one, so connections are reused across fetches; its own
+    /// connect timeout, proxy, cookie store and pool apply
+    pub client: Option<Client>,
+}
+
+impl Default for FetchOptions {
+    fn default() -> Self {
+        Self {
+            user_agent: None,
+            headers: Vec::new(),
+            auth: None,
+            cookies: Vec::new(),
+            cookie_jar: None,
+            connect_timeout: None,
+            read_timeout: None,
+            timeout: None,
+            retry: RetryPolicy::default(),
+            max_redirects: DEFAULT_MAX_REDIRECTS,
+            robots: None,
+            throttle: None,
+            proxy: None,
+            host_policy: None,
+            pool_max_idle_per_host: None,
+            pool_idle_timeout: None,
+            client: None,
+        }
+    }
+}
+
+impl FetchOptions {
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn basic_auth(mut self, username: impl Into<String>, password: Option<String>) -> Self {
+        self.auth = Some(Auth::Basic {
+            username: username.into(),
+            password,
+        });
+        self
+    }
+
+    pub fn bearer_auth(mut self, token: impl Into<String>) -> Self {
+        self.auth = Some(Auth::Bearer(token.into()));
+        self
+    }
+
+    pub fn cookie(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.cookies.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn cookie_jar(mut self, jar: CookieJar) -> Self {
+        self.cookie_jar = Some(jar);
+        self
+    }
+
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    pub fn read_timeout(mut self, timeout: Duration) -> Self {
+        self.read_timeout = Some(timeout);
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn max_redirects(mut self, max_redirects: usize) -> Self {
+        self.max_redirects = max_redirects;
+        self
+    }
+
+    pub fn robots(mut self, robots: RobotsPolicy) -> Self {
+        self.robots = Some(robots);
+        self
+    }
+
+    pub fn throttle(mut self, throttle: HostThrottle) -> Self {
+        self.throttle = Some(throttle);
+        self
+    }
+
+    pub fn proxy(mut self, proxy: ProxyConfig) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    pub fn host_policy(mut self, policy: HostPolicy) -> Self {
+        self.host_policy = Some(policy);
+        self
+    }
+
+    pub fn pool_max_idle_per_host(mut self, max: usize) -> Self {
+        self.pool_max_idle_per_host = Some(max);
+        self
+    }
+
+    pub fn pool_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.pool_idle_timeout = Some(timeout);
+        self
+    }
+
+    pub fn client(mut self, client: Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Add every `name=value` pair of a raw `Cookie` header string
+    pub fn cookie_header(mut self, raw: &str) -> Self {
+        for pair in raw.split(';') {
+            if let Some((name, value)) = pair.split_once('=') {
+                self.cookies
+                    .push((name.trim().to_string(), value.trim().to_string()));
+            }
+        }
+        self
+    }
+
+    /// Build an HTTP client for these options, or share `self.client`
+    ///
+    /// Redirects are followed by `send()` rather than by the client so that
+    /// every hop can be recorded.
+    pub fn build_client(&self) -> Result<Client> {
+        if let Some(client) = &self.client {
+            return Ok(client.clone());
+        }
+        let mut builder = Client::builder().redirect(Policy::none());
+        if let Some(max) = self.pool_max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max);
+        }
+        if let Some(timeout) = self.pool_idle_timeout {
+            builder = builder.pool_idle_timeout(timeout);
+        }
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(proxy.to_proxy()?);
+        }
+        if let Some(jar) = &self.cookie_jar {
+            builder = builder.cookie_provider(jar.0.clone());
+        }
+        let public_only = self
+            .host_policy
+            .as_ref()
+            .is_some_and(|policy| !policy.allow_private);
+        if public_only && !self.uses_proxy() {
+            builder = builder.dns_resolver(Arc::new(PublicResolver));
+        }
+        Ok(builder.build()?)
+    }
+
+    /// Whether requests go through a proxy, configured or from the
+    /// environment
+    fn uses_proxy(&self) -> bool {
+        self.proxy.is_some()
+            || ["HTTP_PROXY", "HTTPS_PROXY", "ALL_PROXY"]
+                .iter()
+                .any(|var| {
+                    std::env::var_os(var).is_some()
+                        || std::env::var_os(var.to_ascii_lowercase()).is_some()
+                })
+    }
+
+    /// Create a GET request carrying the configured headers, auth and cookies
+    pub fn request(&self, client: &Client, url: &str) -> Result<RequestBuilder> {
+        self.build_request(client, Method::GET, Url::parse(url)?, true)
+    }
+
+    /// Build a request; credentials are only attached when `with_credentials`
+    /// is set, so they don't leak to other origins when following redirects.
+    fn build_request(
+        &self,
+        client: &Client,
+        method: Method,
+        url: Url,
+        with_credentials: bool,
+    ) -> Result<RequestBuilder> {
+        let mut request = client
+            .request(method, url)
+            .header(USER_AGENT, self.user_agent_str());
+        // Also set on the request, for clients shared with other options
+        if let Some(timeout) = self.timeout {
+            request = request.timeout(timeout);
+        }
+
+        for (name, value) in &self.headers {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .with_context(|| format!("Invalid header name: {}", name))?;
+            if !with_credentials && (name == AUTHORIZATION || name == COOKIE) {
+                continue;
+            }
+            let value = HeaderValue::from_str(value)
+                .with_context(|| format!("Invalid value for header {}", name))?;
+            request = request.header(name, value);
+        }
+
+        if !with_credentials {
+            return Ok(request);
+        }
+
+        match &self.auth {
+            Some(Auth::Basic { username, password }) => {
+                request = request.basic_auth(username, password.as_ref());
+            }
+            Some(Auth::Bearer(token)) => {
+                request = request.header(AUTHORIZATION, format!("Bearer {}", token));
+            }
+            None => {}
+        }
+
+        if !self.cookies.is_empty() {
+            let cookie = self
+                .cookies
+                .iter()
+                .map(|(name, value)| format!("{}={}", name, value))
+                .collect::<Vec<_>>()
+                .join("; ");
+            request = request.header(COOKIE, cookie);
+        }
+
+        Ok(request)
+    }
+
+    /// Fail if the host policy or robots.txt forbid requesting `url`, then
+    /// wait for the throttle and the host's crawl delay, as `send` does
+    /// before every request
+    pub async fn admit(&self, client: &Client, url: &Url) -> Result<()> {
+        if let Some(policy) = &self.host_policy {
+            policy.check(url).await?;
+        }
+        if let Some(robots) = &self.robots {
+            robots.check(client, url, self.user_agent_str()).await?;
+        }
+        if let Some(throttle) = &self.throttle {
+            throttle.wait(url).await;
+        }
+        Ok(())
+    }
+
+    fn user_agent_str(&self) -> &str {
+        self.user_agent.as_deref().unwrap_or(DEFAULT_USER_AGENT)
+    }
+
+    /// Fetch a URL with these options
+    ///
+    /// Redirects are followed up to `max_redirects` and returned alongside
+    /// the final response. With a robots policy configured, every hop is
+    /// checked against robots.txt first; with a throttle, every hop waits
+    /// for its host's next slot. Each request is retried according to `self.retry`;
+    /// once retries are exhausted the last response is returned even if its
+    /// status indicates failure, so callers can report it.
+    pub async fn send(&self, client: &Client, url: &str) -> Result<Fetched> {
+        self.send_with_method(client, Method::GET, url).await
+    }
+
+    /// Like [`send`](Self::send) with another method, e.g. `HEAD` to check
+    /// that a URL resolves without downloading it
+    pub async fn send_with_method(
+        &self,
+        client: &Client,
+        method: Method,
+        url: &str,
+    ) -> Result<Fetched> {
+        let origin = Url::parse(url)?;
+        let mut current = origin.clone();
+        let mut redirects = Vec::new();
+
+        loop {
+            self.admit(client, &current).await?;
+            let same_origin = current.origin() == origin.origin();
+            let response = self
+                .send_with_retries(client, &method, &current, same_origin)
+                .await?;
+
+            let location = match response.headers().get(LOCATION) {
+                Some(location) if is_redirect(response.status()) && self.max_redirects > 0 => {
+                    current.join(location.to_str()?)?
+                }
+                _ => {
+                    return Ok(Fetched {
+                        response,
+                        redirects,
+                    })
+                }
+            };
+
+            tracing::debug!("{} redirects to {}", current, location);
+            redirects.push(RedirectHop {
+                url: current.to_string(),
+                status: response.status().as_u16(),
+                location: location.to_string(),
+            });
+            if redirects.len() > self.max_redirects {
+                return Err(FerretError::TooManyRedirects(self.max_redirects).into());
+            }
+            current = location;
+        }
+    }
+
+    async fn send_with_retries(
+        &self,
+        client: &Client,
+        method: &Method,
+        url: &Url,
+        with_credentials: bool,
+    ) -> Result<Response> {
+        let mut attempt = 0;
+        loop {
+            let result = self
+                .build_request(client, method.clone(), url.clone(), with_credentials)?
+                .send()
+                .await;
+            let retry_after = match &result {
+                Ok(response) if RetryPolicy::is_retryable(response.status()) => {
+                    retry_after(response)
+                }
+                Err(err) if err.is_timeout() || err.is_connect() => None,
+                _ => return Ok(result?),
+            };
+
+            if attempt >= self.retry.max_retries {
+                return Ok(result?);
+            }
+            let delay = retry_after
+                .map(|delay| delay.min(self.retry.max_backoff))
+                .unwrap_or_else(|| self.retry.backoff(attempt));
+            tracing::debug!("retrying {} in {:?}", url, delay);
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// Await the next piece of a response body, enforcing `read_timeout`
+    ///
+    /// # Example
+    /// ```no_run
+    /// # async fn run(mut response: reqwest::Response) -> anyhow::Result<()> {
+    /// use ferret::fetch::FetchOptions;
+    /// let options = FetchOptions::default().read_timeout(std::time::Duration::from_secs(5));
+    /// while let Some(chunk) = options.read(response.chunk()).await?? {
+    ///     println!("{} bytes", chunk.len());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn read<T>(&self, next: impl Future<Output = T>) -> Result<T> {
+        match self.read_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, next)
+                .await
+                .map_err(|_| FerretError::ReadTimeout(timeout).into()),
+            None => Ok(next.await),
+        }
+    }
+}
+
+/// Whether `host` is `allowed` or one of its subdomains, ignoring case
+pub fn matches_host(host: &str, allowed: &str) -> bool {
+    let host = host.to_ascii_lowercase();
+    let allowed = allowed.to_ascii_lowercase();
+    host == allowed
+        || host
+            .strip_suffix(&allowed)
+            .is_some_and(|prefix| prefix.ends_with('.'))
+}
+
+fn is_redirect(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::MOVED_PERMANENTLY
+            | StatusCode::FOUND
+            | StatusCode::SEE_OTHER
+            | StatusCode::TEMPORARY_REDIRECT
+            | StatusCode::PERMANENT_REDIRECT
+    )
+}
+
+/// Parse a `Retry-After` header given in seconds
+fn retry_after(response: &Response) -> Option<Duration> {
+    let value = response.headers().get(RETRY_AFTER)?.to_str().ok()?;
+    value.trim().parse().ok().map(Duration::from_secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_headers() {
+        let options = FetchOptions::default()
+            .header("X-Test", "1")
+            .bearer_auth("secret")
+            .cookie_header("a=1; b=2");
+        let client = Client::new();
+        let request = options
+            .request(&client, "https://example.com/")
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let headers = request.headers();
+        assert_eq!(headers[USER_AGENT], DEFAULT_USER_AGENT);
+        assert_eq!(headers["x-test"], "1");
+        assert_eq!(headers[AUTHORIZATION], "Bearer secret");
+        assert_eq!(headers[COOKIE], "a=1; b=2");
+    }
+
+    #[tokio::test]
+    async fn test_host_policy() {
+        let check = |policy: &HostPolicy, url: &str| {
+            let policy = policy.clone();
+            let url = Url::parse(url).unwrap();
+            async move { policy.check(&url).await }
+        };
+        let policy = HostPolicy::default();
+        for url in [
+            "http://127.0.0.1/",
+            "http://localhost:8080/",
+            "http://169.254.169.254/latest/meta-data/",
+            "http://10.1.2.3/",
+            "http://100.64.0.1/",
+            "http://0.0.0.0/",
+            "http://[::1]/",
+            "http://[fd00::1]/",
+            "http://[fe80::1]/",
+            "http://[::ffff:192.168.0.1]/",
+            "http://198.18.0.1/",
+            "http://[fec0::1]/",
+            "http://[2002:a00:1::]/",
+            "http://[64:ff9b::a00:1]/",
+        ] {
+            let err = check(&policy, url).await.unwrap_err();
+            assert!(
+                matches!(
+                    err.downcast_ref::<FerretError>(),
+                    Some(FerretError::HostNotAllowed { .. })
+                ),
+                "{}: {}",
+                url,
+                err
+            );
+        }
+        for url in [
+            "http://93.184.215.14/",
+            "http://198.20.0.1/",
+            "https://[2606:4700::1111]/",
+        ] {
+            assert!(check(&policy, url).await.is_ok(), "{}", url);
+        }
+
+        let policy = HostPolicy {
+            allowed: vec!["example.com".to_string(), "127.0.0.1".to_string()],
+            denied: vec!["private.example.com".to_string()],
+            allow_private: true,
+        };
+        assert!(check(&policy, "http://127.0.0.1:3000/").await.is_ok());
+        let err = check(&policy, "https://a.private.example.com/").await;
+        assert!(err.unwrap_err().to_string().contains("is denied"));
+        let err = check(&policy, "https://example.org/").await;
+        assert!(err
+            .unwrap_err()
+            .to_string()
+            .contains("not in the allowed hosts"));
+    }
+
+    #[tokio::test]
+    async fn test_public_resolver() {
+        // Sent without the check, as if the host had resolved to another
+        // address for it
+        let client = FetchOptions::default()
+            .host_policy(HostPolicy::default())
+            .build_client()
+            .unwrap();
+        let err = client.get("http://localhost:9/").send().await.unwrap_err();
+        let err = format!("{:#}", anyhow::Error::from(err));
+        assert!(err.contains("not a public address"), "{}", err);
+    }
+
+    #[test]
+    fn test_backoff_is_bounded() {
+        let policy = RetryPolicy {
+            max_retries: 10,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(1),
+        };
+        for attempt in 0..10 {
+            let expected =
+                (Duration::from_millis(100) * 2u32.pow(attempt)).min(Duration::from_secs(1));
+            let delay = policy.backoff(attempt);
+            assert!(
+                delay >= expected / 2 && delay <= expected,
+                "attempt {}",
+                attempt
+            );
+        }
+    }
+
+    #[test]
+    fn test_proxy_config() {
+        for url in [
+            "http://127.0.0.1:3128",
+            "socks5://127.0.0.1:1080",
+            "socks5h://127.0.0.1:1080",
+        ] {
+            let options = FetchOptions::default().proxy(ProxyConfig::new(url).basic_auth("u", "p"));
+            assert!(options.build_client().is_ok(), "{}", url);
+        }
+
+        let options = FetchOptions::default().proxy(ProxyConfig::new("not a url"));
+        assert!(options.build_client().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_host_throttle() {
+        let throttle = HostThrottle::new(Duration::from_millis(100));
+        let a = Url::parse("https://a.example/1").unwrap();
+        let b = Url::parse("https://b.example/1").unwrap();
+
+        let start = Instant::now();
+        throttle.wait(&a).await;
+        throttle.wait(&b).await;
+        assert!(start.elapsed() < Duration::from_millis(100));
+
+        throttle.clone().wait(&a).await;
+        assert!(start.elapsed() >= Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_shared_client() {
+        // The shared client's own settings apply, not these
+        let options = FetchOptions::default()
+            .proxy(ProxyConfig::new("not a url"))
+            .timeout(Duration::from_secs(5))
+            .client(Client::new());
+        let client = options.build_client().unwrap();
+        let request = options
+            .request(&client, "https://example.com/")
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(request.timeout(), Some(&Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn test_invalid_header_name() {
+        let options = FetchOptions::default().header("Bad Header", "1");
+        let client = Client::new();
+        assert!(options.request(&client, "https://example.com/").is_err());
+    }
+}
@@ -0,0 +1,63 @@
+//! Escaping for page content placed in generated HTML, SVG and XML
+//!
+//! Tag names, attribute names and values come from the analyzed pages,
+//! which may be hostile, so reports must never interpolate them raw.
+
+use serde::Serialize;
+
+/// Escape text for HTML and XML content and attribute values
+///
+/// # Example
+/// ```
+/// assert_eq!(
+///     ferret::html::escape(r#"<a title="x">"#),
+///     "&lt;a title=&quot;x&quot;&gt;"
+/// );
+/// ```
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Serialize `value` as JSON that is safe inside a `<script>` element
+///
+/// `<`, `>` and `&` are written as `\u` escapes, so a string containing
+/// `</script>` or `<!--` cannot end the element. They only occur inside
+/// JSON strings, where the escapes decode to the same value.
+pub fn script_json<T: Serialize + ?Sized>(value: &T) -> serde_json::Result<String> {
+    let json = serde_json::to_string(value)?;
+    let mut escaped = String::with_capacity(json.len());
+    for c in json.chars() {
+        match c {
+            '<' => escaped.push_str("\\u003c"),
+            '>' => escaped.push_str("\\u003e"),
+            '&' => escaped.push_str("\\u0026"),
+            c => escaped.push(c),
+        }
+    }
+    Ok(escaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_script_json_round_trips() {
+        let values = vec!["</script><script>alert(1)</script>", "<!-- a && b -->"];
+        let json = script_json(&values).unwrap();
+        assert!(!json.contains('<') && !json.contains('>') && !json.contains('&'));
+        let decoded: Vec<String> = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, values);
+    }
+}
@@ -0,0 +1,193 @@
+//! Words of visible text, stopword lists and a small language detector
+//!
+//! Detection counts the most common words of each supported language, which
+//! tells them apart reliably from a few dozen words of running text. It
+//! stands in for a statistical detector like whatlang, which isn't a
+//! dependency, and supports only the eight languages of [`STOPWORDS`]:
+//! English, German, French, Spanish, Italian, Dutch, Portuguese and
+//! Swedish. It returns `None` for other languages as well as for short or
+//! ambiguous text, and has no confidence score. Text in another language
+//! that shares short words with a supported one may be detected as that
+//! language.
+
+/// ISO 639-1 codes of the supported languages with their most common
+/// words, lowercase
+pub const STOPWORDS: &[(&str, &[&str])] = &[
+    (
+        "en",
+        &[
+            "the", "and", "of", "to", "a", "in", "is", "it", "you", "that", "he", "was", "for",
+            "on", "are", "with", "as", "his", "they", "be", "at", "this", "have", "from", "or",
+            "by", "not", "but", "what", "all", "were", "we", "when", "your", "can", "there", "an",
+            "which", "their", "if", "will", "has", "more", "our", "i", "my", "me", "do", "so",
+        ],
+    ),
+    (
+        "de",
+        &[
+            "der", "die", "und", "in", "den", "von", "zu", "das", "mit", "sich", "des", "auf",
+            "für", "ist", "im", "dem", "nicht", "ein", "eine", "als", "auch", "es", "an", "werden",
+            "aus", "er", "hat", "dass", "sie", "nach", "wird", "bei", "einer", "um", "am", "sind",
+            "noch", "wie", "einem", "über", "einen", "so", "zum", "war", "haben", "nur", "oder",
+            "aber", "vor", "zur", "bis", "mehr", "durch", "man", "wir", "ich", "sein",
+        ],
+    ),
+    (
+        "fr",
+        &[
+            "le", "de", "la", "les", "et", "des", "en", "un", "du", "une", "que", "est", "pour",
+            "qui", "dans", "a", "par", "plus", "pas", "au", "sur", "ne", "se", "ce", "il", "sont",
+            "aux", "avec", "ou", "son", "sa", "cette", "nous", "vous", "mais", "ont", "je", "été",
+            "leur", "elle", "ses", "comme", "tout", "bien", "aussi", "être", "fait", "on", "y",
+        ],
+    ),
+    (
+        "es",
+        &[
+            "de", "la", "que", "el", "en", "y", "a", "los", "se", "del", "las", "un", "por", "con",
+            "no", "una", "su", "para", "es", "al", "lo", "como", "más", "pero", "sus", "le", "ya",
+            "o", "fue", "este", "ha", "sí", "porque", "esta", "son", "entre", "cuando", "muy",
+            "sin", "sobre", "ser", "también", "me", "hasta", "hay", "donde", "han", "yo", "nos",
+        ],
+    ),
+    (
+        "it",
+        &[
+            "di", "e", "il", "la", "che", "a", "per", "un", "in", "è", "del", "non", "una",
+            "della", "le", "si", "con", "i", "da", "sono", "al", "gli", "dei", "nel", "alla",
+            "come", "anche", "più", "ma", "ha", "lo", "questo", "delle", "o", "se", "ci", "nella",
+            "suo", "mi", "sua", "tra", "essere", "dal", "molto", "degli", "ho", "hanno", "io",
+            "cui",
+        ],
+    ),
+    (
+        "nl",
+        &[
+            "de", "en", "van", "het", "een", "in", "is", "dat", "op", "te", "zijn", "voor", "met",
+            "die", "niet", "aan", "er", "om", "ook", "als", "bij", "maar", "door", "wordt", "of",
+            "naar", "worden", "uit", "dan", "nog", "tot", "kan", "zo", "over", "was", "ze", "je",
+            "wat", "deze", "hij", "heeft", "meer", "ik", "we", "wij", "hun", "geen", "al", "moet",
+        ],
+    ),
+    (
+        "pt",
+        &[
+            "de", "a", "o", "que", "e", "do", "da", "em", "um", "para", "é", "com", "não", "uma",
+            "os", "no", "se", "na", "por", "mais", "as", "dos", "como", "mas", "foi", "ao", "ele",
+            "das", "tem", "à", "seu", "sua", "ou", "ser", "quando", "muito", "há", "nos", "já",
+            "está", "eu", "também", "só", "pelo", "pela", "até", "isso", "ela", "entre", "são",
+        ],
+    ),
+    (
+        "sv",
+        &[
+            "och", "i", "att", "det", "som", "en", "på", "är", "av", "för", "med", "till", "den",
+            "har", "de", "inte", "om", "ett", "han", "men", "var", "jag", "sig", "från", "vi",
+            "så", "kan", "man", "när", "år", "säger", "hon", "under", "också", "efter", "eller",
+            "nu", "sin", "där", "vid", "mot", "ska", "skulle", "kommer", "ut", "får", "finns",
+        ],
+    ),
+];
+
+/// Fewest words of text a language is detected from
+pub const MIN_DETECTION_WORDS: usize = 10;
+
+/// Common words of the language with ISO 639-1 code `language`, e.g. `de`
+///
+/// Only the primary subtag counts, so `de-AT` finds the German list.
+pub fn stopwords(language: &str) -> Option<&'static [&'static str]> {
+    let primary = primary_subtag(language);
+    STOPWORDS
+        .iter()
+        .find(|(code, _)| code.eq_ignore_ascii_case(primary))
+        .map(|(_, words)| *words)
+}
+
+/// The language part of a language tag: `en` for `en-US` or `en_GB`
+pub fn primary_subtag(tag: &str) -> &str {
+    tag.trim().split(['-', '_']).next().unwrap_or_default()
+}
+
+/// Lowercase words of `text`, split at anything but letters, digits and
+/// apostrophes within words
+///
+/// # Example
+/// ```
+/// use ferret::language::words;
+/// assert_eq!(words("Don't panic — it's 42!").collect::<Vec<_>>(), ["don't", "panic", "it's", "42"]);
+/// ```
+pub fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !(c.is_alphanumeric() || c == '\'' || c == '’'))
+        .map(|word| word.trim_matches(['\'', '’']))
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+}
+
+/// ISO 639-1 code of the language `words` are most likely in
+///
+/// Needs at least [`MIN_DETECTION_WORDS`] words, a few of which must be
+/// common words of a supported language.
+///
+/// # Example
+/// ```
+/// use ferret::language::{detect, words};
+/// let text = "Der schnelle braune Fuchs springt über den faulen Hund, und die Katze schläft auf dem Sofa.";
+/// assert_eq!(detect(words(text)), Some("de"));
+/// assert_eq!(detect(words("Lorem ipsum")), None);
+/// ```
+pub fn detect<I>(words: I) -> Option<&'static str>
+where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+{
+    let mut hits = [0usize; STOPWORDS.len()];
+    let mut total = 0;
+    for word in words {
+        let word = word.as_ref();
+        total += 1;
+        for (hits, (_, stopwords)) in hits.iter_mut().zip(STOPWORDS) {
+            if stopwords.contains(&word) {
+                *hits += 1;
+            }
+        }
+    }
+    let (best, &best_hits) = hits.iter().enumerate().max_by_key(|&(_, hits)| hits)?;
+    // At least one in twenty words should be a common one
+    if total < MIN_DETECTION_WORDS || best_hits < 3 || best_hits * 20 < total {
+        return None;
+    }
+    // A tie means the shared words decided nothing
+    if hits.iter().filter(|&&hits| hits == best_hits).count() > 1 {
+        return None;
+    }
+    Some(STOPWORDS[best].0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        let samples = [
+            ("en", "The quick brown fox jumps over the lazy dog, and the cat is sleeping on the sofa."),
+            ("fr", "Le renard brun rapide saute par-dessus le chien paresseux, et le chat dort sur le canapé."),
+            ("es", "El rápido zorro marrón salta sobre el perro perezoso, y el gato duerme en el sofá."),
+            ("it", "La volpe marrone veloce salta sopra il cane pigro, e il gatto dorme sul divano con la coperta."),
+            ("nl", "De snelle bruine vos springt over de luie hond, en de kat slaapt op de bank."),
+            ("pt", "A rápida raposa marrom pula sobre o cão preguiçoso, e o gato dorme no sofá da sala."),
+            ("sv", "Den snabba bruna räven hoppar över den lata hunden, och katten sover på soffan."),
+        ];
+        for (language, text) in samples {
+            assert_eq!(detect(words(text)), Some(language), "{}", text);
+        }
+        assert_eq!(detect(words("一 二 三 四 五 六 七 八 九 十 十一")), None);
+    }
+
+    #[test]
+    fn test_stopwords() {
+        assert!(stopwords("de-AT").unwrap().contains(&"und"));
+        assert!(stopwords("EN_gb").unwrap().contains(&"the"));
+        assert!(stopwords("ja").is_none());
+    }
+}
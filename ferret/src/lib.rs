@@ -1,6 +1,25 @@
 pub mod analyzer;
+pub mod cache;
+pub mod config;
+pub mod crawler;
+pub mod diff;
+pub mod error;
 pub mod exporter;
-pub mod wasm;
+pub mod extract;
+pub mod fetch;
+pub mod html;
+pub mod language;
+pub mod limits;
+pub mod monitor;
 pub mod parser;
+pub mod progress;
+#[cfg(feature = "rendered")]
+pub mod render;
 pub mod reporter;
+pub mod robots;
+pub mod rules;
+pub mod similarity;
+pub mod sink;
+pub mod sniff;
 pub mod walker;
+pub mod wasm;
@@ -0,0 +1,60 @@
+use crate::error::FerretError;
+use serde::{Deserialize, Serialize};
+
+/// Resource guards applied while parsing untrusted documents.
+///
+/// Every limit is optional; `Limits::default()` imposes no restrictions, which
+/// keeps the behaviour of the analyzers unchanged unless a caller opts in.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Limits {
+    /// Maximum number of input bytes read before aborting
+    pub max_input_bytes: Option<usize>,
+    /// Maximum number of nodes (DOM) or elements (stream) processed
+    pub max_nodes: Option<usize>,
+    /// Maximum nesting depth
+    pub max_depth: Option<usize>,
+    /// Maximum number of parse errors tolerated (stream mode); `Some(0)`
+    /// rejects any malformed markup
+    pub max_parse_errors: Option<usize>,
+}
+
+impl Limits {
+    /// Conservative limits suitable for documents fetched from arbitrary URLs
+    pub fn untrusted() -> Self {
+        Self {
+            max_input_bytes: Some(10 * 1024 * 1024),
+            max_nodes: Some(500_000),
+            max_depth: Some(1024),
+            // Real-world HTML is rarely well-formed
+            max_parse_errors: None,
+        }
+    }
+
+    pub fn check_input(&self, bytes: usize) -> Result<(), FerretError> {
+        match self.max_input_bytes {
+            Some(limit) if bytes > limit => Err(FerretError::InputTooLarge { limit }),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn check_nodes(&self, nodes: usize) -> Result<(), FerretError> {
+        match self.max_nodes {
+            Some(limit) if nodes > limit => Err(FerretError::TooManyNodes { limit }),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn check_depth(&self, depth: usize) -> Result<(), FerretError> {
+        match self.max_depth {
+            Some(limit) if depth > limit => Err(FerretError::TooDeep { limit }),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn check_parse_errors(&self, errors: usize) -> Result<(), FerretError> {
+        match self.max_parse_errors {
+            Some(limit) if errors > limit => Err(FerretError::TooManyParseErrors { limit }),
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,1183 @@
+use anyhow::{Context, Result};
+use clap::{Parser, Subcommand, ValueEnum};
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
+use tracing::Level;
+
+use ferret::analyzer::archive::ArchiveFormat;
+use ferret::analyzer::batch::{glob_files, is_glob};
+use ferret::analyzer::css::{CssReport, StylesheetFetcher};
+use ferret::analyzer::section::Section;
+use ferret::analyzer::stream::StreamAnalyzer;
+use ferret::analyzer::{AnalysisResult, AnalysisResultSet, SourceResult};
+use ferret::config::Config;
+use ferret::crawler::{Crawler, Scope, SitemapSource, UrlNormalizer, TRACKING_PARAMS};
+use ferret::diff::AnalysisDiff;
+use ferret::exporter::{registry, GraphFormat, LinkGraphExporter};
+use ferret::monitor::{FingerprintStore, TemplateStatus, DEFAULT_MIN_COUNT};
+use ferret::progress::{Progress, ProgressEvent};
+use ferret::reporter::{
+    Depth, DiffDisplay, FlatDisplay, HistogramDisplay, MarkdownDisplay, RenderOptions, Reporter,
+    SortKey, SortOrder, SummaryDisplay, TreeDisplay,
+};
+use ferret::rules::{self, Rule};
+use ferret::similarity::TemplateCluster;
+use ferret::sink::Sink;
+use indicatif::{HumanBytes, ProgressBar, ProgressStyle};
+
+#[derive(Parser)]
+#[command(
+    name = "ferret",
+    version,
+    about = "Analyze the structure of HTML and XML documents"
+)]
+struct Cli {
+    /// Settings file; defaults to $FERRET_CONFIG or ./ferret.toml if present
+    #[arg(long, global = true, value_name = "PATH")]
+    config: Option<PathBuf>,
+
+    /// Only log errors and hide the progress spinner
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Log more to stderr: -v for progress, -vv for requests
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Analyze a file, directory, archive, URL or standard input
+    Analyze(AnalyzeArgs),
+    /// Compare the structure of two inputs side by side
+    Diff(DiffArgs),
+    /// Follow links from seed URLs and analyze every page reached
+    Crawl(CrawlArgs),
+    /// Report pages whose template changed since the last run
+    Monitor(MonitorArgs),
+}
+
+#[derive(clap::Args)]
+struct MonitorArgs {
+    /// URLs or files to check; each is stored under the name given here
+    #[arg(required = true)]
+    inputs: Vec<String>,
+
+    /// JSON file keeping the fingerprint of each input between runs
+    #[arg(long, value_name = "PATH", default_value = "ferret-fingerprints.json")]
+    store: PathBuf,
+
+    /// Leave out tags, attributes, classes and nestings occurring fewer
+    /// than N times, so one-off content isn't a template change
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_MIN_COUNT)]
+    min_count: usize,
+
+    /// Number of URLs fetched at the same time
+    #[arg(short, long, default_value_t = 8)]
+    jobs: usize,
+
+    /// Report changes without storing the new fingerprints
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Print every check as JSON instead of one line per input
+    #[arg(long)]
+    json: bool,
+
+    #[cfg(feature = "rendered")]
+    #[command(flatten)]
+    render: RenderArgs,
+}
+
+/// Headless browser options of `analyze` and `monitor`
+#[cfg(feature = "rendered")]
+#[derive(clap::Args)]
+#[command(next_help_heading = "Rendering")]
+struct RenderArgs {
+    /// Render URLs in a headless browser before analyzing them, for pages
+    /// built by JavaScript; needs a running chromedriver or geckodriver
+    #[arg(long)]
+    render: bool,
+
+    /// Wait until an element matches SELECTOR before reading the page
+    #[arg(long, value_name = "SELECTOR", requires = "render")]
+    wait_for: Option<String>,
+
+    /// Seconds to wait for the page and --wait-for
+    #[arg(long, value_name = "SECS", default_value_t = 30, requires = "render")]
+    render_timeout: u64,
+
+    /// Address of the WebDriver server
+    #[arg(long, value_name = "URL", default_value = ferret::render::DEFAULT_WEBDRIVER_URL)]
+    webdriver: String,
+
+    #[arg(long, value_enum, default_value_t = BrowserArg::Chrome)]
+    browser: BrowserArg,
+}
+
+#[cfg(feature = "rendered")]
+#[derive(Clone, Copy, ValueEnum)]
+enum BrowserArg {
+    Chrome,
+    Firefox,
+}
+
+#[cfg(feature = "rendered")]
+impl RenderArgs {
+    /// Make `analyzer` render URLs if --render is set
+    fn apply(&self, analyzer: StreamAnalyzer) -> StreamAnalyzer {
+        use ferret::render::{Browser, BrowserOptions};
+
+        if !self.render {
+            return analyzer;
+        }
+        let mut options = BrowserOptions::default()
+            .with_webdriver_url(&self.webdriver)
+            .with_browser(match self.browser {
+                BrowserArg::Chrome => Browser::Chrome,
+                BrowserArg::Firefox => Browser::Firefox,
+            })
+            .with_timeout(Duration::from_secs(self.render_timeout));
+        if let Some(selector) = &self.wait_for {
+            options = options.with_wait_for(selector);
+        }
+        analyzer.with_render(options)
+    }
+}
+
+#[derive(clap::Args)]
+struct DiffArgs {
+    /// Input like for `analyze`, or a result saved as .json, .csv,
+    /// .msgpack or .cbor
+    old: String,
+
+    /// Input or saved result to compare with OLD
+    new: String,
+
+    /// Also list tags and attributes that didn't change
+    #[arg(long)]
+    all: bool,
+
+    /// Only compare tags, not their attributes
+    #[arg(long)]
+    tags_only: bool,
+
+    /// Color the diff; `auto` colors terminals unless NO_COLOR is set
+    #[arg(long, value_enum, default_value_t = ColorMode::Auto)]
+    color: ColorMode,
+}
+
+#[derive(clap::Args)]
+struct CrawlArgs {
+    /// http(s) URLs to start from
+    #[arg(required = true)]
+    seeds: Vec<String>,
+
+    /// Link hops followed from the seeds; 0 only fetches the seeds
+    #[arg(long, value_name = "N", default_value_t = 2)]
+    depth: usize,
+
+    /// Stop after fetching N pages
+    #[arg(long, value_name = "N", default_value_t = 100)]
+    max_pages: usize,
+
+    /// Also follow links to HOST and its subdomains, instead of only the
+    /// seeds' hosts
+    #[arg(long, value_name = "HOST")]
+    allow_host: Vec<String>,
+
+    /// Also drop query parameter NAME when comparing URLs, on top of
+    /// tracking parameters like utm_*; a trailing * matches any suffix
+    #[arg(long, value_name = "NAME")]
+    strip_param: Vec<String>,
+
+    /// Milliseconds between requests to the same host
+    /// [default: fetch.throttle_ms, 250]
+    #[arg(long, value_name = "MS")]
+    delay: Option<u64>,
+
+    /// Number of pages fetched at the same time
+    #[arg(short, long, default_value_t = 4)]
+    jobs: usize,
+
+    /// `json` prints every page with its links; the text reports show the
+    /// merged result
+    #[arg(short, long, value_enum, default_value_t = Format::Summary)]
+    format: Format,
+
+    /// Also write the link graph to PATH, as DOT (.dot, .gv), GEXF (.gexf)
+    /// or JSON (.json), optionally compressed (.gz)
+    #[arg(long, value_name = "PATH")]
+    graph: Option<PathBuf>,
+
+    /// Leave pages that weren't crawled, like external links, out of the
+    /// graph
+    #[arg(long, requires = "graph")]
+    crawled_only: bool,
+
+    /// Request every link found, including external ones, and warn about
+    /// broken links and redirects
+    #[arg(long)]
+    check_links: bool,
+
+    /// Group pages with near-identical structure into template clusters,
+    /// allowing their simhashes to differ in up to BITS bits
+    #[arg(long, value_name = "BITS", num_args = 0..=1, default_missing_value = "6")]
+    clusters: Option<u32>,
+
+    /// Also run the optional analysis NAME on every page: `third-party`,
+    /// `security`, `images`, `perf`, `i18n`, `icons` or `keywords`
+    /// [default: analyzer.sections]
+    #[arg(long, value_name = "NAME")]
+    section: Vec<Section>,
+
+    /// Compare the pages found with the site's sitemaps, read from the
+    /// Sitemap lines of robots.txt or /sitemap.xml, and warn about listed
+    /// URLs that aren't linked and crawled pages that aren't listed
+    #[arg(long)]
+    sitemap: bool,
+
+    /// Compare with the sitemap at URL instead of discovering it; implies
+    /// --sitemap
+    #[arg(long, value_name = "URL")]
+    sitemap_url: Vec<String>,
+
+    /// Exit with status 3 if RULE holds for the merged result, e.g.
+    /// `broken_links > 0` with --check-links
+    #[arg(long, value_name = "RULE", help_heading = "Quality gates")]
+    fail_if: Vec<Rule>,
+
+    /// Publish each page to the NATS server at URL as it is analyzed, with
+    /// the --fail-if rules it violates [default: sink.url]
+    #[arg(long, value_name = "URL")]
+    sink: Option<String>,
+
+    #[command(flatten)]
+    report: ReportArgs,
+}
+
+#[derive(clap::Args)]
+struct AnalyzeArgs {
+    /// Paths, glob patterns, http(s) URLs, or `-` to read from stdin
+    #[arg(required = true)]
+    inputs: Vec<String>,
+
+    /// Only print the merged result of all inputs
+    #[arg(long, conflicts_with = "per_file")]
+    aggregate: bool,
+
+    /// Print a result per input file instead of the merged one
+    #[arg(long)]
+    per_file: bool,
+
+    /// Number of URLs fetched at the same time
+    #[arg(short, long, default_value_t = 8)]
+    jobs: usize,
+
+    /// Defaults to `summary` for directories and archives, `json` otherwise
+    #[arg(short, long, value_enum)]
+    format: Option<Format>,
+
+    /// Write to PATH instead of stdout; without --format, the extension
+    /// picks the format or an exporter, e.g. report.html or tags.csv.gz
+    #[arg(short, long, value_name = "PATH")]
+    output: Option<PathBuf>,
+
+    /// Maximum number of distinct values tracked per attribute
+    /// [default: analyzer.top_values, 10]
+    #[arg(long)]
+    top: Option<usize>,
+
+    /// Only analyze the top level of a directory
+    #[arg(long)]
+    no_recursive: bool,
+
+    /// Analyze a single large file on all cores, splitting it before each
+    /// <ELEMENT> start tag (e.g. `page` for Wikipedia dumps)
+    #[arg(long, value_name = "ELEMENT")]
+    split_on: Option<String>,
+
+    /// Group inputs with near-identical structure into template clusters,
+    /// allowing their simhashes to differ in up to BITS bits
+    #[arg(long, value_name = "BITS", num_args = 0..=1, default_missing_value = "6")]
+    clusters: Option<u32>,
+
+    /// Also run the optional analysis NAME: `third-party` for the scripts,
+    /// stylesheets, iframes and images loaded from other sites, `security`
+    /// for cross-origin resources without Subresource Integrity and missing
+    /// security headers, `images` for srcset and <picture> use and images
+    /// without dimensions, `perf` for resource hints and render-blocking
+    /// resources, `i18n` for declared and detected languages and hreflang
+    /// alternates, `icons` for favicons, the web app manifest and theme
+    /// colors, `keywords` for the most frequent words and phrases, `css`
+    /// for classes and ids missing from the page's CSS or its HTML
+    /// [default: analyzer.sections]
+    #[arg(long, value_name = "NAME")]
+    section: Vec<Section>,
+
+    /// Cross-check classes and ids against the stylesheet PATH as well as
+    /// the <style> blocks; repeat for several. Enables the `css` section
+    #[arg(long, value_name = "PATH")]
+    css: Vec<PathBuf>,
+
+    /// Cross-check classes and ids of URLs against the stylesheets they
+    /// link, downloading each once. Enables the `css` section
+    #[arg(long)]
+    fetch_css: bool,
+
+    #[cfg(feature = "rendered")]
+    #[command(flatten)]
+    render: RenderArgs,
+
+    /// Exit with status 3 if RULE holds, e.g. `count(img[alt=""]) > 0`;
+    /// checked against the merged result, or each file with --per-file
+    #[arg(long, value_name = "RULE", help_heading = "Quality gates")]
+    fail_if: Vec<Rule>,
+
+    /// Exit with status 3 if elements are nested deeper than N
+    #[arg(long, value_name = "N", help_heading = "Quality gates")]
+    max_depth: Option<usize>,
+
+    #[command(flatten)]
+    report: ReportArgs,
+}
+
+/// Exit status when a `--fail-if` or `--max-depth` rule is violated; errors
+/// exit with 1 and invalid arguments with 2
+const EXIT_RULE_VIOLATED: u8 = 3;
+
+/// Exit status when `monitor` finds a changed template
+const EXIT_TEMPLATE_CHANGED: u8 = 4;
+
+/// Options of the tree and flat reports
+#[derive(clap::Args)]
+#[command(next_help_heading = "Report options")]
+struct ReportArgs {
+    /// Sort tags and attributes by
+    #[arg(long, value_enum, default_value_t = Sort::Count)]
+    sort: Sort,
+
+    /// Sort order; names default to ascending, counts to descending
+    #[arg(long, value_enum)]
+    order: Option<Order>,
+
+    /// Show only the first N tags
+    #[arg(long, value_name = "N")]
+    max_tags: Option<usize>,
+
+    /// Show only the first M attributes of each tag
+    #[arg(long, value_name = "M")]
+    max_attributes: Option<usize>,
+
+    /// Color the tree report; `auto` colors terminals unless NO_COLOR is set
+    #[arg(long, value_enum, default_value_t = ColorMode::Auto)]
+    color: ColorMode,
+
+    /// Show each tag's share of all elements and each value's share of its
+    /// attribute; JSON output gains `percent` and `value_percents` fields
+    #[arg(long)]
+    percentages: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ColorMode {
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Sort {
+    Count,
+    Name,
+    /// Number of distinct attributes
+    Attributes,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Order {
+    Asc,
+    Desc,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Json,
+    Tree,
+    Flat,
+    /// Headline numbers and the most frequent tags
+    Summary,
+    /// Markdown table
+    Markdown,
+    /// Bar chart of tag counts and a sparkline of depths
+    Histogram,
+}
+
+/// What an input produced: one document or a batch of them
+enum Output {
+    Single(AnalysisResult),
+    Set(AnalysisResultSet),
+}
+
+#[tokio::main]
+async fn main() -> Result<ExitCode> {
+    let cli = Cli::parse();
+    init_logging(cli.quiet, cli.verbose);
+    let config = Config::load(cli.config.as_deref())?;
+    match cli.command {
+        Command::Analyze(args) => analyze(args, &config, cli.quiet).await,
+        Command::Diff(args) => diff(args, &config).await.map(|()| ExitCode::SUCCESS),
+        Command::Crawl(args) => crawl(args, &config, cli.quiet).await,
+        Command::Monitor(args) => monitor(args, &config, cli.quiet).await,
+    }
+}
+
+async fn analyze(args: AnalyzeArgs, config: &Config, quiet: bool) -> Result<ExitCode> {
+    args.report.color.apply();
+    let mut analyzer = config.stream_analyzer();
+    if let Some(top) = args.top {
+        analyzer.top_values_limit = top;
+    }
+    if args.clusters.is_some() {
+        analyzer = analyzer.with_structure(true);
+    }
+    analyzer = analyzer.with_sections(args.section.iter().copied());
+    let stylesheets = args
+        .css
+        .iter()
+        .map(|path| {
+            fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    if !args.css.is_empty() || args.fetch_css {
+        analyzer = analyzer.with_sections([Section::Css]);
+    }
+    #[cfg(feature = "rendered")]
+    {
+        analyzer = args.render.apply(analyzer);
+    }
+    let spinner = spinner(quiet);
+    if let Some(spinner) = &spinner {
+        analyzer = analyzer.with_progress(spinner_progress(spinner.clone()));
+    }
+    let started = Instant::now();
+    let output = read_inputs(&analyzer, &args).await;
+    if let Some(spinner) = spinner {
+        spinner.finish_and_clear();
+    }
+    let mut output = output?;
+    tracing::info!(
+        "analyzed {} in {:.2?}",
+        args.inputs.join(", "),
+        started.elapsed()
+    );
+
+    if let Output::Set(set) = &output {
+        for entry in set.errors() {
+            tracing::warn!(
+                "{}: {}",
+                entry.source,
+                entry.error.as_deref().unwrap_or_default()
+            );
+        }
+    }
+    if !stylesheets.is_empty() || args.fetch_css {
+        let mut fetcher = if args.fetch_css {
+            Some(StylesheetFetcher::new(analyzer.fetch.clone())?)
+        } else {
+            None
+        };
+        output.check_css(&stylesheets, fetcher.as_mut()).await;
+    }
+    if let (Output::Set(set), Some(max_distance)) = (&mut output, args.clusters) {
+        set.cluster_templates(max_distance);
+    }
+    if args.aggregate {
+        if let Output::Set(set) = output {
+            output = Output::Single(set.aggregate);
+        }
+    }
+    if args.report.percentages {
+        output.add_percentages();
+    }
+
+    let rules = args.rules();
+    let mut options = args.report.options().with_rules(rules.clone());
+    if args.output.is_some() {
+        // Neither the terminal width nor its colors apply to a file
+        options.width = None;
+        options.color &= args.report.color == ColorMode::Always;
+    }
+
+    let output_format = args.output.as_deref().and_then(Format::for_path);
+    let exporter = match (&args.output, args.format.or(output_format)) {
+        (Some(path), None) => registry().for_path(path),
+        _ => None,
+    };
+    if let (Some(path), Some(exporter)) = (&args.output, exporter) {
+        (exporter.create)(Some(&args.inputs.join(", "))).export(output.summary(), path)?;
+        return Ok(check_rules(&rules, &output, args.per_file));
+    }
+
+    let format = args.format.or(output_format).unwrap_or(match output {
+        Output::Single(_) => Format::Json,
+        Output::Set(_) if args.per_file => Format::Json,
+        Output::Set(_) => Format::Summary,
+    });
+    let rendered = match (format, &output) {
+        (Format::Json, Output::Set(set)) if args.per_file => {
+            serde_json::to_string_pretty(&set.entries)?
+        }
+        (Format::Json, Output::Set(set)) => serde_json::to_string_pretty(set)?,
+        (format, Output::Set(set)) if args.per_file => set
+            .entries
+            .iter()
+            .filter_map(|entry| Some((&entry.source, entry.result.as_ref()?)))
+            .map(|(source, result)| {
+                Ok(format!(
+                    "==> {} <==\n{}",
+                    source,
+                    format.render(result, &options)?
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?
+            .join("\n"),
+        (format, output) => format.render(output.summary(), &options)?,
+    };
+    let rendered = match &output {
+        Output::Set(set) if !set.clusters.is_empty() && !matches!(format, Format::Json) => {
+            format!("{}\n{}", rendered, render_clusters(&set.clusters))
+        }
+        _ => rendered,
+    };
+    match &args.output {
+        Some(path) => fs::write(path, rendered + "\n")
+            .with_context(|| format!("Failed to write {}", path.display()))?,
+        None => println!("{}", rendered),
+    }
+    Ok(check_rules(&rules, &output, args.per_file))
+}
+
+/// Print the violations of `rules`, checked against each entry with
+/// `per_file`, and the exit status they call for
+fn check_rules(rules: &[Rule], output: &Output, per_file: bool) -> ExitCode {
+    let checked: Vec<(Option<&str>, &AnalysisResult)> = match output {
+        Output::Set(set) if per_file => set
+            .entries
+            .iter()
+            .filter_map(|entry| Some((Some(entry.source.as_str()), entry.result.as_ref()?)))
+            .collect(),
+        output => vec![(None, output.summary())],
+    };
+    let mut violated = false;
+    for (source, result) in checked {
+        for violation in rules::check(rules, result) {
+            violated = true;
+            match source {
+                Some(source) => eprintln!("{}: rule violated: {}", source, violation),
+                None => eprintln!("rule violated: {}", violation),
+            }
+        }
+    }
+    if violated {
+        ExitCode::from(EXIT_RULE_VIOLATED)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// Log to stderr: warnings by default, errors only with `quiet`, more
+/// with every `-v`
+fn init_logging(quiet: bool, verbose: u8) {
+    let level = match (quiet, verbose) {
+        (true, _) => Level::ERROR,
+        (false, 0) => Level::WARN,
+        (false, 1) => Level::INFO,
+        (false, 2) => Level::DEBUG,
+        (false, _) => Level::TRACE,
+    };
+    tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_writer(std::io::stderr)
+        .with_target(false)
+        .without_time()
+        .init();
+}
+
+/// Spinner on stderr, unless `quiet` or the output goes to a pipe or file
+/// rather than a terminal
+fn spinner(quiet: bool) -> Option<ProgressBar> {
+    if quiet || !console::Term::stdout().is_term() || !console::Term::stderr().is_term() {
+        return None;
+    }
+    let spinner = ProgressBar::new_spinner();
+    spinner.set_style(
+        ProgressStyle::default_spinner()
+            .template("{spinner:.green} {msg}")
+            .unwrap(),
+    );
+    spinner.enable_steady_tick(Duration::from_millis(100));
+    Some(spinner)
+}
+
+/// Show analysis progress in `spinner`
+fn spinner_progress(spinner: ProgressBar) -> Progress {
+    Progress::new(move |event| match event {
+        ProgressEvent::Parsed { bytes, elements } => spinner.set_message(format!(
+            "{} elements, {}",
+            elements,
+            HumanBytes(bytes as u64)
+        )),
+        ProgressEvent::SourceCompleted {
+            source,
+            completed,
+            total,
+            ..
+        } => spinner.set_message(format!("{}/{} {}", completed, total, source)),
+    })
+}
+
+async fn crawl(args: CrawlArgs, config: &Config, quiet: bool) -> Result<ExitCode> {
+    args.report.color.apply();
+    let graph_format = args
+        .graph
+        .as_deref()
+        .map(|path| {
+            GraphFormat::for_path(path).with_context(|| {
+                format!(
+                    "Unknown graph format for {}; use .dot, .gv, .gexf or .json",
+                    path.display()
+                )
+            })
+        })
+        .transpose()?;
+    let sink = match &args.sink {
+        Some(url) => Some(Sink::connect(url, &config.sink.subject).await?),
+        None => config.sink.connect().await?,
+    }
+    .map(|sink| sink.with_rules(args.fail_if.clone()));
+    let mut analyzer = config.stream_analyzer();
+    if args.clusters.is_some() {
+        analyzer = analyzer.with_structure(true);
+    }
+    analyzer = analyzer.with_sections(args.section.iter().copied());
+    let spinner = spinner(quiet);
+    if let Some(spinner) = &spinner {
+        analyzer = analyzer.with_progress(spinner_progress(spinner.clone()));
+    }
+    let mut crawler = Crawler::new(analyzer)
+        .with_max_depth(args.depth)
+        .with_max_pages(args.max_pages)
+        .with_concurrency(args.jobs)
+        .with_link_check(args.check_links);
+    if !args.allow_host.is_empty() {
+        let mut hosts = args.allow_host.clone();
+        hosts.extend(seed_hosts(&args.seeds));
+        crawler = crawler.with_scope(Scope::Hosts(hosts));
+    }
+    if let Some(delay) = args.delay {
+        crawler = crawler.with_delay(Duration::from_millis(delay));
+    }
+    if !args.strip_param.is_empty() {
+        let mut params = TRACKING_PARAMS.map(String::from).to_vec();
+        params.extend(args.strip_param.iter().cloned());
+        crawler = crawler.with_normalizer(UrlNormalizer::default().with_strip_params(params));
+    }
+    if !args.sitemap_url.is_empty() {
+        crawler = crawler.with_sitemap(SitemapSource::Urls(args.sitemap_url.clone()));
+    } else if args.sitemap {
+        crawler = crawler.with_sitemap(SitemapSource::Discover);
+    }
+    if let Some(sink) = &sink {
+        crawler = crawler.with_sink(sink.clone());
+    }
+    let started = Instant::now();
+    let crawl = crawler.crawl(&args.seeds).await;
+    if let Some(spinner) = spinner {
+        spinner.finish_and_clear();
+    }
+    let mut crawl = crawl?;
+    if let Some(sink) = &sink {
+        sink.flush().await?;
+    }
+    tracing::info!(
+        "crawled {} pages in {:.2?}",
+        crawl.pages.len(),
+        started.elapsed()
+    );
+    for page in crawl.errors() {
+        tracing::warn!(
+            "{}: {}",
+            page.url,
+            page.error.as_deref().unwrap_or_default()
+        );
+    }
+    if let Some(report) = &crawl.link_check {
+        for (source, link) in report.broken() {
+            let status = &link.status;
+            match (status.status, &status.error) {
+                (Some(code), _) => {
+                    tracing::warn!("{}: broken link {} ({})", source, link.url, code)
+                }
+                (None, error) => tracing::warn!(
+                    "{}: broken link {} ({})",
+                    source,
+                    link.url,
+                    error.as_deref().unwrap_or("unreachable")
+                ),
+            }
+        }
+        for (source, link) in report.redirected() {
+            let chain: Vec<_> = link
+                .status
+                .redirects
+                .iter()
+                .map(|hop| format!("{} {}", hop.status, hop.location))
+                .collect();
+            tracing::info!("{}: {} redirects: {}", source, link.url, chain.join(" -> "));
+        }
+    }
+    if let Some(coverage) = &crawl.sitemap {
+        for error in &coverage.errors {
+            tracing::warn!("sitemap {}", error);
+        }
+        for url in &coverage.not_linked {
+            tracing::warn!("{}: in the sitemap but not linked", url);
+        }
+        for url in &coverage.not_listed {
+            tracing::warn!("{}: crawled but not in the sitemap", url);
+        }
+        tracing::info!(
+            "{} sitemap URLs, {} not linked, {} pages not listed",
+            coverage.listed,
+            coverage.not_linked.len(),
+            coverage.not_listed.len()
+        );
+    }
+    if let (Some(path), Some(format)) = (&args.graph, graph_format) {
+        let mut graph = crawl.link_graph();
+        if args.crawled_only {
+            graph = graph.crawled_only();
+        }
+        LinkGraphExporter::new(format).export(&graph, path)?;
+    }
+
+    if args.report.percentages {
+        crawl.aggregate.add_percentages();
+        for result in crawl
+            .pages
+            .iter_mut()
+            .filter_map(|page| page.result.as_mut())
+        {
+            result.add_percentages();
+        }
+    }
+    if let Some(max_distance) = args.clusters {
+        crawl.cluster_templates(max_distance);
+    }
+    let options = args.report.options().with_rules(args.fail_if.clone());
+    let rendered = match args.format {
+        Format::Json => serde_json::to_string_pretty(&crawl)?,
+        format if !crawl.clusters.is_empty() => format!(
+            "{}\n{}",
+            format.render(&crawl.aggregate, &options)?,
+            render_clusters(&crawl.clusters)
+        ),
+        format => format.render(&crawl.aggregate, &options)?,
+    };
+    println!("{}", rendered);
+    Ok(check_rules(
+        &args.fail_if,
+        &Output::Single(crawl.aggregate),
+        false,
+    ))
+}
+
+/// Template clusters, largest first, with an example page each
+fn render_clusters(clusters: &[TemplateCluster]) -> String {
+    let mut sorted: Vec<_> = clusters.iter().collect();
+    sorted.sort_by(|a, b| b.pages.cmp(&a.pages).then(a.id.cmp(&b.id)));
+    let mut out = format!("Template clusters: {}", clusters.len());
+    for cluster in sorted {
+        out.push_str(&format!(
+            "\n  #{:<4} {:>6} {:<5}  {}",
+            cluster.id,
+            cluster.pages,
+            if cluster.pages == 1 { "page" } else { "pages" },
+            cluster.example
+        ));
+    }
+    out
+}
+
+/// Hosts of the seeds that parse as URLs
+fn seed_hosts(seeds: &[String]) -> Vec<String> {
+    seeds
+        .iter()
+        .filter_map(|seed| reqwest::Url::parse(seed).ok())
+        .filter_map(|url| url.host_str().map(str::to_string))
+        .collect()
+}
+
+async fn monitor(args: MonitorArgs, config: &Config, quiet: bool) -> Result<ExitCode> {
+    let mut store = FingerprintStore::open(&args.store)?;
+    let mut analyzer = config.stream_analyzer().with_structure(true);
+    #[cfg(feature = "rendered")]
+    {
+        analyzer = args.render.apply(analyzer);
+    }
+    let spinner = spinner(quiet);
+    if let Some(spinner) = &spinner {
+        analyzer = analyzer.with_progress(spinner_progress(spinner.clone()));
+    }
+    let is_url = |input: &str| input.starts_with("http://") || input.starts_with("https://");
+    let urls = args.inputs.iter().filter(|input| is_url(input)).cloned();
+    let mut fetched = analyzer
+        .analyze_urls(urls, args.jobs)
+        .await
+        .entries
+        .into_iter();
+    let mut results = Vec::new();
+    for input in &args.inputs {
+        let result = if is_url(input) {
+            let entry = fetched.next().context("Missing URL result")?;
+            entry
+                .result
+                .ok_or_else(|| anyhow::anyhow!(entry.error.unwrap_or_default()))
+        } else {
+            read_input(&analyzer, input, true, None)
+                .await
+                .map(|output| output.summary().clone())
+        };
+        results.push((input, result));
+    }
+    if let Some(spinner) = spinner {
+        spinner.finish_and_clear();
+    }
+
+    let mut checks = Vec::new();
+    for (input, result) in results {
+        match result {
+            Ok(result) => checks.push(store.check(input, &result, args.min_count)),
+            // The stored fingerprint stays for the next run
+            Err(err) => tracing::warn!("{}: {:#}", input, err),
+        }
+    }
+    if !args.dry_run {
+        store.save()?;
+    }
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&checks)?);
+    } else {
+        for check in &checks {
+            let status = match check.status {
+                TemplateStatus::New => "new",
+                TemplateStatus::Unchanged => "unchanged",
+                TemplateStatus::Changed => "changed",
+            };
+            println!("{:<9} {} {}", status, check.new, check.url);
+            for feature in &check.added {
+                println!("    + {}", feature);
+            }
+            for feature in &check.removed {
+                println!("    - {}", feature);
+            }
+        }
+    }
+    let changed = checks
+        .iter()
+        .any(|check| check.status == TemplateStatus::Changed);
+    Ok(if changed {
+        ExitCode::from(EXIT_TEMPLATE_CHANGED)
+    } else {
+        ExitCode::SUCCESS
+    })
+}
+
+async fn diff(args: DiffArgs, config: &Config) -> Result<()> {
+    args.color.apply();
+    let analyzer = config.stream_analyzer();
+    let old = load_result(&analyzer, &args.old).await?;
+    let new = load_result(&analyzer, &args.new).await?;
+
+    let mut options = RenderOptions {
+        color: args.color != ColorMode::Never,
+        width: terminal_width(),
+        ..RenderOptions::default()
+    };
+    if args.tags_only {
+        options.depth = Depth::Tags;
+    }
+    let display = DiffDisplay::default()
+        .with_labels(&args.old, &args.new)
+        .with_unchanged(args.all);
+    print!(
+        "{}",
+        display.render(&AnalysisDiff::new(&old, &new), &options)
+    );
+    Ok(())
+}
+
+/// Analyze `input`: stdin for `-`, a URL, a directory, an archive or a file
+async fn read_input(
+    analyzer: &StreamAnalyzer,
+    input: &str,
+    recursive: bool,
+    split_on: Option<&str>,
+) -> Result<Output> {
+    let path = Path::new(input);
+    Ok(if input == "-" {
+        Output::Single(analyzer.analyze_stdin()?)
+    } else if input.starts_with("http://") || input.starts_with("https://") {
+        Output::Single(analyzer.analyze_url(input).await?)
+    } else if path.is_dir() {
+        Output::Set(analyzer.analyze_dir(path, recursive)?)
+    } else if ArchiveFormat::from_path(path).is_some() {
+        Output::Set(analyzer.analyze_archive(path)?)
+    } else if let Some(record) = split_on {
+        Output::Single(analyzer.analyze_file_chunked(path, record)?)
+    } else {
+        Output::Single(analyzer.analyze_file(path)?)
+    })
+}
+
+/// What one of several `analyze` inputs refers to
+enum Input {
+    Stdin,
+    Url(String),
+    /// A file, or the files matched by a glob pattern
+    Files(Vec<PathBuf>),
+    Dir(PathBuf),
+    Archive(PathBuf),
+}
+
+impl Input {
+    fn new(input: &str) -> Result<Self> {
+        let path = Path::new(input);
+        Ok(if input == "-" {
+            Input::Stdin
+        } else if input.starts_with("http://") || input.starts_with("https://") {
+            Input::Url(input.to_string())
+        } else if path.is_dir() {
+            Input::Dir(path.to_path_buf())
+        } else if !path.exists() && is_glob(input) {
+            let files = glob_files(input)?;
+            if files.is_empty() {
+                anyhow::bail!("No files match {}", input);
+            }
+            Input::Files(files)
+        } else if ArchiveFormat::from_path(path).is_some() {
+            Input::Archive(path.to_path_buf())
+        } else {
+            Input::Files(vec![path.to_path_buf()])
+        })
+    }
+}
+
+/// Analyze the inputs of `args`; several inputs or a glob pattern give a
+/// set with one entry per file or URL, in input order
+///
+/// Local files are analyzed in parallel, then URLs are fetched `--jobs` at
+/// a time.
+async fn read_inputs(analyzer: &StreamAnalyzer, args: &AnalyzeArgs) -> Result<Output> {
+    let recursive = !args.no_recursive;
+    if let [input] = args.inputs.as_slice() {
+        if !is_glob(input) || Path::new(input).exists() {
+            return read_input(analyzer, input, recursive, args.split_on.as_deref()).await;
+        }
+    }
+
+    let inputs = args
+        .inputs
+        .iter()
+        .map(|input| Input::new(input))
+        .collect::<Result<Vec<_>>>()?;
+    let files = inputs.iter().flat_map(|input| match input {
+        Input::Files(files) => files.clone(),
+        _ => Vec::new(),
+    });
+    let urls = inputs.iter().filter_map(|input| match input {
+        Input::Url(url) => Some(url.clone()),
+        _ => None,
+    });
+    let mut file_entries = analyzer.analyze_files(files).entries.into_iter();
+    let mut url_entries = analyzer
+        .analyze_urls(urls, args.jobs)
+        .await
+        .entries
+        .into_iter();
+
+    let mut entries = Vec::new();
+    for input in inputs {
+        match input {
+            Input::Stdin => {
+                entries.push(SourceResult::new("-".to_string(), analyzer.analyze_stdin()))
+            }
+            Input::Url(_) => entries.extend(url_entries.next()),
+            Input::Files(files) => entries.extend(file_entries.by_ref().take(files.len())),
+            Input::Dir(path) => entries.extend(analyzer.analyze_dir(&path, recursive)?.entries),
+            Input::Archive(path) => entries.extend(analyzer.analyze_archive(&path)?.entries),
+        }
+    }
+    Ok(Output::Set(AnalysisResultSet::from_entries(
+        entries,
+        analyzer.top_values_limit,
+    )))
+}
+
+/// A result saved by an exporter, or the analysis of any other input
+async fn load_result(analyzer: &StreamAnalyzer, input: &str) -> Result<AnalysisResult> {
+    let path = Path::new(input);
+    let extension = path.extension().and_then(|ext| ext.to_str());
+    let open = || -> Result<_> { Ok(BufReader::new(File::open(path)?)) };
+    Ok(match extension {
+        Some("json") => serde_json::from_reader(open()?)?,
+        Some("csv") => AnalysisResult::from_csv(open()?)?,
+        Some("msgpack" | "mpk") => AnalysisResult::from_msgpack_reader(open()?)?,
+        Some("cbor") => AnalysisResult::from_cbor_reader(open()?)?,
+        _ => match read_input(analyzer, input, true, None).await? {
+            Output::Single(result) => result,
+            Output::Set(set) => set.aggregate,
+        },
+    })
+}
+
+/// Columns of the terminal on stdout; `None` when output is redirected
+fn terminal_width() -> Option<usize> {
+    console::Term::stdout()
+        .size_checked()
+        .map(|(_, columns)| columns.into())
+}
+
+impl ColorMode {
+    /// Force colors on for `always`; `colored` handles `auto` itself
+    fn apply(self) {
+        if self == ColorMode::Always {
+            colored::control::set_override(true);
+        }
+    }
+}
+
+impl Output {
+    /// The result shown by text reports: the aggregate for batches
+    fn summary(&self) -> &AnalysisResult {
+        match self {
+            Output::Single(result) => result,
+            Output::Set(set) => &set.aggregate,
+        }
+    }
+
+    /// Add `stylesheets` and, with `fetcher`, the stylesheets each page
+    /// links to the CSS cross-check of every result
+    async fn check_css(
+        &mut self,
+        stylesheets: &[String],
+        mut fetcher: Option<&mut StylesheetFetcher>,
+    ) {
+        let reports: Vec<&mut CssReport> = match self {
+            Output::Single(result) => result.css.iter_mut().collect(),
+            Output::Set(set) => set
+                .entries
+                .iter_mut()
+                .filter_map(|entry| entry.result.as_mut()?.css.as_mut())
+                .collect(),
+        };
+        for report in reports {
+            for css in stylesheets {
+                report.add_stylesheet(css);
+            }
+            if let Some(fetcher) = fetcher.as_deref_mut() {
+                fetcher.add_linked(report).await;
+            }
+        }
+        if let Output::Set(set) = self {
+            let mut aggregate: Option<CssReport> = None;
+            for css in set
+                .entries
+                .iter()
+                .filter_map(|entry| entry.result.as_ref()?.css.as_ref())
+            {
+                aggregate.get_or_insert_with(CssReport::default).merge(css);
+            }
+            set.aggregate.css = aggregate;
+        }
+    }
+
+    /// Fill in the percentage fields of every result
+    fn add_percentages(&mut self) {
+        match self {
+            Output::Single(result) => result.add_percentages(),
+            Output::Set(set) => {
+                set.aggregate.add_percentages();
+                for result in set
+                    .entries
+                    .iter_mut()
+                    .filter_map(|entry| entry.result.as_mut())
+                {
+                    result.add_percentages();
+                }
+            }
+        }
+    }
+}
+
+impl Format {
+    /// Report format implied by the extension of an output file
+    fn for_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "json" => Some(Format::Json),
+            "md" | "markdown" => Some(Format::Markdown),
+            _ => None,
+        }
+    }
+
+    /// `result` as pretty-printed JSON or a text report
+    fn render(self, result: &AnalysisResult, options: &RenderOptions) -> Result<String> {
+        Ok(match self {
+            Format::Json => serde_json::to_string_pretty(result)?,
+            Format::Tree => TreeDisplay.render(result, options),
+            Format::Flat => FlatDisplay.render(result, options),
+            Format::Summary => SummaryDisplay.render(result, options),
+            Format::Markdown => MarkdownDisplay::default().render(result, options),
+            Format::Histogram => HistogramDisplay.render(result, options),
+        })
+    }
+}
+
+impl AnalyzeArgs {
+    /// The `--fail-if` rules followed by `--max-depth`
+    fn rules(&self) -> Vec<Rule> {
+        let mut rules = self.fail_if.clone();
+        rules.extend(self.max_depth.map(Rule::max_depth));
+        rules
+    }
+}
+
+impl ReportArgs {
+    fn options(&self) -> RenderOptions {
+        RenderOptions {
+            sort: match self.sort {
+                Sort::Count => SortKey::Count,
+                Sort::Name => SortKey::Name,
+                Sort::Attributes => SortKey::Attributes,
+            },
+            order: self.order.map(|order| match order {
+                Order::Asc => SortOrder::Ascending,
+                Order::Desc => SortOrder::Descending,
+            }),
+            max_tags: self.max_tags,
+            max_attributes: self.max_attributes,
+            color: self.color != ColorMode::Never,
+            width: terminal_width(),
+            percentages: self.percentages,
+            ..RenderOptions::default()
+        }
+    }
+}
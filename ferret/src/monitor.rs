@@ -0,0 +1,360 @@
+use crate::analyzer::AnalysisResult;
+use crate::cache::Fnv1a;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs::{self, File};
+use std::hash::Hasher;
+use std::io::{BufReader, BufWriter, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Occurrences below which a tag, attribute, class or nesting is left out
+/// of [`AnalysisResult::fingerprint`], so one-off content like an extra
+/// banner doesn't count as a template change
+pub const DEFAULT_MIN_COUNT: usize = 2;
+
+/// Stable hash of the structure of an analysis result
+///
+/// Built from [`structure_features`]: which tags, attributes, `class`
+/// values and parent/child nestings occur, not how often. Two pages of the
+/// same template listing a different number of items get the same
+/// fingerprint, and the hash is the same across runs and builds.
+///
+/// # Example
+/// ```
+/// # use ferret::analyzer::stream::StreamAnalyzer;
+/// let analyzer = StreamAnalyzer::new(10);
+/// let list = |items: &str| format!("<ul class=\"products\">{}</ul><ul></ul>", items);
+/// let two = analyzer.analyze_string(&list("<li>a</li><li>b</li>"))?;
+/// let three = analyzer.analyze_string(&list("<li>a</li><li>b</li><li>c</li>"))?;
+/// let table = analyzer.analyze_string("<table><tr></tr><tr></tr></table>")?;
+///
+/// assert_eq!(two.fingerprint(), three.fingerprint());
+/// assert_ne!(two.fingerprint(), table.fingerprint());
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub struct Fingerprint(pub u64);
+
+impl Fingerprint {
+    /// Hash of `features`, which must be sorted
+    pub fn of<'a>(features: impl IntoIterator<Item = &'a String>) -> Self {
+        let mut hasher = Fnv1a::default();
+        for feature in features {
+            hasher.write(feature.as_bytes());
+            // Separator, so that ["ab", "c"] and ["a", "bc"] differ
+            hasher.write_u8(0xff);
+        }
+        Self(hasher.finish())
+    }
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+impl FromStr for Fingerprint {
+    type Err = anyhow::Error;
+
+    /// Parse the 16 hex digits written by `Display`
+    fn from_str(hex: &str) -> Result<Self> {
+        u64::from_str_radix(hex, 16)
+            .map(Self)
+            .with_context(|| format!("Invalid fingerprint {:?}", hex))
+    }
+}
+
+impl From<Fingerprint> for String {
+    fn from(fingerprint: Fingerprint) -> Self {
+        fingerprint.to_string()
+    }
+}
+
+impl TryFrom<String> for Fingerprint {
+    type Error = anyhow::Error;
+
+    fn try_from(hex: String) -> Result<Self> {
+        hex.parse()
+    }
+}
+
+/// The structural features of `result` occurring at least `min_count`
+/// times, sorted
+///
+/// Features are tag names (`li`), attributes (`a[href]`), `class` values
+/// (`div.card`, with every class of the attribute value) and, when the
+/// result tracks structure, nestings (`ul > li`).
+pub fn structure_features(result: &AnalysisResult, min_count: usize) -> BTreeSet<String> {
+    let mut features = BTreeSet::new();
+    for (name, tag) in &result.tags {
+        if tag.count < min_count {
+            continue;
+        }
+        features.insert(name.clone());
+        for (attribute, stats) in &tag.attributes {
+            if stats.count < min_count {
+                continue;
+            }
+            features.insert(format!("{}[{}]", name, attribute));
+            if attribute != "class" {
+                continue;
+            }
+            for (value, count) in &stats.value_counts {
+                if *count >= min_count {
+                    for class in value.split_whitespace() {
+                        features.insert(format!("{}.{}", name, class));
+                    }
+                }
+            }
+        }
+    }
+    for (parent, children) in &result.children {
+        for (child, count) in children {
+            if *count >= min_count {
+                features.insert(format!("{} > {}", parent, child));
+            }
+        }
+    }
+    features
+}
+
+/// What a page's stored fingerprint says about its latest analysis
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TemplateStatus {
+    /// No fingerprint was stored for the page
+    New,
+    Unchanged,
+    Changed,
+}
+
+/// Outcome of comparing a page with its stored fingerprint
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TemplateCheck {
+    pub url: String,
+    pub status: TemplateStatus,
+    /// Stored fingerprint, unless the page is new
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub old: Option<Fingerprint>,
+    pub new: Fingerprint,
+    /// Features the page gained since the stored fingerprint
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub added: Vec<String>,
+    /// Features the page lost since the stored fingerprint
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub removed: Vec<String>,
+}
+
+/// Fingerprint of a page as stored by [`FingerprintStore`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoredFingerprint {
+    pub fingerprint: Fingerprint,
+    /// The features it was computed from, to tell what changed
+    pub features: Vec<String>,
+}
+
+/// Fingerprints per URL kept in a JSON file between runs, to notice when a
+/// site changes a page's template
+///
+/// # Example
+/// ```no_run
+/// # use ferret::analyzer::stream::StreamAnalyzer;
+/// # async fn run() -> anyhow::Result<()> {
+/// use ferret::monitor::{FingerprintStore, TemplateStatus, DEFAULT_MIN_COUNT};
+///
+/// let mut store = FingerprintStore::open("fingerprints.json")?;
+/// let url = "https://example.com/products/1";
+/// let result = StreamAnalyzer::new(10).analyze_url(url).await?;
+/// let check = store.check(url, &result, DEFAULT_MIN_COUNT);
+/// if check.status == TemplateStatus::Changed {
+///     println!("{} changed: lost {:?}", url, check.removed);
+/// }
+/// store.save()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct FingerprintStore {
+    path: PathBuf,
+    pages: BTreeMap<String, StoredFingerprint>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoreFile {
+    pages: BTreeMap<String, StoredFingerprint>,
+}
+
+impl FingerprintStore {
+    /// Read the fingerprints stored at `path`; a missing file is an empty
+    /// store, created by [`save`](Self::save)
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let pages = match File::open(&path) {
+            Ok(file) => {
+                let file: StoreFile = serde_json::from_reader(BufReader::new(file))
+                    .with_context(|| format!("Failed to read {}", path.display()))?;
+                file.pages
+            }
+            Err(err) if err.kind() == ErrorKind::NotFound => BTreeMap::new(),
+            Err(err) => {
+                return Err(err).with_context(|| format!("Failed to open {}", path.display()))
+            }
+        };
+        Ok(Self { path, pages })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn get(&self, url: &str) -> Option<&StoredFingerprint> {
+        self.pages.get(url)
+    }
+
+    /// Compare `result` with the fingerprint stored for `url` and store its
+    /// own in its place
+    pub fn check(&mut self, url: &str, result: &AnalysisResult, min_count: usize) -> TemplateCheck {
+        let features = structure_features(result, min_count);
+        let new = Fingerprint::of(&features);
+        let features: Vec<String> = features.into_iter().collect();
+        let stored = self.pages.insert(
+            url.to_string(),
+            StoredFingerprint {
+                fingerprint: new,
+                features: features.clone(),
+            },
+        );
+
+        let mut check = TemplateCheck {
+            url: url.to_string(),
+            status: TemplateStatus::New,
+            old: None,
+            new,
+            added: Vec::new(),
+            removed: Vec::new(),
+        };
+        if let Some(stored) = stored {
+            check.old = Some(stored.fingerprint);
+            if stored.fingerprint == new {
+                check.status = TemplateStatus::Unchanged;
+            } else {
+                check.status = TemplateStatus::Changed;
+                let old: BTreeSet<&String> = stored.features.iter().collect();
+                let current: BTreeSet<&String> = features.iter().collect();
+                check.added = current.difference(&old).map(|s| s.to_string()).collect();
+                check.removed = old.difference(&current).map(|s| s.to_string()).collect();
+            }
+        }
+        check
+    }
+
+    /// Write the store back to its file, replacing it atomically
+    pub fn save(&self) -> Result<()> {
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let temp_path = self.path.with_extension("json.tmp");
+        let mut out = BufWriter::new(File::create(&temp_path)?);
+        serde_json::to_writer_pretty(
+            &mut out,
+            &StoreFile {
+                pages: self.pages.clone(),
+            },
+        )?;
+        out.flush()?;
+        fs::rename(&temp_path, &self.path)
+            .with_context(|| format!("Failed to write {}", self.path.display()))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::stream::StreamAnalyzer;
+
+    fn analyze(html: &str) -> AnalysisResult {
+        StreamAnalyzer::new(10)
+            .with_structure(true)
+            .analyze_string(html)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_structure_features() {
+        let result = analyze(
+            r#"<div class="card new"><a href="/a">a</a></div>
+               <div class="card new"><a href="/b">b</a></div>
+               <p id="once">x</p>"#,
+        );
+        let features: Vec<_> = structure_features(&result, 2).into_iter().collect();
+        assert_eq!(
+            features,
+            [
+                "a",
+                "a[href]",
+                "div",
+                "div > a",
+                "div.card",
+                "div.new",
+                "div[class]"
+            ]
+        );
+        assert!(structure_features(&result, 1).contains("p[id]"));
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let a = analyze("<ul><li>1</li><li>2</li></ul><ul></ul>");
+        let b = analyze("<ul><li>1</li><li>2</li><li>3</li></ul><ul></ul><em>x</em>");
+        let c = analyze("<ul><li>1</li><li>2</li></ul><ul></ul><em>x</em><em>y</em>");
+        assert_eq!(a.fingerprint(), b.fingerprint());
+        assert_ne!(a.fingerprint(), c.fingerprint());
+        assert_ne!(a.fingerprint_with(1), b.fingerprint_with(1));
+
+        let fingerprint = a.fingerprint();
+        assert_eq!(
+            fingerprint.to_string().parse::<Fingerprint>().unwrap(),
+            fingerprint
+        );
+        assert!("xyz".parse::<Fingerprint>().is_err());
+    }
+
+    #[test]
+    fn test_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state/fingerprints.json");
+        let url = "https://example.com/";
+        let old = analyze("<div><p>a</p><p>b</p></div><div></div>");
+        let new = analyze("<section><p>a</p><p>b</p></section><section></section>");
+
+        let mut store = FingerprintStore::open(&path).unwrap();
+        assert_eq!(
+            store.check(url, &old, DEFAULT_MIN_COUNT).status,
+            TemplateStatus::New
+        );
+        store.save().unwrap();
+
+        let mut store = FingerprintStore::open(&path).unwrap();
+        assert_eq!(
+            store.get(url).unwrap().fingerprint,
+            old.fingerprint_with(DEFAULT_MIN_COUNT)
+        );
+        let check = store.check(url, &old, DEFAULT_MIN_COUNT);
+        assert_eq!(check.status, TemplateStatus::Unchanged);
+
+        let check = store.check(url, &new, DEFAULT_MIN_COUNT);
+        assert_eq!(check.status, TemplateStatus::Changed);
+        assert_eq!(check.added, ["section", "section > p"]);
+        assert_eq!(check.removed, ["div", "div > p"]);
+        assert_eq!(
+            store.check(url, &new, DEFAULT_MIN_COUNT).status,
+            TemplateStatus::Unchanged
+        );
+    }
+}
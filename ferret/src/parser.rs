@@ -1,3 +1,5 @@
+use crate::limits::Limits;
+use crate::walker::DomWalker;
 use anyhow::Result;
 use tl::{ParserOptions, VDom};
 
@@ -10,16 +12,74 @@ impl FerretParser {
             tl::parse(content, options).map_err(|e| anyhow::anyhow!("Parse error: {:?}", e))?;
         Ok(vdom)
     }
+
+    /// Parse untrusted content, aborting with a `FerretError` when any of the
+    /// configured limits is exceeded.
+    ///
+    /// The input size is checked before parsing; node count and depth are
+    /// checked on the resulting DOM.
+    pub fn parse_with_limits<'a>(content: &'a str, limits: &Limits) -> Result<VDom<'a>> {
+        limits.check_input(content.len())?;
+
+        let vdom = Self::parse(content)?;
+        limits.check_nodes(vdom.nodes().len())?;
+
+        if limits.max_depth.is_some() {
+            let walker = DomWalker::new(vdom.children().to_vec(), vdom.parser());
+            for (_handle, _node, depth) in walker {
+                limits.check_depth(depth)?;
+            }
+        }
+
+        Ok(vdom)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::error::FerretError;
 
     #[test]
     fn test_parse_valid() {
         let html = "<div><p>Hello</p></div>";
         let vdom = FerretParser::parse(html).expect("Failed to parse valid HTML");
-        assert!(vdom.children().len() > 0);
+        assert!(!vdom.children().is_empty());
+    }
+
+    #[test]
+    fn test_parse_with_limits() {
+        let html = "<div><p>Hello</p><p>World</p></div>";
+        assert!(FerretParser::parse_with_limits(html, &Limits::default()).is_ok());
+
+        let limits = Limits {
+            max_input_bytes: Some(10),
+            ..Limits::default()
+        };
+        let err = FerretParser::parse_with_limits(html, &limits).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<FerretError>(),
+            Some(FerretError::InputTooLarge { limit: 10 })
+        ));
+
+        let limits = Limits {
+            max_nodes: Some(3),
+            ..Limits::default()
+        };
+        let err = FerretParser::parse_with_limits(html, &limits).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<FerretError>(),
+            Some(FerretError::TooManyNodes { limit: 3 })
+        ));
+
+        let limits = Limits {
+            max_depth: Some(1),
+            ..Limits::default()
+        };
+        let err = FerretParser::parse_with_limits(html, &limits).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<FerretError>(),
+            Some(FerretError::TooDeep { limit: 1 })
+        ));
     }
 }
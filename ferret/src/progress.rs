@@ -0,0 +1,76 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+pub use tokio_util::sync::CancellationToken;
+
+/// Bytes between two `Parsed` events for the same document
+pub const REPORT_INTERVAL_BYTES: usize = 64 * 1024;
+
+/// Progress of a running analysis
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProgressEvent {
+    /// Bytes read and elements processed so far in the current document
+    Parsed { bytes: usize, elements: usize },
+    /// A file or URL of a batch has been analyzed
+    SourceCompleted {
+        source: String,
+        /// Sources finished so far, including this one
+        completed: usize,
+        total: usize,
+        error: Option<String>,
+        /// Elements of the source by tag name; empty if it failed
+        tags: BTreeMap<String, usize>,
+    },
+}
+
+/// Receiver for progress events
+///
+/// Events are delivered synchronously on the thread doing the analysis, so
+/// callbacks should return quickly; use `channel()` to hand events to
+/// another task.
+///
+/// # Example
+/// ```
+/// use ferret::analyzer::stream::StreamAnalyzer;
+/// use ferret::progress::{Progress, ProgressEvent};
+///
+/// let progress = Progress::new(|event| {
+///     if let ProgressEvent::Parsed { bytes, .. } = event {
+///         eprintln!("{} bytes", bytes);
+///     }
+/// });
+/// let analyzer = StreamAnalyzer::new(10).with_progress(progress);
+/// analyzer.analyze_string("<div></div>")?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Clone)]
+pub struct Progress(Arc<dyn Fn(ProgressEvent) + Send + Sync>);
+
+impl Progress {
+    pub fn new(callback: impl Fn(ProgressEvent) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(callback))
+    }
+
+    /// Report progress to an unbounded channel
+    ///
+    /// Events sent after the receiver is dropped are discarded.
+    pub fn channel() -> (Self, mpsc::UnboundedReceiver<ProgressEvent>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let progress = Self::new(move |event| {
+            let _ = sender.send(event);
+        });
+        (progress, receiver)
+    }
+
+    pub fn report(&self, event: ProgressEvent) {
+        (self.0)(event)
+    }
+}
+
+impl fmt::Debug for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Progress")
+    }
+}
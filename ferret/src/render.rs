@@ -0,0 +1,235 @@
+//! Rendering pages in a headless browser before they are analyzed
+//!
+//! Pages built by JavaScript arrive as little more than an empty
+//! `<div id="root">`; rendering runs their scripts first. The browser is
+//! driven over the W3C WebDriver protocol, so a `chromedriver` or
+//! `geckodriver` must be running, e.g. `chromedriver --port=4444`.
+
+use anyhow::{bail, Context, Result};
+use reqwest::{Client, Method, StatusCode};
+use serde_json::{json, Value};
+use std::time::{Duration, Instant};
+
+/// Where `chromedriver --port=4444` or `geckodriver` listen by default
+pub const DEFAULT_WEBDRIVER_URL: &str = "http://localhost:4444";
+
+/// Time between checks for the element awaited with
+/// [`BrowserOptions::with_wait_for`]
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Browser {
+    #[default]
+    Chrome,
+    Firefox,
+}
+
+/// How pages are rendered
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BrowserOptions {
+    /// Base URL of the WebDriver server
+    pub webdriver_url: String,
+    pub browser: Browser,
+    /// CSS selector of an element to wait for after the page loaded, e.g.
+    /// one the app renders last
+    pub wait_for: Option<String>,
+    /// Limit for loading the page and finding `wait_for`
+    pub timeout: Duration,
+}
+
+impl Default for BrowserOptions {
+    fn default() -> Self {
+        Self {
+            webdriver_url: DEFAULT_WEBDRIVER_URL.to_string(),
+            browser: Browser::default(),
+            wait_for: None,
+            timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+impl BrowserOptions {
+    pub fn with_webdriver_url(mut self, url: impl Into<String>) -> Self {
+        self.webdriver_url = url.into();
+        self
+    }
+
+    pub fn with_browser(mut self, browser: Browser) -> Self {
+        self.browser = browser;
+        self
+    }
+
+    pub fn with_wait_for(mut self, selector: impl Into<String>) -> Self {
+        self.wait_for = Some(selector.into());
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Capabilities requesting a headless session of the browser
+    fn capabilities(&self) -> Value {
+        match self.browser {
+            Browser::Chrome => json!({
+                "browserName": "chrome",
+                "goog:chromeOptions": { "args": ["--headless=new", "--disable-gpu"] },
+            }),
+            Browser::Firefox => json!({
+                "browserName": "firefox",
+                "moz:firefoxOptions": { "args": ["-headless"] },
+            }),
+        }
+    }
+}
+
+/// Loads pages in a headless browser and returns their rendered HTML
+///
+/// Each page gets a fresh browser session, closed again once its HTML has
+/// been read.
+///
+/// # Example
+/// ```no_run
+/// # async fn run() -> anyhow::Result<()> {
+/// use ferret::render::{BrowserOptions, Renderer};
+///
+/// let renderer = Renderer::new(BrowserOptions::default().with_wait_for("#root > *"))?;
+/// let html = renderer.render("https://example.com/app").await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Renderer {
+    options: BrowserOptions,
+    client: Client,
+}
+
+impl Renderer {
+    pub fn new(options: BrowserOptions) -> Result<Self> {
+        // Leave room for the browser to give up on the page first
+        let client = Client::builder()
+            .timeout(options.timeout + Duration::from_secs(30))
+            .build()?;
+        Ok(Self { options, client })
+    }
+
+    /// The HTML of `url` after its scripts ran
+    pub async fn render(&self, url: &str) -> Result<String> {
+        let session = self
+            .command(
+                Method::POST,
+                "session",
+                Some(json!({ "capabilities": { "alwaysMatch": self.options.capabilities() } })),
+            )
+            .await
+            .context("Failed to start a browser session")?;
+        let Some(id) = session["sessionId"].as_str() else {
+            bail!("WebDriver returned no session id");
+        };
+        let session = format!("session/{}", id);
+
+        let html = self.render_in(&session, url).await;
+        if let Err(err) = self.command(Method::DELETE, &session, None).await {
+            tracing::debug!("failed to close browser session: {:#}", err);
+        }
+        html.with_context(|| format!("Failed to render {}", url))
+    }
+
+    async fn render_in(&self, session: &str, url: &str) -> Result<String> {
+        let timeout = self.options.timeout.as_millis() as u64;
+        self.command(
+            Method::POST,
+            &format!("{}/timeouts", session),
+            Some(json!({ "pageLoad": timeout, "script": timeout })),
+        )
+        .await?;
+        self.command(
+            Method::POST,
+            &format!("{}/url", session),
+            Some(json!({ "url": url })),
+        )
+        .await?;
+
+        if let Some(selector) = &self.options.wait_for {
+            let started = Instant::now();
+            let find = json!({ "using": "css selector", "value": selector });
+            loop {
+                match self
+                    .command(
+                        Method::POST,
+                        &format!("{}/element", session),
+                        Some(find.clone()),
+                    )
+                    .await
+                {
+                    Ok(_) => break,
+                    Err(err) if is_no_such_element(&err) => {}
+                    Err(err) => return Err(err),
+                }
+                if started.elapsed() >= self.options.timeout {
+                    bail!(
+                        "{} did not appear within {:?}",
+                        selector,
+                        self.options.timeout
+                    );
+                }
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+        }
+
+        let source = self
+            .command(Method::GET, &format!("{}/source", session), None)
+            .await?;
+        match source {
+            Value::String(html) => Ok(html),
+            _ => bail!("WebDriver returned no page source"),
+        }
+    }
+
+    /// Send a WebDriver command and return its `value`
+    async fn command(&self, method: Method, path: &str, body: Option<Value>) -> Result<Value> {
+        let url = format!(
+            "{}/{}",
+            self.options.webdriver_url.trim_end_matches('/'),
+            path
+        );
+        let mut request = self.client.request(method, &url);
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+        let response = request
+            .send()
+            .await
+            .with_context(|| format!("WebDriver server at {} unreachable", url))?;
+        let status = response.status();
+        let mut reply: Value = response.json().await.unwrap_or(Value::Null);
+        let value = reply.get_mut("value").map(Value::take).unwrap_or_default();
+        if status.is_success() {
+            return Ok(value);
+        }
+        Err(WebDriverError {
+            status,
+            error: value["error"]
+                .as_str()
+                .unwrap_or("unknown error")
+                .to_string(),
+            message: value["message"].as_str().unwrap_or_default().to_string(),
+        }
+        .into())
+    }
+}
+
+/// Error reply of a WebDriver command
+#[derive(Debug, thiserror::Error)]
+#[error("WebDriver {error} ({status}): {message}")]
+struct WebDriverError {
+    status: StatusCode,
+    /// Error code, e.g. `no such element`
+    error: String,
+    message: String,
+}
+
+fn is_no_such_element(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<WebDriverError>()
+        .is_some_and(|err| err.error == "no such element")
+}
@@ -0,0 +1,236 @@
+use crate::diff::{AnalysisDiff, Change};
+use crate::reporter::{fit, Depth, RenderOptions};
+use colored::*;
+use console::measure_text_width;
+use std::fmt::Write;
+
+/// Columns are not shrunk below this many characters
+const MIN_COLUMN_WIDTH: usize = 12;
+
+/// An [`AnalysisDiff`] side by side: the old result on the left, the new
+/// one on the right
+///
+/// Lines start with `+` for additions (green), `-` for removals (red) and
+/// `~` for changed counts (yellow), which are followed by the difference.
+/// Unlike the other reporters it renders a diff rather than a result, so
+/// it doesn't implement [`Reporter`](crate::reporter::Reporter).
+///
+/// # Example
+/// ```
+/// # use ferret::analyzer::stream::StreamAnalyzer;
+/// use ferret::diff::AnalysisDiff;
+/// use ferret::reporter::{DiffDisplay, RenderOptions};
+///
+/// let analyzer = StreamAnalyzer::new(10);
+/// let old = analyzer.analyze_string("<p>a</p>")?;
+/// let new = analyzer.analyze_string("<p>a</p><p>b</p>")?;
+/// let text = DiffDisplay::default()
+///     .render(&AnalysisDiff::new(&old, &new), &RenderOptions::default().with_color(false));
+/// assert!(text.contains("~ p (1)        │ p (2)        +1"));
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub struct DiffDisplay {
+    /// Column headings, e.g. two URLs or run dates
+    pub old_label: String,
+    pub new_label: String,
+    /// Also list tags and attributes whose counts didn't change
+    pub include_unchanged: bool,
+}
+
+impl Default for DiffDisplay {
+    fn default() -> Self {
+        Self {
+            old_label: "old".to_string(),
+            new_label: "new".to_string(),
+            include_unchanged: false,
+        }
+    }
+}
+
+struct Row {
+    change: Change,
+    old: String,
+    new: String,
+    delta: String,
+}
+
+impl Row {
+    fn new(change: Change, name: &str, old_count: usize, new_count: usize) -> Self {
+        let delta = new_count as i64 - old_count as i64;
+        Self {
+            change,
+            old: match change {
+                Change::Added => String::new(),
+                _ => format!("{} ({})", name, old_count),
+            },
+            new: match change {
+                Change::Removed => String::new(),
+                _ => format!("{} ({})", name, new_count),
+            },
+            delta: match change {
+                Change::Changed if delta != 0 => format!("{:+}", delta),
+                _ => String::new(),
+            },
+        }
+    }
+}
+
+impl DiffDisplay {
+    pub fn with_labels(mut self, old: impl Into<String>, new: impl Into<String>) -> Self {
+        self.old_label = old.into();
+        self.new_label = new.into();
+        self
+    }
+
+    pub fn with_unchanged(mut self, include_unchanged: bool) -> Self {
+        self.include_unchanged = include_unchanged;
+        self
+    }
+
+    /// Render `diff`; `options.depth` decides whether attributes are
+    /// listed and `options.width` limits the line length
+    pub fn render(&self, diff: &AnalysisDiff, options: &RenderOptions) -> String {
+        let shown = |change: Change| self.include_unchanged || change != Change::Unchanged;
+        let mut rows = Vec::new();
+        for tag in diff.tags.iter().filter(|tag| shown(tag.change)) {
+            rows.push(Row::new(
+                tag.change,
+                &tag.name,
+                tag.old_count,
+                tag.new_count,
+            ));
+            if options.depth < Depth::Attributes {
+                continue;
+            }
+            for attr in tag.attributes.iter().filter(|attr| shown(attr.change)) {
+                let name = format!("  @{}", attr.name);
+                rows.push(Row::new(attr.change, &name, attr.old_count, attr.new_count));
+            }
+        }
+        let depth_change = if diff.old_max_depth == diff.new_max_depth {
+            Change::Unchanged
+        } else {
+            Change::Changed
+        };
+        if shown(depth_change) {
+            rows.push(Row::new(
+                depth_change,
+                "max depth",
+                diff.old_max_depth,
+                diff.new_max_depth,
+            ));
+        }
+
+        let column = |label: &str, cell: fn(&Row) -> &str| {
+            rows.iter()
+                .map(|row| measure_text_width(cell(row)))
+                .chain([measure_text_width(label), MIN_COLUMN_WIDTH])
+                .max()
+                .unwrap_or(0)
+        };
+        let mut old_width = column(&self.old_label, |row| &row.old);
+        let mut new_width = column(&self.new_label, |row| &row.new);
+        if let Some(width) = options.width {
+            let delta_width = rows.iter().map(|row| row.delta.len()).max().unwrap_or(0);
+            let fixed = 2 + 3 + 1 + delta_width;
+            while fixed + old_width + new_width > width {
+                let widest = if old_width > new_width {
+                    &mut old_width
+                } else {
+                    &mut new_width
+                };
+                if *widest <= MIN_COLUMN_WIDTH {
+                    break;
+                }
+                *widest -= 1;
+            }
+        }
+
+        let mut out = String::new();
+        let header = format!("  {} │ {}", fit(&self.old_label, old_width), self.new_label);
+        writeln!(out, "{}", options.paint(header.trim_end().bold())).unwrap();
+        if rows.is_empty() {
+            writeln!(out, "No structural differences").unwrap();
+        }
+        for row in &rows {
+            let (marker, color): (char, fn(String) -> ColoredString) = match row.change {
+                Change::Added => ('+', |line| line.green()),
+                Change::Removed => ('-', |line| line.red()),
+                Change::Changed => ('~', |line| line.yellow()),
+                Change::Unchanged => (' ', |line| line.normal()),
+            };
+            let line = format!(
+                "{} {} │ {} {}",
+                marker,
+                fit(&row.old, old_width),
+                fit(&row.new, new_width),
+                row.delta
+            );
+            writeln!(out, "{}", options.paint(color(line.trim_end().to_string()))).unwrap();
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::stream::StreamAnalyzer;
+    use crate::analyzer::AnalysisResult;
+
+    fn diff() -> AnalysisDiff {
+        let analyzer = StreamAnalyzer::new(10);
+        let old = analyzer
+            .analyze_string(r#"<div id="a"><span>1</span><span>2</span></div><p>x</p>"#)
+            .unwrap();
+        let new = analyzer
+            .analyze_string(
+                r#"<div id="a" class="b"><section><span>1</span></section></div><p>x</p>"#,
+            )
+            .unwrap();
+        AnalysisDiff::new(&old, &new)
+    }
+
+    #[test]
+    fn test_diff_display() {
+        let options = RenderOptions::default().with_color(false);
+        let text = DiffDisplay::default()
+            .with_labels("before", "after")
+            .render(&diff(), &options);
+        assert_eq!(
+            text.lines().collect::<Vec<_>>(),
+            [
+                "  before        │ after",
+                "~ div (1)       │ div (1)",
+                "+               │   @class (1)",
+                "+               │ section (1)",
+                "~ span (2)      │ span (1)      -1",
+                "~ max depth (2) │ max depth (3) +1",
+            ]
+        );
+
+        let text = DiffDisplay::default()
+            .with_unchanged(true)
+            .render(&diff(), &options.clone().with_depth(Depth::Tags));
+        assert!(text.contains("\n  p (1)         │ p (1)\n"), "{}", text);
+        assert!(!text.contains("@"));
+
+        // Long names are cut off to fit, down to a minimum width
+        let text = DiffDisplay::default().render(&diff(), &options.clone().with_width(32));
+        assert!(
+            text.ends_with("~ max depth (… │ max depth (… +1\n"),
+            "{}",
+            text
+        );
+        let text = DiffDisplay::default().render(&diff(), &options.clone().with_width(10));
+        assert!(
+            text.ends_with("~ max depth (… │ max depth (… +1\n"),
+            "{}",
+            text
+        );
+
+        let same = AnalysisDiff::new(&AnalysisResult::default(), &AnalysisResult::default());
+        let text = DiffDisplay::default().render(&same, &options);
+        assert!(text.ends_with("No structural differences\n"));
+    }
+}
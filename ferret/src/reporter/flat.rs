@@ -0,0 +1,151 @@
+use crate::analyzer::AnalysisResult;
+use crate::reporter::{fit, RenderOptions, Reporter};
+use console::measure_text_width;
+use std::fmt::Write;
+
+const HEADERS: [&str; 4] = ["TAG", "COUNT", "ATTRIBUTE", "ATTR COUNT"];
+
+/// Name columns are not shrunk below this many characters
+const MIN_NAME_WIDTH: usize = 8;
+
+/// One row per tag and attribute, aligned in columns
+///
+/// Columns are as wide as their longest entry. With
+/// [`RenderOptions::width`] set, the tag and attribute columns shrink to
+/// fit and long names are cut off with `…`.
+pub struct FlatDisplay;
+
+impl Reporter for FlatDisplay {
+    fn render(&self, report: &AnalysisResult, options: &RenderOptions) -> String {
+        let mut out = String::new();
+        writeln!(out, "📦 Files analyzed: {}", report.files_analyzed).unwrap();
+
+        let total = report.total_elements();
+        let mut rows = Vec::new();
+        for tag in options.tags(report) {
+            let sorted_attrs = options.attributes(tag);
+            if sorted_attrs.is_empty() {
+                rows.push([
+                    tag.name.clone(),
+                    options.count(tag.count, total),
+                    "-".to_string(),
+                    "-".to_string(),
+                ]);
+            }
+            for (i, attr) in sorted_attrs.iter().enumerate() {
+                let (name, count) = if i == 0 {
+                    (tag.name.clone(), options.count(tag.count, total))
+                } else {
+                    (String::new(), String::new())
+                };
+                rows.push([name, count, attr.name.clone(), attr.count.to_string()]);
+            }
+        }
+
+        let widths = column_widths(&rows, options.width);
+        let line = |out: &mut String, cells: [&str; 4]| {
+            let cells: Vec<_> = cells
+                .iter()
+                .zip(widths)
+                .map(|(cell, width)| fit(cell, width))
+                .collect();
+            writeln!(out, "{}", cells.join(" ").trim_end()).unwrap();
+        };
+
+        line(&mut out, HEADERS);
+        writeln!(out, "{}", "-".repeat(widths.iter().sum::<usize>() + 3)).unwrap();
+        for row in &rows {
+            line(&mut out, [&row[0], &row[1], &row[2], &row[3]]);
+        }
+        out
+    }
+}
+
+/// Widths of the four columns, shrinking the widest name column first
+/// until a line fits in `max_width`
+fn column_widths(rows: &[[String; 4]], max_width: Option<usize>) -> [usize; 4] {
+    let mut widths = HEADERS.map(measure_text_width);
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(measure_text_width(cell));
+        }
+    }
+
+    if let Some(max_width) = max_width {
+        let fixed = widths[1] + widths[3] + 3;
+        while fixed + widths[0] + widths[2] > max_width {
+            let widest = if widths[0] > widths[2] { 0 } else { 2 };
+            if widths[widest] <= MIN_NAME_WIDTH {
+                break;
+            }
+            widths[widest] -= 1;
+        }
+    }
+    widths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::stream::StreamAnalyzer;
+    use crate::reporter::Depth;
+
+    #[test]
+    fn test_flat() {
+        let report = StreamAnalyzer::new(10)
+            .analyze_string(r#"<a href="x" id="y">1</a><a href="z">2</a><br/>"#)
+            .unwrap();
+        let text = FlatDisplay.render(&report, &RenderOptions::default());
+        assert_eq!(
+            text.lines().collect::<Vec<_>>(),
+            [
+                "📦 Files analyzed: 1",
+                "TAG COUNT ATTRIBUTE ATTR COUNT",
+                "------------------------------",
+                "a   2     href      2",
+                "          id        1",
+                "br  1     -         -",
+            ]
+        );
+
+        let options = RenderOptions::default().with_depth(Depth::Tags);
+        let text = FlatDisplay.render(&report, &options);
+        assert_eq!(text.lines().count(), 5);
+    }
+
+    #[test]
+    fn test_flat_fits_width() {
+        let report = StreamAnalyzer::new(10)
+            .analyze_string(
+                r#"<custom-element-with-a-long-name data-attribute-with-an-even-longer-name="1">x</custom-element-with-a-long-name>"#,
+            )
+            .unwrap();
+
+        // Columns line up however long the names are
+        let text = FlatDisplay.render(&report, &RenderOptions::default());
+        let lines: Vec<_> = text.lines().collect();
+        assert_eq!(
+            lines[1].find("ATTRIBUTE"),
+            lines[3].find("data-attribute"),
+            "{}",
+            text
+        );
+
+        let options = RenderOptions::default().with_width(60);
+        let text = FlatDisplay.render(&report, &options);
+        for line in text.lines().skip(1) {
+            assert!(measure_text_width(line) <= 60, "{}", line);
+        }
+        assert_eq!(measure_text_width(text.lines().nth(1).unwrap()), 60);
+        assert!(
+            text.contains("custom-element-with-… 1     data-attribute-with-… 1"),
+            "{}",
+            text
+        );
+
+        // Names keep a few characters however narrow the terminal
+        let options = RenderOptions::default().with_width(10);
+        let text = FlatDisplay.render(&report, &options);
+        assert!(text.contains("custom-… 1     data-at… 1"), "{}", text);
+    }
+}
@@ -0,0 +1,149 @@
+use crate::analyzer::AnalysisResult;
+use crate::reporter::{RenderOptions, Reporter};
+use colored::*;
+use console::measure_text_width;
+use std::fmt::Write;
+
+/// Tags charted when [`RenderOptions::max_tags`] is not set
+const TOP_TAGS: usize = 20;
+
+/// Bar length for the most frequent tag without [`RenderOptions::width`]
+const BAR_WIDTH: usize = 40;
+
+/// Partial blocks for eighths of a character cell
+const EIGHTHS: [char; 8] = [' ', '▏', '▎', '▍', '▌', '▋', '▊', '▉'];
+
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Tag counts as bar chart and the depth distribution as a sparkline
+///
+/// Bars are proportional to the most frequent tag shown and drawn in
+/// eighths of a character cell, so small differences stay visible.
+pub struct HistogramDisplay;
+
+impl Reporter for HistogramDisplay {
+    fn render(&self, report: &AnalysisResult, options: &RenderOptions) -> String {
+        let mut out = String::new();
+        writeln!(out, "📦 Files analyzed: {}", report.files_analyzed).unwrap();
+
+        let options = RenderOptions {
+            max_tags: Some(options.max_tags.unwrap_or(TOP_TAGS)),
+            ..options.clone()
+        };
+        let tags = options.tags(report);
+        let name_width = tags
+            .iter()
+            .map(|tag| measure_text_width(&tag.name))
+            .max()
+            .unwrap_or(0);
+        let max_count = tags.iter().map(|tag| tag.count).max().unwrap_or(0);
+        let total = report.total_elements();
+        let labels: Vec<_> = tags
+            .iter()
+            .map(|tag| options.count(tag.count, total))
+            .collect();
+        let count_width = labels.iter().map(|label| label.len()).max().unwrap_or(0);
+        let bar_width = options.width.map_or(BAR_WIDTH, |width| {
+            width.saturating_sub(name_width + count_width + 2).max(1)
+        });
+
+        for (tag, label) in tags.iter().zip(labels) {
+            let padding = name_width - measure_text_width(&tag.name);
+            writeln!(
+                out,
+                "{}{} {} {}",
+                options.paint(tag.name.bright_cyan()),
+                " ".repeat(padding),
+                options.paint(bar(tag.count, max_count, bar_width).normal()),
+                options.paint(label.yellow())
+            )
+            .unwrap();
+        }
+
+        if !report.depth_counts.is_empty() {
+            writeln!(
+                out,
+                "Depth 1-{} {}",
+                report.depth_counts.len(),
+                options.paint(sparkline(&report.depth_counts).bright_cyan())
+            )
+            .unwrap();
+        }
+        out
+    }
+}
+
+/// Bar for `value` where `max` fills `width` cells
+fn bar(value: usize, max: usize, width: usize) -> String {
+    if max == 0 {
+        return String::new();
+    }
+    let eighths = (value as u128 * width as u128 * 8).div_ceil(max as u128) as usize;
+    let mut bar = "█".repeat(eighths / 8);
+    let rest = eighths % 8;
+    if rest > 0 {
+        bar.push(EIGHTHS[rest]);
+    }
+    bar
+}
+
+/// One block per value, as high as its share of the largest value;
+/// zeros are blank
+fn sparkline(values: &[usize]) -> String {
+    let max = values.iter().copied().max().unwrap_or(0);
+    values
+        .iter()
+        .map(|&value| {
+            if value == 0 {
+                ' '
+            } else {
+                SPARKS[(value * SPARKS.len()).div_ceil(max) - 1]
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::stream::StreamAnalyzer;
+
+    #[test]
+    fn test_bar() {
+        assert_eq!(bar(10, 10, 4), "████");
+        assert_eq!(bar(5, 10, 4), "██");
+        assert_eq!(bar(3, 10, 4), "█▎");
+        // Anything counted gets at least an eighth
+        assert_eq!(bar(1, 1000, 4), "▏");
+        assert_eq!(bar(0, 10, 4), "");
+        assert_eq!(bar(0, 0, 4), "");
+    }
+
+    #[test]
+    fn test_sparkline() {
+        assert_eq!(sparkline(&[1, 4, 8, 0, 2]), "▁▄█ ▂");
+        assert_eq!(sparkline(&[3]), "█");
+        assert_eq!(sparkline(&[]), "");
+    }
+
+    #[test]
+    fn test_histogram() {
+        let report = StreamAnalyzer::new(10)
+            .analyze_string("<ul><li>1</li><li>2</li><li>3</li><li>4</li></ul><p>x</p>")
+            .unwrap();
+        let options = RenderOptions::default().with_color(false).with_width(13);
+        assert_eq!(
+            HistogramDisplay
+                .render(&report, &options)
+                .lines()
+                .collect::<Vec<_>>(),
+            [
+                "📦 Files analyzed: 1",
+                "li ████████ 4",
+                "p  ██ 1",
+                "ul ██ 1",
+                "Depth 1-2 ▄█",
+            ]
+        );
+    }
+}
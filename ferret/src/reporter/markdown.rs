@@ -0,0 +1,155 @@
+use crate::analyzer::AnalysisResult;
+use crate::reporter::{RenderOptions, Reporter};
+use std::fmt::Write;
+
+/// How [`MarkdownDisplay`] lays out a report
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MarkdownLayout {
+    /// The flat view as a table
+    #[default]
+    Table,
+    /// The tree view as nested bullet lists
+    List,
+}
+
+/// Markdown for pasting into pull requests, issues and wikis
+///
+/// Names and values are escaped, so they show up literally instead of
+/// turning into links, emphasis or extra table columns.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MarkdownDisplay {
+    pub layout: MarkdownLayout,
+}
+
+impl MarkdownDisplay {
+    pub fn new(layout: MarkdownLayout) -> Self {
+        Self { layout }
+    }
+}
+
+impl Reporter for MarkdownDisplay {
+    fn render(&self, report: &AnalysisResult, options: &RenderOptions) -> String {
+        let mut out = String::new();
+        writeln!(out, "Files analyzed: {}", report.files_analyzed).unwrap();
+        writeln!(out).unwrap();
+        let total = report.total_elements();
+        match self.layout {
+            MarkdownLayout::Table => {
+                writeln!(out, "| Tag | Count | Attribute | Attr Count |").unwrap();
+                writeln!(out, "| --- | ---: | --- | ---: |").unwrap();
+                for tag in options.tags(report) {
+                    let attributes = options.attributes(tag);
+                    if attributes.is_empty() {
+                        let count = options.count(tag.count, total);
+                        writeln!(out, "| {} | {} | | |", escape(&tag.name), count).unwrap();
+                    }
+                    for (i, attr) in attributes.iter().enumerate() {
+                        let (name, count) = if i == 0 {
+                            (escape(&tag.name), options.count(tag.count, total))
+                        } else {
+                            (String::new(), String::new())
+                        };
+                        writeln!(
+                            out,
+                            "| {} | {} | {} | {} |",
+                            name,
+                            count,
+                            escape(&attr.name),
+                            attr.count
+                        )
+                        .unwrap();
+                    }
+                }
+            }
+            MarkdownLayout::List => {
+                for tag in options.tags(report) {
+                    let count = options.count(tag.count, total);
+                    writeln!(out, "- **{}** ({})", escape(&tag.name), count).unwrap();
+                    for attr in options.attributes(tag) {
+                        writeln!(out, "  - @{} ({})", escape(&attr.name), attr.count).unwrap();
+                        for (value, &count) in options.values(attr) {
+                            let count = options.count(count, attr.count);
+                            writeln!(out, "    - {} ({})", escape(value), count).unwrap();
+                        }
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+/// `text` with Markdown syntax characters backslash-escaped and line
+/// breaks, which would end a table row or list item, turned into spaces
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '|' | '#' | '!' | '~' | '&' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' | '\r' => escaped.push(' '),
+            c => escaped.push(c),
+        }
+    }
+    if escaped.is_empty() {
+        // An empty value would leave an empty list item
+        escaped.push_str("\"\"");
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::stream::StreamAnalyzer;
+
+    fn report() -> AnalysisResult {
+        StreamAnalyzer::new(10)
+            .analyze_string(
+                r#"<a href="/a|b" title="*x*">1</a><a href="/c">2</a><img alt=""><br/>"#,
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn test_table() {
+        let text = MarkdownDisplay::default().render(&report(), &RenderOptions::default());
+        assert_eq!(
+            text.lines().collect::<Vec<_>>(),
+            [
+                "Files analyzed: 1",
+                "",
+                "| Tag | Count | Attribute | Attr Count |",
+                "| --- | ---: | --- | ---: |",
+                "| a | 2 | href | 2 |",
+                "|  |  | title | 1 |",
+                "| br | 1 | | |",
+                "| img | 1 | alt | 1 |",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_list() {
+        let text = MarkdownDisplay::new(MarkdownLayout::List)
+            .render(&report(), &RenderOptions::default().with_max_values(1));
+        assert_eq!(
+            text.lines().collect::<Vec<_>>(),
+            [
+                "Files analyzed: 1",
+                "",
+                "- **a** (2)",
+                "  - @href (2)",
+                "    - /a\\|b (1)",
+                "  - @title (1)",
+                "    - \\*x\\* (1)",
+                "- **br** (1)",
+                "- **img** (1)",
+                "  - @alt (1)",
+                "    - \"\" (1)",
+            ]
+        );
+    }
+}
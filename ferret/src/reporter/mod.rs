@@ -1,140 +1,279 @@
-use crate::analyzer::AnalysisResult;
-use colored::*;
-use std::fmt::Write;
-
-pub struct TreeDisplay;
-
-impl TreeDisplay {
-    pub fn render(report: &AnalysisResult) -> String {
-        let mut out = String::new();
-        writeln!(out, "📦 Files analyzed: {}", report.files_analyzed).unwrap();
-
-        // Sort tags by count desc
-        let mut sorted_tags: Vec<_> = report.tags.values().collect();
-        sorted_tags.sort_by(|a, b| b.count.cmp(&a.count));
-
-        for (i, tag) in sorted_tags.iter().enumerate() {
-            let is_last_tag = i == sorted_tags.len() - 1;
-            let tag_prefix = if is_last_tag {
-                "└── "
-            } else {
-                "├── "
-            };
+//! Plain-text reports for terminals and `text/plain` responses
+
+use crate::analyzer::{percentage, AnalysisResult, AttributeStats, TagStats};
+use crate::rules::Rule;
+use colored::ColoredString;
+use console::{measure_text_width, truncate_str};
+use std::cmp::Ordering;
+
+mod diff;
+mod flat;
+mod histogram;
+mod markdown;
+mod summary;
+mod tree;
+
+pub use diff::DiffDisplay;
+pub use flat::FlatDisplay;
+pub use histogram::HistogramDisplay;
+pub use markdown::{MarkdownDisplay, MarkdownLayout};
+pub use summary::SummaryDisplay;
+pub use tree::TreeDisplay;
+
+/// Renders an analysis result as text
+///
+/// # Example
+/// ```
+/// use ferret::analyzer::stream::StreamAnalyzer;
+/// use ferret::reporter::{reporter, RenderOptions};
+///
+/// let result = StreamAnalyzer::new(10).analyze_string("<ul><li>1</li></ul>")?;
+/// let options = RenderOptions::default().with_color(false);
+/// let text = reporter("tree").unwrap().render(&result, &options);
+/// assert!(text.contains("├── li (1)"));
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub trait Reporter {
+    fn render(&self, report: &AnalysisResult, options: &RenderOptions) -> String;
+}
+
+/// Names accepted by [`reporter`]
+pub const REPORTERS: &[&str] = &[
+    "tree",
+    "flat",
+    "summary",
+    "histogram",
+    "markdown",
+    "markdown-list",
+];
+
+/// Reporter called `name`, one of [`REPORTERS`]
+pub fn reporter(name: &str) -> Option<Box<dyn Reporter + Send + Sync>> {
+    match name {
+        "tree" => Some(Box::new(TreeDisplay)),
+        "flat" => Some(Box::new(FlatDisplay)),
+        "summary" => Some(Box::new(SummaryDisplay)),
+        "histogram" => Some(Box::new(HistogramDisplay)),
+        "markdown" => Some(Box::new(MarkdownDisplay::new(MarkdownLayout::Table))),
+        "markdown-list" => Some(Box::new(MarkdownDisplay::new(MarkdownLayout::List))),
+        _ => None,
+    }
+}
+
+/// What tags, and attributes within a tag, are sorted by
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortKey {
+    #[default]
+    Count,
+    Name,
+    /// Number of distinct attributes of a tag; attributes themselves are
+    /// sorted by count
+    Attributes,
+}
 
-            writeln!(
-                out,
-                "{}{}{}",
-                tag_prefix,
-                tag.name.bright_cyan(),
-                format!(" ({})", tag.count).yellow()
-            )
-            .unwrap();
-
-            // Sort attributes by count desc
-            let mut sorted_attrs: Vec<_> = tag.attributes.values().collect();
-            sorted_attrs.sort_by(|a, b| b.count.cmp(&a.count));
-
-            let child_indent = if is_last_tag { "    " } else { "│   " };
-
-            for (j, attr) in sorted_attrs.iter().enumerate() {
-                let is_last_attr = j == sorted_attrs.len() - 1;
-                let attr_prefix = if is_last_attr {
-                    "└── "
-                } else {
-                    "├── "
-                };
-
-                writeln!(
-                    out,
-                    "{}{}@{}{}",
-                    child_indent,
-                    attr_prefix,
-                    attr.name,
-                    format!(" ({})", attr.count).dimmed()
-                )
-                .unwrap();
-
-                // Print top values
-                let val_indent = if is_last_attr { "    " } else { "│   " };
-                let full_val_indent = format!("{}{}", child_indent, val_indent);
-
-                let mut sorted_vals: Vec<_> = attr.value_counts.iter().collect();
-                sorted_vals.sort_by(|a, b| b.1.cmp(a.1));
-
-                for (k, (val, count)) in sorted_vals.iter().take(5).enumerate() {
-                    let is_last_val = k == sorted_vals.len().min(5) - 1;
-                    let val_prefix = if is_last_val {
-                        "└── "
-                    } else {
-                        "├── "
-                    };
-
-                    writeln!(
-                        out,
-                        "{}{}{} {} ({})",
-                        full_val_indent,
-                        val_prefix,
-                        "──".dimmed(),
-                        val,
-                        count
-                    )
-                    .unwrap();
-                }
-            }
+impl SortKey {
+    /// Order used unless [`RenderOptions::order`] is set: names A to Z,
+    /// numbers largest first
+    pub fn default_order(self) -> SortOrder {
+        match self {
+            SortKey::Name => SortOrder::Ascending,
+            SortKey::Count | SortKey::Attributes => SortOrder::Descending,
         }
-        out
     }
 }
 
-pub struct FlatDisplay;
-
-impl FlatDisplay {
-    pub fn render(report: &AnalysisResult) -> String {
-        let mut out = String::new();
-        writeln!(out, "📦 Files analyzed: {}", report.files_analyzed).unwrap();
-
-        let mut sorted_tags: Vec<_> = report.tags.values().collect();
-        sorted_tags.sort_by(|a, b| b.count.cmp(&a.count));
-
-        writeln!(
-            out,
-            "{:<20} {:<10} {:<30} {:<10}",
-            "TAG", "COUNT", "ATTRIBUTE", "ATTR COUNT"
-        )
-        .unwrap();
-        writeln!(out, "{}", "-".repeat(70)).unwrap();
-
-        for tag in sorted_tags {
-            if tag.attributes.is_empty() {
-                writeln!(
-                    out,
-                    "{:<20} {:<10} {:<30} {:<10}",
-                    tag.name, tag.count, "-", "-"
-                )
-                .unwrap();
-            } else {
-                let mut sorted_attrs: Vec<_> = tag.attributes.values().collect();
-                sorted_attrs.sort_by(|a, b| b.count.cmp(&a.count));
-
-                for (i, attr) in sorted_attrs.iter().enumerate() {
-                    if i == 0 {
-                        writeln!(
-                            out,
-                            "{:<20} {:<10} {:<30} {:<10}",
-                            tag.name, tag.count, attr.name, attr.count
-                        )
-                        .unwrap();
-                    } else {
-                        writeln!(
-                            out,
-                            "{:<20} {:<10} {:<30} {:<10}",
-                            "", "", attr.name, attr.count
-                        )
-                        .unwrap();
-                    }
-                }
-            }
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortOrder {
+    Ascending,
+    Descending,
+}
+
+/// How far below each tag a report goes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Depth {
+    Tags,
+    Attributes,
+    /// Attributes and their most frequent values
+    #[default]
+    Values,
+}
+
+/// Options shared by all reporters
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    /// Ties are broken by name, so reports are stable
+    pub sort: SortKey,
+    /// Defaults to `sort.default_order()`
+    pub order: Option<SortOrder>,
+    /// Show only the first tags in `sort` order
+    pub max_tags: Option<usize>,
+    /// Show only the first attributes of each tag in `sort` order
+    pub max_attributes: Option<usize>,
+    /// Values shown per attribute, most frequent first
+    pub max_values: usize,
+    /// Use ANSI colors; `colored` still turns them off when `NO_COLOR` is
+    /// set or stdout is not a terminal
+    pub color: bool,
+    pub depth: Depth,
+    /// Longest line in columns for table reporters, e.g. the terminal
+    /// width; `None` sizes columns to their content
+    pub width: Option<usize>,
+    /// Show tag counts as a share of all elements and value counts as a
+    /// share of the attribute's occurrences next to the counts
+    pub percentages: bool,
+    /// Rules whose violations the summary lists
+    pub rules: Vec<Rule>,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            sort: SortKey::default(),
+            order: None,
+            max_tags: None,
+            max_attributes: None,
+            max_values: 5,
+            color: true,
+            depth: Depth::default(),
+            width: None,
+            percentages: false,
+            rules: Vec::new(),
         }
-        out
+    }
+}
+
+impl RenderOptions {
+    pub fn with_sort(mut self, sort: SortKey) -> Self {
+        self.sort = sort;
+        self
+    }
+
+    pub fn with_order(mut self, order: SortOrder) -> Self {
+        self.order = Some(order);
+        self
+    }
+
+    pub fn with_max_tags(mut self, max_tags: usize) -> Self {
+        self.max_tags = Some(max_tags);
+        self
+    }
+
+    pub fn with_max_attributes(mut self, max_attributes: usize) -> Self {
+        self.max_attributes = Some(max_attributes);
+        self
+    }
+
+    pub fn with_max_values(mut self, max_values: usize) -> Self {
+        self.max_values = max_values;
+        self
+    }
+
+    pub fn with_color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_depth(mut self, depth: Depth) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    pub fn with_width(mut self, width: usize) -> Self {
+        self.width = Some(width);
+        self
+    }
+
+    pub fn with_percentages(mut self, percentages: bool) -> Self {
+        self.percentages = percentages;
+        self
+    }
+
+    pub fn with_rules(mut self, rules: Vec<Rule>) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    /// Tags of `report` to show, in order
+    fn tags<'a>(&self, report: &'a AnalysisResult) -> Vec<&'a TagStats> {
+        let mut tags: Vec<_> = report.tags.values().collect();
+        tags.sort_by(|a, b| {
+            let ordering = match self.sort {
+                SortKey::Count => a.count.cmp(&b.count),
+                SortKey::Name => a.name.cmp(&b.name),
+                SortKey::Attributes => a.attributes.len().cmp(&b.attributes.len()),
+            };
+            self.directed(ordering).then_with(|| a.name.cmp(&b.name))
+        });
+        if let Some(max_tags) = self.max_tags {
+            tags.truncate(max_tags);
+        }
+        tags
+    }
+
+    /// Attributes of `tag` to show, in order
+    fn attributes<'a>(&self, tag: &'a TagStats) -> Vec<&'a AttributeStats> {
+        if self.depth < Depth::Attributes {
+            return Vec::new();
+        }
+        let mut attributes: Vec<_> = tag.attributes.values().collect();
+        attributes.sort_by(|a, b| {
+            let ordering = match self.sort {
+                SortKey::Count | SortKey::Attributes => a.count.cmp(&b.count),
+                SortKey::Name => a.name.cmp(&b.name),
+            };
+            self.directed(ordering).then_with(|| a.name.cmp(&b.name))
+        });
+        if let Some(max_attributes) = self.max_attributes {
+            attributes.truncate(max_attributes);
+        }
+        attributes
+    }
+
+    /// `ordering` of two items, ascending, turned into the requested order
+    fn directed(&self, ordering: Ordering) -> Ordering {
+        match self.order.unwrap_or(self.sort.default_order()) {
+            SortOrder::Ascending => ordering,
+            SortOrder::Descending => ordering.reverse(),
+        }
+    }
+
+    /// Values of `attribute` to show with their counts, most frequent first
+    fn values<'a>(&self, attribute: &'a AttributeStats) -> Vec<(&'a String, &'a usize)> {
+        if self.depth < Depth::Values {
+            return Vec::new();
+        }
+        let mut values: Vec<_> = attribute.value_counts.iter().collect();
+        values.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+        values.truncate(self.max_values);
+        values
+    }
+
+    /// `count`, followed by its share of `total` when percentages are on,
+    /// e.g. `120, 34.5%`
+    fn count(&self, count: usize, total: usize) -> String {
+        if self.percentages {
+            format!("{}, {:.1}%", count, percentage(count, total))
+        } else {
+            count.to_string()
+        }
+    }
+
+    /// `text` with its styles, or plain when colors are off
+    fn paint(&self, text: ColoredString) -> String {
+        if self.color {
+            text.to_string()
+        } else {
+            text.input
+        }
+    }
+}
+
+/// `cell` padded to `width` columns, or cut off with `…` if wider
+fn fit(cell: &str, width: usize) -> String {
+    let cell_width = measure_text_width(cell);
+    if cell_width > width {
+        truncate_str(cell, width, "…").into_owned()
+    } else {
+        format!("{}{}", cell, " ".repeat(width - cell_width))
     }
 }
@@ -0,0 +1,359 @@
+use crate::analyzer::AnalysisResult;
+use crate::reporter::{RenderOptions, Reporter};
+use crate::rules;
+use colored::*;
+use std::fmt::Write;
+
+/// Tags listed when [`RenderOptions::max_tags`] is not set
+const TOP_TAGS: usize = 5;
+
+/// Headline numbers only, for runs over many files
+///
+/// Shows the number of elements, distinct tags and attributes, the
+/// maximum depth, the parse error count and the most frequent tags, then
+/// the link counts of crawls with link checking, the findings of the
+/// third-party, security, images, perf, i18n, icons, keywords and css
+/// sections if enabled, and the violations of [`RenderOptions::rules`] if
+/// any are set.
+pub struct SummaryDisplay;
+
+impl Reporter for SummaryDisplay {
+    fn render(&self, report: &AnalysisResult, options: &RenderOptions) -> String {
+        let mut out = String::new();
+        writeln!(out, "📦 Files analyzed: {}", report.files_analyzed).unwrap();
+
+        let figures = [
+            ("Elements", report.total_elements()),
+            ("Distinct tags", report.tags.len()),
+            ("Distinct attributes", report.distinct_attributes()),
+            ("Max depth", report.max_depth),
+            ("Parse errors", report.parse_errors.len()),
+        ];
+        for (label, value) in figures {
+            let label = format!("{}:", label);
+            writeln!(
+                out,
+                "{:<21}{}",
+                label,
+                options.paint(value.to_string().yellow())
+            )
+            .unwrap();
+        }
+
+        let options = RenderOptions {
+            max_tags: Some(options.max_tags.unwrap_or(TOP_TAGS)),
+            ..options.clone()
+        };
+        let total = report.total_elements();
+        let top: Vec<_> = options
+            .tags(report)
+            .iter()
+            .map(|tag| {
+                format!(
+                    "{}{}",
+                    options.paint(tag.name.bright_cyan()),
+                    options.paint(format!(" ({})", options.count(tag.count, total)).yellow())
+                )
+            })
+            .collect();
+        writeln!(out, "{:<21}{}", "Top tags:", top.join(", ")).unwrap();
+
+        if let Some(links) = report.links {
+            let broken = format!("{} broken", links.broken);
+            let broken = if links.broken == 0 {
+                broken.green()
+            } else {
+                broken.red()
+            };
+            writeln!(
+                out,
+                "{:<21}{} ({}, {} redirected)",
+                "Links checked:",
+                options.paint(links.checked.to_string().yellow()),
+                options.paint(broken),
+                links.redirected
+            )
+            .unwrap();
+        }
+
+        if let Some(third_party) = &report.third_party {
+            let mut trackers: Vec<&str> = third_party
+                .trackers()
+                .filter_map(|(_, resources)| resources.tracker.as_deref())
+                .collect();
+            trackers.sort_unstable();
+            trackers.dedup();
+            let trackers = if trackers.is_empty() {
+                String::new()
+            } else {
+                let trackers = format!("trackers: {}", trackers.join(", "));
+                format!(" ({})", options.paint(trackers.red()))
+            };
+            writeln!(
+                out,
+                "{:<21}{}{}",
+                "Third parties:",
+                options.paint(third_party.third_parties().to_string().yellow()),
+                trackers
+            )
+            .unwrap();
+        }
+
+        if let Some(security) = &report.security {
+            let count = security.findings.len().to_string();
+            let count = if security.findings.is_empty() {
+                count.green()
+            } else {
+                count.red()
+            };
+            writeln!(out, "{:<21}{}", "Missing integrity:", options.paint(count)).unwrap();
+            if !security.missing_headers.is_empty() {
+                writeln!(
+                    out,
+                    "{:<21}{}",
+                    "Missing headers:",
+                    options.paint(security.missing_headers.join(", ").red())
+                )
+                .unwrap();
+            }
+        }
+
+        if let Some(images) = report.images {
+            writeln!(
+                out,
+                "{:<21}{} of {} ({} without width/height)",
+                "Responsive images:",
+                options.paint(images.responsive.to_string().yellow()),
+                images.images,
+                images.missing_dimensions
+            )
+            .unwrap();
+        }
+
+        if let Some(perf) = report.perf {
+            writeln!(
+                out,
+                "{:<21}{} scripts, {} stylesheets ({} KB inline)",
+                "Render-blocking:",
+                options.paint(perf.blocking_scripts.to_string().yellow()),
+                options.paint(perf.blocking_stylesheets.to_string().yellow()),
+                (perf.inline_script_bytes + perf.inline_style_bytes).div_ceil(1024)
+            )
+            .unwrap();
+        }
+
+        if let Some(i18n) = &report.i18n {
+            let mut parts = vec![match &i18n.lang {
+                Some(lang) => options.paint(lang.yellow()),
+                None => options.paint("none declared".red()),
+            }];
+            if let Some(detected) = &i18n.detected {
+                parts.push(format!("text in {}", detected));
+            }
+            if !i18n.alternates.is_empty() {
+                parts.push(format!("{} hreflang alternates", i18n.alternates.len()));
+            }
+            if i18n.mismatches > 0 {
+                let mismatched = format!("{} mismatched", i18n.mismatches);
+                parts.push(options.paint(mismatched.red()));
+            }
+            writeln!(out, "{:<21}{}", "Language:", parts.join(", ")).unwrap();
+        }
+
+        if let Some(icons) = &report.icons {
+            let mut parts = vec![
+                format!("{} favicons", icons.favicons().count()),
+                format!("{} apple-touch", icons.apple_touch_icons().count()),
+            ];
+            if icons.manifest.is_some() {
+                parts.push("manifest".to_string());
+            }
+            if !icons.missing.is_empty() {
+                let missing = format!("missing: {}", icons.missing.join(", "));
+                parts.push(options.paint(missing.red()));
+            }
+            writeln!(out, "{:<21}{}", "Icons:", parts.join(", ")).unwrap();
+        }
+
+        if let Some(keywords) = &report.keywords {
+            let top: Vec<String> = keywords
+                .top_words
+                .iter()
+                .take(TOP_TAGS)
+                .map(|keyword| format!("{} ({:.1}%)", keyword.term, keyword.density))
+                .collect();
+            writeln!(out, "{:<21}{}", "Keywords:", top.join(", ")).unwrap();
+        }
+
+        if let Some(css) = &report.css {
+            let count = |names: &[String]| {
+                let count = names.len().to_string();
+                if names.is_empty() {
+                    options.paint(count.green())
+                } else {
+                    options.paint(count.red())
+                }
+            };
+            writeln!(
+                out,
+                "{:<21}{} undefined, {} unused classes; {} undefined, {} unused ids",
+                "CSS cross-check:",
+                count(&css.undefined_classes),
+                count(&css.unused_classes),
+                css.undefined_ids.len(),
+                css.unused_ids.len()
+            )
+            .unwrap();
+        }
+
+        if !options.rules.is_empty() {
+            let violations = rules::check(&options.rules, report);
+            let count = violations.len().to_string();
+            let count = if violations.is_empty() {
+                count.green()
+            } else {
+                count.red()
+            };
+            writeln!(out, "{:<21}{}", "Rule violations:", options.paint(count)).unwrap();
+            for violation in violations {
+                writeln!(out, "  {}", options.paint(violation.to_string().red())).unwrap();
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::section::Section;
+    use crate::analyzer::stream::StreamAnalyzer;
+
+    #[test]
+    fn test_summary() {
+        let report = StreamAnalyzer::new(10)
+            .analyze_string(
+                r#"<ul id="m"><li class="a">1</li><li class="b">2</li></ul><p><a href="x" class="c">y</a></p><br><hr><img src="i"><em>z</em>"#,
+            )
+            .unwrap();
+        let options = RenderOptions::default().with_color(false);
+        assert_eq!(
+            SummaryDisplay
+                .render(&report, &options)
+                .lines()
+                .collect::<Vec<_>>(),
+            [
+                "📦 Files analyzed: 1",
+                "Elements:            9",
+                "Distinct tags:       8",
+                "Distinct attributes: 5",
+                "Max depth:           2",
+                "Parse errors:        0",
+                "Top tags:            li (2), a (1), br (1), em (1), hr (1)",
+            ]
+        );
+
+        let text = SummaryDisplay.render(&report, &options.clone().with_max_tags(1));
+        assert!(text.ends_with("Top tags:            li (2)\n"));
+
+        let checked = AnalysisResult {
+            links: Some(crate::analyzer::LinkCounts {
+                checked: 12,
+                broken: 2,
+                redirected: 3,
+                unreachable: 1,
+            }),
+            ..report.clone()
+        };
+        let text = SummaryDisplay.render(&checked, &options);
+        assert!(text.ends_with("Links checked:       12 (2 broken, 3 redirected)\n"));
+
+        let tracked = StreamAnalyzer::new(10)
+            .with_sections([Section::ThirdParty])
+            .analyze_string(
+                r#"<script src="https://www.googletagmanager.com/gtm.js"></script>
+                   <script src="https://www.google-analytics.com/analytics.js"></script>
+                   <link rel="stylesheet" href="https://fonts.googleapis.com/css">"#,
+            )
+            .unwrap();
+        let text = SummaryDisplay.render(&tracked, &options);
+        assert!(text.ends_with(
+            "Third parties:       3 (trackers: Google Analytics, Google Tag Manager)\n"
+        ));
+
+        let secured = AnalysisResult {
+            security: Some(crate::analyzer::security::SecurityReport {
+                missing_headers: vec!["content-security-policy".to_string()],
+                ..Default::default()
+            }),
+            ..report.clone()
+        };
+        let text = SummaryDisplay.render(&secured, &options);
+        assert!(text
+            .ends_with("Missing integrity:   0\nMissing headers:     content-security-policy\n"));
+
+        let images = StreamAnalyzer::new(10)
+            .with_sections([Section::Images])
+            .analyze_string(r#"<img src="a.jpg" srcset="a.jpg 1x, b.jpg 2x"><img src="c.jpg">"#)
+            .unwrap();
+        let text = SummaryDisplay.render(&images, &options);
+        assert!(text.ends_with("Responsive images:   1 of 2 (2 without width/height)\n"));
+
+        let perf = StreamAnalyzer::new(10)
+            .with_sections([Section::Perf])
+            .analyze_string(r#"<script src="a.js"></script><script>go()</script>"#)
+            .unwrap();
+        let text = SummaryDisplay.render(&perf, &options);
+        assert!(text.ends_with("Render-blocking:     1 scripts, 0 stylesheets (1 KB inline)\n"));
+
+        let i18n = StreamAnalyzer::new(10)
+            .with_sections([Section::I18n])
+            .analyze_string(
+                r#"<html lang="de"><p>This page is in English, and the text in it is not
+                   what the header says it is.</p></html>"#,
+            )
+            .unwrap();
+        let text = SummaryDisplay.render(&i18n, &options);
+        assert!(text.ends_with("Language:            de, text in en, 1 mismatched\n"));
+
+        let icons = StreamAnalyzer::new(10)
+            .with_sections([Section::Icons])
+            .analyze_string(
+                r##"<link rel="icon" href="/favicon.ico"><meta name="theme-color" content="#fff">"##,
+            )
+            .unwrap();
+        let text = SummaryDisplay.render(&icons, &options);
+        assert!(text.ends_with(
+            "Icons:               1 favicons, 0 apple-touch, missing: apple-touch-icon, manifest\n"
+        ));
+
+        let keywords = StreamAnalyzer::new(10)
+            .with_sections([Section::Keywords])
+            .analyze_string(
+                "<html lang=en><p>Ferrets, ferrets everywhere, and ferrets eat</p></html>",
+            )
+            .unwrap();
+        let text = SummaryDisplay.render(&keywords, &options);
+        assert!(text
+            .ends_with("Keywords:            ferrets (50.0%), eat (16.7%), everywhere (16.7%)\n"));
+
+        let css = StreamAnalyzer::new(10)
+            .with_sections([Section::Css])
+            .analyze_string(r#"<style>.a, .b, #c {}</style><p class="a x" id="y">z</p>"#)
+            .unwrap();
+        let text = SummaryDisplay.render(&css, &options);
+        assert!(text.ends_with(
+            "CSS cross-check:     1 undefined, 1 unused classes; 1 undefined, 1 unused ids\n"
+        ));
+
+        let rules = ["count(li) > 1", "max_depth > 2"]
+            .iter()
+            .map(|rule| rule.parse().unwrap())
+            .collect();
+        let text = SummaryDisplay.render(&report, &options.with_rules(rules));
+        assert!(text.ends_with(
+            "Rule violations:     1\n\
+             \x20 count(li) > 1 (actual 2)\n"
+        ));
+    }
+}
@@ -0,0 +1,211 @@
+use crate::analyzer::AnalysisResult;
+use crate::reporter::{RenderOptions, Reporter};
+use colored::*;
+use std::fmt::Write;
+
+/// Tags, attributes and values as an indented tree
+pub struct TreeDisplay;
+
+impl Reporter for TreeDisplay {
+    fn render(&self, report: &AnalysisResult, options: &RenderOptions) -> String {
+        let mut out = String::new();
+        writeln!(out, "📦 Files analyzed: {}", report.files_analyzed).unwrap();
+
+        let total = report.total_elements();
+        let sorted_tags = options.tags(report);
+        for (i, tag) in sorted_tags.iter().enumerate() {
+            let is_last_tag = i == sorted_tags.len() - 1;
+            let tag_prefix = if is_last_tag {
+                "└── "
+            } else {
+                "├── "
+            };
+
+            writeln!(
+                out,
+                "{}{}{}",
+                tag_prefix,
+                options.paint(tag.name.bright_cyan()),
+                options.paint(format!(" ({})", options.count(tag.count, total)).yellow())
+            )
+            .unwrap();
+
+            let sorted_attrs = options.attributes(tag);
+            let child_indent = if is_last_tag { "    " } else { "│   " };
+
+            for (j, attr) in sorted_attrs.iter().enumerate() {
+                let is_last_attr = j == sorted_attrs.len() - 1;
+                let attr_prefix = if is_last_attr {
+                    "└── "
+                } else {
+                    "├── "
+                };
+
+                writeln!(
+                    out,
+                    "{}{}@{}{}",
+                    child_indent,
+                    attr_prefix,
+                    attr.name,
+                    options.paint(format!(" ({})", attr.count).dimmed())
+                )
+                .unwrap();
+
+                // Print top values
+                let val_indent = if is_last_attr { "    " } else { "│   " };
+                let full_val_indent = format!("{}{}", child_indent, val_indent);
+
+                let sorted_vals = options.values(attr);
+                for (k, (val, count)) in sorted_vals.iter().enumerate() {
+                    let is_last_val = k == sorted_vals.len() - 1;
+                    let val_prefix = if is_last_val {
+                        "└── "
+                    } else {
+                        "├── "
+                    };
+
+                    writeln!(
+                        out,
+                        "{}{}{} {} ({})",
+                        full_val_indent,
+                        val_prefix,
+                        options.paint("──".dimmed()),
+                        val,
+                        options.count(**count, attr.count)
+                    )
+                    .unwrap();
+                }
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::stream::StreamAnalyzer;
+    use crate::reporter::{Depth, SortKey, SortOrder};
+
+    fn report() -> AnalysisResult {
+        StreamAnalyzer::new(10)
+            .analyze_string(
+                r#"<ul id="m"><li class="a">1</li><li class="b">2</li><li class="a">3</li></ul><p>x</p>"#,
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn test_tree() {
+        let options = RenderOptions::default().with_color(false);
+        let text = TreeDisplay.render(&report(), &options);
+        assert_eq!(
+            text.lines().collect::<Vec<_>>(),
+            [
+                "📦 Files analyzed: 1",
+                "├── li (3)",
+                "│   └── @class (3)",
+                "│       ├── ── a (2)",
+                "│       └── ── b (1)",
+                "├── p (1)",
+                "└── ul (1)",
+                "    └── @id (1)",
+                "        └── ── m (1)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tree_options() {
+        let options = RenderOptions::default()
+            .with_color(false)
+            .with_sort(SortKey::Name)
+            .with_max_tags(2)
+            .with_depth(Depth::Attributes);
+        assert_eq!(
+            TreeDisplay.render(&report(), &options),
+            "📦 Files analyzed: 1\n\
+             ├── li (3)\n\
+             │   └── @class (3)\n\
+             └── p (1)\n"
+        );
+
+        let options = RenderOptions::default()
+            .with_color(false)
+            .with_max_values(1);
+        let text = TreeDisplay.render(&report(), &options);
+        assert!(text.contains("── a (2)"));
+        assert!(!text.contains("── b (1)"));
+    }
+
+    #[test]
+    fn test_tree_percentages() {
+        let options = RenderOptions::default()
+            .with_color(false)
+            .with_percentages(true)
+            .with_max_tags(1);
+        assert_eq!(
+            TreeDisplay.render(&report(), &options),
+            "📦 Files analyzed: 1\n\
+             └── li (3, 60.0%)\n\
+             \x20   └── @class (3)\n\
+             \x20       ├── ── a (2, 66.7%)\n\
+             \x20       └── ── b (1, 33.3%)\n"
+        );
+    }
+
+    #[test]
+    fn test_tree_sorting() {
+        let report = StreamAnalyzer::new(10)
+            .analyze_string(
+                r#"<a href="x" id="y" title="t">1</a><a href="z">2</a><img src="s" alt=""><br/><br/><br/>"#,
+            )
+            .unwrap();
+        let tags = |options: RenderOptions| {
+            let text = TreeDisplay.render(&report, &options.with_color(false));
+            text.lines()
+                .filter(|line| !line.starts_with(['│', ' ', '📦']))
+                .map(|line| line[line.find(' ').unwrap() + 1..].to_string())
+                .collect::<Vec<_>>()
+        };
+
+        let options = RenderOptions::default();
+        assert_eq!(tags(options.clone()), ["br (3)", "a (2)", "img (1)"]);
+        assert_eq!(
+            tags(options.clone().with_order(SortOrder::Ascending)),
+            ["img (1)", "a (2)", "br (3)"]
+        );
+        assert_eq!(
+            tags(options.clone().with_sort(SortKey::Attributes)),
+            ["a (2)", "img (1)", "br (3)"]
+        );
+        assert_eq!(
+            tags(
+                options
+                    .clone()
+                    .with_sort(SortKey::Name)
+                    .with_order(SortOrder::Descending)
+            ),
+            ["img (1)", "br (3)", "a (2)"]
+        );
+
+        let options = options
+            .with_color(false)
+            .with_depth(Depth::Attributes)
+            .with_max_tags(1)
+            .with_max_attributes(2)
+            .with_sort(SortKey::Attributes);
+        assert_eq!(
+            TreeDisplay
+                .render(&report, &options)
+                .lines()
+                .collect::<Vec<_>>(),
+            [
+                "📦 Files analyzed: 1",
+                "└── a (2)",
+                "    ├── @href (2)",
+                "    └── @id (1)",
+            ]
+        );
+    }
+}
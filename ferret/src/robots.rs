@@ -0,0 +1,380 @@
+use crate::error::FerretError;
+use anyhow::Result;
+use reqwest::header::USER_AGENT;
+use reqwest::{Client, Url};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Parsed robots.txt rules
+///
+/// Follows RFC 9309: the group with the most specific matching user-agent
+/// applies (falling back to `*`), the longest matching rule wins, and `Allow`
+/// wins ties. `*` and `$` wildcards are supported in paths, as is the
+/// non-standard but widely used `Crawl-delay`.
+///
+/// # Example
+/// ```
+/// use ferret::robots::RobotsTxt;
+///
+/// let robots = RobotsTxt::parse("User-agent: *\nDisallow: /private\nAllow: /private/ok\n");
+/// assert!(!robots.is_allowed("ferret", "/private/data"));
+/// assert!(robots.is_allowed("ferret", "/private/ok.html"));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RobotsTxt {
+    groups: Vec<Group>,
+    sitemaps: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Group {
+    user_agents: Vec<String>,
+    rules: Vec<Rule>,
+    crawl_delay: Option<Duration>,
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    allow: bool,
+    pattern: String,
+}
+
+impl RobotsTxt {
+    /// A robots.txt that allows everything
+    pub fn allow_all() -> Self {
+        Self::default()
+    }
+
+    /// A robots.txt that disallows everything
+    pub fn disallow_all() -> Self {
+        Self::parse("User-agent: *\nDisallow: /\n")
+    }
+
+    pub fn parse(content: &str) -> Self {
+        let mut groups: Vec<Group> = Vec::new();
+        let mut sitemaps = Vec::new();
+        // Consecutive user-agent lines share one group
+        let mut in_agent_list = false;
+
+        for line in content.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+
+            match key.trim().to_ascii_lowercase().as_str() {
+                "user-agent" => {
+                    if !in_agent_list {
+                        groups.push(Group::default());
+                    }
+                    in_agent_list = true;
+                    if let Some(group) = groups.last_mut() {
+                        group.user_agents.push(value.to_ascii_lowercase());
+                    }
+                }
+                key @ ("allow" | "disallow") => {
+                    in_agent_list = false;
+                    // An empty `Disallow:` allows everything, i.e. has no effect
+                    if let (Some(group), false) = (groups.last_mut(), value.is_empty()) {
+                        group.rules.push(Rule {
+                            allow: key == "allow",
+                            pattern: value.to_string(),
+                        });
+                    }
+                }
+                "crawl-delay" => {
+                    in_agent_list = false;
+                    if let (Some(group), Ok(seconds)) = (groups.last_mut(), value.parse::<f64>()) {
+                        if seconds.is_finite() && seconds >= 0.0 {
+                            group.crawl_delay = Some(Duration::from_secs_f64(seconds));
+                        }
+                    }
+                }
+                // Not part of any group
+                "sitemap" if !value.is_empty() => sitemaps.push(value.to_string()),
+                _ => {}
+            }
+        }
+
+        Self { groups, sitemaps }
+    }
+
+    /// URLs of the `Sitemap:` lines
+    pub fn sitemaps(&self) -> &[String] {
+        &self.sitemaps
+    }
+
+    /// Whether `user_agent` may fetch `path` (including any query string)
+    pub fn is_allowed(&self, user_agent: &str, path: &str) -> bool {
+        let mut best: Option<&Rule> = None;
+        for rule in self.groups_for(user_agent).flat_map(|group| &group.rules) {
+            if !matches_pattern(&rule.pattern, path) {
+                continue;
+            }
+            best = match best {
+                Some(current)
+                    if current.pattern.len() > rule.pattern.len()
+                        || (current.pattern.len() == rule.pattern.len() && current.allow) =>
+                {
+                    Some(current)
+                }
+                _ => Some(rule),
+            };
+        }
+        best.is_none_or(|rule| rule.allow)
+    }
+
+    /// `Crawl-delay` requested for `user_agent`, if any
+    pub fn crawl_delay(&self, user_agent: &str) -> Option<Duration> {
+        self.groups_for(user_agent)
+            .filter_map(|group| group.crawl_delay)
+            .max()
+    }
+
+    /// Groups that apply to `user_agent`
+    ///
+    /// Only the product token (`ferret` in `ferret/0.1`) is compared. The
+    /// groups naming the longest matching token are used; `*` groups only
+    /// apply when no group names the crawler.
+    fn groups_for<'a>(&'a self, user_agent: &str) -> impl Iterator<Item = &'a Group> {
+        let token = product_token(user_agent);
+        let specificity = move |agent: &str| match agent {
+            "*" => Some(0),
+            agent if !agent.is_empty() && token.starts_with(agent) => Some(agent.len()),
+            _ => None,
+        };
+        let best = self
+            .groups
+            .iter()
+            .flat_map(|group| &group.user_agents)
+            .filter_map(|agent| specificity(agent))
+            .max();
+
+        self.groups.iter().filter(move |group| {
+            best.is_some()
+                && group
+                    .user_agents
+                    .iter()
+                    .any(|agent| specificity(agent) == best)
+        })
+    }
+}
+
+/// Lowercased first token of a User-Agent header
+fn product_token(user_agent: &str) -> String {
+    user_agent
+        .split(|c: char| c == '/' || c.is_whitespace())
+        .next()
+        .unwrap_or("")
+        .to_ascii_lowercase()
+}
+
+/// Match a robots.txt path pattern with `*` and a trailing `$`
+fn matches_pattern(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or("");
+    let Some(mut rest) = path.strip_prefix(first) else {
+        return false;
+    };
+
+    let parts: Vec<&str> = parts.collect();
+    for (i, part) in parts.iter().enumerate() {
+        let is_last = i == parts.len() - 1;
+        if is_last && anchored {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+
+    !anchored || rest.is_empty()
+}
+
+/// Fetches, caches and enforces robots.txt for every host requested
+///
+/// Attach it with [`FetchOptions::robots`](crate::fetch::FetchOptions::robots);
+/// requests to disallowed URLs then fail with `FerretError::RobotsDisallowed`
+/// and requests to the same host are spaced by its `Crawl-delay`. Clones
+/// share the same cache, so one policy can serve many analyzers.
+///
+/// Following RFC 9309, a missing robots.txt (4xx) allows everything, while an
+/// unreachable one (5xx or network error) disallows everything.
+#[derive(Debug, Clone, Default)]
+pub struct RobotsPolicy {
+    hosts: Arc<Mutex<HashMap<String, HostState>>>,
+}
+
+#[derive(Debug)]
+struct HostState {
+    robots: RobotsTxt,
+    /// Earliest time the next request may be sent
+    next_request: Instant,
+}
+
+impl RobotsPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use `robots` for the origin of `url` instead of fetching it
+    pub fn insert(&self, url: &str, robots: RobotsTxt) -> Result<()> {
+        let origin = Url::parse(url)?.origin().ascii_serialization();
+        self.hosts.lock().unwrap().insert(
+            origin,
+            HostState {
+                robots,
+                next_request: Instant::now(),
+            },
+        );
+        Ok(())
+    }
+
+    /// Ensure `url` may be fetched and wait for the host's crawl delay
+    pub async fn check(&self, client: &Client, url: &Url, user_agent: &str) -> Result<()> {
+        let origin = url.origin().ascii_serialization();
+        let cached = self.hosts.lock().unwrap().contains_key(&origin);
+        if !cached {
+            let robots = fetch_robots(client, url, user_agent).await;
+            self.hosts
+                .lock()
+                .unwrap()
+                .entry(origin.clone())
+                .or_insert(HostState {
+                    robots,
+                    next_request: Instant::now(),
+                });
+        }
+
+        let path = match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_string(),
+        };
+
+        let wait_until = {
+            let mut hosts = self.hosts.lock().unwrap();
+            let state = hosts.get_mut(&origin).expect("robots.txt cached above");
+            if !state.robots.is_allowed(user_agent, &path) {
+                return Err(FerretError::RobotsDisallowed(url.to_string()).into());
+            }
+            // Reserve the next slot so concurrent requests queue up
+            let slot = state.next_request.max(Instant::now());
+            state.next_request = slot + state.robots.crawl_delay(user_agent).unwrap_or_default();
+            slot
+        };
+        tokio::time::sleep_until(wait_until).await;
+        Ok(())
+    }
+}
+
+async fn fetch_robots(client: &Client, url: &Url, user_agent: &str) -> RobotsTxt {
+    let Ok(robots_url) = url.join("/robots.txt") else {
+        return RobotsTxt::allow_all();
+    };
+    let response = client
+        .get(robots_url)
+        .header(USER_AGENT, user_agent)
+        .send()
+        .await;
+
+    match response {
+        Ok(response) if response.status().is_success() => match response.text().await {
+            Ok(text) => RobotsTxt::parse(&text),
+            Err(_) => RobotsTxt::disallow_all(),
+        },
+        Ok(response) if response.status().is_client_error() => RobotsTxt::allow_all(),
+        _ => RobotsTxt::disallow_all(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROBOTS: &str = r#"
+# Example
+User-agent: *
+Disallow: /private
+Allow: /private/public
+Disallow: /*.pdf$
+Crawl-delay: 2
+
+User-agent: ferret
+User-agent: other
+Disallow: /no-ferrets/
+Allow: /search?q=
+Disallow: /search
+Crawl-delay: 0.5
+"#;
+
+    #[test]
+    fn test_group_selection() {
+        let robots = RobotsTxt::parse(ROBOTS);
+
+        // Named group replaces the `*` group entirely
+        assert!(robots.is_allowed("ferret/0.1.0", "/private"));
+        assert!(!robots.is_allowed("Ferret/0.1.0", "/no-ferrets/page"));
+        assert_eq!(
+            robots.crawl_delay("ferret/0.1.0"),
+            Some(Duration::from_millis(500))
+        );
+
+        assert!(!robots.is_allowed("curl/8.0", "/private/data"));
+        assert!(robots.is_allowed("curl/8.0", "/no-ferrets/page"));
+        assert_eq!(robots.crawl_delay("curl/8.0"), Some(Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn test_rule_precedence() {
+        let robots = RobotsTxt::parse(ROBOTS);
+        assert!(robots.is_allowed("curl", "/private/public/page"));
+        assert!(robots.is_allowed("ferret", "/search?q=rust"));
+        assert!(!robots.is_allowed("ferret", "/search/advanced"));
+        assert!(robots.is_allowed("ferret", "/"));
+    }
+
+    #[test]
+    fn test_wildcards() {
+        assert!(matches_pattern("/*.pdf$", "/docs/file.pdf"));
+        assert!(!matches_pattern("/*.pdf$", "/docs/file.pdf?download"));
+        assert!(matches_pattern("/*.pdf", "/docs/file.pdf?download"));
+        assert!(matches_pattern("/a*b*c", "/axxbyyc/z"));
+        assert!(!matches_pattern("/a*b*c", "/axxc"));
+        assert!(matches_pattern("/exact$", "/exact"));
+        assert!(!matches_pattern("/exact$", "/exact/"));
+    }
+
+    #[test]
+    fn test_empty_and_missing() {
+        let robots = RobotsTxt::parse("User-agent: *\nDisallow:\n");
+        assert!(robots.is_allowed("ferret", "/anything"));
+        assert!(RobotsTxt::allow_all().is_allowed("ferret", "/"));
+        assert!(!RobotsTxt::disallow_all().is_allowed("ferret", "/"));
+    }
+
+    #[test]
+    fn test_sitemaps() {
+        let robots = RobotsTxt::parse(
+            "Sitemap: https://example.com/sitemap.xml\nUser-agent: *\nDisallow: /x\n\
+             SITEMAP: https://example.com/news.xml.gz\nSitemap:\n",
+        );
+        assert_eq!(
+            robots.sitemaps(),
+            [
+                "https://example.com/sitemap.xml",
+                "https://example.com/news.xml.gz"
+            ]
+        );
+        assert!(!robots.is_allowed("ferret", "/x"));
+        assert!(RobotsTxt::parse(ROBOTS).sitemaps().is_empty());
+    }
+}
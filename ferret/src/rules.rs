@@ -0,0 +1,568 @@
+//! Threshold rules on analysis results, e.g. to fail CI builds
+//!
+//! A rule is a condition such as `count(img[alt=""]) > 0` or
+//! `max_depth > 40`; a result violates the rule when the condition holds.
+
+use crate::analyzer::AnalysisResult;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Quantity of an analysis result that a rule compares
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Metric {
+    /// `count(*)`: all elements
+    Elements,
+    /// `count(tag)`
+    Tag(String),
+    /// `count(tag[attribute])`: elements with the attribute
+    Attribute { tag: String, attribute: String },
+    /// `count(tag[attribute="value"])`; only values tracked within the
+    /// analyzer's `top_values_limit` are counted
+    Value {
+        tag: String,
+        attribute: String,
+        value: String,
+    },
+    /// `max_depth`
+    MaxDepth,
+    /// `parse_errors`
+    ParseErrors,
+    /// `distinct_tags`
+    DistinctTags,
+    /// `distinct_attributes`, see [`AnalysisResult::distinct_attributes`]
+    DistinctAttributes,
+    /// `broken_links`; this and the other link metrics are zero unless the
+    /// result comes from a crawl with link checking
+    BrokenLinks,
+    /// `redirected_links`
+    RedirectedLinks,
+    /// `unreachable_links`
+    UnreachableLinks,
+    /// `third_parties`, distinct other sites resources are loaded from;
+    /// this and `trackers` are zero unless the `third-party` section is
+    /// enabled
+    ThirdParties,
+    /// `trackers`, hosts of known trackers resources are loaded from
+    Trackers,
+    /// `missing_integrity`, cross-origin scripts and stylesheets without
+    /// working Subresource Integrity; this and `missing_headers` are zero
+    /// unless the `security` section is enabled
+    MissingIntegrity,
+    /// `missing_headers`, recommended security headers the response lacked
+    MissingHeaders,
+    /// `unsized_images`, images without `width` and `height`; this and
+    /// `unresponsive_images` are zero unless the `images` section is
+    /// enabled
+    UnsizedImages,
+    /// `unresponsive_images`, raster images without `srcset` or
+    /// `<picture>` variants
+    UnresponsiveImages,
+    /// `blocking_scripts`, external scripts without `async` or `defer`;
+    /// this and the other performance metrics are zero unless the `perf`
+    /// section is enabled
+    BlockingScripts,
+    /// `blocking_stylesheets`
+    BlockingStylesheets,
+    /// `inline_bytes`, the size of inline `<script>` and `<style>` code
+    InlineBytes,
+    /// `lang_mismatches`, pages whose text is in another language than
+    /// their `<html lang>`; this and `invalid_hreflang` are zero unless the
+    /// `i18n` section is enabled
+    LangMismatches,
+    /// `invalid_hreflang`
+    InvalidHreflang,
+    /// `missing_icons`, how many of favicon, apple-touch icon, manifest and
+    /// theme color are missing; zero unless the `icons` section is enabled
+    MissingIcons,
+    /// `undefined_classes`, classes used in the HTML that no selector of
+    /// its CSS names; this and `unused_classes` are zero unless the `css`
+    /// section is enabled
+    UndefinedClasses,
+    /// `unused_classes`, classes selected by the CSS but used nowhere
+    UnusedClasses,
+}
+
+impl Metric {
+    /// Value of this metric for `result`; zero for unknown tags and attributes
+    pub fn measure(&self, result: &AnalysisResult) -> usize {
+        let attribute = |tag: &str, attribute: &str| {
+            result
+                .tags
+                .get(tag)
+                .and_then(|stats| stats.attributes.get(attribute))
+        };
+        match self {
+            Metric::Elements => result.total_elements(),
+            Metric::Tag(tag) => result.tags.get(tag).map_or(0, |stats| stats.count),
+            Metric::Attribute {
+                tag,
+                attribute: name,
+            } => attribute(tag, name).map_or(0, |stats| stats.count),
+            Metric::Value {
+                tag,
+                attribute: name,
+                value,
+            } => attribute(tag, name)
+                .and_then(|stats| stats.value_counts.get(value))
+                .copied()
+                .unwrap_or(0),
+            Metric::MaxDepth => result.max_depth,
+            Metric::ParseErrors => result.parse_errors.len(),
+            Metric::DistinctTags => result.tags.len(),
+            Metric::DistinctAttributes => result.distinct_attributes(),
+            Metric::BrokenLinks => result.links.map_or(0, |links| links.broken),
+            Metric::RedirectedLinks => result.links.map_or(0, |links| links.redirected),
+            Metric::UnreachableLinks => result.links.map_or(0, |links| links.unreachable),
+            Metric::ThirdParties => result
+                .third_party
+                .as_ref()
+                .map_or(0, |report| report.third_parties()),
+            Metric::Trackers => result
+                .third_party
+                .as_ref()
+                .map_or(0, |report| report.trackers().count()),
+            Metric::MissingIntegrity => result
+                .security
+                .as_ref()
+                .map_or(0, |report| report.findings.len()),
+            Metric::MissingHeaders => result
+                .security
+                .as_ref()
+                .map_or(0, |report| report.missing_headers.len()),
+            Metric::UnsizedImages => result.images.map_or(0, |images| images.missing_dimensions),
+            Metric::UnresponsiveImages => result.images.map_or(0, |images| images.missing_variants),
+            Metric::BlockingScripts => result.perf.map_or(0, |perf| perf.blocking_scripts),
+            Metric::BlockingStylesheets => result.perf.map_or(0, |perf| perf.blocking_stylesheets),
+            Metric::LangMismatches => result.i18n.as_ref().map_or(0, |i18n| i18n.mismatches),
+            Metric::InvalidHreflang => result
+                .i18n
+                .as_ref()
+                .map_or(0, |i18n| i18n.invalid_hreflang.len()),
+            Metric::InlineBytes => result
+                .perf
+                .map_or(0, |perf| perf.inline_script_bytes + perf.inline_style_bytes),
+            Metric::MissingIcons => result.icons.as_ref().map_or(0, |icons| icons.missing.len()),
+            Metric::UndefinedClasses => result
+                .css
+                .as_ref()
+                .map_or(0, |css| css.undefined_classes.len()),
+            Metric::UnusedClasses => result
+                .css
+                .as_ref()
+                .map_or(0, |css| css.unused_classes.len()),
+        }
+    }
+}
+
+impl fmt::Display for Metric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Metric::Elements => write!(f, "count(*)"),
+            Metric::Tag(tag) => write!(f, "count({})", tag),
+            Metric::Attribute { tag, attribute } => write!(f, "count({}[{}])", tag, attribute),
+            Metric::Value {
+                tag,
+                attribute,
+                value,
+            } => write!(f, "count({}[{}={:?}])", tag, attribute, value),
+            Metric::MaxDepth => write!(f, "max_depth"),
+            Metric::ParseErrors => write!(f, "parse_errors"),
+            Metric::DistinctTags => write!(f, "distinct_tags"),
+            Metric::DistinctAttributes => write!(f, "distinct_attributes"),
+            Metric::BrokenLinks => write!(f, "broken_links"),
+            Metric::RedirectedLinks => write!(f, "redirected_links"),
+            Metric::UnreachableLinks => write!(f, "unreachable_links"),
+            Metric::ThirdParties => write!(f, "third_parties"),
+            Metric::Trackers => write!(f, "trackers"),
+            Metric::MissingIntegrity => write!(f, "missing_integrity"),
+            Metric::MissingHeaders => write!(f, "missing_headers"),
+            Metric::UnsizedImages => write!(f, "unsized_images"),
+            Metric::UnresponsiveImages => write!(f, "unresponsive_images"),
+            Metric::BlockingScripts => write!(f, "blocking_scripts"),
+            Metric::BlockingStylesheets => write!(f, "blocking_stylesheets"),
+            Metric::InlineBytes => write!(f, "inline_bytes"),
+            Metric::LangMismatches => write!(f, "lang_mismatches"),
+            Metric::InvalidHreflang => write!(f, "invalid_hreflang"),
+            Metric::MissingIcons => write!(f, "missing_icons"),
+            Metric::UndefinedClasses => write!(f, "undefined_classes"),
+            Metric::UnusedClasses => write!(f, "unused_classes"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual,
+    Equal,
+    NotEqual,
+}
+
+impl Comparison {
+    /// Two-character operators first, so `>=` isn't read as `>`
+    const ALL: [Comparison; 6] = [
+        Comparison::GreaterOrEqual,
+        Comparison::LessOrEqual,
+        Comparison::Equal,
+        Comparison::NotEqual,
+        Comparison::Greater,
+        Comparison::Less,
+    ];
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Comparison::Greater => ">",
+            Comparison::GreaterOrEqual => ">=",
+            Comparison::Less => "<",
+            Comparison::LessOrEqual => "<=",
+            Comparison::Equal => "==",
+            Comparison::NotEqual => "!=",
+        }
+    }
+
+    pub fn holds(self, left: usize, right: usize) -> bool {
+        match self {
+            Comparison::Greater => left > right,
+            Comparison::GreaterOrEqual => left >= right,
+            Comparison::Less => left < right,
+            Comparison::LessOrEqual => left <= right,
+            Comparison::Equal => left == right,
+            Comparison::NotEqual => left != right,
+        }
+    }
+}
+
+/// A condition that flags a result when it holds
+///
+/// # Example
+/// ```
+/// use ferret::analyzer::stream::StreamAnalyzer;
+/// use ferret::rules::Rule;
+///
+/// let result = StreamAnalyzer::new(10).analyze_string(r#"<p><img alt=""/></p>"#)?;
+/// let rule: Rule = r#"count(img[alt=""]) > 0"#.parse()?;
+/// let violation = rule.check(&result).unwrap();
+/// assert_eq!(violation.to_string(), r#"count(img[alt=""]) > 0 (actual 1)"#);
+/// assert!(Rule::max_depth(2).check(&result).is_none());
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub struct Rule {
+    pub metric: Metric,
+    pub comparison: Comparison,
+    pub threshold: usize,
+}
+
+impl Rule {
+    pub fn new(metric: Metric, comparison: Comparison, threshold: usize) -> Self {
+        Self {
+            metric,
+            comparison,
+            threshold,
+        }
+    }
+
+    /// Flags documents nested deeper than `depth`, i.e. `max_depth > depth`
+    pub fn max_depth(depth: usize) -> Self {
+        Self::new(Metric::MaxDepth, Comparison::Greater, depth)
+    }
+
+    /// The violation of this rule by `result`, if the condition holds
+    pub fn check(&self, result: &AnalysisResult) -> Option<Violation> {
+        let actual = self.metric.measure(result);
+        self.comparison
+            .holds(actual, self.threshold)
+            .then(|| Violation {
+                rule: self.to_string(),
+                actual,
+            })
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {}",
+            self.metric,
+            self.comparison.symbol(),
+            self.threshold
+        )
+    }
+}
+
+impl FromStr for Rule {
+    type Err = anyhow::Error;
+
+    /// Parse `METRIC OP NUMBER`, e.g. `count(div) >= 1000`
+    fn from_str(rule: &str) -> Result<Self> {
+        let (metric, rest) =
+            parse_metric(rule.trim()).with_context(|| format!("Invalid rule {:?}", rule))?;
+        let rest = rest.trim_start();
+        let Some(comparison) = Comparison::ALL
+            .into_iter()
+            .find(|comparison| rest.starts_with(comparison.symbol()))
+        else {
+            bail!(
+                "Invalid rule {:?}: expected one of > >= < <= == != after {}",
+                rule,
+                metric
+            );
+        };
+        let threshold = rest[comparison.symbol().len()..].trim();
+        let threshold = threshold
+            .parse()
+            .with_context(|| format!("Invalid rule {:?}: {:?} is not a count", rule, threshold))?;
+        Ok(Self::new(metric, comparison, threshold))
+    }
+}
+
+/// The metric at the start of `input` and the text after it
+fn parse_metric(input: &str) -> Result<(Metric, &str)> {
+    if let Some(rest) = input.strip_prefix("count(") {
+        let (selector, rest) = split_selector(rest)?;
+        return Ok((parse_selector(selector)?, rest));
+    }
+
+    let end = input
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or(input.len());
+    let metric = match &input[..end] {
+        "max_depth" => Metric::MaxDepth,
+        "parse_errors" => Metric::ParseErrors,
+        "distinct_tags" => Metric::DistinctTags,
+        "distinct_attributes" => Metric::DistinctAttributes,
+        "broken_links" => Metric::BrokenLinks,
+        "redirected_links" => Metric::RedirectedLinks,
+        "unreachable_links" => Metric::UnreachableLinks,
+        "third_parties" => Metric::ThirdParties,
+        "trackers" => Metric::Trackers,
+        "missing_integrity" => Metric::MissingIntegrity,
+        "missing_headers" => Metric::MissingHeaders,
+        "unsized_images" => Metric::UnsizedImages,
+        "unresponsive_images" => Metric::UnresponsiveImages,
+        "blocking_scripts" => Metric::BlockingScripts,
+        "blocking_stylesheets" => Metric::BlockingStylesheets,
+        "inline_bytes" => Metric::InlineBytes,
+        "lang_mismatches" => Metric::LangMismatches,
+        "invalid_hreflang" => Metric::InvalidHreflang,
+        "missing_icons" => Metric::MissingIcons,
+        "undefined_classes" => Metric::UndefinedClasses,
+        "unused_classes" => Metric::UnusedClasses,
+        "" => bail!("expected a metric such as count(div) or max_depth"),
+        name => bail!("unknown metric {:?}", name),
+    };
+    Ok((metric, &input[end..]))
+}
+
+/// Split `selector) rest` at the closing parenthesis, skipping quoted values
+fn split_selector(input: &str) -> Result<(&str, &str)> {
+    let mut quoted = false;
+    for (i, c) in input.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ')' if !quoted => return Ok((&input[..i], &input[i + 1..])),
+            _ => {}
+        }
+    }
+    bail!("missing ) after count(")
+}
+
+/// `*`, `tag`, `tag[attribute]` or `tag[attribute="value"]`
+fn parse_selector(selector: &str) -> Result<Metric> {
+    let selector = selector.trim();
+    if selector == "*" {
+        return Ok(Metric::Elements);
+    }
+    let Some((tag, attribute)) = selector.split_once('[') else {
+        check_name(selector)?;
+        return Ok(Metric::Tag(selector.to_string()));
+    };
+    let Some(attribute) = attribute.strip_suffix(']') else {
+        bail!("missing ] in {}", selector);
+    };
+    check_name(tag)?;
+
+    let Some((attribute, value)) = attribute.split_once('=') else {
+        check_name(attribute)?;
+        return Ok(Metric::Attribute {
+            tag: tag.to_string(),
+            attribute: attribute.to_string(),
+        });
+    };
+    check_name(attribute)?;
+    let Some(value) = value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+    else {
+        bail!("the value in {} must be in double quotes", selector);
+    };
+    Ok(Metric::Value {
+        tag: tag.to_string(),
+        attribute: attribute.to_string(),
+        value: value.to_string(),
+    })
+}
+
+fn check_name(name: &str) -> Result<()> {
+    if name.is_empty() || name.contains(|c: char| c.is_whitespace() || "[]=\"()".contains(c)) {
+        bail!("invalid tag or attribute name {:?}", name);
+    }
+    Ok(())
+}
+
+impl From<Rule> for String {
+    fn from(rule: Rule) -> Self {
+        rule.to_string()
+    }
+}
+
+impl TryFrom<String> for Rule {
+    type Error = anyhow::Error;
+
+    fn try_from(rule: String) -> Result<Self> {
+        rule.parse()
+    }
+}
+
+/// A rule whose condition held, with the measured value
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Violation {
+    pub rule: String,
+    pub actual: usize,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (actual {})", self.rule, self.actual)
+    }
+}
+
+/// Violations of `rules` by `result`, in rule order
+pub fn check(rules: &[Rule], result: &AnalysisResult) -> Vec<Violation> {
+    rules.iter().filter_map(|rule| rule.check(result)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::stream::StreamAnalyzer;
+
+    #[test]
+    fn test_parse() {
+        let rule: Rule = r#" count(img[alt="a) >= b"])>=2 "#.parse().unwrap();
+        assert_eq!(
+            rule,
+            Rule::new(
+                Metric::Value {
+                    tag: "img".into(),
+                    attribute: "alt".into(),
+                    value: "a) >= b".into(),
+                },
+                Comparison::GreaterOrEqual,
+                2
+            )
+        );
+        for text in [
+            "count(*) < 10",
+            "count(div) == 3",
+            "count(a[href]) != 0",
+            "max_depth > 40",
+            "parse_errors <= 1",
+            "distinct_tags > 5",
+            "distinct_attributes >= 5",
+            "broken_links > 0",
+            "redirected_links > 10",
+            "unreachable_links == 0",
+            "third_parties > 10",
+            "trackers == 0",
+            "missing_integrity > 0",
+            "missing_headers >= 2",
+            "unsized_images > 0",
+            "unresponsive_images < 3",
+            "blocking_scripts > 2",
+            "blocking_stylesheets > 1",
+            "inline_bytes > 20000",
+            "lang_mismatches > 0",
+            "invalid_hreflang == 0",
+            "missing_icons > 1",
+            "undefined_classes > 0",
+            "unused_classes <= 50",
+        ] {
+            assert_eq!(text.parse::<Rule>().unwrap().to_string(), text);
+        }
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for text in [
+            "",
+            "count(div > 1",
+            "count() > 1",
+            "count(img[alt) > 1",
+            "count(img[alt=x]) > 1",
+            "depth > 1",
+            "max_depth 40",
+            "max_depth > -1",
+            "max_depth > many",
+        ] {
+            assert!(text.parse::<Rule>().is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_check() {
+        let result = StreamAnalyzer::new(10)
+            .analyze_string(r#"<div><img alt=""/><img alt="logo"/><img src="x"/></div>"#)
+            .unwrap();
+        let rules: Vec<Rule> = [
+            "count(*) == 4",
+            "count(img) > 3",
+            "count(img[alt]) == 2",
+            r#"count(img[alt=""]) > 0"#,
+            "count(table) > 0",
+            "max_depth > 0",
+        ]
+        .iter()
+        .map(|rule| rule.parse().unwrap())
+        .collect();
+        assert_eq!(
+            check(&rules, &result),
+            [
+                Violation {
+                    rule: "count(*) == 4".into(),
+                    actual: 4
+                },
+                Violation {
+                    rule: "count(img[alt]) == 2".into(),
+                    actual: 2
+                },
+                Violation {
+                    rule: r#"count(img[alt=""]) > 0"#.into(),
+                    actual: 1
+                },
+                Violation {
+                    rule: "max_depth > 0".into(),
+                    actual: 1
+                },
+            ]
+        );
+
+        let broken: Rule = "broken_links > 0".parse().unwrap();
+        assert!(broken.check(&result).is_none());
+        let result = AnalysisResult {
+            links: Some(crate::analyzer::LinkCounts {
+                checked: 5,
+                broken: 2,
+                ..Default::default()
+            }),
+            ..result
+        };
+        assert_eq!(broken.check(&result).unwrap().actual, 2);
+    }
+}
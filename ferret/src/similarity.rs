@@ -0,0 +1,217 @@
+use crate::analyzer::AnalysisResult;
+use crate::cache::Fnv1a;
+use crate::monitor::structure_features;
+use serde::{Deserialize, Serialize};
+use std::hash::Hasher;
+
+/// Bits two simhashes may differ in for their pages to share a cluster
+pub const DEFAULT_MAX_DISTANCE: u32 = 6;
+
+/// Similarity hash of the structure of an analysis result
+///
+/// Unlike a [`Fingerprint`](crate::monitor::Fingerprint), which changes
+/// completely when one feature does, similar structures get simhashes
+/// differing in few bits. Every tag, attribute, `class` value and nesting
+/// (see [`structure_features`]) counts once, however often it occurs.
+///
+/// # Example
+/// ```
+/// # use ferret::analyzer::stream::StreamAnalyzer;
+/// use ferret::similarity::SimHash;
+///
+/// let analyzer = StreamAnalyzer::new(10);
+/// let page = |extra: &str| {
+///     let html = format!(
+///         r#"<header><nav><a href="/">Home</a></nav></header>
+///            <main><h1 class="title">T</h1><ul class="specs"><li>a</li></ul>
+///            <img src="p.png" alt="p"><button class="buy">Buy</button>{}</main>
+///            <footer><p class="legal">c</p><span id="year">2024</span></footer>"#,
+///         extra
+///     );
+///     analyzer.analyze_string(&html).map(|result| SimHash::of(&result))
+/// };
+/// let product = page("")?;
+/// let on_sale = page(r#"<em class="sale">-20%</em>"#)?;
+/// let article = SimHash::of(&analyzer.analyze_string("<article><p>Text</p></article>")?);
+///
+/// assert!(product.distance(on_sale) < product.distance(article));
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SimHash(pub u64);
+
+impl SimHash {
+    pub fn of(result: &AnalysisResult) -> Self {
+        let mut weights = [0i32; 64];
+        for feature in structure_features(result, 1) {
+            let mut hasher = Fnv1a::default();
+            hasher.write(feature.as_bytes());
+            // FNV-1a's low bits mix poorly for short inputs; spread them
+            // over the whole word before voting
+            let hash = mix(hasher.finish());
+            for (bit, weight) in weights.iter_mut().enumerate() {
+                *weight += if hash >> bit & 1 == 1 { 1 } else { -1 };
+            }
+        }
+        let hash = weights
+            .iter()
+            .enumerate()
+            .filter(|(_, weight)| **weight > 0)
+            .fold(0u64, |hash, (bit, _)| hash | 1 << bit);
+        Self(hash)
+    }
+
+    /// Number of differing bits, from 0 for the same structure to 64
+    pub fn distance(self, other: SimHash) -> u32 {
+        (self.0 ^ other.0).count_ones()
+    }
+
+    /// Share of equal bits, from 0.0 to 1.0
+    pub fn similarity(self, other: SimHash) -> f64 {
+        1.0 - self.distance(other) as f64 / 64.0
+    }
+}
+
+/// Finalizer of SplitMix64
+fn mix(mut hash: u64) -> u64 {
+    hash = (hash ^ hash >> 30).wrapping_mul(0xbf58476d1ce4e5b9);
+    hash = (hash ^ hash >> 27).wrapping_mul(0x94d049bb133111eb);
+    hash ^ hash >> 31
+}
+
+/// Pages sharing a template
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TemplateCluster {
+    /// Clusters are numbered from 0 in the order of their first page
+    pub id: usize,
+    pub pages: usize,
+    /// Source of the cluster's first page
+    pub example: String,
+}
+
+/// Group pages whose simhashes are within `max_distance` bits of another
+/// page of the group
+///
+/// Takes `(source, result)` pairs and returns the cluster id of each pair,
+/// `None` for those without a result, and the clusters. Comparing every
+/// pair takes a few milliseconds for thousands of pages.
+pub fn cluster_templates<'a, I>(
+    pages: I,
+    max_distance: u32,
+) -> (Vec<Option<usize>>, Vec<TemplateCluster>)
+where
+    I: IntoIterator<Item = (&'a str, Option<&'a AnalysisResult>)>,
+{
+    let pages: Vec<(&str, Option<SimHash>)> = pages
+        .into_iter()
+        .map(|(source, result)| (source, result.map(SimHash::of)))
+        .collect();
+    let hashed: Vec<(usize, SimHash)> = pages
+        .iter()
+        .enumerate()
+        .filter_map(|(index, (_, hash))| Some((index, (*hash)?)))
+        .collect();
+
+    // Union-find over the pages with a result
+    let mut parent: Vec<usize> = (0..pages.len()).collect();
+    fn root(parent: &mut [usize], mut index: usize) -> usize {
+        while parent[index] != index {
+            parent[index] = parent[parent[index]];
+            index = parent[index];
+        }
+        index
+    }
+    for (i, &(a, hash_a)) in hashed.iter().enumerate() {
+        for &(b, hash_b) in &hashed[i + 1..] {
+            if hash_a.distance(hash_b) <= max_distance {
+                let (root_a, root_b) = (root(&mut parent, a), root(&mut parent, b));
+                // Keep the earlier page as the root, so ids follow page order
+                parent[root_a.max(root_b)] = root_a.min(root_b);
+            }
+        }
+    }
+
+    let mut ids = vec![None; pages.len()];
+    let mut clusters: Vec<TemplateCluster> = Vec::new();
+    for &(index, _) in &hashed {
+        let root = root(&mut parent, index);
+        let id = match ids[root] {
+            Some(id) => id,
+            None => {
+                clusters.push(TemplateCluster {
+                    id: clusters.len(),
+                    pages: 0,
+                    example: pages[index].0.to_string(),
+                });
+                clusters.len() - 1
+            }
+        };
+        clusters[id].pages += 1;
+        ids[index] = Some(id);
+    }
+    (ids, clusters)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::stream::StreamAnalyzer;
+
+    #[test]
+    fn test_simhash() {
+        let analyzer = StreamAnalyzer::new(10);
+        let a = SimHash::of(&analyzer.analyze_string("<div><p>a</p></div>").unwrap());
+        let b = SimHash::of(
+            &analyzer
+                .analyze_string("<div><p>a</p><p>b</p></div>")
+                .unwrap(),
+        );
+        assert_eq!(a, b);
+        assert_eq!(a.similarity(b), 1.0);
+        assert_eq!(SimHash(0).distance(SimHash(u64::MAX)), 64);
+        assert_eq!(SimHash::of(&AnalysisResult::default()), SimHash(0));
+    }
+
+    #[test]
+    fn test_cluster_templates() {
+        let analyzer = StreamAnalyzer::new(10);
+        let product = |name: &str| {
+            analyzer
+                .analyze_string(&format!(
+                    r#"<div class="product"><h2>{}</h2><span class="price">1</span>
+                       <img src="{}.png" alt=""><a href="/buy">Buy</a></div>"#,
+                    name, name
+                ))
+                .unwrap()
+        };
+        let article = analyzer
+            .analyze_string("<article><h1>News</h1><p>Text</p><p>More</p></article>")
+            .unwrap();
+        let (a, b) = (product("a"), product("b"));
+        let (ids, clusters) = cluster_templates(
+            [
+                ("/a", Some(&a)),
+                ("/news", Some(&article)),
+                ("/gone", None),
+                ("/b", Some(&b)),
+            ],
+            DEFAULT_MAX_DISTANCE,
+        );
+        assert_eq!(ids, [Some(0), Some(1), None, Some(0)]);
+        assert_eq!(
+            clusters,
+            [
+                TemplateCluster {
+                    id: 0,
+                    pages: 2,
+                    example: "/a".to_string(),
+                },
+                TemplateCluster {
+                    id: 1,
+                    pages: 1,
+                    example: "/news".to_string(),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,185 @@
+use crate::error::FerretError;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// How markup is interpreted by the stream analyzers
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ParseMode {
+    /// HTML rules, switching to lenient XML once an `<?xml ...?>`
+    /// declaration is seen
+    #[default]
+    Auto,
+    /// Lenient HTML: void elements and implicitly closed elements are
+    /// recognised, tag names are case-insensitive for depth tracking and
+    /// mismatched end tags are tolerated
+    Html,
+    /// Strict XML: tag names are case-sensitive and mismatched or unclosed
+    /// elements are reported as parse errors
+    Xml,
+}
+
+/// Number of leading bytes inspected by [`detect`]
+pub const SNIFF_LEN: usize = 512;
+
+/// Magic numbers of common binary formats served where markup is expected
+const BINARY_SIGNATURES: &[(&[u8], &str)] = &[
+    (b"%PDF-", "PDF document"),
+    (b"\x89PNG\r\n\x1a\n", "PNG image"),
+    (b"\xff\xd8\xff", "JPEG image"),
+    (b"GIF87a", "GIF image"),
+    (b"GIF89a", "GIF image"),
+    (b"RIFF", "RIFF media"),
+    (b"PK\x03\x04", "zip archive"),
+    (b"\x00asm", "WebAssembly module"),
+];
+
+/// Choose a parse mode from a `Content-Type` header and the first bytes of
+/// the (decompressed) body
+///
+/// A markup media type decides the mode; otherwise it is sniffed from the
+/// content (an XML declaration means XML, a doctype or `<html>` means HTML).
+/// Binary content (images, PDFs, ...) fails with
+/// `FerretError::UnsupportedContent`, whatever the header claims.
+///
+/// # Example
+/// ```
+/// use ferret::sniff::{detect, ParseMode};
+///
+/// assert_eq!(detect(Some("text/html; charset=utf-8"), b"<p>").unwrap(), ParseMode::Html);
+/// assert_eq!(detect(None, b"<?xml version=\"1.0\"?><rss/>").unwrap(), ParseMode::Xml);
+/// assert!(detect(Some("application/pdf"), b"%PDF-1.7").is_err());
+/// ```
+pub fn detect(content_type: Option<&str>, head: &[u8]) -> Result<ParseMode> {
+    let head = &head[..head.len().min(SNIFF_LEN)];
+    if let Some((_, kind)) = BINARY_SIGNATURES
+        .iter()
+        .find(|(magic, _)| head.starts_with(magic))
+    {
+        return Err(FerretError::UnsupportedContent(kind.to_string()).into());
+    }
+
+    if let Some(mode) = content_type.map(from_content_type).transpose()?.flatten() {
+        return Ok(mode);
+    }
+
+    // Text never contains NUL bytes (UTF-16 markup is not supported either)
+    if head.contains(&0) {
+        return Err(FerretError::UnsupportedContent("binary data".to_string()).into());
+    }
+    Ok(sniff_markup(head))
+}
+
+/// Mode implied by a media type, or `None` when it doesn't say
+fn from_content_type(content_type: &str) -> Result<Option<ParseMode>> {
+    let essence = content_type
+        .split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_ascii_lowercase();
+
+    let mode = match essence.as_str() {
+        "text/html" => Some(ParseMode::Html),
+        "text/xml" | "application/xml" => Some(ParseMode::Xml),
+        // XHTML, SVG, RSS, Atom, ...
+        xml if xml.ends_with("+xml") => Some(ParseMode::Xml),
+        binary
+            if ["image/", "audio/", "video/", "font/"]
+                .iter()
+                .any(|prefix| binary.starts_with(prefix))
+                || matches!(
+                    binary,
+                    "application/pdf" | "application/zip" | "application/wasm"
+                ) =>
+        {
+            return Err(FerretError::UnsupportedContent(essence).into());
+        }
+        _ => None,
+    };
+    Ok(mode)
+}
+
+fn sniff_markup(head: &[u8]) -> ParseMode {
+    let head = head.strip_prefix(b"\xef\xbb\xbf").unwrap_or(head);
+    let start = head
+        .iter()
+        .position(|byte| !byte.is_ascii_whitespace())
+        .unwrap_or(head.len());
+    let head = head[start..].to_ascii_lowercase();
+
+    if head.starts_with(b"<?xml") {
+        ParseMode::Xml
+    } else if head.starts_with(b"<!doctype html") || head.starts_with(b"<html") {
+        ParseMode::Html
+    } else {
+        ParseMode::Auto
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unsupported(result: Result<ParseMode>) -> Option<String> {
+        match result.unwrap_err().downcast_ref::<FerretError>() {
+            Some(FerretError::UnsupportedContent(kind)) => Some(kind.clone()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_content_type() {
+        assert_eq!(detect(Some("TEXT/HTML"), b"").unwrap(), ParseMode::Html);
+        assert_eq!(
+            detect(Some("application/xhtml+xml; charset=utf-8"), b"<html>").unwrap(),
+            ParseMode::Xml
+        );
+        assert_eq!(
+            detect(Some("image/svg+xml"), b"<svg/>").unwrap(),
+            ParseMode::Xml
+        );
+        assert_eq!(
+            unsupported(detect(Some("image/png"), b"")),
+            Some("image/png".to_string())
+        );
+    }
+
+    #[test]
+    fn test_sniffing() {
+        assert_eq!(
+            detect(
+                Some("text/plain"),
+                b"\xef\xbb\xbf\n  <?xml version=\"1.0\"?>"
+            )
+            .unwrap(),
+            ParseMode::Xml
+        );
+        assert_eq!(
+            detect(Some("application/octet-stream"), b"<!DOCTYPE html><html>").unwrap(),
+            ParseMode::Html
+        );
+        assert_eq!(
+            detect(None, b"<div>fragment</div>").unwrap(),
+            ParseMode::Auto
+        );
+        assert_eq!(detect(None, b"").unwrap(), ParseMode::Auto);
+    }
+
+    #[test]
+    fn test_binary_content() {
+        // Signatures win over a misleading header
+        assert_eq!(
+            unsupported(detect(Some("text/html"), b"%PDF-1.4\n")),
+            Some("PDF document".to_string())
+        );
+        assert_eq!(
+            unsupported(detect(None, b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR")),
+            Some("PNG image".to_string())
+        );
+        assert_eq!(
+            unsupported(detect(None, b"\x00\x01\x02garbage")),
+            Some("binary data".to_string())
+        );
+    }
+}
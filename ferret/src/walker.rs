@@ -31,7 +31,7 @@ impl<'a> Iterator for DomWalker<'a> {
             // Collect to vector first because InlineVecIter doesn't support rev()
             let children_vec: Vec<_> = children.top().iter().collect();
             for child in children_vec.into_iter().rev() {
-                self.queue.push_front((child.clone(), depth + 1));
+                self.queue.push_front((*child, depth + 1));
             }
         }
 
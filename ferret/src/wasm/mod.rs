@@ -6,7 +6,8 @@ use tl::VDom;
 use wasm_bindgen::prelude::*;
 
 mod renderer;
-use renderer::{render_html_tree_string, render_tree_string};
+use crate::reporter::{RenderOptions, Reporter, TreeDisplay};
+use renderer::render_html_tree_string;
 
 #[wasm_bindgen]
 pub struct FerretSession {
@@ -136,7 +137,7 @@ pub fn analyze_html(content: &str) -> JsValue {
     result.files_analyzed = 1;
 
     let wasm_result = WasmAnalysisResult {
-        tree_view: render_tree_string(&result),
+        tree_view: TreeDisplay.render(&result, &RenderOptions::default().with_color(false)),
         html_tree: render_html_tree_string(&result),
         data: result,
     };
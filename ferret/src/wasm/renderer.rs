@@ -1,72 +1,5 @@
 use crate::analyzer::AnalysisResult;
-
-pub fn render_tree_string(report: &AnalysisResult) -> String {
-    use std::fmt::Write;
-    let mut out = String::new();
-
-    writeln!(out, "📦 Files analyzed: {}", report.files_analyzed).unwrap();
-
-    let mut sorted_tags: Vec<_> = report.tags.values().collect();
-    sorted_tags.sort_by(|a, b| b.count.cmp(&a.count));
-
-    for (i, tag) in sorted_tags.iter().enumerate() {
-        let is_last_tag = i == sorted_tags.len() - 1;
-        let tag_prefix = if is_last_tag {
-            "└── "
-        } else {
-            "├── "
-        };
-
-        writeln!(out, "{}{} ({})", tag_prefix, tag.name, tag.count).unwrap();
-
-        let mut sorted_attrs: Vec<_> = tag.attributes.values().collect();
-        sorted_attrs.sort_by(|a, b| b.count.cmp(&a.count));
-
-        let child_indent = if is_last_tag { "    " } else { "│   " };
-
-        for (j, attr) in sorted_attrs.iter().enumerate() {
-            let is_last_attr = j == sorted_attrs.len() - 1;
-            let attr_prefix = if is_last_attr {
-                "└── "
-            } else {
-                "├── "
-            };
-
-            writeln!(
-                out,
-                "{}{}@{}{}",
-                child_indent,
-                attr_prefix,
-                attr.name,
-                format!(" ({})", attr.count)
-            )
-            .unwrap();
-
-            let val_indent = if is_last_attr { "    " } else { "│   " };
-            let full_val_indent = format!("{}{}", child_indent, val_indent);
-
-            let mut sorted_vals: Vec<_> = attr.value_counts.iter().collect();
-            sorted_vals.sort_by(|a, b| b.1.cmp(a.1));
-
-            for (k, (val, count)) in sorted_vals.iter().take(5).enumerate() {
-                let is_last_val = k == sorted_vals.len().min(5) - 1;
-                let val_prefix = if is_last_val {
-                    "└── "
-                } else {
-                    "├── "
-                };
-
-                writeln!(
-                    out,
-                    "{}{}{} {} ({})",
-                    full_val_indent, val_prefix, "──", val, count
-                )
-                .unwrap();
-            }
-        }
-    }
-    out
-}
+use crate::html::escape;
 
 pub fn render_html_tree_string(report: &AnalysisResult) -> String {
     use std::fmt::Write;
@@ -75,26 +8,28 @@ pub fn render_html_tree_string(report: &AnalysisResult) -> String {
     writeln!(out, "<ul class='tree'>").unwrap();
 
     let mut sorted_tags: Vec<_> = report.tags.values().collect();
-    sorted_tags.sort_by(|a, b| b.count.cmp(&a.count));
+    sorted_tags.sort_by_key(|t| std::cmp::Reverse(t.count));
 
     for tag in sorted_tags {
         writeln!(
             out,
             "<li><details open><summary><span class='tag'>{}</span> <span class='count'>({})</span></summary>",
-            tag.name, tag.count
+            escape(&tag.name),
+            tag.count
         )
         .unwrap();
 
         if !tag.attributes.is_empty() {
             writeln!(out, "<ul>").unwrap();
             let mut sorted_attrs: Vec<_> = tag.attributes.values().collect();
-            sorted_attrs.sort_by(|a, b| b.count.cmp(&a.count));
+            sorted_attrs.sort_by_key(|a| std::cmp::Reverse(a.count));
 
             for attr in sorted_attrs {
                 writeln!(
                     out,
                     "<li><details><summary><span class='attr'>@{}</span> <span class='count'>({})</span></summary>",
-                    attr.name, attr.count
+                    escape(&attr.name),
+                    attr.count
                 )
                 .unwrap();
 
@@ -107,7 +42,8 @@ pub fn render_html_tree_string(report: &AnalysisResult) -> String {
                         writeln!(
                             out,
                             "<li><span class='val'>{}</span> <span class='count'>({})</span></li>",
-                            val, count
+                            escape(val),
+                            count
                         )
                         .unwrap();
                     }
@@ -123,3 +59,20 @@ pub fn render_html_tree_string(report: &AnalysisResult) -> String {
     writeln!(out, "</ul>").unwrap();
     out
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::stream::StreamAnalyzer;
+
+    #[test]
+    fn test_html_tree_escapes_values() {
+        let html = include_str!("../../tests/fixtures/hostile.html");
+        let result = StreamAnalyzer::new(10).analyze_string(html).unwrap();
+        let tree = render_html_tree_string(&result);
+        assert!(tree.contains("&lt;script&gt;alert(1)&lt;/script&gt;"));
+        for marker in ["<script", "<img", "<svg", "<iframe"] {
+            assert!(!tree.contains(marker), "{}", marker);
+        }
+    }
+}
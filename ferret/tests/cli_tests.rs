@@ -0,0 +1,645 @@
+use assert_cmd::Command;
+use std::fs;
+
+fn ferret() -> Command {
+    Command::cargo_bin("ferret").unwrap()
+}
+
+#[test]
+fn test_analyze_stdin() {
+    let html = fs::read_to_string("tests/fixtures/realistic_sample.html").unwrap();
+    let output = ferret()
+        .args(["analyze", "-"])
+        .write_stdin(html)
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    let result: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(result["files_analyzed"], 1);
+    assert!(result["tags"]["div"]["count"].as_u64().unwrap() > 0);
+}
+
+#[test]
+fn test_analyze_stdin_gzip() {
+    use flate2::write::GzEncoder;
+    use std::io::Write;
+
+    let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(b"<feed><entry/><entry/></feed>").unwrap();
+
+    let output = ferret()
+        .args(["analyze", "-", "--format", "flat"])
+        .write_stdin(encoder.finish().unwrap())
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    assert!(String::from_utf8(output).unwrap().contains("entry"));
+}
+
+#[test]
+fn test_analyze_file_tree() {
+    let output = ferret()
+        .args([
+            "analyze",
+            "tests/fixtures/attributes.html",
+            "--format",
+            "tree",
+        ])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    assert!(String::from_utf8(output)
+        .unwrap()
+        .contains("Files analyzed: 1"));
+}
+
+#[test]
+fn test_analyze_missing_file() {
+    ferret()
+        .args(["analyze", "tests/fixtures/missing.html"])
+        .assert()
+        .failure();
+}
+
+#[test]
+fn test_analyze_file_split_on() {
+    let output = ferret()
+        .args([
+            "analyze",
+            "tests/fixtures/realistic_sample.xml",
+            "--split-on",
+            "p",
+        ])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    let result: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(result["files_analyzed"], 1);
+    assert!(result["tags"]["p"]["count"].as_u64().unwrap() > 0);
+}
+
+#[test]
+fn test_analyze_report_options() {
+    let output = ferret()
+        .args([
+            "analyze",
+            "-",
+            "--format",
+            "flat",
+            "--sort",
+            "name",
+            "--order",
+            "desc",
+            "--max-tags",
+            "2",
+        ])
+        .write_stdin("<a href='x'>1</a><br/><p>2</p><p>3</p>")
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let text = String::from_utf8(output).unwrap();
+    let tags: Vec<_> = text
+        .lines()
+        .skip(3)
+        .filter_map(|line| line.split_whitespace().next())
+        .collect();
+    assert_eq!(tags, ["p", "br"]);
+}
+
+#[test]
+fn test_analyze_percentages() {
+    let output = ferret()
+        .args(["analyze", "-", "--format", "tree", "--percentages"])
+        .write_stdin("<ul><li>1</li><li>2</li><li>3</li></ul>")
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    assert!(String::from_utf8(output).unwrap().contains("li (3, 75.0%)"));
+
+    let output = ferret()
+        .args(["analyze", "-", "--percentages"])
+        .write_stdin("<ul><li>1</li><li>2</li><li>3</li></ul>")
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let json: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(json["tags"]["ul"]["percent"], 25.0);
+}
+
+#[test]
+fn test_analyze_fail_if() {
+    let html = r#"<div><p><img alt=""/><img alt="logo"/></p></div>"#;
+    ferret()
+        .args([
+            "analyze",
+            "-",
+            "--fail-if",
+            "count(img) > 5",
+            "--max-depth",
+            "2",
+        ])
+        .write_stdin(html)
+        .assert()
+        .code(0);
+
+    let output = ferret()
+        .args([
+            "analyze",
+            "-",
+            "--format",
+            "summary",
+            "--fail-if",
+            r#"count(img[alt=""]) > 0"#,
+            "--max-depth",
+            "1",
+        ])
+        .write_stdin(html)
+        .assert()
+        .code(3)
+        .get_output()
+        .clone();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Rule violations:     2"), "{}", stdout);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(
+        stderr.lines().collect::<Vec<_>>(),
+        [
+            r#"rule violated: count(img[alt=""]) > 0 (actual 1)"#,
+            "rule violated: max_depth > 1 (actual 2)",
+        ]
+    );
+
+    ferret()
+        .args(["analyze", "-", "--fail-if", "count(img) >"])
+        .write_stdin(html)
+        .assert()
+        .code(2);
+}
+
+#[test]
+fn test_analyze_output_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let html = "<ul><li>1</li><li>2</li></ul>";
+    let run = |args: &[&str]| {
+        let output = ferret()
+            .current_dir(dir.path())
+            .arg("analyze")
+            .arg("-")
+            .args(args)
+            .write_stdin(html)
+            .assert()
+            .success()
+            .get_output()
+            .clone();
+        assert!(output.stdout.is_empty());
+    };
+
+    run(&["-o", "result.json"]);
+    let json: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(dir.path().join("result.json")).unwrap()).unwrap();
+    assert_eq!(json["tags"]["li"]["count"], 2);
+
+    run(&["-o", "report.md"]);
+    let markdown = fs::read_to_string(dir.path().join("report.md")).unwrap();
+    assert!(markdown.contains("| li | 2 |"), "{}", markdown);
+
+    // Extensions that aren't report formats pick an exporter
+    run(&["-o", "tags.csv"]);
+    let csv = fs::read_to_string(dir.path().join("tags.csv")).unwrap();
+    assert!(csv.starts_with("Tag,Count"), "{}", csv);
+
+    // --format wins over the extension
+    run(&["-o", "tree.txt", "--format", "tree", "--color", "always"]);
+    let tree = fs::read_to_string(dir.path().join("tree.txt")).unwrap();
+    assert!(tree.contains("li"), "{}", tree);
+}
+
+#[test]
+fn test_analyze_quiet() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("a.html"), "<p>x</p>").unwrap();
+    // Gzip magic bytes followed by a corrupt stream
+    fs::write(dir.path().join("b.html.gz"), b"\x1f\x8b\x08\x00garbage").unwrap();
+    let stderr = |args: &[&str]| {
+        let output = ferret()
+            .arg("analyze")
+            .arg(dir.path())
+            .args(args)
+            .assert()
+            .success()
+            .get_output()
+            .clone();
+        String::from_utf8(output.stderr).unwrap()
+    };
+
+    assert!(stderr(&[]).contains("b.html.gz"));
+    assert_eq!(stderr(&["-q"]), "");
+    assert!(stderr(&["-v"]).contains("INFO"));
+}
+
+#[test]
+fn test_config_file() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(
+        dir.path().join("ferret.toml"),
+        "[analyzer]\ntop_values = 1\n",
+    )
+    .unwrap();
+    let html = r#"<p class="a"></p><p class="b"></p>"#;
+    let values = |args: &[&str]| -> usize {
+        let output = ferret()
+            .current_dir(dir.path())
+            .env_remove("FERRET_CONFIG")
+            .env_remove("FERRET_TOP_VALUES")
+            .args(args)
+            .write_stdin(html)
+            .assert()
+            .success()
+            .get_output()
+            .stdout
+            .clone();
+        let json: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        json["tags"]["p"]["attributes"]["class"]["value_counts"]
+            .as_object()
+            .unwrap()
+            .len()
+    };
+
+    // ./ferret.toml is picked up; flags override it
+    assert_eq!(values(&["analyze", "-"]), 1);
+    assert_eq!(values(&["analyze", "-", "--top", "5"]), 2);
+
+    fs::write(
+        dir.path().join("other.toml"),
+        "[analyzer]\ntop_values = 5\n",
+    )
+    .unwrap();
+    assert_eq!(values(&["--config", "other.toml", "analyze", "-"]), 2);
+
+    fs::write(dir.path().join("bad.toml"), "[analyzer]\ntop = 5\n").unwrap();
+    ferret()
+        .current_dir(dir.path())
+        .args(["analyze", "-", "--config", "bad.toml"])
+        .write_stdin(html)
+        .assert()
+        .failure();
+}
+
+#[test]
+fn test_analyze_color() {
+    let tree = |extra: &[&str]| {
+        let output = ferret()
+            .args(["analyze", "-", "--format", "tree"])
+            .args(extra)
+            .write_stdin("<p>x</p>")
+            .assert()
+            .success()
+            .get_output()
+            .stdout
+            .clone();
+        String::from_utf8(output).unwrap()
+    };
+    // Not a terminal
+    assert!(!tree(&[]).contains('\x1b'));
+    assert!(tree(&["--color", "always"]).contains('\x1b'));
+    assert!(!tree(&["--color", "never"]).contains('\x1b'));
+}
+
+#[test]
+fn test_analyze_multiple_inputs() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::create_dir(dir.path().join("pages")).unwrap();
+    fs::write(dir.path().join("a.html"), "<ul><li>1</li><li>2</li></ul>").unwrap();
+    fs::write(dir.path().join("pages/b.html"), "<p>x</p>").unwrap();
+    fs::write(dir.path().join("pages/c.html"), "<p>y</p>").unwrap();
+    let json = |args: &[&str]| -> serde_json::Value {
+        let output = ferret()
+            .current_dir(dir.path())
+            .arg("analyze")
+            .args(args)
+            .assert()
+            .success()
+            .get_output()
+            .stdout
+            .clone();
+        serde_json::from_slice(&output).unwrap()
+    };
+
+    // Entries follow the input order, globs expand in path order
+    let set = json(&["pages/*.html", "a.html", "--format", "json"]);
+    let sources: Vec<_> = set["entries"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| entry["source"].as_str().unwrap().replace('\\', "/"))
+        .collect();
+    assert_eq!(sources, ["pages/b.html", "pages/c.html", "a.html"]);
+    assert_eq!(set["aggregate"]["tags"]["p"]["count"], 2);
+
+    let merged = json(&["pages/*.html", "a.html", "--aggregate"]);
+    assert_eq!(merged["files_analyzed"], 3);
+    assert_eq!(merged["tags"]["li"]["count"], 2);
+
+    let entries = json(&["a.html", "pages/b.html", "--per-file"]);
+    assert_eq!(entries.as_array().unwrap().len(), 2);
+    assert_eq!(entries[1]["result"]["tags"]["p"]["count"], 1);
+
+    let output = ferret()
+        .current_dir(dir.path())
+        .args([
+            "analyze",
+            "a.html",
+            "pages/b.html",
+            "--per-file",
+            "--format",
+            "tree",
+        ])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let text = String::from_utf8(output).unwrap();
+    assert!(
+        text.contains("==> a.html <==\n📦 Files analyzed: 1"),
+        "{}",
+        text
+    );
+    assert!(text.contains("==> pages/b.html <=="), "{}", text);
+
+    ferret()
+        .current_dir(dir.path())
+        .args(["analyze", "missing/*.html", "a.html"])
+        .assert()
+        .failure();
+    ferret()
+        .args(["analyze", "a.html", "--aggregate", "--per-file"])
+        .assert()
+        .failure();
+}
+
+#[test]
+fn test_analyze_dir_defaults_to_summary() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("a.html"), "<ul><li>1</li><li>2</li></ul>").unwrap();
+    fs::write(dir.path().join("b.html"), "<p>x</p>").unwrap();
+
+    let output = ferret()
+        .args(["analyze", dir.path().to_str().unwrap()])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let text = String::from_utf8(output).unwrap();
+    assert!(text.contains("Files analyzed: 2"), "{}", text);
+    assert!(text.contains("Elements:            4"), "{}", text);
+    assert!(
+        text.contains("Top tags:            li (2), p (1), ul (1)"),
+        "{}",
+        text
+    );
+
+    // JSON stays available
+    let output = ferret()
+        .args(["analyze", dir.path().to_str().unwrap(), "--format", "json"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let set: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(set["aggregate"]["files_analyzed"], 2);
+}
+
+#[test]
+fn test_diff() {
+    let dir = tempfile::tempdir().unwrap();
+    let old = dir.path().join("old.csv");
+    let new = dir.path().join("new.html");
+    fs::write(
+        &old,
+        "Tag,Count,Attribute,Attribute Count,Value,Value Count\np,2,,,,\nbr,1,,,,\n",
+    )
+    .unwrap();
+    fs::write(&new, "<p>1</p><p>2</p><p>3</p><hr>").unwrap();
+
+    let output = ferret()
+        .current_dir(dir.path())
+        .args(["diff", "old.csv", "new.html"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let text = String::from_utf8(output).unwrap();
+    assert_eq!(
+        text.lines().collect::<Vec<_>>(),
+        [
+            "  old.csv       │ new.html",
+            "- br (1)        │",
+            "+               │ hr (1)",
+            "~ p (2)         │ p (3)         +1",
+            "~ max depth (0) │ max depth (1) +1",
+        ]
+    );
+}
+
+#[test]
+fn test_crawl_invalid_seed() {
+    let output = ferret()
+        .args(["crawl", "not a url"])
+        .assert()
+        .failure()
+        .get_output()
+        .stderr
+        .clone();
+    assert!(String::from_utf8_lossy(&output).contains("Invalid URL"));
+}
+
+#[test]
+fn test_crawl_graph_format() {
+    let output = ferret()
+        .args(["crawl", "https://example.com/", "--graph", "links.png"])
+        .assert()
+        .failure()
+        .get_output()
+        .stderr
+        .clone();
+    assert!(String::from_utf8_lossy(&output).contains("Unknown graph format"));
+}
+
+#[test]
+fn test_monitor() {
+    let dir = tempfile::tempdir().unwrap();
+    let page = dir.path().join("page.html");
+    let store = dir.path().join("fingerprints.json");
+    let monitor = || {
+        let mut command = ferret();
+        command.args(["monitor", page.to_str().unwrap(), "--store"]);
+        command.arg(&store);
+        command
+    };
+    let stdout = |assert: assert_cmd::assert::Assert| {
+        String::from_utf8(assert.get_output().stdout.clone()).unwrap()
+    };
+
+    fs::write(&page, "<ul><li>a</li><li>b</li></ul><ul></ul>").unwrap();
+    assert!(stdout(monitor().assert().success()).starts_with("new "));
+    fs::write(&page, "<ul><li>a</li><li>b</li><li>c</li></ul><ul></ul>").unwrap();
+    assert!(stdout(monitor().assert().success()).starts_with("unchanged "));
+
+    fs::write(&page, "<ol><li>a</li><li>b</li></ol><ol></ol>").unwrap();
+    let output = stdout(monitor().arg("--dry-run").assert().code(4));
+    assert!(output.starts_with("changed "));
+    assert!(output.contains("    + ol > li\n"));
+    assert!(output.contains("    - ul\n"));
+    // The dry run kept the old fingerprint
+    monitor().arg("--json").assert().code(4);
+    monitor().assert().success();
+}
+
+#[test]
+fn test_analyze_clusters() {
+    let dir = tempfile::tempdir().unwrap();
+    let product = r#"<div class="product"><h2>A</h2><span class="price">1</span>
+        <img src="a.png" alt=""><a href="/buy">Buy</a></div>"#;
+    let pages = [
+        ("a.html", product.to_string()),
+        ("b.html", product.replace("A", "B")),
+        (
+            "news.html",
+            "<article><h1>News</h1><p>a</p><p>b</p></article>".to_string(),
+        ),
+    ];
+    let mut inputs = Vec::new();
+    for (name, html) in pages {
+        let path = dir.path().join(name);
+        fs::write(&path, html).unwrap();
+        inputs.push(path.to_str().unwrap().to_string());
+    }
+
+    let output = ferret()
+        .args(["analyze", "--clusters", "--format", "json"])
+        .args(&inputs)
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let set: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    let ids: Vec<_> = set["entries"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| entry["cluster"].as_u64().unwrap())
+        .collect();
+    assert_eq!(ids, [0, 0, 1]);
+    assert_eq!(set["clusters"][0]["pages"], 2);
+
+    let output = ferret()
+        .args(["analyze", "--format", "summary", "--clusters=0"])
+        .args(&inputs)
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.contains("Template clusters: 2\n  #0         2 pages"));
+}
+
+#[test]
+fn test_analyze_third_party() {
+    let html = r#"<link rel="canonical" href="https://shop.example/">
+        <script src="/app.js"></script>
+        <script src="https://connect.facebook.net/en_US/fbevents.js"></script>
+        <img src="https://images.cdn.test/a.png">"#;
+    let output = ferret()
+        .args(["analyze", "-", "--section", "third-party"])
+        .write_stdin(html)
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let json: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(json["third_party"]["first_party"], 1);
+    assert_eq!(
+        json["third_party"]["origins"]["connect.facebook.net"]["tracker"],
+        "Meta Pixel"
+    );
+
+    ferret()
+        .args([
+            "analyze",
+            "-",
+            "--section",
+            "third-party",
+            "--fail-if",
+            "trackers > 0",
+        ])
+        .write_stdin(html)
+        .assert()
+        .code(3);
+
+    ferret()
+        .args(["analyze", "-", "--section", "seo"])
+        .write_stdin(html)
+        .assert()
+        .code(2);
+}
+
+#[test]
+fn test_analyze_css() {
+    let dir = tempfile::tempdir().unwrap();
+    let css = dir.path().join("site.css");
+    fs::write(&css, ".card, .hidden { display: block }").unwrap();
+    let html = r#"<style>#main { margin: 0 }</style>
+        <main id="main"><div class="card shadow">x</div></main>"#;
+    let output = ferret()
+        .args(["analyze", "-", "--css"])
+        .arg(&css)
+        .write_stdin(html)
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let json: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(
+        json["css"]["undefined_classes"],
+        serde_json::json!(["shadow"])
+    );
+    assert_eq!(json["css"]["unused_classes"], serde_json::json!(["hidden"]));
+    assert!(json["css"].get("undefined_ids").is_none());
+
+    ferret()
+        .args([
+            "analyze",
+            "-",
+            "--fail-if",
+            "undefined_classes > 0",
+            "--css",
+        ])
+        .arg(&css)
+        .write_stdin(html)
+        .assert()
+        .code(3);
+}
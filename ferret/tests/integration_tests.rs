@@ -280,24 +280,650 @@ fn test_tag_count_accumulation() {
     assert_eq!(result.tags.get("span").unwrap().count, 2);
 }
 
-// Note: URL tests would require a mock HTTP server
-// Consider using `mockito` or `wiremock` for actual URL testing
-// Example placeholder:
-// #[tokio::test]
-// async fn test_analyze_url() {
-//     use mockito::Server;
-//     let mut server = Server::new_async().await;
-//     let mock = server.mock("GET", "/test.xml")
-//         .with_body("<root><item /></root>")
-//         .create();
-//
-//     let analyzer = StreamAnalyzer::new(10);
-//     let url = format!("{}/test.xml", server.url());
-//     let result = analyzer.analyze_url(&url).await.unwrap();
-//
-//     assert!(result.tags.contains_key("root"));
-//     mock.assert();
-// }
+#[tokio::test]
+async fn test_analyze_url() {
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/test.xml"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("<root><item /></root>"))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let analyzer = StreamAnalyzer::new(10);
+    let url = format!("{}/test.xml", server.uri());
+    let result = analyzer.analyze_url(&url).await.unwrap();
+
+    assert!(result.tags.contains_key("root"));
+    assert!(result.tags.contains_key("item"));
+}
+
+#[tokio::test]
+async fn test_analyze_url_sections() {
+    use ferret::analyzer::section::Section;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    let server = MockServer::start().await;
+    let html = r#"<html><head>
+        <script src="/app.js"></script>
+        <script src="https://cdn.test/lib.js"></script>
+        <script src="https://www.googletagmanager.com/gtag/js"></script>
+        </head></html>"#;
+    Mock::given(method("GET"))
+        .and(path("/page"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("content-type", "text/html")
+                .insert_header("x-frame-options", "DENY")
+                .insert_header("x-content-type-options", "nosniff")
+                .set_body_string(html),
+        )
+        .mount(&server)
+        .await;
+
+    let analyzer = StreamAnalyzer::new(10).with_sections([Section::ThirdParty, Section::Security]);
+    let result = analyzer
+        .analyze_url(&format!("{}/page", server.uri()))
+        .await
+        .unwrap();
+
+    let third_party = result.third_party.unwrap();
+    assert_eq!(third_party.first_party, 1);
+    assert_eq!(third_party.third_parties(), 2);
+
+    let security = result.security.unwrap();
+    assert_eq!(security.findings.len(), 2);
+    assert_eq!(security.headers["x-frame-options"], "DENY");
+    // The mock server speaks plain http, so HSTS isn't expected
+    assert_eq!(
+        security.missing_headers,
+        ["content-security-policy", "referrer-policy"]
+    );
+}
+
+#[tokio::test]
+async fn test_fetch_linked_stylesheets() {
+    use ferret::analyzer::css::StylesheetFetcher;
+    use ferret::analyzer::section::Section;
+    use ferret::fetch::FetchOptions;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    let server = MockServer::start().await;
+    let html = r#"<html><head>
+        <link rel="stylesheet" href="/css/site.css">
+        <link rel="stylesheet" href="/css/missing.css">
+        </head><body class="home"><nav class="menu"></nav></body></html>"#;
+    Mock::given(method("GET"))
+        .and(path("/page"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(html))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/css/site.css"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(".menu, .footer { }"))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let analyzer = StreamAnalyzer::new(10).with_sections([Section::Css]);
+    let url = format!("{}/page", server.uri());
+    let mut fetcher = StylesheetFetcher::new(FetchOptions::default()).unwrap();
+    for _ in 0..2 {
+        let mut css = analyzer.analyze_url(&url).await.unwrap().css.unwrap();
+        assert_eq!(
+            css.stylesheets,
+            [
+                format!("{}/css/site.css", server.uri()),
+                format!("{}/css/missing.css", server.uri())
+            ]
+        );
+        fetcher.add_linked(&mut css).await;
+        assert_eq!(css.undefined_classes, ["home"]);
+        assert_eq!(css.unused_classes, ["footer"]);
+    }
+}
+
+#[tokio::test]
+async fn test_analyze_url_large_body() {
+    use wiremock::matchers::method;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    let records = 100_000;
+    let mut body = String::from("<feed>");
+    for i in 0..records {
+        body.push_str(&format!(r#"<entry id="{}"><title>Item</title></entry>"#, i));
+    }
+    body.push_str("</feed>");
+
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(body))
+        .mount(&server)
+        .await;
+
+    let analyzer = StreamAnalyzer::new(10);
+    let result = analyzer.analyze_url(&server.uri()).await.unwrap();
+
+    assert_eq!(result.tags.get("entry").unwrap().count, records);
+    assert_eq!(result.tags.get("title").unwrap().count, records);
+    assert_eq!(result.max_depth, 3);
+}
+
+fn gzip(content: &str) -> Vec<u8> {
+    use flate2::write::GzEncoder;
+    use std::io::Write;
+
+    let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(content.as_bytes()).unwrap();
+    encoder.finish().unwrap()
+}
+
+#[test]
+fn test_analyze_gzip_file() {
+    use std::io::Write;
+
+    let xml = read_fixture("realistic_sample.xml");
+    let expected = StreamAnalyzer::new(10).analyze_string(&xml).unwrap();
+
+    // Detected by extension
+    let mut file = tempfile::Builder::new()
+        .suffix(".xml.gz")
+        .tempfile()
+        .unwrap();
+    file.write_all(&gzip(&xml)).unwrap();
+    let result = StreamAnalyzer::new(10).analyze_file(file.path()).unwrap();
+    assert_eq!(result.tags.len(), expected.tags.len());
+    assert_eq!(result.max_depth, expected.max_depth);
+
+    // Detected by magic bytes
+    let mut file = tempfile::Builder::new().suffix(".xml").tempfile().unwrap();
+    file.write_all(&gzip(&xml)).unwrap();
+    let result = StreamAnalyzer::new(10).analyze_file(file.path()).unwrap();
+    assert_eq!(result.tags.len(), expected.tags.len());
+}
+
+#[tokio::test]
+async fn test_analyze_url_compressed() {
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    let body = "<urlset><url><loc>https://example.com/</loc></url></urlset>";
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/encoded.xml"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("Content-Encoding", "gzip")
+                .set_body_bytes(gzip(body)),
+        )
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/sitemap.xml.gz"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("Content-Type", "application/gzip")
+                .set_body_bytes(gzip(body)),
+        )
+        .mount(&server)
+        .await;
+
+    let analyzer = StreamAnalyzer::new(10);
+    for name in ["encoded.xml", "sitemap.xml.gz"] {
+        let url = format!("{}/{}", server.uri(), name);
+        let result = analyzer.analyze_url(&url).await.unwrap();
+        assert_eq!(result.tags.get("loc").unwrap().count, 1, "{}", name);
+    }
+}
+
+#[tokio::test]
+async fn test_analyze_url_fetch_options() {
+    use ferret::fetch::FetchOptions;
+    use wiremock::matchers::{header, method};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(header("User-Agent", "Mozilla/5.0 (compatible; test)"))
+        .and(header("Accept-Language", "de"))
+        .and(header("Authorization", "Basic dXNlcjpwYXNz"))
+        .and(header("Cookie", "consent=yes"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("<div></div>"))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(403))
+        .mount(&server)
+        .await;
+
+    let options = FetchOptions::default()
+        .user_agent("Mozilla/5.0 (compatible; test)")
+        .header("Accept-Language", "de")
+        .basic_auth("user", Some("pass".to_string()))
+        .cookie("consent", "yes");
+    let analyzer = StreamAnalyzer::new(10).with_fetch_options(options);
+    let result = analyzer.analyze_url(&server.uri()).await.unwrap();
+    assert!(result.tags.contains_key("div"));
+
+    // Without the expected headers the server refuses the request
+    let analyzer = StreamAnalyzer::new(10);
+    assert!(analyzer.analyze_url(&server.uri()).await.is_err());
+}
+
+#[tokio::test]
+async fn test_analyze_url_retries() {
+    use ferret::fetch::{FetchOptions, RetryPolicy};
+    use std::time::Duration;
+    use wiremock::matchers::method;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(503))
+        .up_to_n_times(2)
+        .with_priority(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "0"))
+        .up_to_n_times(1)
+        .with_priority(2)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("<div></div>"))
+        .with_priority(3)
+        .mount(&server)
+        .await;
+
+    let retry = RetryPolicy {
+        max_retries: 3,
+        initial_backoff: Duration::from_millis(1),
+        max_backoff: Duration::from_millis(10),
+    };
+
+    // Not enough retries: the last 429 is reported
+    let analyzer =
+        StreamAnalyzer::new(10).with_fetch_options(FetchOptions::default().retry(RetryPolicy {
+            max_retries: 2,
+            ..retry
+        }));
+    assert!(analyzer.analyze_url(&server.uri()).await.is_err());
+
+    server.reset().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(503))
+        .up_to_n_times(3)
+        .with_priority(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("<div></div>"))
+        .with_priority(2)
+        .mount(&server)
+        .await;
+
+    let analyzer = StreamAnalyzer::new(10).with_fetch_options(FetchOptions::default().retry(retry));
+    let result = analyzer.analyze_url(&server.uri()).await.unwrap();
+    assert!(result.tags.contains_key("div"));
+    assert_eq!(server.received_requests().await.unwrap().len(), 4);
+}
+
+#[tokio::test]
+async fn test_analyze_url_timeout() {
+    use ferret::fetch::FetchOptions;
+    use std::time::Duration;
+    use wiremock::matchers::method;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_string("<div></div>")
+                .set_delay(Duration::from_secs(2)),
+        )
+        .mount(&server)
+        .await;
+
+    let analyzer = StreamAnalyzer::new(10)
+        .with_fetch_options(FetchOptions::default().timeout(Duration::from_millis(100)));
+    assert!(analyzer.analyze_url(&server.uri()).await.is_err());
+}
+
+#[tokio::test]
+async fn test_analyze_url_redirects() {
+    use ferret::fetch::FetchOptions;
+    use wiremock::matchers::{header_exists, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    let server = MockServer::start().await;
+    let other = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/old"))
+        .respond_with(ResponseTemplate::new(301).insert_header("Location", "/new"))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/new"))
+        .respond_with(
+            ResponseTemplate::new(302)
+                .insert_header("Location", format!("{}/consent", other.uri()).as_str()),
+        )
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(header_exists("Authorization"))
+        .respond_with(ResponseTemplate::new(400))
+        .mount(&other)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/consent"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("<form></form>"))
+        .mount(&other)
+        .await;
+
+    let options = FetchOptions::default().bearer_auth("secret");
+    let analyzer = StreamAnalyzer::new(10).with_fetch_options(options.clone());
+    let url = format!("{}/old", server.uri());
+    let result = analyzer.analyze_url(&url).await.unwrap();
+
+    assert!(result.tags.contains_key("form"));
+    assert_eq!(result.redirects.len(), 2);
+    assert_eq!(result.redirects[0].url, url);
+    assert_eq!(result.redirects[0].status, 301);
+    assert_eq!(
+        result.redirects[0].location,
+        format!("{}/new", server.uri())
+    );
+    assert_eq!(result.redirects[1].status, 302);
+    assert_eq!(
+        result.redirects[1].location,
+        format!("{}/consent", other.uri())
+    );
+
+    // Limited or disabled redirects
+    let analyzer = StreamAnalyzer::new(10).with_fetch_options(options.clone().max_redirects(1));
+    let err = analyzer.analyze_url(&url).await.unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<ferret::error::FerretError>(),
+        Some(ferret::error::FerretError::TooManyRedirects(1))
+    ));
+
+    let analyzer = StreamAnalyzer::new(10).with_fetch_options(options.max_redirects(0));
+    assert!(analyzer.analyze_url(&url).await.is_err());
+}
+
+#[tokio::test]
+async fn test_analyze_url_cookie_jar() {
+    use ferret::fetch::{CookieJar, FetchOptions};
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/gate"))
+        .respond_with(
+            ResponseTemplate::new(302)
+                .insert_header("Set-Cookie", "session=abc; Path=/")
+                .insert_header("Location", "/content"),
+        )
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/content"))
+        .and(header("Cookie", "session=abc"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("<article></article>"))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/content"))
+        .respond_with(ResponseTemplate::new(403))
+        .mount(&server)
+        .await;
+
+    // Without a jar the cookie set by the gate is dropped
+    let analyzer = StreamAnalyzer::new(10);
+    let url = format!("{}/gate", server.uri());
+    assert!(analyzer.analyze_url(&url).await.is_err());
+
+    let jar = CookieJar::new();
+    let analyzer =
+        StreamAnalyzer::new(10).with_fetch_options(FetchOptions::default().cookie_jar(jar.clone()));
+    let result = analyzer.analyze_url(&url).await.unwrap();
+    assert!(result.tags.contains_key("article"));
+
+    // The session is kept for later requests
+    let content = format!("{}/content", server.uri());
+    assert_eq!(jar.cookies(&content).as_deref(), Some("session=abc"));
+    assert!(analyzer.analyze_url(&content).await.is_ok());
+
+    // Pre-seeded cookies
+    let jar = CookieJar::new();
+    jar.add(&server.uri(), "session=abc").unwrap();
+    let analyzer =
+        StreamAnalyzer::new(10).with_fetch_options(FetchOptions::default().cookie_jar(jar));
+    assert!(analyzer.analyze_url(&content).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_analyze_url_robots() {
+    use ferret::error::FerretError;
+    use ferret::fetch::FetchOptions;
+    use ferret::robots::RobotsPolicy;
+    use std::time::{Duration, Instant};
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/robots.txt"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(
+            "User-agent: *\nDisallow: /admin\nCrawl-delay: 0.2\n\nUser-agent: BadBot\nDisallow: /\n",
+        ))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/page"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("<p></p>"))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/old"))
+        .respond_with(ResponseTemplate::new(301).insert_header("Location", "/admin/page"))
+        .mount(&server)
+        .await;
+
+    let policy = RobotsPolicy::new();
+    let analyzer =
+        StreamAnalyzer::new(10).with_fetch_options(FetchOptions::default().robots(policy.clone()));
+
+    let start = Instant::now();
+    analyzer
+        .analyze_url(&format!("{}/page", server.uri()))
+        .await
+        .unwrap();
+    analyzer
+        .analyze_url(&format!("{}/page", server.uri()))
+        .await
+        .unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(200));
+
+    // Redirect targets are checked too
+    let err = analyzer
+        .analyze_url(&format!("{}/old", server.uri()))
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<FerretError>(),
+        Some(FerretError::RobotsDisallowed(url)) if url.ends_with("/admin/page")
+    ));
+
+    let analyzer = StreamAnalyzer::new(10).with_fetch_options(
+        FetchOptions::default()
+            .user_agent("BadBot/1.0")
+            .robots(policy),
+    );
+    assert!(analyzer
+        .analyze_url(&format!("{}/page", server.uri()))
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_analyze_url_forward_proxy() {
+    use ferret::fetch::{FetchOptions, ProxyConfig};
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    // The mock server stands in for an HTTP proxy: the target host never
+    // resolves, so the request can only succeed by going through it.
+    let proxy = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/page"))
+        .and(header("Proxy-Authorization", "Basic dXNlcjpzZWNyZXQ="))
+        .respond_with(ResponseTemplate::new(200).set_body_string("<main></main>"))
+        .expect(1)
+        .mount(&proxy)
+        .await;
+
+    let options =
+        FetchOptions::default().proxy(ProxyConfig::new(proxy.uri()).basic_auth("user", "secret"));
+    let analyzer = StreamAnalyzer::new(10).with_fetch_options(options);
+    let result = analyzer
+        .analyze_url("http://ferret-test.invalid/page")
+        .await
+        .unwrap();
+    assert!(result.tags.contains_key("main"));
+}
+
+#[tokio::test]
+async fn test_analyze_url_cache_revalidation() {
+    use ferret::cache::HttpCache;
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/feed.xml"))
+        .and(header("If-None-Match", "\"v1\""))
+        .respond_with(ResponseTemplate::new(304))
+        .expect(2)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/feed.xml"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("ETag", "\"v1\"")
+                .set_body_string(r#"<feed><entry id="1"/><entry id="2"/></feed>"#),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let cache = HttpCache::new(dir.path());
+    let url = format!("{}/feed.xml", server.uri());
+
+    let analyzer = StreamAnalyzer::new(10).with_cache(cache.clone());
+    let fresh = analyzer.analyze_url(&url).await.unwrap();
+    assert_eq!(fresh.tags["entry"].count, 2);
+    assert_eq!(cache.get(&url).unwrap().etag.as_deref(), Some("\"v1\""));
+
+    let cached = analyzer.analyze_url(&url).await.unwrap();
+    assert_eq!(cached.tags["entry"].count, 2);
+    assert_eq!(cached.tags["entry"].attributes["id"].value_counts.len(), 2);
+
+    // Different settings re-analyze the stored body
+    let analyzer = StreamAnalyzer::new(1).with_cache(cache);
+    let result = analyzer.analyze_url(&url).await.unwrap();
+    assert_eq!(result.tags["entry"].count, 2);
+}
+
+#[tokio::test]
+async fn test_analyze_url_http_error() {
+    use wiremock::matchers::method;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(404))
+        .mount(&server)
+        .await;
+
+    let analyzer = StreamAnalyzer::new(10);
+    assert!(analyzer.analyze_url(&server.uri()).await.is_err());
+}
+
+#[tokio::test]
+async fn test_analyze_url_content_type() {
+    use ferret::error::FerretError;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    // The same tag soup, served as HTML and as XML
+    let body = "<ul><li>a<li>b</ul><P>x</p>";
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/page"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(body, "text/html; charset=utf-8"))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/feed"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(body, "application/rss+xml"))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/sniffed"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_string(format!(r#"<?xml version="1.0"?>{}"#, body)),
+        )
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/logo"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_raw(b"\x89PNG\r\n\x1a\n\0\0".to_vec(), "text/html"),
+        )
+        .mount(&server)
+        .await;
+
+    let analyzer = StreamAnalyzer::new(10);
+
+    let html = analyzer
+        .analyze_url(&format!("{}/page", server.uri()))
+        .await
+        .unwrap();
+    assert_eq!(html.max_depth, 2);
+    assert!(html.parse_errors.is_empty());
+
+    for url in ["feed", "sniffed"] {
+        let xml = analyzer
+            .analyze_url(&format!("{}/{}", server.uri(), url))
+            .await
+            .unwrap();
+        assert_eq!(xml.max_depth, 3, "{}", url);
+        assert!(!xml.parse_errors.is_empty(), "{}", url);
+    }
+
+    let err = analyzer
+        .analyze_url(&format!("{}/logo", server.uri()))
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<FerretError>(),
+        Some(FerretError::UnsupportedContent(kind)) if kind == "PNG image"
+    ));
+}
+
 #[test]
 fn test_fixture_attributes() {
     let html = read_fixture("attributes.html");
@@ -307,7 +933,7 @@ fn test_fixture_attributes() {
     // attributes.html likely contains various attributes
     // We assume it has at least some content.
     // Without seeing content, we just check it runs and finds something.
-    assert!(result.tags.len() > 0);
+    assert!(!result.tags.is_empty());
 }
 
 #[test]
@@ -355,7 +981,7 @@ fn test_fixture_xml() {
     let result = analyzer.analyze_string(&xml).unwrap();
 
     // XML tags should be found
-    assert!(result.tags.len() > 0);
+    assert!(!result.tags.is_empty());
 }
 
 #[test]
@@ -375,5 +1001,660 @@ fn test_fixture_unicode() {
     let result = analyzer.analyze_string(&html).unwrap();
 
     // Basic check that it didn't crash on unicode
-    assert!(result.tags.len() > 0);
+    assert!(!result.tags.is_empty());
+}
+
+#[test]
+fn test_analyze_dir_and_glob() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    fs::create_dir_all(root.join("sub/deeper")).unwrap();
+    fs::create_dir_all(root.join(".hidden")).unwrap();
+    fs::write(root.join("a.html"), r#"<div class="x"><p></p></div>"#).unwrap();
+    fs::write(root.join("b.xml"), r#"<feed><entry/></feed>"#).unwrap();
+    fs::write(root.join("notes.txt"), "<div></div>").unwrap();
+    fs::write(root.join("sub/c.html"), r#"<div class="y"></div>"#).unwrap();
+    fs::write(root.join("sub/deeper/d.htm"), "<div></div>").unwrap();
+    fs::write(root.join(".hidden/e.html"), "<div></div>").unwrap();
+    fs::write(root.join("sub/f.html.gz"), gzip("<section></section>")).unwrap();
+
+    let analyzer = StreamAnalyzer::new(10);
+
+    let set = analyzer.analyze_dir(root, false).unwrap();
+    assert_eq!(set.entries.len(), 2);
+    assert_eq!(set.aggregate.files_analyzed, 2);
+    assert_eq!(set.aggregate.tags["div"].count, 1);
+    assert!(set.aggregate.tags.contains_key("entry"));
+
+    let set = analyzer.analyze_dir(root, true).unwrap();
+    assert_eq!(set.entries.len(), 5);
+    assert_eq!(set.errors().count(), 0);
+    assert_eq!(set.aggregate.tags["div"].count, 3);
+    assert_eq!(set.aggregate.tags["div"].attributes["class"].count, 2);
+    assert!(set.aggregate.tags.contains_key("section"));
+    let a = set
+        .entries
+        .iter()
+        .find(|entry| entry.source.ends_with("a.html"))
+        .unwrap();
+    assert_eq!(a.result.as_ref().unwrap().tags["p"].count, 1);
+
+    let pattern = format!("{}/**/*.html", root.display());
+    let set = analyzer.analyze_glob(&pattern).unwrap();
+    assert_eq!(set.entries.len(), 2);
+
+    assert!(analyzer.analyze_dir(&root.join("missing"), true).is_err());
+}
+
+#[test]
+fn test_analyze_files_reports_errors() {
+    use ferret::limits::Limits;
+
+    let dir = tempfile::tempdir().unwrap();
+    let small = dir.path().join("small.html");
+    let large = dir.path().join("large.html");
+    fs::write(&small, "<p></p>").unwrap();
+    fs::write(&large, "<p>".repeat(100)).unwrap();
+
+    let analyzer = StreamAnalyzer::new(10).with_limits(Limits {
+        max_input_bytes: Some(50),
+        ..Limits::default()
+    });
+    let set = analyzer.analyze_files(vec![small, large, dir.path().join("missing.html")]);
+
+    assert_eq!(set.entries.len(), 3);
+    assert_eq!(set.errors().count(), 2);
+    assert!(set.entries[0].result.is_some());
+    assert!(set.entries[1].error.as_ref().unwrap().contains("50 bytes"));
+    assert_eq!(set.aggregate.files_analyzed, 1);
+}
+
+#[test]
+fn test_analyze_archives() {
+    use std::io::Write;
+
+    let dir = tempfile::tempdir().unwrap();
+    let members: [(&str, &[u8]); 3] = [
+        ("site/index.html", b"<div class=\"a\"><p></p></div>"),
+        ("site/feed.xml", b"<feed><entry/></feed>"),
+        ("site/style.css", b"div { color: red }"),
+    ];
+
+    let zip_path = dir.path().join("dump.zip");
+    let mut zip = zip::ZipWriter::new(fs::File::create(&zip_path).unwrap());
+    for (name, content) in members {
+        zip.start_file(name, zip::write::SimpleFileOptions::default())
+            .unwrap();
+        zip.write_all(content).unwrap();
+    }
+    zip.finish().unwrap();
+
+    let tar_path = dir.path().join("dump.tar.gz");
+    let encoder = flate2::write::GzEncoder::new(
+        fs::File::create(&tar_path).unwrap(),
+        flate2::Compression::default(),
+    );
+    let mut tar = tar::Builder::new(encoder);
+    for (name, content) in members {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        tar.append_data(&mut header, name, content).unwrap();
+    }
+    tar.into_inner().unwrap().finish().unwrap();
+
+    let analyzer = StreamAnalyzer::new(10);
+    for path in [zip_path, tar_path] {
+        let set = analyzer.analyze_archive(&path).unwrap();
+        let sources: Vec<_> = set.entries.iter().map(|e| e.source.as_str()).collect();
+        assert_eq!(sources, ["site/index.html", "site/feed.xml"], "{:?}", path);
+        assert_eq!(set.aggregate.files_analyzed, 2);
+        assert!(set.aggregate.tags.contains_key("p"));
+        assert!(set.aggregate.tags.contains_key("entry"));
+    }
+
+    assert!(analyzer
+        .analyze_archive(&dir.path().join("dump.rar"))
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_analyze_urls() {
+    use ferret::fetch::{FetchOptions, HostThrottle};
+    use std::time::{Duration, Instant};
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    let server = MockServer::start().await;
+    for page in ["a", "b", "c"] {
+        Mock::given(method("GET"))
+            .and(path(format!("/{}", page)))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(format!("<{0}></{0}>", page))
+                    .set_delay(Duration::from_millis(100)),
+            )
+            .mount(&server)
+            .await;
+    }
+
+    let urls: Vec<String> = ["a", "b", "missing", "c"]
+        .iter()
+        .map(|page| format!("{}/{}", server.uri(), page))
+        .collect();
+
+    let analyzer = StreamAnalyzer::new(10);
+    let start = Instant::now();
+    let set = analyzer.analyze_urls(urls.clone(), 4).await;
+    assert!(start.elapsed() < Duration::from_millis(300));
+
+    let sources: Vec<_> = set.entries.iter().map(|e| e.source.clone()).collect();
+    assert_eq!(sources, urls);
+    assert!(set.entries[2].error.as_ref().unwrap().contains("404"));
+    assert_eq!(set.aggregate.files_analyzed, 3);
+    for tag in ["a", "b", "c"] {
+        assert!(set.aggregate.tags.contains_key(tag));
+    }
+
+    // A per-host delay serializes requests to the same server
+    let analyzer = StreamAnalyzer::new(10).with_fetch_options(
+        FetchOptions::default().throttle(HostThrottle::new(Duration::from_millis(150))),
+    );
+    let start = Instant::now();
+    let set = analyzer.analyze_urls(urls, 4).await;
+    assert!(start.elapsed() >= Duration::from_millis(450));
+    assert_eq!(set.errors().count(), 1);
+}
+
+#[test]
+fn test_progress_and_cancellation() {
+    use ferret::error::FerretError;
+    use ferret::progress::{CancellationToken, Progress, ProgressEvent, REPORT_INTERVAL_BYTES};
+    use std::sync::{Arc, Mutex};
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let sink = events.clone();
+    let analyzer = StreamAnalyzer::new(10).with_progress(Progress::new(move |event| {
+        sink.lock().unwrap().push(event);
+    }));
+
+    let html = format!("<root>{}</root>", "<item>value</item>".repeat(10_000));
+    analyzer.analyze_string(&html).unwrap();
+    {
+        let events = events.lock().unwrap();
+        assert!(events.len() >= html.len() / REPORT_INTERVAL_BYTES);
+        assert_eq!(
+            events.last(),
+            Some(&ProgressEvent::Parsed {
+                bytes: html.len(),
+                elements: 10_001
+            })
+        );
+    }
+
+    let dir = tempfile::tempdir().unwrap();
+    let paths: Vec<_> = (0..3)
+        .map(|i| {
+            let path = dir.path().join(format!("{}.html", i));
+            fs::write(&path, "<p></p>").unwrap();
+            path
+        })
+        .collect();
+    events.lock().unwrap().clear();
+    analyzer.analyze_files(paths.clone());
+    let completed: Vec<_> = events
+        .lock()
+        .unwrap()
+        .iter()
+        .filter_map(|event| match event {
+            ProgressEvent::SourceCompleted {
+                completed, total, ..
+            } => Some((*completed, *total)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(completed, [(1, 3), (2, 3), (3, 3)]);
+
+    let token = CancellationToken::new();
+    let analyzer = StreamAnalyzer::new(10).with_cancellation(token.clone());
+    assert!(analyzer.analyze_string(&html).is_ok());
+    token.cancel();
+    let err = analyzer.analyze_string(&html).unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<FerretError>(),
+        Some(FerretError::Cancelled)
+    ));
+    let set = analyzer.analyze_files(paths);
+    assert_eq!(set.errors().count(), 3);
+}
+
+#[tokio::test]
+async fn test_cancel_url_fetch() {
+    use ferret::error::FerretError;
+    use ferret::progress::CancellationToken;
+    use std::time::{Duration, Instant};
+    use wiremock::matchers::method;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(10)))
+        .mount(&server)
+        .await;
+
+    let token = CancellationToken::new();
+    let analyzer = StreamAnalyzer::new(10).with_cancellation(token.clone());
+    let canceller = tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        token.cancel();
+    });
+
+    let start = Instant::now();
+    let err = analyzer.analyze_url(&server.uri()).await.unwrap_err();
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert!(matches!(
+        err.downcast_ref::<FerretError>(),
+        Some(FerretError::Cancelled)
+    ));
+    canceller.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_crawler() {
+    use ferret::crawler::{Crawler, Scope};
+    use std::time::Duration;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    let server = MockServer::start().await;
+    let pages = [
+        (
+            "/",
+            r#"<a href="/a">A</a><a href="b#part">B</a><a href="https://elsewhere.test/">Out</a>"#,
+        ),
+        ("/a", r#"<a href="/">Home</a><a href="/c">C</a>"#),
+        ("/b", r#"<p><a href="/a">A</a></p>"#),
+        ("/c", r#"<a href="/d">D</a>"#),
+    ];
+    for (route, body) in pages {
+        Mock::given(method("GET"))
+            .and(path(route))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(body, "text/html"))
+            .mount(&server)
+            .await;
+    }
+
+    let crawler = Crawler::new(StreamAnalyzer::new(10)).with_delay(Duration::ZERO);
+    let crawl = crawler
+        .with_max_depth(1)
+        .crawl([format!("{}/", server.uri())])
+        .await
+        .unwrap();
+    let urls: Vec<_> = crawl
+        .pages
+        .iter()
+        .map(|page| (page.url.trim_start_matches(&server.uri()), page.depth))
+        .collect();
+    assert_eq!(urls, [("/", 0), ("/a", 1), ("/b", 1)]);
+    assert_eq!(crawl.errors().count(), 0);
+    assert_eq!(crawl.pages[0].links.len(), 3);
+    assert_eq!(crawl.pages[0].links[1].text, "B");
+    let graph = crawl.link_graph();
+    // The three pages, the external link and /c beyond the depth limit
+    assert_eq!(graph.nodes.len(), 5);
+    assert_eq!(graph.edges.len(), 6);
+    assert_eq!(graph.crawled_only().edges.len(), 4);
+    assert_eq!(crawl.aggregate.files_analyzed, 3);
+    assert_eq!(crawl.aggregate.tags["a"].count, 6);
+    assert_eq!(crawl.to_result_set().entries.len(), 3);
+
+    // /d is missing: the page is reported, not the crawl failed
+    let crawler = Crawler::new(StreamAnalyzer::new(10)).with_delay(Duration::ZERO);
+    let crawl = crawler.crawl([format!("{}/", server.uri())]).await.unwrap();
+    assert_eq!(crawl.pages.len(), 4);
+    let crawl = crawler
+        .with_max_depth(5)
+        .with_max_pages(10)
+        .crawl([format!("{}/", server.uri())])
+        .await
+        .unwrap();
+    assert_eq!(crawl.pages.len(), 5);
+    let errors: Vec<_> = crawl.errors().collect();
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].url.ends_with("/d"));
+    assert!(errors[0].error.as_deref().unwrap().contains("404"));
+
+    let crawler = Crawler::new(StreamAnalyzer::new(10))
+        .with_delay(Duration::ZERO)
+        .with_max_pages(2)
+        .with_scope(Scope::Hosts(vec!["elsewhere.test".to_string()]));
+    let crawl = crawler.crawl([format!("{}/", server.uri())]).await.unwrap();
+    assert_eq!(crawl.pages.len(), 2);
+    assert!(crawl.pages[1].url.starts_with("https://elsewhere.test"));
+
+    assert!(Crawler::new(StreamAnalyzer::new(10))
+        .crawl(["not a url"])
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_link_checker() {
+    use ferret::crawler::{extract_links, Crawler, LinkChecker};
+    use ferret::fetch::FetchOptions;
+    use ferret::rules::Rule;
+    use std::time::Duration;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    let server = MockServer::start().await;
+    let home = r#"<a href="/ok">OK</a><a href="/gone">Gone</a><a href="/moved">Moved</a>
+        <a href="/nohead">No HEAD</a><a href="http://127.0.0.1:1/">Down</a>"#;
+    Mock::given(method("GET"))
+        .and(path("/"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(home, "text/html"))
+        .mount(&server)
+        .await;
+    Mock::given(path("/ok"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw("<p>ok</p>", "text/html"))
+        .mount(&server)
+        .await;
+    Mock::given(path("/moved"))
+        .respond_with(
+            ResponseTemplate::new(301).insert_header("Location", format!("{}/ok", server.uri())),
+        )
+        .mount(&server)
+        .await;
+    Mock::given(method("HEAD"))
+        .and(path("/nohead"))
+        .respond_with(ResponseTemplate::new(405))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/nohead"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&server)
+        .await;
+
+    let base: reqwest::Url = format!("{}/", server.uri()).parse().unwrap();
+    let report = LinkChecker::new(FetchOptions::default())
+        .check([(base.to_string(), extract_links(home, &base))])
+        .await
+        .unwrap();
+    let statuses: Vec<_> = report.pages[0]
+        .links
+        .iter()
+        .map(|link| (link.text.as_str(), link.status.status))
+        .collect();
+    assert_eq!(
+        statuses,
+        [
+            ("OK", Some(200)),
+            ("Gone", Some(404)),
+            ("Moved", Some(200)),
+            ("No HEAD", Some(200)),
+            ("Down", None),
+        ]
+    );
+    let broken: Vec<_> = report
+        .broken()
+        .map(|(_, link)| link.text.as_str())
+        .collect();
+    assert_eq!(broken, ["Gone", "Down"]);
+    assert_eq!(report.pages[0].links[2].status.redirects[0].status, 301);
+    assert!(report.pages[0].links[4].status.error.is_some());
+
+    let crawl = Crawler::new(StreamAnalyzer::new(10))
+        .with_delay(Duration::ZERO)
+        .with_link_check(true)
+        .crawl([base.as_str()])
+        .await
+        .unwrap();
+    let counts = crawl.aggregate.links.unwrap();
+    assert_eq!(
+        (
+            counts.checked,
+            counts.broken,
+            counts.redirected,
+            counts.unreachable
+        ),
+        (5, 2, 1, 1)
+    );
+    // Pages linked from the seed were crawled, so only their links are new
+    assert_eq!(crawl.link_check.unwrap().pages.len(), 1);
+    let rule: Rule = "broken_links > 0".parse().unwrap();
+    assert_eq!(rule.check(&crawl.aggregate).unwrap().actual, 2);
+}
+
+#[tokio::test]
+async fn test_crawler_dedup() {
+    use ferret::crawler::{Crawler, UrlNormalizer};
+    use ferret::progress::{Progress, ProgressEvent};
+    use std::time::Duration;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    let server = MockServer::start().await;
+    let home = r#"<a href="/p?utm_source=news">P</a><a href="/p?b=2&amp;a=1">P</a>
+        <a href="/p?a=1&b=2#top">P</a><a href="/q">Q</a>"#;
+    let canonical = r#"<link rel="canonical" href="/p"><p>page</p>"#;
+    for (route, body) in [("/", home), ("/p", canonical), ("/q", canonical)] {
+        Mock::given(method("GET"))
+            .and(path(route))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(body, "text/html"))
+            .mount(&server)
+            .await;
+    }
+
+    let (progress, mut events) = Progress::channel();
+    let crawler =
+        Crawler::new(StreamAnalyzer::new(10).with_progress(progress)).with_delay(Duration::ZERO);
+    let crawl = crawler.crawl([server.uri()]).await.unwrap();
+    // Duplicates are reported like any other page
+    let mut completed = Vec::new();
+    while let Ok(event) = events.try_recv() {
+        if let ProgressEvent::SourceCompleted { source, .. } = event {
+            completed.push(source.trim_start_matches(&server.uri()).to_string());
+        }
+    }
+    assert_eq!(completed, ["/", "/p", "/p?a=1&b=2", "/q"]);
+    let pages: Vec<_> = crawl
+        .pages
+        .iter()
+        .map(|page| {
+            (
+                page.url.trim_start_matches(&server.uri()),
+                page.duplicate_of
+                    .as_deref()
+                    .map(|url| url.trim_start_matches(&server.uri())),
+            )
+        })
+        .collect();
+    assert_eq!(
+        pages,
+        [
+            ("/", None),
+            ("/p", None),
+            ("/p?a=1&b=2", Some("/p")),
+            ("/q", Some("/p")),
+        ]
+    );
+    assert_eq!(crawl.aggregate.files_analyzed, 2);
+    assert_eq!(
+        crawl.pages[2].canonical,
+        Some(format!("{}/p", server.uri()))
+    );
+
+    let crawl = crawler
+        .with_normalizer(UrlNormalizer::minimal())
+        .crawl([server.uri()])
+        .await
+        .unwrap();
+    assert_eq!(crawl.pages.len(), 5);
+    assert_eq!(crawl.aggregate.files_analyzed, 2);
+}
+
+#[tokio::test]
+async fn test_crawler_sitemap() {
+    use ferret::crawler::{Crawler, SitemapSource};
+    use std::time::Duration;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    let server = MockServer::start().await;
+    let uri = server.uri();
+    let robots = format!("User-agent: *\nSitemap: {}/index.xml\n", uri);
+    let index = format!(
+        "<sitemapindex><sitemap><loc>{}/pages.xml</loc></sitemap></sitemapindex>",
+        uri
+    );
+    let pages = format!(
+        "<urlset><url><loc>{0}/</loc></url><url><loc>{0}/orphan</loc></url></urlset>",
+        uri
+    );
+    let routes = [
+        ("/", "<a href=\"/a\">A</a>".to_string(), "text/html"),
+        ("/a", "<p>a</p>".to_string(), "text/html"),
+        ("/robots.txt", robots, "text/plain"),
+        ("/index.xml", index, "application/xml"),
+        ("/pages.xml", pages, "application/xml"),
+    ];
+    for (route, body, mime) in routes {
+        Mock::given(method("GET"))
+            .and(path(route))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(body, mime))
+            .mount(&server)
+            .await;
+    }
+
+    let crawler = Crawler::new(StreamAnalyzer::new(10)).with_delay(Duration::ZERO);
+    let crawl = crawler
+        .with_sitemap(SitemapSource::Discover)
+        .crawl([&uri])
+        .await
+        .unwrap();
+    let coverage = crawl.sitemap.unwrap();
+    assert_eq!(
+        coverage.sitemaps,
+        [format!("{}/index.xml", uri), format!("{}/pages.xml", uri)]
+    );
+    assert_eq!(coverage.listed, 2);
+    assert_eq!(coverage.not_linked, [format!("{}/orphan", uri)]);
+    assert_eq!(coverage.not_listed, [format!("{}/a", uri)]);
+
+    let crawler = Crawler::new(StreamAnalyzer::new(10)).with_delay(Duration::ZERO);
+    let crawl = crawler
+        .with_max_depth(0)
+        .with_sitemap(SitemapSource::Urls(vec![format!("{}/missing.xml", uri)]))
+        .crawl([&uri])
+        .await
+        .unwrap();
+    let coverage = crawl.sitemap.unwrap();
+    assert!(coverage.sitemaps.is_empty());
+    assert_eq!(coverage.errors.len(), 1);
+    assert_eq!(coverage.not_listed, [format!("{}/", uri)]);
+}
+
+#[cfg(feature = "rendered")]
+#[tokio::test]
+async fn test_analyze_url_rendered() {
+    use ferret::error::FerretError;
+    use ferret::fetch::{FetchOptions, HostPolicy};
+    use ferret::render::BrowserOptions;
+    use serde_json::json;
+    use std::time::Duration;
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    // Stands in for chromedriver
+    let webdriver = MockServer::start().await;
+    let reply = |value: serde_json::Value| {
+        ResponseTemplate::new(200).set_body_json(json!({ "value": value }))
+    };
+    Mock::given(method("POST"))
+        .and(path("/session"))
+        .and(body_partial_json(
+            json!({ "capabilities": { "alwaysMatch": { "browserName": "chrome" } } }),
+        ))
+        .respond_with(reply(json!({ "sessionId": "s1", "capabilities": {} })))
+        .mount(&webdriver)
+        .await;
+    for route in ["/session/s1/timeouts", "/session/s1/url"] {
+        Mock::given(method("POST"))
+            .and(path(route))
+            .respond_with(reply(json!(null)))
+            .mount(&webdriver)
+            .await;
+    }
+    // The app renders its list on the second poll
+    Mock::given(method("POST"))
+        .and(path("/session/s1/element"))
+        .respond_with(ResponseTemplate::new(404).set_body_json(json!({
+            "value": { "error": "no such element", "message": "not yet" }
+        })))
+        .up_to_n_times(1)
+        .mount(&webdriver)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/session/s1/element"))
+        .respond_with(reply(
+            json!({ "element-6066-11e4-a52e-4f735466cecf": "e1" }),
+        ))
+        .mount(&webdriver)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/session/s1/source"))
+        .respond_with(reply(json!(
+            r#"<html><body><div id="root"><ul><li>a</li><li>b</li></ul></div></body></html>"#
+        )))
+        .mount(&webdriver)
+        .await;
+    Mock::given(method("DELETE"))
+        .and(path("/session/s1"))
+        .respond_with(reply(json!(null)))
+        // Sessions are closed after failed renders too
+        .expect(2)
+        .mount(&webdriver)
+        .await;
+
+    let options = BrowserOptions::default()
+        .with_webdriver_url(webdriver.uri())
+        .with_wait_for("#root li")
+        .with_timeout(Duration::from_secs(5));
+    let analyzer = StreamAnalyzer::new(10).with_render(options.clone());
+    let result = analyzer.analyze_url("https://app.test/").await.unwrap();
+    assert_eq!(result.tags["li"].count, 2);
+
+    // The element never appears
+    let analyzer = StreamAnalyzer::new(10).with_render(
+        options
+            .clone()
+            .with_wait_for("#missing")
+            .with_timeout(Duration::from_millis(50)),
+    );
+    Mock::given(method("POST"))
+        .and(path("/session/s1/element"))
+        .and(body_partial_json(json!({ "value": "#missing" })))
+        .respond_with(ResponseTemplate::new(404).set_body_json(json!({
+            "value": { "error": "no such element", "message": "none" }
+        })))
+        .with_priority(1)
+        .mount(&webdriver)
+        .await;
+    let err = analyzer.analyze_url("https://app.test/").await.unwrap_err();
+    assert!(format!("{:#}", err).contains("#missing did not appear"));
+
+    // Checked before the browser is asked to load the page
+    let analyzer = StreamAnalyzer::new(10)
+        .with_render(options)
+        .with_fetch_options(FetchOptions::default().host_policy(HostPolicy::default()));
+    let err = analyzer
+        .analyze_url("http://169.254.169.254/latest/meta-data/")
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<FerretError>(),
+        Some(FerretError::HostNotAllowed { .. })
+    ));
 }
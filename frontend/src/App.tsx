@@ -3,6 +3,7 @@ import { Header } from './components/Header';
 import { Sidebar } from './components/Sidebar';
 import { MainContent } from './components/MainContent';
 import type { AnalysisResult } from './types';
+import { apiBase } from './api';
 import './App.css';
 
 function App() {
@@ -18,7 +19,6 @@ function App() {
     setData(null);
     setCurrentUrl(url); // Store the URL being analyzed
     try {
-      const apiBase = import.meta.env.VITE_API_URL || 'http://localhost:3000';
       const endpoint = `${apiBase}/api/report/${url}`;
       const response = await fetch(endpoint);
 
@@ -0,0 +1,4 @@
+// scapi serves the built UI itself, so its API is on the same origin; the
+// dev server runs on a port of its own
+export const apiBase: string =
+  import.meta.env.VITE_API_URL || (import.meta.env.DEV ? 'http://localhost:3000' : '');
@@ -2,6 +2,7 @@ import React, { useState, useEffect } from 'react';
 import { Table, Code, Network } from 'lucide-react';
 import ReactJson from 'react-json-view';
 import type { AnalysisResult } from '../types';
+import { apiBase } from '../api';
 import './MainContent.css';
 
 interface MainContentProps {
@@ -21,7 +22,6 @@ export const MainContent: React.FC<MainContentProps> = ({ data, currentUrl }) =>
 
     useEffect(() => {
         if (view === 'csv' && currentUrl) {
-            const apiBase = import.meta.env.VITE_API_URL || 'http://localhost:3000';
             fetch(`${apiBase}/api/export/${currentUrl}?format=csv`)
                 .then(res => res.text())
                 .then(text => {
@@ -53,7 +53,6 @@ export const MainContent: React.FC<MainContentProps> = ({ data, currentUrl }) =>
         );
     }
 
-    const apiBase = import.meta.env.VITE_API_URL || 'http://localhost:3000';
     const iframeSrc = (format: string) => `${apiBase}/api/export/${currentUrl}?format=${format}`;
 
     // E-Ink Theme for ReactJson
@@ -4,5 +4,6 @@ import react from '@vitejs/plugin-react'
 // https://vite.dev/config/
 export default defineConfig({
   plugins: [react()],
-  base: '/pli-scraper/',
+  // Relative, so the build works on GitHub Pages and served by scapi at `/`
+  base: './',
 })
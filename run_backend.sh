@@ -1,10 +1,15 @@
 #!/bin/bash
 set -e
 
+if [ ! -d "frontend/dist" ] && command -v npm > /dev/null; then
+    echo "Building the UI..."
+    (cd frontend && { [ -d "node_modules" ] || npm install; } && npm run build)
+fi
+
 echo "Building scapi..."
 cargo build -p scapi
 
-echo "Starting scapi on port 3000..."
+echo "Starting scapi on port 3000, with the UI at http://localhost:3000/ ..."
 export PORT=3000
 # Run the binary directly or via cargo run (cargo run is convenient for dev)
 cargo run -p scapi
@@ -0,0 +1,264 @@
+//! Operating the server without restarting it
+//!
+//! The routes under `/api/admin` need `Authorization: Bearer <token>` with
+//! `server.admin_token`, and answer `404 Not Found` while none is set:
+//!
+//! - `GET /api/admin/cache` lists the cached analyses; `DELETE` purges
+//!   them, or only those of `?url=`
+//! - `GET /api/admin/jobs` lists the jobs kept; `DELETE /api/admin/jobs/:id`
+//!   cancels one that hasn't finished
+//! - `GET /api/admin/schedules` lists the schedules; `POST` registers one
+//!   and `DELETE /api/admin/schedules/:id` stops one not configured
+//! - `GET /api/admin/usage` counts the requests of each client address
+
+use axum::{
+    extract::{Path, Query, Request, State},
+    http::{
+        header::{AUTHORIZATION, WWW_AUTHENTICATE},
+        StatusCode,
+    },
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{delete, get},
+    Json, Router,
+};
+use serde::Deserialize;
+use serde_json::json;
+use std::time::Instant;
+
+use crate::{schedules, AppState};
+
+pub fn router(state: &AppState) -> Router<AppState> {
+    Router::new()
+        .route("/cache", get(handler_cache).delete(handler_purge_cache))
+        .route("/jobs", get(handler_jobs))
+        .route("/jobs/:id", delete(handler_cancel_job))
+        .route(
+            "/schedules",
+            get(handler_schedules).post(schedules::handler_create_schedule),
+        )
+        .route("/schedules/:id", delete(schedules::handler_delete_schedule))
+        .route("/usage", get(handler_usage))
+        .route_layer(middleware::from_fn_with_state(state.clone(), authorize))
+}
+
+/// Middleware letting through requests bearing the admin token
+async fn authorize(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let Some(token) = state.config.server.admin_token.as_deref() else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let given = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    match given {
+        Some(given) if same(given.trim().as_bytes(), token.as_bytes()) => next.run(request).await,
+        _ => (
+            StatusCode::UNAUTHORIZED,
+            [(WWW_AUTHENTICATE, "Bearer")],
+            "Admin token required",
+        )
+            .into_response(),
+    }
+}
+
+/// Whether `a` and `b` are equal, in a time not telling where they differ
+fn same(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+async fn handler_cache(State(state): State<AppState>) -> Response {
+    Json(json!({ "entries": state.cache.entries(Instant::now()) })).into_response()
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct PurgeParams {
+    /// Only the entries of this URL
+    url: Option<String>,
+}
+
+async fn handler_purge_cache(
+    Query(params): Query<PurgeParams>,
+    State(state): State<AppState>,
+) -> Response {
+    let purged = state.cache.purge(params.url.as_deref());
+    tracing::info!(purged, "Purged the cache");
+    Json(json!({ "purged": purged })).into_response()
+}
+
+async fn handler_jobs(State(state): State<AppState>) -> Response {
+    Json(json!({ "jobs": state.jobs.list() })).into_response()
+}
+
+/// The status of the cancelled job; `409 Conflict` once it has finished
+async fn handler_cancel_job(Path(id): Path<String>, State(state): State<AppState>) -> Response {
+    let Some(job) = state.jobs.get(&id) else {
+        return (StatusCode::NOT_FOUND, format!("No job {}", id)).into_response();
+    };
+    if !job.cancel() {
+        let state = serde_json::to_string(&job.status().state).unwrap();
+        return (StatusCode::CONFLICT, format!("Job is {}", state)).into_response();
+    }
+    Json(job.status()).into_response()
+}
+
+async fn handler_schedules(State(state): State<AppState>) -> Response {
+    Json(json!({ "schedules": state.schedules.list() })).into_response()
+}
+
+async fn handler_usage(State(state): State<AppState>) -> Response {
+    Json(json!({ "clients": state.rate_limiter.usage(Instant::now()) })).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::serve_site;
+    use ferret::config::Config;
+    use std::net::SocketAddr;
+    use std::time::Duration;
+
+    async fn serve(admin_token: Option<&str>) -> String {
+        let mut config = Config::default();
+        config.server.allow_private = true;
+        config.server.admin_token = admin_token.map(str::to_string);
+        let app = crate::app(AppState::new(config).unwrap())
+            .into_make_service_with_connect_info::<SocketAddr>();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        base
+    }
+
+    #[tokio::test]
+    async fn test_admin() {
+        let site = serve_site().await;
+        let base = serve(Some("secret")).await;
+        let client = reqwest::Client::new();
+        let admin = |method: reqwest::Method, path: &str| {
+            client
+                .request(method, format!("{}/api/admin{}", base, path))
+                .bearer_auth("secret")
+        };
+        let get = |path: &str| {
+            let request = admin(reqwest::Method::GET, path);
+            async move {
+                let response = request.send().await.unwrap();
+                assert_eq!(response.status().as_u16(), 200);
+                response.json::<serde_json::Value>().await.unwrap()
+            }
+        };
+
+        let unauthorized = client
+            .get(format!("{}/api/admin/cache", base))
+            .bearer_auth("wrong")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(unauthorized.status().as_u16(), 401);
+        assert_eq!(unauthorized.headers()["www-authenticate"], "Bearer");
+
+        let report = format!("{}/api/report/{}/page", base, site);
+        assert!(client
+            .get(&report)
+            .send()
+            .await
+            .unwrap()
+            .status()
+            .is_success());
+        let entries = get("/cache").await;
+        assert_eq!(entries["entries"][0]["url"], format!("{}/page", site));
+        assert_eq!(entries["entries"][0]["private"], false);
+        let purged: serde_json::Value = admin(reqwest::Method::DELETE, "/cache")
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(purged["purged"], 1);
+        assert_eq!(get("/cache").await["entries"], json!([]));
+
+        let created: serde_json::Value = client
+            .post(format!("{}/api/jobs", base))
+            .json(&json!({ "kind": "batch", "urls": [format!("{}/slow", site)] }))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let id = created["id"].as_str().unwrap();
+        assert_eq!(get("/jobs").await["jobs"][0]["id"], id);
+        let cancel = || admin(reqwest::Method::DELETE, &format!("/jobs/{}", id)).send();
+        let cancelled = cancel().await.unwrap();
+        assert_eq!(cancelled.status().as_u16(), 200);
+        let cancelled: serde_json::Value = cancelled.json().await.unwrap();
+        assert_eq!(cancelled["state"], "cancelled");
+        assert_eq!(cancel().await.unwrap().status().as_u16(), 409);
+        // Stays cancelled once the page would have been fetched
+        tokio::time::sleep(Duration::from_millis(2500)).await;
+        let status: serde_json::Value = client
+            .get(format!("{}/api/jobs/{}", base, id))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(status["state"], "cancelled");
+        let missing = admin(reqwest::Method::DELETE, "/jobs/nope").send().await;
+        assert_eq!(missing.unwrap().status().as_u16(), 404);
+
+        let unauthorized = client
+            .get(format!("{}/api/admin/schedules", base))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(unauthorized.status().as_u16(), 401);
+        let created: serde_json::Value = admin(reqwest::Method::POST, "/schedules")
+            .json(&json!({ "url": format!("{}/page", site), "interval_secs": 3600 }))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let id = created["id"].as_str().unwrap();
+        assert_eq!(get("/schedules").await["schedules"][0]["id"], id);
+        let path = format!("/schedules/{}", id);
+        let unauthorized = client
+            .delete(format!("{}/api/admin{}", base, path))
+            .bearer_auth("wrong")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(unauthorized.status().as_u16(), 401);
+        let deleted = admin(reqwest::Method::DELETE, &path).send().await.unwrap();
+        assert_eq!(deleted.status().as_u16(), 204);
+        assert_eq!(get("/schedules").await["schedules"], json!([]));
+
+        let usage = get("/usage").await;
+        assert_eq!(usage["clients"][0]["client"], "127.0.0.1");
+        assert!(usage["clients"][0]["allowed"].as_u64().unwrap() >= 8);
+        assert!(usage["clients"][0]["remaining"].as_u64().unwrap() < 120);
+
+        // Disabled without a token
+        let base = serve(None).await;
+        let response = client
+            .get(format!("{}/api/admin/usage", base))
+            .bearer_auth("")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 404);
+    }
+
+    #[test]
+    fn test_same() {
+        assert!(same(b"secret", b"secret"));
+        assert!(!same(b"secret", b"secreT"));
+        assert!(!same(b"secret", b"secrets"));
+    }
+}
@@ -0,0 +1,197 @@
+//! Analyses of recently fetched pages
+//!
+//! `GET /api/report/<url>` answers from here while an analysis of the same
+//! URL, fetched and analyzed with the same options, is younger than
+//! `server.cache_ttl_secs`.
+//! `?refresh=true` fetches the page again and replaces the entry.
+//! `GET /api/admin/cache` lists the entries and `DELETE` purges them.
+
+use ferret::analyzer::AnalysisResult;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::AnalysisSpec;
+
+/// What a page was fetched and analyzed with, besides its URL
+///
+/// Cookies and credentials are part of it, since the page may differ for
+/// each user.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    pub url: String,
+    pub analysis: AnalysisSpec,
+    pub user_agent: Option<String>,
+    pub cookie: Option<String>,
+    pub max_redirects: Option<usize>,
+    pub authorization: Option<String>,
+    /// Headers of the request forwarded to the page
+    pub forwarded: Vec<(String, String)>,
+}
+
+impl CacheKey {
+    /// Whether the page was fetched on behalf of a particular user
+    pub fn is_private(&self) -> bool {
+        self.cookie.is_some() || self.authorization.is_some() || !self.forwarded.is_empty()
+    }
+}
+
+/// An entry as listed by `GET /api/admin/cache`, without the credentials
+/// of its key
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CacheEntry {
+    pub url: String,
+    pub private: bool,
+    pub age_secs: u64,
+}
+
+pub struct ResultCache {
+    /// How long an analysis is reused; zero disables the cache
+    ttl: Duration,
+    max_entries: usize,
+    entries: Mutex<HashMap<CacheKey, (Instant, Arc<AnalysisResult>)>>,
+}
+
+impl ResultCache {
+    pub fn new(ttl: Duration, max_entries: usize) -> Self {
+        Self {
+            ttl,
+            max_entries,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// The analysis stored under `key` and its age, unless it expired
+    pub fn get(&self, key: &CacheKey, now: Instant) -> Option<(Arc<AnalysisResult>, Duration)> {
+        let mut entries = self.entries.lock().unwrap();
+        let (stored, result) = entries.get(key)?;
+        let age = now.saturating_duration_since(*stored);
+        if age >= self.ttl {
+            entries.remove(key);
+            return None;
+        }
+        Some((Arc::clone(result), age))
+    }
+
+    /// Store `result`, dropping expired entries, then the oldest ones, when
+    /// the cache is full
+    pub fn insert(&self, key: CacheKey, result: Arc<AnalysisResult>, now: Instant) {
+        if self.ttl.is_zero() || self.max_entries == 0 {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= self.max_entries && !entries.contains_key(&key) {
+            entries.retain(|_, (stored, _)| now.saturating_duration_since(*stored) < self.ttl);
+            while entries.len() >= self.max_entries {
+                let oldest = entries
+                    .iter()
+                    .min_by_key(|(_, (stored, _))| *stored)
+                    .map(|(key, _)| key.clone());
+                match oldest {
+                    Some(oldest) => entries.remove(&oldest),
+                    None => break,
+                };
+            }
+        }
+        entries.insert(key, (now, result));
+    }
+
+    /// The entries that haven't expired, oldest first
+    pub fn entries(&self, now: Instant) -> Vec<CacheEntry> {
+        let entries = self.entries.lock().unwrap();
+        let mut listed: Vec<_> = entries
+            .iter()
+            .map(|(key, (stored, _))| (key, now.saturating_duration_since(*stored)))
+            .filter(|(_, age)| *age < self.ttl)
+            .map(|(key, age)| CacheEntry {
+                url: key.url.clone(),
+                private: key.is_private(),
+                age_secs: age.as_secs(),
+            })
+            .collect();
+        listed.sort_by(|a, b| b.age_secs.cmp(&a.age_secs).then(a.url.cmp(&b.url)));
+        listed
+    }
+
+    /// Drop the entries of `url`, or all of them, returning how many were
+    /// dropped
+    pub fn purge(&self, url: Option<&str>) -> usize {
+        let mut entries = self.entries.lock().unwrap();
+        let before = entries.len();
+        match url {
+            Some(url) => entries.retain(|key, _| key.url != url),
+            None => entries.clear(),
+        }
+        before - entries.len()
+    }
+}
+
+impl Default for ResultCache {
+    fn default() -> Self {
+        Self::new(Duration::ZERO, 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(url: &str) -> CacheKey {
+        CacheKey {
+            url: url.to_string(),
+            analysis: AnalysisSpec::new(&crate::AppState::default()),
+            user_agent: None,
+            cookie: None,
+            max_redirects: None,
+            authorization: None,
+            forwarded: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_get_insert() {
+        let cache = ResultCache::new(Duration::from_secs(60), 2);
+        let start = Instant::now();
+        let result = Arc::new(AnalysisResult::default());
+        cache.insert(key("https://a.test/"), Arc::clone(&result), start);
+        cache.insert(
+            key("https://b.test/"),
+            Arc::clone(&result),
+            start + Duration::from_secs(1),
+        );
+
+        let later = start + Duration::from_secs(10);
+        let (_, age) = cache.get(&key("https://a.test/"), later).unwrap();
+        assert_eq!(age.as_secs(), 10);
+        let mut private = key("https://a.test/");
+        private.cookie = Some("session=1".to_string());
+        assert!(private.is_private());
+        assert!(cache.get(&private, later).is_none());
+
+        // Full: the oldest entry makes room
+        cache.insert(key("https://c.test/"), Arc::clone(&result), later);
+        assert!(cache.get(&key("https://a.test/"), later).is_none());
+        assert!(cache.get(&key("https://b.test/"), later).is_some());
+        assert!(cache.get(&key("https://c.test/"), later).is_some());
+
+        let listed = cache.entries(later);
+        assert_eq!(listed.len(), 2);
+        assert_eq!(listed[0].url, "https://b.test/");
+        assert_eq!(listed[0].age_secs, 9);
+        assert_eq!(cache.purge(Some("https://b.test/")), 1);
+        assert_eq!(cache.purge(Some("https://b.test/")), 0);
+
+        assert!(cache
+            .get(&key("https://c.test/"), later + Duration::from_secs(60))
+            .is_none());
+
+        let disabled = ResultCache::default();
+        disabled.insert(key("https://a.test/"), result, start);
+        assert!(disabled.get(&key("https://a.test/"), start).is_none());
+    }
+}
@@ -0,0 +1,170 @@
+//! `GET /api/diff?a=...&b=...`: what changed between two analyses
+//!
+//! Each side is a run id from the history or a URL, analyzed like
+//! `GET /api/report` does, cache included.
+
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use ferret::analyzer::AnalysisResult;
+use ferret::diff::AnalysisDiff;
+use ferret::exporter::{DiffExporter, DiffFormat};
+use serde::Deserialize;
+use std::sync::Arc;
+
+use crate::{cached_analysis, error_response, AnalysisSpec, AppState, FetchParams};
+
+/// Query parameters of `GET /api/diff`
+#[derive(Deserialize)]
+pub struct DiffParams {
+    /// Old side, a run id or a URL
+    a: String,
+    /// New side, a run id or a URL
+    b: String,
+    /// `json` (default), `html` or `csv`
+    format: Option<String>,
+    /// Also list tags and attributes whose counts didn't change (HTML and
+    /// CSV)
+    #[serde(default)]
+    unchanged: bool,
+    /// Fetch URLs even if their analysis is cached
+    #[serde(default)]
+    refresh: bool,
+    #[serde(flatten)]
+    fetch: FetchParams,
+}
+
+pub async fn handler_diff(
+    Query(params): Query<DiffParams>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Response {
+    let format = match params.format.as_deref() {
+        None | Some("json") => None,
+        Some("html") => Some(DiffFormat::Html),
+        Some("csv") => Some(DiffFormat::Csv),
+        Some(other) => {
+            let message = format!(
+                "Unknown diff format {:?}, expected json, html or csv",
+                other
+            );
+            return (StatusCode::BAD_REQUEST, message).into_response();
+        }
+    };
+    let (old, old_label) = match side(&state, &params, &params.a, &headers).await {
+        Ok(side) => side,
+        Err(response) => return response,
+    };
+    let (new, new_label) = match side(&state, &params, &params.b, &headers).await {
+        Ok(side) => side,
+        Err(response) => return response,
+    };
+    let diff = AnalysisDiff::new(&old, &new);
+
+    let Some(format) = format else {
+        return Json(diff).into_response();
+    };
+    let exporter = DiffExporter::new(format)
+        .with_labels(old_label, new_label)
+        .with_unchanged(params.unchanged);
+    let mut body = Vec::new();
+    if let Err(e) = exporter.export_to_writer(&diff, &mut body) {
+        return error_response(StatusCode::INTERNAL_SERVER_ERROR, "Export error", e);
+    }
+    let content_type = match format {
+        DiffFormat::Html => "text/html; charset=utf-8",
+        DiffFormat::Csv => "text/csv",
+    };
+    ([("Content-Type", content_type)], body).into_response()
+}
+
+/// The analysis `side` names and its label
+async fn side(
+    state: &AppState,
+    params: &DiffParams,
+    side: &str,
+    headers: &HeaderMap,
+) -> Result<(Arc<AnalysisResult>, String), Response> {
+    if let Ok(id) = side.parse::<u64>() {
+        let run = state.history.get(id).ok_or_else(|| {
+            (StatusCode::NOT_FOUND, format!("Run {} not found", id)).into_response()
+        })?;
+        let label = format!("run {} of {}", run.id, run.source);
+        return Ok((Arc::new(run.result.clone()), label));
+    }
+    state.check_url(side).map_err(IntoResponse::into_response)?;
+    let key = params
+        .fetch
+        .cache_key(side, headers, state, AnalysisSpec::new(state));
+    let (result, _) = cached_analysis(state, &key, &params.fetch, headers, params.refresh).await?;
+    Ok((result, side.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{local_state, serve_site};
+
+    async fn diff(state: &AppState, query: &str) -> Response {
+        let uri = format!("/api/diff?{}", query).parse().unwrap();
+        handler_diff(
+            Query::try_from_uri(&uri).unwrap(),
+            State(state.clone()),
+            HeaderMap::new(),
+        )
+        .await
+    }
+
+    async fn body(response: Response) -> String {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_handler_diff() {
+        let base = serve_site().await;
+        let state = local_state();
+
+        let response = diff(&state, &format!("a={0}/page&b={0}/list", base)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let json: serde_json::Value = serde_json::from_str(&body(response).await).unwrap();
+        let tags = json["tags"].as_array().unwrap();
+        let change = |name: &str| {
+            let tag = tags.iter().find(|tag| tag["name"] == name).unwrap();
+            tag["change"].as_str().unwrap().to_string()
+        };
+        assert_eq!(change("p"), "removed");
+        assert_eq!(change("ul"), "added");
+
+        // Both URLs were recorded, as runs 1 and 2
+        let response = diff(&state, &format!("a=1&b={}/page&format=html", base)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()["content-type"],
+            "text/html; charset=utf-8"
+        );
+        assert!(body(response).await.contains("No structural changes."));
+
+        assert_eq!(
+            diff(&state, "a=1&b=2&format=csv").await.status(),
+            StatusCode::OK
+        );
+        assert_eq!(
+            diff(&state, "a=1&b=3").await.status(),
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            diff(&state, "a=1&b=2&format=pdf").await.status(),
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            diff(&state, "a=1&b=ftp://example.com").await.status(),
+            StatusCode::BAD_REQUEST
+        );
+    }
+}
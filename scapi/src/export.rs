@@ -0,0 +1,327 @@
+//! Report formats picked by `?format=` or the `Accept` header, streamed
+//!
+//! `?format=` names a format of the registry or one of its extensions
+//! (`markdown`, `jsonl`) and wins over `Accept`, whose media ranges are
+//! tried by quality. With neither, or `*/*`, `export.format` is served.
+//! The report is written on a blocking thread straight into the response
+//! body, so binary formats like XLSX and Parquet come out intact.
+//!
+//! `POST /api/export/batch` answers with a [`bundle`] of the reports of
+//! several URLs instead.
+
+use anyhow::Result;
+use axum::{body::Body, body::Bytes, http::StatusCode};
+use ferret::analyzer::{AnalysisResult, AnalysisResultSet};
+use ferret::exporter::{BoxedExporter, ExporterRegistry, Format};
+use serde_json::json;
+use std::io::{self, BufWriter, Cursor, Write};
+use tokio::sync::mpsc;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+/// Bytes written before a chunk is sent to the client
+const CHUNK_SIZE: usize = 64 * 1024;
+/// Chunks written ahead of a slow client
+const CHUNKS_AHEAD: usize = 4;
+
+/// The format to answer with, given `?format=` and the `Accept` header
+pub fn negotiate(
+    registry: &ExporterRegistry,
+    name: Option<&str>,
+    accept: Option<&str>,
+    default: &str,
+) -> Result<Format, (StatusCode, String)> {
+    if let Some(name) = name {
+        return registry
+            .get(name)
+            .or_else(|| registry.for_extension(name))
+            .ok_or_else(|| unknown_format(registry, name));
+    }
+    let default = || {
+        registry
+            .get(default)
+            .ok_or_else(|| unknown_format(registry, default))
+    };
+    let Some(accept) = accept.filter(|accept| !accept.trim().is_empty()) else {
+        return default();
+    };
+    for range in media_ranges(accept) {
+        match range.split_once('/') {
+            Some(("*", "*")) => return default(),
+            Some((kind, "*")) => {
+                let default = default()?;
+                if default.content_type.starts_with(&format!("{}/", kind)) {
+                    return Ok(default);
+                }
+                if let Some(format) = registry
+                    .names()
+                    .filter_map(|name| registry.get(name))
+                    .find(|format| format.content_type.starts_with(&format!("{}/", kind)))
+                {
+                    return Ok(format);
+                }
+            }
+            _ => {
+                if let Some(format) = registry.for_media_type(&range) {
+                    return Ok(format);
+                }
+            }
+        }
+    }
+    let served: Vec<_> = registry
+        .names()
+        .filter_map(|name| registry.get(name))
+        .map(|format| format.content_type)
+        .collect();
+    Err((
+        StatusCode::NOT_ACCEPTABLE,
+        format!(
+            "No format matches '{}'. Expected one of: {}",
+            accept,
+            served.join(", ")
+        ),
+    ))
+}
+
+fn unknown_format(registry: &ExporterRegistry, name: &str) -> (StatusCode, String) {
+    let known: Vec<_> = registry.names().collect();
+    (
+        StatusCode::BAD_REQUEST,
+        format!(
+            "Unknown format '{}'. Expected one of: {}",
+            name,
+            known.join(", ")
+        ),
+    )
+}
+
+/// Media ranges of an `Accept` header, lowercased and without parameters,
+/// by descending quality; those with `q=0` are left out
+fn media_ranges(accept: &str) -> Vec<String> {
+    let mut ranges: Vec<(f32, String)> = accept
+        .split(',')
+        .filter_map(|range| {
+            let mut parts = range.split(';');
+            let media_type = parts.next()?.trim().to_ascii_lowercase();
+            let quality = parts
+                .filter_map(|param| param.trim().strip_prefix("q="))
+                .find_map(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+            (!media_type.is_empty() && quality > 0.0).then_some((quality, media_type))
+        })
+        .collect();
+    // Stable, so equal qualities keep the client's order
+    ranges.sort_by(|a, b| b.0.total_cmp(&a.0));
+    ranges.into_iter().map(|(_, range)| range).collect()
+}
+
+/// A body streaming `result` as `exporter` writes it
+///
+/// Headers are sent before the export runs, so an export failing halfway
+/// aborts the response instead of turning it into an error status.
+pub fn stream(exporter: BoxedExporter, result: AnalysisResult) -> Body {
+    let (sender, mut chunks) = mpsc::channel(CHUNKS_AHEAD);
+    let span = tracing::Span::current();
+    tokio::task::spawn_blocking(move || {
+        let _entered = span.enter();
+        let mut writer = BufWriter::with_capacity(CHUNK_SIZE, ChunkWriter(sender.clone()));
+        let written = exporter
+            .export_to_writer(&result, &mut writer)
+            .and_then(|()| Ok(writer.flush()?));
+        drop(writer);
+        match written {
+            Ok(()) => {}
+            Err(_) if sender.is_closed() => tracing::debug!("Client left during the export"),
+            Err(err) => {
+                tracing::error!("Export failed: {:#}", err);
+                let _ = sender.blocking_send(Err(io::Error::other(err.to_string())));
+            }
+        }
+    });
+    // Ended streams may be polled again, e.g. by the compression layer
+    Body::from_stream(futures::stream::poll_fn(move |cx| chunks.poll_recv(cx)))
+}
+
+/// Most characters of a URL kept in the name of its report
+const MAX_NAME_LEN: usize = 60;
+
+/// A zip archive of the reports of `set` as `format`
+///
+/// Each URL analyzed gets `<n>-<url>.<ext>`, numbered in request order, and
+/// all of them together `aggregate.<ext>`. `index.json` lists the URLs with
+/// their report, or the error of those that failed.
+pub fn bundle(format: Format, set: &AnalysisResultSet) -> Result<Vec<u8>> {
+    let extension = format.extensions.first().copied().unwrap_or("html");
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let mut index = Vec::new();
+    for (number, entry) in set.entries.iter().enumerate() {
+        let Some(result) = &entry.result else {
+            index.push(json!({ "url": entry.source, "error": entry.error }));
+            continue;
+        };
+        let name = format!(
+            "{:03}-{}.{}",
+            number + 1,
+            file_name(&entry.source),
+            extension
+        );
+        zip.start_file(name.as_str(), options)?;
+        (format.create)(Some(&entry.source)).export_to_writer(result, &mut zip)?;
+        index.push(json!({ "url": entry.source, "file": name }));
+    }
+    let aggregate = format!("aggregate.{}", extension);
+    zip.start_file(aggregate.as_str(), options)?;
+    (format.create)(None).export_to_writer(&set.aggregate, &mut zip)?;
+
+    zip.start_file("index.json", options)?;
+    let index = json!({ "format": format.name, "aggregate": aggregate, "entries": index });
+    serde_json::to_writer_pretty(&mut zip, &index)?;
+    Ok(zip.finish()?.into_inner())
+}
+
+/// `url` without its scheme, as a file name of letters, digits, dots and
+/// dashes
+fn file_name(url: &str) -> String {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let mut name = String::new();
+    for c in rest.chars() {
+        if c.is_ascii_alphanumeric() || c == '.' {
+            name.push(c);
+        } else if !name.is_empty() && !name.ends_with('-') {
+            name.push('-');
+        }
+    }
+    name.truncate(MAX_NAME_LEN);
+    let name = name.trim_end_matches(['-', '.']);
+    match name.is_empty() {
+        true => "page".to_string(),
+        false => name.to_string(),
+    }
+}
+
+/// Sends what is written as body chunks, failing once the client is gone
+struct ChunkWriter(mpsc::Sender<io::Result<Bytes>>);
+
+impl Write for ChunkWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .blocking_send(Ok(Bytes::copy_from_slice(buf)))
+            .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ferret::analyzer::SourceResult;
+    use ferret::exporter::registry;
+
+    #[test]
+    fn test_negotiate() {
+        let registry = registry();
+        let name = |format: Option<&str>, accept: Option<&str>| {
+            negotiate(&registry, format, accept, "csv").map(|format| format.name)
+        };
+        assert_eq!(name(None, None), Ok("csv"));
+        assert_eq!(name(None, Some("*/*")), Ok("csv"));
+        assert_eq!(name(Some("markdown"), Some("text/csv")), Ok("md"));
+        assert_eq!(name(Some("NDJSON"), None), Ok("ndjson"));
+        assert_eq!(name(Some("elasticsearch"), None), Ok("elasticsearch"));
+        assert_eq!(
+            name(None, Some("text/csv;q=0.5, application/json")),
+            Ok("json")
+        );
+        assert_eq!(
+            name(None, Some("application/x-ndjson, text/csv")),
+            Ok("ndjson")
+        );
+        assert_eq!(
+            name(None, Some("application/vnd.apache.parquet")),
+            Ok("parquet")
+        );
+        assert_eq!(name(None, Some("text/html;q=0, text/*")), Ok("csv"));
+        assert_eq!(name(None, Some("image/*")), Ok("svg"));
+        assert_eq!(
+            name(None, Some("application/pdf")).unwrap_err().0,
+            StatusCode::NOT_ACCEPTABLE
+        );
+        assert_eq!(
+            name(Some("pdf"), None).unwrap_err().0,
+            StatusCode::BAD_REQUEST
+        );
+    }
+
+    #[test]
+    fn test_bundle() {
+        let result =
+            |html: &str| crate::analyze_html(html, &ferret::limits::Limits::untrusted(), 10);
+        let set = AnalysisResultSet::from_entries(
+            vec![
+                SourceResult::new("https://a.test/".to_string(), result("<p>a</p>")),
+                SourceResult::new(
+                    "https://b.test/x?y=1".to_string(),
+                    Err(anyhow::anyhow!("HTTP error")),
+                ),
+                SourceResult::new("https://c.test/".to_string(), result("<p><b>c</b></p>")),
+            ],
+            10,
+        );
+        let bundled = bundle(registry().get("csv").unwrap(), &set).unwrap();
+
+        let mut archive = zip::ZipArchive::new(Cursor::new(bundled)).unwrap();
+        let mut names: Vec<_> = archive.file_names().collect();
+        names.sort();
+        assert_eq!(
+            names,
+            [
+                "001-a.test.csv",
+                "003-c.test.csv",
+                "aggregate.csv",
+                "index.json"
+            ]
+        );
+        let mut read = |name: &str| {
+            let mut text = String::new();
+            io::Read::read_to_string(&mut archive.by_name(name).unwrap(), &mut text).unwrap();
+            text
+        };
+        assert!(read("003-c.test.csv").contains("\nb,"));
+        assert!(read("aggregate.csv").contains("\np,2,"));
+        let index: serde_json::Value = serde_json::from_str(&read("index.json")).unwrap();
+        assert_eq!(index["entries"][0]["file"], "001-a.test.csv");
+        assert_eq!(index["entries"][1]["error"], "HTTP error");
+
+        assert_eq!(
+            file_name("https://example.com/a/b?c=d"),
+            "example.com-a-b-c-d"
+        );
+        assert_eq!(file_name("https:///"), "page");
+        assert_eq!(file_name(&format!("http://{}", "a".repeat(100))).len(), 60);
+    }
+
+    #[tokio::test]
+    async fn test_stream() {
+        let result = crate::analyze_html(
+            &"<p>a</p>".repeat(10_000),
+            &ferret::limits::Limits::untrusted(),
+            10,
+        )
+        .unwrap();
+        let format = registry().get("xlsx").unwrap();
+        let body = stream((format.create)(None), result.clone());
+        let streamed = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+
+        let mut written = Vec::new();
+        (format.create)(None)
+            .export_to_writer(&result, &mut written)
+            .unwrap();
+        assert_eq!(streamed.len(), written.len());
+        assert!(streamed.starts_with(b"PK"));
+    }
+}
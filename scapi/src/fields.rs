@@ -0,0 +1,161 @@
+//! The parts of a JSON report a client asked for
+//!
+//! `GET /api/report?fields=tags.count,max_depth` keeps only the listed
+//! fields, each a dotted path into the report. Inside `tags` and
+//! `attributes`, which are keyed by name, the path continues in every
+//! entry, so `tags.count` keeps the count of each tag. Paths into lists
+//! apply to each item, and paths to fields the report lacks are skipped.
+//! `?summary=true` adds [`SUMMARY`].
+
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+/// Fields of `?summary=true`: the tag counts without their attributes
+pub const SUMMARY: &str = "files_analyzed,max_depth,tags.name,tags.count";
+/// Objects keyed by tag or attribute name
+const KEYED: [&str; 2] = ["tags", "attributes"];
+
+/// Field paths as a tree; `None` keeps the whole value
+#[derive(Debug, Default)]
+pub struct Fields(BTreeMap<String, Option<Fields>>);
+
+impl Fields {
+    /// Fields of a comma-separated list of dotted paths
+    pub fn parse(list: &str) -> Result<Self, String> {
+        let mut fields = Self::default();
+        for path in list
+            .split(',')
+            .map(str::trim)
+            .filter(|path| !path.is_empty())
+        {
+            let segments: Vec<_> = path.split('.').collect();
+            if segments.iter().any(|segment| segment.is_empty()) {
+                return Err(format!("Invalid field {:?}", path));
+            }
+            fields.insert(&segments);
+        }
+        match fields.0.is_empty() {
+            true => Err("fields must name at least one field".to_string()),
+            false => Ok(fields),
+        }
+    }
+
+    fn insert(&mut self, path: &[&str]) {
+        let Some((first, rest)) = path.split_first() else {
+            return;
+        };
+        if rest.is_empty() {
+            // The whole value, whatever was asked of it before
+            self.0.insert(first.to_string(), None);
+            return;
+        }
+        let entry = self
+            .0
+            .entry(first.to_string())
+            .or_insert_with(|| Some(Self::default()));
+        if let Some(fields) = entry {
+            fields.insert(rest);
+        }
+    }
+
+    /// Add the paths of `other`
+    pub fn merge(&mut self, other: Fields) {
+        for (name, fields) in other.0 {
+            match (self.0.get_mut(&name), fields) {
+                (Some(Some(known)), Some(fields)) => known.merge(fields),
+                (Some(None), _) => {}
+                (_, fields) => {
+                    self.0.insert(name, fields);
+                }
+            }
+        }
+    }
+
+    /// `value` with only these fields
+    pub fn project(&self, value: Value) -> Value {
+        match value {
+            Value::Object(mut object) => Value::Object(
+                self.0
+                    .iter()
+                    .filter_map(|(name, fields)| {
+                        let value = object.remove(name)?;
+                        let value = match fields {
+                            None => value,
+                            Some(fields) if KEYED.contains(&name.as_str()) => {
+                                fields.project_entries(value)
+                            }
+                            Some(fields) => fields.project(value),
+                        };
+                        Some((name.clone(), value))
+                    })
+                    .collect(),
+            ),
+            Value::Array(items) => {
+                Value::Array(items.into_iter().map(|item| self.project(item)).collect())
+            }
+            value => value,
+        }
+    }
+
+    /// `value` with these fields kept in each of its entries
+    fn project_entries(&self, value: Value) -> Value {
+        match value {
+            Value::Object(entries) => Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(key, entry)| (key, self.project(entry)))
+                    .collect::<Map<_, _>>(),
+            ),
+            value => self.project(value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_project() {
+        let report = json!({
+            "files_analyzed": 1,
+            "max_depth": 3,
+            "tags": {
+                "a": {
+                    "name": "a",
+                    "count": 2,
+                    "attributes": {
+                        "href": { "name": "href", "count": 2, "value_counts": { "/": 2 } },
+                    },
+                },
+            },
+            "redirects": [{ "url": "http://a.test/", "status": 301 }],
+        });
+        let project = |list| Fields::parse(list).unwrap().project(report.clone());
+
+        assert_eq!(
+            project("tags.count, max_depth,links"),
+            json!({ "max_depth": 3, "tags": { "a": { "count": 2 } } })
+        );
+        assert_eq!(
+            project("tags.attributes.count,redirects.status"),
+            json!({
+                "tags": { "a": { "attributes": { "href": { "count": 2 } } } },
+                "redirects": [{ "status": 301 }],
+            })
+        );
+        // The whole value wins over parts of it
+        assert_eq!(project("tags.count,tags"), project("tags"));
+        assert_eq!(project("tags,tags.count"), project("tags"));
+
+        let mut summary = Fields::parse(SUMMARY).unwrap();
+        summary.merge(Fields::parse("tags.attributes.count").unwrap());
+        let tag = &summary.project(report.clone())["tags"]["a"];
+        assert_eq!(tag["count"], 2);
+        assert_eq!(tag["attributes"]["href"], json!({ "count": 2 }));
+
+        assert!(Fields::parse("tags..count").is_err());
+        assert!(Fields::parse(" , ").is_err());
+    }
+}
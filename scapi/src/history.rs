@@ -0,0 +1,446 @@
+//! Analyses of the pages fetched by the server, kept to follow them over time
+//!
+//! Every page analyzed by `GET /api/report` or a batch is a run, numbered in
+//! order. With `server.history_path` set, runs are appended to that JSON
+//! Lines file and read back on start; otherwise they last as long as the
+//! server. Only the latest `server.max_history_runs` are kept in memory,
+//! and the file is rewritten with them once it holds twice as many. A run
+//! left half-written at the end of the file by a crash is dropped.
+//!
+//! `GET /api/history?url=...` lists the runs of a URL, and
+//! `GET /api/trend?url=...&metric=tags.div.count` one number of their
+//! results, named by its path in the JSON result, as a time series.
+
+use anyhow::{Context, Result};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use ferret::analyzer::AnalysisResult;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::AppState;
+
+/// Runs listed by `GET /api/history` unless `limit` says otherwise
+const DEFAULT_LIMIT: usize = 100;
+
+/// One analysis of a page
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Run {
+    pub id: u64,
+    pub source: String,
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    pub result: AnalysisResult,
+}
+
+pub struct History {
+    /// Runs kept in memory; `0` records none
+    max_runs: usize,
+    inner: Mutex<Inner>,
+}
+
+struct Inner {
+    runs: VecDeque<Arc<Run>>,
+    next_id: u64,
+    file: Option<File>,
+    /// Where `file` is, to compact it
+    path: PathBuf,
+    /// Runs in `file`, including those no longer kept in memory
+    file_runs: usize,
+}
+
+impl History {
+    /// History lasting as long as the server
+    pub fn new(max_runs: usize) -> Self {
+        Self {
+            max_runs,
+            inner: Mutex::new(Inner {
+                runs: VecDeque::new(),
+                next_id: 1,
+                file: None,
+                path: PathBuf::new(),
+                file_runs: 0,
+            }),
+        }
+    }
+
+    /// History appended to the JSON Lines file at `path`, starting with the
+    /// runs already in it; a missing file is created
+    pub fn open(path: &Path, max_runs: usize) -> Result<Self> {
+        let history = Self::new(max_runs);
+        let mut inner = history.inner.lock().unwrap();
+        inner.path = path.to_path_buf();
+        let mut torn = false;
+        match File::open(path) {
+            Ok(file) => {
+                let mut lines = BufReader::new(file).lines().enumerate().peekable();
+                while let Some((number, line)) = lines.next() {
+                    let line =
+                        line.with_context(|| format!("Failed to read {}", path.display()))?;
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    let run: Run = match serde_json::from_str(&line) {
+                        Ok(run) => run,
+                        // Half-written when the server stopped
+                        Err(err) if lines.peek().is_none() => {
+                            tracing::warn!(
+                                "Dropping the incomplete last run of {}: {}",
+                                path.display(),
+                                err
+                            );
+                            torn = true;
+                            continue;
+                        }
+                        Err(err) => {
+                            return Err(err).with_context(|| {
+                                format!("Invalid run on line {} of {}", number + 1, path.display())
+                            })
+                        }
+                    };
+                    inner.next_id = inner.next_id.max(run.id + 1);
+                    inner.file_runs += 1;
+                    inner.push(Arc::new(run), max_runs);
+                }
+            }
+            Err(err) if err.kind() == ErrorKind::NotFound => {}
+            Err(err) => {
+                return Err(err).with_context(|| format!("Failed to open {}", path.display()))
+            }
+        }
+        if torn || inner.file_runs > inner.runs.len() {
+            inner.compact()?;
+        } else {
+            if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+                std::fs::create_dir_all(dir)?;
+            }
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .with_context(|| format!("Failed to open {}", path.display()))?;
+            inner.file = Some(file);
+        }
+        drop(inner);
+        Ok(history)
+    }
+
+    /// Add a run of `source`, returning its id
+    pub fn record(&self, source: &str, result: &AnalysisResult) -> Result<Option<u64>> {
+        if self.max_runs == 0 {
+            return Ok(None);
+        }
+        let timestamp = unix_time();
+        let mut inner = self.inner.lock().unwrap();
+        let run = Run {
+            id: inner.next_id,
+            source: source.to_string(),
+            timestamp,
+            result: result.clone(),
+        };
+        inner.next_id += 1;
+        if let Some(file) = &mut inner.file {
+            let mut line = serde_json::to_vec(&run)?;
+            line.push(b'\n');
+            file.write_all(&line)
+                .context("Failed to write the history")?;
+            inner.file_runs += 1;
+        }
+        let id = run.id;
+        inner.push(Arc::new(run), self.max_runs);
+        if inner.file.is_some() && inner.file_runs >= 2 * self.max_runs {
+            inner.compact()?;
+        }
+        Ok(Some(id))
+    }
+
+    pub fn get(&self, id: u64) -> Option<Arc<Run>> {
+        let inner = self.inner.lock().unwrap();
+        let index = inner.runs.binary_search_by_key(&id, |run| run.id).ok()?;
+        Some(Arc::clone(&inner.runs[index]))
+    }
+
+    /// Runs of `source`, oldest first
+    pub fn runs(&self, source: &str) -> Vec<Arc<Run>> {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .runs
+            .iter()
+            .filter(|run| run.source == source)
+            .cloned()
+            .collect()
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl Inner {
+    fn push(&mut self, run: Arc<Run>, max_runs: usize) {
+        self.runs.push_back(run);
+        while self.runs.len() > max_runs {
+            self.runs.pop_front();
+        }
+    }
+
+    /// Rewrite the file with only the runs kept in memory
+    fn compact(&mut self) -> Result<()> {
+        let dir = match self.path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        std::fs::create_dir_all(dir)?;
+        let mut temp = tempfile::NamedTempFile::new_in(dir)
+            .with_context(|| format!("Failed to compact {}", self.path.display()))?;
+        for run in &self.runs {
+            serde_json::to_writer(&mut temp, &**run)?;
+            temp.write_all(b"\n")?;
+        }
+        let file = temp
+            .persist(&self.path)
+            .with_context(|| format!("Failed to compact {}", self.path.display()))?;
+        self.file = Some(file);
+        self.file_runs = self.runs.len();
+        Ok(())
+    }
+}
+
+impl AppState {
+    /// Add a run to the history, returning its id; failing to store it
+    /// doesn't fail the request that analyzed the page
+    pub(crate) fn record(&self, source: &str, result: &AnalysisResult) -> Option<u64> {
+        self.history.record(source, result).unwrap_or_else(|err| {
+            tracing::error!("Failed to record {} in the history: {:#}", source, err);
+            None
+        })
+    }
+}
+
+/// Seconds since the Unix epoch
+pub(crate) fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs())
+}
+
+/// Query parameters of `GET /api/history`
+#[derive(Deserialize)]
+pub struct HistoryParams {
+    url: String,
+    /// Latest runs listed, 100 by default
+    limit: Option<usize>,
+}
+
+/// Query parameters of `GET /api/trend`
+#[derive(Deserialize)]
+pub struct TrendParams {
+    url: String,
+    /// Dot-separated path of a number in the results, e.g. `max_depth` or
+    /// `tags.div.count`
+    metric: String,
+}
+
+#[derive(Debug, Serialize)]
+struct Trend {
+    url: String,
+    metric: String,
+    points: Vec<TrendPoint>,
+}
+
+#[derive(Debug, Serialize)]
+struct TrendPoint {
+    run: u64,
+    timestamp: u64,
+    /// `None` when the result doesn't have the metric, e.g. a tag missing
+    /// from that run
+    value: Option<f64>,
+}
+
+/// List the latest runs of a URL, oldest first
+pub async fn handler_history(
+    Query(params): Query<HistoryParams>,
+    State(state): State<AppState>,
+) -> Response {
+    let runs = state.history.runs(&params.url);
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT);
+    let latest: Vec<&Run> = runs
+        .iter()
+        .skip(runs.len().saturating_sub(limit))
+        .map(|run| &**run)
+        .collect();
+    Json(latest).into_response()
+}
+
+/// One number of the results of a URL over its runs
+pub async fn handler_trend(
+    Query(params): Query<TrendParams>,
+    State(state): State<AppState>,
+) -> Response {
+    let mut points = Vec::new();
+    for run in state.history.runs(&params.url) {
+        let value = match metric(&run.result, &params.metric) {
+            Ok(value) => value,
+            Err(message) => return (StatusCode::BAD_REQUEST, message).into_response(),
+        };
+        points.push(TrendPoint {
+            run: run.id,
+            timestamp: run.timestamp,
+            value,
+        });
+    }
+    Json(Trend {
+        url: params.url,
+        metric: params.metric,
+        points,
+    })
+    .into_response()
+}
+
+/// The number at the dot-separated `path` of `result`, if it has one
+fn metric(result: &AnalysisResult, path: &str) -> Result<Option<f64>, String> {
+    let json = serde_json::to_value(result).map_err(|e| e.to_string())?;
+    let value = path.split('.').try_fold(&json, |value, key| value.get(key));
+    match value {
+        None | Some(serde_json::Value::Null) => Ok(None),
+        Some(serde_json::Value::Number(number)) => Ok(number.as_f64()),
+        Some(_) => Err(format!("Metric {:?} is not a number", path)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{local_state, serve_site};
+
+    fn result(html: &str) -> AnalysisResult {
+        crate::analyze_html(html, &ferret::limits::Limits::untrusted(), 10).unwrap()
+    }
+
+    #[test]
+    fn test_open_record() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history/runs.jsonl");
+        let history = History::open(&path, 2).unwrap();
+        for html in ["<p>a</p>", "<div></div>", "<p>a</p><p>b</p>"] {
+            history.record("https://a.test/", &result(html)).unwrap();
+        }
+        history.record("https://b.test/", &result("")).unwrap();
+        let runs = history.runs("https://a.test/");
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].id, 3);
+
+        // Read back; the file was compacted to the 2 runs kept
+        let history = History::open(&path, 10).unwrap();
+        let ids: Vec<u64> = history
+            .runs("https://a.test/")
+            .iter()
+            .map(|run| run.id)
+            .collect();
+        assert_eq!(ids, [3]);
+        assert_eq!(
+            history.record("https://a.test/", &result("")).unwrap(),
+            Some(5)
+        );
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 3);
+
+        // A run cut short by a crash is dropped from the file
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(br#"{"id":6,"source":"https://a."#).unwrap();
+        let history = History::open(&path, 10).unwrap();
+        assert_eq!(history.runs("https://a.test/").len(), 2);
+        assert_eq!(
+            history.record("https://a.test/", &result("")).unwrap(),
+            Some(6)
+        );
+        assert_eq!(
+            History::open(&path, 10)
+                .unwrap()
+                .runs("https://a.test/")
+                .len(),
+            3
+        );
+        // Unlike a broken line followed by others
+        std::fs::write(&path, "{}\n{}\n").unwrap();
+        assert!(History::open(&path, 10).is_err());
+
+        assert_eq!(
+            History::new(0)
+                .record("https://a.test/", &result(""))
+                .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_metric() {
+        let result = result("<div><p>a</p><p>b</p></div>");
+        assert_eq!(metric(&result, "tags.p.count"), Ok(Some(2.0)));
+        assert_eq!(metric(&result, "max_depth"), Ok(Some(2.0)));
+        assert_eq!(metric(&result, "tags.table.count"), Ok(None));
+        assert!(metric(&result, "tags.p").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_handler_trend() {
+        let base = serve_site().await;
+        let state = local_state();
+        let url = format!("{}/counter", base);
+        for _ in 0..2 {
+            let uri = "/api/report?refresh=true".parse().unwrap();
+            crate::handler_report(
+                axum::extract::Path(url.clone()),
+                Query::try_from_uri(&uri).unwrap(),
+                State(state.clone()),
+                axum::http::HeaderMap::new(),
+            )
+            .await;
+        }
+
+        let uri = format!("/api/trend?url={}&metric=tags.p.count", url)
+            .parse()
+            .unwrap();
+        let response =
+            handler_trend(Query::try_from_uri(&uri).unwrap(), State(state.clone())).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let trend: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        let values: Vec<_> = trend["points"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|point| point["value"].as_f64().unwrap())
+            .collect();
+        assert_eq!(values, [1.0, 2.0]);
+
+        let uri = format!("/api/history?url={}&limit=1", url).parse().unwrap();
+        let response =
+            handler_history(Query::try_from_uri(&uri).unwrap(), State(state.clone())).await;
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let runs: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(runs.as_array().unwrap().len(), 1);
+        assert_eq!(runs[0]["id"], 2);
+        assert_eq!(runs[0]["result"]["tags"]["p"]["count"], 2);
+
+        let uri = format!("/api/trend?url={}&metric=tags", url)
+            .parse()
+            .unwrap();
+        let response = handler_trend(Query::try_from_uri(&uri).unwrap(), State(state)).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
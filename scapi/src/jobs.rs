@@ -0,0 +1,807 @@
+//! Batches and crawls run in the background
+//!
+//! `POST /api/jobs` queues a job and answers at once with its id; clients
+//! poll `GET /api/jobs/:id` for its progress and fetch the analysis from
+//! `GET /api/jobs/:id/result` once it has completed. At most
+//! `server.job_concurrency` jobs run at the same time, and the store keeps
+//! `server.max_jobs` of them, dropping the oldest finished ones first.
+//!
+//! [`Job::events`] follows a job as it runs, for `/api/ws` and as
+//! Server-Sent Events from `GET /api/jobs/:id/events`.
+//!
+//! `GET /api/admin/jobs` lists the jobs kept and `DELETE
+//! /api/admin/jobs/:id` cancels one, see [`crate::admin`].
+//!
+//! Jobs unfinished when the server shuts down are saved as [`Checkpoint`]s
+//! and [`resume`]d when it starts again, see [`crate::shutdown`].
+
+use anyhow::Result;
+use axum::{
+    extract::{Path, Query, State},
+    http::{header::LOCATION, HeaderMap, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeMap, VecDeque};
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use tokio::sync::{broadcast, watch, Semaphore};
+use tokio::task::AbortHandle;
+use tokio::time::Instant;
+use tracing::Instrument;
+
+use ferret::analyzer::stream::StreamAnalyzer;
+use ferret::analyzer::AnalysisResultSet;
+use ferret::config::ServerConfig;
+use ferret::crawler::{CrawlResult, Crawler};
+use ferret::fetch::FetchOptions;
+use ferret::progress::{Progress, ProgressEvent};
+
+use crate::{run_batch, AppState, FetchParams};
+
+/// Page events kept for subscribers lagging behind
+const PAGE_EVENTS: usize = 256;
+
+/// What a job fetches, as the JSON body of `POST /api/jobs`
+///
+/// ```json
+/// {"kind": "batch", "urls": ["https://example.com/", "https://example.org/"]}
+/// {"kind": "crawl", "urls": ["https://example.com/"], "max_depth": 3, "max_pages": 500}
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum JobRequest {
+    /// Analyze each URL, like `POST /api/batch`
+    Batch {
+        urls: Vec<String>,
+        concurrency: Option<usize>,
+    },
+    /// Crawl the sites of the seed URLs
+    Crawl {
+        urls: Vec<String>,
+        max_depth: Option<usize>,
+        /// At most `server.max_job_pages`, the default
+        max_pages: Option<usize>,
+    },
+}
+
+impl JobRequest {
+    fn urls(&self) -> &[String] {
+        match self {
+            Self::Batch { urls, .. } | Self::Crawl { urls, .. } => urls,
+        }
+    }
+
+    fn kind(&self) -> JobKind {
+        match self {
+            Self::Batch { .. } => JobKind::Batch,
+            Self::Crawl { .. } => JobKind::Crawl,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobKind {
+    Batch,
+    Crawl,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    /// Waiting for one of the running jobs to finish
+    Queued,
+    Running,
+    Completed,
+    /// The job could not run at all; pages that fail to load don't fail
+    /// the job but are counted in `errors`
+    Failed,
+    /// Stopped by an administrator before it finished
+    Cancelled,
+}
+
+impl JobState {
+    pub fn is_finished(self) -> bool {
+        matches!(self, Self::Completed | Self::Failed | Self::Cancelled)
+    }
+}
+
+/// Progress of a job, as returned by `GET /api/jobs/:id`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct JobStatus {
+    pub id: String,
+    pub kind: JobKind,
+    pub state: JobState,
+    /// Pages fetched so far, successfully or not
+    pub completed: usize,
+    /// Pages to fetch, as far as known; crawls find more as they go
+    pub total: usize,
+    /// Pages that failed to load or analyze
+    pub errors: usize,
+    /// Why the job failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// A page of a job has been fetched, successfully or not
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PageEvent {
+    pub source: String,
+    pub completed: usize,
+    pub total: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Elements of all pages finished so far, by tag name
+    pub tags: BTreeMap<String, usize>,
+}
+
+/// What happens to a running job, tagged by `event`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum JobEvent {
+    /// The job was queued, started, completed or failed
+    Status(JobStatus),
+    Page(PageEvent),
+}
+
+impl JobEvent {
+    /// The `event` tag
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Status(_) => "status",
+            Self::Page(_) => "page",
+        }
+    }
+}
+
+/// Analysis of a completed job
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum JobOutput {
+    Batch(AnalysisResultSet),
+    Crawl(CrawlResult),
+}
+
+pub struct Job {
+    /// Only state changes notify subscribers; page counts are sent as
+    /// page events
+    status: watch::Sender<JobStatus>,
+    pages: broadcast::Sender<PageEvent>,
+    /// Elements of the finished pages by tag name
+    tags: Mutex<BTreeMap<String, usize>>,
+    output: OnceLock<JobOutput>,
+    /// How to run the job again, unless it was given credentials
+    checkpoint: OnceLock<Checkpoint>,
+    /// The task running the job, aborted to cancel it
+    task: OnceLock<AbortHandle>,
+}
+
+/// An unfinished job, to run again from the start after a restart
+///
+/// Credentials aren't saved, so jobs given a cookie,
+/// `X-Target-Authorization` or forwarded headers have none.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub id: String,
+    pub request: JobRequest,
+    pub user_agent: Option<String>,
+    pub max_redirects: Option<usize>,
+}
+
+impl Job {
+    pub fn status(&self) -> JobStatus {
+        self.status.borrow().clone()
+    }
+
+    pub fn output(&self) -> Option<&JobOutput> {
+        self.output.get()
+    }
+
+    /// Stop the job, unless it has finished; returns whether it was stopped
+    pub fn cancel(&self) -> bool {
+        let cancelled = self.status.send_if_modified(|status| {
+            if status.state.is_finished() {
+                return false;
+            }
+            status.state = JobState::Cancelled;
+            true
+        });
+        if let (true, Some(task)) = (cancelled, self.task.get()) {
+            tracing::info!(id = %self.status().id, "Cancelled");
+            task.abort();
+        }
+        cancelled
+    }
+
+    /// Events of the job from now on, starting with its current status
+    pub fn events(self: &Arc<Self>) -> JobEvents {
+        let mut status = self.status.subscribe();
+        status.mark_changed();
+        JobEvents {
+            status,
+            pages: self.pages.subscribe(),
+            finished: false,
+            job: self.clone(),
+        }
+    }
+
+    /// A progress receiver counting finished pages
+    fn progress(self: &Arc<Self>) -> Progress {
+        let job = self.clone();
+        Progress::new(move |event| {
+            let ProgressEvent::SourceCompleted {
+                source,
+                completed,
+                total,
+                error,
+                tags,
+            } = event
+            else {
+                return;
+            };
+            let total = total.max(completed);
+            let tags = {
+                let mut totals = job.tags.lock().unwrap();
+                for (tag, count) in tags {
+                    *totals.entry(tag).or_default() += count;
+                }
+                totals.clone()
+            };
+            job.status.send_if_modified(|status| {
+                status.completed = completed;
+                status.total = total;
+                status.errors += usize::from(error.is_some());
+                false
+            });
+            let _ = job.pages.send(PageEvent {
+                source,
+                completed,
+                total,
+                error,
+                tags,
+            });
+        })
+    }
+
+    /// Record the outcome of the job, unless it was cancelled meanwhile
+    fn finish(&self, output: Result<JobOutput>) {
+        self.status.send_if_modified(|status| {
+            if status.state.is_finished() {
+                return false;
+            }
+            match output {
+                Ok(output) => {
+                    tracing::info!("Completed");
+                    let _ = self.output.set(output);
+                    status.state = JobState::Completed;
+                }
+                Err(e) => {
+                    tracing::warn!("Failed: {:#}", e);
+                    status.state = JobState::Failed;
+                    status.error = Some(format!("{:#}", e));
+                }
+            }
+            true
+        });
+    }
+}
+
+/// Events of a job, ending after it has finished
+///
+/// Subscribers too slow to keep up miss page events, never status ones.
+pub struct JobEvents {
+    status: watch::Receiver<JobStatus>,
+    pages: broadcast::Receiver<PageEvent>,
+    finished: bool,
+    /// Keeps the senders alive should the job be dropped from the store
+    job: Arc<Job>,
+}
+
+impl JobEvents {
+    pub fn job(&self) -> &Arc<Job> {
+        &self.job
+    }
+
+    pub async fn next(&mut self) -> Option<JobEvent> {
+        if self.finished {
+            return None;
+        }
+        loop {
+            // Pages first, so they all come before the final status
+            tokio::select! {
+                biased;
+                page = self.pages.recv() => match page {
+                    Ok(page) => return Some(JobEvent::Page(page)),
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                },
+                changed = self.status.changed() => {
+                    changed.ok()?;
+                    let status = self.status.borrow_and_update().clone();
+                    self.finished = status.state.is_finished();
+                    return Some(JobEvent::Status(status));
+                }
+            }
+        }
+    }
+}
+
+/// Jobs of the server, oldest first
+pub struct Jobs {
+    jobs: Mutex<VecDeque<(String, Arc<Job>)>>,
+    /// One permit per job allowed to run
+    running: Arc<Semaphore>,
+    max_jobs: usize,
+    next_id: AtomicU64,
+    /// Makes ids hard to guess, as anyone knowing one can read the job
+    ids: RandomState,
+}
+
+impl Jobs {
+    pub fn new(config: &ServerConfig) -> Self {
+        Self {
+            jobs: Mutex::new(VecDeque::new()),
+            running: Arc::new(Semaphore::new(config.job_concurrency.max(1))),
+            max_jobs: config.max_jobs.max(1),
+            next_id: AtomicU64::new(0),
+            ids: RandomState::new(),
+        }
+    }
+
+    /// Stop starting queued jobs, for good
+    pub fn close(&self) {
+        self.running.close();
+    }
+
+    /// Wait for the running jobs to finish, until `deadline`
+    pub async fn drain(&self, deadline: Instant) {
+        let running: Vec<_> = {
+            let jobs = self.jobs.lock().unwrap();
+            jobs.iter()
+                .filter(|(_, job)| job.status.borrow().state == JobState::Running)
+                .map(|(_, job)| job.status.subscribe())
+                .collect()
+        };
+        let finished =
+            futures::future::join_all(running.into_iter().map(|mut status| async move {
+                let _ = status.wait_for(|status| status.state.is_finished()).await;
+            }));
+        let _ = tokio::time::timeout_at(deadline, finished).await;
+    }
+
+    /// Checkpoints of the unfinished jobs, and how many unfinished jobs
+    /// have none
+    pub fn checkpoints(&self) -> (Vec<Checkpoint>, usize) {
+        let jobs = self.jobs.lock().unwrap();
+        let mut checkpoints = Vec::new();
+        let mut missing = 0;
+        for (_, job) in jobs.iter() {
+            if job.status.borrow().state.is_finished() {
+                continue;
+            }
+            match job.checkpoint.get() {
+                Some(checkpoint) => checkpoints.push(checkpoint.clone()),
+                None => missing += 1,
+            }
+        }
+        (checkpoints, missing)
+    }
+
+    /// Statuses of the jobs kept, oldest first
+    pub fn list(&self) -> Vec<JobStatus> {
+        let jobs = self.jobs.lock().unwrap();
+        jobs.iter().map(|(_, job)| job.status()).collect()
+    }
+
+    pub fn get(&self, id: &str) -> Option<Arc<Job>> {
+        let jobs = self.jobs.lock().unwrap();
+        jobs.iter()
+            .find(|(job_id, _)| job_id == id)
+            .map(|(_, job)| job.clone())
+    }
+
+    /// Jobs kept, by state
+    pub fn counts(&self) -> JobCounts {
+        let jobs = self.jobs.lock().unwrap();
+        let mut counts = JobCounts::default();
+        for (_, job) in jobs.iter() {
+            match job.status.borrow().state {
+                JobState::Queued => counts.queued += 1,
+                JobState::Running => counts.running += 1,
+                JobState::Completed | JobState::Failed | JobState::Cancelled => {
+                    counts.finished += 1
+                }
+            }
+        }
+        counts.full = jobs.len() >= self.max_jobs && counts.finished == 0;
+        counts
+    }
+
+    /// Add a queued job, with a new id unless it is given one, or `None` if
+    /// the store is full of unfinished jobs
+    fn insert(&self, id: Option<String>, kind: JobKind, total: usize) -> Option<Arc<Job>> {
+        let mut jobs = self.jobs.lock().unwrap();
+        if jobs.len() >= self.max_jobs {
+            let finished = jobs
+                .iter()
+                .position(|(_, job)| job.status.borrow().state.is_finished())?;
+            jobs.remove(finished);
+        }
+        let id = id.unwrap_or_else(|| {
+            let mut hasher = self.ids.build_hasher();
+            hasher.write_u64(self.next_id.fetch_add(1, Ordering::Relaxed));
+            format!("{:016x}", hasher.finish())
+        });
+        let (status, _) = watch::channel(JobStatus {
+            id: id.clone(),
+            kind,
+            state: JobState::Queued,
+            completed: 0,
+            total,
+            errors: 0,
+            error: None,
+        });
+        let job = Arc::new(Job {
+            status,
+            pages: broadcast::channel(PAGE_EVENTS).0,
+            tags: Mutex::new(BTreeMap::new()),
+            output: OnceLock::new(),
+            checkpoint: OnceLock::new(),
+            task: OnceLock::new(),
+        });
+        jobs.push_back((id, job.clone()));
+        Some(job)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct JobCounts {
+    pub queued: usize,
+    pub running: usize,
+    pub finished: usize,
+    /// New jobs are refused until one finishes
+    pub full: bool,
+}
+
+impl Default for Jobs {
+    fn default() -> Self {
+        Self::new(&ServerConfig::default())
+    }
+}
+
+/// Run `request` to completion as `job`, once a slot is free
+async fn run(
+    state: AppState,
+    job: Arc<Job>,
+    request: JobRequest,
+    fetch: FetchOptions,
+    private: bool,
+) {
+    let Ok(_permit) = state.jobs.running.clone().acquire_owned().await else {
+        return;
+    };
+    // Cancelled while it was queued, before the task was aborted
+    let started = job.status.send_if_modified(|status| {
+        let queued = status.state == JobState::Queued;
+        if queued {
+            status.state = JobState::Running;
+        }
+        queued
+    });
+    if !started {
+        return;
+    }
+    let server = &state.config.server;
+    let progress = job.progress();
+    let output = match request {
+        JobRequest::Batch { urls, concurrency } => {
+            let concurrency = concurrency
+                .unwrap_or(server.batch_concurrency)
+                .clamp(1, server.batch_concurrency.max(1));
+            run_batch(&state, fetch, urls, concurrency, !private, Some(&progress))
+                .await
+                .map(JobOutput::Batch)
+        }
+        JobRequest::Crawl {
+            urls,
+            max_depth,
+            max_pages,
+        } => {
+            let analyzer = StreamAnalyzer::new(state.config.analyzer.top_values)
+                .with_limits(state.limits())
+                .with_fetch_options(fetch)
+                .with_progress(progress);
+            let mut crawler = Crawler::new(analyzer)
+                .with_max_pages(max_pages.unwrap_or(usize::MAX).min(server.max_job_pages))
+                .with_concurrency(server.batch_concurrency);
+            if let Some(max_depth) = max_depth {
+                crawler = crawler.with_max_depth(max_depth);
+            }
+            if let Some(sink) = &state.sink {
+                crawler = crawler.with_sink(sink.clone());
+            }
+            crawler.crawl(&urls).await.map(JobOutput::Crawl)
+        }
+    };
+    job.finish(output);
+}
+
+/// Queue `request`, fetching as `params` and the request `headers` say,
+/// returning the events of the job from the start, or why it is refused
+pub fn start(
+    state: &AppState,
+    request: JobRequest,
+    params: &FetchParams,
+    headers: &HeaderMap,
+) -> Result<JobEvents, (StatusCode, String)> {
+    queue(state, None, request, params, headers)
+}
+
+/// Queue a job saved at shutdown again, under the same id
+pub fn resume(state: &AppState, checkpoint: Checkpoint) -> Result<(), (StatusCode, String)> {
+    let params = FetchParams {
+        user_agent: checkpoint.user_agent,
+        cookie: None,
+        max_redirects: checkpoint.max_redirects,
+    };
+    let id = Some(checkpoint.id);
+    queue(state, id, checkpoint.request, &params, &HeaderMap::new()).map(drop)
+}
+
+fn queue(
+    state: &AppState,
+    id: Option<String>,
+    request: JobRequest,
+    params: &FetchParams,
+    headers: &HeaderMap,
+) -> Result<JobEvents, (StatusCode, String)> {
+    let urls = request.urls();
+    if urls.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "No URLs given".to_string()));
+    }
+    let max_pages = state.config.server.max_job_pages;
+    if urls.len() > max_pages {
+        return Err((
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("Too many URLs: {} (at most {})", urls.len(), max_pages),
+        ));
+    }
+    for url in urls {
+        state
+            .check_url(url)
+            .map_err(|(status, reason)| (status, format!("{}: {}", reason, url)))?;
+    }
+
+    let job = state.jobs.insert(id, request.kind(), urls.len()).ok_or((
+        StatusCode::SERVICE_UNAVAILABLE,
+        "Too many unfinished jobs, try again later".to_string(),
+    ))?;
+    let private = params.has_credentials(headers, state);
+    if !private {
+        let _ = job.checkpoint.set(Checkpoint {
+            id: job.status().id,
+            request: request.clone(),
+            user_agent: params.user_agent.clone(),
+            max_redirects: params.max_redirects,
+        });
+    }
+    let fetch = params.to_options(headers, state);
+    let events = job.events();
+    let span = tracing::info_span!("job", id = %job.status().id);
+    let task =
+        tokio::spawn(run(state.clone(), job.clone(), request, fetch, private).instrument(span));
+    let _ = job.task.set(task.abort_handle());
+    Ok(events)
+}
+
+/// Queue a batch or crawl, answering `202 Accepted` with the job's status
+/// and its URL in `Location`
+pub async fn handler_create_job(
+    Query(params): Query<FetchParams>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<JobRequest>,
+) -> Response {
+    let status = match start(&state, request, &params, &headers) {
+        Ok(events) => events.job().status(),
+        Err(refused) => return refused.into_response(),
+    };
+    (
+        StatusCode::ACCEPTED,
+        [(LOCATION, format!("/api/jobs/{}", status.id))],
+        Json(status),
+    )
+        .into_response()
+}
+
+pub async fn handler_job(Path(id): Path<String>, State(state): State<AppState>) -> Response {
+    match state.jobs.get(&id) {
+        Some(job) => Json(job.status()).into_response(),
+        None => not_found(&id),
+    }
+}
+
+/// The analysis of a completed job; `409 Conflict` while it is queued or
+/// running
+pub async fn handler_job_result(Path(id): Path<String>, State(state): State<AppState>) -> Response {
+    let Some(job) = state.jobs.get(&id) else {
+        return not_found(&id);
+    };
+    let status = job.status();
+    match (status.state, job.output()) {
+        (JobState::Completed, Some(output)) => Json(output).into_response(),
+        (JobState::Failed, _) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Job failed: {}", status.error.unwrap_or_default()),
+        )
+            .into_response(),
+        _ => (
+            StatusCode::CONFLICT,
+            format!("Job is {}", serde_json::to_string(&status.state).unwrap()),
+        )
+            .into_response(),
+    }
+}
+
+/// The events of a job as Server-Sent Events, named `status` and `page`
+/// with the JSON of the [`JobEvent`] as data, from its current status
+/// until it has finished
+pub async fn handler_job_events(Path(id): Path<String>, State(state): State<AppState>) -> Response {
+    let Some(job) = state.jobs.get(&id) else {
+        return not_found(&id);
+    };
+    let events = futures::stream::unfold(job.events(), |mut events| async move {
+        let event = events.next().await?;
+        let sse = Event::default().event(event.name()).json_data(&event);
+        Some((sse, events))
+    });
+    Sse::new(events)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+fn not_found(id: &str) -> Response {
+    (StatusCode::NOT_FOUND, format!("No job {}", id)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{local_state, serve_site};
+    use std::time::Duration;
+
+    async fn body(response: Response) -> serde_json::Value {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_jobs() {
+        let base = serve_site().await;
+        let state = local_state();
+        let create = |request: serde_json::Value| {
+            let query = Query::try_from_uri(&"/api/jobs".parse().unwrap()).unwrap();
+            let request = serde_json::from_value(request).unwrap();
+            handler_create_job(query, State(state.clone()), HeaderMap::new(), Json(request))
+        };
+
+        let response = create(serde_json::json!({
+            "kind": "batch",
+            "urls": [format!("{}/page", base), format!("{}/missing", base)],
+        }))
+        .await;
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        let location = response.headers()[LOCATION].to_str().unwrap().to_string();
+        let created = body(response).await;
+        assert_eq!(created["kind"], "batch");
+        assert_eq!(created["total"], 2);
+        let id = created["id"].as_str().unwrap().to_string();
+        assert_eq!(location, format!("/api/jobs/{}", id));
+
+        let job = state.jobs.get(&id).unwrap();
+        let mut updates = job.status.subscribe();
+        tokio::time::timeout(
+            Duration::from_secs(10),
+            updates.wait_for(|status| status.state.is_finished()),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+        let status = body(handler_job(Path(id.clone()), State(state.clone())).await).await;
+        assert_eq!(status["state"], "completed");
+        assert_eq!(status["completed"], 2);
+        assert_eq!(status["errors"], 1);
+        let result = body(handler_job_result(Path(id), State(state.clone())).await).await;
+        assert_eq!(result["entries"][0]["result"]["tags"]["p"]["count"], 2);
+        assert!(result["entries"][1]["error"].is_string());
+
+        let status = |response: Response| response.status();
+        assert_eq!(
+            status(handler_job(Path("nope".into()), State(state.clone())).await),
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            status(create(serde_json::json!({"kind": "crawl", "urls": []})).await),
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            status(create(serde_json::json!({"kind": "crawl", "urls": ["file:///etc"]})).await),
+            StatusCode::BAD_REQUEST
+        );
+    }
+
+    #[tokio::test]
+    async fn test_job_events() {
+        let base = serve_site().await;
+        let state = local_state();
+        let request = serde_json::from_value(serde_json::json!({
+            "kind": "batch",
+            "urls": [format!("{}/page", base), format!("{}/list", base)],
+        }))
+        .unwrap();
+        let events = start(&state, request, &FetchParams::default(), &HeaderMap::new()).unwrap();
+        let id = events.job().status().id;
+
+        let response = handler_job_events(Path(id), State(state.clone())).await;
+        assert_eq!(response.headers()["content-type"], "text/event-stream");
+        let bytes = tokio::time::timeout(
+            Duration::from_secs(10),
+            axum::body::to_bytes(response.into_body(), usize::MAX),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        let text = String::from_utf8(bytes.to_vec()).unwrap();
+        let names: Vec<_> = text
+            .lines()
+            .filter_map(|line| line.strip_prefix("event: "))
+            .collect();
+        assert_eq!(names.iter().filter(|&&name| name == "page").count(), 2);
+        assert_eq!(names.last(), Some(&"status"));
+        let last = text
+            .lines()
+            .rev()
+            .find_map(|line| line.strip_prefix("data: "))
+            .unwrap();
+        let last: serde_json::Value = serde_json::from_str(last).unwrap();
+        assert_eq!(last["state"], "completed");
+        assert_eq!(last["completed"], 2);
+
+        assert_eq!(
+            handler_job_events(Path("nope".into()), State(state))
+                .await
+                .status(),
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    #[test]
+    fn test_jobs_eviction() {
+        let jobs = Jobs::new(&ServerConfig {
+            max_jobs: 2,
+            ..ServerConfig::default()
+        });
+        let first = jobs.insert(None, JobKind::Batch, 1).unwrap();
+        let second = jobs.insert(None, JobKind::Batch, 1).unwrap();
+        assert_ne!(first.status().id, second.status().id);
+        // Both unfinished
+        assert!(jobs.insert(None, JobKind::Batch, 1).is_none());
+
+        second.finish(Err(anyhow::anyhow!("boom")));
+        let third = jobs.insert(None, JobKind::Crawl, 1).unwrap();
+        assert!(jobs.get(&first.status().id).is_some());
+        assert!(jobs.get(&second.status().id).is_none());
+        assert_eq!(third.status().state, JobState::Queued);
+        assert_eq!(second.status().error.as_deref(), Some("boom"));
+    }
+}
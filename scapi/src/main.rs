@@ -1,219 +1,1053 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use axum::{
-    extract::{Path, Query},
-    http::StatusCode,
+    body::Bytes,
+    extract::{DefaultBodyLimit, Path, Query, State},
+    http::{
+        header::{ACCEPT, AGE, CACHE_CONTROL, CONTENT_DISPOSITION, CONTENT_TYPE, VARY},
+        HeaderMap, HeaderValue, StatusCode,
+    },
+    middleware,
     response::{IntoResponse, Response},
-    routing::get,
+    routing::{get, post},
     Json, Router,
 };
 use serde::Deserialize;
-use std::io::Read;
+use std::collections::BTreeSet;
+use std::future::Future;
 use std::net::SocketAddr;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use tokio::time::error::Elapsed;
+use tower_http::compression::predicate::{DefaultPredicate, NotForContentType, Predicate};
+use tower_http::compression::CompressionLayer;
 use tower_http::cors::{Any, CorsLayer};
+use tower_http::services::ServeDir;
 
-use ferret::analyzer::{AnalysisResult, Analyzer, StatsAnalyzer};
-use ferret::exporter::{CsvExporter, Exporter, GraphVisualizerExporter, HtmlTreeExporter};
+use ferret::analyzer::section::Section;
+use ferret::analyzer::stream::StreamAnalyzer;
+use ferret::analyzer::{AnalysisResult, AnalysisResultSet, Analyzer, SourceResult, StatsAnalyzer};
+use ferret::config::Config;
+use ferret::error::FerretError;
+use ferret::exporter::registry;
+use ferret::fetch::{FetchOptions, Fetched};
+use ferret::limits::Limits;
 use ferret::parser::FerretParser;
-use ferret::reporter::{FlatDisplay, TreeDisplay};
+use ferret::progress::{Progress, ProgressEvent};
+use ferret::reporter::{reporter, RenderOptions};
+use ferret::sink::Sink;
 use ferret::walker::DomWalker;
-use indicatif::{ProgressBar, ProgressStyle};
+
+/// Request header whose value is sent as `Authorization` to the target URL
+const TARGET_AUTHORIZATION: &str = "x-target-authorization";
+/// Headers never forwarded to the target URL, whatever
+/// `server.forward_headers` says, as they describe the connection to this
+/// server
+const UNFORWARDED_HEADERS: [&str; 10] = [
+    "host",
+    "connection",
+    "keep-alive",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+    "content-length",
+    "proxy-authorization",
+    TARGET_AUTHORIZATION,
+];
+/// Most frequent values a report may keep per attribute
+const MAX_TOP_VALUES: usize = 1000;
+
+mod admin;
+mod cache;
+mod diff;
+mod export;
+mod fields;
+mod history;
+mod jobs;
+mod logging;
+mod metrics;
+mod notifiers;
+mod proxy;
+mod rate_limit;
+mod schedules;
+mod shutdown;
+mod webhooks;
+mod ws;
+
+use cache::{CacheKey, ResultCache};
+use fields::Fields;
+use history::History;
+use jobs::Jobs;
+use metrics::Metrics;
+use rate_limit::RateLimiter;
+use schedules::Schedules;
+
+/// Settings shared by all handlers, loaded from `ferret.toml` and `FERRET_*`
+/// environment variables (see `ferret::config`), and the background jobs
+#[derive(Clone, Default)]
+struct AppState {
+    config: Arc<Config>,
+    jobs: Arc<Jobs>,
+    rate_limiter: Arc<RateLimiter>,
+    cache: Arc<ResultCache>,
+    history: Arc<History>,
+    schedules: Arc<Schedules>,
+    metrics: Arc<Metrics>,
+    /// Where the pages of crawl jobs are published, if `sink.url` is set
+    sink: Option<Sink>,
+    /// Every fetch goes through it, so connections and TLS sessions are
+    /// reused across requests
+    client: reqwest::Client,
+}
+
+impl AppState {
+    /// State keeping the history in memory, see [`with_history`](Self::with_history)
+    ///
+    /// Fails if the fetch settings, e.g. the proxy, are invalid.
+    fn new(config: Config) -> Result<Self> {
+        Ok(Self {
+            client: config
+                .fetch
+                .options()
+                .host_policy(config.server.host_policy())
+                .build_client()?,
+            jobs: Arc::new(Jobs::new(&config.server)),
+            rate_limiter: Arc::new(RateLimiter::new(config.server.rate_limit)),
+            cache: Arc::new(ResultCache::new(
+                Duration::from_secs(config.server.cache_ttl_secs),
+                config.server.cache_entries,
+            )),
+            history: Arc::new(History::new(config.server.max_history_runs)),
+            schedules: Arc::default(),
+            metrics: Arc::default(),
+            sink: None,
+            config: Arc::new(config),
+        })
+    }
+
+    fn with_history(mut self, history: History) -> Self {
+        self.history = Arc::new(history);
+        self
+    }
+
+    fn with_sink(mut self, sink: Sink) -> Self {
+        self.sink = Some(sink);
+        self
+    }
+
+    /// How long fetching a page may take, body included; zero for no limit
+    fn fetch_timeout(&self) -> Duration {
+        Duration::from_secs(self.config.server.fetch_timeout_secs)
+    }
+
+    /// The configured limits, falling back to `Limits::untrusted()` for
+    /// each limit left unset
+    fn limits(&self) -> Limits {
+        let configured = self.config.analyzer.limits;
+        let untrusted = Limits::untrusted();
+        Limits {
+            max_input_bytes: configured.max_input_bytes.or(untrusted.max_input_bytes),
+            max_nodes: configured.max_nodes.or(untrusted.max_nodes),
+            max_depth: configured.max_depth.or(untrusted.max_depth),
+            max_parse_errors: configured.max_parse_errors.or(untrusted.max_parse_errors),
+        }
+    }
+
+    /// The response refusing `target_url`, if it may not be fetched
+    fn reject(&self, target_url: &str) -> Option<Response> {
+        self.check_url(target_url)
+            .err()
+            .map(IntoResponse::into_response)
+    }
+
+    /// Why `target_url` may not be fetched, if it may not
+    fn check_url(&self, target_url: &str) -> Result<(), (StatusCode, &'static str)> {
+        if !target_url.starts_with("http://") && !target_url.starts_with("https://") {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "Invalid URL format. Expected http://... or https://...",
+            ));
+        }
+        if !self.config.server.allows(target_url) {
+            return Err((StatusCode::FORBIDDEN, "Host is not allowed"));
+        }
+        Ok(())
+    }
+}
 
 #[derive(Deserialize)]
 struct ReportParams {
     format: Option<String>,
+    /// Add tag and value shares, e.g. `?percentages=true`
+    #[serde(default)]
+    percentages: bool,
+    /// Fetch the page even if its analysis is cached, e.g. `?refresh=true`
+    #[serde(default)]
+    refresh: bool,
+    /// Comma-separated dotted paths the JSON report is limited to, e.g.
+    /// `?fields=tags.count,max_depth` (see `fields`)
+    fields: Option<String>,
+    /// Only the tag counts, e.g. `?summary=true`, with any `fields`
+    #[serde(default)]
+    summary: bool,
+    #[serde(flatten)]
+    analysis: AnalysisParams,
+    #[serde(flatten)]
+    fetch: FetchParams,
+}
+
+impl ReportParams {
+    /// The fields of the JSON report, or `None` for all of them
+    fn fields(&self) -> Result<Option<Fields>, (StatusCode, String)> {
+        let invalid = |message| (StatusCode::BAD_REQUEST, message);
+        let mut fields = match &self.fields {
+            Some(list) => Some(Fields::parse(list).map_err(invalid)?),
+            None => None,
+        };
+        if self.summary {
+            let summary = Fields::parse(fields::SUMMARY).map_err(invalid)?;
+            fields.get_or_insert_with(Fields::default).merge(summary);
+        }
+        if fields.is_some() && self.format.as_deref().and_then(reporter).is_some() {
+            return Err(invalid(
+                "fields and summary only apply to JSON reports".to_string(),
+            ));
+        }
+        Ok(fields)
+    }
+}
+
+/// Query parameters of `GET /api/report` choosing how the page is analyzed,
+/// e.g. `?analyzers=stats,images&top_values=25&include_tags=a,img`
+#[derive(Default, Deserialize)]
+struct AnalysisParams {
+    /// Comma-separated: `stats`, always run, `structure` for the counts of
+    /// tags inside other tags, and section names (see
+    /// `ferret::analyzer::section`)
+    analyzers: Option<String>,
+    /// Most frequent values kept per attribute [default: analyzer.top_values]
+    #[serde(default, deserialize_with = "parse_opt")]
+    top_values: Option<usize>,
+    /// Comma-separated tag names the report is limited to
+    include_tags: Option<String>,
+    /// Refuse pages nested deeper, if lower than `analyzer.limits.max_depth`
+    #[serde(default, deserialize_with = "parse_opt")]
+    max_depth: Option<usize>,
+}
+
+impl AnalysisParams {
+    fn to_spec(&self, state: &AppState) -> Result<AnalysisSpec, (StatusCode, String)> {
+        let mut spec = AnalysisSpec::new(state);
+        if let Some(top_values) = self.top_values {
+            if top_values > MAX_TOP_VALUES {
+                let message = format!("top_values must be at most {}", MAX_TOP_VALUES);
+                return Err((StatusCode::BAD_REQUEST, message));
+            }
+            spec.top_values = top_values;
+        }
+        for name in split_list(self.analyzers.as_deref()) {
+            match name.as_str() {
+                "stats" => {}
+                "structure" => spec.structure = true,
+                name => {
+                    let section = name.parse::<Section>().map_err(|_| {
+                        let names = Section::ALL.map(Section::name).join(", ");
+                        let message = format!(
+                            "Unknown analyzer {:?}, expected stats, structure, {}",
+                            name, names
+                        );
+                        (StatusCode::BAD_REQUEST, message)
+                    })?;
+                    spec.sections.push(section);
+                }
+            }
+        }
+        spec.sections.sort_by_key(|section| section.name());
+        spec.sections.dedup();
+        spec.max_depth = self.max_depth;
+        Ok(spec)
+    }
+
+    /// Tag names the report keeps, or `None` for all of them
+    fn include_tags(&self) -> Option<BTreeSet<String>> {
+        self.include_tags.as_ref()?;
+        Some(split_list(self.include_tags.as_deref()).collect())
+    }
+}
+
+/// The lowercased, non-empty items of a comma-separated list
+fn split_list(list: Option<&str>) -> impl Iterator<Item = String> + '_ {
+    list.unwrap_or("")
+        .split(',')
+        .map(|item| item.trim().to_ascii_lowercase())
+        .filter(|item| !item.is_empty())
+}
+
+/// How a page is analyzed, as part of its cache key
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct AnalysisSpec {
+    top_values: usize,
+    structure: bool,
+    /// Sorted by name, without duplicates
+    sections: Vec<Section>,
+    max_depth: Option<usize>,
+}
+
+impl AnalysisSpec {
+    /// Tag statistics only, as configured
+    fn new(state: &AppState) -> Self {
+        Self {
+            top_values: state.config.analyzer.top_values,
+            structure: false,
+            sections: Vec::new(),
+            max_depth: None,
+        }
+    }
+
+    /// The server's limits, with `max_depth` if lower
+    fn limits(&self, state: &AppState) -> Limits {
+        let mut limits = state.limits();
+        if let Some(max_depth) = self.max_depth {
+            limits.max_depth = Some(limits.max_depth.map_or(max_depth, |max| max.min(max_depth)));
+        }
+        limits
+    }
+}
+
+/// Query parameters of `POST /api/batch`
+#[derive(Deserialize)]
+struct BatchParams {
+    /// URLs fetched at the same time, at most `server.batch_concurrency`
+    concurrency: Option<usize>,
+    #[serde(flatten)]
+    fetch: FetchParams,
+}
+
+/// Query parameters of `POST /api/analyze`
+#[derive(Deserialize)]
+struct AnalyzeParams {
+    format: Option<String>,
+    #[serde(default)]
+    percentages: bool,
+}
+
+/// JSON body of `POST /api/analyze`
+#[derive(Deserialize)]
+struct AnalyzeBody {
+    html: String,
 }
 
 #[derive(Deserialize)]
 struct ExportParams {
     format: Option<String>,
+    #[serde(flatten)]
+    fetch: FetchParams,
+}
+
+/// Query parameters of `POST /api/export/batch`
+#[derive(Deserialize)]
+struct ExportBatchParams {
+    format: Option<String>,
+    /// URLs fetched at the same time, at most `server.batch_concurrency`
+    concurrency: Option<usize>,
+    #[serde(flatten)]
+    fetch: FetchParams,
+}
+
+/// Options for fetching the target URL
+#[derive(Default, Deserialize)]
+struct FetchParams {
+    user_agent: Option<String>,
+    /// Raw cookie string, e.g. `a=1; b=2`
+    cookie: Option<String>,
+    /// `0` disables following redirects
+    #[serde(default, deserialize_with = "parse_opt")]
+    max_redirects: Option<usize>,
+}
+
+/// An optional query parameter parsed from its text
+///
+/// Needed for numbers in structs flattened into others, which get every
+/// value as a string.
+fn parse_opt<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    let value: Option<String> = Option::deserialize(deserializer)?;
+    value
+        .map(|value| value.trim().parse().map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+impl FetchParams {
+    fn to_options(&self, headers: &HeaderMap, state: &AppState) -> FetchOptions {
+        let mut options = state
+            .config
+            .fetch
+            .options()
+            .host_policy(state.config.server.host_policy())
+            .client(state.client.clone());
+        // Bounds each request too; see `within` for the whole fetch
+        let fetch_timeout = state.fetch_timeout();
+        if !fetch_timeout.is_zero() && options.timeout.is_none_or(|t| t > fetch_timeout) {
+            options = options.timeout(fetch_timeout);
+        }
+        // Query parameters and `X-Target-Authorization` take precedence
+        for (name, value) in forwarded_headers(headers, state) {
+            options = match name.as_str() {
+                "user-agent" => options.user_agent(value),
+                "cookie" => options.cookie_header(&value),
+                "authorization" if target_authorization(headers).is_some() => options,
+                _ => options.header(name, value),
+            };
+        }
+        if let Some(user_agent) = &self.user_agent {
+            options = options.user_agent(user_agent);
+        }
+        if let Some(cookie) = &self.cookie {
+            options = options.cookie_header(cookie);
+        }
+        if let Some(max_redirects) = self.max_redirects {
+            options = options.max_redirects(max_redirects);
+        }
+        if let Some(auth) = target_authorization(headers) {
+            options = options.header("Authorization", auth);
+        }
+        options
+    }
+
+    /// Whether the request passes credentials on to the target URL: a
+    /// cookie, `X-Target-Authorization` or forwarded headers, which may be
+    /// any
+    fn has_credentials(&self, headers: &HeaderMap, state: &AppState) -> bool {
+        self.cookie.is_some()
+            || target_authorization(headers).is_some()
+            || !forwarded_headers(headers, state).is_empty()
+    }
+
+    /// Identifies the analysis of `url` fetched with these options and
+    /// analyzed as `analysis` says
+    fn cache_key(
+        &self,
+        url: &str,
+        headers: &HeaderMap,
+        state: &AppState,
+        analysis: AnalysisSpec,
+    ) -> CacheKey {
+        CacheKey {
+            url: url.to_string(),
+            analysis,
+            user_agent: self.user_agent.clone(),
+            cookie: self.cookie.clone(),
+            max_redirects: self.max_redirects,
+            authorization: target_authorization(headers).map(str::to_string),
+            forwarded: forwarded_headers(headers, state),
+        }
+    }
+}
+
+fn target_authorization(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(TARGET_AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+}
+
+/// The request headers named by `server.forward_headers`, as lowercase
+/// names and values, sorted
+fn forwarded_headers(headers: &HeaderMap, state: &AppState) -> Vec<(String, String)> {
+    let mut forwarded: Vec<_> = state
+        .config
+        .server
+        .forward_headers
+        .iter()
+        .map(|name| name.trim().to_ascii_lowercase())
+        .filter(|name| !UNFORWARDED_HEADERS.contains(&name.as_str()))
+        .flat_map(|name| {
+            headers
+                .get_all(name.as_str())
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .map(|value| (name.clone(), value.to_string()))
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    forwarded.sort();
+    forwarded.dedup();
+    forwarded
 }
 
 async fn handler_report(
     Path(target_url): Path<String>,
     Query(params): Query<ReportParams>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
 ) -> impl IntoResponse {
     // Reconstruct URL if needed (axum *path wildcard matches the rest of the path including slashes)
     // However, if the user passes `api/report/https://example.com`, `target_url` will be `https://example.com`.
     // Validating URL scheme.
-    if !target_url.starts_with("http://") && !target_url.starts_with("https://") {
-        return (
-            StatusCode::BAD_REQUEST,
-            "Invalid URL format. Expected http://... or https://...",
-        )
-            .into_response();
+    if let Some(response) = state.reject(&target_url) {
+        return response;
     }
 
-    let pb = ProgressBar::new_spinner();
-    pb.set_style(
-        ProgressStyle::default_spinner()
-            .template("{spinner:.green} {msg}")
-            .unwrap()
-            .tick_chars("⠋⠙⠹⠸⠼⠴⠦⠧⠇⠏"),
-    );
-    pb.enable_steady_tick(std::time::Duration::from_millis(100));
-
-    pb.set_message(format!("Fetching {}", target_url));
-    let body_str = match reqwest::get(&target_url).await {
-        Ok(resp) => match resp.text().await {
-            Ok(text) => text,
+    let spec = match params.analysis.to_spec(&state) {
+        Ok(spec) => spec,
+        Err(refused) => return refused.into_response(),
+    };
+    let fields = match params.fields() {
+        Ok(fields) => fields,
+        Err(refused) => return refused.into_response(),
+    };
+    let key = params.fetch.cache_key(&target_url, &headers, &state, spec);
+    let (mut analysis_result, age) =
+        match cached_analysis(&state, &key, &params.fetch, &headers, params.refresh).await {
+            Ok(analysis) => analysis,
+            Err(response) => return response,
+        };
+    if let Some(tags) = params.analysis.include_tags() {
+        Arc::make_mut(&mut analysis_result)
+            .tags
+            .retain(|name, _| tags.contains(name));
+    }
+    if params.percentages {
+        Arc::make_mut(&mut analysis_result).add_percentages();
+    }
+
+    let mut response = match fields {
+        Some(fields) => match serde_json::to_value(&*analysis_result) {
+            Ok(report) => Json(fields.project(report)).into_response(),
             Err(e) => {
-                pb.finish_with_message("Fetch failed");
-                return (
-                    StatusCode::BAD_REQUEST,
-                    format!("Failed to read body: {}", e),
-                )
-                    .into_response();
+                return error_response(StatusCode::INTERNAL_SERVER_ERROR, "Report error", e.into())
             }
         },
-        Err(err) => {
-            pb.finish_with_message("Fetch failed");
-            let code = err.status().map(|s| s.as_u16()).unwrap_or(400);
-            return (
-                StatusCode::from_u16(code).unwrap_or(StatusCode::BAD_REQUEST),
-                format!("Proxy error: {}", err),
-            )
-                .into_response();
+        None => report_response(
+            &analysis_result,
+            params.format.as_deref(),
+            params.percentages,
+        ),
+    };
+    let ttl = state.cache.ttl();
+    if !ttl.is_zero() {
+        let scope = if key.is_private() {
+            "private"
+        } else {
+            "public"
+        };
+        let max_age = ttl.saturating_sub(age).as_secs();
+        let headers = response.headers_mut();
+        headers.insert(
+            CACHE_CONTROL,
+            HeaderValue::from_str(&format!("{}, max-age={}", scope, max_age)).unwrap(),
+        );
+        headers.insert(AGE, HeaderValue::from(age.as_secs()));
+    }
+    response
+}
+
+/// The analysis of the page `key` names and its age, from the cache unless
+/// it has none or `refresh` is set
+///
+/// Only analyses made as [`AnalysisSpec::new`] are recorded in the history,
+/// so runs of a URL stay comparable, and never those of pages fetched with
+/// the user's credentials.
+async fn cached_analysis(
+    state: &AppState,
+    key: &CacheKey,
+    fetch: &FetchParams,
+    headers: &HeaderMap,
+    refresh: bool,
+) -> Result<(Arc<AnalysisResult>, Duration), Response> {
+    let cached = match refresh {
+        true => None,
+        false => state.cache.get(key, Instant::now()),
+    };
+    if let Some(cached) = cached {
+        state.metrics.cache_hits.fetch_add(1, Ordering::Relaxed);
+        return Ok(cached);
+    }
+    state.metrics.cache_misses.fetch_add(1, Ordering::Relaxed);
+    let result = fetch_analysis(&key.url, fetch, &key.analysis, state, headers).await?;
+    if key.analysis == AnalysisSpec::new(state) && !key.is_private() {
+        state.record(&key.url, &result);
+    }
+    let result = Arc::new(result);
+    state
+        .cache
+        .insert(key.clone(), Arc::clone(&result), Instant::now());
+    Ok((result, Duration::ZERO))
+}
+
+/// Fetch and analyze a page for `GET /api/report` or `GET /api/diff`
+async fn fetch_analysis(
+    target_url: &str,
+    params: &FetchParams,
+    spec: &AnalysisSpec,
+    state: &AppState,
+    headers: &HeaderMap,
+) -> Result<AnalysisResult, Response> {
+    let limits = spec.limits(state);
+    let options = params.to_options(headers, state);
+    if spec.structure || !spec.sections.is_empty() {
+        // Sections need the page URL and response headers, which only the
+        // stream analyzer has; it fetches the page as it parses it
+        let analyzer = StreamAnalyzer::new(spec.top_values)
+            .with_limits(limits)
+            .with_fetch_options(options)
+            .with_structure(spec.structure)
+            .with_sections(spec.sections.iter().copied());
+        let start = Instant::now();
+        let result = within(state.fetch_timeout(), analyzer.analyze_url(target_url)).await;
+        state.metrics.analysis.observe(start.elapsed());
+        return result
+            .and_then(|result| result)
+            .map_err(|e| error_response(StatusCode::BAD_REQUEST, "Analysis error", e));
+    }
+
+    let start = Instant::now();
+    let fetched = within(state.fetch_timeout(), async {
+        match fetch(target_url, &options).await {
+            Ok(fetched) => match read_body(fetched.response, &limits).await {
+                Ok(text) => Ok((text, fetched.redirects)),
+                Err(e) => Err(error_response(
+                    StatusCode::BAD_REQUEST,
+                    "Failed to read body",
+                    e,
+                )),
+            },
+            Err(err) => {
+                let status = err
+                    .downcast_ref::<reqwest::Error>()
+                    .and_then(|e| e.status())
+                    .and_then(|s| StatusCode::from_u16(s.as_u16()).ok())
+                    .unwrap_or(StatusCode::BAD_REQUEST);
+                Err(error_response(status, "Proxy error", err))
+            }
+        }
+    })
+    .await;
+    state.metrics.fetch.observe(start.elapsed());
+    let (body_str, redirects) = match fetched {
+        Ok(fetched) => fetched?,
+        Err(e) => {
+            return Err(error_response(
+                StatusCode::GATEWAY_TIMEOUT,
+                "Proxy error",
+                e,
+            ))
         }
     };
 
-    pb.set_message("Analyzing HTML...");
     // Ferret Analysis
-    let analysis_result = match analyze_html(&body_str) {
-        Ok(result) => {
-            pb.finish_with_message(format!("Analysis complete for {}", target_url));
-            result
+    let top_values = spec.top_values;
+    match (state.metrics.analysis).time(|| analyze_html(&body_str, &limits, top_values)) {
+        Ok(mut result) => {
+            result.redirects = redirects;
+            Ok(result)
         }
-        Err(e) => {
-            pb.finish_with_message("Analysis failed");
+        Err(e) => Err(error_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Analysis error",
+            e,
+        )),
+    }
+}
+
+/// Analyze HTML sent in the request body, as `text/html` or as JSON
+/// `{"html": "..."}`, e.g. a rendered DOM or an email body
+async fn handler_analyze(
+    Query(params): Query<AnalyzeParams>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let limits = state.limits();
+    if let Err(e) = limits.check_input(body.len()) {
+        return error_response(StatusCode::BAD_REQUEST, "Invalid body", e.into());
+    }
+    let content_type = headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .map(|value| value.trim().to_ascii_lowercase());
+    let html = match content_type.as_deref() {
+        Some("application/json") => match serde_json::from_slice::<AnalyzeBody>(&body) {
+            Ok(body) => body.html,
+            Err(e) => {
+                return (StatusCode::BAD_REQUEST, format!("Invalid JSON body: {}", e))
+                    .into_response()
+            }
+        },
+        None | Some("text/html" | "text/plain" | "application/xhtml+xml") => {
+            String::from_utf8_lossy(&body).into_owned()
+        }
+        Some(other) => {
             return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Analysis error: {}", e),
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                format!(
+                    "Unsupported content type '{}'. Expected text/html or application/json",
+                    other
+                ),
             )
-                .into_response();
+                .into_response()
         }
     };
 
-    match params.format.as_deref() {
-        Some("tree") => {
-            let report = TreeDisplay::render(&analysis_result);
-            Response::builder()
-                .header("Content-Type", "text/plain")
-                .body(axum::body::Body::from(report))
-                .unwrap()
-                .into_response()
+    let top_values = state.config.analyzer.top_values;
+    let mut result = match (state.metrics.analysis)
+        .time(|| analyze_html(&html, &limits, top_values))
+    {
+        Ok(result) => result,
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, "Analysis error", e),
+    };
+    if params.percentages {
+        result.add_percentages();
+    }
+    report_response(&result, params.format.as_deref(), params.percentages)
+}
+
+/// Fetch and analyze each URL of a JSON array, a few at a time, into a
+/// result set with one entry per URL, in request order, and their
+/// aggregate
+///
+/// URLs that fail to load or may not be fetched get an entry with the
+/// error rather than failing the batch.
+async fn handler_batch(
+    Query(params): Query<BatchParams>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(urls): Json<Vec<String>>,
+) -> Response {
+    let server = &state.config.server;
+    if urls.len() > server.max_batch_urls {
+        return (
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!(
+                "Too many URLs: {} (at most {})",
+                urls.len(),
+                server.max_batch_urls
+            ),
+        )
+            .into_response();
+    }
+    let concurrency = params
+        .concurrency
+        .unwrap_or(server.batch_concurrency)
+        .clamp(1, server.batch_concurrency.max(1));
+    let options = params.fetch.to_options(&headers, &state);
+    let record = !params.fetch.has_credentials(&headers, &state);
+    match run_batch(&state, options, urls, concurrency, record, None).await {
+        Ok(set) => Json(set).into_response(),
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, "Batch error", e),
+    }
+}
+
+/// Fetch and analyze `urls`, `concurrency` at a time, reporting each
+/// finished URL to `progress`
+///
+/// The analyses go in the history if `record` is set, which it mustn't be
+/// for pages fetched with the user's credentials.
+///
+/// Fails only if no client can be built; URLs that fail to load or may
+/// not be fetched get an entry with the error.
+async fn run_batch(
+    state: &AppState,
+    options: FetchOptions,
+    urls: Vec<String>,
+    concurrency: usize,
+    record: bool,
+    progress: Option<&Progress>,
+) -> Result<AnalysisResultSet> {
+    let client = options.build_client()?;
+    let limits = state.limits();
+    let top_values = state.config.analyzer.top_values;
+
+    let semaphore = Arc::new(Semaphore::new(concurrency.max(1)));
+    let mut tasks = JoinSet::new();
+    for (index, url) in urls.iter().enumerate() {
+        if let Err((_, reason)) = state.check_url(url) {
+            let error = anyhow::anyhow!(reason);
+            tasks.spawn(async move { (index, Err(error)) });
+            continue;
         }
-        Some("flat") => {
-            let report = FlatDisplay::render(&analysis_result);
+        let (url, options, client) = (url.clone(), options.clone(), client.clone());
+        let (semaphore, metrics) = (semaphore.clone(), state.metrics.clone());
+        let timeout = state.fetch_timeout();
+        tasks.spawn(async move {
+            let _permit = semaphore.acquire_owned().await;
+            let result = analyze_page(
+                &options, &client, &url, &limits, top_values, timeout, &metrics,
+            )
+            .await;
+            (index, result)
+        });
+    }
+
+    let mut results: Vec<Option<Result<AnalysisResult>>> = urls.iter().map(|_| None).collect();
+    let mut completed = 0;
+    while let Some(joined) = tasks.join_next().await {
+        let (index, result) = joined?;
+        completed += 1;
+        if let (true, Ok(result)) = (record, &result) {
+            state.record(&urls[index], result);
+        }
+        if let Some(progress) = progress {
+            progress.report(ProgressEvent::SourceCompleted {
+                source: urls[index].clone(),
+                completed,
+                total: urls.len(),
+                error: result.as_ref().err().map(|e| format!("{:#}", e)),
+                tags: result
+                    .as_ref()
+                    .map(AnalysisResult::tag_counts)
+                    .unwrap_or_default(),
+            });
+        }
+        results[index] = Some(result);
+    }
+    let entries = urls.into_iter().zip(results).map(|(url, result)| {
+        let result = result.unwrap_or_else(|| Err(anyhow::anyhow!("Not analyzed")));
+        SourceResult::new(url, result)
+    });
+    Ok(AnalysisResultSet::from_entries(entries, top_values))
+}
+
+/// `result` as the text report `format`, or as JSON if it names none
+fn report_response(result: &AnalysisResult, format: Option<&str>, percentages: bool) -> Response {
+    match format.and_then(reporter) {
+        Some(reporter) => {
+            // Escape codes would end up verbatim in the response body
+            let options = RenderOptions::default()
+                .with_color(false)
+                .with_percentages(percentages);
+            let report = reporter.render(result, &options);
             Response::builder()
                 .header("Content-Type", "text/plain")
                 .body(axum::body::Body::from(report))
                 .unwrap()
                 .into_response()
         }
-        _ => Json(analysis_result).into_response(),
+        None => Json(result).into_response(),
     }
 }
 
+/// The report of the target URL in the format picked by [`export::negotiate`]
 async fn handler_export(
     Path(target_url): Path<String>,
     Query(params): Query<ExportParams>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
 ) -> impl IntoResponse {
-    if !target_url.starts_with("http://") && !target_url.starts_with("https://") {
-        return (
-            StatusCode::BAD_REQUEST,
-            "Invalid URL format. Expected http://... or https://...",
-        )
-            .into_response();
+    if let Some(response) = state.reject(&target_url) {
+        return response;
     }
+    // Refused before fetching anything
+    let accept = headers.get(ACCEPT).and_then(|accept| accept.to_str().ok());
+    let format = match export::negotiate(
+        &registry(),
+        params.format.as_deref(),
+        accept,
+        &state.config.export.format,
+    ) {
+        Ok(format) => format,
+        Err(refused) => return refused.into_response(),
+    };
 
-    let pb = ProgressBar::new_spinner();
-    pb.set_style(
-        ProgressStyle::default_spinner()
-            .template("{spinner:.green} {msg}")
-            .unwrap(),
-    );
-    pb.enable_steady_tick(std::time::Duration::from_millis(100));
+    let limits = state.limits();
+    let options = params.fetch.to_options(&headers, &state);
 
-    pb.set_message(format!("Fetching {}", target_url));
-    let body_str = match reqwest::get(&target_url).await {
-        Ok(resp) => resp.text().await.unwrap_or_default(),
-        Err(e) => return (StatusCode::BAD_REQUEST, format!("Fetch error: {}", e)).into_response(),
+    let start = Instant::now();
+    let fetched = within(state.fetch_timeout(), async {
+        let fetched = fetch(&target_url, &options).await?;
+        read_body(fetched.response, &limits).await
+    })
+    .await
+    .and_then(|fetched| fetched);
+    state.metrics.fetch.observe(start.elapsed());
+    let body_str = match fetched {
+        Ok(text) => text,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, "Fetch error", e),
     };
 
-    pb.set_message("Analyzing HTML...");
-    let analysis_result = match analyze_html(&body_str) {
-        Ok(res) => {
-            pb.finish_with_message("Done");
-            res
-        }
-        Err(e) => {
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Analysis error: {}", e),
-            )
-                .into_response()
-        }
+    let top_values = state.config.analyzer.top_values;
+    let analysis_result = match (state.metrics.analysis)
+        .time(|| analyze_html(&body_str, &limits, top_values))
+    {
+        Ok(res) => res,
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, "Analysis error", e),
     };
 
-    let (exporter, content_type, extension): (Box<dyn Exporter>, &str, &str) =
-        match params.format.as_deref() {
-            Some("csv") => (Box::new(CsvExporter), "text/csv", "csv"),
-            Some("html") => (Box::new(HtmlTreeExporter), "text/html", "html"),
-            Some("graph") => (Box::new(GraphVisualizerExporter), "text/html", "html"),
-            _ => (Box::new(CsvExporter), "text/csv", "csv"),
-        };
+    // The source labels Prometheus scrapes, like the blackbox exporter's `/probe`
+    let exporter = (format.create)(Some(&target_url));
+    let extension = format.extensions.first().copied().unwrap_or("html");
 
-    match tempfile::NamedTempFile::new() {
-        Ok(temp_file) => {
-            let temp_path = temp_file.path().to_owned();
-
-            if let Err(e) = exporter.export(&analysis_result, &temp_path) {
-                return (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    format!("Export error: {}", e),
-                )
-                    .into_response();
-            }
+    Response::builder()
+        .header(CONTENT_TYPE, format.content_type)
+        .header(
+            CONTENT_DISPOSITION,
+            format!("inline; filename=\"report.{}\"", extension),
+        )
+        .header(VARY, "Accept")
+        .body(export::stream(exporter, analysis_result))
+        .unwrap()
+        .into_response()
+}
 
-            let mut content = String::new();
-            if let Ok(mut file) = std::fs::File::open(&temp_path) {
-                if let Err(e) = file.read_to_string(&mut content) {
-                    return (
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        format!("File read error: {}", e),
-                    )
-                        .into_response();
-                }
-            } else {
-                return (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    "Failed to open temp file",
-                )
-                    .into_response();
-            }
+/// Fetch and analyze each URL of a JSON array, like `POST /api/batch`,
+/// into a zip archive of their reports in `?format=`, see
+/// [`export::bundle`]
+async fn handler_export_batch(
+    Query(params): Query<ExportBatchParams>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(urls): Json<Vec<String>>,
+) -> Response {
+    let server = &state.config.server;
+    if urls.len() > server.max_batch_urls {
+        return (
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!(
+                "Too many URLs: {} (at most {})",
+                urls.len(),
+                server.max_batch_urls
+            ),
+        )
+            .into_response();
+    }
+    // `Accept` is about the archive, not the reports in it
+    let format = match export::negotiate(
+        &registry(),
+        params.format.as_deref(),
+        None,
+        &state.config.export.format,
+    ) {
+        Ok(format) => format,
+        Err(refused) => return refused.into_response(),
+    };
+    let concurrency = params
+        .concurrency
+        .unwrap_or(server.batch_concurrency)
+        .clamp(1, server.batch_concurrency.max(1));
+    let options = params.fetch.to_options(&headers, &state);
+    let record = !params.fetch.has_credentials(&headers, &state);
+    let set = match run_batch(&state, options, urls, concurrency, record, None).await {
+        Ok(set) => set,
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, "Batch error", e),
+    };
 
-            Response::builder()
-                .header("Content-Type", content_type)
-                .header(
-                    "Content-Disposition",
-                    format!("inline; filename=\"report.{}\"", extension),
-                )
-                .body(axum::body::Body::from(content))
-                .unwrap()
-                .into_response()
-        }
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Failed to create temp file: {}", e),
+    let bundled = tokio::task::spawn_blocking(move || export::bundle(format, &set)).await;
+    match bundled
+        .map_err(anyhow::Error::from)
+        .and_then(|bundled| bundled)
+    {
+        Ok(bundle) => (
+            [
+                (CONTENT_TYPE, "application/zip"),
+                (CONTENT_DISPOSITION, "attachment; filename=\"reports.zip\""),
+            ],
+            bundle,
         )
             .into_response(),
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, "Export error", e),
+    }
+}
+
+/// Fetch and analyze `url` within `timeout`, failing on HTTP error statuses
+async fn analyze_page(
+    options: &FetchOptions,
+    client: &reqwest::Client,
+    url: &str,
+    limits: &Limits,
+    top_values: usize,
+    timeout: Duration,
+    metrics: &Metrics,
+) -> Result<AnalysisResult> {
+    let start = Instant::now();
+    let fetched = within(timeout, async {
+        let fetched = options.send(client, url).await?;
+        if !fetched.response.status().is_success() {
+            anyhow::bail!("HTTP error: {}", fetched.response.status());
+        }
+        let body = read_body(fetched.response, limits).await?;
+        Ok((body, fetched.redirects))
+    })
+    .await;
+    metrics.fetch.observe(start.elapsed());
+    let (body, redirects) = fetched??;
+    let mut result = metrics
+        .analysis
+        .time(|| analyze_html(&body, limits, top_values))?;
+    result.redirects = redirects;
+    Ok(result)
+}
+
+/// `fetch` bounded by `timeout` unless it's zero, failing with
+/// [`Elapsed`] past it
+///
+/// Unlike the timeout of reqwest, which applies to each request, it bounds
+/// the whole fetch: retries, redirects and reading the body.
+async fn within<T>(timeout: Duration, fetch: impl Future<Output = T>) -> Result<T> {
+    if timeout.is_zero() {
+        return Ok(fetch.await);
+    }
+    tokio::time::timeout(timeout, fetch)
+        .await
+        .with_context(|| format!("Fetching took longer than {} s", timeout.as_secs()))
+}
+
+async fn fetch(url: &str, options: &FetchOptions) -> Result<Fetched> {
+    let client = options.build_client()?;
+    options.send(&client, url).await
+}
+
+/// Read a response body, aborting as soon as it exceeds `limits.max_input_bytes`
+async fn read_body(mut resp: reqwest::Response, limits: &Limits) -> Result<String> {
+    if let Some(length) = resp.content_length() {
+        limits.check_input(length as usize)?;
+    }
+
+    let mut body = Vec::new();
+    while let Some(chunk) = resp.chunk().await? {
+        body.extend_from_slice(&chunk);
+        limits.check_input(body.len())?;
     }
+    Ok(String::from_utf8_lossy(&body).into_owned())
 }
 
-fn analyze_html(html: &str) -> Result<AnalysisResult> {
-    let vdom = FerretParser::parse(html).map_err(|e| anyhow::anyhow!("Parse error: {:?}", e))?;
+/// Build an error response, reporting limit violations as 413 Payload Too
+/// Large, refused hosts as 403 Forbidden and fetches that took too long as
+/// 504 Gateway Timeout
+fn error_response(status: StatusCode, context: &str, err: anyhow::Error) -> Response {
+    let timed_out = err
+        .downcast_ref::<reqwest::Error>()
+        .is_some_and(reqwest::Error::is_timeout)
+        || err.downcast_ref::<Elapsed>().is_some();
+    let status = match err.downcast_ref::<FerretError>() {
+        _ if timed_out => StatusCode::GATEWAY_TIMEOUT,
+        Some(
+            FerretError::InputTooLarge { .. }
+            | FerretError::TooManyNodes { .. }
+            | FerretError::TooDeep { .. },
+        ) => StatusCode::PAYLOAD_TOO_LARGE,
+        Some(FerretError::UnsupportedContent(_)) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        Some(FerretError::HostNotAllowed { .. }) => StatusCode::FORBIDDEN,
+        Some(FerretError::ReadTimeout(_)) => StatusCode::GATEWAY_TIMEOUT,
+        _ => status,
+    };
+    match status.is_server_error() {
+        true => tracing::error!(status = status.as_u16(), "{}: {:#}", context, err),
+        false => tracing::debug!(status = status.as_u16(), "{}: {:#}", context, err),
+    }
+    (status, format!("{}: {}", context, err)).into_response()
+}
+
+fn analyze_html(html: &str, limits: &Limits, top_values: usize) -> Result<AnalysisResult> {
+    let vdom = FerretParser::parse_with_limits(html, limits)?;
     let walker = DomWalker::new(vdom.children().to_vec(), vdom.parser());
-    let mut analyzer = StatsAnalyzer::new(10);
+    let mut analyzer = StatsAnalyzer::new(top_values);
 
     for (_handle, node, depth) in walker {
         analyzer.visit(node, depth);
@@ -223,26 +1057,109 @@ fn analyze_html(html: &str) -> Result<AnalysisResult> {
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let port = std::env::var("PORT").unwrap_or_else(|_| "8080".to_string());
-    let port = port.parse::<u16>()?;
-    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+    let config = Config::load(None)?;
+    logging::init(&config.server)?;
+    let addr = SocketAddr::from(([0, 0, 0, 0], config.server.port));
+
+    tracing::info!("Ferret Axum Server listening on {}", addr);
+
+    let mut state = AppState::new(config)?;
+    if let Some(path) = &state.config.server.history_path {
+        let history = History::open(path, state.config.server.max_history_runs)?;
+        state = state.with_history(history);
+    }
+    if let Some(sink) = state.config.sink.connect().await? {
+        tracing::info!("Publishing crawled pages to {}", state.config.sink.subject);
+        let rules = state.config.server.alert_rules.clone();
+        state = state.with_sink(sink.with_rules(rules));
+    }
+    schedules::start_configured(&state)?;
+
+    shutdown::resume(&state)?;
+    match (&state.config.server.ui_dir, ui_dir(&state)) {
+        (_, Some(dir)) => tracing::info!("Serving the UI from {} at /", dir.display()),
+        (Some(dir), None) => tracing::info!(
+            "No UI at {}, build it with `npm run build` in frontend/",
+            dir.display()
+        ),
+        (None, None) => {}
+    }
 
-    println!("Ferret Axum Server listening on {}", addr);
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    shutdown::serve(listener, app(state.clone()), &state).await
+}
+
+fn app(state: AppState) -> Router {
+    // Bodies over the input limit are rejected by the handler with 413
+    let body_limit = state
+        .limits()
+        .max_input_bytes
+        .map_or(DefaultBodyLimit::disable(), |max| {
+            DefaultBodyLimit::max(max.saturating_add(1))
+        });
 
     let cors = CorsLayer::new()
         .allow_origin(Any)
         .allow_methods(Any)
         .allow_headers(Any);
 
-    let app = Router::new()
+    let router = Router::new()
         .route("/api/report/*url", get(handler_report))
         .route("/api/export/*url", get(handler_export))
-        .layer(cors);
+        .route("/api/export/batch", post(handler_export_batch))
+        .route("/api/proxy/*url", get(proxy::handler_proxy))
+        .route("/api/analyze", post(handler_analyze).layer(body_limit))
+        .route("/api/batch", post(handler_batch))
+        .route("/api/jobs", post(jobs::handler_create_job))
+        .route("/api/jobs/:id", get(jobs::handler_job))
+        .route("/api/jobs/:id/result", get(jobs::handler_job_result))
+        .route("/api/jobs/:id/events", get(jobs::handler_job_events))
+        .route("/api/ws", get(ws::handler_ws))
+        .route("/api/history", get(history::handler_history))
+        .route("/api/trend", get(history::handler_trend))
+        .route("/api/diff", get(diff::handler_diff))
+        .route("/api/schedules/:id", get(schedules::handler_schedule))
+        .nest("/api/admin", admin::router(&state))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            rate_limit::limit,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            metrics::track,
+        ))
+        .layer(middleware::from_fn(logging::trace))
+        // Probes and scrapes come often, from the cluster itself
+        .route("/healthz", get(metrics::handler_healthz))
+        .route("/readyz", get(metrics::handler_readyz))
+        .route("/metrics", get(metrics::handler_metrics));
+    // Whatever the API doesn't answer is looked up in the UI
+    let router = match ui_dir(&state) {
+        Some(dir) => router.fallback_service(ServeDir::new(dir)),
+        None => router,
+    };
+    router.layer(compression()).layer(cors).with_state(state)
+}
 
-    let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+/// `server.ui_dir`, if the UI was built there
+fn ui_dir(state: &AppState) -> Option<&std::path::Path> {
+    let dir = state.config.server.ui_dir.as_deref()?;
+    dir.is_dir().then_some(dir)
+}
 
-    Ok(())
+/// Gzip or Brotli, as the client accepts, for bodies over 32 bytes and
+/// streamed ones, except images, event streams and formats compressed
+/// already
+fn compression() -> CompressionLayer<impl Predicate> {
+    let predicate = DefaultPredicate::new()
+        .and(NotForContentType::const_new(
+            "application/vnd.openxmlformats",
+        ))
+        .and(NotForContentType::const_new(
+            "application/vnd.apache.parquet",
+        ))
+        .and(NotForContentType::const_new("application/zip"));
+    CompressionLayer::new().compress_when(predicate)
 }
 
 #[cfg(test)]
@@ -252,8 +1169,677 @@ mod tests {
     #[test]
     fn test_analyze_html_basic() {
         let html = r#"<html><body><h1>Hello</h1></body></html>"#;
-        let result = analyze_html(html).expect("Analysis failed");
+        let result = analyze_html(html, &Limits::untrusted(), 10).expect("Analysis failed");
         assert!(result.tags.contains_key("h1"));
         assert_eq!(result.tags.get("h1").unwrap().count, 1);
     }
+
+    #[test]
+    fn test_analyze_html_limits() {
+        let html = r#"<html><body><h1>Hello</h1></body></html>"#;
+        let limits = Limits {
+            max_nodes: Some(2),
+            ..Limits::default()
+        };
+        let err = analyze_html(html, &limits, 10).unwrap_err();
+        let response = error_response(StatusCode::INTERNAL_SERVER_ERROR, "Analysis error", err);
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn test_handler_analyze() {
+        let state = AppState::default();
+        let analyze = |content_type: Option<&str>, query: &str, body: &str| {
+            let mut headers = HeaderMap::new();
+            if let Some(content_type) = content_type {
+                headers.insert(CONTENT_TYPE, content_type.parse().unwrap());
+            }
+            let query =
+                Query::try_from_uri(&format!("/api/analyze?{}", query).parse().unwrap()).unwrap();
+            handler_analyze(
+                query,
+                State(state.clone()),
+                headers,
+                Bytes::from(body.to_string()),
+            )
+        };
+        let body = |response: Response| async {
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            String::from_utf8(bytes.to_vec()).unwrap()
+        };
+
+        let response = analyze(Some("text/html; charset=utf-8"), "", "<p>a</p><p>b</p>").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let json: serde_json::Value = serde_json::from_str(&body(response).await).unwrap();
+        assert_eq!(json["tags"]["p"]["count"], 2);
+
+        let response = analyze(
+            Some("application/json"),
+            "format=summary",
+            r#"{"html": "<ul><li>1</li></ul>"}"#,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(body(response).await.contains("Elements:"));
+
+        let status = |response: Response| response.status();
+        assert_eq!(
+            status(analyze(Some("application/json"), "", r#"{"url": "x"}"#).await),
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            status(analyze(Some("image/png"), "", "").await),
+            StatusCode::UNSUPPORTED_MEDIA_TYPE
+        );
+        let large = "<p>x</p>".repeat(Limits::untrusted().max_input_bytes.unwrap() / 8 + 1);
+        assert_eq!(
+            status(analyze(None, "", &large).await),
+            StatusCode::PAYLOAD_TOO_LARGE
+        );
+    }
+
+    /// Base URL of a local site serving `/page`, `/list`, `/large`, a
+    /// `/slow` page taking two seconds and a `/counter` page with one more
+    /// paragraph on each request
+    pub(crate) async fn serve_site() -> String {
+        let hits = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let site = Router::new()
+            .route(
+                "/counter",
+                get(move || async move {
+                    let hits = hits.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+                    axum::response::Html("<p>n</p>".repeat(hits))
+                }),
+            )
+            .route("/large", get(|| async { "x".repeat(10_000) }))
+            .route(
+                "/slow",
+                get(|| async {
+                    tokio::time::sleep(Duration::from_secs(2)).await;
+                    "<p>late</p>"
+                }),
+            )
+            .route(
+                "/hops/:n",
+                get(|Path(n): Path<u32>| async move {
+                    // Each hop is quick, all of them aren't
+                    tokio::time::sleep(Duration::from_millis(600)).await;
+                    match n {
+                        0 => axum::response::Html("<p>there</p>").into_response(),
+                        n => axum::response::Redirect::to(&format!("/hops/{}", n - 1))
+                            .into_response(),
+                    }
+                }),
+            )
+            .route(
+                "/page",
+                get(|| async { axum::response::Html("<p>a</p><p>b</p>") }),
+            )
+            .route(
+                "/list",
+                get(|| async { axum::response::Html("<ul><li>x</li></ul>") }),
+            )
+            .route(
+                "/echo",
+                get(|headers: HeaderMap| async move {
+                    // Headers the page was fetched with, as attributes
+                    let attributes: Vec<_> = headers
+                        .iter()
+                        .map(|(name, value)| {
+                            format!("data-{}=\"{}\"", name, value.to_str().unwrap_or_default())
+                        })
+                        .collect();
+                    axum::response::Html(format!("<i {}></i>", attributes.join(" ")))
+                }),
+            )
+            .route(
+                "/links",
+                get(|| async {
+                    axum::response::Html(
+                        r#"<p><a class="x" href="/a">a</a><a class="y" href="/b">b</a><img src="i.png"></p>"#,
+                    )
+                }),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, site).await });
+        base
+    }
+
+    /// State allowing requests to the local site of [`serve_site`]
+    pub(crate) fn local_state() -> AppState {
+        let mut config = Config::default();
+        config.server.allow_private = true;
+        AppState::new(config).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_handler_batch() {
+        let base = serve_site().await;
+        let state = local_state();
+        let batch = |query: &str, urls: Vec<String>| {
+            let query =
+                Query::try_from_uri(&format!("/api/batch?{}", query).parse().unwrap()).unwrap();
+            handler_batch(query, State(state.clone()), HeaderMap::new(), Json(urls))
+        };
+        let urls = vec![
+            format!("{}/page", base),
+            format!("{}/missing", base),
+            "ftp://example.com/".to_string(),
+            format!("{}/list", base),
+        ];
+        let response = batch("concurrency=2", urls.clone()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        let entries = json["entries"].as_array().unwrap();
+        let sources: Vec<_> = entries.iter().map(|entry| &entry["source"]).collect();
+        assert_eq!(sources, urls.iter().collect::<Vec<_>>());
+        assert_eq!(entries[0]["result"]["tags"]["p"]["count"], 2);
+        assert!(entries[1]["error"].as_str().unwrap().contains("404"));
+        assert!(entries[2]["error"]
+            .as_str()
+            .unwrap()
+            .contains("Invalid URL"));
+        assert_eq!(entries[3]["result"]["tags"]["li"]["count"], 1);
+        let page = format!("{}/page", base);
+        assert_eq!(state.history.runs(&page).len(), 1);
+        // Pages fetched with the user's cookie stay out of the history
+        let response = batch("cookie=session=1", vec![page.clone()]).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(state.history.runs(&page).len(), 1);
+
+        let too_many = vec![format!("{}/page", base); 101];
+        assert_eq!(
+            batch("", too_many).await.status(),
+            StatusCode::PAYLOAD_TOO_LARGE
+        );
+
+        // Private addresses are refused by default
+        let response = handler_batch(
+            Query::try_from_uri(&"/api/batch".parse().unwrap()).unwrap(),
+            State(AppState::default()),
+            HeaderMap::new(),
+            Json(vec![format!("{}/page", base)]),
+        )
+        .await;
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        let error = json["entries"][0]["error"].as_str().unwrap();
+        assert!(error.contains("not a public address"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_handler_report_limits() {
+        let base = serve_site().await;
+        let mut config = Config::default();
+        config.server.allow_private = true;
+        config.server.fetch_timeout_secs = 1;
+        config.analyzer.limits.max_input_bytes = Some(1000);
+        let state = AppState::new(config).unwrap();
+        let report = |path: &str| {
+            let query = Query::try_from_uri(&"/api/report".parse().unwrap()).unwrap();
+            handler_report(
+                Path(format!("{}{}", base, path)),
+                query,
+                State(state.clone()),
+                HeaderMap::new(),
+            )
+        };
+        let status = |response: Response| response.status();
+        assert_eq!(
+            status(report("/page").await.into_response()),
+            StatusCode::OK
+        );
+        assert_eq!(
+            status(report("/large").await.into_response()),
+            StatusCode::PAYLOAD_TOO_LARGE
+        );
+        assert_eq!(
+            status(report("/slow").await.into_response()),
+            StatusCode::GATEWAY_TIMEOUT
+        );
+        assert_eq!(
+            status(report("/hops/0").await.into_response()),
+            StatusCode::OK
+        );
+        assert_eq!(
+            status(report("/hops/3").await.into_response()),
+            StatusCode::GATEWAY_TIMEOUT
+        );
+
+        let response = handler_batch(
+            Query::try_from_uri(&"/api/batch".parse().unwrap()).unwrap(),
+            State(state.clone()),
+            HeaderMap::new(),
+            Json(vec![format!("{}/hops/3", base)]),
+        )
+        .await;
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        let error = json["entries"][0]["error"].as_str().unwrap();
+        assert!(error.contains("longer than 1 s"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_handler_report_cache() {
+        let base = serve_site().await;
+        let state = local_state();
+        let report = |query: &str| {
+            let uri = format!("/api/report?{}", query).parse().unwrap();
+            handler_report(
+                Path(format!("{}/counter", base)),
+                Query::try_from_uri(&uri).unwrap(),
+                State(state.clone()),
+                HeaderMap::new(),
+            )
+        };
+        let paragraphs = |response: Response| async {
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+            json["tags"]["p"]["count"].as_u64().unwrap()
+        };
+
+        let response = report("").await.into_response();
+        assert_eq!(response.headers()[CACHE_CONTROL], "public, max-age=300");
+        assert_eq!(response.headers()[AGE], "0");
+        assert_eq!(paragraphs(response).await, 1);
+        // Cached before percentages are added
+        assert_eq!(
+            paragraphs(report("percentages=true").await.into_response()).await,
+            1
+        );
+        assert_eq!(
+            paragraphs(report("refresh=true").await.into_response()).await,
+            2
+        );
+        assert_eq!(paragraphs(report("").await.into_response()).await, 2);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(TARGET_AUTHORIZATION, HeaderValue::from_static("Bearer x"));
+        let response = handler_report(
+            Path(format!("{}/counter", base)),
+            Query::try_from_uri(&"/api/report".parse().unwrap()).unwrap(),
+            State(state.clone()),
+            headers,
+        )
+        .await
+        .into_response();
+        assert_eq!(response.headers()[CACHE_CONTROL], "private, max-age=300");
+        assert_eq!(paragraphs(response).await, 3);
+        // Only the public fetches are in the history
+        assert_eq!(state.history.runs(&format!("{}/counter", base)).len(), 2);
+        assert_eq!(state.metrics.cache_hits.load(Ordering::Relaxed), 2);
+        assert_eq!(state.metrics.cache_misses.load(Ordering::Relaxed), 3);
+    }
+
+    #[tokio::test]
+    async fn test_forward_headers() {
+        let base = serve_site().await;
+        let mut config = Config::default();
+        config.server.allow_private = true;
+        config.server.forward_headers =
+            ["Authorization", "cookie", "user-agent", "x-api-key", "host"]
+                .map(str::to_string)
+                .to_vec();
+        let state = AppState::new(config).unwrap();
+        let report = |query: &str, headers: &[(&'static str, &'static str)]| {
+            let uri = format!("/api/report?{}", query).parse().unwrap();
+            let headers = headers
+                .iter()
+                .map(|(name, value)| (name.parse().unwrap(), HeaderValue::from_static(value)))
+                .collect();
+            let response = handler_report(
+                Path(format!("{}/echo", base)),
+                Query::try_from_uri(&uri).unwrap(),
+                State(state.clone()),
+                headers,
+            );
+            async { response.await.into_response() }
+        };
+        let headers_sent = |response: Response| async {
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+            let attributes = json["tags"]["i"]["attributes"].as_object().unwrap();
+            attributes
+                .iter()
+                .map(|(name, attribute)| {
+                    let values = attribute["value_counts"].as_object().unwrap();
+                    (name.clone(), values.keys().next().unwrap().clone())
+                })
+                .collect::<std::collections::BTreeMap<_, _>>()
+        };
+
+        let headers = [
+            ("authorization", "Bearer a"),
+            ("cookie", "session=1"),
+            ("user-agent", "browser"),
+            ("x-api-key", "k"),
+            ("x-other", "o"),
+            ("host", "scapi.test"),
+        ];
+        let response = report("", &headers).await;
+        assert_eq!(response.headers()[CACHE_CONTROL], "private, max-age=300");
+        let sent = headers_sent(response).await;
+        assert_eq!(sent["data-authorization"], "Bearer a");
+        assert_eq!(sent["data-cookie"], "session=1");
+        assert_eq!(sent["data-user-agent"], "browser");
+        assert_eq!(sent["data-x-api-key"], "k");
+        assert!(!sent.contains_key("data-x-other"));
+        assert_ne!(sent["data-host"], "scapi.test");
+
+        // Query parameters and X-Target-Authorization win
+        let response = report(
+            "user_agent=bot&cookie=theme=dark",
+            &[
+                ("authorization", "Bearer a"),
+                (TARGET_AUTHORIZATION, "Bearer b"),
+                ("cookie", "session=1"),
+                ("user-agent", "browser"),
+            ],
+        )
+        .await;
+        let sent = headers_sent(response).await;
+        assert_eq!(sent["data-authorization"], "Bearer b");
+        assert_eq!(sent["data-cookie"], "session=1; theme=dark");
+        assert_eq!(sent["data-user-agent"], "bot");
+    }
+
+    #[tokio::test]
+    async fn test_handler_report_analysis() {
+        let base = serve_site().await;
+        let state = local_state();
+        let report = |page: &str, query: &str| {
+            let uri = format!("/api/report?{}", query).parse().unwrap();
+            let response = handler_report(
+                Path(format!("{}/{}", base, page)),
+                Query::try_from_uri(&uri).unwrap(),
+                State(state.clone()),
+                HeaderMap::new(),
+            );
+            async { response.await.into_response() }
+        };
+
+        let response = report(
+            "links",
+            "analyzers=stats,images&top_values=1&include_tags=A,img",
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        let tags: BTreeSet<_> = json["tags"].as_object().unwrap().keys().collect();
+        assert_eq!(tags.into_iter().collect::<Vec<_>>(), ["a", "img"]);
+        let classes = &json["tags"]["a"]["attributes"]["class"]["value_counts"];
+        assert_eq!(classes.as_object().unwrap().len(), 1);
+        assert!(json["images"].is_object());
+        // Only analyses with the default options are recorded
+        assert!(state.history.runs(&format!("{}/links", base)).is_empty());
+
+        let status = |page, query| async move { report(page, query).await.status() };
+        assert_eq!(
+            status("links", "analyzers=seo").await,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            status("links", "top_values=5000").await,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            status("list", "max_depth=1").await,
+            StatusCode::PAYLOAD_TOO_LARGE
+        );
+        assert_eq!(status("list", "max_redirects=0").await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_handler_report_fields() {
+        let base = serve_site().await;
+        let state = local_state();
+        let report = |query: &str| {
+            let uri = format!("/api/report?{}", query).parse().unwrap();
+            let response = handler_report(
+                Path(format!("{}/links", base)),
+                Query::try_from_uri(&uri).unwrap(),
+                State(state.clone()),
+                HeaderMap::new(),
+            );
+            async { response.await.into_response() }
+        };
+        let json = |response: Response| async {
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            serde_json::from_slice::<serde_json::Value>(&bytes).unwrap()
+        };
+
+        let projected = json(report("fields=tags.count,max_depth").await).await;
+        assert_eq!(projected["max_depth"], 2);
+        assert_eq!(projected["tags"]["a"], serde_json::json!({ "count": 2 }));
+        assert!(projected.get("files_analyzed").is_none());
+
+        let summary = json(report("summary=true&fields=tags.attributes.count").await).await;
+        assert_eq!(summary["files_analyzed"], 1);
+        assert_eq!(summary["tags"]["img"]["name"], "img");
+        assert_eq!(summary["tags"]["a"]["attributes"]["class"]["count"], 2);
+        assert!(summary["tags"]["a"]["attributes"]["class"]
+            .get("value_counts")
+            .is_none());
+
+        for query in ["fields=tags..count", "summary=true&format=tree"] {
+            assert_eq!(report(query).await.status(), StatusCode::BAD_REQUEST);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_handler_export() {
+        let base = serve_site().await;
+        let state = local_state();
+        let export = |query: &str, accept: &str| {
+            let uri = format!("/api/export?{}", query).parse().unwrap();
+            let mut headers = HeaderMap::new();
+            headers.insert(ACCEPT, HeaderValue::from_str(accept).unwrap());
+            let response = handler_export(
+                Path(format!("{}/page", base)),
+                Query::try_from_uri(&uri).unwrap(),
+                State(state.clone()),
+                headers,
+            );
+            async { response.await.into_response() }
+        };
+        let body = |response: Response| async {
+            axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap()
+        };
+
+        let response = export("", "application/vnd.apache.parquet, */*;q=0.1").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[CONTENT_TYPE],
+            "application/vnd.apache.parquet"
+        );
+        assert_eq!(response.headers()[VARY], "Accept");
+        assert!(body(response).await.starts_with(b"PAR1"));
+
+        let response = export("format=xlsx", "text/csv").await;
+        assert!(response.headers()[CONTENT_DISPOSITION]
+            .to_str()
+            .unwrap()
+            .ends_with("report.xlsx\""));
+        assert!(body(response).await.starts_with(b"PK"));
+
+        let response = export("format=markdown", "*/*").await;
+        assert_eq!(response.headers()[CONTENT_TYPE], "text/markdown");
+        let response = export("", "application/x-ndjson").await;
+        let ndjson = String::from_utf8(body(response).await.to_vec()).unwrap();
+        // One record per tag, without bulk actions
+        assert!(ndjson.lines().all(|line| {
+            let record: serde_json::Value = serde_json::from_str(line).unwrap();
+            record["tag"].is_string()
+        }));
+
+        assert_eq!(
+            export("", "application/pdf").await.status(),
+            StatusCode::NOT_ACCEPTABLE
+        );
+        assert_eq!(
+            export("format=pdf", "*/*").await.status(),
+            StatusCode::BAD_REQUEST
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handler_export_batch() {
+        let base = serve_site().await;
+        let state = local_state();
+        let export = |query: &str, urls: Vec<String>| {
+            let uri = format!("/api/export/batch?{}", query).parse().unwrap();
+            let query = Query::try_from_uri(&uri).unwrap();
+            handler_export_batch(query, State(state.clone()), HeaderMap::new(), Json(urls))
+        };
+
+        let urls = vec![format!("{}/page", base), format!("{}/missing", base)];
+        let response = export("format=json&concurrency=2", urls).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/zip");
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(bytes)).unwrap();
+        let host = base.trim_start_matches("http://").replace(':', "-");
+        let page = format!("001-{}-page.json", host);
+        let report: serde_json::Value =
+            serde_json::from_reader(archive.by_name(&page).unwrap()).unwrap();
+        assert_eq!(report["tags"]["p"]["count"], 2);
+        let index: serde_json::Value =
+            serde_json::from_reader(archive.by_name("index.json").unwrap()).unwrap();
+        assert_eq!(index["aggregate"], "aggregate.json");
+        assert!(index["entries"][1]["error"]
+            .as_str()
+            .unwrap()
+            .contains("404"));
+        assert!(archive.by_name("aggregate.json").is_ok());
+
+        let too_many = vec![format!("{}/page", base); 101];
+        assert_eq!(
+            export("", too_many).await.status(),
+            StatusCode::PAYLOAD_TOO_LARGE
+        );
+        assert_eq!(
+            export("format=pdf", vec![]).await.status(),
+            StatusCode::BAD_REQUEST
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ui() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("index.html"), "<div id=\"root\"></div>").unwrap();
+        std::fs::create_dir(dir.path().join("assets")).unwrap();
+        std::fs::write(dir.path().join("assets/app.js"), "render()").unwrap();
+        let mut config = Config::default();
+        config.server.ui_dir = Some(dir.path().to_path_buf());
+        let app =
+            app(AppState::new(config).unwrap()).into_make_service_with_connect_info::<SocketAddr>();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let client = reqwest::Client::new();
+        let get = |path: &str| client.get(format!("{}{}", base, path)).send();
+        let index = get("/").await.unwrap();
+        assert_eq!(index.headers()[CONTENT_TYPE.as_str()], "text/html");
+        assert_eq!(index.text().await.unwrap(), "<div id=\"root\"></div>");
+        let script = get("/assets/app.js").await.unwrap();
+        assert!(script.headers()[CONTENT_TYPE.as_str()]
+            .to_str()
+            .unwrap()
+            .contains("javascript"));
+        assert_eq!(get("/missing.js").await.unwrap().status().as_u16(), 404);
+        // The API comes first
+        assert_eq!(get("/healthz").await.unwrap().text().await.unwrap(), "ok");
+        let job = get("/api/jobs/a").await.unwrap();
+        assert_eq!(job.status().as_u16(), 404);
+        assert_eq!(job.text().await.unwrap(), "No job a");
+    }
+
+    #[tokio::test]
+    async fn test_compression() {
+        let site = serve_site().await;
+        let app = app(local_state()).into_make_service_with_connect_info::<SocketAddr>();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        // Left to the test to decode
+        let client = reqwest::Client::builder()
+            .no_gzip()
+            .no_brotli()
+            .build()
+            .unwrap();
+        let get = |path: String, encoding: &'static str| {
+            client
+                .get(format!("{}{}", base, path))
+                .header("accept-encoding", encoding)
+                .send()
+        };
+        let encoding = |response: &reqwest::Response| {
+            response
+                .headers()
+                .get("content-encoding")
+                .map(|encoding| encoding.to_str().unwrap().to_string())
+        };
+
+        let response = get(format!("/api/report/{}/links", site), "gzip")
+            .await
+            .unwrap();
+        assert_eq!(encoding(&response).as_deref(), Some("gzip"));
+        assert!(response.bytes().await.unwrap().starts_with(&[0x1f, 0x8b]));
+        let response = get(format!("/api/export/{}/links?format=json", site), "br")
+            .await
+            .unwrap();
+        assert_eq!(encoding(&response).as_deref(), Some("br"));
+        // Zipped already
+        let response = get(format!("/api/export/{}/links?format=xlsx", site), "gzip")
+            .await
+            .unwrap();
+        assert_eq!(encoding(&response), None);
+        assert!(response.bytes().await.unwrap().starts_with(b"PK"));
+        let response = get(format!("/api/report/{}/links", site), "identity")
+            .await
+            .unwrap();
+        assert_eq!(encoding(&response), None);
+    }
+
+    #[test]
+    fn test_app_state_config() {
+        let mut config = Config::default();
+        config.analyzer.limits.max_depth = Some(64);
+        config.server.allowed_hosts = vec!["example.com".to_string()];
+        let state = AppState::new(config).unwrap();
+
+        let limits = state.limits();
+        assert_eq!(limits.max_depth, Some(64));
+        assert_eq!(limits.max_nodes, Limits::untrusted().max_nodes);
+
+        assert!(state.reject("https://www.example.com/").is_none());
+        let status = |url| state.reject(url).map(|response| response.status());
+        assert_eq!(status("https://example.org/"), Some(StatusCode::FORBIDDEN));
+        assert_eq!(status("ftp://example.com/"), Some(StatusCode::BAD_REQUEST));
+    }
 }
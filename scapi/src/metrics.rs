@@ -0,0 +1,247 @@
+//! Health checks and Prometheus metrics
+//!
+//! `/healthz` answers as long as the server runs, and `/readyz` as long as
+//! it accepts new jobs, for liveness and readiness probes. `/metrics`
+//! exposes requests per route and status, fetch and analysis durations,
+//! report cache hits and misses and the jobs kept by state, in the
+//! Prometheus text format. None of them are rate limited.
+
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::{header::CONTENT_TYPE, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::AppState;
+
+/// Upper bounds of the duration buckets, in seconds
+const BUCKETS: [f64; 12] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+];
+
+#[derive(Default)]
+pub struct Metrics {
+    /// Responses by method, route and status
+    requests: Mutex<BTreeMap<(String, String, u16), u64>>,
+    /// Fetching pages, body included
+    pub fetch: Histogram,
+    /// Analyzing fetched or posted HTML
+    pub analysis: Histogram,
+    pub cache_hits: AtomicU64,
+    pub cache_misses: AtomicU64,
+}
+
+#[derive(Default)]
+pub struct Histogram {
+    /// Observations up to each bound of `BUCKETS`
+    buckets: [AtomicU64; BUCKETS.len()],
+    count: AtomicU64,
+    sum_micros: AtomicU64,
+}
+
+impl Histogram {
+    pub fn observe(&self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        for (bound, bucket) in BUCKETS.iter().zip(&self.buckets) {
+            if seconds <= *bound {
+                bucket.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        let micros = u64::try_from(duration.as_micros()).unwrap_or(u64::MAX);
+        self.sum_micros.fetch_add(micros, Ordering::Relaxed);
+    }
+
+    /// Run `f`, observing how long it takes
+    pub fn time<T>(&self, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let value = f();
+        self.observe(start.elapsed());
+        value
+    }
+
+    fn write(&self, out: &mut String, name: &str, help: &str) {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} histogram", name);
+        for (bound, bucket) in BUCKETS.iter().zip(&self.buckets) {
+            let count = bucket.load(Ordering::Relaxed);
+            let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, bound, count);
+        }
+        let count = self.count.load(Ordering::Relaxed);
+        let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, count);
+        let sum = self.sum_micros.load(Ordering::Relaxed) as f64 / 1e6;
+        let _ = writeln!(out, "{}_sum {}", name, sum);
+        let _ = writeln!(out, "{}_count {}", name, count);
+    }
+}
+
+impl Metrics {
+    fn count_request(&self, method: &str, route: &str, status: u16) {
+        let mut requests = self.requests.lock().unwrap();
+        *requests
+            .entry((method.to_string(), route.to_string(), status))
+            .or_default() += 1;
+    }
+
+    /// The metrics in the Prometheus text format
+    fn render(&self, state: &AppState) -> String {
+        let mut out = String::new();
+        out.push_str("# HELP scapi_requests_total Responses by method, route and status.\n");
+        out.push_str("# TYPE scapi_requests_total counter\n");
+        for ((method, route, status), count) in self.requests.lock().unwrap().iter() {
+            let _ = writeln!(
+                out,
+                "scapi_requests_total{{method=\"{}\",route=\"{}\",status=\"{}\"}} {}",
+                method,
+                escape_label(route),
+                status,
+                count
+            );
+        }
+        self.fetch.write(
+            &mut out,
+            "scapi_fetch_duration_seconds",
+            "Time taken to fetch a page, body included.",
+        );
+        self.analysis.write(
+            &mut out,
+            "scapi_analysis_duration_seconds",
+            "Time taken to analyze a page.",
+        );
+        for (name, counter, help) in [
+            (
+                "scapi_cache_hits_total",
+                &self.cache_hits,
+                "Reports answered from the cache.",
+            ),
+            (
+                "scapi_cache_misses_total",
+                &self.cache_misses,
+                "Reports that fetched their page.",
+            ),
+        ] {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} counter", name);
+            let _ = writeln!(out, "{} {}", name, counter.load(Ordering::Relaxed));
+        }
+        let jobs = state.jobs.counts();
+        out.push_str("# HELP scapi_jobs Jobs kept, by state.\n");
+        out.push_str("# TYPE scapi_jobs gauge\n");
+        for (name, count) in [
+            ("queued", jobs.queued),
+            ("running", jobs.running),
+            ("finished", jobs.finished),
+        ] {
+            let _ = writeln!(out, "scapi_jobs{{state=\"{}\"}} {}", name, count);
+        }
+        out
+    }
+}
+
+/// Label values escape backslashes, quotes and newlines
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Middleware counting responses by method, matched route and status
+pub async fn track(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or("unmatched", MatchedPath::as_str)
+        .to_string();
+    let response = next.run(request).await;
+    state
+        .metrics
+        .count_request(&method, &route, response.status().as_u16());
+    response
+}
+
+pub async fn handler_healthz() -> &'static str {
+    "ok"
+}
+
+/// `503 Service Unavailable` while the job store is full of unfinished jobs
+pub async fn handler_readyz(State(state): State<AppState>) -> Response {
+    let jobs = state.jobs.counts();
+    let status = match jobs.full {
+        true => StatusCode::SERVICE_UNAVAILABLE,
+        false => StatusCode::OK,
+    };
+    (status, Json(json!({ "ready": !jobs.full, "jobs": jobs }))).into_response()
+}
+
+pub async fn handler_metrics(State(state): State<AppState>) -> Response {
+    (
+        [(CONTENT_TYPE, "text/plain; version=0.0.4; charset=utf-8")],
+        state.metrics.render(&state),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::SocketAddr;
+
+    #[test]
+    fn test_histogram() {
+        let histogram = Histogram::default();
+        histogram.observe(Duration::from_millis(30));
+        histogram.observe(Duration::from_secs(60));
+        let mut out = String::new();
+        histogram.write(&mut out, "test_seconds", "Test.");
+        assert!(out.contains("test_seconds_bucket{le=\"0.025\"} 0\n"));
+        assert!(out.contains("test_seconds_bucket{le=\"0.05\"} 1\n"));
+        assert!(out.contains("test_seconds_bucket{le=\"30\"} 1\n"));
+        assert!(out.contains("test_seconds_bucket{le=\"+Inf\"} 2\n"));
+        assert!(out.contains("test_seconds_sum 60.03\n"));
+        assert!(out.contains("test_seconds_count 2\n"));
+    }
+
+    #[tokio::test]
+    async fn test_endpoints() {
+        let mut config = ferret::config::Config::default();
+        config.server.rate_limit = 1;
+        let app = crate::app(AppState::new(config).unwrap())
+            .into_make_service_with_connect_info::<SocketAddr>();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let client = reqwest::Client::new();
+        let get = |path: &str| client.get(format!("{}{}", base, path)).send();
+        assert_eq!(get("/api/jobs/a").await.unwrap().status().as_u16(), 404);
+        assert_eq!(get("/api/jobs/b").await.unwrap().status().as_u16(), 429);
+        // Not rate limited
+        for _ in 0..2 {
+            assert_eq!(get("/healthz").await.unwrap().status().as_u16(), 200);
+        }
+        let ready: serde_json::Value = get("/readyz").await.unwrap().json().await.unwrap();
+        assert_eq!(ready["ready"], true);
+
+        let metrics = get("/metrics").await.unwrap().text().await.unwrap();
+        assert!(
+            metrics.contains(
+                "scapi_requests_total{method=\"GET\",route=\"/api/jobs/:id\",status=\"404\"} 1\n"
+            ),
+            "{}",
+            metrics
+        );
+        assert!(metrics.contains("status=\"429\"} 1\n"), "{}", metrics);
+        assert!(metrics.contains("scapi_jobs{state=\"queued\"} 0\n"));
+        assert!(metrics.contains("scapi_cache_hits_total 0\n"));
+    }
+}
@@ -0,0 +1,266 @@
+//! Chat messages for the `slack` and `discord` webhook formats
+//!
+//! Both show the changed tags with their old and new counts, largest
+//! changes first, or the violated rules with their measured values, and
+//! link to the report, diff and history when `server.public_url` makes the
+//! links absolute.
+
+use serde_json::{json, Value};
+
+use crate::webhooks::Event;
+
+/// Longest Slack header text
+const SLACK_HEADER_CHARS: usize = 150;
+/// Most fields of a Slack section
+const SLACK_FIELDS: usize = 10;
+/// Longest Discord embed title
+const DISCORD_TITLE_CHARS: usize = 256;
+/// Most fields of a Discord embed
+const DISCORD_FIELDS: usize = 25;
+
+const CHANGE_COLOR: u32 = 0xe67e22;
+const VIOLATION_COLOR: u32 = 0xcf222e;
+
+/// What the messages of both formats say
+struct Message {
+    title: String,
+    summary: String,
+    /// Tag or rule and what became of it
+    fields: Vec<(String, String)>,
+    /// Labels and absolute URLs
+    links: Vec<(&'static str, String)>,
+}
+
+impl Message {
+    fn new(event: &Event) -> Self {
+        let (title, summary, fields) = match event {
+            Event::Change(change) => {
+                let mut summary = format!("Tags changed: {}", change.changes);
+                if change.old_max_depth != change.new_max_depth {
+                    summary.push_str(&format!(
+                        ", max depth: {} → {}",
+                        change.old_max_depth, change.new_max_depth
+                    ));
+                }
+                let fields = change
+                    .tags
+                    .iter()
+                    .map(|tag| {
+                        let counts =
+                            format!("{} → {} ({:+})", tag.old_count, tag.new_count, tag.delta());
+                        (format!("<{}>", tag.name), counts)
+                    })
+                    .collect();
+                (format!("Page changed: {}", change.source), summary, fields)
+            }
+            Event::Violation(violation) => {
+                let summary = format!("Rules newly violated: {}", violation.violations.len());
+                let fields = violation
+                    .violations
+                    .iter()
+                    .map(|v| (v.rule.clone(), format!("actual {}", v.actual)))
+                    .collect();
+                (
+                    format!("Rules violated: {}", violation.source),
+                    summary,
+                    fields,
+                )
+            }
+        };
+        let links = event.links();
+        let links = [
+            ("Report", Some(&links.report)),
+            ("Diff", links.diff.as_ref()),
+            ("History", Some(&links.history)),
+        ]
+        .into_iter()
+        .filter_map(|(label, url)| Some((label, url?.clone())))
+        .filter(|(_, url)| url.starts_with("http://") || url.starts_with("https://"))
+        .collect();
+        Self {
+            title,
+            summary,
+            fields,
+            links,
+        }
+    }
+}
+
+/// A Slack message with a header, the summary, a field per tag or rule and
+/// link buttons, for an incoming webhook
+pub fn slack(event: &Event) -> Value {
+    let message = Message::new(event);
+    let mut blocks = vec![
+        json!({
+            "type": "header",
+            "text": { "type": "plain_text", "text": truncate(&message.title, SLACK_HEADER_CHARS) },
+        }),
+        json!({
+            "type": "section",
+            "text": { "type": "mrkdwn", "text": slack_escape(&message.summary) },
+        }),
+    ];
+    if !message.fields.is_empty() {
+        let fields: Vec<Value> = message
+            .fields
+            .iter()
+            .take(SLACK_FIELDS)
+            .map(|(name, value)| {
+                let text = format!("*{}*\n{}", slack_escape(name), slack_escape(value));
+                json!({ "type": "mrkdwn", "text": text })
+            })
+            .collect();
+        blocks.push(json!({ "type": "section", "fields": fields }));
+    }
+    if !message.links.is_empty() {
+        let buttons: Vec<Value> = message
+            .links
+            .iter()
+            .map(|(label, url)| {
+                json!({
+                    "type": "button",
+                    "text": { "type": "plain_text", "text": label },
+                    "url": url,
+                })
+            })
+            .collect();
+        blocks.push(json!({ "type": "actions", "elements": buttons }));
+    }
+    json!({
+        // Shown in notifications, where blocks aren't
+        "text": format!("{} ({})", message.title, message.summary),
+        "blocks": blocks,
+    })
+}
+
+/// A Discord message with an embed: the summary and links, and an inline
+/// field per tag or rule
+pub fn discord(event: &Event) -> Value {
+    let message = Message::new(event);
+    let mut description = message.summary.clone();
+    if !message.links.is_empty() {
+        let links: Vec<String> = message
+            .links
+            .iter()
+            .map(|(label, url)| format!("[{}]({})", label, url))
+            .collect();
+        description.push('\n');
+        description.push_str(&links.join(" · "));
+    }
+    let fields: Vec<Value> = message
+        .fields
+        .iter()
+        .take(DISCORD_FIELDS)
+        .map(|(name, value)| json!({ "name": name, "value": value, "inline": true }))
+        .collect();
+    let color = match event {
+        Event::Change(_) => CHANGE_COLOR,
+        Event::Violation(_) => VIOLATION_COLOR,
+    };
+    json!({
+        "embeds": [{
+            "title": truncate(&message.title, DISCORD_TITLE_CHARS),
+            "description": description,
+            "color": color,
+            "fields": fields,
+        }],
+    })
+}
+
+/// Slack reads `&`, `<` and `>` as markup
+fn slack_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(max_chars - 1).collect();
+    truncated.push('…');
+    truncated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::webhooks::{ChangeEvent, ViolationEvent};
+    use crate::AppState;
+    use ferret::config::Config;
+    use ferret::diff::AnalysisDiff;
+    use ferret::rules::Violation;
+
+    fn state(public_url: Option<&str>) -> AppState {
+        let mut config = Config::default();
+        config.server.public_url = public_url.map(str::to_string);
+        AppState::new(config).unwrap()
+    }
+
+    fn change(state: &AppState) -> Event {
+        let analyze =
+            |html| crate::analyze_html(html, &ferret::limits::Limits::untrusted(), 10).unwrap();
+        let diff = AnalysisDiff::new(&analyze("<p>a</p>"), &analyze("<p>a</p><p>b</p>"));
+        Event::Change(ChangeEvent::new(
+            state,
+            "1",
+            "https://a.test/",
+            (1, 2),
+            &diff,
+        ))
+    }
+
+    #[test]
+    fn test_slack() {
+        let state = state(Some("https://ferret.test"));
+        let message = slack(&change(&state));
+        assert_eq!(
+            message["text"],
+            "Page changed: https://a.test/ (Tags changed: 1)"
+        );
+        let blocks = message["blocks"].as_array().unwrap();
+        assert_eq!(blocks[2]["fields"][0]["text"], "*&lt;p&gt;*\n1 → 2 (+1)");
+        assert_eq!(
+            blocks[3]["elements"][1]["url"],
+            "https://ferret.test/api/diff?a=1&b=2"
+        );
+
+        // Relative links can't be buttons
+        let message = slack(&change(&self::state(None)));
+        assert_eq!(message["blocks"].as_array().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_discord() {
+        let state = state(Some("https://ferret.test/"));
+        let event = Event::Violation(ViolationEvent::new(
+            &state,
+            "1",
+            "https://a.test/",
+            3,
+            vec![Violation {
+                rule: "max_depth > 40".to_string(),
+                actual: 41,
+            }],
+        ));
+        let message = discord(&event);
+        let embed = &message["embeds"][0];
+        assert_eq!(embed["title"], "Rules violated: https://a.test/");
+        assert_eq!(embed["color"], VIOLATION_COLOR);
+        assert_eq!(embed["fields"][0]["name"], "max_depth > 40");
+        assert_eq!(embed["fields"][0]["value"], "actual 41");
+        let description = embed["description"].as_str().unwrap();
+        assert!(description.starts_with(
+            "Rules newly violated: 1\n[Report](https://ferret.test/api/report/https://a.test/)"
+        ));
+        assert!(!description.contains("Diff"));
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("abc", 3), "abc");
+        assert_eq!(truncate("abcd", 3), "ab…");
+        assert_eq!(truncate("äöüß", 3), "äö…");
+    }
+}
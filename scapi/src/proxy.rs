@@ -0,0 +1,227 @@
+//! Pages passed through while they are analyzed
+//!
+//! `GET /api/proxy/<url>` answers with the status of the page and a
+//! `multipart/mixed` body: first the page as it arrives, with its own
+//! `Content-Type` and its final URL as `Content-Location`, then its
+//! analysis as JSON once the page ended. Clients get the page without
+//! waiting for the analysis, which runs on the chunks as they pass. A
+//! page the analyzer gives up on, e.g. for being too large, is still
+//! passed on whole, followed by `{"error": ...}`. A page still arriving
+//! past `server.fetch_timeout_secs` is cut off.
+
+use axum::{
+    body::{Body, Bytes},
+    extract::{Path, Query, State},
+    http::{header::CONTENT_TYPE, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    BoxError,
+};
+use ferret::analyzer::incremental::IncrementalAnalyzer;
+use ferret::analyzer::stream::StreamAnalyzer;
+use ferret::fetch::Fetched;
+use futures::StreamExt;
+use serde_json::json;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tracing::Instrument;
+
+use crate::{error_response, fetch, logging, within, AppState, FetchParams};
+
+/// Chunks of the page read ahead of a slow client
+const CHUNKS_AHEAD: usize = 8;
+
+pub async fn handler_proxy(
+    Path(target_url): Path<String>,
+    Query(params): Query<FetchParams>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Response {
+    if let Some(response) = state.reject(&target_url) {
+        return response;
+    }
+    let options = params.to_options(&headers, &state);
+    let start = Instant::now();
+    let fetched = within(state.fetch_timeout(), fetch(&target_url, &options)).await;
+    let Fetched { response, .. } = match fetched.and_then(|fetched| fetched) {
+        Ok(fetched) => fetched,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, "Fetch error", e),
+    };
+    let analyzer = StreamAnalyzer::new(state.config.analyzer.top_values)
+        .with_limits(state.limits())
+        .incremental();
+
+    // Unguessable, so the page can't end its own part early
+    let boundary = format!("ferret-{}", logging::new_request_id());
+    let status = StatusCode::from_u16(response.status().as_u16()).unwrap_or(StatusCode::OK);
+    let (sender, mut chunks) = mpsc::channel(CHUNKS_AHEAD);
+    tokio::spawn(
+        pass(state, response, analyzer, boundary.clone(), start, sender)
+            .instrument(tracing::Span::current()),
+    );
+    let body = Body::from_stream(futures::stream::poll_fn(move |cx| chunks.poll_recv(cx)));
+    (
+        status,
+        [(
+            CONTENT_TYPE,
+            format!("multipart/mixed; boundary=\"{}\"", boundary),
+        )],
+        body,
+    )
+        .into_response()
+}
+
+/// Send the parts of the body to `sender`, analyzing the page on the way
+async fn pass(
+    state: AppState,
+    response: reqwest::Response,
+    analyzer: IncrementalAnalyzer,
+    boundary: String,
+    start: Instant,
+    sender: mpsc::Sender<Result<Bytes, BoxError>>,
+) {
+    let timeout = state.fetch_timeout();
+    let deadline = (!timeout.is_zero()).then(|| tokio::time::Instant::from_std(start + timeout));
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("application/octet-stream");
+    let head = format!(
+        "--{}\r\nContent-Type: {}\r\nContent-Location: {}\r\n\r\n",
+        boundary,
+        content_type,
+        response.url()
+    );
+    if sender.send(Ok(head.into())).await.is_err() {
+        return;
+    }
+
+    let mut analysis = Ok(analyzer);
+    let mut analysis_time = Duration::ZERO;
+    let mut page = response.bytes_stream();
+    loop {
+        let next = match deadline {
+            Some(deadline) => tokio::time::timeout_at(deadline, page.next()).await,
+            None => Ok(page.next().await),
+        };
+        let chunk = match next {
+            Ok(None) => break,
+            Ok(Some(Ok(chunk))) => chunk,
+            Ok(Some(Err(err))) => {
+                // Aborts the response, so the client can tell the page is cut off
+                tracing::debug!("Failed to read the page: {}", err);
+                let _ = sender.send(Err(err.into())).await;
+                return;
+            }
+            Err(elapsed) => {
+                tracing::debug!("Page still arriving after {} s", timeout.as_secs());
+                let _ = sender.send(Err(elapsed.into())).await;
+                return;
+            }
+        };
+        if let Ok(analyzer) = &mut analysis {
+            let analyzed = Instant::now();
+            if let Err(err) = analyzer.feed(&chunk) {
+                analysis = Err(err);
+            }
+            analysis_time += analyzed.elapsed();
+        }
+        if sender.send(Ok(chunk)).await.is_err() {
+            tracing::debug!("Client left during the page");
+            return;
+        }
+    }
+    state.metrics.fetch.observe(start.elapsed());
+
+    let finished = Instant::now();
+    let result = analysis.and_then(IncrementalAnalyzer::finish);
+    // Only the time spent analyzing, not waiting for the page
+    (state.metrics.analysis).observe(analysis_time + finished.elapsed());
+    let report = match result {
+        Ok(result) => serde_json::to_vec(&result),
+        Err(err) => serde_json::to_vec(&json!({ "error": format!("{:#}", err) })),
+    };
+    let mut tail = format!(
+        "\r\n--{}\r\nContent-Type: application/json\r\n\r\n",
+        boundary
+    )
+    .into_bytes();
+    tail.extend(report.unwrap_or_default());
+    tail.extend(format!("\r\n--{}--\r\n", boundary).into_bytes());
+    let _ = sender.send(Ok(tail.into())).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{local_state, serve_site};
+    use ferret::config::Config;
+
+    /// The page and the analysis of a proxied response
+    async fn parts(response: Response) -> (String, String, serde_json::Value) {
+        let content_type = response.headers()[CONTENT_TYPE].to_str().unwrap();
+        let boundary = content_type
+            .split_once("boundary=")
+            .unwrap()
+            .1
+            .trim_matches('"')
+            .to_string();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        let body = body
+            .strip_suffix(&format!("\r\n--{}--\r\n", boundary))
+            .unwrap();
+        let parts: Vec<_> = body.split(&format!("--{}\r\n", boundary)).collect();
+        assert_eq!(parts.len(), 3, "{}", body);
+        let (head, page) = parts[1].split_once("\r\n\r\n").unwrap();
+        let page = page.strip_suffix("\r\n").unwrap();
+        let (_, report) = parts[2].split_once("\r\n\r\n").unwrap();
+        (
+            head.to_string(),
+            page.to_string(),
+            serde_json::from_str(report).unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_handler_proxy() {
+        let base = serve_site().await;
+        let proxy = |state: AppState, page: &str| {
+            handler_proxy(
+                Path(format!("{}/{}", base, page)),
+                Query(FetchParams::default()),
+                State(state),
+                HeaderMap::new(),
+            )
+        };
+
+        let response = proxy(local_state(), "links").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let (head, page, report) = parts(response).await;
+        assert!(head.contains("Content-Type: text/html; charset=utf-8"));
+        assert!(head.contains(&format!("Content-Location: {}/links", base)));
+        assert!(page.starts_with("<p><a class=\"x\" href=\"/a\">"));
+        assert_eq!(report["tags"]["a"]["count"], 2);
+
+        // Passed on whole even though the analyzer gave up
+        let mut config = Config::default();
+        config.server.allow_private = true;
+        config.analyzer.limits.max_input_bytes = Some(100);
+        let state = AppState::new(config).unwrap();
+        let (_, page, report) = parts(proxy(state, "large").await).await;
+        assert_eq!(page.len(), 10_000);
+        assert!(report["error"].as_str().unwrap().contains("100"));
+
+        let refused = proxy(AppState::default(), "links").await;
+        assert_eq!(refused.status(), StatusCode::FORBIDDEN);
+
+        let mut config = Config::default();
+        config.server.allow_private = true;
+        config.server.fetch_timeout_secs = 1;
+        let state = AppState::new(config).unwrap();
+        let response = proxy(state, "hops/3").await;
+        assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+    }
+}
@@ -0,0 +1,266 @@
+//! Requests per client IP address
+//!
+//! Each address has a bucket of `server.rate_limit` requests, refilled at
+//! that many per minute, so clients may send a burst of them at once.
+//! Requests finding their bucket empty get `429 Too Many Requests` with a
+//! `Retry-After` header; without them every fetching endpoint would let
+//! anyone make the server send requests on their behalf as fast as it can.
+//! The requests of each client are counted, limit or not, for
+//! `GET /api/admin/usage`.
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header::RETRY_AFTER, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::AppState;
+
+/// Clients tracked before the least recently seen are forgotten
+const MAX_CLIENTS: usize = 10_000;
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    allowed: u64,
+    limited: u64,
+}
+
+/// Requests of a client since it was last forgotten
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ClientUsage {
+    pub client: IpAddr,
+    /// Requests let through
+    pub allowed: u64,
+    /// Requests answered `429 Too Many Requests`
+    pub limited: u64,
+    /// Requests the client may send right now; `None` without a limit
+    pub remaining: Option<u32>,
+    /// Seconds since its last request
+    pub idle_secs: u64,
+}
+
+pub struct RateLimiter {
+    /// Requests per minute and size of the buckets; `0` allows any number
+    per_minute: u32,
+    max_clients: usize,
+    buckets: Mutex<HashMap<IpAddr, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(per_minute: u32) -> Self {
+        Self {
+            per_minute,
+            max_clients: MAX_CLIENTS,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Take a request from the bucket of `ip`, or tell how long until the
+    /// next one is allowed
+    fn acquire(&self, ip: IpAddr, now: Instant) -> Result<(), Duration> {
+        let capacity = f64::from(self.per_minute);
+        let per_second = capacity / 60.0;
+        let refill = |bucket: &Bucket| {
+            let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+            (bucket.tokens + elapsed * per_second).min(capacity)
+        };
+
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= self.max_clients && !buckets.contains_key(&ip) {
+            forget_idlest(&mut buckets, self.max_clients / 10 + 1);
+        }
+        let bucket = buckets.entry(ip).or_insert(Bucket {
+            tokens: capacity,
+            updated: now,
+            allowed: 0,
+            limited: 0,
+        });
+        bucket.tokens = refill(bucket);
+        bucket.updated = now;
+        if self.per_minute > 0 {
+            if bucket.tokens < 1.0 {
+                bucket.limited += 1;
+                return Err(Duration::from_secs_f64((1.0 - bucket.tokens) / per_second));
+            }
+            bucket.tokens -= 1.0;
+        }
+        bucket.allowed += 1;
+        Ok(())
+    }
+
+    /// Requests of the clients tracked, busiest first
+    pub fn usage(&self, now: Instant) -> Vec<ClientUsage> {
+        let capacity = f64::from(self.per_minute);
+        let buckets = self.buckets.lock().unwrap();
+        let mut usage: Vec<_> = buckets
+            .iter()
+            .map(|(ip, bucket)| {
+                let idle = now.saturating_duration_since(bucket.updated);
+                let tokens = bucket.tokens + idle.as_secs_f64() * capacity / 60.0;
+                ClientUsage {
+                    client: *ip,
+                    allowed: bucket.allowed,
+                    limited: bucket.limited,
+                    remaining: (self.per_minute > 0).then(|| tokens.min(capacity) as u32),
+                    idle_secs: idle.as_secs(),
+                }
+            })
+            .collect();
+        usage.sort_by(|a, b| {
+            (b.allowed + b.limited)
+                .cmp(&(a.allowed + a.limited))
+                .then(a.client.cmp(&b.client))
+        });
+        usage
+    }
+}
+
+/// Forget the `count` clients seen the longest ago
+fn forget_idlest(buckets: &mut HashMap<IpAddr, Bucket>, count: usize) {
+    let mut seen: Vec<Instant> = buckets.values().map(|bucket| bucket.updated).collect();
+    if count >= seen.len() {
+        buckets.clear();
+        return;
+    }
+    let (_, &mut cutoff, _) = seen.select_nth_unstable(count);
+    buckets.retain(|_, bucket| bucket.updated >= cutoff);
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+/// Middleware answering `429 Too Many Requests` to clients over their limit
+///
+/// Requests whose client address is unknown, such as those of a server not
+/// started with `into_make_service_with_connect_info`, aren't limited.
+pub async fn limit(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    if let Some(ip) = client_ip(&request, state.config.server.trust_forwarded_for) {
+        if let Err(wait) = state.rate_limiter.acquire(ip, Instant::now()) {
+            let seconds = wait.as_secs_f64().ceil().max(1.0) as u64;
+            return (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(RETRY_AFTER, seconds.to_string())],
+                format!("Too many requests, retry in {} s", seconds),
+            )
+                .into_response();
+        }
+    }
+    next.run(request).await
+}
+
+fn client_ip(request: &Request, trust_forwarded_for: bool) -> Option<IpAddr> {
+    let forwarded = trust_forwarded_for
+        .then(|| request.headers().get("x-forwarded-for")?.to_str().ok())
+        .flatten()
+        .and_then(|value| value.rsplit(',').next()?.trim().parse().ok());
+    forwarded.or_else(|| {
+        request
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ferret::config::Config;
+
+    #[test]
+    fn test_acquire() {
+        let limiter = RateLimiter::new(2);
+        let (a, b) = ("10.0.0.1".parse().unwrap(), "10.0.0.2".parse().unwrap());
+        let start = Instant::now();
+        assert!(limiter.acquire(a, start).is_ok());
+        assert!(limiter.acquire(a, start).is_ok());
+        let wait = limiter.acquire(a, start).unwrap_err();
+        assert_eq!(wait.as_secs(), 30);
+        assert!(limiter.acquire(b, start).is_ok());
+        assert!(limiter.acquire(a, start + Duration::from_secs(30)).is_ok());
+        assert!(limiter.acquire(a, start + Duration::from_secs(31)).is_err());
+
+        let usage = limiter.usage(start + Duration::from_secs(31));
+        assert_eq!(usage.len(), 2);
+        assert_eq!((usage[0].client, usage[0].allowed), (a, 3));
+        assert_eq!((usage[0].limited, usage[0].remaining), (2, Some(0)));
+        assert_eq!((usage[1].client, usage[1].allowed), (b, 1));
+        assert_eq!((usage[1].remaining, usage[1].idle_secs), (Some(2), 31));
+
+        let unlimited = RateLimiter::new(0);
+        assert!((0..1000).all(|_| unlimited.acquire(a, start).is_ok()));
+        let usage = unlimited.usage(start);
+        assert_eq!((usage[0].allowed, usage[0].remaining), (1000, None));
+    }
+
+    #[test]
+    fn test_forget_idlest() {
+        for per_minute in [0, 60] {
+            let mut limiter = RateLimiter::new(per_minute);
+            limiter.max_clients = 20;
+            let start = Instant::now();
+            for i in 0..100u8 {
+                let ip = IpAddr::from([10, 0, 0, i]);
+                let now = start + Duration::from_millis(u64::from(i));
+                assert!(limiter.acquire(ip, now).is_ok());
+                assert!(limiter.buckets.lock().unwrap().len() <= 20);
+            }
+            let usage = limiter.usage(start + Duration::from_secs(1));
+            assert!(usage
+                .iter()
+                .any(|usage| usage.client == IpAddr::from([10, 0, 0, 99])));
+            assert!(!usage
+                .iter()
+                .any(|usage| usage.client == IpAddr::from([10, 0, 0, 0])));
+        }
+    }
+
+    #[test]
+    fn test_client_ip() {
+        let request = |forwarded_for: &str| {
+            let mut request = Request::new(axum::body::Body::empty());
+            request
+                .headers_mut()
+                .insert("x-forwarded-for", forwarded_for.parse().unwrap());
+            request
+                .extensions_mut()
+                .insert(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 4000))));
+            request
+        };
+        // The client may send a header of its own, that the proxy appends to
+        let spoofed = request("1.2.3.4, 203.0.113.7");
+        assert_eq!(client_ip(&spoofed, true), "203.0.113.7".parse().ok());
+        assert_eq!(client_ip(&spoofed, false), "127.0.0.1".parse().ok());
+        assert_eq!(client_ip(&request("junk"), true), "127.0.0.1".parse().ok());
+    }
+
+    #[tokio::test]
+    async fn test_limit() {
+        let mut config = Config::default();
+        config.server.rate_limit = 2;
+        let app = crate::app(AppState::new(config).unwrap())
+            .into_make_service_with_connect_info::<SocketAddr>();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/api/jobs/unknown", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let client = reqwest::Client::new();
+        for _ in 0..2 {
+            let response = client.get(&url).send().await.unwrap();
+            assert_eq!(response.status().as_u16(), 404);
+        }
+        let response = client.get(&url).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 429);
+        assert_eq!(response.headers()["retry-after"], "30");
+    }
+}
@@ -0,0 +1,422 @@
+//! Pages analyzed again and again, to notice when they change
+//!
+//! A schedule fetches its URL every `interval_secs`, starting right away,
+//! records each analysis in the history and compares it with the previous
+//! run of the URL, notifying the webhooks of changes and of newly violated
+//! `server.alert_rules`. Schedules come from `server.schedules` and from
+//! `POST /api/admin/schedules`, as they fetch pages and notify the webhooks
+//! on the operator's behalf; `GET /api/schedules/:id` shows the latest
+//! diff. Ids are random like those of jobs, so anyone knowing one can see
+//! the status of its schedule, but only of that one.
+
+use axum::{
+    extract::{Path, State},
+    http::{header::LOCATION, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use ferret::analyzer::AnalysisResult;
+use ferret::diff::AnalysisDiff;
+use ferret::rules::{Rule, Violation};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::AbortHandle;
+use tokio::time::MissedTickBehavior;
+use tracing::Instrument;
+
+use crate::history::unix_time;
+use crate::history::Run;
+use crate::logging::new_request_id;
+use crate::webhooks::{self, ChangeEvent, Event, ViolationEvent};
+use crate::{analyze_page, AppState, FetchParams};
+
+/// JSON body of `POST /api/admin/schedules`
+#[derive(Debug, Deserialize)]
+pub struct ScheduleRequest {
+    pub url: String,
+    pub interval_secs: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScheduleStatus {
+    pub id: String,
+    pub url: String,
+    pub interval_secs: u64,
+    /// Set in `server.schedules`, so not deleted over the API
+    pub configured: bool,
+    /// Analyses attempted so far, failed ones included
+    pub checks: usize,
+    /// When the page was last fetched, in seconds since the Unix epoch
+    pub last_checked: Option<u64>,
+    /// History run of the latest analysis
+    pub last_run: Option<u64>,
+    /// Why the latest analysis failed
+    pub error: Option<String>,
+    /// Whether the latest analysis differs from the run before it
+    pub changed: Option<bool>,
+    pub diff: Option<AnalysisDiff>,
+}
+
+struct Schedule {
+    status: Arc<Mutex<ScheduleStatus>>,
+    task: AbortHandle,
+}
+
+impl Drop for Schedule {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[derive(Default)]
+pub struct Schedules {
+    entries: Mutex<BTreeMap<String, Schedule>>,
+}
+
+impl Schedules {
+    pub fn list(&self) -> Vec<ScheduleStatus> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .values()
+            .map(|schedule| schedule.status.lock().unwrap().clone())
+            .collect()
+    }
+
+    pub fn get(&self, id: &str) -> Option<ScheduleStatus> {
+        let entries = self.entries.lock().unwrap();
+        let schedule = entries.get(id)?;
+        let status = schedule.status.lock().unwrap().clone();
+        Some(status)
+    }
+
+    /// Stop the schedule `id`, unless it is missing or configured
+    pub fn remove(&self, id: &str) -> Result<(), (StatusCode, String)> {
+        let mut entries = self.entries.lock().unwrap();
+        let Some(schedule) = entries.get(id) else {
+            return Err((StatusCode::NOT_FOUND, format!("Schedule {} not found", id)));
+        };
+        if schedule.status.lock().unwrap().configured {
+            return Err((
+                StatusCode::CONFLICT,
+                format!("Schedule {} is configured, remove it from the config", id),
+            ));
+        }
+        entries.remove(id);
+        Ok(())
+    }
+}
+
+/// Start the schedules of `server.schedules`
+pub fn start_configured(state: &AppState) -> anyhow::Result<()> {
+    for schedule in &state.config.server.schedules {
+        let request = ScheduleRequest {
+            url: schedule.url.clone(),
+            interval_secs: schedule.interval_secs,
+        };
+        if let Err((_, reason)) = insert(state, request, true) {
+            anyhow::bail!("Invalid schedule of {}: {}", schedule.url, reason);
+        }
+    }
+    Ok(())
+}
+
+/// Start analyzing `request.url` on its interval
+pub fn add(
+    state: &AppState,
+    request: ScheduleRequest,
+) -> Result<ScheduleStatus, (StatusCode, String)> {
+    insert(state, request, false)
+}
+
+fn insert(
+    state: &AppState,
+    request: ScheduleRequest,
+    configured: bool,
+) -> Result<ScheduleStatus, (StatusCode, String)> {
+    state
+        .check_url(&request.url)
+        .map_err(|(status, reason)| (status, reason.to_string()))?;
+    let min_secs = state.config.server.min_schedule_secs.max(1);
+    if request.interval_secs < min_secs {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("interval_secs must be at least {}", min_secs),
+        ));
+    }
+
+    let mut entries = state.schedules.entries.lock().unwrap();
+    if entries.len() >= state.config.server.max_schedules {
+        return Err((
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Too many schedules, delete one first".to_string(),
+        ));
+    }
+    let id = new_request_id();
+    let status = Arc::new(Mutex::new(ScheduleStatus {
+        id: id.clone(),
+        url: request.url,
+        interval_secs: request.interval_secs,
+        configured,
+        checks: 0,
+        last_checked: None,
+        last_run: None,
+        error: None,
+        changed: None,
+        diff: None,
+    }));
+    // Not a child of the request span, as the schedule outlives the request
+    let span = tracing::info_span!(parent: None, "schedule", id = %id);
+    let task =
+        tokio::spawn(run(state.clone(), Arc::clone(&status)).instrument(span)).abort_handle();
+    let snapshot = status.lock().unwrap().clone();
+    entries.insert(id, Schedule { status, task });
+    Ok(snapshot)
+}
+
+async fn run(state: AppState, status: Arc<Mutex<ScheduleStatus>>) {
+    let (url, interval_secs) = {
+        let status = status.lock().unwrap();
+        (status.url.clone(), status.interval_secs)
+    };
+    let mut ticks = tokio::time::interval(Duration::from_secs(interval_secs));
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        ticks.tick().await;
+        check(&state, &url, &status).await;
+    }
+}
+
+/// Analyze `url` once and compare it with its previous run
+async fn check(state: &AppState, url: &str, status: &Mutex<ScheduleStatus>) {
+    let options = FetchParams::default().to_options(&HeaderMap::new(), state);
+    let limits = state.limits();
+    let analysis = async {
+        let client = options.build_client()?;
+        analyze_page(
+            &options,
+            &client,
+            url,
+            &limits,
+            state.config.analyzer.top_values,
+            state.fetch_timeout(),
+            &state.metrics,
+        )
+        .await
+    }
+    .await;
+    let previous = state.history.runs(url).pop();
+
+    let run = analysis
+        .as_ref()
+        .ok()
+        .and_then(|result| state.record(url, result));
+    let mut status = status.lock().unwrap();
+    status.checks += 1;
+    status.last_checked = Some(unix_time());
+    match analysis {
+        Ok(result) => {
+            let diff = previous
+                .as_ref()
+                .map(|previous| AnalysisDiff::new(&previous.result, &result));
+            if let (Some(previous), Some(run), Some(diff)) = (&previous, run, &diff) {
+                let event = ChangeEvent::new(state, &status.id, url, (previous.id, run), diff);
+                webhooks::notify(state, Event::Change(event));
+            }
+            let violations = new_violations(
+                &state.config.server.alert_rules,
+                &result,
+                previous.as_deref(),
+            );
+            if let (Some(run), false) = (run, violations.is_empty()) {
+                let event = ViolationEvent::new(state, &status.id, url, run, violations);
+                webhooks::notify(state, Event::Violation(event));
+            }
+            status.changed = diff.as_ref().map(|diff| !diff.is_empty());
+            status.diff = diff;
+            status.last_run = run;
+            status.error = None;
+        }
+        Err(err) => {
+            tracing::warn!("Failed to analyze {}: {:#}", url, err);
+            status.error = Some(format!("{:#}", err));
+        }
+    }
+}
+
+/// Violations of `rules` by `result` that the previous run didn't have
+fn new_violations(
+    rules: &[Rule],
+    result: &AnalysisResult,
+    previous: Option<&Run>,
+) -> Vec<Violation> {
+    rules
+        .iter()
+        .filter(|rule| previous.is_none_or(|previous| rule.check(&previous.result).is_none()))
+        .filter_map(|rule| rule.check(result))
+        .collect()
+}
+
+pub async fn handler_create_schedule(
+    State(state): State<AppState>,
+    Json(request): Json<ScheduleRequest>,
+) -> Response {
+    match add(&state, request) {
+        Ok(status) => (
+            StatusCode::CREATED,
+            [(LOCATION, format!("/api/schedules/{}", status.id))],
+            Json(status),
+        )
+            .into_response(),
+        Err(refused) => refused.into_response(),
+    }
+}
+
+pub async fn handler_schedule(Path(id): Path<String>, State(state): State<AppState>) -> Response {
+    match state.schedules.get(&id) {
+        Some(status) => Json(status).into_response(),
+        None => (StatusCode::NOT_FOUND, format!("Schedule {} not found", id)).into_response(),
+    }
+}
+
+/// `409 Conflict` for configured schedules
+pub async fn handler_delete_schedule(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+) -> Response {
+    match state.schedules.remove(&id) {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(refused) => refused.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::serve_site;
+    use ferret::config::Config;
+    use serde_json::json;
+    use std::net::SocketAddr;
+
+    #[tokio::test]
+    async fn test_schedule() {
+        let base = serve_site().await;
+        let mut config = Config::default();
+        config.server.allow_private = true;
+        config.server.min_schedule_secs = 1;
+        config.server.max_schedules = 1;
+        let state = AppState::new(config).unwrap();
+        let request = |url: String, interval_secs| ScheduleRequest { url, interval_secs };
+
+        let url = format!("{}/counter", base);
+        let status = add(&state, request(url.clone(), 1)).unwrap();
+        let refused = add(&state, request(url.clone(), 1)).unwrap_err();
+        assert_eq!(refused.0, StatusCode::SERVICE_UNAVAILABLE);
+
+        let status = loop {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            let status = state.schedules.get(&status.id).unwrap();
+            if status.checks >= 2 {
+                break status;
+            }
+        };
+        assert_eq!(status.error, None);
+        assert_eq!(status.changed, Some(true));
+        let p = status
+            .diff
+            .unwrap()
+            .tags
+            .into_iter()
+            .find(|tag| tag.name == "p");
+        assert_eq!(p.map(|p| (p.old_count, p.new_count)), Some((1, 2)));
+        assert_eq!(state.history.runs(&url).len(), 2);
+
+        assert!(state.schedules.remove(&status.id).is_ok());
+        let missing = state.schedules.remove(&status.id).unwrap_err();
+        assert_eq!(missing.0, StatusCode::NOT_FOUND);
+        let refused = add(&state, request(url, 0)).unwrap_err();
+        assert_eq!(refused.0, StatusCode::BAD_REQUEST);
+        let refused = add(&state, request("ftp://example.com".to_string(), 60)).unwrap_err();
+        assert_eq!(refused.0, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_schedule_api() {
+        let site = serve_site().await;
+        let mut config = Config::default();
+        config.server.allow_private = true;
+        config.server.admin_token = Some("secret".to_string());
+        config.server.schedules = vec![ferret::config::ScheduleConfig {
+            url: format!("{}/page", site),
+            interval_secs: 3600,
+        }];
+        let state = AppState::new(config).unwrap();
+        start_configured(&state).unwrap();
+        let configured = state.schedules.list().pop().unwrap();
+        assert!(configured.configured);
+        assert_eq!(configured.id.len(), 16);
+        let app = crate::app(state.clone()).into_make_service_with_connect_info::<SocketAddr>();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let server = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        let client = reqwest::Client::new();
+        let request = json!({ "url": format!("{}/page", site), "interval_secs": 60 });
+        let base = format!("{}/api/admin/schedules", server);
+        let configured_url = format!("{}/{}", base, configured.id);
+
+        let created = client.post(&base).json(&request).send().await.unwrap();
+        assert_eq!(created.status().as_u16(), 401);
+        let deleted = client.delete(&configured_url).send().await.unwrap();
+        assert_eq!(deleted.status().as_u16(), 401);
+        for public in [
+            "/api/schedules".to_string(),
+            format!("/api/schedules/{}", configured.id),
+        ] {
+            let deleted = client.delete(format!("{}{}", server, public)).send().await;
+            assert!(deleted.unwrap().status().is_client_error());
+        }
+        let status = client
+            .get(format!("{}/api/schedules/{}", server, configured.id))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(status.status().as_u16(), 200);
+
+        let deleted = client.delete(&configured_url).bearer_auth("secret").send();
+        assert_eq!(deleted.await.unwrap().status().as_u16(), 409);
+        let created = client.post(&base).bearer_auth("secret").json(&request);
+        let created: serde_json::Value = created.send().await.unwrap().json().await.unwrap();
+        assert_eq!(created["configured"], false);
+        let deleted = client
+            .delete(format!("{}/{}", base, created["id"].as_str().unwrap()))
+            .bearer_auth("secret")
+            .send();
+        assert_eq!(deleted.await.unwrap().status().as_u16(), 204);
+        assert_eq!(state.schedules.list().len(), 1);
+    }
+
+    #[test]
+    fn test_new_violations() {
+        let analyze =
+            |html| crate::analyze_html(html, &ferret::limits::Limits::untrusted(), 10).unwrap();
+        let rules: Vec<Rule> = ["count(p) > 1", "count(img) > 0"]
+            .iter()
+            .map(|rule| rule.parse().unwrap())
+            .collect();
+        let previous = Run {
+            id: 1,
+            source: "https://a.test/".to_string(),
+            timestamp: 0,
+            result: analyze("<p>a</p><p>b</p>"),
+        };
+        let result = analyze("<p>a</p><p>b</p><img>");
+        let rules_of =
+            |violations: Vec<Violation>| violations.into_iter().map(|v| v.rule).collect::<Vec<_>>();
+        assert_eq!(
+            rules_of(new_violations(&rules, &result, Some(&previous))),
+            ["count(img) > 0"]
+        );
+        assert_eq!(rules_of(new_violations(&rules, &result, None)).len(), 2);
+    }
+}
@@ -0,0 +1,209 @@
+//! Stopping the server without dropping work
+//!
+//! On SIGTERM or SIGINT the server stops accepting connections and starting
+//! queued jobs. Requests in flight and running jobs then get
+//! `server.shutdown_timeout_secs` to finish. The jobs still unfinished are
+//! saved to `server.jobs_path` and [`resume`]d under the same ids when the
+//! server starts again, from the start.
+
+use anyhow::{Context, Result};
+use axum::Router;
+use futures::FutureExt;
+use std::future::{Future, IntoFuture};
+use std::io::ErrorKind;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::time::Instant;
+
+use crate::jobs::{self, Checkpoint};
+use crate::AppState;
+
+/// Serve `app` until the process is told to stop, then wind down
+pub async fn serve(listener: TcpListener, app: Router, state: &AppState) -> Result<()> {
+    serve_until(listener, app, state, signal()).await
+}
+
+/// Serve `app` until `stop` resolves, then wait for requests and running
+/// jobs and save the unfinished jobs
+async fn serve_until(
+    listener: TcpListener,
+    app: Router,
+    state: &AppState,
+    stop: impl Future<Output = ()> + Send + 'static,
+) -> Result<()> {
+    let stop = stop.shared();
+    let server = axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(stop.clone())
+    .into_future();
+    tokio::pin!(server);
+    let stopped = tokio::select! {
+        result = &mut server => {
+            result?;
+            false
+        }
+        () = stop => true,
+    };
+
+    let timeout = Duration::from_secs(state.config.server.shutdown_timeout_secs);
+    let deadline = Instant::now() + timeout;
+    state.jobs.close();
+    if stopped {
+        match tokio::time::timeout_at(deadline, &mut server).await {
+            Ok(result) => result?,
+            Err(_) => tracing::warn!("Dropped the requests still running after {:?}", timeout),
+        }
+    }
+    state.jobs.drain(deadline).await;
+    checkpoint(state)
+}
+
+/// Resolves on SIGINT, or SIGTERM on Unix
+async fn signal() {
+    let terminate = async {
+        #[cfg(unix)]
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+                return;
+            }
+            Err(err) => tracing::error!("Failed to listen for SIGTERM: {}", err),
+        }
+        std::future::pending::<()>().await
+    };
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        () = terminate => {}
+    }
+    tracing::info!("Shutting down");
+}
+
+/// Save the unfinished jobs to `server.jobs_path`
+fn checkpoint(state: &AppState) -> Result<()> {
+    let (checkpoints, missing) = state.jobs.checkpoints();
+    if missing > 0 {
+        tracing::warn!("Dropped {} unfinished jobs given credentials", missing);
+    }
+    let Some(path) = &state.config.server.jobs_path else {
+        if !checkpoints.is_empty() {
+            tracing::warn!(
+                "Dropped {} unfinished jobs, set server.jobs_path to keep them",
+                checkpoints.len()
+            );
+        }
+        return Ok(());
+    };
+    save(path, &checkpoints)?;
+    tracing::info!(
+        "Saved {} unfinished jobs to {}",
+        checkpoints.len(),
+        path.display()
+    );
+    Ok(())
+}
+
+fn save(path: &Path, checkpoints: &[Checkpoint]) -> Result<()> {
+    // Written aside and renamed, so a crash can't leave half a file
+    let partial = path.with_extension("partial");
+    std::fs::write(&partial, serde_json::to_vec_pretty(checkpoints)?)
+        .with_context(|| format!("Failed to write {}", partial.display()))?;
+    std::fs::rename(&partial, path).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Queue the jobs saved at the last shutdown again, removing the file
+pub fn resume(state: &AppState) -> Result<()> {
+    let Some(path) = &state.config.server.jobs_path else {
+        return Ok(());
+    };
+    let checkpoints: Vec<Checkpoint> = match std::fs::read(path) {
+        Ok(bytes) => serde_json::from_slice(&bytes)
+            .with_context(|| format!("Invalid jobs file {}", path.display()))?,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err).with_context(|| format!("Failed to read {}", path.display())),
+    };
+    for checkpoint in checkpoints {
+        let id = checkpoint.id.clone();
+        match jobs::resume(state, checkpoint) {
+            Ok(()) => tracing::info!("Resumed job {}", id),
+            Err((_, reason)) => tracing::warn!("Failed to resume job {}: {}", id, reason),
+        }
+    }
+    // Jobs still unfinished at the next shutdown are saved again
+    std::fs::remove_file(path).with_context(|| format!("Failed to remove {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::serve_site;
+    use ferret::config::Config;
+
+    #[tokio::test]
+    async fn test_serve_until() {
+        let site = serve_site().await;
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = Config::default();
+        config.server.allow_private = true;
+        config.server.job_concurrency = 1;
+        config.server.jobs_path = Some(dir.path().join("jobs.json"));
+        let state = AppState::new(config.clone()).unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+        let server = {
+            let state = state.clone();
+            tokio::spawn(async move {
+                let stop = async {
+                    let _ = stopped.await;
+                };
+                serve_until(listener, crate::app(state.clone()), &state, stop).await
+            })
+        };
+
+        let client = reqwest::Client::new();
+        let mut ids = Vec::new();
+        for page in ["slow", "page"] {
+            let job: serde_json::Value = client
+                .post(format!("{}/api/jobs", base))
+                .json(&serde_json::json!({"kind": "batch", "urls": [format!("{}/{}", site, page)]}))
+                .send()
+                .await
+                .unwrap()
+                .json()
+                .await
+                .unwrap();
+            ids.push(job["id"].as_str().unwrap().to_string());
+        }
+        let report = tokio::spawn(
+            client
+                .get(format!("{}/api/report/{}/slow", base, site))
+                .send(),
+        );
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        stop.send(()).unwrap();
+        server.await.unwrap().unwrap();
+
+        // The request in flight and the running job finished, the queued
+        // job never started
+        assert_eq!(report.await.unwrap().unwrap().status().as_u16(), 200);
+        let status = |id: &str| state.jobs.get(id).unwrap().status().state;
+        assert_eq!(status(&ids[0]), jobs::JobState::Completed);
+        assert_eq!(status(&ids[1]), jobs::JobState::Queued);
+
+        let resumed = AppState::new(config).unwrap();
+        resume(&resumed).unwrap();
+        assert!(resumed.jobs.get(&ids[0]).is_none());
+        let job = resumed.jobs.get(&ids[1]).unwrap();
+        let mut updates = job.events();
+        while updates.next().await.is_some() {}
+        assert_eq!(job.status().state, jobs::JobState::Completed);
+        assert!(!dir.path().join("jobs.json").exists());
+        // Nothing to resume
+        resume(&resumed).unwrap();
+    }
+}
@@ -0,0 +1,320 @@
+//! Notifications of scheduled pages that changed or broke a rule
+//!
+//! When a scheduled analysis differs from the previous run of its URL in at
+//! least `server.webhook_min_changes` tags, each of `server.webhooks` is
+//! sent a [`ChangeEvent`] as a JSON `POST`; when it starts violating some of
+//! `server.alert_rules`, a [`ViolationEvent`]. Webhooks in the `slack` or
+//! `discord` format get a chat message instead, see [`crate::notifiers`].
+//!
+//! Webhooks with a secret get the HMAC-SHA256 of the body in
+//! `X-Ferret-Signature: sha256=<hex>`. Network errors, `429` and `5xx`
+//! answers are retried with doubling delays.
+
+use anyhow::Result;
+use ferret::config::{WebhookConfig, WebhookFormat};
+use ferret::diff::{AnalysisDiff, TagDiff};
+use ferret::rules::Violation;
+use serde::Serialize;
+use std::time::Duration;
+use tracing::Instrument;
+
+use crate::{notifiers, AppState};
+
+/// Deliveries attempted per webhook and notification
+const ATTEMPTS: u32 = 4;
+/// Delay before the first retry, doubled for each one after it
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+const TIMEOUT: Duration = Duration::from_secs(10);
+/// Changed tags listed in a notification
+const TOP_CHANGES: usize = 10;
+
+pub const SIGNATURE_HEADER: &str = "x-ferret-signature";
+
+/// Body of the notification of a changed page
+#[derive(Debug, Clone, Serialize)]
+pub struct ChangeEvent {
+    /// Always `change`
+    pub event: &'static str,
+    pub schedule: String,
+    pub source: String,
+    /// History runs compared
+    pub previous_run: u64,
+    pub run: u64,
+    /// Tags added, removed or changed
+    pub changes: usize,
+    pub old_max_depth: usize,
+    pub new_max_depth: usize,
+    /// The changed tags with the largest count changes first, at most 10
+    pub tags: Vec<TagDiff>,
+    pub links: Links,
+}
+
+/// Body of the notification of a page violating rules it didn't before
+#[derive(Debug, Clone, Serialize)]
+pub struct ViolationEvent {
+    /// Always `violation`
+    pub event: &'static str,
+    pub schedule: String,
+    pub source: String,
+    pub run: u64,
+    /// The rules newly violated
+    pub violations: Vec<Violation>,
+    pub links: Links,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum Event {
+    Change(ChangeEvent),
+    Violation(ViolationEvent),
+}
+
+impl Event {
+    pub fn links(&self) -> &Links {
+        match self {
+            Event::Change(event) => &event.links,
+            Event::Violation(event) => &event.links,
+        }
+    }
+}
+
+/// Server pages about the event, absolute when `server.public_url` is set
+#[derive(Debug, Clone, Serialize)]
+pub struct Links {
+    pub report: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diff: Option<String>,
+    pub history: String,
+}
+
+impl Links {
+    fn new(state: &AppState, source: &str, runs: Option<(u64, u64)>) -> Self {
+        let base = state.config.server.public_url.as_deref().unwrap_or("");
+        let base = base.trim_end_matches('/');
+        let encoded: String = url::form_urlencoded::byte_serialize(source.as_bytes()).collect();
+        Self {
+            report: format!("{}/api/report/{}", base, source),
+            diff: runs.map(|(old, new)| format!("{}/api/diff?a={}&b={}", base, old, new)),
+            history: format!("{}/api/history?url={}", base, encoded),
+        }
+    }
+}
+
+impl ChangeEvent {
+    pub fn new(
+        state: &AppState,
+        schedule: &str,
+        source: &str,
+        (previous_run, run): (u64, u64),
+        diff: &AnalysisDiff,
+    ) -> Self {
+        let mut tags: Vec<TagDiff> = diff.changes().cloned().collect();
+        let changes = tags.len();
+        tags.sort_by_key(|tag| std::cmp::Reverse(tag.delta().unsigned_abs()));
+        tags.truncate(TOP_CHANGES);
+        Self {
+            event: "change",
+            schedule: schedule.to_string(),
+            source: source.to_string(),
+            previous_run,
+            run,
+            changes,
+            old_max_depth: diff.old_max_depth,
+            new_max_depth: diff.new_max_depth,
+            tags,
+            links: Links::new(state, source, Some((previous_run, run))),
+        }
+    }
+}
+
+impl ViolationEvent {
+    pub fn new(
+        state: &AppState,
+        schedule: &str,
+        source: &str,
+        run: u64,
+        violations: Vec<Violation>,
+    ) -> Self {
+        Self {
+            event: "violation",
+            schedule: schedule.to_string(),
+            source: source.to_string(),
+            run,
+            violations,
+            links: Links::new(state, source, None),
+        }
+    }
+}
+
+/// Send `event` to every configured webhook in the background, unless it
+/// is a change of too few tags
+pub fn notify(state: &AppState, event: Event) {
+    let server = &state.config.server;
+    if let Event::Change(change) = &event {
+        if change.changes < server.webhook_min_changes.max(1) {
+            return;
+        }
+    }
+    for webhook in server.webhooks.clone() {
+        let payload = match webhook.format {
+            WebhookFormat::Json => serde_json::to_value(&event),
+            WebhookFormat::Slack => Ok(notifiers::slack(&event)),
+            WebhookFormat::Discord => Ok(notifiers::discord(&event)),
+        };
+        let body = match payload.and_then(|payload| serde_json::to_vec(&payload)) {
+            Ok(body) => body,
+            Err(err) => return tracing::error!("Failed to serialize a notification: {}", err),
+        };
+        let span = tracing::info_span!("webhook", url = %webhook.url);
+        tokio::spawn(
+            async move {
+                match deliver(&webhook, body, RETRY_DELAY).await {
+                    Ok(()) => tracing::debug!("Notified"),
+                    Err(err) => tracing::warn!("Failed to notify: {:#}", err),
+                }
+            }
+            .instrument(span),
+        );
+    }
+}
+
+/// POST `body` to `webhook`, retrying failures after `retry_delay`,
+/// doubled each time
+async fn deliver(webhook: &WebhookConfig, body: Vec<u8>, retry_delay: Duration) -> Result<()> {
+    let client = reqwest::Client::builder().timeout(TIMEOUT).build()?;
+    let signature = webhook.secret.as_ref().map(|secret| sign(secret, &body));
+    let mut delay = retry_delay;
+    for attempt in 1..=ATTEMPTS {
+        let mut request = client
+            .post(&webhook.url)
+            .header("Content-Type", "application/json")
+            .body(body.clone());
+        if let Some(signature) = &signature {
+            request = request.header(SIGNATURE_HEADER, signature);
+        }
+        let error = match request.send().await {
+            Ok(response) if response.status().is_success() => return Ok(()),
+            Ok(response) => {
+                let status = response.status();
+                let retryable = status.is_server_error() || status.as_u16() == 429;
+                if !retryable {
+                    anyhow::bail!("HTTP error: {}", status);
+                }
+                anyhow::anyhow!("HTTP error: {}", status)
+            }
+            Err(err) => err.into(),
+        };
+        if attempt == ATTEMPTS {
+            return Err(error.context(format!("Gave up after {} attempts", ATTEMPTS)));
+        }
+        tracing::debug!(attempt, "Retrying: {:#}", error);
+        tokio::time::sleep(delay).await;
+        delay *= 2;
+    }
+    unreachable!("the last attempt returns")
+}
+
+/// `sha256=` and the hex HMAC-SHA256 of `body` keyed with `secret`
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let key = ring::hmac::Key::new(ring::hmac::HMAC_SHA256, secret.as_bytes());
+    let tag = ring::hmac::sign(&key, body);
+    let hex: String = tag
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("sha256={}", hex)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{http::HeaderMap, routing::post, Router};
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_sign() {
+        // RFC 4231, test case 2
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_deliver() {
+        // Fails once, then records what it receives
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let receiver = {
+            let received = Arc::clone(&received);
+            Router::new().route(
+                "/hook",
+                post(move |headers: HeaderMap, body: String| async move {
+                    let mut received = received.lock().unwrap();
+                    received.push((
+                        headers[SIGNATURE_HEADER].to_str().unwrap().to_string(),
+                        body,
+                    ));
+                    match received.len() {
+                        1 => axum::http::StatusCode::SERVICE_UNAVAILABLE,
+                        _ => axum::http::StatusCode::NO_CONTENT,
+                    }
+                }),
+            )
+        };
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, receiver).await });
+
+        let webhook = WebhookConfig {
+            url: format!("{}/hook", base),
+            secret: Some("key".to_string()),
+            format: WebhookFormat::Json,
+        };
+        let body = br#"{"event":"change"}"#.to_vec();
+        deliver(&webhook, body.clone(), Duration::from_millis(10))
+            .await
+            .unwrap();
+        {
+            let received = received.lock().unwrap();
+            assert_eq!(received.len(), 2);
+            assert_eq!(received[1].0, sign("key", &body));
+            assert_eq!(received[1].1, r#"{"event":"change"}"#);
+        }
+
+        let missing = WebhookConfig {
+            url: format!("{}/missing", base),
+            secret: None,
+            format: WebhookFormat::Json,
+        };
+        let err = deliver(&missing, body, Duration::from_millis(10))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("404"), "{}", err);
+    }
+
+    #[test]
+    fn test_change_event() {
+        let mut config = ferret::config::Config::default();
+        config.server.public_url = Some("https://ferret.test/".to_string());
+        let state = AppState::new(config).unwrap();
+        let analyze =
+            |html| crate::analyze_html(html, &ferret::limits::Limits::untrusted(), 10).unwrap();
+        let diff = AnalysisDiff::new(
+            &analyze("<p>a</p><i>b</i>"),
+            &analyze("<p>a</p><p>b</p><p>c</p>"),
+        );
+        let event = ChangeEvent::new(&state, "1", "https://a.test/?q=1", (4, 7), &diff);
+        assert_eq!(event.changes, 2);
+        let tags: Vec<_> = event.tags.iter().map(|tag| tag.name.as_str()).collect();
+        assert_eq!(tags, ["p", "i"]);
+        assert_eq!(
+            event.links.diff.as_deref(),
+            Some("https://ferret.test/api/diff?a=4&b=7")
+        );
+        assert_eq!(
+            event.links.history,
+            "https://ferret.test/api/history?url=https%3A%2F%2Fa.test%2F%3Fq%3D1"
+        );
+    }
+}
@@ -0,0 +1,459 @@
+//! Jobs followed live over a WebSocket
+//!
+//! Clients connect to `/api/ws` and send a job request as their first text
+//! message, like the body of `POST /api/jobs`, or connect to
+//! `/api/ws?job=ID` to follow a job already queued. The server then sends
+//! each [`JobEvent`](crate::jobs::JobEvent) as a JSON text message, so a dashboard can show pages
+//! as they finish with the running tag totals, and closes the connection
+//! once the job has finished; its analysis is at `GET /api/jobs/:id/result`.
+//! A refused request gets an `{"event": "error", "message": ...}` message.
+//!
+//! Only the part of RFC 6455 this needs is implemented: text and control
+//! frames, without extensions or subprotocols.
+
+use anyhow::{bail, Result};
+use axum::{
+    body::Body,
+    extract::{Query, Request, State},
+    http::{
+        header::{
+            CONNECTION, SEC_WEBSOCKET_ACCEPT, SEC_WEBSOCKET_KEY, SEC_WEBSOCKET_VERSION, UPGRADE,
+        },
+        HeaderMap, HeaderValue, StatusCode,
+    },
+    response::{IntoResponse, Response},
+};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use hyper_util::rt::TokioIo;
+use serde::Deserialize;
+use std::fmt;
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc;
+
+use crate::jobs::{self, Job, JobRequest};
+use crate::{AppState, FetchParams};
+
+/// Appended to the client's key to prove the server speaks WebSocket
+const HANDSHAKE_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Largest message accepted from a client, more than any job request needs
+const MAX_MESSAGE_BYTES: usize = 1024 * 1024;
+
+const OPCODE_CONTINUATION: u8 = 0x0;
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xA;
+
+const CLOSE_NORMAL: u16 = 1000;
+const CLOSE_PROTOCOL_ERROR: u16 = 1002;
+const CLOSE_POLICY_VIOLATION: u16 = 1008;
+const CLOSE_TOO_BIG: u16 = 1009;
+
+#[derive(Deserialize)]
+pub struct WsParams {
+    /// Follow this job instead of starting one
+    job: Option<String>,
+    #[serde(flatten)]
+    fetch: FetchParams,
+}
+
+/// Upgrade to a WebSocket streaming the events of a job
+pub async fn handler_ws(
+    Query(params): Query<WsParams>,
+    State(state): State<AppState>,
+    mut request: Request,
+) -> Response {
+    let Some(accept) = handshake_key(request.headers()).map(|key| accept_key(key.as_bytes()))
+    else {
+        return (
+            StatusCode::UPGRADE_REQUIRED,
+            [(UPGRADE, "websocket")],
+            "Expected a WebSocket upgrade",
+        )
+            .into_response();
+    };
+    let job = match &params.job {
+        Some(id) => match state.jobs.get(id) {
+            Some(job) => Some(job),
+            None => return (StatusCode::NOT_FOUND, format!("No job {}", id)).into_response(),
+        },
+        None => None,
+    };
+    let headers = request.headers().clone();
+
+    let upgrade = hyper::upgrade::on(&mut request);
+    tokio::spawn(async move {
+        if let Ok(upgraded) = upgrade.await {
+            let _ = session(TokioIo::new(upgraded), state, job, params.fetch, headers).await;
+        }
+    });
+    Response::builder()
+        .status(StatusCode::SWITCHING_PROTOCOLS)
+        .header(UPGRADE, "websocket")
+        .header(CONNECTION, "upgrade")
+        .header(SEC_WEBSOCKET_ACCEPT, accept)
+        .body(Body::empty())
+        .unwrap()
+}
+
+/// The `Sec-WebSocket-Key` of a version 13 upgrade request
+fn handshake_key(headers: &HeaderMap) -> Option<&str> {
+    let has_token = |name, token: &str| {
+        headers.get_all(name).iter().any(|value: &HeaderValue| {
+            value.to_str().is_ok_and(|value| {
+                value
+                    .split(',')
+                    .any(|t| t.trim().eq_ignore_ascii_case(token))
+            })
+        })
+    };
+    if !has_token(CONNECTION, "upgrade")
+        || !has_token(UPGRADE, "websocket")
+        || headers
+            .get(SEC_WEBSOCKET_VERSION)
+            .map(HeaderValue::as_bytes)
+            != Some(b"13")
+    {
+        return None;
+    }
+    headers.get(SEC_WEBSOCKET_KEY)?.to_str().ok()
+}
+
+/// `Sec-WebSocket-Accept` answering the client's `key`
+fn accept_key(key: &[u8]) -> String {
+    let mut input = key.to_vec();
+    input.extend_from_slice(HANDSHAKE_GUID.as_bytes());
+    let digest = ring::digest::digest(&ring::digest::SHA1_FOR_LEGACY_USE_ONLY, &input);
+    BASE64.encode(digest.as_ref())
+}
+
+/// Messages from the client
+#[derive(Debug, PartialEq)]
+enum Incoming {
+    Text(String),
+    Ping(Vec<u8>),
+    /// The client closed the connection, with the code to answer
+    Close(u16),
+}
+
+async fn session<S>(
+    io: S,
+    state: AppState,
+    job: Option<Arc<Job>>,
+    params: FetchParams,
+    headers: HeaderMap,
+) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    let (reader, mut writer) = tokio::io::split(io);
+    let (sender, mut incoming) = mpsc::channel(16);
+    let reading = tokio::spawn(read_messages(reader, sender));
+
+    let events = match job {
+        Some(job) => Ok(job.events()),
+        None => match incoming.recv().await {
+            Some(Incoming::Text(text)) => serde_json::from_str::<JobRequest>(&text)
+                .map_err(|e| format!("Invalid job request: {}", e))
+                .and_then(|request| {
+                    jobs::start(&state, request, &params, &headers).map_err(|(_, reason)| reason)
+                }),
+            Some(Incoming::Close(code)) => {
+                reading.abort();
+                return close(&mut writer, code).await;
+            }
+            _ => Err("Expected a job request".to_string()),
+        },
+    };
+    let mut events = match events {
+        Ok(events) => events,
+        Err(message) => {
+            reading.abort();
+            let error = serde_json::json!({"event": "error", "message": message});
+            write_frame(&mut writer, OPCODE_TEXT, error.to_string().as_bytes(), None).await?;
+            return close(&mut writer, CLOSE_POLICY_VIOLATION).await;
+        }
+    };
+
+    let code = loop {
+        tokio::select! {
+            event = events.next() => match event {
+                Some(event) => {
+                    let text = serde_json::to_string(&event)?;
+                    write_frame(&mut writer, OPCODE_TEXT, text.as_bytes(), None).await?;
+                }
+                None => break CLOSE_NORMAL,
+            },
+            message = incoming.recv() => match message {
+                Some(Incoming::Ping(payload)) => {
+                    write_frame(&mut writer, OPCODE_PONG, &payload, None).await?;
+                }
+                Some(Incoming::Text(_)) => {}
+                Some(Incoming::Close(code)) => break code,
+                None => break CLOSE_NORMAL,
+            },
+        }
+    };
+    reading.abort();
+    close(&mut writer, code).await
+}
+
+async fn close<W: AsyncWrite + Unpin>(writer: &mut W, code: u16) -> Result<()> {
+    write_frame(writer, OPCODE_CLOSE, &code.to_be_bytes(), None).await?;
+    writer.shutdown().await?;
+    Ok(())
+}
+
+/// Forward the client's messages to `sender` until it closes the
+/// connection or breaks the protocol
+async fn read_messages<R: AsyncRead + Unpin>(mut reader: R, sender: mpsc::Sender<Incoming>) {
+    let mut message: Option<Vec<u8>> = None;
+    let code = loop {
+        let frame = match read_frame(&mut reader, MAX_MESSAGE_BYTES).await {
+            Ok(frame) => frame,
+            Err(e) if e.is::<FrameTooLarge>() => break CLOSE_TOO_BIG,
+            Err(_) => break CLOSE_PROTOCOL_ERROR,
+        };
+        // Clients must mask their frames
+        if !frame.masked {
+            break CLOSE_PROTOCOL_ERROR;
+        }
+        let incoming = match frame.opcode {
+            OPCODE_PING => Incoming::Ping(frame.payload),
+            OPCODE_PONG => continue,
+            OPCODE_CLOSE => break CLOSE_NORMAL,
+            OPCODE_TEXT | OPCODE_CONTINUATION => {
+                let buffer = match (frame.opcode, message.as_mut()) {
+                    (OPCODE_TEXT, None) => message.insert(frame.payload),
+                    (OPCODE_CONTINUATION, Some(buffer)) => {
+                        buffer.extend_from_slice(&frame.payload);
+                        buffer
+                    }
+                    _ => break CLOSE_PROTOCOL_ERROR,
+                };
+                if buffer.len() > MAX_MESSAGE_BYTES {
+                    break CLOSE_TOO_BIG;
+                }
+                if !frame.fin {
+                    continue;
+                }
+                match String::from_utf8(message.take().unwrap_or_default()) {
+                    Ok(text) => Incoming::Text(text),
+                    Err(_) => break CLOSE_PROTOCOL_ERROR,
+                }
+            }
+            // Binary messages aren't expected
+            _ => break CLOSE_PROTOCOL_ERROR,
+        };
+        if sender.send(incoming).await.is_err() {
+            return;
+        }
+    };
+    let _ = sender.send(Incoming::Close(code)).await;
+}
+
+/// A frame longer than the reader accepts, with its length
+#[derive(Debug)]
+struct FrameTooLarge(u64);
+
+impl fmt::Display for FrameTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Frame too large: {} bytes", self.0)
+    }
+}
+
+impl std::error::Error for FrameTooLarge {}
+
+#[derive(Debug, PartialEq)]
+struct Frame {
+    fin: bool,
+    opcode: u8,
+    masked: bool,
+    /// Unmasked
+    payload: Vec<u8>,
+}
+
+async fn read_frame<R: AsyncRead + Unpin>(reader: &mut R, max_len: usize) -> Result<Frame> {
+    let mut head = [0; 2];
+    reader.read_exact(&mut head).await?;
+    if head[0] & 0x70 != 0 {
+        bail!("Reserved bits set");
+    }
+    let len = match head[1] & 0x7F {
+        126 => reader.read_u16().await? as u64,
+        127 => reader.read_u64().await?,
+        len => len as u64,
+    };
+    if len > max_len as u64 {
+        return Err(FrameTooLarge(len).into());
+    }
+    let masked = head[1] & 0x80 != 0;
+    let mut mask = [0; 4];
+    if masked {
+        reader.read_exact(&mut mask).await?;
+    }
+    let mut payload = vec![0; len as usize];
+    reader.read_exact(&mut payload).await?;
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+    Ok(Frame {
+        fin: head[0] & 0x80 != 0,
+        opcode: head[0] & 0x0F,
+        masked,
+        payload,
+    })
+}
+
+/// Write a final frame, masked with `mask` as clients do
+async fn write_frame<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    opcode: u8,
+    payload: &[u8],
+    mask: Option<[u8; 4]>,
+) -> Result<()> {
+    let mut frame = vec![0x80 | opcode];
+    let mask_bit = if mask.is_some() { 0x80 } else { 0 };
+    match payload.len() {
+        len @ 0..=125 => frame.push(mask_bit | len as u8),
+        len @ 126..=0xFFFF => {
+            frame.push(mask_bit | 126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(mask_bit | 127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    match mask {
+        Some(mask) => {
+            frame.extend_from_slice(&mask);
+            frame.extend(
+                payload
+                    .iter()
+                    .enumerate()
+                    .map(|(i, byte)| byte ^ mask[i % 4]),
+            );
+        }
+        None => frame.extend_from_slice(payload),
+    }
+    writer.write_all(&frame).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{local_state, serve_site};
+    use tokio::io::{AsyncBufReadExt, BufReader};
+
+    #[test]
+    fn test_accept_key() {
+        // The example of RFC 6455, section 1.3
+        assert_eq!(
+            accept_key(b"dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[tokio::test]
+    async fn test_frames() {
+        let (mut client, mut server) = tokio::io::duplex(1 << 20);
+        let long = "x".repeat(70_000);
+        for payload in ["", "hello", &long[..200], &long] {
+            write_frame(
+                &mut client,
+                OPCODE_TEXT,
+                payload.as_bytes(),
+                Some([1, 2, 3, 4]),
+            )
+            .await
+            .unwrap();
+            let frame = read_frame(&mut server, 100_000).await.unwrap();
+            assert!(frame.fin && frame.masked);
+            assert_eq!(frame.opcode, OPCODE_TEXT);
+            assert_eq!(frame.payload, payload.as_bytes());
+        }
+        write_frame(&mut server, OPCODE_TEXT, long.as_bytes(), None)
+            .await
+            .unwrap();
+        let err = read_frame(&mut client, 1000).await.unwrap_err();
+        assert!(err.is::<FrameTooLarge>());
+    }
+
+    #[tokio::test]
+    async fn test_ws_job() {
+        let site = serve_site().await;
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, crate::app(local_state())).await });
+
+        let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let mut stream = BufReader::new(stream);
+        stream
+            .get_mut()
+            .write_all(
+                b"GET /api/ws HTTP/1.1\r\nHost: localhost\r\nConnection: Upgrade\r\n\
+                  Upgrade: websocket\r\nSec-WebSocket-Version: 13\r\n\
+                  Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n",
+            )
+            .await
+            .unwrap();
+        let mut response = Vec::new();
+        loop {
+            let mut line = String::new();
+            stream.read_line(&mut line).await.unwrap();
+            if line == "\r\n" {
+                break;
+            }
+            response.push(line.trim_end().to_ascii_lowercase());
+        }
+        assert!(response[0].starts_with("http/1.1 101"));
+        assert!(response.contains(&"sec-websocket-accept: s3pplmbitxaq9kygzzhzrbk+xoo=".into()));
+
+        let request = serde_json::json!({
+            "kind": "batch",
+            "urls": [format!("{}/page", site), format!("{}/list", site)],
+            "concurrency": 1,
+        });
+        let mask = Some([7, 7, 7, 7]);
+        write_frame(
+            &mut stream,
+            OPCODE_TEXT,
+            request.to_string().as_bytes(),
+            mask,
+        )
+        .await
+        .unwrap();
+
+        let mut events = Vec::new();
+        loop {
+            let frame = read_frame(&mut stream, MAX_MESSAGE_BYTES).await.unwrap();
+            assert!(!frame.masked);
+            if frame.opcode == OPCODE_CLOSE {
+                assert_eq!(frame.payload, CLOSE_NORMAL.to_be_bytes());
+                break;
+            }
+            let event: serde_json::Value = serde_json::from_slice(&frame.payload).unwrap();
+            events.push(event);
+        }
+        let kinds: Vec<_> = events.iter().map(|event| event["event"].clone()).collect();
+        assert_eq!(kinds.first().unwrap(), "status");
+        let pages: Vec<_> = events
+            .iter()
+            .filter(|event| event["event"] == "page")
+            .collect();
+        assert_eq!(pages.len(), 2);
+        assert_eq!(pages[1]["completed"], 2);
+        assert_eq!(pages[1]["tags"]["ul"], 1);
+        assert_eq!(pages[1]["tags"]["p"], 2);
+        let last = events.last().unwrap();
+        assert_eq!(last["event"], "status");
+        assert_eq!(last["state"], "completed");
+        assert_eq!(last["completed"], 2);
+    }
+}